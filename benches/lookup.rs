@@ -0,0 +1,40 @@
+//! Lookup throughput on the mock backend, without network or cache:
+//! `cargo bench --bench lookup`. The `lookup` group compares the nested
+//! error maps the SDK used to build ("before") with the typed result
+//! structs ("after"). To compare two revisions instead, run
+//! `cargo bench --bench lookup -- --save-baseline before` on the first and
+//! `cargo bench --bench lookup -- --baseline before` on the second.
+
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{LookupOptions, SelfieRecordsSDK};
+
+fn sdk() -> SelfieRecordsSDK {
+    let resolver = MockTxtResolver::new()
+        .with_record("alice.user._bitcoin-payment.example.com", &["bitcoin:bc1qalice"])
+        .with_record("alice.user._nostr.example.com", &["npub1alice"])
+        .with_record("alice.user._pgp.example.com", &[]);
+    SelfieRecordsSDK::builder().resolver(Arc::new(resolver)).build().unwrap()
+}
+
+fn lookup(c: &mut Criterion) {
+    let sdk = sdk();
+    let options = LookupOptions::new().attempts(1).timeout(Duration::from_secs(1));
+    let keys = || Some(vec!["bitcoin-payment", "nostr", "pgp"]);
+
+    let mut group = c.benchmark_group("lookup");
+    group.bench_function("before/map", |b| {
+        b.iter(|| black_box(sdk.get_records_with("alice@example.com", keys(), None, &options).to_map()))
+    });
+    group.bench_function("after/typed", |b| {
+        b.iter(|| black_box(sdk.get_records_response("alice@example.com", keys(), None, &options)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lookup);
+criterion_main!(benches);
@@ -0,0 +1,21 @@
+//! With the `ffi` feature, generates the C header from src/ffi.rs into
+//! `OUT_DIR`; tests/ffi.rs checks that include/selfie_records.h is it.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "ffi")]
+    ffi_header();
+}
+
+#[cfg(feature = "ffi")]
+fn ffi_header() {
+    use std::env;
+    use std::path::Path;
+
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config = cbindgen::Config::from_file(Path::new(&crate_dir).join("cbindgen.toml")).unwrap();
+    let bindings = cbindgen::generate_with_config(&crate_dir, config).expect("src/ffi.rs is valid input for cbindgen");
+    bindings.write_to_file(Path::new(&env::var("OUT_DIR").unwrap()).join("selfie_records.h"));
+}
@@ -0,0 +1,75 @@
+/* C interface of selfie_records_sdk, built with the `ffi` feature and
+ * generated from src/ffi.rs by cbindgen, see build.rs. To update it, copy
+ * the header the build writes to OUT_DIR over this one.
+ *
+ * Positive codes are the SelfieError numeric codes, e.g. 1 for an invalid
+ * name, of the first failed key in the order asked for. */
+
+#ifndef SELFIE_RECORDS_H
+#define SELFIE_RECORDS_H
+
+/* An opaque handle, never dereferenced and never reused once freed. */
+typedef struct SelfieRecordsSDK SelfieRecordsSDK;
+
+#define SELFIE_OK 0
+
+#define SELFIE_E_NULL_POINTER -1
+
+#define SELFIE_E_INVALID_UTF8 -2
+
+/**
+ * A handle or string that was already freed or never returned.
+ */
+#define SELFIE_E_UNKNOWN_HANDLE -3
+
+#define SELFIE_E_PANIC -4
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Builds an SDK from `config_json`, an object with the keys of a config
+ * file, e.g. `{"nameservers": ["9.9.9.9"], "cache": {"ttl": 300}}`, or
+ * the defaults when it is null. Returns its handle, an id rather than
+ * an address, or null when the config is invalid.
+ *
+ * # Safety
+ *
+ * `config_json` is null or a NUL-terminated string.
+ */
+SelfieRecordsSDK *selfie_sdk_new(const char *config_json);
+
+/**
+ * Looks up `keys_csv`, comma-separated keys, or the default keys when it
+ * is null or empty, of `name`, and points `out_json` at the result, to be
+ * freed with `selfie_string_free`. `out_json` is set whenever
+ * the lookup ran, even when a key failed.
+ *
+ * # Safety
+ *
+ * `name` and `keys_csv` are null or NUL-terminated strings, and
+ * `out_json` is null or valid for a write.
+ */
+int selfie_sdk_get_records(const SelfieRecordsSDK *sdk,
+                           const char *name,
+                           const char *keys_csv,
+                           char **out_json);
+
+/**
+ * Frees an SDK from `selfie_sdk_new`. Null is ignored.
+ */
+int selfie_sdk_free(SelfieRecordsSDK *sdk);
+
+/**
+ * Frees a string the SDK returned. Null is ignored. Safe to call with any
+ * pointer, since only ones the SDK returned and did not free yet are
+ * dereferenced.
+ */
+int selfie_string_free(char *string);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SELFIE_RECORDS_H */
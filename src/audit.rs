@@ -0,0 +1,142 @@
+//! A record of every DNS question the SDK asks and what came back, one
+//! JSON object per query. This covers the TXT queries sent through the
+//! configured resolver, retries included; DNSSEC chain fetches, consensus
+//! checks and cross-check queries go to other servers and are not logged.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use data_encoding::HEXLOWER;
+use log::error;
+use ring::hmac;
+
+use crate::encoding::{self, RawRecord};
+use crate::error::SelfieError;
+use crate::wire::{Transport, WireInfo};
+
+/// One query, as sent to the resolver. Retries are separate queries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    /// When the query was sent.
+    pub timestamp: SystemTime,
+    /// The name being looked up, or its keyed hash when identifiers are hashed.
+    pub identifier: String,
+    pub qname: String,
+    /// The backend asked, e.g. `8.8.8.8:53` or `system`.
+    pub resolver: String,
+    /// `None` when the query failed before an answer was read.
+    pub transport: Option<Transport>,
+    pub outcome: Result<Vec<String>, SelfieError>,
+    pub rcode: Option<u16>,
+    pub ttl: Option<u32>,
+    pub duration: Duration,
+}
+
+impl AuditEntry {
+    /// The entry as a JSON object with a stable set of keys; absent values
+    /// are `null`. `outcome` is `answered`, `no_records` or `error`.
+    pub fn to_json(&self) -> serde_json::Value {
+        let (outcome, answers, error) = match &self.outcome {
+            Ok(answers) if answers.is_empty() => ("no_records", Some(answers.clone()), None),
+            Ok(answers) => ("answered", Some(answers.clone()), None),
+            Err(e) => ("error", None, Some(e.to_string())),
+        };
+        serde_json::json!({
+            "timestamp_ms": self.timestamp.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0),
+            "identifier": self.identifier,
+            "qname": self.qname,
+            "resolver": self.resolver,
+            "transport": self.transport.map(|transport| transport.to_string()),
+            "outcome": outcome,
+            "answers": answers,
+            "error": error,
+            "rcode": self.rcode,
+            "ttl": self.ttl,
+            "duration_us": self.duration.as_micros() as u64,
+        })
+    }
+}
+
+/// Destination for audit entries. Called from the lookup itself, so
+/// implementations should not block for long.
+pub trait AuditSink: Send + Sync {
+    fn record(&self, entry: &AuditEntry);
+}
+
+/// Appends entries as JSON lines to a file. Each line goes out in a single
+/// append, so lines from concurrent lookups never interleave. When the
+/// file has been renamed away, e.g. by logrotate, it is created again.
+#[derive(Debug)]
+pub struct FileAuditSink {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl FileAuditSink {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = Self::append(&path)?;
+        Ok(FileAuditSink { path, file: Mutex::new(file) })
+    }
+
+    fn append(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    fn write_line(&self, line: &[u8]) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        match std::fs::metadata(&self.path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => *file = Self::append(&self.path)?,
+            _ => {}
+        }
+        file.write_all(line)
+    }
+}
+
+impl AuditSink for FileAuditSink {
+    fn record(&self, entry: &AuditEntry) {
+        let mut line = entry.to_json().to_string().into_bytes();
+        line.push(b'\n');
+        if let Err(e) = self.write_line(&line) {
+            error!("Cannot write audit entry to {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// The SDK's audit configuration.
+pub(crate) struct AuditLog {
+    pub(crate) sink: Arc<dyn AuditSink>,
+    pub(crate) identifier_key: Option<hmac::Key>,
+}
+
+impl AuditLog {
+    pub(crate) fn record(
+        &self,
+        identifier: &str,
+        qname: &str,
+        resolver: &str,
+        timestamp: SystemTime,
+        duration: Duration,
+        outcome: &Result<(Vec<RawRecord>, WireInfo), SelfieError>,
+    ) {
+        let identifier = match &self.identifier_key {
+            Some(key) => format!("hmac-sha256:{}", HEXLOWER.encode(hmac::sign(key, identifier.as_bytes()).as_ref())),
+            None => identifier.to_string(),
+        };
+        let wire = outcome.as_ref().ok().map(|(_, wire)| *wire);
+        self.sink.record(&AuditEntry {
+            timestamp,
+            identifier,
+            qname: qname.to_string(),
+            resolver: resolver.to_string(),
+            transport: wire.map(|wire| wire.transport_used),
+            outcome: outcome.as_ref().map(|(records, _)| encoding::lossy(records)).map_err(Clone::clone),
+            rcode: wire.and_then(|wire| wire.response_code),
+            ttl: wire.and_then(|wire| wire.ttl),
+            duration,
+        });
+    }
+}
@@ -0,0 +1,59 @@
+//! Base58Check strings, as used by BIP-47 payment codes.
+
+use crate::sha256::sha256;
+
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const CHECKSUM_LENGTH: usize = 4;
+
+/// Decodes `value` and verifies its checksum, the first four bytes of the
+/// double SHA-256 of the payload; returns the payload.
+pub(crate) fn decode_check(value: &str) -> Result<Vec<u8>, String> {
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in value.bytes() {
+        let mut carry = ALPHABET.iter().position(|&a| a == c).ok_or("invalid base58 character")? as u32;
+        // `bytes` is little-endian during the conversion.
+        for byte in bytes.iter_mut() {
+            carry += u32::from(*byte) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    let zeros = value.bytes().take_while(|&c| c == b'1').count();
+    bytes.extend(std::iter::repeat_n(0, zeros));
+    bytes.reverse();
+    if bytes.len() < CHECKSUM_LENGTH {
+        return Err("base58 string too short".to_string());
+    }
+    let (payload, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LENGTH);
+    if sha256(&sha256(payload))[..CHECKSUM_LENGTH] != *checksum {
+        return Err("base58 checksum mismatch".to_string());
+    }
+    Ok(payload.to_vec())
+}
+
+/// Encodes `payload` followed by its checksum.
+pub(crate) fn encode_check(payload: &[u8]) -> String {
+    let mut data = payload.to_vec();
+    data.extend_from_slice(&sha256(&sha256(payload))[..CHECKSUM_LENGTH]);
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in &data {
+        let mut carry = u32::from(byte);
+        for digit in digits.iter_mut() {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = data.iter().take_while(|&&byte| byte == 0).count();
+    let mut encoded = "1".repeat(zeros);
+    encoded.extend(digits.iter().rev().map(|&digit| ALPHABET[digit as usize] as char));
+    encoded
+}
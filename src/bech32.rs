@@ -0,0 +1,89 @@
+//! Bech32 (BIP-173) strings, as used by nostr `npub` keys and LNURLs.
+//! Lengths are not capped at 90 characters since LNURLs routinely exceed it.
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u32; 5] = [0x3b6a_57b2, 0x2650_8e6d, 0x1ea1_19fa, 0x3d42_33dd, 0x2a14_62b3];
+const CHECKSUM_LENGTH: usize = 6;
+
+fn polymod(values: impl IntoIterator<Item = u8>) -> u32 {
+    let mut checksum = 1u32;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x01ff_ffff) << 5) ^ u32::from(value);
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+fn hrp_expand(hrp: &str) -> impl Iterator<Item = u8> + '_ {
+    hrp.bytes().map(|b| b >> 5).chain([0]).chain(hrp.bytes().map(|b| b & 31))
+}
+
+/// Regroups `data` from `from`-bit to `to`-bit values. Without `pad`,
+/// leftover bits must be zero padding.
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut accumulator = 0u32;
+    let mut bits = 0;
+    let mut out = Vec::new();
+    let max = (1 << to) - 1;
+    for &value in data {
+        accumulator = (accumulator << from) | u32::from(value);
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            out.push(((accumulator >> bits) & max) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            out.push(((accumulator << (to - bits)) & max) as u8);
+        }
+    } else if bits >= from || (accumulator << (to - bits)) & max != 0 {
+        return None;
+    }
+    Some(out)
+}
+
+/// Splits a bech32 string into its lowercased human-readable part and
+/// payload bytes, verifying the checksum.
+pub(crate) fn decode(value: &str) -> Result<(String, Vec<u8>), String> {
+    if value.chars().any(|c| c.is_ascii_lowercase()) && value.chars().any(|c| c.is_ascii_uppercase()) {
+        return Err("mixed-case bech32 string".to_string());
+    }
+    let value = value.to_ascii_lowercase();
+    let (hrp, data) = value.rsplit_once('1').ok_or("missing bech32 separator")?;
+    if hrp.is_empty() || data.len() < CHECKSUM_LENGTH {
+        return Err("bech32 string too short".to_string());
+    }
+    if !hrp.bytes().all(|b| (33..=126).contains(&b)) {
+        return Err("invalid bech32 prefix".to_string());
+    }
+    let data: Vec<u8> = data
+        .bytes()
+        .map(|b| CHARSET.iter().position(|&c| c == b).map(|i| i as u8))
+        .collect::<Option<_>>()
+        .ok_or("invalid bech32 character")?;
+    if polymod(hrp_expand(hrp).chain(data.iter().copied())) != 1 {
+        return Err("bech32 checksum mismatch".to_string());
+    }
+    let payload = convert_bits(&data[..data.len() - CHECKSUM_LENGTH], 5, 8, false).ok_or("invalid bech32 padding")?;
+    Ok((hrp.to_string(), payload))
+}
+
+/// Encodes `payload` under the lowercase prefix `hrp`.
+pub(crate) fn encode(hrp: &str, payload: &[u8]) -> String {
+    let data = convert_bits(payload, 8, 5, true).expect("padding always succeeds");
+    let checksum = polymod(hrp_expand(hrp).chain(data.iter().copied()).chain([0; CHECKSUM_LENGTH])) ^ 1;
+    let mut encoded = format!("{}1", hrp);
+    for value in data {
+        encoded.push(CHARSET[value as usize] as char);
+    }
+    for i in 0..CHECKSUM_LENGTH {
+        encoded.push(CHARSET[((checksum >> (5 * (5 - i))) & 31) as usize] as char);
+    }
+    encoded
+}
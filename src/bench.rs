@@ -0,0 +1,209 @@
+//! Compares resolvers on selfie lookups: repeated uncached lookups of the
+//! same name against each one, behind `selfie bench`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::SelfieError;
+use crate::options::LookupOptions;
+use crate::resolver::TxtResolver;
+use crate::response::RecordsResponse;
+use crate::SelfieRecordsSDK;
+use crate::time::Instant;
+
+const DEFAULT_ITERATIONS: u32 = 20;
+const DEFAULT_WARMUP: u32 = 2;
+const DEFAULT_BUDGET: Duration = Duration::from_secs(60);
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Minimum, median, 95th percentile and maximum of a set of latencies,
+/// percentiles by the nearest-rank method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyStats {
+    pub min: Duration,
+    pub median: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+impl LatencyStats {
+    /// `None` when there are no samples.
+    pub fn from_samples(samples: &[Duration]) -> Option<Self> {
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        let rank = |percentile: usize| sorted[(sorted.len() * percentile).div_ceil(100).max(1) - 1];
+        Some(LatencyStats { min: *sorted.first()?, median: rank(50), p95: rank(95), max: *sorted.last()? })
+    }
+}
+
+/// The measured lookups against one server.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerBench {
+    pub server: String,
+    /// Latency of each lookup that succeeded.
+    pub samples: Vec<Duration>,
+    /// Lookups where a key failed with anything but a missing record.
+    pub failures: u32,
+    /// The values seen per key over the successful lookups, `None` for no
+    /// record.
+    pub answers: BTreeMap<String, BTreeSet<Option<String>>>,
+}
+
+impl ServerBench {
+    pub fn new(server: &str) -> Self {
+        ServerBench { server: server.to_string(), ..ServerBench::default() }
+    }
+
+    /// Adds a lookup that took `latency` and gave `response`.
+    pub fn record(&mut self, latency: Duration, response: &RecordsResponse) {
+        let failed = response.iter().any(|(_, result)| !matches!(result.error, None | Some(SelfieError::NoRecords | SelfieError::NxDomain)));
+        if failed {
+            self.failures += 1;
+            return;
+        }
+        self.samples.push(latency);
+        for (key, result) in response.iter() {
+            self.answers.entry(key.to_string()).or_default().insert(result.value.clone());
+        }
+    }
+
+    pub fn lookups(&self) -> u32 {
+        self.samples.len() as u32 + self.failures
+    }
+
+    /// Share of lookups that succeeded, 0 when there were none.
+    pub fn success_rate(&self) -> f64 {
+        match self.lookups() {
+            0 => 0.0,
+            lookups => self.samples.len() as f64 / lookups as f64,
+        }
+    }
+
+    pub fn stats(&self) -> Option<LatencyStats> {
+        LatencyStats::from_samples(&self.samples)
+    }
+}
+
+/// What `Bench::run` measured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchReport {
+    pub name: String,
+    pub servers: Vec<ServerBench>,
+    /// Whether the time budget ran out before every iteration was done.
+    pub budget_exhausted: bool,
+}
+
+impl BenchReport {
+    /// Whether every server that answered at all always gave the same
+    /// values as the others.
+    pub fn consistent(&self) -> bool {
+        let mut answering = self.servers.iter().filter(|server| !server.samples.is_empty());
+        let Some(first) = answering.next() else {
+            return true;
+        };
+        first.answers.values().all(|values| values.len() == 1) && answering.all(|server| server.answers == first.answers)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let millis = |d: Duration| d.as_secs_f64() * 1000.0;
+        let servers: Vec<serde_json::Value> = self
+            .servers
+            .iter()
+            .map(|server| {
+                let stats = server.stats().map(|stats| {
+                    serde_json::json!({
+                        "min_ms": millis(stats.min),
+                        "median_ms": millis(stats.median),
+                        "p95_ms": millis(stats.p95),
+                        "max_ms": millis(stats.max),
+                    })
+                });
+                serde_json::json!({
+                    "server": server.server,
+                    "lookups": server.lookups(),
+                    "failures": server.failures,
+                    "success_rate": server.success_rate(),
+                    "latency": stats,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "name": self.name,
+            "servers": servers,
+            "consistent": self.consistent(),
+            "budget_exhausted": self.budget_exhausted,
+        })
+    }
+}
+
+/// Repeated lookups of `name`'s `keys` against several resolvers, taking
+/// turns so that a change in network conditions affects them alike.
+#[derive(Debug, Clone)]
+pub struct Bench {
+    name: String,
+    keys: Vec<String>,
+    iterations: u32,
+    warmup: u32,
+    budget: Duration,
+}
+
+impl Bench {
+    pub fn new(name: &str, keys: &[&str]) -> Self {
+        Bench {
+            name: name.to_string(),
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+            iterations: DEFAULT_ITERATIONS,
+            warmup: DEFAULT_WARMUP,
+            budget: DEFAULT_BUDGET,
+        }
+    }
+
+    /// Measured lookups per server. Defaults to 20.
+    pub fn iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Lookups per server made first and left out of the results, which
+    /// fill caches along the way. Defaults to 2.
+    pub fn warmup(mut self, warmup: u32) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Time the whole run may take, warm-up included; no lookup starts
+    /// after it and none may outlast it. Defaults to a minute.
+    pub fn budget(mut self, budget: Duration) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Runs the lookups, once over and without retries, against each
+    /// labelled resolver. Answers are never cached.
+    pub fn run(&self, servers: &[(String, Arc<dyn TxtResolver>)]) -> BenchReport {
+        let started = Instant::now();
+        let sdks: Vec<SelfieRecordsSDK> =
+            servers.iter().map(|(_, resolver)| SelfieRecordsSDK::with_resolver(resolver.clone())).collect();
+        let mut results: Vec<ServerBench> = servers.iter().map(|(label, _)| ServerBench::new(label)).collect();
+        let keys = || Some(self.keys.iter().map(String::as_str).collect());
+
+        let mut budget_exhausted = false;
+        'rounds: for round in 0..self.warmup + self.iterations {
+            for (sdk, result) in sdks.iter().zip(&mut results) {
+                let remaining = self.budget.saturating_sub(started.elapsed());
+                if remaining.is_zero() {
+                    budget_exhausted = true;
+                    break 'rounds;
+                }
+                let options = LookupOptions::new().attempts(1).timeout(remaining.min(LOOKUP_TIMEOUT));
+                let lookup = Instant::now();
+                let response = sdk.get_records_response(&self.name, keys(), None, &options);
+                if round >= self.warmup {
+                    result.record(lookup.elapsed(), &response);
+                }
+            }
+        }
+        BenchReport { name: self.name.clone(), servers: results, budget_exhausted }
+    }
+}
@@ -0,0 +1,676 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::cache::CacheConfig;
+use crate::config::{Config, ConfigError};
+use crate::consensus::NameserverDiscovery;
+use crate::encoding::{ResponseLimits, Sanitization};
+use crate::name::{self, LocalPartPolicy, NameScheme, TemplateError, TemplateNameScheme, DEFAULT_DOMAIN_TEMPLATE, DEFAULT_EMAIL_TEMPLATE};
+use crate::options::LookupOptions;
+use crate::overrides::{OverrideError, RecordOverrides};
+use crate::parser::RecordParser;
+use crate::proxy::Socks5Config;
+use crate::resolver::TxtResolver;
+use crate::routing::{self, NameServerGroup, Routes};
+use crate::transport::{DnsTransport, TransportFactory};
+use crate::SelfieRecordsSDK;
+
+/// Why `SdkBuilder::build` rejected a configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BuildError {
+    #[error("DNSSEC is required but no trust anchors are configured")]
+    NoTrustAnchors,
+    #[error("{0}")]
+    InvalidOverride(OverrideError),
+    #[error("Record overrides are configured but forbidden")]
+    OverridesForbidden,
+    #[error("Cannot open audit log: {0}")]
+    AuditLog(String),
+    #[error("Invalid route: {0}")]
+    InvalidRoute(String),
+    #[error("{0}")]
+    InvalidConfig(ConfigError),
+    #[error("Timeouts must be longer than zero")]
+    ZeroTimeout,
+    #[error("The nameserver list is empty")]
+    NoNameservers,
+    #[error("Invalid quorum: {0}")]
+    InvalidQuorum(String),
+    #[error("The default record key list is empty")]
+    NoDefaultKeys,
+    #[error("Query limits must be larger than zero")]
+    ZeroQueryLimit,
+    #[error("A SOCKS5 proxy only carries TCP: use TransportPreference::TcpOnly")]
+    ProxyNeedsTcp,
+    #[error("The system resolver cannot use a SOCKS5 proxy: set nameservers or a resolver")]
+    ProxyNeedsNameservers,
+    #[error("{0}")]
+    InvalidNameTemplate(TemplateError),
+    #[error("Response limits must be larger than zero")]
+    ZeroResponseLimit,
+}
+
+/// Where the SDK's own resolver gets its nameservers from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum NameserverSource {
+    /// The operating system's configuration: `/etc/resolv.conf` on Unix,
+    /// the registry on Windows. When it cannot be read, Google Public DNS
+    /// is used and a warning logged.
+    #[default]
+    System,
+    /// See `SdkBuilder::nameservers`.
+    Static(Vec<SocketAddr>),
+}
+
+/// How the SDK's resolver, and the server a call names, are queried.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TransportPreference {
+    /// UDP, asking again over TCP when the answer is truncated, as large
+    /// `pgp` keys or BOLT12 offers often are.
+    #[default]
+    UdpThenTcp,
+    TcpOnly,
+}
+
+/// Configures a `SelfieRecordsSDK`.
+#[derive(Default)]
+pub struct SdkBuilder {
+    pub(crate) resolver: Option<Arc<dyn TxtResolver>>,
+    pub(crate) nameserver_source: NameserverSource,
+    pub(crate) transport: TransportPreference,
+    pub(crate) proxy: Option<Socks5Config>,
+    pub(crate) nameserver_timeout: Option<Duration>,
+    quorum: Option<usize>,
+    pub(crate) max_queries_per_second: Option<u32>,
+    pub(crate) max_in_flight: Option<usize>,
+    pub(crate) lookup_options: LookupOptions,
+    pub(crate) default_keys: Option<Vec<String>>,
+    pub(crate) routes: Routes,
+    invalid_route: Option<String>,
+    invalid_config: Option<ConfigError>,
+    pub(crate) name_scheme: Option<Arc<dyn NameScheme>>,
+    domain_template: Option<String>,
+    email_template: Option<String>,
+    pub(crate) name_template_fallbacks: Vec<TemplateNameScheme>,
+    pub(crate) local_part_policy: LocalPartPolicy,
+    pub(crate) parsers: Vec<Arc<dyn RecordParser>>,
+    pub(crate) record_version: Option<u32>,
+    pub(crate) overrides: RecordOverrides,
+    invalid_override: Option<OverrideError>,
+    forbid_overrides: bool,
+    pub(crate) cache: Option<CacheConfig>,
+    pub(crate) offline: bool,
+    pub(crate) nameservers: Option<Arc<dyn NameserverDiscovery>>,
+    pub(crate) authoritative_transport: Option<TransportFactory>,
+    pub(crate) public_only: bool,
+    pub(crate) cross_check: bool,
+    pub(crate) cross_check_resolver: Option<Arc<dyn TxtResolver>>,
+    pub(crate) strict_cross_check: bool,
+    pub(crate) strict_encoding: bool,
+    pub(crate) response_limits: ResponseLimits,
+    pub(crate) sanitization: Sanitization,
+    pub(crate) chunked_keys: Vec<String>,
+    pub(crate) fallback_to_default: bool,
+    #[cfg(feature = "audit")]
+    audit_path: Option<std::path::PathBuf>,
+    #[cfg(feature = "audit")]
+    pub(crate) audit_sink: Option<Arc<dyn crate::audit::AuditSink>>,
+    #[cfg(feature = "audit")]
+    pub(crate) audit_identifier_key: Option<ring::hmac::Key>,
+    pub(crate) blocked_suffixes: Vec<String>,
+    #[cfg(feature = "signatures")]
+    pub(crate) pgp_keys: Option<Arc<dyn crate::linkage::PgpKeySource>>,
+    #[cfg(feature = "nip05")]
+    pub(crate) nip05_source: Option<Arc<dyn crate::nip05::Nip05Source>>,
+    #[cfg(feature = "dnssec")]
+    pub(crate) trust_anchors: Option<Vec<crate::dnssec::TrustAnchor>>,
+    #[cfg(feature = "dnssec")]
+    pub(crate) dnssec_source: Option<Arc<dyn crate::dnssec::RecordSource>>,
+    #[cfg(feature = "dnssec")]
+    pub(crate) require_dnssec: bool,
+    #[cfg(feature = "dnssec")]
+    pub(crate) report_dnssec: bool,
+}
+
+impl SdkBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from the resolver, cache and DNSSEC settings of `config`;
+    /// per-call settings are in `Config::lookup_options`. Settings that
+    /// cannot be applied, such as an unreadable trust anchor file, fail
+    /// `build`.
+    pub fn from_config(config: Config) -> Self {
+        let mut builder = Self::new();
+        match config.resolver() {
+            Ok(resolver) => builder.resolver = resolver,
+            Err(e) => builder.invalid_config = Some(e),
+        }
+        builder.cache = config.cache_ttl.map(fixed_lifetime);
+        builder.offline = config.offline.unwrap_or(false);
+        builder.max_queries_per_second = config.max_queries_per_second;
+        builder.max_in_flight = config.max_in_flight.map(|queries| queries as usize);
+        builder.domain_template = config.domain_template;
+        builder.email_template = config.email_template;
+        #[cfg(feature = "dnssec")]
+        {
+            builder.require_dnssec = config.dnssec == Some(crate::config::DnssecMode::Require);
+            builder.report_dnssec = config.dnssec == Some(crate::config::DnssecMode::Report);
+            if let Some(path) = &config.trust_anchor {
+                match crate::dnssec::load_trust_anchors(path) {
+                    Ok(anchors) => builder.trust_anchors = Some(anchors),
+                    Err(e) => {
+                        let e = ConfigError::InvalidValue { key: "dnssec.trust_anchor".to_string(), reason: e.to_string() };
+                        builder.invalid_config.get_or_insert(e);
+                    }
+                }
+            }
+        }
+        builder
+    }
+
+    /// Sends every query to `resolver` instead of the system's DNS.
+    pub fn resolver(mut self, resolver: Arc<dyn TxtResolver>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Sends every query to `servers` instead of the system's DNS, asking
+    /// the next one only when a server fails with a retryable error. Takes
+    /// the place of `resolver`.
+    pub fn nameservers(self, servers: &[SocketAddr]) -> Self {
+        self.nameserver_source(NameserverSource::Static(servers.to_vec()))
+    }
+
+    /// `NameserverSource::System` unless another is given. The system's
+    /// nameservers are only used when no `resolver` is set either.
+    pub fn nameserver_source(mut self, source: NameserverSource) -> Self {
+        self.nameserver_source = source;
+        self
+    }
+
+    pub fn get_nameserver_source(&self) -> &NameserverSource {
+        &self.nameserver_source
+    }
+
+    /// How `nameservers`, the system's nameservers and the server a call
+    /// names are queried; `UdpThenTcp` by default. `KeyResult::wire` says
+    /// which transport answered.
+    pub fn transport_preference(mut self, transport: TransportPreference) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    pub fn get_transport_preference(&self) -> TransportPreference {
+        self.transport
+    }
+
+    /// `transport_preference(TransportPreference::TcpOnly)` when set.
+    pub fn tcp_only(mut self, tcp_only: bool) -> Self {
+        self.transport = match tcp_only {
+            true => TransportPreference::TcpOnly,
+            false => TransportPreference::UdpThenTcp,
+        };
+        self
+    }
+
+    /// Sends the SDK's DNS traffic through the SOCKS5 `proxy`, e.g.
+    /// `Socks5Config::tor()`: queries to `nameservers` and to the server a
+    /// call names, whose host name the proxy resolves, DoH requests, and the
+    /// default servers of cross-checks, consensus checks and DNSSEC. Since
+    /// a proxy only carries TCP, `build` fails unless the transport
+    /// preference is `TcpOnly`, and since the system resolver cannot use
+    /// one, unless `nameservers` or a `resolver` are set. Resolvers and
+    /// transports passed in, `route_key` groups included, are used as given.
+    /// Failing to reach the proxy fails lookups with `ProxyUnreachable`.
+    pub fn proxy(mut self, proxy: Socks5Config) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    pub fn get_proxy(&self) -> Option<&Socks5Config> {
+        self.proxy.as_ref()
+    }
+
+    /// Time each of `nameservers`, or of the system's, has to answer
+    /// before the next one is asked, `timeout` by default for the system's
+    /// and five seconds for the others. Keep it under `timeout`, which
+    /// bounds the whole attempt, for the others to get a turn.
+    pub fn nameserver_timeout(mut self, timeout: Duration) -> Self {
+        self.nameserver_timeout = Some(timeout);
+        self
+    }
+
+    /// Asks all of `nameservers` at once and only accepts records that at
+    /// least `quorum` of them return, failing lookups with
+    /// `SelfieError::Disagreement` otherwise.
+    pub fn quorum(mut self, quorum: usize) -> Self {
+        self.quorum = Some(quorum);
+        self
+    }
+
+    /// Spaces the DNS queries of lookups, batches and watches evenly, at
+    /// most `per_second` of them a second, to stay under the abuse limits
+    /// of public resolvers. A query that would wait past its attempt's
+    /// timeout or the call's deadline fails with `RateLimited` instead.
+    pub fn max_queries_per_second(mut self, per_second: u32) -> Self {
+        self.max_queries_per_second = Some(per_second);
+        self
+    }
+
+    /// Sends at most `queries` DNS queries at once, holding the others
+    /// back like `max_queries_per_second`.
+    pub fn max_in_flight(mut self, queries: usize) -> Self {
+        self.max_in_flight = Some(queries);
+        self
+    }
+
+    /// Per-attempt timeout of calls that take no `LookupOptions`, such as
+    /// `get_records`. Defaults to five seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.lookup_options = self.lookup_options.timeout(timeout);
+        self
+    }
+
+    /// Attempts per key of calls that take no `LookupOptions`.
+    pub fn attempts(mut self, attempts: u32) -> Self {
+        self.lookup_options = self.lookup_options.attempts(attempts);
+        self
+    }
+
+    /// Keys looked up when a call names none, instead of `DEFAULT_RECORDS`.
+    pub fn default_keys(mut self, keys: &[&str]) -> Self {
+        self.default_keys = Some(keys.iter().map(|key| key.to_string()).collect());
+        self
+    }
+
+    /// Sends the queries for `key` to `group`, e.g. `"pgp"` to a corporate
+    /// resolver. Key rules take precedence over `route_suffix` rules; keys
+    /// without a rule go to the default resolver, or to the server passed
+    /// to the call. `KeyResult::route` records the rule used.
+    pub fn route_key(mut self, key: &str, group: impl Into<NameServerGroup>) -> Self {
+        match group.into().into_resolver() {
+            Ok(resolver) => self.routes.insert_key(key, resolver),
+            Err(e) => {
+                self.invalid_route.get_or_insert(e);
+            }
+        }
+        self
+    }
+
+    /// Sends the queries for identifiers whose domain is `suffix` or ends in
+    /// `.suffix` to `group`. The longest matching suffix wins.
+    pub fn route_suffix(mut self, suffix: &str, group: impl Into<NameServerGroup>) -> Self {
+        match group.into().into_resolver() {
+            Ok(resolver) => self.routes.insert_suffix(suffix, resolver),
+            Err(e) => {
+                self.invalid_route.get_or_insert(e);
+            }
+        }
+        self
+    }
+
+    /// Replaces the selfie/BIP-353 owner-name layout.
+    pub fn name_scheme(mut self, scheme: impl NameScheme + 'static) -> Self {
+        self.name_scheme = Some(Arc::new(scheme));
+        self
+    }
+
+    /// Builds the owner names of domains' records from `template`, e.g.
+    /// `_{key}._selfie.{domain}`, instead of `DEFAULT_DOMAIN_TEMPLATE`; see
+    /// `TemplateNameScheme`. Takes the place of `name_scheme`, and `build`
+    /// rejects templates `TemplateNameScheme::parse` would.
+    pub fn domain_template(mut self, template: &str) -> Self {
+        self.domain_template = Some(template.to_string());
+        self
+    }
+
+    /// Like `domain_template` for addresses' records, e.g.
+    /// `{user}._{key}.{domain}`, instead of `DEFAULT_EMAIL_TEMPLATE`.
+    pub fn email_template(mut self, template: &str) -> Self {
+        self.email_template = Some(template.to_string());
+        self
+    }
+
+    /// Schemes tried in order after the name scheme for keys that have no
+    /// records under it, e.g. while records move to another layout. The
+    /// first to have records wins, and `KeyResult::name_template` says
+    /// which. `build` checks them like `TemplateNameScheme::parse`.
+    pub fn name_template_fallbacks(mut self, fallbacks: Vec<TemplateNameScheme>) -> Self {
+        self.name_template_fallbacks = fallbacks;
+        self
+    }
+
+    /// How local parts of addresses are normalized before lookups and
+    /// publishing, `LocalPartPolicy::default()` unless set.
+    pub fn local_part_policy(mut self, policy: LocalPartPolicy) -> Self {
+        self.local_part_policy = policy;
+        self
+    }
+
+    /// Parses the values of `parser.key()` with `parser`, in place of the
+    /// built-in parser of a well-known key.
+    pub fn register_parser(mut self, parser: impl RecordParser + 'static) -> Self {
+        self.parsers.push(Arc::new(parser));
+        self
+    }
+
+    /// Fails payment records whose value is not valid UTF-8 or contains
+    /// control characters with `SelfieError::InvalidRecord`, instead of
+    /// returning them flagged with `KeyResult::encoding_issue`.
+    pub fn strict_encoding(mut self, strict: bool) -> Self {
+        self.strict_encoding = strict;
+        self
+    }
+
+    /// Bounds the TXT answers lookups accept, `ResponseLimits::default()`
+    /// unless set.
+    pub fn response_limits(mut self, limits: ResponseLimits) -> Self {
+        self.response_limits = limits;
+        self
+    }
+
+    /// Trims values and escapes or rejects their control characters before
+    /// returning them; values are returned as received unless set.
+    pub fn sanitize_values(mut self, sanitization: Sanitization) -> Self {
+        self.sanitization = sanitization;
+        self
+    }
+
+    /// Reads records of `key` whose value is a chunk manifest,
+    /// `chunks=N;sha256=<hex>`, as a payload split over the records of
+    /// `{key}.1` to `{key}.N`: the chunks are fetched concurrently and
+    /// concatenated, and the result must match the digest. Missing chunks
+    /// fail with `SelfieError::MissingChunk` and a wrong digest with
+    /// `SelfieError::ChunkDigestMismatch`. `SelfieRecord::chunked` builds
+    /// the records to publish.
+    pub fn chunked_key(mut self, key: &str) -> Self {
+        self.chunked_keys.push(key.to_string());
+        self
+    }
+
+    /// Looks records up at their `_v{version}` name first, e.g.
+    /// `_v2._bitcoin-payment.example.com`, and at the unversioned name only
+    /// when that one has no records (NXDOMAIN or NODATA). Failures such as
+    /// timeouts are reported without falling back. `KeyResult::record_version`
+    /// tells which name answered. Consensus checks use the unversioned name.
+    pub fn record_version(mut self, version: Option<u32>) -> Self {
+        self.record_version = version;
+        self
+    }
+
+    /// Answers `key` of `identifier` with `value` without querying DNS.
+    /// `identifier` may also be `*@domain`, covering every address there.
+    pub fn override_record(mut self, identifier: &str, key: &str, value: &str) -> Self {
+        if let Err(e) = self.overrides.insert(identifier, key, value) {
+            self.invalid_override.get_or_insert(e);
+        }
+        self
+    }
+
+    /// Adds overrides loaded with `RecordOverrides::load`.
+    pub fn overrides(mut self, overrides: RecordOverrides) -> Self {
+        self.overrides.extend(overrides);
+        self
+    }
+
+    /// Makes `build` fail if any override is configured, so that a
+    /// production binary cannot have its answers pinned by configuration.
+    pub fn forbid_overrides(mut self, forbid: bool) -> Self {
+        self.forbid_overrides = forbid;
+        self
+    }
+
+    /// Keeps answers for `ttl`, whatever their own TTL, and serves them
+    /// instead of querying again. Expired answers are still served in
+    /// offline mode.
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache = Some(fixed_lifetime(ttl));
+        self
+    }
+
+    /// Keeps answers for as long as their TTL, within the bounds of
+    /// `config`, and serves them instead of querying again; results served
+    /// from the cache have `Source::Cache`. Expired answers are still served
+    /// in offline mode.
+    pub fn cache(mut self, config: CacheConfig) -> Self {
+        self.cache = Some(config);
+        self
+    }
+
+    /// Starts the SDK in offline mode; see `SelfieRecordsSDK::set_offline`.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Finds the authoritative servers `check_authoritative_consensus`
+    /// queries with `discovery` instead of NS lookups through the first
+    /// nameserver, the SDK's or else the system's.
+    pub fn nameserver_discovery(mut self, discovery: Arc<dyn NameserverDiscovery>) -> Self {
+        self.nameservers = Some(discovery);
+        self
+    }
+
+    /// Queries the authoritative servers `check_authoritative_consensus`
+    /// finds over transports opened by `open`, given each address, instead
+    /// of UDP and TCP sockets.
+    pub fn authoritative_transport(
+        mut self,
+        open: impl Fn(std::net::SocketAddr) -> Arc<dyn DnsTransport> + Send + Sync + 'static,
+    ) -> Self {
+        self.authoritative_transport = Some(Arc::new(open));
+        self
+    }
+
+    /// Rejects special-use and private names (`.local`, `.internal`,
+    /// `.home.arpa`, `.test`, `.invalid`, `.localhost`), IP addresses and
+    /// single-label domains before any query is sent, so that internal
+    /// hostnames do not leak to public resolvers. `.onion` names are
+    /// rejected too unless the resolver can reach Tor.
+    pub fn public_only(mut self, public_only: bool) -> Self {
+        self.public_only = public_only;
+        self
+    }
+
+    /// Adds `suffix`, e.g. `corp` or `lan`, to the names public-only mode
+    /// rejects. Has no effect unless `public_only` is set.
+    pub fn block_suffix(mut self, suffix: &str) -> Self {
+        self.blocked_suffixes.push(suffix.to_string());
+        self
+    }
+
+    /// Re-queries every answer fetched from DNS through a second resolver
+    /// and records in `KeyResult::cross_check` whether the two agree. The
+    /// second query shares the key's deadline; cache hits and overrides
+    /// are not re-checked, and mismatching answers are not cached. The second resolver defaults to 1.1.1.1 over
+    /// UDP; pass a `DohResolver` to `cross_check_resolver` for a channel a
+    /// network that intercepts port 53 cannot tamper with.
+    pub fn cross_check(mut self, cross_check: bool) -> Self {
+        self.cross_check = cross_check;
+        self
+    }
+
+    /// Cross-checks answers against `resolver`. Enables cross-checking.
+    pub fn cross_check_resolver(mut self, resolver: Arc<dyn TxtResolver>) -> Self {
+        self.cross_check = true;
+        self.cross_check_resolver = Some(resolver);
+        self
+    }
+
+    /// Turns a cross-check mismatch into a `CrossCheckMismatch` error for
+    /// that key. A cross-check resolver that fails does not fail the key.
+    pub fn strict_cross_check(mut self, strict: bool) -> Self {
+        self.strict_cross_check = strict;
+        self
+    }
+
+    /// When the server passed to `get_records` cannot be reached or does
+    /// not answer in time, asks the SDK's resolver once instead and sets
+    /// `KeyResult::used_fallback_resolver`. Answers from the server,
+    /// including missing records and those failing DNSSEC validation, are
+    /// kept. Off by default.
+    pub fn fallback_to_default(mut self, fallback: bool) -> Self {
+        self.fallback_to_default = fallback;
+        self
+    }
+
+    /// Appends a JSON line for every query to the file at `path`; see
+    /// `audit::FileAuditSink`. `build` fails if the file cannot be opened.
+    #[cfg(feature = "audit")]
+    pub fn audit_log(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.audit_path = Some(path.into());
+        self
+    }
+
+    /// Sends an `AuditEntry` for every query to `sink`.
+    #[cfg(feature = "audit")]
+    pub fn audit_sink(mut self, sink: Arc<dyn crate::audit::AuditSink>) -> Self {
+        self.audit_sink = Some(sink);
+        self
+    }
+
+    /// Logs identifiers as their HMAC-SHA256 under `key`, hex-encoded, so
+    /// that entries for the same name can be correlated without the log
+    /// revealing who was looked up.
+    #[cfg(feature = "audit")]
+    pub fn hash_audit_identifiers(mut self, key: &[u8]) -> Self {
+        self.audit_identifier_key = Some(ring::hmac::Key::new(ring::hmac::HMAC_SHA256, key));
+        self
+    }
+
+    /// Fetches the OpenPGP keys `pgp` records refer to by URL or fingerprint.
+    #[cfg(feature = "signatures")]
+    pub fn pgp_key_source(mut self, source: Arc<dyn crate::linkage::PgpKeySource>) -> Self {
+        self.pgp_keys = Some(source);
+        self
+    }
+
+    /// Fetches the `nostr.json` documents `cross_check_nip05` compares with,
+    /// instead of from `https://<domain>`.
+    #[cfg(feature = "nip05")]
+    pub fn nip05_source(mut self, source: Arc<dyn crate::nip05::Nip05Source>) -> Self {
+        self.nip05_source = Some(source);
+        self
+    }
+
+    /// Trusts `anchor` as a root key. The first anchor added replaces the
+    /// built-in IANA root keys; add several to cover a rollover.
+    #[cfg(feature = "dnssec")]
+    pub fn trust_anchor(mut self, anchor: crate::dnssec::TrustAnchor) -> Self {
+        self.trust_anchors.get_or_insert_with(Vec::new).push(anchor);
+        self
+    }
+
+    /// Replaces the trusted root keys, e.g. with `dnssec::load_trust_anchors`.
+    #[cfg(feature = "dnssec")]
+    pub fn trust_anchors(mut self, anchors: impl IntoIterator<Item = crate::dnssec::TrustAnchor>) -> Self {
+        self.trust_anchors = Some(anchors.into_iter().collect());
+        self
+    }
+
+    /// Fetches DNSSEC chains from `source` instead of the first nameserver,
+    /// the SDK's or else the system's.
+    #[cfg(feature = "dnssec")]
+    pub fn dnssec_source(mut self, source: Arc<dyn crate::dnssec::RecordSource>) -> Self {
+        self.dnssec_source = Some(source);
+        self
+    }
+
+    /// Only returns values whose chain of trust validates; anything else
+    /// becomes an error for that key, `DnssecInsecure` for an unsigned zone
+    /// and `Dnssec` for a broken chain.
+    #[cfg(feature = "dnssec")]
+    pub fn require_dnssec(mut self, require: bool) -> Self {
+        self.require_dnssec = require;
+        self
+    }
+
+    /// Validates values like `require_dnssec` but returns them either way,
+    /// setting `KeyResult::authenticated` for those that validate. Offline,
+    /// where chains cannot be fetched, values are returned unvalidated.
+    #[cfg(feature = "dnssec")]
+    pub fn report_dnssec(mut self, report: bool) -> Self {
+        self.report_dnssec = report;
+        self
+    }
+
+    pub fn build(mut self) -> Result<SelfieRecordsSDK, BuildError> {
+        if self.forbid_overrides && (!self.overrides.is_empty() || self.invalid_override.is_some()) {
+            return Err(BuildError::OverridesForbidden);
+        }
+        if let Some(e) = self.invalid_override.take() {
+            return Err(BuildError::InvalidOverride(e));
+        }
+        if let Some(e) = self.invalid_route.take() {
+            return Err(BuildError::InvalidRoute(e));
+        }
+        if let Some(e) = self.invalid_config.take() {
+            return Err(BuildError::InvalidConfig(e));
+        }
+        if self.lookup_options.get_timeout().is_zero() || self.nameserver_timeout.is_some_and(|timeout| timeout.is_zero()) {
+            return Err(BuildError::ZeroTimeout);
+        }
+        if self.default_keys.as_ref().is_some_and(Vec::is_empty) {
+            return Err(BuildError::NoDefaultKeys);
+        }
+        if self.max_queries_per_second == Some(0) || self.max_in_flight == Some(0) {
+            return Err(BuildError::ZeroQueryLimit);
+        }
+        let limits = self.response_limits;
+        if limits.max_records_per_key == 0 || limits.max_value_bytes == 0 || limits.max_total_bytes == 0 {
+            return Err(BuildError::ZeroResponseLimit);
+        }
+        if self.domain_template.is_some() || self.email_template.is_some() {
+            let domain_template = self.domain_template.as_deref().unwrap_or(DEFAULT_DOMAIN_TEMPLATE);
+            let email_template = self.email_template.as_deref().unwrap_or(DEFAULT_EMAIL_TEMPLATE);
+            let scheme = TemplateNameScheme::parse(domain_template, email_template).map_err(BuildError::InvalidNameTemplate)?;
+            self.name_scheme = Some(Arc::new(scheme));
+        }
+        for fallback in &self.name_template_fallbacks {
+            name::check_template(fallback.domain_template(), false).map_err(BuildError::InvalidNameTemplate)?;
+            name::check_template(fallback.email_template(), true).map_err(BuildError::InvalidNameTemplate)?;
+        }
+        if self.proxy.is_some() {
+            if self.transport != TransportPreference::TcpOnly {
+                return Err(BuildError::ProxyNeedsTcp);
+            }
+            if self.resolver.is_none() && self.nameserver_source == NameserverSource::System {
+                return Err(BuildError::ProxyNeedsNameservers);
+            }
+        }
+        match &self.nameserver_source {
+            NameserverSource::Static(servers) if servers.is_empty() => return Err(BuildError::NoNameservers),
+            NameserverSource::Static(servers) => {
+                if let Some(quorum) = self.quorum {
+                    routing::check_quorum(quorum, servers.len()).map_err(BuildError::InvalidQuorum)?;
+                }
+                self.resolver = Some(routing::nameserver_resolver(servers, self.transport, self.proxy.as_ref(), self.nameserver_timeout, self.quorum));
+            }
+            NameserverSource::System if self.quorum.is_some() => {
+                return Err(BuildError::InvalidQuorum("no nameservers are configured".to_string()))
+            }
+            NameserverSource::System => {}
+        }
+        #[cfg(feature = "audit")]
+        if let Some(path) = self.audit_path.take() {
+            let sink = crate::audit::FileAuditSink::open(&path)
+                .map_err(|e| BuildError::AuditLog(format!("{}: {}", path.display(), e)))?;
+            self.audit_sink = Some(Arc::new(sink));
+        }
+        #[cfg(feature = "dnssec")]
+        if (self.require_dnssec || self.report_dnssec) && self.trust_anchors.as_ref().is_some_and(Vec::is_empty) {
+            return Err(BuildError::NoTrustAnchors);
+        }
+        Ok(SelfieRecordsSDK::from_builder(self))
+    }
+}
+
+/// A cache keeping every answer for `ttl`.
+fn fixed_lifetime(ttl: Duration) -> CacheConfig {
+    CacheConfig { min_ttl: ttl, max_ttl: ttl, ..CacheConfig::default() }
+}
@@ -0,0 +1,116 @@
+//! Answers kept from earlier lookups, keyed by owner name, while their TTL
+//! lasts, including answers without records. Expired entries are kept
+//! until replaced or evicted so that offline mode can still serve them,
+//! flagged as stale.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+
+use crate::encoding::RawRecord;
+use crate::time::Instant;
+
+/// Which answers `SdkBuilder::cache` keeps, and for how long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    /// Answers kept at most; the one that expires first makes room for a
+    /// new one.
+    pub max_entries: usize,
+    /// An answer stays fresh for its TTL, raised to `min_ttl` and then
+    /// capped at `max_ttl`. Answers without a TTL get `min_ttl`.
+    pub min_ttl: Duration,
+    pub max_ttl: Duration,
+    /// How long an answer without records, NXDOMAIN or NODATA, stays
+    /// fresh when it carries no SOA minimum TTL; capped at `max_ttl`, but
+    /// not raised to `min_ttl`. Zero keeps no such answers. Results served
+    /// from one have `Source::Cache` and a `NoRecords` or `NxDomain` error.
+    pub negative_ttl: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            enabled: true,
+            max_entries: 1024,
+            min_ttl: Duration::ZERO,
+            max_ttl: Duration::from_secs(3600),
+            negative_ttl: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Cache {
+    /// `None` disables the cache.
+    config: Option<CacheConfig>,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    answer: Answer,
+    expires_at: Instant,
+}
+
+/// An answer as received, with the time and TTL of the original lookup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Answer {
+    pub(crate) records: Vec<RawRecord>,
+    pub(crate) resolved_at: Option<SystemTime>,
+    pub(crate) ttl: Option<u32>,
+    /// The name did not exist, for answers without records.
+    pub(crate) nxdomain: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Hit {
+    pub(crate) answer: Answer,
+    pub(crate) stale: bool,
+}
+
+impl Cache {
+    pub(crate) fn new(config: Option<CacheConfig>) -> Self {
+        Cache { config: config.filter(|config| config.enabled), entries: Mutex::new(HashMap::new()) }
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<Hit> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.get(name).map(|entry| Hit {
+            answer: entry.answer.clone(),
+            stale: entry.expires_at <= Instant::now(),
+        })
+    }
+
+    pub(crate) fn insert(&self, name: &str, answer: Answer) {
+        let Some(config) = self.config else {
+            return;
+        };
+        let ttl = answer.ttl.map(|ttl| Duration::from_secs(ttl.into()));
+        let ttl = match answer.records.is_empty() {
+            true => ttl.unwrap_or(config.negative_ttl).min(config.max_ttl),
+            false => ttl.unwrap_or(config.min_ttl).max(config.min_ttl).min(config.max_ttl),
+        };
+        if ttl.is_zero() && answer.records.is_empty() {
+            return;
+        }
+        let entry = Entry { expires_at: Instant::now() + ttl, answer };
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if !entries.contains_key(name) && entries.len() >= config.max_entries {
+            // Nothing to evict means `max_entries` is zero.
+            let Some(first) = entries.iter().min_by_key(|(_, entry)| entry.expires_at).map(|(name, _)| name.clone()) else {
+                return;
+            };
+            entries.remove(&first);
+        }
+        entries.insert(name.to_string(), entry);
+    }
+
+    pub(crate) fn remove(&self, name: &str) {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner).remove(name);
+    }
+
+    pub(crate) fn clear(&self) {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+}
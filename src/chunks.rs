@@ -0,0 +1,114 @@
+//! Payloads too large for one TXT record, such as PGP keys, published in
+//! numbered chunks: the key's own record holds a manifest,
+//! `chunks=N;sha256=<hex>`, and the records of keys `{key}.1` to `{key}.N`
+//! hold the pieces, e.g. `_pgp.1.example.com`, concatenated in order.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Poll;
+
+use crate::records::{from_hex, to_hex};
+use crate::sha256::sha256;
+
+/// Most chunks a manifest may announce.
+pub(crate) const MAX_CHUNKS: u32 = 64;
+
+/// The record announcing a chunked payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ChunkManifest {
+    pub(crate) count: u32,
+    sha256: [u8; 32],
+}
+
+impl ChunkManifest {
+    pub(crate) fn new(payload: &[u8], count: u32) -> Self {
+        ChunkManifest { count, sha256: sha256(payload) }
+    }
+
+    /// `None` when `value` is not a manifest at all, which leaves it an
+    /// ordinary value.
+    pub(crate) fn parse(value: &str) -> Option<Result<Self, String>> {
+        if !value.trim_start().starts_with("chunks=") {
+            return None;
+        }
+        let (mut count, mut digest) = (None, None);
+        for field in value.split(';').map(str::trim) {
+            match field.split_once('=') {
+                Some(("chunks", n)) => count = Some(n),
+                Some(("sha256", hex)) => digest = Some(hex),
+                _ => return Some(Err(format!("unknown manifest field {:?}", field))),
+            }
+        }
+        let count = match count.and_then(|n| n.parse::<u32>().ok()) {
+            Some(count @ 1..=MAX_CHUNKS) => count,
+            _ => return Some(Err(format!("chunk count must be from 1 to {}", MAX_CHUNKS))),
+        };
+        let Some(sha256) = digest.and_then(from_hex).and_then(|bytes| <[u8; 32]>::try_from(bytes).ok()) else {
+            return Some(Err("sha256 must be 64 hex digits".to_string()));
+        };
+        Some(Ok(ChunkManifest { count, sha256 }))
+    }
+
+    /// Whether `payload` is the one the manifest was made for.
+    pub(crate) fn matches(&self, payload: &[u8]) -> bool {
+        sha256(payload) == self.sha256
+    }
+}
+
+impl fmt::Display for ChunkManifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "chunks={};sha256={}", self.count, to_hex(&self.sha256))
+    }
+}
+
+/// The key whose record holds chunk `index`, counting from one.
+pub(crate) fn chunk_key(key: &str, index: u32) -> String {
+    format!("{}.{}", key, index)
+}
+
+/// Splits `payload` into pieces of at most `size` bytes, each ending on a
+/// character boundary.
+pub(crate) fn split(payload: &str, size: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = payload;
+    while !rest.is_empty() {
+        let mut end = size.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        // A character longer than `size` still makes a piece of its own.
+        if end == 0 {
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        let (piece, tail) = rest.split_at(end);
+        pieces.push(piece);
+        rest = tail;
+    }
+    pieces
+}
+
+/// Runs `futures` concurrently, returning their outputs in order.
+pub(crate) async fn join_all<F: Future>(futures: Vec<F>) -> Vec<F::Output> {
+    let mut futures: Vec<Pin<Box<F>>> = futures.into_iter().map(Box::pin).collect();
+    let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+    std::future::poll_fn(|cx| {
+        let mut pending = false;
+        for (future, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+            if output.is_some() {
+                continue;
+            }
+            match future.as_mut().poll(cx) {
+                Poll::Ready(done) => *output = Some(done),
+                Poll::Pending => pending = true,
+            }
+        }
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    })
+    .await;
+    outputs.into_iter().map(|output| output.expect("every future is done")).collect()
+}
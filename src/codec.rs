@@ -0,0 +1,1014 @@
+//! Compact binary encodings of `RecordsResponse` for constrained links:
+//! CBOR (RFC 8949) with the `cbor` feature and MessagePack with `msgpack`.
+//! Both carry the same document, with small integer map keys in place of
+//! field names:
+//!
+//! ```text
+//! response  = { 0: version (1), 1: { key text => result }, ?2: queries_issued }
+//! result    = { ?0: value text, ?1: error, ?2: source, ?3: stale (true),
+//!               ?4: offline (true), ?5: attempts, ?6: backoff ns,
+//!               ?7: wire, ?8: signature text, ?9: resolved_at ns since
+//!               the Unix epoch, ?10: ttl ns, ?11: cross_check,
+//!               ?12: record_version, ?13: encoding_issue,
+//!               ?14: raw_value bytes, ?15: [[character-string bytes]],
+//!               ?16: route, ?17: resolver text,
+//!               ?18: used_fallback_resolver (true), ?19: answered_by,
+//!               ?20: chunked (true), ?21: authenticated (true),
+//!               ?22: query, ?23: parse_error text,
+//!               ?24: name_template text }
+//! source    = 0 override | 1 cache | 2 dns
+//! wire      = { ?0: response_size, 1: truncated bool, 2: transport,
+//!               ?3: edns_udp_size, ?4: ttl s, ?5: rcode, ?6: server text }
+//! answered_by = { 0: address text, 1: transport }
+//! query     = { 0: fqdn text, ?1: nameserver text, 2: rtt ns, 3: attempts }
+//! encoding_issue = 0 not_utf8 | 1 control_characters
+//! route     = { 0: 0 default | 1 key | 2 suffix, ?1: suffix text }
+//! transport = 0 udp | 1 tcp | 2 doh | 3 resolver | 4 tls
+//! signature = "verified" | "invalid" | "no-key"
+//! cross_check = { 0: 0 verified | 1 mismatch | 2 unavailable,
+//!                 1: [other value text] for mismatch, reason text for unavailable }
+//! error     = { 0: kind text, kind-specific fields from 1 }
+//! ```
+//!
+//! Field 15 holds the raw records and is left out when they are a single
+//! record of one character-string equal to `raw_value`. `parsed` is not
+//! carried; `parse_error` is.
+//!
+//! Error kinds and their fields:
+//!
+//! ```text
+//! "invalid_name"      1: name, 2: { 0: reason text, ?1: max | label | position }
+//! "no_records"
+//! "timeout"           1: "global" | "key" | "call", 2: timeout, budget or deadline ns,
+//!                     3: attempts
+//! "rate_limited"      1: server, ?2: retry_after ns
+//! "dnssec"            1: message
+//! "offline"
+//! "invalid_record"    1: key, 2: reason
+//! "invalid_signature"
+//! "cross_check_mismatch" 1: [other value text]
+//! "cname_loop"        1: [owner name text]
+//! "cname_chain_too_long" 1: [owner name text]
+//! "resolver"          1: message
+//! "budget_exceeded"   1: max_queries
+//! "missing_chunk"     1: index, 2: count
+//! "chunk_digest_mismatch"
+//! "nxdomain"
+//! "invalid_nameserver" 1: server
+//! "disagreement"      1: quorum, 2: [answer text]
+//! "multiple_records"  1: count
+//! "not_a_bitcoin_uri" 1: [value text]
+//! "fingerprint_mismatch" 1: [expected text], 2: [fetched text]
+//! "invalid_key"       1: key
+//! "nameserver_resolution_failed" 1: host, 2: reason
+//! "proxy_unreachable" 1: proxy, 2: reason
+//! "response_too_large" 1: name, 2: reason
+//! "tls_certificate"   1: server, 2: reason
+//! "dnssec_insecure"   1: message
+//! ```
+//!
+//! Field numbers and names are never reused for something else. Readers
+//! skip map keys they do not know, so fields can be added without breaking
+//! older consumers; an unknown error kind decodes as a resolver error.
+//! Binary fields are written as byte strings, never as base64 text. Maps
+//! are written in ascending key order, so equal responses encode to equal
+//! bytes. Integers are unsigned and use the shortest form.
+
+use std::net::SocketAddr;
+use std::time::{Duration, UNIX_EPOCH};
+
+use thiserror::Error;
+
+use crate::cross_check::CrossCheck;
+use crate::encoding::{RawRecord, ValueEncodingIssue};
+use crate::error::{SelfieError, TimeoutBudget};
+use crate::name::NameError;
+use crate::parser::ParseError;
+use crate::response::{KeyResult, QueryMeta, RecordsResponse, Source};
+use crate::routing::Route;
+use crate::wire::{ServerInfo, Transport, WireInfo};
+
+const VERSION: u64 = 1;
+/// Deepest nesting a decoder accepts; the schema itself needs four levels.
+const MAX_DEPTH: usize = 16;
+
+/// Why bytes could not be decoded into a `RecordsResponse`.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid encoded response: {0}")]
+pub struct DecodeError(String);
+
+fn malformed(reason: impl Into<String>) -> DecodeError {
+    DecodeError(reason.into())
+}
+
+/// The data model both encodings share.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Null,
+    Bool(bool),
+    Uint(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+}
+
+impl Value {
+    fn text(self, what: &str) -> Result<String, DecodeError> {
+        match self {
+            Value::Text(text) => Ok(text),
+            other => Err(malformed(format!("{} should be text, got {:?}", what, other))),
+        }
+    }
+
+    fn bytes(self, what: &str) -> Result<Vec<u8>, DecodeError> {
+        match self {
+            Value::Bytes(bytes) => Ok(bytes),
+            other => Err(malformed(format!("{} should be a byte string, got {:?}", what, other))),
+        }
+    }
+
+    fn uint(self, what: &str) -> Result<u64, DecodeError> {
+        match self {
+            Value::Uint(n) => Ok(n),
+            other => Err(malformed(format!("{} should be an unsigned integer, got {:?}", what, other))),
+        }
+    }
+
+    fn bool(self, what: &str) -> Result<bool, DecodeError> {
+        match self {
+            Value::Bool(b) => Ok(b),
+            other => Err(malformed(format!("{} should be a boolean, got {:?}", what, other))),
+        }
+    }
+
+    /// The entries of a map keyed by small integers, in ascending order.
+    fn fields(self, what: &str) -> Result<Fields, DecodeError> {
+        let Value::Map(entries) = self else {
+            return Err(malformed(format!("{} should be a map", what)));
+        };
+        let fields = entries
+            .into_iter()
+            .map(|(key, value)| Ok((key.uint(&format!("{} field number", what))?, value)))
+            .collect::<Result<Vec<_>, DecodeError>>()?;
+        Ok(Fields { what: what.to_string(), fields })
+    }
+}
+
+/// Integer-keyed map entries being decoded.
+struct Fields {
+    what: String,
+    fields: Vec<(u64, Value)>,
+}
+
+impl Fields {
+    fn take(&mut self, number: u64) -> Option<Value> {
+        let index = self.fields.iter().position(|(n, _)| *n == number)?;
+        Some(self.fields.remove(index).1)
+    }
+
+    fn required(&mut self, number: u64) -> Result<Value, DecodeError> {
+        self.take(number).ok_or_else(|| malformed(format!("{} lacks field {}", self.what, number)))
+    }
+
+    fn text(&mut self, number: u64) -> Result<String, DecodeError> {
+        let what = format!("{} field {}", self.what, number);
+        self.required(number)?.text(&what)
+    }
+
+    fn uint(&mut self, number: u64) -> Result<u64, DecodeError> {
+        let what = format!("{} field {}", self.what, number);
+        self.required(number)?.uint(&what)
+    }
+
+    fn optional_uint(&mut self, number: u64) -> Result<Option<u64>, DecodeError> {
+        let what = format!("{} field {}", self.what, number);
+        self.take(number).map(|value| value.uint(&what)).transpose()
+    }
+}
+
+/// Builds an integer-keyed map, leaving out absent fields.
+#[derive(Default)]
+struct FieldsBuilder(Vec<(Value, Value)>);
+
+impl FieldsBuilder {
+    fn put(mut self, number: u64, value: impl Into<Option<Value>>) -> Self {
+        if let Some(value) = value.into() {
+            self.0.push((Value::Uint(number), value));
+        }
+        self
+    }
+
+    fn build(self) -> Value {
+        Value::Map(self.0)
+    }
+}
+
+fn narrow<T: TryFrom<u64>>(n: Option<u64>, what: &str) -> Result<Option<T>, DecodeError> {
+    n.map(|n| T::try_from(n).map_err(|_| malformed(format!("{} out of range", what)))).transpose()
+}
+
+fn nanos(duration: Duration) -> Value {
+    Value::Uint(u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX))
+}
+
+fn text(value: &str) -> Value {
+    Value::Text(value.to_string())
+}
+
+fn encode_response(response: &RecordsResponse) -> Value {
+    let mut entries: Vec<(&str, &KeyResult)> = response.iter().collect();
+    entries.sort_by_key(|(key, _)| *key);
+    let entries = entries.into_iter().map(|(key, result)| (text(key), encode_result(result))).collect();
+    FieldsBuilder::default()
+        .put(0, Value::Uint(VERSION))
+        .put(1, Value::Map(entries))
+        .put(2, (response.queries_issued > 0).then(|| Value::Uint(response.queries_issued.into())))
+        .build()
+}
+
+fn encode_result(result: &KeyResult) -> Value {
+    let source = result.source.map(|source| {
+        Value::Uint(match source {
+            Source::Override => 0,
+            Source::Cache => 1,
+            Source::Dns => 2,
+        })
+    });
+    #[cfg(feature = "signatures")]
+    let signature = result.signature.map(|status| text(&status.to_string()));
+    #[cfg(not(feature = "signatures"))]
+    let signature: Option<Value> = None;
+    let resolved_at =
+        result.resolved_at.map(|time| nanos(time.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO)));
+
+    FieldsBuilder::default()
+        .put(0, result.value.as_deref().map(text))
+        .put(1, result.error.as_ref().map(encode_error))
+        .put(2, source)
+        .put(3, result.stale.then_some(Value::Bool(true)))
+        .put(4, result.offline.then_some(Value::Bool(true)))
+        .put(5, result.attempts.map(|attempts| Value::Uint(attempts.into())))
+        .put(6, result.backoff.map(nanos))
+        .put(7, result.wire.as_ref().map(encode_wire))
+        .put(8, signature)
+        .put(9, resolved_at)
+        .put(10, result.ttl.map(nanos))
+        .put(11, result.cross_check.as_ref().map(encode_cross_check))
+        .put(12, result.record_version.map(|version| Value::Uint(version.into())))
+        .put(13, result.encoding_issue.map(|issue| Value::Uint(match issue {
+            ValueEncodingIssue::NotUtf8 => 0,
+            ValueEncodingIssue::ControlCharacters => 1,
+        })))
+        .put(14, result.raw_value.clone().map(Value::Bytes))
+        .put(15, (result.raw_records != implied_records(&result.raw_value)).then(|| encode_records(&result.raw_records)))
+        .put(16, result.route.as_ref().map(encode_route))
+        .put(17, result.resolver.as_deref().map(text))
+        .put(18, result.used_fallback_resolver.then_some(Value::Bool(true)))
+        .put(19, result.answered_by.as_ref().map(encode_server))
+        .put(20, result.chunked.then_some(Value::Bool(true)))
+        .put(21, result.authenticated.then_some(Value::Bool(true)))
+        .put(22, result.query.as_ref().map(encode_query))
+        .put(23, result.parse_error.as_ref().map(|e| text(&e.reason)))
+        .put(24, result.name_template.as_deref().map(text))
+        .build()
+}
+
+fn encode_query(query: &QueryMeta) -> Value {
+    FieldsBuilder::default()
+        .put(0, text(&query.fqdn))
+        .put(1, query.nameserver.map(|nameserver| text(&nameserver.to_string())))
+        .put(2, nanos(query.rtt))
+        .put(3, Value::Uint(query.attempts.into()))
+        .build()
+}
+
+fn encode_route(route: &Route) -> Value {
+    let fields = FieldsBuilder::default();
+    match route {
+        Route::Default => fields.put(0, Value::Uint(0)),
+        Route::Key => fields.put(0, Value::Uint(1)),
+        Route::Suffix(suffix) => fields.put(0, Value::Uint(2)).put(1, text(suffix)),
+    }
+    .build()
+}
+
+/// The raw records a result without field 15 has.
+fn implied_records(raw_value: &Option<Vec<u8>>) -> Vec<RawRecord> {
+    raw_value.iter().map(|value| RawRecord::new([value])).collect()
+}
+
+fn encode_records(records: &[RawRecord]) -> Value {
+    let encode = |record: &RawRecord| Value::Array(record.strings().map(|string| Value::Bytes(string.to_vec())).collect());
+    Value::Array(records.iter().map(encode).collect())
+}
+
+fn texts(values: &[String]) -> Value {
+    Value::Array(values.iter().map(|value| text(value)).collect())
+}
+
+fn encode_cross_check(cross_check: &CrossCheck) -> Value {
+    let fields = FieldsBuilder::default();
+    match cross_check {
+        CrossCheck::Verified => fields.put(0, Value::Uint(0)),
+        CrossCheck::Mismatch { other_values } => fields.put(0, Value::Uint(1)).put(1, texts(other_values)),
+        CrossCheck::Unavailable { reason } => fields.put(0, Value::Uint(2)).put(1, text(reason)),
+    }
+    .build()
+}
+
+fn encode_transport(transport: Transport) -> Value {
+    Value::Uint(match transport {
+        Transport::Udp => 0,
+        Transport::Tcp => 1,
+        Transport::Doh => 2,
+        Transport::Resolver => 3,
+        Transport::Tls => 4,
+    })
+}
+
+fn encode_wire(wire: &WireInfo) -> Value {
+    FieldsBuilder::default()
+        .put(0, wire.response_size.map(|size| Value::Uint(size as u64)))
+        .put(1, Value::Bool(wire.truncated))
+        .put(2, encode_transport(wire.transport_used))
+        .put(3, wire.edns_udp_size.map(|size| Value::Uint(size.into())))
+        .put(4, wire.ttl.map(|ttl| Value::Uint(ttl.into())))
+        .put(5, wire.response_code.map(|code| Value::Uint(code.into())))
+        .put(6, wire.server.map(|server| text(&server.to_string())))
+        .build()
+}
+
+fn encode_server(server: &ServerInfo) -> Value {
+    FieldsBuilder::default().put(0, text(&server.address.to_string())).put(1, encode_transport(server.transport)).build()
+}
+
+fn encode_error(error: &SelfieError) -> Value {
+    let fields = FieldsBuilder::default();
+    match error {
+        SelfieError::InvalidName { name, reason } => {
+            let (kind, detail) = match reason {
+                NameError::Empty => ("empty", None),
+                NameError::TooLong { max } => ("too_long", Some(Value::Uint(*max as u64))),
+                NameError::EmptyLabel => ("empty_label", None),
+                NameError::LabelTooLong => ("label_too_long", None),
+                NameError::InvalidLabel { label } => ("invalid_label", Some(text(label))),
+                NameError::SingleLabelDomain => ("single_label_domain", None),
+                NameError::AddressLiteral => ("address_literal", None),
+                NameError::MultipleAt => ("multiple_at", None),
+                NameError::EmptyLocalPart => ("empty_local_part", None),
+                NameError::LocalPartTooLong => ("local_part_too_long", None),
+                NameError::InvalidLocalPart { position } => ("invalid_local_part", Some(Value::Uint(*position as u64))),
+                NameError::UnterminatedQuote => ("unterminated_quote", None),
+                NameError::MissingDomain => ("missing_domain", None),
+                NameError::SpecialUseDomain => ("special_use_domain", None),
+            };
+            let reason = FieldsBuilder::default().put(0, text(kind)).put(1, detail).build();
+            fields.put(0, text("invalid_name")).put(1, text(name)).put(2, reason)
+        }
+        SelfieError::NoRecords => fields.put(0, text("no_records")),
+        SelfieError::Timeout(budget) => {
+            let (kind, duration, attempts) = match budget {
+                TimeoutBudget::Global { timeout, attempts } => ("global", *timeout, *attempts),
+                TimeoutBudget::Key { budget, attempts } => ("key", *budget, *attempts),
+                TimeoutBudget::Call { deadline, attempts } => ("call", *deadline, *attempts),
+            };
+            fields
+                .put(0, text("timeout"))
+                .put(1, text(kind))
+                .put(2, nanos(duration))
+                .put(3, Value::Uint(attempts.into()))
+        }
+        SelfieError::RateLimited { server, retry_after } => {
+            fields.put(0, text("rate_limited")).put(1, text(server)).put(2, retry_after.map(nanos))
+        }
+        SelfieError::Dnssec(message) => fields.put(0, text("dnssec")).put(1, text(message)),
+        SelfieError::Offline => fields.put(0, text("offline")),
+        SelfieError::InvalidRecord { key, reason } => {
+            fields.put(0, text("invalid_record")).put(1, text(key)).put(2, text(reason))
+        }
+        SelfieError::InvalidSignature => fields.put(0, text("invalid_signature")),
+        SelfieError::CrossCheckMismatch { other_values } => {
+            fields.put(0, text("cross_check_mismatch")).put(1, texts(other_values))
+        }
+        SelfieError::CnameLoop { chain } => fields.put(0, text("cname_loop")).put(1, texts(chain)),
+        SelfieError::CnameChainTooLong { chain } => {
+            fields.put(0, text("cname_chain_too_long")).put(1, texts(chain))
+        }
+        SelfieError::Resolver(message) => fields.put(0, text("resolver")).put(1, text(message)),
+        SelfieError::BudgetExceeded { max_queries } => {
+            fields.put(0, text("budget_exceeded")).put(1, Value::Uint((*max_queries).into()))
+        }
+        SelfieError::MissingChunk { index, count } => fields
+            .put(0, text("missing_chunk"))
+            .put(1, Value::Uint((*index).into()))
+            .put(2, Value::Uint((*count).into())),
+        SelfieError::ChunkDigestMismatch => fields.put(0, text("chunk_digest_mismatch")),
+        SelfieError::NxDomain => fields.put(0, text("nxdomain")),
+        SelfieError::InvalidNameserver { server } => fields.put(0, text("invalid_nameserver")).put(1, text(server)),
+        SelfieError::Disagreement { quorum, answers } => fields
+            .put(0, text("disagreement"))
+            .put(1, Value::Uint((*quorum).into()))
+            .put(2, texts(answers)),
+        SelfieError::MultipleRecords { count } => {
+            fields.put(0, text("multiple_records")).put(1, Value::Uint((*count).into()))
+        }
+        SelfieError::NotABitcoinUri { values } => fields.put(0, text("not_a_bitcoin_uri")).put(1, texts(values)),
+        SelfieError::FingerprintMismatch { expected, fetched } => {
+            fields.put(0, text("fingerprint_mismatch")).put(1, texts(expected)).put(2, texts(fetched))
+        }
+        SelfieError::InvalidKey { key } => fields.put(0, text("invalid_key")).put(1, text(key)),
+        SelfieError::NameserverResolutionFailed { host, reason } => {
+            fields.put(0, text("nameserver_resolution_failed")).put(1, text(host)).put(2, text(reason))
+        }
+        SelfieError::ProxyUnreachable { proxy, reason } => {
+            fields.put(0, text("proxy_unreachable")).put(1, text(proxy)).put(2, text(reason))
+        }
+        SelfieError::ResponseTooLarge { name, reason } => {
+            fields.put(0, text("response_too_large")).put(1, text(name)).put(2, text(reason))
+        }
+        SelfieError::TlsCertificate { server, reason } => {
+            fields.put(0, text("tls_certificate")).put(1, text(server)).put(2, text(reason))
+        }
+        SelfieError::DnssecInsecure(message) => fields.put(0, text("dnssec_insecure")).put(1, text(message)),
+    }
+    .build()
+}
+
+fn decode_response(value: Value) -> Result<RecordsResponse, DecodeError> {
+    let mut fields = value.fields("response")?;
+    let version = fields.uint(0)?;
+    if version != VERSION {
+        return Err(malformed(format!("unsupported version {}", version)));
+    }
+    let Value::Map(entries) = fields.required(1)? else {
+        return Err(malformed("response entries should be a map"));
+    };
+    let mut response = RecordsResponse::default();
+    for (key, result) in entries {
+        let key = key.text("record key")?;
+        response.insert(&key, decode_result(result)?);
+    }
+    response.queries_issued = narrow(fields.optional_uint(2)?, "queries issued")?.unwrap_or(0);
+    Ok(response)
+}
+
+fn decode_result(value: Value) -> Result<KeyResult, DecodeError> {
+    let mut fields = value.fields("result")?;
+    let source = match fields.optional_uint(2)? {
+        None => None,
+        Some(0) => Some(Source::Override),
+        Some(1) => Some(Source::Cache),
+        Some(2) => Some(Source::Dns),
+        Some(n) => return Err(malformed(format!("unknown source {}", n))),
+    };
+    let flag = |fields: &mut Fields, number| -> Result<bool, DecodeError> {
+        Ok(fields.take(number).map(|value| value.bool("flag")).transpose()?.unwrap_or(false))
+    };
+    let stale = flag(&mut fields, 3)?;
+    let offline = flag(&mut fields, 4)?;
+    let used_fallback_resolver = flag(&mut fields, 18)?;
+    let chunked = flag(&mut fields, 20)?;
+    let authenticated = flag(&mut fields, 21)?;
+    let attempts = fields
+        .optional_uint(5)?
+        .map(|n| u32::try_from(n).map_err(|_| malformed("attempts out of range")))
+        .transpose()?;
+    let record_version = narrow(fields.optional_uint(12)?, "record version")?;
+    let raw_value = fields.take(14).map(|value| value.bytes("raw value")).transpose()?;
+    let encoding_issue = match fields.optional_uint(13)? {
+        None => None,
+        Some(0) => Some(ValueEncodingIssue::NotUtf8),
+        Some(1) => Some(ValueEncodingIssue::ControlCharacters),
+        Some(n) => return Err(malformed(format!("unknown encoding issue {}", n))),
+    };
+    #[cfg_attr(not(feature = "signatures"), allow(unused_variables))]
+    let signature = fields.take(8).map(|value| value.text("signature")).transpose()?;
+
+    Ok(KeyResult {
+        value: fields.take(0).map(|value| value.text("value")).transpose()?,
+        error: fields.take(1).map(decode_error).transpose()?,
+        source,
+        stale,
+        offline,
+        attempts,
+        backoff: fields.optional_uint(6)?.map(Duration::from_nanos),
+        wire: fields.take(7).map(decode_wire).transpose()?,
+        #[cfg(feature = "signatures")]
+        signature: signature.map(|status| parse_signature(&status)).transpose()?,
+        resolved_at: fields.optional_uint(9)?.map(|ns| UNIX_EPOCH + Duration::from_nanos(ns)),
+        ttl: fields.optional_uint(10)?.map(Duration::from_nanos),
+        cross_check: fields.take(11).map(decode_cross_check).transpose()?,
+        record_version,
+        name_template: fields.take(24).map(|value| value.text("name template")).transpose()?,
+        encoding_issue,
+        raw_records: match fields.take(15) {
+            Some(records) => decode_records(records)?,
+            None => implied_records(&raw_value),
+        },
+        raw_value,
+        route: fields.take(16).map(decode_route).transpose()?,
+        resolver: fields.take(17).map(|value| value.text("resolver")).transpose()?,
+        used_fallback_resolver,
+        answered_by: fields.take(19).map(decode_server).transpose()?,
+        chunked,
+        authenticated,
+        query: fields.take(22).map(decode_query).transpose()?,
+        parsed: None,
+        parse_error: fields.take(23).map(|value| value.text("parse error")).transpose()?.map(ParseError::new),
+    })
+}
+
+fn decode_query(value: Value) -> Result<QueryMeta, DecodeError> {
+    let mut fields = value.fields("query")?;
+    Ok(QueryMeta {
+        fqdn: fields.text(0)?,
+        nameserver: fields.take(1).map(|value| decode_address(value.text("nameserver")?)).transpose()?,
+        rtt: Duration::from_nanos(fields.uint(2)?),
+        attempts: u32::try_from(fields.uint(3)?).map_err(|_| malformed("attempts out of range"))?,
+    })
+}
+
+fn decode_texts(value: Value, what: &str) -> Result<Vec<String>, DecodeError> {
+    match value {
+        Value::Array(items) => items.into_iter().map(|item| item.text(what)).collect(),
+        other => Err(malformed(format!("{} should be an array, got {:?}", what, other))),
+    }
+}
+
+fn decode_route(value: Value) -> Result<Route, DecodeError> {
+    let mut fields = value.fields("route")?;
+    Ok(match fields.uint(0)? {
+        0 => Route::Default,
+        1 => Route::Key,
+        2 => Route::Suffix(fields.text(1)?),
+        n => return Err(malformed(format!("unknown route {}", n))),
+    })
+}
+
+fn decode_records(value: Value) -> Result<Vec<RawRecord>, DecodeError> {
+    let Value::Array(records) = value else {
+        return Err(malformed("raw records should be an array"));
+    };
+    records
+        .into_iter()
+        .map(|record| match record {
+            Value::Array(strings) => {
+                let strings: Vec<Vec<u8>> =
+                    strings.into_iter().map(|string| string.bytes("character-string")).collect::<Result<_, _>>()?;
+                Ok(RawRecord::new(strings))
+            }
+            other => Err(malformed(format!("raw record should be an array, got {:?}", other))),
+        })
+        .collect()
+}
+
+fn decode_cross_check(value: Value) -> Result<CrossCheck, DecodeError> {
+    let mut fields = value.fields("cross check")?;
+    Ok(match fields.uint(0)? {
+        0 => CrossCheck::Verified,
+        1 => CrossCheck::Mismatch { other_values: decode_texts(fields.required(1)?, "other value")? },
+        2 => CrossCheck::Unavailable { reason: fields.text(1)? },
+        n => return Err(malformed(format!("unknown cross check outcome {}", n))),
+    })
+}
+
+#[cfg(feature = "signatures")]
+fn parse_signature(status: &str) -> Result<crate::signature::SignatureStatus, DecodeError> {
+    use crate::signature::SignatureStatus;
+
+    match status {
+        "verified" => Ok(SignatureStatus::Verified),
+        "invalid" => Ok(SignatureStatus::Invalid),
+        "no-key" => Ok(SignatureStatus::NoKey),
+        other => Err(malformed(format!("unknown signature status {:?}", other))),
+    }
+}
+
+fn decode_transport(n: u64) -> Result<Transport, DecodeError> {
+    match n {
+        0 => Ok(Transport::Udp),
+        1 => Ok(Transport::Tcp),
+        2 => Ok(Transport::Doh),
+        3 => Ok(Transport::Resolver),
+        4 => Ok(Transport::Tls),
+        n => Err(malformed(format!("unknown transport {}", n))),
+    }
+}
+
+fn decode_address(address: String) -> Result<SocketAddr, DecodeError> {
+    address.parse().map_err(|_| malformed(format!("invalid server address {:?}", address)))
+}
+
+fn decode_server(value: Value) -> Result<ServerInfo, DecodeError> {
+    let mut fields = value.fields("server")?;
+    Ok(ServerInfo { address: decode_address(fields.text(0)?)?, transport: decode_transport(fields.uint(1)?)? })
+}
+
+fn decode_wire(value: Value) -> Result<WireInfo, DecodeError> {
+    let mut fields = value.fields("wire")?;
+    let transport_used = decode_transport(fields.uint(2)?)?;
+    Ok(WireInfo {
+        response_size: narrow(fields.optional_uint(0)?, "response size")?,
+        truncated: fields.required(1)?.bool("truncated")?,
+        transport_used,
+        edns_udp_size: narrow(fields.optional_uint(3)?, "EDNS UDP size")?,
+        ttl: narrow(fields.optional_uint(4)?, "TTL")?,
+        response_code: narrow(fields.optional_uint(5)?, "RCODE")?,
+        server: fields.take(6).map(|value| value.text("server").and_then(decode_address)).transpose()?,
+    })
+}
+
+fn decode_error(value: Value) -> Result<SelfieError, DecodeError> {
+    let mut fields = value.fields("error")?;
+    let kind = fields.text(0)?;
+    Ok(match kind.as_str() {
+        "invalid_name" => {
+            let name = fields.text(1)?;
+            let mut reason = fields.required(2)?.fields("name error")?;
+            let reason_kind = reason.text(0)?;
+            let reason = match reason_kind.as_str() {
+                "empty" => NameError::Empty,
+                "too_long" => NameError::TooLong { max: reason.uint(1)? as usize },
+                "empty_label" => NameError::EmptyLabel,
+                "label_too_long" => NameError::LabelTooLong,
+                "invalid_label" => NameError::InvalidLabel { label: reason.text(1)? },
+                "single_label_domain" => NameError::SingleLabelDomain,
+                "address_literal" => NameError::AddressLiteral,
+                "multiple_at" => NameError::MultipleAt,
+                "empty_local_part" => NameError::EmptyLocalPart,
+                "local_part_too_long" => NameError::LocalPartTooLong,
+                "invalid_local_part" => NameError::InvalidLocalPart { position: reason.uint(1)? as usize },
+                "unterminated_quote" => NameError::UnterminatedQuote,
+                "missing_domain" => NameError::MissingDomain,
+                "special_use_domain" => NameError::SpecialUseDomain,
+                other => return Err(malformed(format!("unknown name error {:?}", other))),
+            };
+            SelfieError::InvalidName { name, reason }
+        }
+        "no_records" => SelfieError::NoRecords,
+        "timeout" => {
+            let budget_kind = fields.text(1)?;
+            let duration = Duration::from_nanos(fields.uint(2)?);
+            let attempts = u32::try_from(fields.uint(3)?).map_err(|_| malformed("attempts out of range"))?;
+            SelfieError::Timeout(match budget_kind.as_str() {
+                "global" => TimeoutBudget::Global { timeout: duration, attempts },
+                "key" => TimeoutBudget::Key { budget: duration, attempts },
+                "call" => TimeoutBudget::Call { deadline: duration, attempts },
+                other => return Err(malformed(format!("unknown timeout budget {:?}", other))),
+            })
+        }
+        "rate_limited" => SelfieError::RateLimited {
+            server: fields.text(1)?,
+            retry_after: fields.optional_uint(2)?.map(Duration::from_nanos),
+        },
+        "dnssec" => SelfieError::Dnssec(fields.text(1)?),
+        "offline" => SelfieError::Offline,
+        "invalid_record" => SelfieError::InvalidRecord { key: fields.text(1)?, reason: fields.text(2)? },
+        "invalid_signature" => SelfieError::InvalidSignature,
+        "cross_check_mismatch" => {
+            SelfieError::CrossCheckMismatch { other_values: decode_texts(fields.required(1)?, "other value")? }
+        }
+        "cname_loop" => SelfieError::CnameLoop { chain: decode_texts(fields.required(1)?, "owner name")? },
+        "cname_chain_too_long" => {
+            SelfieError::CnameChainTooLong { chain: decode_texts(fields.required(1)?, "owner name")? }
+        }
+        "resolver" => SelfieError::Resolver(fields.text(1)?),
+        "budget_exceeded" => SelfieError::BudgetExceeded {
+            max_queries: u32::try_from(fields.uint(1)?).map_err(|_| malformed("max queries out of range"))?,
+        },
+        "missing_chunk" => SelfieError::MissingChunk {
+            index: u32::try_from(fields.uint(1)?).map_err(|_| malformed("chunk index out of range"))?,
+            count: u32::try_from(fields.uint(2)?).map_err(|_| malformed("chunk count out of range"))?,
+        },
+        "chunk_digest_mismatch" => SelfieError::ChunkDigestMismatch,
+        "nxdomain" => SelfieError::NxDomain,
+        "invalid_nameserver" => SelfieError::InvalidNameserver { server: fields.text(1)? },
+        "disagreement" => SelfieError::Disagreement {
+            quorum: u32::try_from(fields.uint(1)?).map_err(|_| malformed("quorum out of range"))?,
+            answers: decode_texts(fields.required(2)?, "answer")?,
+        },
+        "multiple_records" => SelfieError::MultipleRecords {
+            count: u32::try_from(fields.uint(1)?).map_err(|_| malformed("record count out of range"))?,
+        },
+        "not_a_bitcoin_uri" => SelfieError::NotABitcoinUri { values: decode_texts(fields.required(1)?, "value")? },
+        "fingerprint_mismatch" => SelfieError::FingerprintMismatch {
+            expected: decode_texts(fields.required(1)?, "fingerprint")?,
+            fetched: decode_texts(fields.required(2)?, "fingerprint")?,
+        },
+        "invalid_key" => SelfieError::InvalidKey { key: fields.text(1)? },
+        "nameserver_resolution_failed" => SelfieError::NameserverResolutionFailed { host: fields.text(1)?, reason: fields.text(2)? },
+        "proxy_unreachable" => SelfieError::ProxyUnreachable { proxy: fields.text(1)?, reason: fields.text(2)? },
+        "response_too_large" => SelfieError::ResponseTooLarge { name: fields.text(1)?, reason: fields.text(2)? },
+        "tls_certificate" => SelfieError::TlsCertificate { server: fields.text(1)?, reason: fields.text(2)? },
+        "dnssec_insecure" => SelfieError::DnssecInsecure(fields.text(1)?),
+        other => SelfieError::Resolver(format!("{} error", other)),
+    })
+}
+
+/// Bounds-checked reading of encoded bytes.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if len > self.data.len() {
+            return Err(malformed("unexpected end of input"));
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    /// A big-endian unsigned integer of `len` bytes.
+    fn uint(&mut self, len: usize) -> Result<u64, DecodeError> {
+        Ok(self.take(len)?.iter().fold(0, |n, &b| (n << 8) | u64::from(b)))
+    }
+
+    /// A length that must fit in what is left of the input, given that
+    /// each counted item takes at least one byte.
+    fn length(&mut self, len: u64) -> Result<usize, DecodeError> {
+        usize::try_from(len)
+            .ok()
+            .filter(|&len| len <= self.data.len())
+            .ok_or_else(|| malformed("length exceeds input"))
+    }
+
+    fn finish(self) -> Result<(), DecodeError> {
+        match self.data.is_empty() {
+            true => Ok(()),
+            false => Err(malformed("trailing bytes after document")),
+        }
+    }
+}
+
+fn utf8(bytes: &[u8]) -> Result<String, DecodeError> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| malformed("text is not UTF-8"))
+}
+
+#[cfg(feature = "cbor")]
+mod cbor {
+    use super::{malformed, utf8, DecodeError, Reader, Value, MAX_DEPTH};
+
+    fn head(out: &mut Vec<u8>, major: u8, n: u64) {
+        let major = major << 5;
+        match n {
+            0..=23 => out.push(major | n as u8),
+            24..=0xff => out.extend([major | 24, n as u8]),
+            0x100..=0xffff => {
+                out.push(major | 25);
+                out.extend((n as u16).to_be_bytes());
+            }
+            0x1_0000..=0xffff_ffff => {
+                out.push(major | 26);
+                out.extend((n as u32).to_be_bytes());
+            }
+            _ => {
+                out.push(major | 27);
+                out.extend(n.to_be_bytes());
+            }
+        }
+    }
+
+    pub(super) fn encode(value: &Value, out: &mut Vec<u8>) {
+        match value {
+            Value::Null => out.push(0xf6),
+            Value::Bool(false) => out.push(0xf4),
+            Value::Bool(true) => out.push(0xf5),
+            Value::Uint(n) => head(out, 0, *n),
+            Value::Bytes(bytes) => {
+                head(out, 2, bytes.len() as u64);
+                out.extend(bytes);
+            }
+            Value::Text(text) => {
+                head(out, 3, text.len() as u64);
+                out.extend(text.as_bytes());
+            }
+            Value::Array(items) => {
+                head(out, 4, items.len() as u64);
+                items.iter().for_each(|item| encode(item, out));
+            }
+            Value::Map(entries) => {
+                head(out, 5, entries.len() as u64);
+                for (key, value) in entries {
+                    encode(key, out);
+                    encode(value, out);
+                }
+            }
+        }
+    }
+
+    pub(super) fn decode(data: &[u8]) -> Result<Value, DecodeError> {
+        let mut reader = Reader { data };
+        let value = decode_value(&mut reader, 0)?;
+        reader.finish()?;
+        Ok(value)
+    }
+
+    fn decode_value(reader: &mut Reader, depth: usize) -> Result<Value, DecodeError> {
+        if depth > MAX_DEPTH {
+            return Err(malformed("nesting too deep"));
+        }
+        let initial = reader.byte()?;
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        if major == 7 {
+            return match info {
+                20 => Ok(Value::Bool(false)),
+                21 => Ok(Value::Bool(true)),
+                22 => Ok(Value::Null),
+                _ => Err(malformed(format!("unsupported CBOR simple value or float 0x{:02x}", initial))),
+            };
+        }
+        let n = match info {
+            0..=23 => u64::from(info),
+            24 => reader.uint(1)?,
+            25 => reader.uint(2)?,
+            26 => reader.uint(4)?,
+            27 => reader.uint(8)?,
+            31 => return Err(malformed("indefinite-length CBOR items are not supported")),
+            _ => return Err(malformed(format!("reserved CBOR additional info {}", info))),
+        };
+        match major {
+            0 => Ok(Value::Uint(n)),
+            2 => {
+                let len = reader.length(n)?;
+                Ok(Value::Bytes(reader.take(len)?.to_vec()))
+            }
+            3 => {
+                let len = reader.length(n)?;
+                Ok(Value::Text(utf8(reader.take(len)?)?))
+            }
+            4 => {
+                let len = reader.length(n)?;
+                (0..len).map(|_| decode_value(reader, depth + 1)).collect::<Result<_, _>>().map(Value::Array)
+            }
+            5 => {
+                let len = reader.length(n)?;
+                (0..len)
+                    .map(|_| Ok((decode_value(reader, depth + 1)?, decode_value(reader, depth + 1)?)))
+                    .collect::<Result<_, _>>()
+                    .map(Value::Map)
+            }
+            1 => Err(malformed("negative integers are not part of the schema")),
+            _ => Err(malformed("CBOR tags are not part of the schema")),
+        }
+    }
+}
+
+#[cfg(feature = "msgpack")]
+mod msgpack {
+    use super::{malformed, utf8, DecodeError, Reader, Value, MAX_DEPTH};
+
+    /// Writes a length or count in the fix form when it is under the fix
+    /// limit, else with the 8-, 16- or 32-bit marker.
+    fn length(out: &mut Vec<u8>, len: usize, fix: Option<(u8, usize)>, markers: [Option<u8>; 3]) {
+        match (fix, markers) {
+            (Some((base, limit)), _) if len < limit => out.push(base | len as u8),
+            (_, [Some(marker), _, _]) if len <= 0xff => out.extend([marker, len as u8]),
+            (_, [_, Some(marker), _]) if len <= 0xffff => {
+                out.push(marker);
+                out.extend((len as u16).to_be_bytes());
+            }
+            (_, [_, _, Some(marker)]) => {
+                out.push(marker);
+                out.extend((len as u32).to_be_bytes());
+            }
+            _ => unreachable!("every length has a 32-bit form"),
+        }
+    }
+
+    pub(super) fn encode(value: &Value, out: &mut Vec<u8>) {
+        match value {
+            Value::Null => out.push(0xc0),
+            Value::Bool(false) => out.push(0xc2),
+            Value::Bool(true) => out.push(0xc3),
+            Value::Uint(n) => match *n {
+                0..=0x7f => out.push(*n as u8),
+                0x80..=0xff => out.extend([0xcc, *n as u8]),
+                0x100..=0xffff => {
+                    out.push(0xcd);
+                    out.extend((*n as u16).to_be_bytes());
+                }
+                0x1_0000..=0xffff_ffff => {
+                    out.push(0xce);
+                    out.extend((*n as u32).to_be_bytes());
+                }
+                _ => {
+                    out.push(0xcf);
+                    out.extend(n.to_be_bytes());
+                }
+            },
+            Value::Bytes(bytes) => {
+                length(out, bytes.len(), None, [Some(0xc4), Some(0xc5), Some(0xc6)]);
+                out.extend(bytes);
+            }
+            Value::Text(text) => {
+                length(out, text.len(), Some((0xa0, 32)), [Some(0xd9), Some(0xda), Some(0xdb)]);
+                out.extend(text.as_bytes());
+            }
+            Value::Array(items) => {
+                length(out, items.len(), Some((0x90, 16)), [None, Some(0xdc), Some(0xdd)]);
+                items.iter().for_each(|item| encode(item, out));
+            }
+            Value::Map(entries) => {
+                length(out, entries.len(), Some((0x80, 16)), [None, Some(0xde), Some(0xdf)]);
+                for (key, value) in entries {
+                    encode(key, out);
+                    encode(value, out);
+                }
+            }
+        }
+    }
+
+    pub(super) fn decode(data: &[u8]) -> Result<Value, DecodeError> {
+        let mut reader = Reader { data };
+        let value = decode_value(&mut reader, 0)?;
+        reader.finish()?;
+        Ok(value)
+    }
+
+    fn decode_value(reader: &mut Reader, depth: usize) -> Result<Value, DecodeError> {
+        if depth > MAX_DEPTH {
+            return Err(malformed("nesting too deep"));
+        }
+        let marker = reader.byte()?;
+        let (kind, len) = match marker {
+            0x00..=0x7f => return Ok(Value::Uint(marker.into())),
+            0x80..=0x8f => ('m', u64::from(marker & 0x0f)),
+            0x90..=0x9f => ('a', u64::from(marker & 0x0f)),
+            0xa0..=0xbf => ('s', u64::from(marker & 0x1f)),
+            0xc0 => return Ok(Value::Null),
+            0xc2 => return Ok(Value::Bool(false)),
+            0xc3 => return Ok(Value::Bool(true)),
+            0xc4 => ('b', reader.uint(1)?),
+            0xc5 => ('b', reader.uint(2)?),
+            0xc6 => ('b', reader.uint(4)?),
+            0xcc => return Ok(Value::Uint(reader.uint(1)?)),
+            0xcd => return Ok(Value::Uint(reader.uint(2)?)),
+            0xce => return Ok(Value::Uint(reader.uint(4)?)),
+            0xcf => return Ok(Value::Uint(reader.uint(8)?)),
+            // Signed forms are accepted for non-negative values, which some
+            // encoders emit.
+            0xd0..=0xd3 => {
+                let size = 1 << (marker - 0xd0);
+                let raw = reader.uint(size)?;
+                let sign_bit = 1u64 << (size * 8 - 1);
+                return match raw & sign_bit {
+                    0 => Ok(Value::Uint(raw)),
+                    _ => Err(malformed("negative integers are not part of the schema")),
+                };
+            }
+            0xd9 => ('s', reader.uint(1)?),
+            0xda => ('s', reader.uint(2)?),
+            0xdb => ('s', reader.uint(4)?),
+            0xdc => ('a', reader.uint(2)?),
+            0xdd => ('a', reader.uint(4)?),
+            0xde => ('m', reader.uint(2)?),
+            0xdf => ('m', reader.uint(4)?),
+            _ => return Err(malformed(format!("unsupported MessagePack marker 0x{:02x}", marker))),
+        };
+        let len = reader.length(len)?;
+        match kind {
+            'b' => Ok(Value::Bytes(reader.take(len)?.to_vec())),
+            's' => Ok(Value::Text(utf8(reader.take(len)?)?)),
+            'a' => (0..len).map(|_| decode_value(reader, depth + 1)).collect::<Result<_, _>>().map(Value::Array),
+            _ => (0..len)
+                .map(|_| Ok((decode_value(reader, depth + 1)?, decode_value(reader, depth + 1)?)))
+                .collect::<Result<_, _>>()
+                .map(Value::Map),
+        }
+    }
+}
+
+impl RecordsResponse {
+    /// Encodes the response as CBOR; the schema is described in `codec`.
+    #[cfg(feature = "cbor")]
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        cbor::encode(&encode_response(self), &mut out);
+        out
+    }
+
+    #[cfg(feature = "cbor")]
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, DecodeError> {
+        decode_response(cbor::decode(bytes)?)
+    }
+
+    /// Encodes the response as MessagePack, with the same schema as CBOR.
+    #[cfg(feature = "msgpack")]
+    pub fn to_msgpack(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        msgpack::encode(&encode_response(self), &mut out);
+        out
+    }
+
+    #[cfg(feature = "msgpack")]
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self, DecodeError> {
+        decode_response(msgpack::decode(bytes)?)
+    }
+}
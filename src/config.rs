@@ -0,0 +1,500 @@
+//! Settings shared by programs and the `selfie` CLI, so that a host is
+//! configured once: a TOML file and `SELFIE_*` environment variables.
+//!
+//! ```toml
+//! nameservers = ["9.9.9.9", "149.112.112.112:53"]
+//! transport = "dns"          # or "doh", which needs doh_url
+//! doh_url = "http://127.0.0.1:8053/dns-query"
+//! timeout = 5                # seconds for a whole lookup
+//! deadline = 3               # seconds for a whole call, every key included
+//! nameserver_timeout = 2     # seconds before the next nameserver is asked
+//! quorum = 2                 # nameservers that must return the same records
+//! attempts = 2
+//! max_queries_per_second = 20
+//! max_in_flight = 8          # queries sent at once
+//! keys = ["bitcoin-payment", "nostr"]
+//! domain_template = "_{key}._selfie.{domain}"
+//! email_template = "{user}._{key}.{domain}"
+//!
+//! [cache]
+//! ttl = 300                  # seconds
+//! offline = false
+//!
+//! [dnssec]
+//! mode = "require"           # or "report" or "off"
+//! trust_anchor = "/etc/selfie/root.key"
+//! ```
+//!
+//! Every key is optional and has an environment variable, listed in
+//! `SETTINGS`; lists are comma-separated there, e.g.
+//! `SELFIE_DNS=9.9.9.9,149.112.112.112`. A CLI flag beats the variable,
+//! which beats the file, which beats the built-in default.
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::builder::{NameserverSource, TransportPreference};
+use crate::doh::DohResolver;
+use crate::name;
+use crate::options::LookupOptions;
+use crate::overrides::{is_comment, toml_key, toml_string};
+use crate::resolver::TxtResolver;
+use crate::routing;
+
+/// The variable naming the config file to read instead of `default_path`.
+pub const CONFIG_VAR: &str = "SELFIE_CONFIG";
+
+/// Each config file key and its environment variable.
+pub const SETTINGS: [(&str, &str); 17] = [
+    ("nameservers", "SELFIE_DNS"),
+    ("transport", "SELFIE_TRANSPORT"),
+    ("doh_url", "SELFIE_DOH_URL"),
+    ("timeout", "SELFIE_TIMEOUT"),
+    ("deadline", "SELFIE_DEADLINE"),
+    ("nameserver_timeout", "SELFIE_NAMESERVER_TIMEOUT"),
+    ("quorum", "SELFIE_QUORUM"),
+    ("attempts", "SELFIE_ATTEMPTS"),
+    ("max_queries_per_second", "SELFIE_MAX_QUERIES_PER_SECOND"),
+    ("max_in_flight", "SELFIE_MAX_IN_FLIGHT"),
+    ("keys", "SELFIE_KEYS"),
+    ("domain_template", "SELFIE_DOMAIN_TEMPLATE"),
+    ("email_template", "SELFIE_EMAIL_TEMPLATE"),
+    ("cache.ttl", "SELFIE_CACHE_TTL"),
+    ("cache.offline", "SELFIE_OFFLINE"),
+    ("dnssec.mode", "SELFIE_DNSSEC"),
+    ("dnssec.trust_anchor", "SELFIE_TRUST_ANCHOR"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ConfigError {
+    #[error("Cannot read config file {path}: {reason}")]
+    Io { path: String, reason: String },
+    #[error("Invalid config file: line {line}: {reason}")]
+    Parse { line: usize, reason: String },
+    #[error("Unknown config key {0}")]
+    UnknownKey(String),
+    /// `key` is the config file key, or the variable the value came from.
+    #[error("Invalid value for {key}: {reason}")]
+    InvalidValue { key: String, reason: String },
+}
+
+/// How queries reach the nameservers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// Plain DNS to `nameservers`, or the system's resolver without them.
+    Dns,
+    /// DNS-over-HTTPS to `doh_url`.
+    Doh,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnssecMode {
+    Off,
+    /// See `SdkBuilder::require_dnssec`.
+    Require,
+    /// See `SdkBuilder::report_dnssec`.
+    Report,
+}
+
+/// One layer of settings; `None` leaves a setting to the layers below.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// `None` uses the operating system's; see `nameserver_source`.
+    pub nameservers: Option<Vec<SocketAddr>>,
+    /// Defaults to `Doh` when `doh_url` is set, `Dns` otherwise.
+    pub transport: Option<Transport>,
+    pub doh_url: Option<String>,
+    pub timeout: Option<Duration>,
+    /// See `LookupOptions::deadline`.
+    pub deadline: Option<Duration>,
+    /// See `SdkBuilder::nameserver_timeout`.
+    pub nameserver_timeout: Option<Duration>,
+    /// See `SdkBuilder::quorum`.
+    pub quorum: Option<u32>,
+    pub attempts: Option<u32>,
+    /// See `SdkBuilder::max_queries_per_second`.
+    pub max_queries_per_second: Option<u32>,
+    /// See `SdkBuilder::max_in_flight`.
+    pub max_in_flight: Option<u32>,
+    /// Keys looked up when a command is given none.
+    pub keys: Option<Vec<String>>,
+    /// See `SdkBuilder::domain_template`.
+    pub domain_template: Option<String>,
+    /// See `SdkBuilder::email_template`.
+    pub email_template: Option<String>,
+    pub cache_ttl: Option<Duration>,
+    pub offline: Option<bool>,
+    pub dnssec: Option<DnssecMode>,
+    pub trust_anchor: Option<PathBuf>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/selfie-records/config.toml`, where the config
+    /// directory defaults to `~/.config`.
+    pub fn default_path() -> Option<PathBuf> {
+        let home = || std::env::var_os("HOME").map(|home| Path::new(&home).join(".config"));
+        let base = std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from).or_else(home)?;
+        Some(base.join("selfie-records").join("config.toml"))
+    }
+
+    /// The settings without CLI flags: the file at `path`, else the one
+    /// named by `SELFIE_CONFIG`, else `default_path` if it exists, with the
+    /// environment variables on top.
+    pub fn discover(path: Option<&Path>) -> Result<Config, ConfigError> {
+        let named = path.map(PathBuf::from).or_else(|| std::env::var_os(CONFIG_VAR).map(PathBuf::from));
+        let file = match named.or_else(|| Config::default_path().filter(|path| path.exists())) {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+        Ok(file.merge(Config::from_env()?))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Config, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| ConfigError::Io { path: path.display().to_string(), reason: e.to_string() })?;
+        Config::parse(&text)
+    }
+
+    /// Parses the subset of TOML config files need: the tables and keys of
+    /// the module example, with values on one line.
+    pub fn parse(text: &str) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        let mut table = String::new();
+        let mut seen = HashSet::new();
+        for (number, line) in text.lines().enumerate() {
+            let parse_error = |reason: &str| ConfigError::Parse { line: number + 1, reason: reason.to_string() };
+            let line = line.trim();
+            if is_comment(line) {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let (name, rest) = toml_key(header.trim_start()).ok_or_else(|| parse_error("invalid table name"))?;
+                if !rest.trim_start().starts_with(']') || !is_comment(&rest.trim_start()[1..]) {
+                    return Err(parse_error("expected ']' after table name"));
+                }
+                table = format!("{}.", name);
+                continue;
+            }
+            let (key, rest) = toml_key(line).ok_or_else(|| parse_error("invalid key"))?;
+            let rest = rest.trim_start().strip_prefix('=').ok_or_else(|| parse_error("expected '='"))?;
+            let (value, rest) = toml_value(rest.trim_start()).ok_or_else(|| parse_error("invalid value"))?;
+            if !is_comment(rest) {
+                return Err(parse_error("unexpected text after value"));
+            }
+            let key = format!("{}{}", table, key);
+            if !seen.insert(key.clone()) {
+                return Err(parse_error(&format!("{} is set twice", key)));
+            }
+            config.set(&key, &key, value)?;
+        }
+        Ok(config)
+    }
+
+    /// The settings in `SELFIE_*` variables of this process.
+    pub fn from_env() -> Result<Config, ConfigError> {
+        Config::from_vars(std::env::vars())
+    }
+
+    /// The settings in `vars`, name and value pairs as from `std::env::vars`.
+    /// Variables not in `SETTINGS` are ignored.
+    pub fn from_vars(vars: impl IntoIterator<Item = (String, String)>) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        for (name, text) in vars {
+            if let Some((key, _)) = SETTINGS.iter().find(|(_, var)| *var == name) {
+                config.set(key, &name, env_value(key, &text))?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// The settings of a JSON object with the keys of a config file, tables
+    /// as nested objects and lists as arrays.
+    #[cfg(any(feature = "ffi", feature = "python"))]
+    pub(crate) fn from_json(text: &str) -> Result<Config, ConfigError> {
+        use serde_json::Value;
+
+        let invalid = |key: &str, reason: &str| ConfigError::InvalidValue { key: key.to_string(), reason: reason.to_string() };
+        let json: Value = serde_json::from_str(text).map_err(|e| ConfigError::Parse { line: e.line(), reason: e.to_string() })?;
+        let Value::Object(object) = json else {
+            return Err(invalid("config", "expected a JSON object"));
+        };
+        let mut settings = Vec::new();
+        for (key, value) in object {
+            match value {
+                Value::Object(table) => settings.extend(table.into_iter().map(|(name, value)| (format!("{}.{}", key, name), value))),
+                value => settings.push((key, value)),
+            }
+        }
+        let mut vars = Vec::new();
+        for (key, value) in settings {
+            let (_, var) = SETTINGS.iter().find(|(name, _)| *name == key).ok_or_else(|| ConfigError::UnknownKey(key.clone()))?;
+            let text = match value {
+                Value::String(text) => text,
+                Value::Number(number) => number.to_string(),
+                Value::Bool(flag) => flag.to_string(),
+                Value::Array(items) => {
+                    let items: Option<Vec<&str>> = items.iter().map(Value::as_str).collect();
+                    items.ok_or_else(|| invalid(&key, "expected an array of strings"))?.join(",")
+                }
+                _ => return Err(invalid(&key, "expected a string, number, boolean or array")),
+            };
+            vars.push((var.to_string(), text));
+        }
+        Config::from_vars(vars)
+    }
+
+    /// This layer with the settings of `over` on top.
+    pub fn merge(self, over: Config) -> Config {
+        Config {
+            nameservers: over.nameservers.or(self.nameservers),
+            transport: over.transport.or(self.transport),
+            doh_url: over.doh_url.or(self.doh_url),
+            timeout: over.timeout.or(self.timeout),
+            deadline: over.deadline.or(self.deadline),
+            nameserver_timeout: over.nameserver_timeout.or(self.nameserver_timeout),
+            quorum: over.quorum.or(self.quorum),
+            attempts: over.attempts.or(self.attempts),
+            max_queries_per_second: over.max_queries_per_second.or(self.max_queries_per_second),
+            max_in_flight: over.max_in_flight.or(self.max_in_flight),
+            keys: over.keys.or(self.keys),
+            domain_template: over.domain_template.or(self.domain_template),
+            email_template: over.email_template.or(self.email_template),
+            cache_ttl: over.cache_ttl.or(self.cache_ttl),
+            offline: over.offline.or(self.offline),
+            dnssec: over.dnssec.or(self.dnssec),
+            trust_anchor: over.trust_anchor.or(self.trust_anchor),
+        }
+    }
+
+    /// The per-call settings, `timeout`, `deadline` and `attempts`.
+    pub fn lookup_options(&self) -> LookupOptions {
+        let options = LookupOptions::new();
+        let options = match self.timeout {
+            Some(timeout) => options.timeout(timeout),
+            None => options,
+        };
+        let options = match self.deadline {
+            Some(deadline) => options.deadline(deadline),
+            None => options,
+        };
+        match self.attempts {
+            Some(attempts) => options.attempts(attempts),
+            None => options,
+        }
+    }
+
+    /// Where plain DNS queries go: `Static` with `nameservers` when any
+    /// are set, `System` otherwise.
+    pub fn nameserver_source(&self) -> NameserverSource {
+        match &self.nameservers {
+            Some(servers) if !servers.is_empty() => NameserverSource::Static(servers.clone()),
+            _ => NameserverSource::System,
+        }
+    }
+
+    /// The resolver the transport settings describe, `None` for the
+    /// system's.
+    pub(crate) fn resolver(&self) -> Result<Option<Arc<dyn TxtResolver>>, ConfigError> {
+        let invalid = |key: &str, reason: String| ConfigError::InvalidValue { key: key.to_string(), reason };
+        let transport = self.transport.unwrap_or(match self.doh_url {
+            Some(_) => Transport::Doh,
+            None => Transport::Dns,
+        });
+        match transport {
+            Transport::Doh => {
+                let url = self.doh_url.as_deref().ok_or_else(|| invalid("doh_url", "required by transport \"doh\"".to_string()))?;
+                let resolver = DohResolver::new(url).map_err(|e| invalid("doh_url", e.to_string()))?;
+                Ok(Some(Arc::new(resolver)))
+            }
+            Transport::Dns => match (self.nameservers.as_deref(), self.quorum) {
+                (None | Some([]), None) => Ok(None),
+                (None | Some([]), Some(_)) => Err(invalid("quorum", "no nameservers are configured".to_string())),
+                (Some(servers), quorum) => {
+                    let quorum = quorum.map(|quorum| quorum as usize);
+                    if let Some(quorum) = quorum {
+                        routing::check_quorum(quorum, servers.len()).map_err(|e| invalid("quorum", e))?;
+                    }
+                    Ok(Some(routing::nameserver_resolver(servers, TransportPreference::UdpThenTcp, None, self.nameserver_timeout, quorum)))
+                }
+            },
+        }
+    }
+
+    /// Sets `key`, naming `source` in errors.
+    fn set(&mut self, key: &str, source: &str, value: Value) -> Result<(), ConfigError> {
+        let invalid = |reason: String| ConfigError::InvalidValue { key: source.to_string(), reason };
+        match key {
+            "nameservers" => {
+                let servers = value.strings().map_err(invalid)?;
+                self.nameservers =
+                    Some(servers.iter().map(|server| routing::parse_nameserver(server)).collect::<Result<_, _>>().map_err(invalid)?);
+            }
+            "transport" => {
+                self.transport = Some(match value.string().map_err(invalid)?.as_str() {
+                    "dns" => Transport::Dns,
+                    "doh" => Transport::Doh,
+                    other => return Err(invalid(format!("expected \"dns\" or \"doh\", not {:?}", other))),
+                })
+            }
+            "doh_url" => {
+                let url = value.string().map_err(invalid)?;
+                DohResolver::new(&url).map_err(|e| invalid(e.to_string()))?;
+                self.doh_url = Some(url);
+            }
+            "timeout" => self.timeout = Some(value.seconds().map_err(invalid)?),
+            "deadline" => self.deadline = Some(value.seconds().map_err(invalid)?),
+            "nameserver_timeout" => {
+                let timeout = value.seconds().ok().filter(|timeout| !timeout.is_zero());
+                self.nameserver_timeout = Some(timeout.ok_or_else(|| invalid("expected a positive number of seconds".to_string()))?);
+            }
+            "quorum" => self.quorum = Some(value.count().map_err(invalid)?),
+            "attempts" => self.attempts = Some(value.count().map_err(invalid)?),
+            "max_queries_per_second" => self.max_queries_per_second = Some(positive(value).map_err(invalid)?),
+            "max_in_flight" => self.max_in_flight = Some(positive(value).map_err(invalid)?),
+            "keys" => self.keys = Some(value.strings().map_err(invalid)?),
+            "domain_template" | "email_template" => {
+                let template = value.string().map_err(invalid)?;
+                let email = key == "email_template";
+                name::check_template(&template, email).map_err(|e| invalid(e.reason))?;
+                match email {
+                    true => self.email_template = Some(template),
+                    false => self.domain_template = Some(template),
+                }
+            }
+            "cache.ttl" => self.cache_ttl = Some(value.seconds().map_err(invalid)?),
+            "cache.offline" => self.offline = Some(value.flag().map_err(invalid)?),
+            "dnssec.mode" => {
+                self.dnssec = Some(match value.string().map_err(invalid)?.as_str() {
+                    "off" => DnssecMode::Off,
+                    "require" if cfg!(feature = "dnssec") => DnssecMode::Require,
+                    "report" if cfg!(feature = "dnssec") => DnssecMode::Report,
+                    "require" | "report" => return Err(invalid("DNSSEC support is not compiled in".to_string())),
+                    other => return Err(invalid(format!("expected \"off\", \"require\" or \"report\", not {:?}", other))),
+                })
+            }
+            "dnssec.trust_anchor" if cfg!(feature = "dnssec") => {
+                self.trust_anchor = Some(PathBuf::from(value.string().map_err(invalid)?));
+            }
+            "dnssec.trust_anchor" => return Err(invalid("DNSSEC support is not compiled in".to_string())),
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn string(self) -> Result<String, String> {
+        match self {
+            Value::String(text) => Ok(text),
+            _ => Err("expected a string".to_string()),
+        }
+    }
+
+    fn strings(self) -> Result<Vec<String>, String> {
+        match self {
+            Value::Array(values) => values.into_iter().map(Value::string).collect::<Result<_, _>>(),
+            _ => Err("expected a list of strings".to_string()),
+        }
+        .map_err(|_| "expected a list of strings".to_string())
+    }
+
+    fn seconds(self) -> Result<Duration, String> {
+        let seconds = match self {
+            Value::Integer(seconds) => seconds as f64,
+            Value::Float(seconds) => seconds,
+            _ => f64::NAN,
+        };
+        Duration::try_from_secs_f64(seconds).map_err(|_| "expected a non-negative number of seconds".to_string())
+    }
+
+    fn count(self) -> Result<u32, String> {
+        match self {
+            Value::Integer(count) => u32::try_from(count).ok(),
+            _ => None,
+        }
+        .ok_or_else(|| "expected a non-negative integer".to_string())
+    }
+
+    fn flag(self) -> Result<bool, String> {
+        match self {
+            Value::Bool(flag) => Ok(flag),
+            Value::Integer(0) => Ok(false),
+            Value::Integer(1) => Ok(true),
+            _ => Err("expected true or false".to_string()),
+        }
+    }
+}
+
+/// Reads a string, number, boolean or one-line array.
+fn toml_value(text: &str) -> Option<(Value, &str)> {
+    if text.starts_with(['"', '\'']) {
+        let (string, rest) = toml_string(text)?;
+        return Some((Value::String(string), rest));
+    }
+    if let Some(mut rest) = text.strip_prefix('[') {
+        let mut values = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(rest) = rest.strip_prefix(']') {
+                return Some((Value::Array(values), rest));
+            }
+            let (value, after) = toml_value(rest)?;
+            values.push(value);
+            rest = after.trim_start();
+            match rest.strip_prefix(',') {
+                Some(after) => rest = after,
+                None if rest.starts_with(']') => {}
+                None => return None,
+            }
+        }
+    }
+    let end = text.find(|c: char| !(c.is_ascii_alphanumeric() || "+-._".contains(c))).unwrap_or(text.len());
+    let (word, rest) = text.split_at(end);
+    let value = match word {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => {
+            let number = word.replace('_', "");
+            match number.parse() {
+                Ok(integer) => Value::Integer(integer),
+                Err(_) => Value::Float(number.parse().ok().filter(|float: &f64| float.is_finite())?),
+            }
+        }
+    };
+    Some((value, rest))
+}
+
+fn positive(value: Value) -> Result<u32, String> {
+    value.count().ok().filter(|&count| count > 0).ok_or_else(|| "expected a positive integer".to_string())
+}
+
+/// An environment variable's text as the value type of `key`. Text that
+/// does not parse is passed on as a string, for `set` to reject.
+fn env_value(key: &str, text: &str) -> Value {
+    match key {
+        "nameservers" | "keys" => Value::Array(
+            text.split(',').map(str::trim).filter(|item| !item.is_empty()).map(|item| Value::String(item.to_string())).collect(),
+        ),
+        "timeout" | "deadline" | "nameserver_timeout" | "quorum" | "attempts" | "max_queries_per_second" | "max_in_flight" | "cache.ttl"
+        | "cache.offline" => match toml_value(text.trim()) {
+            Some((value, "")) => value,
+            _ => Value::String(text.to_string()),
+        },
+        _ => Value::String(text.to_string()),
+    }
+}
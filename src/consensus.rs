@@ -0,0 +1,261 @@
+//! Checks that every authoritative nameserver of a zone serves the same
+//! TXT values for a record. Disagreement usually means a half-finished
+//! migration or a secondary serving something it should not.
+
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use trust_dns_proto::rr::{Name, RData, RecordType};
+
+use crate::error::SelfieError;
+use crate::resolver::TxtResolver;
+use crate::transport::{DnsTransport, TransportFactory};
+use crate::wire::{self, DirectResolver, Transports};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An authoritative nameserver and the addresses it was found at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nameserver {
+    pub host: String,
+    pub addrs: Vec<SocketAddr>,
+}
+
+/// The zone a name belongs to and the servers authoritative for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZoneServers {
+    pub zone: String,
+    pub nameservers: Vec<Nameserver>,
+}
+
+/// Finds the authoritative servers for the zone enclosing a name.
+#[async_trait]
+pub trait NameserverDiscovery: Send + Sync {
+    async fn discover(&self, qname: &str) -> Result<ZoneServers, SelfieError>;
+}
+
+/// Discovers nameservers through a recursive resolver: NS lookups from the
+/// name upwards until the zone apex, then A and AAAA lookups for each host.
+#[derive(Debug, Clone)]
+pub struct RecursiveDiscovery {
+    transports: Transports,
+    timeout: Duration,
+    port: u16,
+}
+
+impl RecursiveDiscovery {
+    pub fn new(server: SocketAddr) -> Self {
+        RecursiveDiscovery { transports: Transports::direct(server), timeout: DEFAULT_TIMEOUT, port: 53 }
+    }
+
+    /// Sends the NS and address lookups over `transport` instead.
+    pub fn with_transport(transport: Arc<dyn DnsTransport>) -> Self {
+        RecursiveDiscovery { transports: Transports::custom(transport), timeout: DEFAULT_TIMEOUT, port: 53 }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Port the authoritative servers are queried on, 53 by default.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    async fn addresses(&self, host: &Name) -> Vec<SocketAddr> {
+        let mut addrs = Vec::new();
+        for rtype in [RecordType::A, RecordType::AAAA] {
+            let Ok(response) = wire::query(&self.transports, host, rtype, false, self.timeout).await else {
+                continue;
+            };
+            addrs.extend(response.answers().iter().filter_map(|record| match record.rdata() {
+                RData::A(ip) => Some(SocketAddr::new(IpAddr::V4(*ip), self.port)),
+                RData::AAAA(ip) => Some(SocketAddr::new(IpAddr::V6(*ip), self.port)),
+                _ => None,
+            }));
+        }
+        addrs
+    }
+}
+
+/// Discovers through the first of the system's nameservers.
+impl Default for RecursiveDiscovery {
+    fn default() -> Self {
+        RecursiveDiscovery::new(crate::resolver::system_nameservers()[0])
+    }
+}
+
+#[async_trait]
+impl NameserverDiscovery for RecursiveDiscovery {
+    async fn discover(&self, qname: &str) -> Result<ZoneServers, SelfieError> {
+        let mut name = Name::from_utf8(qname).map_err(|e| SelfieError::Resolver(format!("Invalid name {}: {}", qname, e)))?;
+        name.set_fqdn(true);
+        while !name.is_root() {
+            let response = wire::query(&self.transports, &name, RecordType::NS, false, self.timeout).await?;
+            let mut hosts: Vec<Name> = response
+                .answers()
+                .iter()
+                .filter(|record| *record.name() == name)
+                .filter_map(|record| match record.rdata() {
+                    RData::NS(host) => Some(host.clone()),
+                    _ => None,
+                })
+                .collect();
+            if hosts.is_empty() {
+                name = name.base_name();
+                continue;
+            }
+            hosts.sort();
+            hosts.dedup();
+
+            let mut nameservers = Vec::new();
+            for host in hosts {
+                let addrs = self.addresses(&host).await;
+                nameservers.push(Nameserver { host: trim_root(&host), addrs });
+            }
+            return Ok(ZoneServers { zone: trim_root(&name), nameservers });
+        }
+        Err(SelfieError::Resolver(format!("No NS records found for {} or its parents", qname)))
+    }
+}
+
+/// What one server answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerOutcome {
+    /// The values served, sorted; empty when the server has no such record.
+    Answered(Vec<String>),
+    /// The server could not be queried or refused to answer.
+    Unreachable(String),
+}
+
+/// The outcome for one address of one nameserver. `address` is `None` for
+/// a host that has no address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerReport {
+    pub nameserver: String,
+    pub address: Option<SocketAddr>,
+    pub outcome: ServerOutcome,
+}
+
+impl fmt::Display for ServerReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.address {
+            Some(address) => write!(f, "{} ({})", self.nameserver, address),
+            None => f.write_str(&self.nameserver),
+        }
+    }
+}
+
+/// Servers that answered with the same set of values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueGroup {
+    pub values: Vec<String>,
+    /// Servers as `host (address)`.
+    pub servers: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Consensus {
+    /// Every server that answered served these values.
+    Consistent(Vec<String>),
+    /// Servers disagree; groups are ordered by how many servers are in them.
+    Divergent(Vec<ValueGroup>),
+    /// No server could be queried.
+    NoAnswers,
+}
+
+impl fmt::Display for Consensus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Consensus::Consistent(_) => f.write_str("consistent"),
+            Consensus::Divergent(groups) => write!(f, "divergent ({} different answers)", groups.len()),
+            Consensus::NoAnswers => f.write_str("no server answered"),
+        }
+    }
+}
+
+/// Per-server answers for one record name across a zone's nameservers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsensusReport {
+    pub qname: String,
+    pub zone: String,
+    pub servers: Vec<ServerReport>,
+}
+
+impl ConsensusReport {
+    /// Groups the servers that answered by the values they served.
+    pub fn groups(&self) -> Vec<ValueGroup> {
+        let mut groups: Vec<ValueGroup> = Vec::new();
+        for server in &self.servers {
+            let ServerOutcome::Answered(values) = &server.outcome else {
+                continue;
+            };
+            match groups.iter_mut().find(|group| group.values == *values) {
+                Some(group) => group.servers.push(server.to_string()),
+                None => groups.push(ValueGroup { values: values.clone(), servers: vec![server.to_string()] }),
+            }
+        }
+        groups.sort_by_key(|group| std::cmp::Reverse(group.servers.len()));
+        groups
+    }
+
+    /// Unreachable servers are left out: they neither confirm nor dispute
+    /// the others' answers.
+    pub fn verdict(&self) -> Consensus {
+        let mut groups = self.groups();
+        match groups.len() {
+            0 => Consensus::NoAnswers,
+            1 => Consensus::Consistent(groups.remove(0).values),
+            _ => Consensus::Divergent(groups),
+        }
+    }
+}
+
+/// Queries every address of every nameserver of `servers` for the TXT
+/// values at `qname`, concurrently, over transports from `transport` when
+/// given.
+pub(crate) async fn check(servers: ZoneServers, qname: &str, transport: Option<&TransportFactory>) -> ConsensusReport {
+    let mut pending = Vec::new();
+    for nameserver in &servers.nameservers {
+        if nameserver.addrs.is_empty() {
+            pending.push((nameserver.host.clone(), None, None));
+        }
+        for &address in &nameserver.addrs {
+            let resolver = match transport {
+                Some(transport) => DirectResolver::with_transport(transport(address)),
+                None => DirectResolver::new(address),
+            };
+            let resolver = resolver.timeout(DEFAULT_TIMEOUT);
+            let qname = qname.to_string();
+            let query = tokio::spawn(async move { resolver.txt_lookup(&qname).await });
+            pending.push((nameserver.host.clone(), Some(address), Some(query)));
+        }
+    }
+
+    let mut reports = Vec::new();
+    for (nameserver, address, query) in pending {
+        let outcome = match query {
+            None => ServerOutcome::Unreachable("no address found for nameserver".to_string()),
+            Some(query) => match query.await {
+                Ok(Ok(mut values)) => {
+                    values.sort();
+                    ServerOutcome::Answered(values)
+                }
+                Ok(Err(e)) => ServerOutcome::Unreachable(e.to_string()),
+                Err(e) => ServerOutcome::Unreachable(e.to_string()),
+            },
+        };
+        reports.push(ServerReport { nameserver, address, outcome });
+    }
+    ConsensusReport { qname: qname.to_string(), zone: servers.zone, servers: reports }
+}
+
+fn trim_root(name: &Name) -> String {
+    let name = name.to_string();
+    name.strip_suffix('.').unwrap_or(&name).to_string()
+}
@@ -0,0 +1,58 @@
+//! Re-asking a second, independent resolver to catch answers injected by
+//! captive portals or a hijacked resolver.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::resolver::TxtResolver;
+
+/// What the cross-check resolver made of an answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CrossCheck {
+    /// Both resolvers returned the same record set.
+    Verified,
+    /// The cross-check resolver returned `other_values` instead, which is
+    /// empty if it found no records.
+    Mismatch { other_values: Vec<String> },
+    /// The cross-check resolver failed or ran out of time, so the answer
+    /// could not be confirmed either way.
+    Unavailable { reason: String },
+}
+
+impl fmt::Display for CrossCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrossCheck::Verified => f.write_str("verified"),
+            CrossCheck::Mismatch { other_values } => write!(f, "mismatch ({:?})", other_values),
+            CrossCheck::Unavailable { reason } => write!(f, "unavailable ({})", reason),
+        }
+    }
+}
+
+pub(crate) struct CrossChecker {
+    pub(crate) resolver: Arc<dyn TxtResolver>,
+    pub(crate) strict: bool,
+}
+
+impl CrossChecker {
+    /// Looks `name` up again within `timeout` and compares the record set,
+    /// ignoring order, with `answers`.
+    pub(crate) async fn check(&self, name: &str, answers: &[String], timeout: Duration) -> CrossCheck {
+        if timeout.is_zero() {
+            return CrossCheck::Unavailable { reason: "no time left".to_string() };
+        }
+        let other_values = match crate::time::timeout(timeout, self.resolver.txt_lookup(name)).await {
+            Ok(Ok(values)) => values,
+            Ok(Err(e)) => return CrossCheck::Unavailable { reason: e.to_string() },
+            Err(_) => return CrossCheck::Unavailable { reason: format!("timed out after {}ms", timeout.as_millis()) },
+        };
+        let (mut ours, mut theirs) = (answers.to_vec(), other_values.clone());
+        ours.sort();
+        theirs.sort();
+        match ours == theirs {
+            true => CrossCheck::Verified,
+            false => CrossCheck::Mismatch { other_values },
+        }
+    }
+}
@@ -0,0 +1,506 @@
+//! Walks the DNSSEC chain of trust for a name, zone cut by zone cut, and
+//! reports where it holds and where it breaks.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use trust_dns_proto::rr::dnssec::rdata::{DNSSECRData, DNSSECRecordType, DNSKEY, DS, SIG};
+use trust_dns_proto::rr::dnssec::Verifier;
+use trust_dns_proto::rr::{DNSClass, Name, RData, Record, RecordType};
+
+use crate::encoding::{self, RawRecord};
+use crate::error::SelfieError;
+use crate::transport::DnsTransport;
+use crate::wire::{self, CnameChain, Transports};
+
+mod anchors;
+mod proof;
+
+pub use anchors::{load_trust_anchors, parse_trust_anchors, TrustAnchor, TrustAnchorError};
+pub use proof::{prove, verify_proof, ProofError, ProvenRecord};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where the trace fetches records from. Answers must include the RRSIGs
+/// covering the requested type.
+#[async_trait]
+pub trait RecordSource: Send + Sync {
+    async fn fetch(&self, name: &Name, rtype: RecordType) -> Result<Vec<Record>, SelfieError>;
+}
+
+/// Fetches records from a recursive resolver with the DNSSEC OK bit set.
+#[derive(Debug, Clone)]
+pub struct NetworkSource {
+    transports: Transports,
+    timeout: Duration,
+}
+
+impl NetworkSource {
+    pub fn new(server: SocketAddr) -> Self {
+        NetworkSource { transports: Transports::direct(server), timeout: DEFAULT_TIMEOUT }
+    }
+
+    /// Fetches over `transport` instead; it must carry the large, signed
+    /// answers, since truncated ones are not retried.
+    pub fn with_transport(transport: Arc<dyn DnsTransport>) -> Self {
+        NetworkSource { transports: Transports::custom(transport), timeout: DEFAULT_TIMEOUT }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Fetches from the first of the system's nameservers.
+impl Default for NetworkSource {
+    fn default() -> Self {
+        NetworkSource::new(crate::resolver::system_nameservers()[0])
+    }
+}
+
+#[async_trait]
+impl RecordSource for NetworkSource {
+    async fn fetch(&self, name: &Name, rtype: RecordType) -> Result<Vec<Record>, SelfieError> {
+        let mut response = wire::query(&self.transports, name, rtype, true, self.timeout).await?;
+        Ok(response.take_answers())
+    }
+}
+
+/// Where an SDK fetches DNSSEC chains from, which root keys it trusts and
+/// whether lookups must validate.
+#[derive(Clone)]
+pub(crate) struct Validation {
+    pub(crate) source: Arc<dyn RecordSource>,
+    pub(crate) anchors: Vec<TrustAnchor>,
+    pub(crate) required: bool,
+    /// Validates without requiring, see `SdkBuilder::report_dnssec`.
+    pub(crate) reported: bool,
+}
+
+impl Validation {
+    pub(crate) fn new(source: Arc<dyn RecordSource>, anchors: Option<Vec<TrustAnchor>>, required: bool, reported: bool) -> Self {
+        Validation {
+            source,
+            anchors: anchors.unwrap_or_else(TrustAnchor::iana_root),
+            required,
+            reported,
+        }
+    }
+
+    /// Checks `answers`, the TXT records a lookup of `qname` resolved,
+    /// against its chain of trust: they must be the RRset it authenticates.
+    pub(crate) async fn validate_txt(&self, qname: &str, answers: &[RawRecord]) -> Result<(), SelfieError> {
+        let trace = trace(self.source.as_ref(), qname, &self.anchors, crate::time::now()).await?;
+        match (trace.verdict().clone(), trace.final_answer()) {
+            (Verdict::Secure, Some(signed)) if signed.records.len() == answers.len() && answers.iter().all(|record| signed.records.contains(record)) => Ok(()),
+            (Verdict::Secure, _) => Err(SelfieError::Dnssec(format!("the records resolved for {} are not the signed ones", qname))),
+            (verdict @ Verdict::Insecure(_), _) => Err(SelfieError::DnssecInsecure(verdict.to_string())),
+            (verdict, _) => Err(SelfieError::Dnssec(verdict.to_string())),
+        }
+    }
+}
+
+/// Outcome of checking one link of the chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Secure,
+    Insecure(InsecureReason),
+    Bogus(BogusReason),
+}
+
+impl Verdict {
+    pub fn is_secure(&self) -> bool {
+        matches!(self, Verdict::Secure)
+    }
+}
+
+/// Why a link could not be authenticated, without evidence of tampering.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InsecureReason {
+    /// The zone is delegated without a DS record at its parent.
+    MissingDs { zone: String },
+}
+
+/// Why a link failed validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BogusReason {
+    /// No DNSKEY at the top of the chain matches a trust anchor.
+    NoTrustedKey,
+    /// None of the DNSKEYs matches a DS record published by the parent.
+    DsMismatch,
+    MissingSignature { rtype: RecordType },
+    /// No RRSIG was made by a key of the expected zone.
+    NoMatchingKey { rtype: RecordType },
+    InvalidSignature { rtype: RecordType, key_tag: u16 },
+    ExpiredSignature { rtype: RecordType, key_tag: u16 },
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Secure => f.write_str("secure"),
+            Verdict::Insecure(InsecureReason::MissingDs { zone }) => {
+                write!(f, "insecure (no DS for {} at its parent)", zone)
+            }
+            Verdict::Bogus(reason) => write!(f, "bogus ({})", reason),
+        }
+    }
+}
+
+impl fmt::Display for BogusReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BogusReason::NoTrustedKey => f.write_str("no DNSKEY matches a trust anchor"),
+            BogusReason::DsMismatch => f.write_str("no DNSKEY matches the parent's DS records"),
+            BogusReason::MissingSignature { rtype } => write!(f, "{} is not signed", rtype),
+            BogusReason::NoMatchingKey { rtype } => write!(f, "no zone key made the RRSIG over {}", rtype),
+            BogusReason::InvalidSignature { rtype, key_tag } => {
+                write!(f, "RRSIG over {} by key {} does not verify", rtype, key_tag)
+            }
+            BogusReason::ExpiredSignature { rtype, key_tag } => {
+                write!(f, "RRSIG over {} by key {} is outside its validity period", rtype, key_tag)
+            }
+        }
+    }
+}
+
+/// One zone on the path from the trust anchor to the queried name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZoneLevel {
+    pub zone: String,
+    /// Key tags of the DS records the parent publishes for this zone.
+    pub ds_key_tags: Vec<u16>,
+    /// Key tags of the zone's DNSKEY set.
+    pub dnskey_tags: Vec<u16>,
+    pub verdict: Verdict,
+}
+
+/// The TXT RRset at the end of the chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnswerCheck {
+    pub values: Vec<String>,
+    /// The rdata `values` were read from.
+    pub records: Vec<RawRecord>,
+    pub verdict: Verdict,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnssecTrace {
+    pub qname: String,
+    pub zones: Vec<ZoneLevel>,
+    /// `None` when the name has no TXT records, or is an alias.
+    pub answer: Option<AnswerCheck>,
+    /// Set when the name is an alias instead of holding TXT records.
+    pub alias: Option<Alias>,
+}
+
+/// The CNAME RRset at a traced name, checked with the keys of the name's
+/// own zone, and the trace of its target, checked from the trust anchors
+/// on its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alias {
+    pub target: String,
+    /// The verdict on the CNAME RRset.
+    pub verdict: Verdict,
+    pub trace: Box<DnssecTrace>,
+}
+
+impl DnssecTrace {
+    /// The first non-secure verdict along the chain, aliases followed, or
+    /// `Secure`.
+    pub fn verdict(&self) -> &Verdict {
+        let own = self
+            .zones
+            .iter()
+            .map(|level| &level.verdict)
+            .chain(self.answer.iter().map(|answer| &answer.verdict))
+            .chain(self.alias.iter().map(|alias| &alias.verdict))
+            .find(|verdict| !verdict.is_secure());
+        match (own, &self.alias) {
+            (Some(verdict), _) => verdict,
+            (None, Some(alias)) => alias.trace.verdict(),
+            (None, None) => &Verdict::Secure,
+        }
+    }
+
+    /// The TXT RRset at the end of the alias chain, if it has one.
+    pub fn final_answer(&self) -> Option<&AnswerCheck> {
+        match &self.alias {
+            Some(alias) => alias.trace.final_answer(),
+            None => self.answer.as_ref(),
+        }
+    }
+}
+
+/// Traces the chain of trust for the TXT records at `qname`, starting from
+/// the root zone keys matching any of `anchors` and evaluating signature
+/// validity at `now`. Only records owned by `qname` count: when it is an
+/// alias, the CNAME is checked against `qname`'s zone and its target traced
+/// from the anchors again, up to eight aliases deep.
+pub async fn trace(
+    source: &dyn RecordSource,
+    qname: &str,
+    anchors: &[TrustAnchor],
+    now: SystemTime,
+) -> Result<DnssecTrace, SelfieError> {
+    let mut qname_fqdn = Name::from_utf8(qname).map_err(|e| SelfieError::Resolver(e.to_string()))?;
+    qname_fqdn.set_fqdn(true);
+    let now = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs() as u32).unwrap_or(0);
+
+    let mut chain = CnameChain::new(qname_fqdn, wire::DEFAULT_MAX_CNAME_CHAIN);
+    let mut links = Vec::new();
+    loop {
+        let (link, alias) = trace_link(source, chain.last(), anchors, now).await?;
+        match alias {
+            Some((target, verdict)) => {
+                links.push((link, Some((target.to_string(), verdict))));
+                chain.push(target)?;
+            }
+            None => {
+                links.push((link, None));
+                break;
+            }
+        }
+    }
+    let mut traced: Option<DnssecTrace> = None;
+    for (mut link, alias) in links.into_iter().rev() {
+        if let (Some((target, verdict)), Some(trace)) = (alias, traced.take()) {
+            link.alias = Some(Alias { target, verdict, trace: Box::new(trace) });
+        }
+        traced = Some(link);
+    }
+    Ok(traced.expect("the chain has a link"))
+}
+
+/// Traces `qname` alone: its zones, and its TXT RRset or the target of its
+/// CNAME with the verdict on the CNAME.
+async fn trace_link(
+    source: &dyn RecordSource,
+    qname: &Name,
+    anchors: &[TrustAnchor],
+    now: u32,
+) -> Result<(DnssecTrace, Option<(Name, Verdict)>), SelfieError> {
+    let mut zones = Vec::new();
+    let mut zone = Name::root();
+    let root_keys = source.fetch(&zone, dnskey_type()).await?;
+    let (mut keys, verdict) = verify_root_keys(&root_keys, anchors, now);
+    zones.push(level(&zone, Vec::new(), &root_keys, verdict));
+
+    if zones[0].verdict.is_secure() {
+        for depth in 1..=qname.num_labels() as usize {
+            let child = qname.trim_to(depth);
+            let ds_records = source.fetch(&child, ds_type()).await?;
+            let ds: Vec<&DS> = ds_records.iter().filter(|record| record.name() == &child).filter_map(as_ds).collect();
+
+            if ds.is_empty() {
+                if is_zone_cut(source, &child).await? {
+                    let verdict = Verdict::Insecure(InsecureReason::MissingDs { zone: child.to_string() });
+                    let child_keys = source.fetch(&child, dnskey_type()).await?;
+                    zones.push(level(&child, Vec::new(), &child_keys, verdict));
+                    break;
+                }
+                continue;
+            }
+
+            let ds_tags = ds.iter().map(|ds| ds.key_tag()).collect();
+            if let Err(reason) = verify_rrset(&child, ds_type(), &ds_records, &keys, &zone, now) {
+                zones.push(level(&child, ds_tags, &[], Verdict::Bogus(reason)));
+                break;
+            }
+            let child_keys = source.fetch(&child, dnskey_type()).await?;
+            let (verified, verdict) = verify_delegated_keys(&child, &child_keys, &ds, now);
+            let secure = verdict.is_secure();
+            zones.push(level(&child, ds_tags, &child_keys, verdict));
+            if !secure {
+                break;
+            }
+            zone = child;
+            keys = verified;
+        }
+    }
+
+    // Resolvers add the records of alias targets, and an attacker may add
+    // anything; none of it is covered by the signatures checked here.
+    let fetched = source.fetch(qname, RecordType::TXT).await?;
+    let owned = |rtype: RecordType| fetched.iter().filter(move |record| record.name() == qname && record.record_type() == rtype);
+    let records: Vec<RawRecord> = owned(RecordType::TXT)
+        .filter_map(|record| match record.rdata() {
+            RData::TXT(txt) => Some(RawRecord::new(txt.txt_data())),
+            _ => None,
+        })
+        .collect();
+    let target = owned(RecordType::CNAME).find_map(|record| match record.rdata() {
+        RData::CNAME(target) => Some(target.clone()),
+        _ => None,
+    });
+    let chain_verdict = zones.iter().map(|level| &level.verdict).find(|v| !v.is_secure()).cloned();
+    let check = |rtype: RecordType| match chain_verdict.clone() {
+        Some(verdict) => verdict,
+        None => match verify_rrset(qname, rtype, &fetched, &keys, &zone, now) {
+            Ok(()) => Verdict::Secure,
+            Err(reason) => Verdict::Bogus(reason),
+        },
+    };
+    let (answer, alias) = if !records.is_empty() {
+        let verdict = check(RecordType::TXT);
+        (Some(AnswerCheck { values: encoding::lossy(&records), records, verdict }), None)
+    } else {
+        (None, target.map(|target| (target, check(RecordType::CNAME))))
+    };
+
+    Ok((DnssecTrace { qname: qname.to_string(), zones, answer, alias: None }, alias))
+}
+
+/// The records a resolver would answer a query for `rtype` at `name` with
+/// from `records`: those of that type, or else the name's CNAME, with the
+/// RRSIGs covering them.
+pub(crate) fn answer_from(records: &[Record], name: &Name, rtype: RecordType) -> Vec<Record> {
+    let select = |rtype: RecordType| -> Vec<Record> {
+        records
+            .iter()
+            .filter(|record| record.name() == name)
+            .filter(|record| match as_rrsig(record) {
+                Some(sig) => sig.type_covered() == rtype,
+                None => record.record_type() == rtype,
+            })
+            .cloned()
+            .collect()
+    };
+    let answer = select(rtype);
+    if rtype == RecordType::CNAME || answer.iter().any(|record| !is_rrsig(record)) {
+        return answer;
+    }
+    select(RecordType::CNAME)
+}
+
+/// The name has its own NS or DNSKEY set, so a delegation happens here.
+async fn is_zone_cut(source: &dyn RecordSource, name: &Name) -> Result<bool, SelfieError> {
+    if source.fetch(name, dnskey_type()).await?.iter().any(|r| r.name() == name && as_dnskey(r).is_some()) {
+        return Ok(true);
+    }
+    let ns = source.fetch(name, RecordType::NS).await?;
+    Ok(ns.iter().any(|record| record.record_type() == RecordType::NS && record.name() == name))
+}
+
+fn verify_root_keys(records: &[Record], anchors: &[TrustAnchor], now: u32) -> (Vec<DNSKEY>, Verdict) {
+    let trusted: Vec<DNSKEY> = owned_keys(records, &Name::root())
+        .filter(|key| anchors.iter().any(|anchor| anchor.matches(key)))
+        .cloned()
+        .collect();
+    if trusted.is_empty() {
+        return (Vec::new(), Verdict::Bogus(BogusReason::NoTrustedKey));
+    }
+    match verify_rrset(&Name::root(), dnskey_type(), records, &trusted, &Name::root(), now) {
+        Ok(()) => (zone_keys(records, &Name::root()), Verdict::Secure),
+        Err(reason) => (Vec::new(), Verdict::Bogus(reason)),
+    }
+}
+
+fn verify_delegated_keys(zone: &Name, records: &[Record], ds: &[&DS], now: u32) -> (Vec<DNSKEY>, Verdict) {
+    let entry_points: Vec<DNSKEY> = owned_keys(records, zone)
+        .filter(|key| ds.iter().any(|ds| ds.covers(zone, key).unwrap_or(false)))
+        .cloned()
+        .collect();
+    if entry_points.is_empty() {
+        return (Vec::new(), Verdict::Bogus(BogusReason::DsMismatch));
+    }
+    match verify_rrset(zone, dnskey_type(), records, &entry_points, zone, now) {
+        Ok(()) => (zone_keys(records, zone), Verdict::Secure),
+        Err(reason) => (Vec::new(), Verdict::Bogus(reason)),
+    }
+}
+
+/// Checks that some RRSIG in `records` over the `rtype` RRset of `name` was
+/// made by one of `keys` on behalf of `signer` and is currently valid.
+/// Records of other names and types are ignored.
+fn verify_rrset(name: &Name, rtype: RecordType, records: &[Record], keys: &[DNSKEY], signer: &Name, now: u32) -> Result<(), BogusReason> {
+    let owned = || records.iter().filter(|record| record.name() == name);
+    let rrset: Vec<Record> = owned().filter(|record| record.record_type() == rtype).cloned().collect();
+    let sigs: Vec<&SIG> = owned()
+        .filter_map(as_rrsig)
+        .filter(|sig| sig.type_covered() == rtype)
+        .collect();
+    if sigs.is_empty() {
+        return Err(BogusReason::MissingSignature { rtype });
+    }
+
+    let mut failure = BogusReason::NoMatchingKey { rtype };
+    for sig in sigs.iter().filter(|sig| sig.signer_name() == signer) {
+        let candidates = keys
+            .iter()
+            .filter(|key| key.algorithm() == sig.algorithm() && key.calculate_key_tag().ok() == Some(sig.key_tag()));
+        for key in candidates {
+            let key_tag = sig.key_tag();
+            if now < sig.sig_inception() || now > sig.sig_expiration() {
+                failure = BogusReason::ExpiredSignature { rtype, key_tag };
+                continue;
+            }
+            match key.verify_rrsig(name, DNSClass::IN, sig, &rrset) {
+                Ok(()) => return Ok(()),
+                Err(_) => failure = BogusReason::InvalidSignature { rtype, key_tag },
+            }
+        }
+    }
+    Err(failure)
+}
+
+fn level(zone: &Name, ds_key_tags: Vec<u16>, keys: &[Record], verdict: Verdict) -> ZoneLevel {
+    ZoneLevel {
+        zone: zone.to_string(),
+        ds_key_tags,
+        dnskey_tags: keys
+            .iter()
+            .filter_map(as_dnskey)
+            .filter_map(|key| key.calculate_key_tag().ok())
+            .collect(),
+        verdict,
+    }
+}
+
+fn zone_keys(records: &[Record], zone: &Name) -> Vec<DNSKEY> {
+    owned_keys(records, zone).filter(|key| key.zone_key() && !key.revoke()).cloned().collect()
+}
+
+/// The DNSKEYs of `zone` among `records`.
+fn owned_keys<'a>(records: &'a [Record], zone: &'a Name) -> impl Iterator<Item = &'a DNSKEY> {
+    records.iter().filter(move |record| record.name() == zone).filter_map(as_dnskey)
+}
+
+fn dnskey_type() -> RecordType {
+    RecordType::DNSSEC(DNSSECRecordType::DNSKEY)
+}
+
+fn ds_type() -> RecordType {
+    RecordType::DNSSEC(DNSSECRecordType::DS)
+}
+
+fn is_rrsig(record: &Record) -> bool {
+    as_rrsig(record).is_some()
+}
+
+fn as_dnskey(record: &Record) -> Option<&DNSKEY> {
+    match record.rdata() {
+        RData::DNSSEC(DNSSECRData::DNSKEY(key)) => Some(key),
+        _ => None,
+    }
+}
+
+fn as_ds(record: &Record) -> Option<&DS> {
+    match record.rdata() {
+        RData::DNSSEC(DNSSECRData::DS(ds)) => Some(ds),
+        _ => None,
+    }
+}
+
+fn as_rrsig(record: &Record) -> Option<&SIG> {
+    match record.rdata() {
+        RData::DNSSEC(DNSSECRData::SIG(sig)) if record.record_type() == RecordType::DNSSEC(DNSSECRecordType::RRSIG) => {
+            Some(sig)
+        }
+        _ => None,
+    }
+}
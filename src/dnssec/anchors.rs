@@ -0,0 +1,203 @@
+//! Root trust anchors: the built-in IANA set, or keys supplied by the
+//! caller for private roots and rollovers.
+
+use std::path::Path;
+
+use data_encoding::{BASE64, HEXUPPER_PERMISSIVE};
+use thiserror::Error;
+use trust_dns_proto::rr::dnssec::rdata::{DNSKEY, DS};
+use trust_dns_proto::rr::dnssec::{Algorithm, DigestType};
+use trust_dns_proto::rr::Name;
+
+/// IANA root KSK DS records, from https://data.iana.org/root-anchors/root-anchors.xml.
+const IANA_ROOT_DS: [(u16, &str); 2] = [
+    (20326, "E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D"),
+    (38696, "683D2D0ACB8C9B712A1948B27F741219298D0A450D612C483AF444A4C0FB2B16"),
+];
+
+const DNSKEY_PROTOCOL: u8 = 3;
+
+/// A root key the chain of trust may start from, given as the key itself
+/// or as the digest of one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrustAnchor {
+    Dnskey(DNSKEY),
+    Ds(DS),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid trust anchor: {0}")]
+pub struct TrustAnchorError(String);
+
+impl TrustAnchor {
+    pub fn from_dnskey(dnskey: DNSKEY) -> Self {
+        TrustAnchor::Dnskey(dnskey)
+    }
+
+    pub fn from_ds(ds: DS) -> Self {
+        TrustAnchor::Ds(ds)
+    }
+
+    /// The root key-signing keys published by IANA.
+    pub fn iana_root() -> Vec<TrustAnchor> {
+        IANA_ROOT_DS
+            .iter()
+            .map(|(key_tag, digest)| {
+                let digest = HEXUPPER_PERMISSIVE.decode(digest.as_bytes()).unwrap();
+                TrustAnchor::Ds(DS::new(*key_tag, Algorithm::RSASHA256, DigestType::SHA256, digest))
+            })
+            .collect()
+    }
+
+    /// Whether `key`, published at the root, is this anchor.
+    pub(crate) fn matches(&self, key: &DNSKEY) -> bool {
+        match self {
+            TrustAnchor::Dnskey(anchor) => anchor.algorithm() == key.algorithm() && anchor.public_key() == key.public_key(),
+            TrustAnchor::Ds(ds) => ds.covers(&Name::root(), key).unwrap_or(false),
+        }
+    }
+}
+
+/// Reads trust anchors from a file; see `parse_trust_anchors`.
+pub fn load_trust_anchors(path: impl AsRef<Path>) -> Result<Vec<TrustAnchor>, TrustAnchorError> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| TrustAnchorError(format!("cannot read {}: {}", path.display(), e)))?;
+    parse_trust_anchors(&text)
+}
+
+/// Parses root trust anchors in either of BIND's formats: a
+/// `trust-anchors` (or `managed-keys`/`trusted-keys`) clause from
+/// named.conf, or zone-file DNSKEY and DS records such as a `root.key`.
+pub fn parse_trust_anchors(text: &str) -> Result<Vec<TrustAnchor>, TrustAnchorError> {
+    let anchors = if text.contains('{') { parse_named_conf(text)? } else { parse_zone_file(text)? };
+    if anchors.is_empty() {
+        return Err(TrustAnchorError("no DNSKEY or DS anchors found".to_string()));
+    }
+    Ok(anchors)
+}
+
+fn parse_named_conf(text: &str) -> Result<Vec<TrustAnchor>, TrustAnchorError> {
+    let text = strip_conf_comments(text);
+    let mut anchors = Vec::new();
+    let mut rest = text.as_str();
+    while let Some(open) = rest.find('{') {
+        let close = rest[open..].find('}').ok_or_else(|| TrustAnchorError("unclosed '{'".to_string()))? + open;
+        for statement in rest[open + 1..close].split(';') {
+            let tokens: Vec<String> = statement.split_whitespace().map(|token| token.replace('"', "")).collect();
+            let Some((owner, tokens)) = tokens.split_first() else {
+                continue;
+            };
+            check_root(owner)?;
+            let anchor = match tokens.first().map(String::as_str) {
+                Some("initial-key" | "static-key") => parse_dnskey(&tokens[1..])?,
+                Some("initial-ds" | "static-ds") => parse_ds(&tokens[1..])?,
+                _ => parse_dnskey(tokens)?,
+            };
+            anchors.push(anchor);
+        }
+        rest = &rest[close + 1..];
+    }
+    Ok(anchors)
+}
+
+fn strip_conf_comments(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        rest = rest[start..].find("*/").map_or("", |end| &rest[start + end + 2..]);
+    }
+    stripped.push_str(rest);
+    stripped
+        .lines()
+        .map(|line| {
+            let end = [line.find("//"), line.find('#')].into_iter().flatten().min().unwrap_or(line.len());
+            &line[..end]
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn parse_zone_file(text: &str) -> Result<Vec<TrustAnchor>, TrustAnchorError> {
+    let uncommented: Vec<&str> = text.lines().map(|line| line.split(';').next().unwrap_or("")).collect();
+    // Parentheses let a record continue over several lines.
+    let joined = uncommented.join("\n");
+    let mut records = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0usize;
+    for line in joined.lines() {
+        depth += line.matches('(').count();
+        depth = depth.saturating_sub(line.matches(')').count());
+        current.push(' ');
+        current.push_str(&line.replace(['(', ')'], " "));
+        if depth == 0 {
+            records.push(std::mem::take(&mut current));
+        }
+    }
+
+    let mut anchors = Vec::new();
+    for record in records {
+        let tokens: Vec<String> = record.split_whitespace().map(str::to_string).collect();
+        let Some((owner, tokens)) = tokens.split_first() else {
+            continue;
+        };
+        if owner.starts_with('$') {
+            continue;
+        }
+        check_root(owner)?;
+        let Some(type_index) = tokens.iter().position(|token| matches!(token.to_ascii_uppercase().as_str(), "DNSKEY" | "DS")) else {
+            return Err(TrustAnchorError(format!("expected a DNSKEY or DS record: {}", record.trim())));
+        };
+        let rdata = &tokens[type_index + 1..];
+        let anchor = match tokens[type_index].to_ascii_uppercase().as_str() {
+            "DNSKEY" => parse_dnskey(rdata)?,
+            _ => parse_ds(rdata)?,
+        };
+        anchors.push(anchor);
+    }
+    Ok(anchors)
+}
+
+fn check_root(owner: &str) -> Result<(), TrustAnchorError> {
+    if owner != "." {
+        return Err(TrustAnchorError(format!("only root anchors are supported, got {:?}", owner)));
+    }
+    Ok(())
+}
+
+fn parse_dnskey(tokens: &[String]) -> Result<TrustAnchor, TrustAnchorError> {
+    let [flags, protocol, algorithm, key @ ..] = tokens else {
+        return Err(TrustAnchorError("DNSKEY needs flags, protocol, algorithm and key".to_string()));
+    };
+    let flags: u16 = parse_number(flags, "flags")?;
+    if parse_number::<u8>(protocol, "protocol")? != DNSKEY_PROTOCOL {
+        return Err(TrustAnchorError(format!("DNSKEY protocol must be 3, got {}", protocol)));
+    }
+    let algorithm = Algorithm::from_u8(parse_number(algorithm, "algorithm")?);
+    let key = BASE64
+        .decode(key.concat().as_bytes())
+        .map_err(|e| TrustAnchorError(format!("invalid DNSKEY key: {}", e)))?;
+    Ok(TrustAnchor::Dnskey(DNSKEY::new(flags & 0x0100 != 0, flags & 0x0001 != 0, flags & 0x0080 != 0, algorithm, key)))
+}
+
+fn parse_ds(tokens: &[String]) -> Result<TrustAnchor, TrustAnchorError> {
+    let [key_tag, algorithm, digest_type, digest @ ..] = tokens else {
+        return Err(TrustAnchorError("DS needs key tag, algorithm, digest type and digest".to_string()));
+    };
+    let digest_type = DigestType::from_u8(parse_number(digest_type, "digest type")?)
+        .map_err(|e| TrustAnchorError(format!("invalid DS digest type: {}", e)))?;
+    let digest = HEXUPPER_PERMISSIVE
+        .decode(digest.concat().as_bytes())
+        .map_err(|e| TrustAnchorError(format!("invalid DS digest: {}", e)))?;
+    Ok(TrustAnchor::Ds(DS::new(
+        parse_number(key_tag, "key tag")?,
+        Algorithm::from_u8(parse_number(algorithm, "algorithm")?),
+        digest_type,
+        digest,
+    )))
+}
+
+fn parse_number<T: std::str::FromStr>(token: &str, field: &str) -> Result<T, TrustAnchorError> {
+    token.parse().map_err(|_| TrustAnchorError(format!("invalid {} {:?}", field, token)))
+}
@@ -0,0 +1,123 @@
+//! Self-contained proofs of a TXT RRset: every record its chain of trust
+//! was checked with, so another machine can check it again offline. In
+//! the spirit of the RFC 9102 chain that bLIP 32 uses for BIP 353.
+
+use std::sync::{Mutex, PoisonError};
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use thiserror::Error;
+use trust_dns_proto::rr::{Name, Record, RecordType};
+use trust_dns_proto::serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder};
+
+use super::{answer_from, trace, BogusReason, DnssecTrace, InsecureReason, RecordSource, TrustAnchor, Verdict};
+use crate::encoding::RawRecord;
+use crate::error::SelfieError;
+
+/// A TXT RRset whose chain of trust validated, with the records proving it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvenRecord {
+    pub qname: String,
+    pub values: Vec<String>,
+    /// The rdata `values` were read from.
+    pub records: Vec<RawRecord>,
+    /// The DNSKEY, DS, TXT and RRSIG records the chain was checked with,
+    /// in wire format with uncompressed names, one after another.
+    pub proof: Vec<u8>,
+}
+
+impl ProvenRecord {
+    /// The records of `proof`.
+    pub fn proof_records(&self) -> Result<Vec<Record>, ProofError> {
+        decode(&self.proof)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ProofError {
+    /// `zone` is delegated without a DS record, so nothing below it is signed.
+    #[error("{zone} is not signed")]
+    Unsigned { zone: String },
+    #[error("The chain of trust is broken: {0}")]
+    BogusChain(BogusReason),
+    #[error("Invalid proof: {0}")]
+    Malformed(String),
+    #[error(transparent)]
+    Lookup(#[from] SelfieError),
+}
+
+/// Traces the chain of trust for the TXT records at `qname` like `trace`,
+/// keeping every record fetched as the proof.
+pub async fn prove(source: &dyn RecordSource, qname: &str, anchors: &[TrustAnchor], now: SystemTime) -> Result<ProvenRecord, ProofError> {
+    let recording = Recording { source, records: Mutex::new(Vec::new()) };
+    let trace = trace(&recording, qname, anchors, now).await?;
+    let records = recording.records.into_inner().unwrap_or_else(PoisonError::into_inner);
+    proven(trace, encode(&records)?)
+}
+
+/// Checks `proof` for the TXT records at `qname` from `anchors`, as of
+/// `now`, without a query.
+pub async fn verify_proof(qname: &str, proof: &[u8], anchors: &[TrustAnchor], now: SystemTime) -> Result<ProvenRecord, ProofError> {
+    let source = ProofSource(decode(proof)?);
+    let trace = trace(&source, qname, anchors, now).await?;
+    proven(trace, proof.to_vec())
+}
+
+fn proven(trace: DnssecTrace, proof: Vec<u8>) -> Result<ProvenRecord, ProofError> {
+    match trace.verdict() {
+        Verdict::Secure => {}
+        Verdict::Insecure(InsecureReason::MissingDs { zone }) => return Err(ProofError::Unsigned { zone: zone.clone() }),
+        Verdict::Bogus(reason) => return Err(ProofError::BogusChain(reason.clone())),
+    }
+    let answer = trace.final_answer().ok_or(SelfieError::NoRecords)?;
+    Ok(ProvenRecord { qname: trace.qname.clone(), values: answer.values.clone(), records: answer.records.clone(), proof })
+}
+
+fn encode(records: &[Record]) -> Result<Vec<u8>, ProofError> {
+    let mut bytes = Vec::new();
+    let mut encoder = BinEncoder::new(&mut bytes);
+    encoder.set_canonical_names(true);
+    for record in records {
+        record.emit(&mut encoder).map_err(|e| SelfieError::Resolver(e.to_string()))?;
+    }
+    Ok(bytes)
+}
+
+fn decode(proof: &[u8]) -> Result<Vec<Record>, ProofError> {
+    let mut decoder = BinDecoder::new(proof);
+    let mut records = Vec::new();
+    while !decoder.is_empty() {
+        records.push(Record::read(&mut decoder).map_err(|e| ProofError::Malformed(e.to_string()))?);
+    }
+    Ok(records)
+}
+
+/// Passes fetches through to `source`, keeping each record once.
+struct Recording<'a> {
+    source: &'a dyn RecordSource,
+    records: Mutex<Vec<Record>>,
+}
+
+#[async_trait]
+impl RecordSource for Recording<'_> {
+    async fn fetch(&self, name: &Name, rtype: RecordType) -> Result<Vec<Record>, SelfieError> {
+        let fetched = self.source.fetch(name, rtype).await?;
+        let mut records = self.records.lock().unwrap_or_else(PoisonError::into_inner);
+        for record in &fetched {
+            if !records.contains(record) {
+                records.push(record.clone());
+            }
+        }
+        Ok(fetched)
+    }
+}
+
+/// Serves the records of a proof as a resolver would, see `answer_from`.
+struct ProofSource(Vec<Record>);
+
+#[async_trait]
+impl RecordSource for ProofSource {
+    async fn fetch(&self, name: &Name, rtype: RecordType) -> Result<Vec<Record>, SelfieError> {
+        Ok(answer_from(&self.0, name, rtype))
+    }
+}
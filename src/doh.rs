@@ -0,0 +1,365 @@
+//! DNS-over-HTTPS (RFC 8484) transport: wire-format queries POSTed as
+//! `application/dns-message`, or sent with GET as a base64url `dns`
+//! parameter. `https://` endpoints, such as the public resolvers of
+//! `DohResolver::cloudflare`, `google` and `quad9`, need the `tls` feature,
+//! or on wasm32 the `wasm` one; without either only `http://` ones, e.g. a
+//! local forwarder, can be used.
+//!
+//! Queries to the configured endpoint share keep-alive HTTP/1.1
+//! connections, TLS sessions included, or one HTTP/2 connection carrying
+//! them all at once when an `https://` endpoint offers HTTP/2, see
+//! `DohResolver::max_connections`.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use trust_dns_proto::op::Message;
+use trust_dns_proto::rr::{Name, RecordType};
+
+use crate::encoding::{self, RawRecord};
+use crate::error::SelfieError;
+use crate::http::{self, Endpoint, Pool, Response};
+
+pub use crate::http::PoolStats;
+use crate::proxy::Socks5Config;
+use crate::resolver::{Readiness, TxtResolver};
+use crate::time::{self, Instant};
+#[cfg(feature = "tls")]
+use crate::tls::TlsConfig;
+use crate::wire::{self, Transport, WireInfo};
+
+const CONTENT_TYPE: &str = "application/dns-message";
+const DEFAULT_USER_AGENT: &str = concat!("selfie-records-sdk/", env!("CARGO_PKG_VERSION"));
+/// Hops followed once redirects are allowed.
+const MAX_REDIRECTS: usize = 5;
+const DEFAULT_MAX_CONNECTIONS: usize = 4;
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How queries are put on the wire (RFC 8484 section 4.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DohMethod {
+    /// The query as the request body.
+    #[default]
+    Post,
+    /// The query as an unpadded base64url `dns` query parameter, which
+    /// lets HTTP caches store answers.
+    Get,
+}
+
+/// A `TxtResolver` sending queries to a DoH endpoint.
+#[derive(Debug, Clone)]
+pub struct DohResolver {
+    url: String,
+    endpoint: Endpoint,
+    method: DohMethod,
+    headers: Vec<(String, String)>,
+    user_agent: String,
+    follow_redirects: bool,
+    max_cname_chain: usize,
+    /// Shared by clones, `None` when pooling is disabled.
+    pool: Option<Arc<Pool>>,
+    max_connections: usize,
+    idle_timeout: Duration,
+}
+
+impl DohResolver {
+    /// Parses an endpoint such as `https://dns.example/dns-query` or
+    /// `http://127.0.0.1:8053/dns-query`. The path, and any query string,
+    /// are kept as given; `/dns-query` is used when there is none.
+    pub fn new(url: &str) -> Result<Self, SelfieError> {
+        let endpoint = Endpoint::parse(url, "/dns-query")
+            .map_err(|reason| SelfieError::Resolver(format!("Invalid DoH endpoint {}: {}", url, reason)))?;
+        Ok(DohResolver {
+            url: url.to_string(),
+            endpoint,
+            method: DohMethod::default(),
+            headers: Vec::new(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            follow_redirects: false,
+            max_cname_chain: wire::DEFAULT_MAX_CNAME_CHAIN,
+            pool: Some(Arc::new(Pool::new(DEFAULT_MAX_CONNECTIONS, DEFAULT_IDLE_TIMEOUT))),
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        })
+    }
+
+    /// Cloudflare's resolver, `https://cloudflare-dns.com/dns-query`.
+    #[cfg(any(feature = "tls", target_arch = "wasm32"))]
+    pub fn cloudflare() -> Self {
+        DohResolver::new("https://cloudflare-dns.com/dns-query").expect("the preset URL is valid")
+    }
+
+    /// Google Public DNS, `https://dns.google/dns-query`.
+    #[cfg(any(feature = "tls", target_arch = "wasm32"))]
+    pub fn google() -> Self {
+        DohResolver::new("https://dns.google/dns-query").expect("the preset URL is valid")
+    }
+
+    /// Quad9's filtering resolver, `https://dns.quad9.net/dns-query`.
+    #[cfg(any(feature = "tls", target_arch = "wasm32"))]
+    pub fn quad9() -> Self {
+        DohResolver::new("https://dns.quad9.net/dns-query").expect("the preset URL is valid")
+    }
+
+    /// Checks an `https://` endpoint's certificate, and those of redirect
+    /// targets, against `config` instead of Mozilla's roots.
+    #[cfg(feature = "tls")]
+    pub fn tls_config(mut self, config: TlsConfig) -> Self {
+        if self.endpoint.tls.is_some() {
+            self.endpoint.tls = Some(config);
+            self.pool = self.new_pool();
+        }
+        self
+    }
+
+    pub fn method(mut self, method: DohMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Adds a header sent with every query, such as `Authorization` for an
+    /// authenticating proxy. Names and values may not contain line breaks.
+    pub fn header(mut self, name: &str, value: &str) -> Result<Self, SelfieError> {
+        let valid_name = !name.is_empty() && name.bytes().all(|b| b.is_ascii_graphic() && b != b':');
+        if !valid_name || value.contains(['\r', '\n']) {
+            return Err(SelfieError::Resolver(format!("Invalid DoH header {:?}", name)));
+        }
+        self.headers.push((name.to_string(), value.trim().to_string()));
+        Ok(self)
+    }
+
+    /// Replaces the default `selfie-records-sdk/<version>` User-Agent.
+    pub fn user_agent(mut self, user_agent: &str) -> Result<Self, SelfieError> {
+        if user_agent.contains(['\r', '\n']) {
+            return Err(SelfieError::Resolver("Invalid DoH User-Agent".to_string()));
+        }
+        self.user_agent = user_agent.to_string();
+        Ok(self)
+    }
+
+    /// Follows up to five redirects, re-sending the query as is.
+    /// Redirects are refused by default so that a compromised or
+    /// misconfigured endpoint cannot send queries, and any credentials in
+    /// the headers, elsewhere.
+    pub fn follow_redirects(mut self, follow: bool) -> Self {
+        self.follow_redirects = follow;
+        self
+    }
+
+    /// CNAME hops accepted in an answer before the lookup fails with
+    /// `SelfieError::CnameChainTooLong`. Defaults to 8.
+    pub fn max_cname_chain(mut self, max: usize) -> Self {
+        self.max_cname_chain = max;
+        self
+    }
+
+    /// Connects to the endpoint, and to any redirect target, through
+    /// `proxy`, which also resolves its host name.
+    pub fn proxy(mut self, proxy: Socks5Config) -> Self {
+        self.endpoint.proxy = Some(proxy);
+        self
+    }
+
+    /// Connections to the endpoint kept open for later queries, and the
+    /// most queries in flight at once over HTTP/1.1; more wait for a
+    /// connection. Over HTTP/2 queries share one connection instead. A
+    /// connection broken while idle or mid-response is replaced and the
+    /// query sent again, once. Defaults to 4; 0 opens a connection per
+    /// query, with no limit.
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = max;
+        self.pool = self.new_pool();
+        self
+    }
+
+    /// How long a connection may stay idle before it is closed rather than
+    /// reused. Defaults to 30 seconds.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self.pool = self.new_pool();
+        self
+    }
+
+    /// Counters of the connection pool, all zero when pooling is disabled.
+    pub fn pool_stats(&self) -> PoolStats {
+        self.pool.as_ref().map(|pool| pool.stats()).unwrap_or_default()
+    }
+
+    fn new_pool(&self) -> Option<Arc<Pool>> {
+        (self.max_connections > 0).then(|| Arc::new(Pool::new(self.max_connections, self.idle_timeout)))
+    }
+
+    fn error(&self, e: impl std::fmt::Display) -> SelfieError {
+        SelfieError::Resolver(format!("Error querying {}: {}", self.url, e))
+    }
+
+    fn http_error(&self, e: http::Error) -> SelfieError {
+        match e {
+            http::Error::ProxyUnreachable { proxy, reason } => SelfieError::ProxyUnreachable { proxy: proxy.to_string(), reason },
+            http::Error::Certificate { reason } => SelfieError::TlsCertificate { server: self.url.clone(), reason },
+            e => self.error(e),
+        }
+    }
+
+    /// Sends `query` to `endpoint` with the configured method and headers,
+    /// over the pool when it is the configured endpoint rather than a
+    /// redirect target.
+    async fn send(&self, endpoint: &Endpoint, query: &[u8]) -> Result<Response, SelfieError> {
+        let mut headers = vec![("Accept", CONTENT_TYPE), ("User-Agent", self.user_agent.as_str())];
+        headers.extend(self.headers.iter().map(|(name, value)| (name.as_str(), value.as_str())));
+        let (method, path, body) = match self.method {
+            DohMethod::Post => ("POST", endpoint.path.clone(), Some((CONTENT_TYPE, query))),
+            DohMethod::Get => {
+                let separator = if endpoint.path.contains('?') { '&' } else { '?' };
+                ("GET", format!("{}{}dns={}", endpoint.path, separator, base64url(query)), None)
+            }
+        };
+        let result = match &self.pool {
+            Some(pool) if *endpoint == self.endpoint => endpoint.pooled(pool, method, &path, &headers, body).await,
+            _ => endpoint.request(method, &path, &headers, body).await,
+        };
+        result.map_err(|e| self.http_error(e))
+    }
+}
+
+#[async_trait]
+impl TxtResolver for DohResolver {
+    async fn txt_lookup(&self, name: &str) -> Result<Vec<String>, SelfieError> {
+        Ok(self.txt_lookup_with_info(name).await?.0)
+    }
+
+    fn describe(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn txt_lookup_with_info(&self, name: &str) -> Result<(Vec<String>, WireInfo), SelfieError> {
+        let (records, info) = self.txt_lookup_raw(name).await?;
+        Ok((encoding::lossy(&records), info))
+    }
+
+    async fn txt_lookup_raw(&self, name: &str) -> Result<(Vec<RawRecord>, WireInfo), SelfieError> {
+        let mut qname = Name::from_utf8(name).map_err(|e| self.error(e))?;
+        qname.set_fqdn(true);
+        let mut query = wire::build_query(&qname, RecordType::TXT, false);
+        let mut chain = wire::CnameChain::new(qname, self.max_cname_chain);
+        query.set_id(0);
+        let body = query.to_vec().map_err(|e| self.error(e))?;
+
+        let mut endpoint = self.endpoint.clone();
+        let mut response = self.send(&endpoint, &body).await?;
+        for _ in 0..MAX_REDIRECTS {
+            if !matches!(response.status, 301 | 302 | 303 | 307 | 308) {
+                break;
+            }
+            let location = response.header("location").unwrap_or_default();
+            if !self.follow_redirects {
+                return Err(self.error(format!("refusing HTTP {} redirect to {}", response.status, location)));
+            }
+            endpoint = match location.strip_prefix('/') {
+                Some(_) => Endpoint { path: location.to_string(), ..endpoint },
+                None => {
+                    let mut target = Endpoint::parse(location, "/").map_err(|reason| self.error(format!("invalid redirect to {}: {}", location, reason)))?;
+                    target.proxy = endpoint.proxy;
+                    #[cfg(feature = "tls")]
+                    if target.tls.is_some() && self.endpoint.tls.is_some() {
+                        target.tls = self.endpoint.tls.clone();
+                    }
+                    target
+                }
+            };
+            response = self.send(&endpoint, &body).await?;
+        }
+        match response.status {
+            200 => {}
+            429 | 503 => {
+                return Err(SelfieError::RateLimited {
+                    server: self.url.clone(),
+                    retry_after: response
+                        .header("retry-after")
+                        .and_then(|value| parse_retry_after(value, time::now())),
+                })
+            }
+            status => return Err(self.error(format!("HTTP status {}", status))),
+        }
+
+        let message = Message::from_vec(&response.body).map_err(|e| self.error(e))?;
+        let info = WireInfo::from_message(&message, response.body.len(), Transport::Doh);
+        // The endpoint resolves aliases itself; one it left unresolved has
+        // nothing behind it.
+        Ok((wire::txt_answers(&message, name, &mut chain)?.unwrap_or_default(), info))
+    }
+
+    /// Opens a pooled connection to the endpoint, or with pooling disabled
+    /// only checks that one can be opened.
+    async fn warm_up(&self, probe: Option<&str>) -> Option<Readiness> {
+        let started = Instant::now();
+        let connected = match &self.pool {
+            Some(pool) => self.endpoint.preconnect(pool).await,
+            None => self.endpoint.probe().await.map(|()| true),
+        };
+        let (connected, mut error) = match connected {
+            Ok(connected) => (connected, None),
+            Err(e) => (false, Some(self.http_error(e))),
+        };
+        if let (None, Some(probe)) = (&error, probe) {
+            error = self.txt_lookup_raw(probe).await.err();
+        }
+        Some(Readiness { endpoint: self.url.clone(), connected, elapsed: started.elapsed(), error })
+    }
+}
+
+/// Unpadded base64url (RFC 4648 section 5).
+fn base64url(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    encoded
+}
+
+/// Reads a `Retry-After` header given as delay-seconds or as an IMF-fixdate
+/// such as `Wed, 21 Oct 2015 07:28:00 GMT`. Dates in the past mean no delay.
+pub fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = parse_http_date(value)?;
+    Some(date.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let (_weekday, rest) = value.split_once(", ")?;
+    let fields: Vec<&str> = rest.split(' ').collect();
+    let [day, month, year, time, "GMT"] = fields.as_slice() else {
+        return None;
+    };
+    let day: u64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|m| m == month)? as u64 + 1;
+    let year: i64 = year.parse().ok()?;
+    let time: Vec<u64> = time.split(':').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    let [hour, minute, second] = time.as_slice() else {
+        return None;
+    };
+    if !(1..=31).contains(&day) || *hour > 23 || *minute > 59 || *second > 60 {
+        return None;
+    }
+
+    // Days since the epoch for a proleptic Gregorian date (Hinnant's algorithm).
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year as i64;
+    let days = u64::try_from(era * 146_097 + day_of_era - 719_468).ok()?;
+
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86_400 + hour * 3600 + minute * 60 + second))
+}
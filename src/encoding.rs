@@ -0,0 +1,211 @@
+//! TXT rdata is arbitrary bytes, and values end up in logs and terminals:
+//! the records as received, bounds on their size, their conversion to
+//! text, and escaping for display.
+
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::error::SelfieError;
+
+/// Keys whose values `SdkBuilder::strict_encoding` rejects when they have
+/// an encoding issue.
+pub(crate) const PAYMENT_KEYS: [&str; 2] = ["bitcoin-payment", "bip47"];
+
+/// Why a value's text cannot be taken at face value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueEncodingIssue {
+    /// The rdata is not valid UTF-8. `KeyResult::value` is a lossy
+    /// conversion of `KeyResult::raw_bytes`.
+    NotUtf8,
+    /// The value contains C0 or C1 control characters, such as NUL or the
+    /// escape starting an ANSI sequence.
+    ControlCharacters,
+}
+
+impl fmt::Display for ValueEncodingIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueEncodingIssue::NotUtf8 => f.write_str("not_utf8"),
+            ValueEncodingIssue::ControlCharacters => f.write_str("control_characters"),
+        }
+    }
+}
+
+impl ValueEncodingIssue {
+    /// The issue with the value `bytes` and their text form `value`.
+    pub(crate) fn of(bytes: &[u8], value: &str) -> Option<Self> {
+        if std::str::from_utf8(bytes).is_err() {
+            Some(ValueEncodingIssue::NotUtf8)
+        } else if value.chars().any(char::is_control) {
+            Some(ValueEncodingIssue::ControlCharacters)
+        } else {
+            None
+        }
+    }
+}
+
+/// Bounds on the TXT answers `SdkBuilder::response_limits` accepts, so a
+/// hostile or broken zone cannot make the SDK hold and hand on arbitrary
+/// amounts of data. An answer beyond any of them fails with
+/// `SelfieError::ResponseTooLarge`; nothing is truncated. Each chunk of a
+/// chunked value is an answer of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResponseLimits {
+    /// TXT records in one answer, unrelated ones included.
+    pub max_records_per_key: usize,
+    /// Bytes of one record, its character-strings joined.
+    pub max_value_bytes: usize,
+    /// Bytes of all records of one answer.
+    pub max_total_bytes: usize,
+}
+
+impl Default for ResponseLimits {
+    fn default() -> Self {
+        ResponseLimits { max_records_per_key: 8, max_value_bytes: 4096, max_total_bytes: 16384 }
+    }
+}
+
+impl ResponseLimits {
+    pub(crate) fn check(&self, name: &str, records: &[RawRecord]) -> Result<(), SelfieError> {
+        let too_large = |reason: String| Err(SelfieError::ResponseTooLarge { name: name.to_string(), reason });
+        if records.len() > self.max_records_per_key {
+            return too_large(format!("{} records, at most {}", records.len(), self.max_records_per_key));
+        }
+        if let Some(record) = records.iter().find(|record| record.bytes.len() > self.max_value_bytes) {
+            return too_large(format!("a record of {} bytes, at most {}", record.bytes.len(), self.max_value_bytes));
+        }
+        let total: usize = records.iter().map(|record| record.bytes.len()).sum();
+        if total > self.max_total_bytes {
+            return too_large(format!("{} bytes, at most {}", total, self.max_total_bytes));
+        }
+        Ok(())
+    }
+}
+
+/// What `SdkBuilder::sanitize_values` does to `KeyResult::value` before
+/// returning it. `KeyResult::raw_value` and `KeyResult::raw_records` are
+/// always the bytes as received.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Sanitization {
+    /// The value is the text of the bytes, flagged with
+    /// `KeyResult::encoding_issue` when it cannot be taken at face value.
+    #[default]
+    Off,
+    /// Leading and trailing whitespace is trimmed and control characters
+    /// are escaped as by `escape_controls`. Invalid UTF-8 is replaced with
+    /// U+FFFD, as it is anyway.
+    Escape,
+    /// Leading and trailing whitespace is trimmed, and a value that is
+    /// still not valid UTF-8 or contains control characters fails with
+    /// `SelfieError::InvalidRecord`.
+    Reject,
+}
+
+impl Sanitization {
+    /// `value`, the text of `bytes`, as returned, or the issue it is
+    /// rejected for.
+    pub(crate) fn apply(self, bytes: &[u8], value: String) -> Result<String, ValueEncodingIssue> {
+        if self == Sanitization::Off {
+            return Ok(value);
+        }
+        let trimmed = value.trim();
+        match ValueEncodingIssue::of(bytes, trimmed) {
+            None => Ok(trimmed.to_string()),
+            Some(issue) if self == Sanitization::Reject => Err(issue),
+            Some(_) => Ok(escape_controls(trimmed).into_owned()),
+        }
+    }
+}
+
+/// One TXT record's rdata: its character-strings, byte for byte.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RawRecord {
+    bytes: Vec<u8>,
+    /// Where each character-string ends in `bytes`.
+    ends: Vec<usize>,
+}
+
+impl RawRecord {
+    pub fn new<S: AsRef<[u8]>>(strings: impl IntoIterator<Item = S>) -> Self {
+        let mut record = RawRecord::default();
+        for string in strings {
+            record.bytes.extend_from_slice(string.as_ref());
+            record.ends.push(record.bytes.len());
+        }
+        record
+    }
+
+    /// The character-strings concatenated, which is how the SDK reads a
+    /// record's value.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// The character-strings as they were on the wire.
+    pub fn strings(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        let starts = std::iter::once(0).chain(self.ends.iter().copied());
+        starts.zip(self.ends.iter().copied()).map(|(start, end)| &self.bytes[start..end])
+    }
+
+    /// The record as text, invalid UTF-8 sequences replaced with U+FFFD.
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.bytes)
+    }
+}
+
+/// Serialized as its character-strings in standard base64.
+#[cfg(feature = "serde")]
+impl serde::Serialize for RawRecord {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.strings().map(|string| data_encoding::BASE64.encode(string)))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for RawRecord {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let strings = Vec::<String>::deserialize(deserializer)?;
+        let strings = strings.iter().map(|string| data_encoding::BASE64.decode(string.as_bytes()));
+        strings.collect::<Result<Vec<_>, _>>().map(RawRecord::new).map_err(serde::de::Error::custom)
+    }
+}
+
+/// The records as text, see `RawRecord::to_string_lossy`.
+pub(crate) fn lossy(records: &[RawRecord]) -> Vec<String> {
+    records.iter().map(|record| record.to_string_lossy().into_owned()).collect()
+}
+
+/// The bytes of a value made of `records`, joined by spaces like the value.
+pub(crate) fn joined(records: &[RawRecord]) -> Vec<u8> {
+    match records {
+        [record] => record.bytes.clone(),
+        _ => records.iter().map(RawRecord::bytes).collect::<Vec<_>>().join(&b' '),
+    }
+}
+
+/// Escapes control characters in `text` as `\u{..}`, so that a value
+/// printed to a terminal cannot move the cursor, recolour or clear it.
+///
+/// ```
+/// use selfie_records_sdk::escape_controls;
+///
+/// assert_eq!(escape_controls("bitcoin:bc1q\u{1b}[2J"), "bitcoin:bc1q\\u{1b}[2J");
+/// assert_eq!(escape_controls("npub1example"), "npub1example");
+/// ```
+pub fn escape_controls(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(char::is_control) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        match c.is_control() {
+            true => escaped.extend(c.escape_unicode()),
+            false => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
@@ -0,0 +1,335 @@
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::name::NameError;
+
+/// Errors produced while resolving a selfie record.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SelfieError {
+    #[error("Invalid name {name:?}: {reason}")]
+    InvalidName { name: String, reason: NameError },
+    #[error("No TXT records found")]
+    NoRecords,
+    #[error("Timed out: {0}")]
+    Timeout(TimeoutBudget),
+    /// The server asked us to back off, e.g. with HTTP 429 or 503.
+    #[error("Rate limited by {server}{}", retry_after.map(|d| format!(" (retry after {}s)", d.as_secs())).unwrap_or_default())]
+    RateLimited { server: String, retry_after: Option<Duration> },
+    /// The chain of trust is broken: a signature is missing, does not
+    /// verify or was made by a key nothing vouches for.
+    #[error("DNSSEC validation failed: {0}")]
+    Dnssec(String),
+    /// Offline mode is on and neither an override nor a cached answer exists.
+    #[error("Offline: no cached answer")]
+    Offline,
+    /// The record exists but its value does not parse as the key's format.
+    #[error("Invalid {key} record: {reason}")]
+    InvalidRecord { key: String, reason: String },
+    #[error("Inline signature does not verify")]
+    InvalidSignature,
+    /// Strict cross-checking is on and the second resolver disagreed.
+    #[error("Answer differs from the cross-check resolver's {other_values:?}")]
+    CrossCheckMismatch { other_values: Vec<String> },
+    /// The name's CNAME chain leads back to a name already on it; `chain`
+    /// lists every owner name in order, ending with the repeated one.
+    #[error("CNAME loop: {}", chain.join(" -> "))]
+    CnameLoop { chain: Vec<String> },
+    /// The name's CNAME chain is longer than the configured limit.
+    #[error("CNAME chain too long: {}", chain.join(" -> "))]
+    CnameChainTooLong { chain: Vec<String> },
+    /// The call sent as many queries as `LookupOptions::max_queries` allows.
+    #[error("Query budget of {max_queries} exhausted")]
+    BudgetExceeded { max_queries: u32 },
+    #[error("{0}")]
+    Resolver(String),
+    /// A chunked record's manifest announces a chunk that has no record.
+    #[error("Chunk {index} of {count} is missing")]
+    MissingChunk { index: u32, count: u32 },
+    /// A chunked record's chunks do not hash to its manifest's digest.
+    #[error("Reassembled chunks do not match the manifest's sha256")]
+    ChunkDigestMismatch,
+    /// Neither the record nor the name it was looked up for exists, e.g.
+    /// a mistyped domain. `NoRecords` means the name exists.
+    #[error("Name does not exist")]
+    NxDomain,
+    /// The server passed to a call is not an address, a host name, a DoH
+    /// URL or a DoT server.
+    #[error("Invalid nameserver {server:?}")]
+    InvalidNameserver { server: String },
+    /// Fewer than `quorum` nameservers returned the same records; `answers`
+    /// says what each one returned, in the configured order.
+    #[error("Fewer than {quorum} nameservers agree: {}", answers.join("; "))]
+    Disagreement { quorum: u32, answers: Vec<String> },
+    /// A BIP-353 name has `count` `bitcoin:` records, which makes its
+    /// payment instruction ambiguous.
+    #[error("{count} bitcoin: records where BIP 353 allows one")]
+    MultipleRecords { count: u32 },
+    /// None of a BIP-353 name's payment records is a `bitcoin:` URI.
+    #[error("No bitcoin: URI among {values:?}")]
+    NotABitcoinUri { values: Vec<String> },
+    /// None of the keys fetched from a `pgp` key URL has one of the
+    /// fingerprints the name's `pgp` records give.
+    #[error("Fetched key {fetched:?} matches none of the fingerprints {expected:?}")]
+    FingerprintMismatch { expected: Vec<String>, fetched: Vec<String> },
+    /// A key passed to a call is not 1 to 61 letters, digits or hyphens,
+    /// see `RecordKey`.
+    #[error("Invalid record key {key:?}: keys are 1 to 61 letters, digits or hyphens")]
+    InvalidKey { key: String },
+    /// The host name passed to a call as its server has no addresses, or
+    /// they could not be looked up.
+    #[error("Could not resolve nameserver {host}: {reason}")]
+    NameserverResolutionFailed { host: String, reason: String },
+    /// The SOCKS5 proxy queries go through could not be connected to, or
+    /// refused the SDK; see `SdkBuilder::proxy`.
+    #[error("SOCKS5 proxy {proxy} unreachable: {reason}")]
+    ProxyUnreachable { proxy: String, reason: String },
+    /// The answer for `name` exceeds the SDK's `ResponseLimits`; see
+    /// `SdkBuilder::response_limits`.
+    #[error("Response for {name} is too large: {reason}")]
+    ResponseTooLarge { name: String, reason: String },
+    /// The DoH or DoT server's certificate did not validate for the name
+    /// it was reached by, e.g. it expired or no trusted root issued it.
+    #[error("Certificate of {server} rejected: {reason}")]
+    TlsCertificate { server: String, reason: String },
+    /// The records are in an unsigned zone, so DNSSEC cannot vouch for
+    /// them; unlike `Dnssec`, nothing is known to be forged.
+    #[error("Not signed with DNSSEC: {0}")]
+    DnssecInsecure(String),
+}
+
+impl SelfieError {
+    /// A stable identifier for the kind of error, such as `E_TIMEOUT`, for
+    /// consumers that cannot match on the enum. Codes are append-only: a
+    /// code is never renamed, renumbered or reused for another kind.
+    pub fn code(&self) -> &'static str {
+        self.codes().0
+    }
+
+    /// The numeric form of `code`, under the same append-only rule.
+    pub fn numeric_code(&self) -> u16 {
+        self.codes().1
+    }
+
+    fn codes(&self) -> (&'static str, u16) {
+        match self {
+            SelfieError::InvalidName { .. } => ("E_INVALID_NAME", 1),
+            SelfieError::NoRecords => ("E_NO_RECORDS", 2),
+            SelfieError::Timeout(_) => ("E_TIMEOUT", 3),
+            SelfieError::RateLimited { .. } => ("E_RATE_LIMITED", 4),
+            SelfieError::Dnssec(_) => ("E_DNSSEC_BOGUS", 5),
+            SelfieError::Offline => ("E_OFFLINE", 6),
+            SelfieError::InvalidRecord { .. } => ("E_INVALID_RECORD", 7),
+            SelfieError::InvalidSignature => ("E_INVALID_SIGNATURE", 8),
+            SelfieError::CrossCheckMismatch { .. } => ("E_CROSS_CHECK_MISMATCH", 9),
+            SelfieError::CnameLoop { .. } => ("E_CNAME_LOOP", 10),
+            SelfieError::CnameChainTooLong { .. } => ("E_CNAME_CHAIN_TOO_LONG", 11),
+            SelfieError::Resolver(_) => ("E_RESOLVER", 12),
+            SelfieError::BudgetExceeded { .. } => ("E_BUDGET_EXCEEDED", 13),
+            SelfieError::MissingChunk { .. } => ("E_MISSING_CHUNK", 14),
+            SelfieError::ChunkDigestMismatch => ("E_CHUNK_DIGEST_MISMATCH", 15),
+            SelfieError::NxDomain => ("E_NXDOMAIN", 16),
+            SelfieError::InvalidNameserver { .. } => ("E_INVALID_NAMESERVER", 17),
+            SelfieError::Disagreement { .. } => ("E_DISAGREEMENT", 18),
+            SelfieError::MultipleRecords { .. } => ("E_MULTIPLE_RECORDS", 19),
+            SelfieError::NotABitcoinUri { .. } => ("E_NOT_A_BITCOIN_URI", 20),
+            SelfieError::FingerprintMismatch { .. } => ("E_FINGERPRINT_MISMATCH", 21),
+            SelfieError::InvalidKey { .. } => ("E_INVALID_KEY", 22),
+            SelfieError::NameserverResolutionFailed { .. } => ("E_NAMESERVER_RESOLUTION_FAILED", 23),
+            SelfieError::ProxyUnreachable { .. } => ("E_PROXY_UNREACHABLE", 24),
+            SelfieError::ResponseTooLarge { .. } => ("E_RESPONSE_TOO_LARGE", 25),
+            SelfieError::TlsCertificate { .. } => ("E_TLS_CERTIFICATE", 26),
+            SelfieError::DnssecInsecure(_) => ("E_DNSSEC_INSECURE", 27),
+        }
+    }
+
+    /// Whether another attempt could plausibly succeed.
+    pub(crate) fn is_retryable(&self) -> bool {
+        matches!(self, SelfieError::Timeout(_) | SelfieError::RateLimited { .. } | SelfieError::Resolver(_) | SelfieError::ProxyUnreachable { .. })
+    }
+
+    /// Whether the server could not be reached or did not answer in time,
+    /// as opposed to answering, even with an error.
+    pub(crate) fn is_unreachable(&self) -> bool {
+        matches!(self, SelfieError::Timeout(_) | SelfieError::Resolver(_) | SelfieError::ProxyUnreachable { .. })
+    }
+}
+
+/// The time budget a lookup ran out of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutBudget {
+    /// The global per-attempt timeout, exceeded on every attempt.
+    Global { timeout: Duration, attempts: u32 },
+    /// A per-key budget covering all attempts for that key.
+    Key { budget: Duration, attempts: u32 },
+    /// The deadline of the whole call, see `LookupOptions::deadline`.
+    Call { deadline: Duration, attempts: u32 },
+}
+
+impl std::fmt::Display for TimeoutBudget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeoutBudget::Global { timeout, attempts } => write!(
+                f,
+                "global timeout of {}ms exceeded after {} attempt(s)",
+                timeout.as_millis(),
+                attempts
+            ),
+            TimeoutBudget::Key { budget, attempts } => write!(
+                f,
+                "per-key budget of {}ms exceeded after {} attempt(s)",
+                budget.as_millis(),
+                attempts
+            ),
+            TimeoutBudget::Call { deadline, attempts } => write!(
+                f,
+                "call deadline of {}ms exceeded after {} attempt(s)",
+                deadline.as_millis(),
+                attempts
+            ),
+        }
+    }
+}
+
+/// Serialized as `{ "code", "numeric_code", "message" }`, plus `fields`
+/// for kinds that have any: the variant's fields by name, durations as
+/// `*_ns` nanoseconds, a `Timeout`'s budget as `budget` and the text of
+/// `Dnssec`, `DnssecInsecure` and `Resolver` as `message`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for SelfieError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let fields = self.json_fields();
+        let mut state = serializer.serialize_struct("SelfieError", 3 + usize::from(fields.is_some()))?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("numeric_code", &self.numeric_code())?;
+        state.serialize_field("message", &self.to_string())?;
+        if let Some(fields) = fields {
+            state.serialize_field("fields", &fields)?;
+        }
+        state.end()
+    }
+}
+
+/// Read back by `code` and `fields`. JSON without usable `fields`, as
+/// written before they were added, comes back as `Resolver` with its
+/// message, as does an unknown code.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SelfieError {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        struct Json {
+            code: String,
+            message: String,
+            #[serde(default)]
+            fields: serde_json::Map<String, serde_json::Value>,
+        }
+
+        let json = Json::deserialize(deserializer)?;
+        Ok(SelfieError::from_json(&json.code, &json.fields).unwrap_or(SelfieError::Resolver(json.message)))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl SelfieError {
+    fn json_fields(&self) -> Option<serde_json::Value> {
+        use serde_json::json;
+
+        let nanos = |duration: &Duration| u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        Some(match self {
+            SelfieError::InvalidName { name, reason } => json!({ "name": name, "reason": reason }),
+            SelfieError::NoRecords
+            | SelfieError::Offline
+            | SelfieError::InvalidSignature
+            | SelfieError::ChunkDigestMismatch
+            | SelfieError::NxDomain => return None,
+            SelfieError::Timeout(budget) => {
+                let (kind, duration, attempts) = match budget {
+                    TimeoutBudget::Global { timeout, attempts } => ("global", timeout, attempts),
+                    TimeoutBudget::Key { budget, attempts } => ("key", budget, attempts),
+                    TimeoutBudget::Call { deadline, attempts } => ("call", deadline, attempts),
+                };
+                json!({ "budget": kind, "duration_ns": nanos(duration), "attempts": attempts })
+            }
+            SelfieError::RateLimited { server, retry_after } => {
+                json!({ "server": server, "retry_after_ns": retry_after.as_ref().map(nanos) })
+            }
+            SelfieError::Dnssec(message) | SelfieError::DnssecInsecure(message) | SelfieError::Resolver(message) => {
+                json!({ "message": message })
+            }
+            SelfieError::InvalidRecord { key, reason } => json!({ "key": key, "reason": reason }),
+            SelfieError::CrossCheckMismatch { other_values } => json!({ "other_values": other_values }),
+            SelfieError::CnameLoop { chain } | SelfieError::CnameChainTooLong { chain } => json!({ "chain": chain }),
+            SelfieError::BudgetExceeded { max_queries } => json!({ "max_queries": max_queries }),
+            SelfieError::MissingChunk { index, count } => json!({ "index": index, "count": count }),
+            SelfieError::InvalidNameserver { server } => json!({ "server": server }),
+            SelfieError::Disagreement { quorum, answers } => json!({ "quorum": quorum, "answers": answers }),
+            SelfieError::MultipleRecords { count } => json!({ "count": count }),
+            SelfieError::NotABitcoinUri { values } => json!({ "values": values }),
+            SelfieError::FingerprintMismatch { expected, fetched } => json!({ "expected": expected, "fetched": fetched }),
+            SelfieError::InvalidKey { key } => json!({ "key": key }),
+            SelfieError::NameserverResolutionFailed { host, reason } => json!({ "host": host, "reason": reason }),
+            SelfieError::ProxyUnreachable { proxy, reason } => json!({ "proxy": proxy, "reason": reason }),
+            SelfieError::ResponseTooLarge { name, reason } => json!({ "name": name, "reason": reason }),
+            SelfieError::TlsCertificate { server, reason } => json!({ "server": server, "reason": reason }),
+        })
+    }
+
+    /// The kind `code` names, from the `fields` `json_fields` wrote.
+    fn from_json(code: &str, fields: &serde_json::Map<String, serde_json::Value>) -> Option<Self> {
+        let text = |key: &str| -> Option<String> { Some(fields.get(key)?.as_str()?.to_string()) };
+        let texts = |key: &str| -> Option<Vec<String>> {
+            fields.get(key)?.as_array()?.iter().map(|value| value.as_str().map(str::to_string)).collect()
+        };
+        let number = |key: &str| -> Option<u32> { u32::try_from(fields.get(key)?.as_u64()?).ok() };
+        let nanos = |key: &str| -> Option<Duration> { Some(Duration::from_nanos(fields.get(key)?.as_u64()?)) };
+
+        Some(match code {
+            "E_INVALID_NAME" => {
+                SelfieError::InvalidName { name: text("name")?, reason: serde_json::from_value(fields.get("reason")?.clone()).ok()? }
+            }
+            "E_NO_RECORDS" => SelfieError::NoRecords,
+            "E_TIMEOUT" => {
+                let (duration, attempts) = (nanos("duration_ns")?, number("attempts")?);
+                SelfieError::Timeout(match text("budget")?.as_str() {
+                    "global" => TimeoutBudget::Global { timeout: duration, attempts },
+                    "key" => TimeoutBudget::Key { budget: duration, attempts },
+                    "call" => TimeoutBudget::Call { deadline: duration, attempts },
+                    _ => return None,
+                })
+            }
+            "E_RATE_LIMITED" => SelfieError::RateLimited {
+                server: text("server")?,
+                retry_after: match fields.get("retry_after_ns")? {
+                    serde_json::Value::Null => None,
+                    _ => Some(nanos("retry_after_ns")?),
+                },
+            },
+            "E_DNSSEC_BOGUS" => SelfieError::Dnssec(text("message")?),
+            "E_OFFLINE" => SelfieError::Offline,
+            "E_INVALID_RECORD" => SelfieError::InvalidRecord { key: text("key")?, reason: text("reason")? },
+            "E_INVALID_SIGNATURE" => SelfieError::InvalidSignature,
+            "E_CROSS_CHECK_MISMATCH" => SelfieError::CrossCheckMismatch { other_values: texts("other_values")? },
+            "E_CNAME_LOOP" => SelfieError::CnameLoop { chain: texts("chain")? },
+            "E_CNAME_CHAIN_TOO_LONG" => SelfieError::CnameChainTooLong { chain: texts("chain")? },
+            "E_RESOLVER" => SelfieError::Resolver(text("message")?),
+            "E_BUDGET_EXCEEDED" => SelfieError::BudgetExceeded { max_queries: number("max_queries")? },
+            "E_MISSING_CHUNK" => SelfieError::MissingChunk { index: number("index")?, count: number("count")? },
+            "E_CHUNK_DIGEST_MISMATCH" => SelfieError::ChunkDigestMismatch,
+            "E_NXDOMAIN" => SelfieError::NxDomain,
+            "E_INVALID_NAMESERVER" => SelfieError::InvalidNameserver { server: text("server")? },
+            "E_DISAGREEMENT" => SelfieError::Disagreement { quorum: number("quorum")?, answers: texts("answers")? },
+            "E_MULTIPLE_RECORDS" => SelfieError::MultipleRecords { count: number("count")? },
+            "E_NOT_A_BITCOIN_URI" => SelfieError::NotABitcoinUri { values: texts("values")? },
+            "E_FINGERPRINT_MISMATCH" => SelfieError::FingerprintMismatch { expected: texts("expected")?, fetched: texts("fetched")? },
+            "E_INVALID_KEY" => SelfieError::InvalidKey { key: text("key")? },
+            "E_NAMESERVER_RESOLUTION_FAILED" => SelfieError::NameserverResolutionFailed { host: text("host")?, reason: text("reason")? },
+            "E_PROXY_UNREACHABLE" => SelfieError::ProxyUnreachable { proxy: text("proxy")?, reason: text("reason")? },
+            "E_RESPONSE_TOO_LARGE" => SelfieError::ResponseTooLarge { name: text("name")?, reason: text("reason")? },
+            "E_TLS_CERTIFICATE" => SelfieError::TlsCertificate { server: text("server")?, reason: text("reason")? },
+            "E_DNSSEC_INSECURE" => SelfieError::DnssecInsecure(text("message")?),
+            _ => return None,
+        })
+    }
+}
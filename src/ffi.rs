@@ -0,0 +1,179 @@
+//! A C interface for embedding the SDK, e.g. in a mobile wallet. Results
+//! cross the boundary as the serde JSON of `RecordsResponse`; the
+//! declarations are in `include/selfie_records.h`, which build.rs
+//! generates from this module with cbindgen.
+//!
+//! Every function returns one of the `SELFIE_*` codes or, for a lookup
+//! with a failed key, that key's `SelfieError::numeric_code`. Panics are
+//! caught and returned as `SELFIE_E_PANIC`. Freeing a handle or string
+//! again, or passing one that was never returned, fails with
+//! `SELFIE_E_UNKNOWN_HANDLE`. Handles are opaque ids that are never
+//! reused, so such a call touches nothing. Strings are their addresses,
+//! which a later string can take once one is freed; freeing the old
+//! pointer again then frees the new string, so for strings the check is
+//! best-effort.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use log::error;
+
+use crate::builder::SdkBuilder;
+use crate::config::Config;
+use crate::response::RecordEntry;
+use crate::SelfieRecordsSDK;
+
+pub const SELFIE_OK: c_int = 0;
+pub const SELFIE_E_NULL_POINTER: c_int = -1;
+pub const SELFIE_E_INVALID_UTF8: c_int = -2;
+/// A handle or string that was already freed or never returned.
+pub const SELFIE_E_UNKNOWN_HANDLE: c_int = -3;
+pub const SELFIE_E_PANIC: c_int = -4;
+
+/// The live SDKs by handle, so that a lookup racing `selfie_sdk_free`
+/// keeps its SDK alive until it returns. Handles count up from 1, so null
+/// is never one.
+static SDKS: Mutex<Handles> = Mutex::new(Handles { next: 1, live: BTreeMap::new() });
+static STRINGS: Mutex<BTreeSet<usize>> = Mutex::new(BTreeSet::new());
+
+struct Handles {
+    next: usize,
+    live: BTreeMap<usize, Arc<SelfieRecordsSDK>>,
+}
+
+/// Builds an SDK from `config_json`, an object with the keys of a config
+/// file, e.g. `{"nameservers": ["9.9.9.9"], "cache": {"ttl": 300}}`, or
+/// the defaults when it is null. Returns its handle, an id rather than
+/// an address, or null when the config is invalid.
+///
+/// # Safety
+///
+/// `config_json` is null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn selfie_sdk_new(config_json: *const c_char) -> *mut SelfieRecordsSDK {
+    let built = catch_unwind(AssertUnwindSafe(|| {
+        let config = match config_json.is_null() {
+            true => Config::default(),
+            false => {
+                let text = CStr::from_ptr(config_json).to_str().map_err(|e| e.to_string())?;
+                Config::from_json(text).map_err(|e| e.to_string())?
+            }
+        };
+        let mut builder = SdkBuilder::from_config(config.clone());
+        builder.lookup_options = config.lookup_options();
+        builder.default_keys = config.keys.clone();
+        builder.build().map_err(|e| e.to_string())
+    }));
+    match built {
+        Ok(Ok(sdk)) => {
+            let mut sdks = SDKS.lock().unwrap_or_else(PoisonError::into_inner);
+            let handle = sdks.next;
+            sdks.next += 1;
+            sdks.live.insert(handle, Arc::new(sdk));
+            handle as *mut SelfieRecordsSDK
+        }
+        Ok(Err(e)) => {
+            error!("Error creating SDK: {}", e);
+            std::ptr::null_mut()
+        }
+        Err(_) => {
+            error!("Panic creating SDK");
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Looks up `keys_csv`, comma-separated keys, or the default keys when it
+/// is null or empty, of `name`, and points `out_json` at the result, to be
+/// freed with `selfie_string_free`. `out_json` is set whenever
+/// the lookup ran, even when a key failed.
+///
+/// # Safety
+///
+/// `name` and `keys_csv` are null or NUL-terminated strings, and
+/// `out_json` is null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn selfie_sdk_get_records(
+    sdk: *const SelfieRecordsSDK,
+    name: *const c_char,
+    keys_csv: *const c_char,
+    out_json: *mut *mut c_char,
+) -> c_int {
+    catch_unwind(AssertUnwindSafe(|| {
+        if sdk.is_null() || name.is_null() || out_json.is_null() {
+            return SELFIE_E_NULL_POINTER;
+        }
+        *out_json = std::ptr::null_mut();
+        let Some(sdk) = SDKS.lock().unwrap_or_else(PoisonError::into_inner).live.get(&(sdk as usize)).cloned() else {
+            return SELFIE_E_UNKNOWN_HANDLE;
+        };
+        let Ok(name) = CStr::from_ptr(name).to_str() else {
+            return SELFIE_E_INVALID_UTF8;
+        };
+        let keys = match keys_csv.is_null() {
+            true => "",
+            false => match CStr::from_ptr(keys_csv).to_str() {
+                Ok(keys) => keys,
+                Err(_) => return SELFIE_E_INVALID_UTF8,
+            },
+        };
+        let keys: Option<Vec<&str>> = (!keys.trim().is_empty()).then(|| keys.split(',').map(str::trim).collect());
+        let order = keys.clone().unwrap_or_else(|| sdk.default_keys());
+
+        let response = sdk.get_records_response(name, keys, None, &sdk.defaults);
+        let json = serde_json::to_string(&response).expect("responses serialize");
+        let json = CString::new(json).expect("JSON escapes NUL");
+        let pointer = json.into_raw();
+        STRINGS.lock().unwrap_or_else(PoisonError::into_inner).insert(pointer as usize);
+        *out_json = pointer;
+
+        // The first failed key in the order the keys were asked for
+        // decides the code.
+        let failed = order.iter().filter_map(|key| response.get(key)).find_map(|result| match result.entry() {
+            RecordEntry::Error(e) => Some(e.numeric_code()),
+            _ => None,
+        });
+        failed.map_or(SELFIE_OK, c_int::from)
+    }))
+    .unwrap_or(SELFIE_E_PANIC)
+}
+
+/// Frees an SDK from `selfie_sdk_new`. Null is ignored.
+#[no_mangle]
+pub extern "C" fn selfie_sdk_free(sdk: *mut SelfieRecordsSDK) -> c_int {
+    if sdk.is_null() {
+        return SELFIE_OK;
+    }
+    catch_unwind(AssertUnwindSafe(|| {
+        let removed = SDKS.lock().unwrap_or_else(PoisonError::into_inner).live.remove(&(sdk as usize));
+        match removed {
+            Some(_) => SELFIE_OK,
+            None => SELFIE_E_UNKNOWN_HANDLE,
+        }
+    }))
+    .unwrap_or(SELFIE_E_PANIC)
+}
+
+/// Frees a string the SDK returned. Null is ignored. Safe to call with any
+/// pointer, since only ones the SDK returned and did not free yet are
+/// dereferenced.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn selfie_string_free(string: *mut c_char) -> c_int {
+    if string.is_null() {
+        return SELFIE_OK;
+    }
+    catch_unwind(AssertUnwindSafe(|| {
+        let removed = STRINGS.lock().unwrap_or_else(PoisonError::into_inner).remove(&(string as usize));
+        match removed {
+            true => {
+                drop(unsafe { CString::from_raw(string) });
+                SELFIE_OK
+            }
+            false => SELFIE_E_UNKNOWN_HANDLE,
+        }
+    }))
+    .unwrap_or(SELFIE_E_PANIC)
+}
@@ -0,0 +1,322 @@
+//! A health report on a name's published records: whether each one is
+//! found and well-formed, how it is served, and whether public resolvers
+//! see it too. Behind `selfie check`.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use trust_dns_proto::rr::{Name, RData, RecordType};
+
+use crate::encoding;
+use crate::error::SelfieError;
+use crate::options::LookupOptions;
+use crate::records::{Bip21Uri, Did, Lnurl, NodeUri, NostrKey, PaymentCode, PgpRecord};
+use crate::resolver::TxtResolver;
+use crate::response::{KeyResult, Source};
+use crate::wire::{self, DirectResolver, Transports};
+use crate::SelfieRecordsSDK;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+/// TTLs outside this range are reported: shorter ones defeat caching and
+/// longer ones keep a changed record stale for more than a day.
+const MIN_TTL: Duration = Duration::from_secs(60);
+const MAX_TTL: Duration = Duration::from_secs(86400);
+
+/// Outcome of one check, ordered from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Pass => f.write_str("PASS"),
+            Status::Warn => f.write_str("WARN"),
+            Status::Fail => f.write_str("FAIL"),
+        }
+    }
+}
+
+/// One check and what it found, e.g. `ttl`, `WARN`, `30s is short`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub check: &'static str,
+    pub status: Status,
+    pub detail: String,
+}
+
+impl Finding {
+    fn new(check: &'static str, status: Status, detail: impl Into<String>) -> Self {
+        Finding { check, status, detail: detail.into() }
+    }
+}
+
+/// The checks run on one record key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyHealth {
+    pub key: String,
+    /// The owner name that was queried; `None` for overrides and invalid
+    /// names.
+    pub qname: Option<String>,
+    pub value: Option<String>,
+    pub findings: Vec<Finding>,
+}
+
+impl KeyHealth {
+    /// The worst status of the findings.
+    pub fn status(&self) -> Status {
+        worst(&self.findings)
+    }
+}
+
+/// What `HealthCheck::run` found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthReport {
+    pub name: String,
+    /// In the order the keys were given.
+    pub keys: Vec<KeyHealth>,
+    /// Findings about the name as a whole.
+    pub findings: Vec<Finding>,
+}
+
+impl HealthReport {
+    /// The worst status of every finding: `Fail` when something is broken,
+    /// `Warn` when something should be looked at.
+    pub fn status(&self) -> Status {
+        self.keys.iter().map(KeyHealth::status).fold(worst(&self.findings), Status::max)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let findings = |findings: &[Finding]| -> Vec<serde_json::Value> {
+            findings
+                .iter()
+                .map(|finding| {
+                    serde_json::json!({
+                        "check": finding.check,
+                        "status": finding.status.to_string(),
+                        "detail": finding.detail,
+                    })
+                })
+                .collect()
+        };
+        let keys: Vec<serde_json::Value> = self
+            .keys
+            .iter()
+            .map(|key| {
+                serde_json::json!({
+                    "key": key.key,
+                    "qname": key.qname,
+                    "value": key.value,
+                    "status": key.status().to_string(),
+                    "findings": findings(&key.findings),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "name": self.name,
+            "status": self.status().to_string(),
+            "findings": findings(&self.findings),
+            "keys": keys,
+        })
+    }
+}
+
+fn worst(findings: &[Finding]) -> Status {
+    findings.iter().map(|finding| finding.status).max().unwrap_or(Status::Pass)
+}
+
+/// Looks up a name's records through the SDK and checks each answer: that
+/// it parses, its DNSSEC status and TTL, conflicting records, CNAME
+/// delegation, and that the propagation servers give the same values.
+#[derive(Debug, Clone)]
+pub struct HealthCheck {
+    name: String,
+    keys: Option<Vec<String>>,
+    propagation_servers: Vec<SocketAddr>,
+    timeout: Duration,
+    options: LookupOptions,
+}
+
+impl HealthCheck {
+    pub fn new(name: &str) -> Self {
+        HealthCheck {
+            name: name.to_string(),
+            keys: None,
+            propagation_servers: vec![([1, 1, 1, 1], 53).into(), ([8, 8, 8, 8], 53).into()],
+            timeout: DEFAULT_TIMEOUT,
+            options: LookupOptions::default(),
+        }
+    }
+
+    /// Record keys to check instead of the default ones.
+    pub fn keys(mut self, keys: &[&str]) -> Self {
+        self.keys = Some(keys.iter().map(|key| key.to_string()).collect());
+        self
+    }
+
+    /// Recursive resolvers asked directly for each record found, the first
+    /// one also for CNAMEs. Defaults to 1.1.1.1 and 8.8.8.8.
+    pub fn propagation_servers(mut self, servers: Vec<SocketAddr>) -> Self {
+        self.propagation_servers = servers;
+        self
+    }
+
+    /// Timeout for each query to a propagation server. Defaults to five
+    /// seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Options for the lookups through the SDK.
+    pub fn lookup_options(mut self, options: LookupOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn run(&self, sdk: &SelfieRecordsSDK) -> HealthReport {
+        let keys: Vec<&str> = match &self.keys {
+            Some(keys) => keys.iter().map(String::as_str).collect(),
+            None => sdk.default_keys(),
+        };
+        let response = sdk.get_records_response(&self.name, Some(keys.clone()), None, &self.options);
+        #[cfg(feature = "dnssec")]
+        let dnssec_required = sdk.validation.required;
+        #[cfg(not(feature = "dnssec"))]
+        let dnssec_required = false;
+
+        let mut report = HealthReport { name: self.name.clone(), keys: Vec::new(), findings: Vec::new() };
+        for key in keys {
+            let Some(result) = response.get(key) else {
+                continue;
+            };
+            let qname = match result.source {
+                Some(Source::Override) => None,
+                _ => sdk.versioned_query_name(&self.name, key, result.record_version),
+            };
+            let mut health = KeyHealth { key: key.to_string(), qname, value: result.value.clone(), findings: Vec::new() };
+            if check_found(&mut health, result) {
+                check_value(&mut health, result, dnssec_required);
+                if let Some(qname) = health.qname.clone() {
+                    let findings = sdk.runtime.block_on(self.check_dns(&qname, result));
+                    health.findings.extend(findings);
+                }
+            }
+            report.keys.push(health);
+        }
+        if report.keys.iter().all(|key| key.value.is_none()) {
+            report.findings.push(Finding::new("records", Status::Fail, "no records found"));
+        }
+        report
+    }
+
+    /// The CNAME and propagation findings for a record found at `qname`.
+    async fn check_dns(&self, qname: &str, result: &KeyResult) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        if let Some(&server) = self.propagation_servers.first() {
+            if let Some(target) = cname_target(server, qname, self.timeout).await {
+                findings.push(Finding::new("cname", Status::Pass, format!("delegated to {}", target)));
+            }
+        }
+
+        let mut expected = encoding::lossy(&result.raw_records);
+        expected.sort();
+        for &server in &self.propagation_servers {
+            let resolver = DirectResolver::new(server).timeout(self.timeout);
+            let finding = match resolver.txt_lookup_raw(qname).await {
+                Ok((records, _)) if records.is_empty() => Finding::new("propagation", Status::Warn, format!("{} has no record", server)),
+                Ok((records, _)) => {
+                    let mut values = encoding::lossy(&records);
+                    values.sort();
+                    match values == expected {
+                        true => Finding::new("propagation", Status::Pass, format!("{} agrees", server)),
+                        false => Finding::new("propagation", Status::Warn, format!("{} answers {}", server, values.join(" | "))),
+                    }
+                }
+                Err(e) => Finding::new("propagation", Status::Warn, format!("{} failed: {}", server, e)),
+            };
+            findings.push(finding);
+        }
+        findings
+    }
+}
+
+/// Adds the finding on whether the key was found; `false` when there is
+/// nothing further to check.
+fn check_found(health: &mut KeyHealth, result: &KeyResult) -> bool {
+    let finding = match (&result.value, &result.error) {
+        (Some(_), _) => Finding::new("found", Status::Pass, "found"),
+        (None, Some(SelfieError::NoRecords) | None) => Finding::new("found", Status::Pass, "not published"),
+        (None, Some(e)) => Finding::new("found", Status::Fail, e.to_string()),
+    };
+    health.findings.push(finding);
+    result.value.is_some()
+}
+
+fn check_value(health: &mut KeyHealth, result: &KeyResult, dnssec_required: bool) {
+    let Some(value) = &result.value else {
+        return;
+    };
+    if let Some(parsed) = parse(&health.key, value) {
+        health.findings.push(match parsed {
+            Ok(()) => Finding::new("value", Status::Pass, "valid"),
+            Err(e) => Finding::new("value", Status::Fail, e.to_string()),
+        });
+    }
+    if let Some(issue) = &result.encoding_issue {
+        health.findings.push(Finding::new("encoding", Status::Warn, issue.to_string()));
+    }
+    if result.source == Some(Source::Override) {
+        health.findings.push(Finding::new("source", Status::Warn, "set by a local override, not looked up"));
+        return;
+    }
+
+    health.findings.push(match dnssec_required {
+        true => Finding::new("dnssec", Status::Pass, "validated"),
+        false => Finding::new("dnssec", Status::Warn, "not validated"),
+    });
+    if let Some(ttl) = result.ttl {
+        health.findings.push(match ttl {
+            ttl if ttl < MIN_TTL => Finding::new("ttl", Status::Warn, format!("{}s is short", ttl.as_secs())),
+            ttl if ttl > MAX_TTL => Finding::new("ttl", Status::Warn, format!("{}s is long", ttl.as_secs())),
+            ttl => Finding::new("ttl", Status::Pass, format!("{}s", ttl.as_secs())),
+        });
+    }
+    let records = result.raw_records.len();
+    if records > 1 {
+        // BIP-353 allows a single payment instruction per name.
+        let status = if health.key == "bitcoin-payment" { Status::Fail } else { Status::Warn };
+        health.findings.push(Finding::new("records", status, format!("{} records, joined into one value", records)));
+    }
+}
+
+/// Parses `value` as the record type of `key`; `None` for keys without one.
+fn parse(key: &str, value: &str) -> Option<Result<(), SelfieError>> {
+    let parsed = match key {
+        "bitcoin-payment" => Bip21Uri::from_str(value).map(drop),
+        "pgp" => PgpRecord::from_str(value).map(drop),
+        "nostr" => NostrKey::from_str(value).map(drop),
+        "node-uri" => NodeUri::from_str(value).map(drop),
+        "bip47" => PaymentCode::from_str(value).map(drop),
+        "did" => Did::from_str(value).map(drop),
+        "lnurl" | "lightning-address" => Lnurl::from_str(value).map(drop),
+        _ => return None,
+    };
+    Some(parsed)
+}
+
+/// The name `qname` is an alias of, if any.
+async fn cname_target(server: SocketAddr, qname: &str, timeout: Duration) -> Option<String> {
+    let mut name = Name::from_utf8(qname).ok()?;
+    name.set_fqdn(true);
+    let response = wire::query(&Transports::direct(server), &name, RecordType::CNAME, false, timeout).await.ok()?;
+    response.answers().iter().find_map(|record| match record.rdata() {
+        RData::CNAME(target) if record.name() == &name => Some(target.to_string().trim_end_matches('.').to_string()),
+        _ => None,
+    })
+}
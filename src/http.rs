@@ -0,0 +1,751 @@
+//! A minimal HTTP/1.1 client for the DoH transport, webhooks and provider
+//! APIs: one request per connection, or keep-alive connections from a
+//! `Pool`, where `https://` endpoints offering HTTP/2 get one connection
+//! carrying the requests at once instead. `https://` URLs need the `tls`
+//! feature. On wasm32 requests go through `fetch` instead, which keeps
+//! connections of its own.
+
+use std::fmt;
+use std::io;
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::pin::Pin;
+#[cfg(feature = "tls")]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+use std::sync::{Mutex, PoisonError};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+#[cfg(feature = "tls")]
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::runtime::{self, Handle};
+use tokio::sync::Semaphore;
+
+use crate::net::TcpStream;
+use crate::proxy::Socks5Config;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::proxy::{self, Target};
+use crate::time::Instant;
+#[cfg(feature = "tls")]
+use crate::tls::TlsConfig;
+use crate::transport::TransportError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Endpoint {
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    pub(crate) path: String,
+    /// Connections go through this proxy, which resolves `host`.
+    pub(crate) proxy: Option<Socks5Config>,
+    pub(crate) https: bool,
+    /// Set for `https://` endpoints.
+    #[cfg(feature = "tls")]
+    pub(crate) tls: Option<TlsConfig>,
+}
+
+impl Endpoint {
+    /// Parses an `http://host[:port][/path]` or `https://` URL, using
+    /// `default_path` when it has none. `https://` ones check the server's
+    /// certificate against Mozilla's roots.
+    pub(crate) fn parse(url: &str, default_path: &str) -> Result<Endpoint, String> {
+        let (rest, https) = match (url.strip_prefix("http://"), url.strip_prefix("https://")) {
+            (Some(rest), _) => (rest, false),
+            (_, Some(rest)) if cfg!(any(feature = "tls", target_arch = "wasm32")) => (rest, true),
+            (_, Some(_)) => return Err("https needs the tls feature".to_string()),
+            _ => return Err("expected an http:// or https:// URL".to_string()),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => rest.split_at(slash),
+            None => (rest, default_path),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (host, port.parse().map_err(|_| "invalid port")?),
+            _ => (authority, if https { 443 } else { 80 }),
+        };
+        if host.is_empty() {
+            return Err("missing host".to_string());
+        }
+        Ok(Endpoint {
+            host: host.trim_start_matches('[').trim_end_matches(']').to_string(),
+            port,
+            path: path.to_string(),
+            proxy: None,
+            https,
+            #[cfg(feature = "tls")]
+            tls: https.then(TlsConfig::new),
+        })
+    }
+
+    fn is_https(&self) -> bool {
+        self.https
+    }
+
+    /// The `Host` header value: the host, bracketed if IPv6, and the port
+    /// unless it is the scheme's default.
+    fn authority(&self) -> String {
+        let host = match self.host.contains(':') {
+            true => format!("[{}]", self.host),
+            false => self.host.clone(),
+        };
+        match (self.port, self.is_https()) {
+            (80, false) | (443, true) => host,
+            (port, _) => format!("{}:{}", host, port),
+        }
+    }
+
+    /// POSTs `body` with the given extra headers and reads the whole response.
+    #[cfg(feature = "webhook")]
+    pub(crate) async fn post(&self, content_type: &str, headers: &[(&str, &str)], body: &[u8]) -> Result<Response, Error> {
+        self.request("POST", &self.path, headers, Some((content_type, body))).await
+    }
+
+    /// Sends `method` for `path`, which replaces the endpoint's own path.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn request(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<(&str, &[u8])>,
+    ) -> Result<Response, Error> {
+        let mut stream = self.connect().await?;
+        send(&mut stream, &self.encode(method, path, headers, body, false)).await?;
+
+        let mut raw = Vec::new();
+        stream.read_to_end(&mut raw).await.map_err(Error::io)?;
+        Response::parse(&raw, true).ok_or(Error::Failed("malformed HTTP response".to_string()))
+    }
+
+    /// Like `request`, over a connection from `pool`, which gets it back
+    /// unless the server closes it. An HTTP/2 connection is shared instead:
+    /// requests over it need no turn of their own. A request that fails at
+    /// the connection level, e.g. because the server closed an idle
+    /// connection or dropped one mid-response, is sent once more on a new
+    /// connection.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn pooled(
+        &self,
+        pool: &Pool,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<(&str, &[u8])>,
+    ) -> Result<Response, Error> {
+        let mut permit = None;
+        let mut stream = match pool.share() {
+            Some(stream) => stream,
+            None => {
+                permit = Some(pool.permits.acquire().await.map_err(|_| Error::Failed("connection pool closed".to_string()))?);
+                match pool.take() {
+                    Some(stream) => stream,
+                    None => self.open(pool).await?,
+                }
+            }
+        };
+        if stream.is_multiplexed() {
+            permit = None;
+        }
+        let (response, reusable) = match self.send_pooled(&mut stream, method, path, headers, body).await {
+            Ok(exchanged) => exchanged,
+            Err(_) => {
+                pool.retrying();
+                if permit.is_none() {
+                    permit = Some(pool.permits.acquire().await.map_err(|_| Error::Failed("connection pool closed".to_string()))?);
+                }
+                stream = self.open(pool).await?;
+                if stream.is_multiplexed() {
+                    drop(permit.take());
+                }
+                self.send_pooled(&mut stream, method, path, headers, body).await?
+            }
+        };
+        if reusable {
+            pool.put(stream);
+        }
+        Ok(response)
+    }
+
+    /// Opens a connection into `pool` unless it has an idle one for this
+    /// runtime, telling whether one was opened.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn preconnect(&self, pool: &Pool) -> Result<bool, Error> {
+        let _permit = pool.permits.acquire().await.map_err(|_| Error::Failed("connection pool closed".to_string()))?;
+        if pool.has_idle() {
+            return Ok(false);
+        }
+        let stream = self.open(pool).await?;
+        if !stream.is_multiplexed() {
+            pool.put(stream);
+        }
+        Ok(true)
+    }
+
+    /// GETs `path`, which replaces the endpoint's own path, failing once
+    /// the response grows past `limit` bytes, headers included.
+    #[cfg(all(feature = "http", not(target_arch = "wasm32")))]
+    pub(crate) async fn get_limited(&self, path: &str, limit: usize) -> Result<Response, Error> {
+        let mut stream = self.connect().await?;
+        send(&mut stream, &self.encode("GET", path, &[], None, false)).await?;
+
+        let mut raw = Vec::new();
+        (&mut stream).take(limit as u64 + 1).read_to_end(&mut raw).await.map_err(Error::io)?;
+        if raw.len() > limit {
+            return Err(Error::Failed(format!("response is larger than {} bytes", limit)));
+        }
+        Response::parse(&raw, true).ok_or(Error::Failed("malformed HTTP response".to_string()))
+    }
+
+    /// Checks that a connection can be opened, without keeping it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn probe(&self) -> Result<(), Error> {
+        self.connect().await.map(drop)
+    }
+
+    /// Opens a connection for `pool`, offering HTTP/2 to `https://`
+    /// endpoints. The pool keeps a handle to an HTTP/2 one from the start.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn open(&self, pool: &Pool) -> Result<Pooled, Error> {
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            let (stream, http2) = tls.connect_http(self.connect_tcp().await?, &self.host).await?;
+            if !http2 {
+                return Ok(pool.opened(Pooled::Http1(Connection::Tls(Box::new(stream)))));
+            }
+            let connection = Http2::handshake(stream).await?;
+            pool.put(Pooled::Http2(connection.clone()));
+            return Ok(pool.opened(Pooled::Http2(connection)));
+        }
+        Ok(pool.opened(Pooled::Http1(Connection::Plain(self.connect_tcp().await?))))
+    }
+
+    /// Sends one request over `stream`, telling whether the connection can
+    /// carry another. A handle to an HTTP/2 connection is never put back,
+    /// the pool kept one.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn send_pooled(
+        &self,
+        stream: &mut Pooled,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<(&str, &[u8])>,
+    ) -> Result<(Response, bool), Error> {
+        match stream {
+            Pooled::Http1(connection) => exchange(connection, &self.encode(method, path, headers, body, true)).await,
+            #[cfg(feature = "tls")]
+            Pooled::Http2(connection) => Ok((connection.request(&self.authority(), method, path, headers, body).await?, false)),
+        }
+    }
+
+    /// Opens a connection, and a TLS session over it for `https://`.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn connect(&self) -> Result<Connection, Error> {
+        let stream = self.connect_tcp().await?;
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            return Ok(Connection::Tls(Box::new(tls.connect(stream, &self.host).await?)));
+        }
+        Ok(Connection::Plain(stream))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn connect_tcp(&self) -> Result<TcpStream, Error> {
+        let Some(proxy) = &self.proxy else {
+            return TcpStream::connect((self.host.as_str(), self.port)).await.map_err(Error::io);
+        };
+        let target = match self.host.parse::<IpAddr>() {
+            Ok(ip) => Target::Address(SocketAddr::new(ip, self.port)),
+            Err(_) => Target::Host(self.host.clone(), self.port),
+        };
+        Ok(proxy::connect(proxy, &target).await?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn encode(&self, method: &str, path: &str, headers: &[(&str, &str)], body: Option<(&str, &[u8])>, keep_alive: bool) -> Vec<u8> {
+        let connection = if keep_alive { "keep-alive" } else { "close" };
+        let mut head = format!("{} {} HTTP/1.1\r\nHost: {}\r\nConnection: {}\r\n", method, path, self.authority(), connection);
+        if let Some((content_type, body)) = body {
+            head.push_str(&format!("Content-Type: {}\r\nContent-Length: {}\r\n", content_type, body.len()));
+        }
+        for (name, value) in headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        let mut request = head.into_bytes();
+        if let Some((_, body)) = body {
+            request.extend_from_slice(body);
+        }
+        request
+    }
+}
+
+/// The same requests through `fetch`, which has no way to use a proxy.
+/// It opens and keeps connections itself, so warming up has nothing to do.
+#[cfg(target_arch = "wasm32")]
+impl Endpoint {
+    pub(crate) async fn request(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<(&str, &[u8])>,
+    ) -> Result<Response, Error> {
+        if let Some(proxy) = &self.proxy {
+            return Err(Error::ProxyUnreachable { proxy: proxy.addr, reason: "fetch cannot use a SOCKS5 proxy".to_string() });
+        }
+        let url = format!("{}://{}{}", if self.https { "https" } else { "http" }, self.authority(), path);
+        crate::wasm::fetch(method, &url, headers, body).await.map_err(Error::Failed)
+    }
+
+    /// Like `request`, with at most the pool's number of requests in flight.
+    pub(crate) async fn pooled(
+        &self,
+        pool: &Pool,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<(&str, &[u8])>,
+    ) -> Result<Response, Error> {
+        let _permit = pool.permits.acquire().await.map_err(|_| Error::Failed("connection pool closed".to_string()))?;
+        self.request(method, path, headers, body).await
+    }
+
+    pub(crate) async fn preconnect(&self, _: &Pool) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    #[cfg(feature = "http")]
+    pub(crate) async fn get_limited(&self, path: &str, limit: usize) -> Result<Response, Error> {
+        let response = self.request("GET", path, &[], None).await?;
+        let headers: usize = response.headers.iter().map(|(name, value)| name.len() + value.len() + 4).sum();
+        if headers + response.body.len() > limit {
+            return Err(Error::Failed(format!("response is larger than {} bytes", limit)));
+        }
+        Ok(response)
+    }
+
+    pub(crate) async fn probe(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Writes `request` out in full, through any TLS buffering.
+#[cfg(not(target_arch = "wasm32"))]
+async fn send(stream: &mut Connection, request: &[u8]) -> Result<(), Error> {
+    stream.write_all(request).await.map_err(Error::io)?;
+    stream.flush().await.map_err(Error::io)
+}
+
+/// Sends `request` and reads one response. The connection can carry
+/// another request afterwards if the response said where it ended and the
+/// server did not ask to close.
+#[cfg(not(target_arch = "wasm32"))]
+async fn exchange(stream: &mut Connection, request: &[u8]) -> Result<(Response, bool), Error> {
+    send(stream, request).await?;
+    let mut raw = Vec::new();
+    let mut buffer = [0; 4096];
+    loop {
+        if let Some(response) = Response::parse(&raw, false) {
+            let close = response.header("connection").is_some_and(|value| value.eq_ignore_ascii_case("close"));
+            return Ok((response, !close));
+        }
+        let len = stream.read(&mut buffer).await.map_err(Error::io)?;
+        if len == 0 {
+            return match Response::parse(&raw, true) {
+                Some(response) if !raw.is_empty() => Ok((response, false)),
+                _ => Err(Error::Failed("connection closed before the response was complete".to_string())),
+            };
+        }
+        raw.extend_from_slice(&buffer[..len]);
+    }
+}
+
+/// Counters of a connection pool, as returned by `DohResolver::pool_stats`
+/// and `TlsTransport::pool_stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PoolStats {
+    /// Connections opened, retries included.
+    pub opened: u64,
+    /// Requests sent over a connection kept from an earlier one.
+    pub reused: u64,
+    /// Requests sent again on a new connection after the first one failed.
+    pub retried: u64,
+    /// Connections kept open for later requests, including HTTP/2 and DoT
+    /// ones busy carrying others.
+    pub idle: usize,
+}
+
+/// Keep-alive connections to one endpoint. At most `max_connections` are
+/// in use at once, further requests wait for one; idle ones are closed
+/// after `idle_timeout`. Connections carrying requests at once, over
+/// HTTP/2 or pipelined DoT, are shared rather than taken, and a request
+/// over one needs no permit. A connection is only reused on the runtime
+/// that opened it, since its I/O is driven there. On wasm32, where `fetch`
+/// keeps the connections, it only limits the requests in flight.
+#[derive(Debug)]
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+pub(crate) struct Pool<S = Pooled> {
+    max_connections: usize,
+    idle_timeout: Duration,
+    idle: Mutex<Vec<Idle<S>>>,
+    pub(crate) permits: Semaphore,
+    opened: AtomicU64,
+    reused: AtomicU64,
+    retried: AtomicU64,
+}
+
+#[derive(Debug)]
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+struct Idle<S> {
+    stream: S,
+    runtime: Option<runtime::Id>,
+    since: Instant,
+}
+
+impl<S> Pool<S> {
+    pub(crate) fn new(max_connections: usize, idle_timeout: Duration) -> Self {
+        Pool {
+            max_connections,
+            idle_timeout,
+            idle: Mutex::new(Vec::new()),
+            permits: Semaphore::new(max_connections),
+            opened: AtomicU64::new(0),
+            reused: AtomicU64::new(0),
+            retried: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn stats(&self) -> PoolStats {
+        PoolStats {
+            opened: self.opened.load(Ordering::Relaxed),
+            reused: self.reused.load(Ordering::Relaxed),
+            retried: self.retried.load(Ordering::Relaxed),
+            idle: self.idle.lock().unwrap_or_else(PoisonError::into_inner).len(),
+        }
+    }
+
+}
+
+/// What a `Pool` keeps. `share` gives another handle to a connection
+/// carrying requests at once, `None` for one carrying a request at a time.
+pub(crate) trait Shared: Sized {
+    fn share(&self) -> Option<Self>;
+
+    /// Whether a shared connection is known to be closed.
+    fn is_closed(&self) -> bool {
+        false
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+impl<S: Shared> Pool<S> {
+    pub(crate) fn opened(&self, stream: S) -> S {
+        self.opened.fetch_add(1, Ordering::Relaxed);
+        stream
+    }
+
+    /// Counts a request sent again on a new connection.
+    pub(crate) fn retrying(&self) {
+        self.retried.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The most recently used idle connection of this runtime, or a handle
+    /// to it when it is shared, closing the ones that have been idle for
+    /// too long.
+    pub(crate) fn take(&self) -> Option<S> {
+        self.find(false)
+    }
+
+    /// Like `take`, only for a shared connection.
+    pub(crate) fn share(&self) -> Option<S> {
+        self.find(true)
+    }
+
+    fn find(&self, shared_only: bool) -> Option<S> {
+        let runtime = current_runtime();
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        idle.retain(|connection| connection.since.elapsed() < self.idle_timeout && !connection.stream.is_closed());
+        let position = idle.iter().rposition(|connection| connection.runtime == runtime)?;
+        let stream = match idle[position].stream.share() {
+            Some(stream) => {
+                idle[position].since = Instant::now();
+                stream
+            }
+            None if shared_only => return None,
+            None => idle.remove(position).stream,
+        };
+        self.reused.fetch_add(1, Ordering::Relaxed);
+        Some(stream)
+    }
+
+    pub(crate) fn has_idle(&self) -> bool {
+        let runtime = current_runtime();
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        idle.retain(|connection| connection.since.elapsed() < self.idle_timeout && !connection.stream.is_closed());
+        idle.iter().any(|connection| connection.runtime == runtime)
+    }
+
+    pub(crate) fn put(&self, stream: S) {
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        if idle.len() < self.max_connections {
+            idle.push(Idle { stream, runtime: current_runtime(), since: Instant::now() });
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+fn current_runtime() -> Option<runtime::Id> {
+    Handle::try_current().ok().map(|handle| handle.id())
+}
+
+/// What a DoH pool keeps: a connection carrying a request at a time, or a
+/// handle to an HTTP/2 one.
+#[derive(Debug)]
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+pub(crate) enum Pooled {
+    Http1(Connection),
+    #[cfg(feature = "tls")]
+    Http2(Http2),
+}
+
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+impl Pooled {
+    fn is_multiplexed(&self) -> bool {
+        !matches!(self, Pooled::Http1(_))
+    }
+}
+
+impl Shared for Pooled {
+    fn share(&self) -> Option<Self> {
+        match self {
+            Pooled::Http1(_) => None,
+            #[cfg(feature = "tls")]
+            Pooled::Http2(connection) => Some(Pooled::Http2(connection.clone())),
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        match self {
+            Pooled::Http1(_) => false,
+            #[cfg(feature = "tls")]
+            Pooled::Http2(connection) => connection.closed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A handle to an HTTP/2 connection, whose frames a task on the runtime
+/// that opened it reads and writes until it closes.
+#[cfg(feature = "tls")]
+#[derive(Debug, Clone)]
+pub(crate) struct Http2 {
+    requests: h2::client::SendRequest<Bytes>,
+    closed: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "tls")]
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+impl Http2 {
+    async fn handshake(stream: tokio_rustls::client::TlsStream<TcpStream>) -> Result<Http2, Error> {
+        let (requests, connection) = h2::client::handshake(stream).await.map_err(Error::http2)?;
+        let closed = Arc::new(AtomicBool::new(false));
+        let driver = closed.clone();
+        tokio::spawn(async move {
+            let _ = connection.await;
+            driver.store(true, Ordering::Relaxed);
+        });
+        Ok(Http2 { requests, closed })
+    }
+
+    /// Sends a request on a stream of its own, once the server allows
+    /// another, and reads the whole response.
+    async fn request(
+        &self,
+        authority: &str,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<(&str, &[u8])>,
+    ) -> Result<Response, Error> {
+        let mut request = http::Request::builder().method(method).uri(format!("https://{}{}", authority, path));
+        if let Some((content_type, body)) = body {
+            request = request.header("content-type", content_type).header("content-length", body.len());
+        }
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let request = request.body(()).map_err(|e| Error::Failed(e.to_string()))?;
+
+        let mut requests = self.requests.clone().ready().await.map_err(Error::http2)?;
+        let (response, mut stream) = requests.send_request(request, body.is_none()).map_err(Error::http2)?;
+        if let Some((_, body)) = body {
+            stream.send_data(Bytes::copy_from_slice(body), true).map_err(Error::http2)?;
+        }
+        let (head, mut received) = response.await.map_err(Error::http2)?.into_parts();
+        let mut body = Vec::new();
+        while let Some(data) = received.data().await {
+            let data = data.map_err(Error::http2)?;
+            let _ = received.flow_control().release_capacity(data.len());
+            body.extend_from_slice(&data);
+        }
+        let headers = head
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str().to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+            .collect();
+        Ok(Response { status: head.status.as_u16(), headers, body })
+    }
+}
+
+/// A connection to an endpoint, with a TLS session for `https://` ones.
+#[derive(Debug)]
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+pub(crate) enum Connection {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<tokio_rustls::client::TlsStream<TcpStream>>),
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Connection::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Connection::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Connection::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Connection::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Error {
+    Failed(String),
+    /// See `TransportError::ProxyUnreachable`.
+    ProxyUnreachable { proxy: SocketAddr, reason: String },
+    /// See `TransportError::Certificate`.
+    Certificate { reason: String },
+}
+
+impl Error {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn io(e: std::io::Error) -> Error {
+        Error::Failed(e.to_string())
+    }
+
+    #[cfg(feature = "tls")]
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    fn http2(e: h2::Error) -> Error {
+        Error::Failed(format!("HTTP/2: {}", e))
+    }
+}
+
+impl From<TransportError> for Error {
+    fn from(e: TransportError) -> Self {
+        match e {
+            TransportError::ProxyUnreachable { proxy, reason } => Error::ProxyUnreachable { proxy, reason },
+            TransportError::Certificate { reason, .. } => Error::Certificate { reason },
+            e => Error::Failed(e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Failed(message) => f.write_str(message),
+            Error::ProxyUnreachable { proxy, reason } => write!(f, "SOCKS5 proxy {} unreachable: {}", proxy, reason),
+            Error::Certificate { reason } => write!(f, "certificate rejected: {}", reason),
+        }
+    }
+}
+
+pub(crate) struct Response {
+    pub(crate) status: u16,
+    /// Names in lowercase.
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Response {
+    /// Parses a response read until the server closed the connection, or
+    /// when `closed` is false, one with a `Content-Length` or chunked body
+    /// that is complete. `None` means malformed, or not complete yet.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn parse(raw: &[u8], closed: bool) -> Option<Response> {
+        let split = raw.windows(4).position(|window| window == b"\r\n\r\n")?;
+        let head = std::str::from_utf8(&raw[..split]).ok()?;
+        let mut lines = head.split("\r\n");
+        let status = lines.next()?.split_whitespace().nth(1)?.parse().ok()?;
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
+        let mut response = Response { status, headers, body: Vec::new() };
+
+        let body = &raw[split + 4..];
+        response.body = if matches!(status, 100..=199 | 204 | 304) {
+            Vec::new()
+        } else if response.header("transfer-encoding").is_some_and(|v| v.eq_ignore_ascii_case("chunked")) {
+            dechunk(body)?
+        } else {
+            match response.header("content-length") {
+                Some(len) => body.get(..len.parse().ok()?)?.to_vec(),
+                None if closed => body.to_vec(),
+                None => return None,
+            }
+        };
+        Some(response)
+    }
+
+    /// Looks up a header by its lowercase name.
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn dechunk(mut data: &[u8]) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = data.windows(2).position(|window| window == b"\r\n")?;
+        let size = std::str::from_utf8(&data[..line_end]).ok()?.split(';').next()?.trim();
+        let size = usize::from_str_radix(size, 16).ok()?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            return Some(body);
+        }
+        body.extend_from_slice(data.get(..size)?);
+        data = data.get(size + 2..)?;
+    }
+}
@@ -0,0 +1,81 @@
+//! Coalesces concurrent lookups of the same name so that only one query
+//! reaches the backend and every caller receives its result.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Mutex, PoisonError};
+
+use tokio::sync::watch;
+
+#[derive(Debug)]
+pub(crate) struct InFlight<T> {
+    lookups: Mutex<HashMap<String, watch::Receiver<Option<T>>>>,
+}
+
+impl<T> Default for InFlight<T> {
+    fn default() -> Self {
+        InFlight { lookups: Mutex::new(HashMap::new()) }
+    }
+}
+
+enum Role<T> {
+    Leader(watch::Sender<Option<T>>),
+    Follower(watch::Receiver<Option<T>>),
+}
+
+/// Removes the leader's entry when its lookup finishes, panics or is dropped.
+struct Entry<'a, T> {
+    in_flight: &'a InFlight<T>,
+    key: &'a str,
+}
+
+impl<T> Drop for Entry<'_, T> {
+    fn drop(&mut self) {
+        self.in_flight.lookups.lock().unwrap_or_else(PoisonError::into_inner).remove(self.key);
+    }
+}
+
+impl<T: Clone> InFlight<T> {
+    /// Runs `lookup` unless one for `key` is already running, in which case
+    /// its result is awaited instead. If the running lookup is abandoned
+    /// without a result, a waiting caller takes over and runs its own.
+    pub(crate) async fn run<F>(&self, key: &str, lookup: impl Fn() -> F) -> T
+    where
+        F: Future<Output = T>,
+    {
+        loop {
+            let role = {
+                let mut lookups = self.lookups.lock().unwrap_or_else(PoisonError::into_inner);
+                match lookups.get(key) {
+                    Some(receiver) => Role::Follower(receiver.clone()),
+                    None => {
+                        let (sender, receiver) = watch::channel(None);
+                        lookups.insert(key.to_string(), receiver);
+                        Role::Leader(sender)
+                    }
+                }
+            };
+
+            match role {
+                Role::Leader(sender) => {
+                    let entry = Entry { in_flight: self, key };
+                    let outcome = lookup().await;
+                    // Once the entry is gone no caller can start waiting, so
+                    // the outcome is only copied when someone already is.
+                    drop(entry);
+                    if sender.receiver_count() > 0 {
+                        sender.send_replace(Some(outcome.clone()));
+                    }
+                    return outcome;
+                }
+                Role::Follower(mut receiver) => {
+                    if let Ok(outcome) = receiver.wait_for(Option::is_some).await {
+                        if let Some(outcome) = outcome.as_ref() {
+                            return outcome.clone();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Record keys: the `{key}` of the `_{key}` label a record is published
+//! under.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::SelfieError;
+
+/// The longest key, which leaves room for the leading underscore in a
+/// 63-byte label.
+const MAX_KEY_LEN: usize = 61;
+
+/// A record key: 1 to 61 ASCII letters, digits or hyphens, lowercase.
+/// The constants name the well-known keys; `custom` any other.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RecordKey(Cow<'static, str>);
+
+impl RecordKey {
+    pub const BITCOIN_PAYMENT: RecordKey = RecordKey(Cow::Borrowed("bitcoin-payment"));
+    pub const PGP: RecordKey = RecordKey(Cow::Borrowed("pgp"));
+    pub const NOSTR: RecordKey = RecordKey(Cow::Borrowed("nostr"));
+    pub const NODE_URI: RecordKey = RecordKey(Cow::Borrowed("node-uri"));
+    pub const BIP47: RecordKey = RecordKey(Cow::Borrowed("bip47"));
+    pub const DID: RecordKey = RecordKey(Cow::Borrowed("did"));
+    pub const LNURL: RecordKey = RecordKey(Cow::Borrowed("lnurl"));
+    pub const LIGHTNING_ADDRESS: RecordKey = RecordKey(Cow::Borrowed("lightning-address"));
+
+    /// `key` lowercased, or `InvalidKey` when it is empty, longer than
+    /// `MAX_KEY_LEN` or holds anything but letters, digits and hyphens.
+    pub fn custom(key: &str) -> Result<RecordKey, SelfieError> {
+        let valid = (1..=MAX_KEY_LEN).contains(&key.len()) && key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+        if !valid {
+            return Err(SelfieError::InvalidKey { key: key.to_string() });
+        }
+        Ok(RecordKey(Cow::Owned(key.to_ascii_lowercase())))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for RecordKey {
+    type Err = SelfieError;
+
+    fn from_str(key: &str) -> Result<Self, SelfieError> {
+        RecordKey::custom(key)
+    }
+}
+
+impl AsRef<str> for RecordKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RecordKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
@@ -1,124 +1,1599 @@
+//! Looks up selfie records: the TXT records a domain or an address such as
+//! `alice@example.com` publishes under `_{key}` names, e.g. a bitcoin
+//! payment URI, a PGP key or a nostr key.
+//!
+//! ```no_run
+//! use selfie_records_sdk::{RecordEntry, SelfieRecordsSDK};
+//!
+//! let sdk = SelfieRecordsSDK::new();
+//! let records = sdk.get_records("alice@example.com", None, None);
+//! match records.get("bitcoin-payment").map(|result| result.entry()) {
+//!     Some(RecordEntry::Found { value }) => println!("{}", value),
+//!     Some(RecordEntry::Error(e)) => eprintln!("{} ({})", e, e.code()),
+//!     _ => println!("no payment record"),
+//! }
+//! ```
+//!
+//! `SdkBuilder` configures the resolver, caching and validation.
+//! `validate_name` and `get_txt_record_key` work on names without a lookup.
+//!
+//! The SDK logs through the `log` crate and leaves installing a logger to
+//! the application; `init_logging` installs a simple one.
+//!
+//! With the `wasm` feature the crate builds for `wasm32-unknown-unknown`,
+//! where lookups go through `fetch` to a DoH resolver, Cloudflare's unless
+//! the builder names another, and time comes from JavaScript. Only the
+//! async calls work there: the blocking ones, `watch` and anything sending
+//! plain DNS, such as consensus and DNSSEC checks, need threads or sockets.
+//! The features built on `ring`, `dnssec`, `signatures`, `tls`, `webhook` and
+//! `audit`, need its C code, which for wasm32 takes clang and ring's
+//! `wasm32_c` feature; `--no-default-features --features wasm` leaves
+//! them out.
+//!
+//! The `python` feature builds the `selfie_records` Python module; with
+//! maturin, `maturin develop` installs it and `pytest` runs its tests,
+//! which `cargo test --features python` also runs in an embedded
+//! interpreter.
+
+#[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+compile_error!("building for wasm32 needs the `wasm` feature");
+
 use std::collections::HashMap;
-use std::net::Ipv4Addr;
-use std::str::FromStr;
-use regex::Regex;
-use log::{info, debug, error, LevelFilter};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use log::{info, debug, error, warn, LevelFilter, SetLoggerError};
 use simple_logger::SimpleLogger;
-use trust_dns_resolver::{Resolver, config::*};
+use tokio::runtime::Runtime;
+use time::Instant;
+
+#[cfg(feature = "audit")]
+pub mod audit;
+mod base58;
+mod bech32;
+pub mod bench;
+mod builder;
+mod cache;
+mod chunks;
+pub mod config;
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+pub mod codec;
+pub mod consensus;
+mod cross_check;
+#[cfg(feature = "dnssec")]
+pub mod dnssec;
+pub mod doh;
+mod encoding;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod health;
+mod http;
+mod inflight;
+mod key;
+#[cfg(feature = "signatures")]
+pub mod linkage;
+mod matrix;
+mod name;
+mod net;
+mod observer;
+#[cfg(feature = "nip05")]
+pub mod nip05;
+#[cfg(feature = "signatures")]
+mod openpgp;
+mod options;
+mod overrides;
+mod parser;
+mod profile;
+mod progress;
+mod proxy;
+pub mod publish;
+#[cfg(feature = "python")]
+pub mod python;
+mod records;
+mod resolver;
+mod response;
+mod routing;
+#[cfg(feature = "server")]
+pub mod server;
+mod sha256;
+mod throttle;
+mod time;
+#[cfg(feature = "tls")]
+mod tls;
+mod transport;
+#[cfg(feature = "signatures")]
+pub mod signature;
+pub mod verify;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+pub mod watch;
+mod wire;
+#[cfg(feature = "test-util")]
+pub mod testing;
+
+pub use builder::{BuildError, NameserverSource, SdkBuilder, TransportPreference};
+pub use cache::CacheConfig;
+pub use cross_check::CrossCheck;
+pub use encoding::{escape_controls, RawRecord, ResponseLimits, Sanitization, ValueEncodingIssue};
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+pub use codec::DecodeError;
+pub use error::{SelfieError, TimeoutBudget};
+pub use key::RecordKey;
+pub use name::{
+    get_txt_record_key, parse_identifier, parse_txt_record_key, validate_name, validate_name_with, Identifier, LocalPartPolicy, NameError, NameKind,
+    NameScheme, NormalizedDomain, SelfieNameScheme, TemplateError, TemplateNameScheme, ValidationError, DEFAULT_DOMAIN_TEMPLATE,
+    DEFAULT_EMAIL_TEMPLATE,
+};
+pub use matrix::{MatrixCell, MatrixCells, MatrixResponse};
+pub use observer::{Observer, QueryOutcome};
+pub use options::LookupOptions;
+pub use overrides::{OverrideError, RecordOverrides};
+pub use parser::{ParseError, ParsedValue, RecordParser};
+pub use profile::{DnssecStatus, SelfieProfile};
+pub use progress::{KeyOutcome, ProgressEvent, ProgressSummary};
+pub use proxy::{Socks5Auth, Socks5Config, Socks5Transport};
+pub use records::{Bip21Uri, Bip353Instruction, Did, Host, Lnurl, NodeUri, NostrKey, PaymentCode, PgpRecord};
+pub use resolver::{Readiness, TxtResolver};
+pub use response::{KeyResult, QueryMeta, RecordEntry, RecordsResponse, Source};
+pub use routing::{NameServerGroup, Route};
+use routing::NameserverSpec;
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
+pub use transport::{DnsTransport, TcpTransport, TlsNameserver, TransportError, TransportFactory, UdpTransport};
+#[cfg(feature = "tls")]
+pub use transport::TlsTransport;
+pub use wire::{DirectResolver, ServerInfo, Transport, WireInfo};
 
-const DEFAULT_RECORDS: [&str; 4] = ["bitcoin-payment", "pgp", "nostr", "node-uri"];
+/// The record keys looked up when a call names none.
+pub const DEFAULT_RECORDS: [&str; 4] = ["bitcoin-payment", "pgp", "nostr", "node-uri"];
 
-#[derive(Debug)]
+/// Every key `SelfieProfile` parses into a typed field: `DEFAULT_RECORDS`
+/// and the ones only looked up when asked for.
+pub const EXTENDED_RECORDS: [&str; 8] = ["bitcoin-payment", "pgp", "nostr", "node-uri", "bip47", "did", "lnurl", "lightning-address"];
+
+/// The RCODE of an answer for a name that does not exist.
+const NXDOMAIN: u16 = 3;
+
+/// `Send` and `Sync`: one SDK, shared through an `Arc`, can serve every
+/// thread or task of a program.
 pub struct SelfieRecordsSDK {
-    resolver: Resolver,
+    runtime: OwnRuntime,
+    resolver: Arc<dyn TxtResolver>,
+    routes: routing::Routes,
+    /// How reports name `resolver`.
+    resolver_label: &'static str,
+    name_scheme: Arc<dyn NameScheme>,
+    name_template_fallbacks: Vec<TemplateNameScheme>,
+    local_part_policy: LocalPartPolicy,
+    record_version: Option<u32>,
+    overrides: RecordOverrides,
+    parsers: parser::Parsers,
+    cache: cache::Cache,
+    offline: AtomicBool,
+    in_flight: inflight::InFlight<Resolved>,
+    limiter: throttle::QueryLimiter,
+    nameservers: Arc<dyn consensus::NameserverDiscovery>,
+    authoritative_transport: Option<TransportFactory>,
+    public_only: Option<name::PublicOnly>,
+    cross_check: Option<cross_check::CrossChecker>,
+    strict_encoding: bool,
+    response_limits: ResponseLimits,
+    sanitization: Sanitization,
+    chunked_keys: Vec<String>,
+    fallback_to_default: bool,
+    /// Options of calls that take none.
+    defaults: LookupOptions,
+    default_keys: Vec<String>,
+    transport: TransportPreference,
+    proxy: Option<Socks5Config>,
+    observer: observer::Observers,
+    #[cfg(feature = "audit")]
+    audit: Option<audit::AuditLog>,
+    #[cfg(feature = "signatures")]
+    pgp_keys: Option<Arc<dyn linkage::PgpKeySource>>,
+    #[cfg(feature = "nip05")]
+    nip05: Arc<dyn nip05::Nip05Source>,
+    #[cfg(feature = "dnssec")]
+    validation: dnssec::Validation,
+}
+
+impl std::fmt::Debug for SelfieRecordsSDK {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SelfieRecordsSDK").finish_non_exhaustive()
+    }
+}
+
+impl Default for SelfieRecordsSDK {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SelfieRecordsSDK {
-    pub fn new(debug: bool) -> Self {
-        if debug {
-            SimpleLogger::new().with_level(LevelFilter::Debug).init().unwrap();
-        } else {
-            SimpleLogger::new().with_level(LevelFilter::Error).init().unwrap();
+    pub fn new() -> Self {
+        SdkBuilder::new().build().expect("default configuration is valid")
+    }
+
+    /// Builds an SDK that sends every query to `resolver`.
+    pub fn with_resolver(resolver: Arc<dyn TxtResolver>) -> Self {
+        SdkBuilder::new().resolver(resolver).build().expect("default configuration is valid")
+    }
+
+    pub fn builder() -> SdkBuilder {
+        SdkBuilder::new()
+    }
+
+    pub(crate) fn from_builder(builder: SdkBuilder) -> Self {
+        let runtime = OwnRuntime(Some(new_runtime()));
+        let resolver_label = if builder.resolver.is_some() { "custom" } else { "system" };
+        let resolver = builder.resolver.unwrap_or_else(|| {
+            let _guard = runtime.enter();
+            Arc::new(resolver::system_resolver(builder.transport, builder.nameserver_timeout.unwrap_or(builder.lookup_options.get_timeout())))
+        });
+        let proxy = builder.proxy.clone();
+        // The checks ask the SDK's first nameserver, or the system's, over
+        // the configured transport or through the proxy.
+        let check_server = || match &builder.nameserver_source {
+            NameserverSource::Static(servers) if !servers.is_empty() => servers[0],
+            _ => resolver::system_nameservers()[0],
+        };
+        let check_transport = |server: SocketAddr| -> Option<Arc<dyn DnsTransport>> {
+            match (&builder.proxy, builder.transport) {
+                (Some(proxy), _) => Some(Arc::new(Socks5Transport::new(proxy.clone(), server))),
+                (None, TransportPreference::TcpOnly) => Some(Arc::new(TcpTransport::new(server))),
+                (None, TransportPreference::UdpThenTcp) => None,
+            }
+        };
+        let authoritative_transport = builder.authoritative_transport.or_else(|| {
+            let proxy = builder.proxy.clone()?;
+            Some(Arc::new(move |server| Arc::new(Socks5Transport::new(proxy.clone(), server)) as Arc<dyn DnsTransport>) as TransportFactory)
+        });
+        SelfieRecordsSDK {
+            runtime,
+            resolver,
+            routes: builder.routes,
+            resolver_label,
+            name_scheme: builder.name_scheme.unwrap_or_else(|| Arc::new(SelfieNameScheme)),
+            name_template_fallbacks: builder.name_template_fallbacks,
+            local_part_policy: builder.local_part_policy,
+            record_version: builder.record_version,
+            overrides: builder.overrides,
+            parsers: parser::Parsers::new(builder.parsers),
+            cache: cache::Cache::new(builder.cache),
+            offline: AtomicBool::new(builder.offline),
+            in_flight: inflight::InFlight::default(),
+            limiter: throttle::QueryLimiter::new(builder.max_queries_per_second, builder.max_in_flight),
+            nameservers: builder.nameservers.unwrap_or_else(|| {
+                let server = check_server();
+                Arc::new(match check_transport(server) {
+                    Some(transport) => consensus::RecursiveDiscovery::with_transport(transport),
+                    None => consensus::RecursiveDiscovery::new(server),
+                })
+            }),
+            authoritative_transport,
+            public_only: builder.public_only.then(|| name::PublicOnly::new(&builder.blocked_suffixes)),
+            cross_check: builder.cross_check.then(|| cross_check::CrossChecker {
+                resolver: builder
+                    .cross_check_resolver
+                    .unwrap_or_else(|| match check_transport(([1, 1, 1, 1], 53).into()) {
+                        Some(transport) => Arc::new(DirectResolver::with_transport(transport)),
+                        None => Arc::new(DirectResolver::new(([1, 1, 1, 1], 53).into())),
+                    }),
+                strict: builder.strict_cross_check,
+            }),
+            strict_encoding: builder.strict_encoding,
+            response_limits: builder.response_limits,
+            sanitization: builder.sanitization,
+            chunked_keys: builder.chunked_keys,
+            fallback_to_default: builder.fallback_to_default,
+            defaults: builder.lookup_options,
+            default_keys: builder.default_keys.unwrap_or_else(|| DEFAULT_RECORDS.iter().map(|key| key.to_string()).collect()),
+            transport: builder.transport,
+            proxy,
+            observer: observer::Observers::default(),
+            #[cfg(feature = "audit")]
+            audit: builder.audit_sink.map(|sink| audit::AuditLog { sink, identifier_key: builder.audit_identifier_key }),
+            #[cfg(feature = "signatures")]
+            pgp_keys: builder.pgp_keys,
+            #[cfg(feature = "nip05")]
+            nip05: builder.nip05_source.unwrap_or_else(|| Arc::new(nip05::HttpNip05Source::new())),
+            #[cfg(feature = "dnssec")]
+            validation: dnssec::Validation::new(
+                builder.dnssec_source.unwrap_or_else(|| {
+                    let server = check_server();
+                    Arc::new(match check_transport(server) {
+                        Some(transport) => dnssec::NetworkSource::with_transport(transport),
+                        None => dnssec::NetworkSource::new(server),
+                    })
+                }),
+                builder.trust_anchors,
+                builder.require_dnssec,
+                builder.report_dnssec,
+            ),
         }
+    }
 
-        let resolver = Resolver::new(ResolverConfig::default(), ResolverOpts::default()).unwrap();
-        SelfieRecordsSDK { resolver }
+    /// Looks up `filters`, or the default keys, of `name`. `dns_server`
+    /// sends the call's queries to another server instead of the SDK's
+    /// resolver: an address such as `9.9.9.9`, `8.8.8.8:5353` or
+    /// `[2606:4700:4700::1111]:53`, a host name such as `dns.quad9.net` or
+    /// `my-pihole.lan:5353`, with port 53 by default, or a DoH endpoint
+    /// URL. A host name is looked up first and each of its addresses is
+    /// tried in turn; when that fails, every key fails with
+    /// `NameserverResolutionFailed`. Any other value fails every key with
+    /// `InvalidNameserver`. The server only serves this call: concurrent
+    /// calls naming other servers, or none, neither use it nor share its
+    /// cached answers.
+    ///
+    /// Keys are matched case-insensitively; a key that is not a valid
+    /// `RecordKey` fails with `InvalidKey` without a query. Results are
+    /// keyed by the filters as passed; `RecordsResponse::to_map` gives them
+    /// as the string maps earlier versions returned.
+    pub fn get_records(&self, name: &str, filters: Option<Vec<&str>>, dns_server: Option<&str>) -> RecordsResponse {
+        self.get_records_with(name, filters, dns_server, &self.defaults)
     }
 
-    pub fn get_records(&self, name: &str, filters: Option<Vec<&str>>, dns_server: Option<&str>) -> HashMap<String, HashMap<String, Option<String>>> {
-        let filters = filters.unwrap_or(DEFAULT_RECORDS.to_vec());
-        let dns_server = dns_server.unwrap_or("8.8.8.8");
+    /// Like `get_records`, with `RecordKey`s for the keys.
+    pub fn get_records_for_keys(&self, name: &str, keys: &[RecordKey], dns_server: Option<&str>) -> RecordsResponse {
+        self.get_records(name, Some(keys.iter().map(RecordKey::as_str).collect()), dns_server)
+    }
+
+    /// Like `get_records`, with per-call timeouts and retries taken from `options`.
+    pub fn get_records_with(&self, name: &str, filters: Option<Vec<&str>>, dns_server: Option<&str>, options: &LookupOptions) -> RecordsResponse {
+        self.get_records_response(name, filters, dns_server, options)
+    }
 
-        let mut results = HashMap::new();
+    /// The same as `get_records_with`.
+    pub fn get_records_response(&self, name: &str, filters: Option<Vec<&str>>, dns_server: Option<&str>, options: &LookupOptions) -> RecordsResponse {
+        let options = self.snapshot(options);
+        self.runtime.block_on(self.get_records_inner(name, filters, dns_server, &options))
+    }
 
-        if let Ok(ip) = Ipv4Addr::from_str(dns_server) {
-            self.resolver.update_nameservers(&ResolverConfig::from_parts(None, vec![NameServerConfig {
-                socket_addr: (ip, 53).into(),
-                protocol: Protocol::Udp,
-                tls_dns_name: None,
-                trust_anchor: None,
-                tls_config: None,
-            }])).unwrap();
+    /// Like `get_records`, for callers already on a tokio runtime, which
+    /// the lookups run on.
+    pub async fn get_records_async(&self, name: &str, filters: Option<Vec<&str>>, dns_server: Option<&str>) -> RecordsResponse {
+        self.get_records_response_async(name, filters, dns_server, &self.defaults).await
+    }
+
+    /// Like `get_records_response`, looking the keys up concurrently, at
+    /// most `options.concurrency(..)` at a time. A key that fails does not
+    /// stop the others. Each key has a query budget of its own, and
+    /// progress is not reported.
+    pub async fn get_records_response_async(
+        &self,
+        name: &str,
+        filters: Option<Vec<&str>>,
+        dns_server: Option<&str>,
+        options: &LookupOptions,
+    ) -> RecordsResponse {
+        let filters = filters.unwrap_or_else(|| self.default_keys());
+        let options = self.snapshot(options).without_progress();
+        let permits = tokio::sync::Semaphore::new(options.get_concurrency().max(1));
+        let lookups = filters.iter().map(|&key| {
+            let (options, permits) = (&options, &permits);
+            async move {
+                let _permit = permits.acquire().await.expect("the semaphore is never closed");
+                self.get_records_inner(name, Some(vec![key]), dns_server, options).await
+            }
+        });
+        let mut response = RecordsResponse::with_capacity(filters.len());
+        for (key, looked_up) in filters.iter().zip(chunks::join_all(lookups.collect()).await) {
+            response.queries_issued += looked_up.queries_issued;
+            if let Some(result) = looked_up.get(key) {
+                response.insert(key, result.clone());
+            }
         }
+        response
+    }
 
-        for key in filters.iter() {
-            let domain_check = self.validate_domain_or_subdomain(key, name);
-            let email_check = self.validate_email_address(key, name);
+    /// Looks up the one record `key` of `name`, like `get_records` with a
+    /// single filter. `entry()` of the result tells a missing record from
+    /// a failed lookup.
+    pub fn get_record(&self, name: &str, key: &str) -> KeyResult {
+        let mut response = self.get_records_response(name, Some(vec![key]), None, &self.defaults);
+        response.take(key).expect("the key is looked up")
+    }
 
-            if domain_check.is_some() && email_check.is_some() {
-                let mut error_map = HashMap::new();
-                error_map.insert("value".to_string(), None);
-                error_map.insert("error".to_string(), Some(domain_check.unwrap_or(email_check.unwrap())));
-                results.insert(key.to_string(), error_map);
-                continue;
+    /// Like `get_record`, for callers already on a tokio runtime.
+    pub async fn get_record_async(&self, name: &str, key: &str) -> KeyResult {
+        let options = self.snapshot(&self.defaults);
+        let mut response = self.get_records_inner(name, Some(vec![key]), None, &options).await;
+        response.take(key).expect("the key is looked up")
+    }
+
+    /// The `bitcoin-payment` record of `name`.
+    pub fn get_bitcoin_payment(&self, name: &str) -> KeyResult {
+        self.get_record(name, RecordKey::BITCOIN_PAYMENT.as_str())
+    }
+
+    /// The `nostr` record of `name`.
+    pub fn get_nostr(&self, name: &str) -> KeyResult {
+        self.get_record(name, RecordKey::NOSTR.as_str())
+    }
+
+    /// Looks up the well-known records of `name` and parses each into its
+    /// typed field.
+    pub fn resolve_profile(&self, name: &str) -> SelfieProfile {
+        self.resolve_profile_with(name, None, None, &self.defaults)
+    }
+
+    /// Like `resolve_profile`, looking up `filters` instead of the default
+    /// keys. Keys outside the well-known set are returned in `extra`.
+    pub fn resolve_profile_with(&self, name: &str, filters: Option<Vec<&str>>, dns_server: Option<&str>, options: &LookupOptions) -> SelfieProfile {
+        let response = self.get_records_response(name, filters, dns_server, options);
+        let resolver = dns_server.unwrap_or(self.resolver_label);
+        #[cfg(feature = "dnssec")]
+        let dnssec_required = self.validation.required;
+        #[cfg(not(feature = "dnssec"))]
+        let dnssec_required = false;
+        SelfieProfile::from_response(name, &response, resolver, dnssec_required, time::now())
+    }
+
+    /// Checks that `name` publishes `expected` as one of its `key` records,
+    /// e.g. to confirm that a record a user was asked to add has
+    /// propagated. Compares exactly; see `verify_record_with`.
+    pub fn verify_record(&self, name: &str, key: &str, expected: &str) -> verify::VerificationResult {
+        self.verify_record_with(name, key, expected, verify::Comparison::Exact)
+    }
+
+    /// Like `verify_record`, comparing each record with `comparison`.
+    pub fn verify_record_with(&self, name: &str, key: &str, expected: &str, comparison: verify::Comparison) -> verify::VerificationResult {
+        self.verify_record_with_options(name, key, expected, comparison, &self.defaults)
+    }
+
+    /// Like `verify_record_with`, with per-call `options`, e.g.
+    /// `bypass_cache(true)` so that a propagation check always asks DNS.
+    pub fn verify_record_with_options(
+        &self,
+        name: &str,
+        key: &str,
+        expected: &str,
+        comparison: verify::Comparison,
+        options: &LookupOptions,
+    ) -> verify::VerificationResult {
+        let response = self.get_records_response(name, Some(vec![key]), None, options);
+        let result = response.get(key).expect("the key is looked up");
+        let value = match result.entry() {
+            RecordEntry::Found { value } => value,
+            RecordEntry::NotFound => return verify::VerificationResult::NotFound,
+            RecordEntry::Error(e) => return verify::VerificationResult::Error(e.clone()),
+        };
+        // Overrides have no records, only their value.
+        let actual = match result.raw_records.is_empty() {
+            true => vec![value.to_string()],
+            false => result.values(),
+        };
+        match actual.iter().any(|actual| comparison.matches(expected, actual)) {
+            true => verify::VerificationResult::Match,
+            false => verify::VerificationResult::MismatchFound { actual },
+        }
+    }
+
+    /// Checks every record of `manifest`, at most `concurrency(..)` lookups
+    /// at a time; see `verify::Manifest::verify` to pass options.
+    pub fn verify_manifest(&self, manifest: &verify::Manifest) -> verify::ManifestReport {
+        manifest.verify(self, &self.defaults)
+    }
+
+    /// Looks up the BIP-353 payment instruction of `address`, e.g.
+    /// `₿alice@example.com`. Fails with `MultipleRecords` when more than one
+    /// record is a `bitcoin:` URI and with `NotABitcoinUri` when none is.
+    /// The instruction is only DNSSEC-validated with `require_dnssec` or
+    /// `report_dnssec`.
+    pub fn get_bip353(&self, address: &str) -> Result<Bip353Instruction, SelfieError> {
+        let key = "bitcoin-payment";
+        let response = self.get_records_response(address, Some(vec![key]), None, &self.defaults);
+        let result = response.get(key).expect("the key is looked up");
+        if result.value.is_none() {
+            return Err(result.error.clone().unwrap_or(SelfieError::NoRecords));
+        }
+        Bip353Instruction::from_records(result.values(), result.authenticated)
+    }
+
+    /// Looks up the `nostr` record of the address `name` and compares its
+    /// key with the NIP-05 entry for it. Bare domains are `NotApplicable`;
+    /// a missing or malformed record, or a `nostr.json` that cannot be
+    /// fetched or read, is an error.
+    #[cfg(feature = "nip05")]
+    pub fn cross_check_nip05(&self, name: &str) -> Result<nip05::Nip05Check, SelfieError> {
+        let Identifier::Email { local, domain } = self.identifier(name, false)? else {
+            return Ok(nip05::Nip05Check::NotApplicable);
+        };
+        let response = self.get_records_response(name, Some(vec!["nostr"]), None, &self.defaults);
+        let result = response.get("nostr").expect("the key is looked up");
+        let Some(value) = &result.value else {
+            return Err(result.error.clone().unwrap_or(SelfieError::NoRecords));
+        };
+        let dns: NostrKey = value.parse()?;
+        let document = self.runtime.block_on(self.nip05.fetch(&domain, &local))?;
+        nip05::compare(dns.public_key, &document, &local)
+    }
+
+    /// Fetches the key the `pgp` records of `name` point at, through the
+    /// configured key source or else a default `HttpPgpKeySource`, and
+    /// checks it against the fingerprint records, if any. Fails with
+    /// `FingerprintMismatch` when no fetched key has one of them.
+    #[cfg(feature = "http")]
+    pub fn fetch_and_verify_pgp(&self, name: &str) -> Result<linkage::PgpVerification, SelfieError> {
+        let identifier = self.identifier(name, false)?;
+        let response = self.get_records_response(name, Some(vec![linkage::PGP_RECORD]), None, &self.defaults);
+        let result = response.get(linkage::PGP_RECORD).expect("the key is looked up");
+        if result.value.is_none() {
+            return Err(result.error.clone().unwrap_or(SelfieError::NoRecords));
+        }
+        let records = result.values().iter().map(|value| value.parse()).collect::<Result<Vec<PgpRecord>, _>>()?;
+        let url = records.iter().find_map(|record| match record {
+            PgpRecord::Url(url) => Some(url.clone()),
+            _ => None,
+        });
+        let Some(url) = url else {
+            return Err(SelfieError::InvalidRecord { key: linkage::PGP_RECORD.to_string(), reason: "no key URL".to_string() });
+        };
+        let expected: Vec<String> = records
+            .iter()
+            .filter_map(|record| match record {
+                PgpRecord::Fingerprint(fingerprint) => Some(fingerprint.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let locator = linkage::KeyLocator::Url(url.clone());
+        let source = self.pgp_keys.clone().unwrap_or_else(|| Arc::new(linkage::HttpPgpKeySource::new()));
+        let data = self.runtime.block_on(source.fetch_key(&locator, &identifier))?;
+        let keys = openpgp::parse_key_block(&data)
+            .map_err(|reason| SelfieError::InvalidRecord { key: linkage::PGP_RECORD.to_string(), reason: format!("key at {}: {}", url, reason) })?;
+        let mut fingerprints: Vec<String> = keys.iter().map(openpgp::PublicKey::fingerprint_hex).collect();
+        fingerprints.dedup();
+        let matched = expected.iter().find(|fingerprint| fingerprints.contains(fingerprint)).cloned();
+        if !expected.is_empty() && matched.is_none() {
+            return Err(SelfieError::FingerprintMismatch { expected, fetched: fingerprints });
+        }
+        Ok(linkage::PgpVerification { url, fingerprints, matched })
+    }
+
+    /// Checks the default records of `name` against 1.1.1.1 and 8.8.8.8;
+    /// see `health::HealthCheck` for other keys and servers.
+    pub fn health_check(&self, name: &str) -> health::HealthReport {
+        health::HealthCheck::new(name).run(self)
+    }
+
+    /// Looks up every key of `keys` for every name of `names`, at most
+    /// `options.concurrency(..)` lookups at a time. Names whose record has
+    /// the same owner name share one lookup.
+    pub fn resolve_matrix(&self, names: &[&str], keys: &[&str], options: &LookupOptions) -> MatrixResponse {
+        let started = Instant::now();
+        let mut cells = self.matrix_cells(names, keys, options);
+        let mut response = MatrixResponse::default();
+        for cell in &mut cells {
+            response.insert(cell);
+        }
+        response.lookups = cells.lookups();
+        response.duration = started.elapsed();
+        response
+    }
+
+    /// Like `resolve_matrix`, yielding each cell as soon as its lookup
+    /// finishes, e.g. to report progress.
+    pub fn matrix_cells(&self, names: &[&str], keys: &[&str], options: &LookupOptions) -> MatrixCells<'_> {
+        MatrixCells::new(self, names, keys, self.snapshot(options).without_progress())
+    }
+
+    /// Looks up `filters`, or the default keys, of each of `names`, at most
+    /// `options.concurrency(..)` lookups at a time, sharing lookups and the
+    /// cache like `resolve_matrix`. Names given twice are looked up once,
+    /// and a failure only fails the results it belongs to. Every name
+    /// has a response, by the name as given.
+    pub fn get_records_batch(&self, names: &[&str], filters: Option<Vec<&str>>, options: &LookupOptions) -> HashMap<String, RecordsResponse> {
+        self.get_records_batch_with_progress(names, filters, options, |_, _| {})
+    }
+
+    /// Like `get_records_batch`, calling `on_progress(done, total)` each
+    /// time all results of another of the `total` distinct names are in.
+    pub fn get_records_batch_with_progress(
+        &self,
+        names: &[&str],
+        filters: Option<Vec<&str>>,
+        options: &LookupOptions,
+        mut on_progress: impl FnMut(usize, usize) + Send,
+    ) -> HashMap<String, RecordsResponse> {
+        let keys = filters.unwrap_or_else(|| self.default_keys());
+        let options = self.snapshot(options).without_progress();
+        self.runtime.block_on(matrix::batch(self, names, &keys, &options, &mut on_progress))
+    }
+
+    /// Like `get_records_batch`, for callers already on a tokio runtime.
+    pub async fn get_records_batch_async(&self, names: &[&str], filters: Option<Vec<&str>>, options: &LookupOptions) -> HashMap<String, RecordsResponse> {
+        let keys = filters.unwrap_or_else(|| self.default_keys());
+        let options = self.snapshot(options).without_progress();
+        matrix::batch(self, names, &keys, &options, &mut |_, _| {}).await
+    }
+
+    /// The owner name a lookup of `key` for `name` queries, `None` when
+    /// it would not query one: the name is invalid or overridden.
+    fn query_name(&self, name: &str, key: &str) -> Option<String> {
+        self.versioned_query_name(name, key, self.record_version)
+    }
+
+    /// Like `query_name`, for the name of record version `version`.
+    fn versioned_query_name(&self, name: &str, key: &str, version: Option<u32>) -> Option<String> {
+        let identifier = self.identifier(name, self.resolver.resolves_onion()).ok()?;
+        if self.overrides.get(&identifier, key).is_some() {
+            return None;
+        }
+        self.record_key(&identifier, key, version).ok()
+    }
+
+    /// Opens connections to the endpoints lookups go to, the resolver and
+    /// those of routing rules, so that the first lookup does not wait for
+    /// them. Calling it again only opens connections that were closed
+    /// since. Only backends keeping connections, such as `DohResolver`,
+    /// report on their endpoint.
+    pub fn warm_up(&self) -> Vec<Readiness> {
+        self.warm_up_probing(None)
+    }
+
+    /// Like `warm_up`, also sending a throwaway TXT query for `probe`, such
+    /// as `_bitcoin-payment.example.com`, to each endpoint so that its
+    /// cache holds the answer.
+    pub fn warm_up_probing(&self, probe: Option<&str>) -> Vec<Readiness> {
+        if self.is_offline() {
+            return Vec::new();
+        }
+        let mut resolvers: Vec<&Arc<dyn TxtResolver>> = Vec::new();
+        for resolver in std::iter::once(&self.resolver).chain(self.routes.resolvers()) {
+            if !resolvers.iter().any(|seen| Arc::ptr_eq(seen, resolver)) {
+                resolvers.push(resolver);
+            }
+        }
+        self.runtime.block_on(async {
+            let mut ready = Vec::new();
+            for resolver in resolvers {
+                ready.extend(resolver.warm_up(probe).await);
             }
+            ready
+        })
+    }
+
+    /// Runs `warm_up` on a thread of its own, for callers that do not wait
+    /// for it.
+    pub fn warm_up_in_background(self: &Arc<Self>) -> std::thread::JoinHandle<Vec<Readiness>> {
+        let sdk = self.clone();
+        std::thread::spawn(move || sdk.warm_up())
+    }
 
-            let domain_name = self.get_txt_record_key(name, key);
-            debug!("Resolving TXT record for: {}", domain_name);
-
-            match self.resolve_txt(&domain_name) {
-                Ok(answers) => {
-                    if answers.is_empty() {
-                        let mut error_map = HashMap::new();
-                        error_map.insert("value".to_string(), None);
-                        error_map.insert("error".to_string(), Some("No TXT records found".to_string()));
-                        results.insert(key.to_string(), error_map);
-                    } else {
-                        let value = answers.join(" ");
-                        let mut success_map = HashMap::new();
-                        success_map.insert("value".to_string(), Some(value));
-                        success_map.insert("error".to_string(), None);
-                        results.insert(key.to_string(), success_map);
+    /// Looks up `filters`, or the default keys, of `name` now and then every
+    /// `interval`, on a thread of its own, calling `on_change` with each
+    /// value that appears, changes or disappears. Failed lookups are not
+    /// taken for removals.
+    pub fn watch(
+        self: &Arc<Self>,
+        name: &str,
+        filters: Option<Vec<&str>>,
+        interval: Duration,
+        on_change: impl FnMut(watch::ChangeEvent) + Send + 'static,
+    ) -> watch::RecordWatcher {
+        self.watch_with(name, filters, &watch::WatchOptions::new(interval), on_change)
+    }
+
+    pub fn watch_with(
+        self: &Arc<Self>,
+        name: &str,
+        filters: Option<Vec<&str>>,
+        options: &watch::WatchOptions,
+        on_change: impl FnMut(watch::ChangeEvent) + Send + 'static,
+    ) -> watch::RecordWatcher {
+        watch::RecordWatcher::spawn(self, name, filters, options, on_change)
+    }
+
+    /// Like `watch`, polling on the caller's tokio runtime and yielding the
+    /// changes from the returned stream.
+    pub fn watch_async(self: &Arc<Self>, name: &str, filters: Option<Vec<&str>>, interval: Duration) -> watch::ChangeStream {
+        self.watch_async_with(name, filters, &watch::WatchOptions::new(interval))
+    }
+
+    pub fn watch_async_with(self: &Arc<Self>, name: &str, filters: Option<Vec<&str>>, options: &watch::WatchOptions) -> watch::ChangeStream {
+        watch::ChangeStream::spawn(self, name, filters, options)
+    }
+
+    /// Switches offline mode for calls starting from now. While offline the
+    /// SDK never opens a socket: only overrides and cached answers, stale
+    /// ones included, are served, and everything else fails with
+    /// `SelfieError::Offline`. Calls already running keep the mode they
+    /// started with.
+    pub fn set_offline(&self, offline: bool) {
+        self.offline.store(offline, Ordering::SeqCst);
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline.load(Ordering::SeqCst)
+    }
+
+    /// Tells `observer` of every query lookups send and every answer they
+    /// take from the cache, replacing any observer set before.
+    pub fn set_observer(&self, observer: Arc<dyn Observer + Send + Sync>) {
+        self.observer.set(observer);
+    }
+
+    /// Forgets every cached answer, stale ones included, so that the next
+    /// lookups query again.
+    pub fn clear_cache(&self) {
+        self.cache.clear();
+    }
+
+    /// The resolver a call's `dns_server` names: a DoH endpoint URL, a DoT
+    /// server, an IPv4 or IPv6 address, or a host name, with an optional
+    /// port, 53 by default. A host name is looked up with the system
+    /// resolver first, unless offline or left to the proxy, and its servers
+    /// are asked in turn; failing to look it up fails the call. Without the
+    /// `tls` feature naming a DoT server fails the call, as does anything
+    /// else.
+    async fn explicit_resolver(&self, server: &str, options: &LookupOptions) -> Result<Arc<dyn TxtResolver>, SelfieError> {
+        let addresses = match server.parse()? {
+            #[cfg(feature = "tls")]
+            NameserverSpec::Tls(server) => {
+                info!("Using DoT server {}", server);
+                let transport = TlsTransport::new(server);
+                return Ok(Arc::new(DirectResolver::with_transport(Arc::new(match &self.proxy {
+                    Some(proxy) => transport.proxy(proxy.clone()),
+                    None => transport,
+                }))));
+            }
+            #[cfg(not(feature = "tls"))]
+            NameserverSpec::Tls(server) => return Err(SelfieError::Resolver(format!("DNS-over-TLS to {} needs the tls feature", server))),
+            NameserverSpec::Doh(url) => {
+                info!("Using DoH endpoint {}", url);
+                let resolver = doh::DohResolver::new(&url)?;
+                return Ok(Arc::new(match &self.proxy {
+                    Some(proxy) => resolver.proxy(proxy.clone()),
+                    None => resolver,
+                }));
+            }
+            NameserverSpec::Address(address) => vec![address],
+            NameserverSpec::Host { host, port } if options.get_offline() => return Ok(Arc::new(routing::UnresolvedHost { host, port })),
+            NameserverSpec::Host { host, port } => {
+                if let Some(proxy) = &self.proxy {
+                    return Ok(Arc::new(DirectResolver::with_transport(Arc::new(Socks5Transport::to_host(proxy.clone(), &host, port)))));
+                }
+                let timeout = options.time_left().map_or(options.get_timeout(), |left| left.min(options.get_timeout()));
+                let addresses = routing::bootstrap(&host, port, timeout).await?;
+                debug!("Resolved nameserver {} to {:?}", host, addresses);
+                addresses
+            }
+        };
+        info!("Using DNS server {}", addresses.iter().map(SocketAddr::to_string).collect::<Vec<_>>().join(","));
+        Ok(routing::nameserver_resolver(&addresses, self.transport, self.proxy.as_ref(), None, None))
+    }
+
+    /// The keys of calls that name none.
+    pub(crate) fn default_keys(&self) -> Vec<&str> {
+        self.default_keys.iter().map(String::as_str).collect()
+    }
+
+    /// Fixes the SDK-wide offline mode into a call's options, so that the
+    /// whole call sees one mode.
+    fn snapshot(&self, options: &LookupOptions) -> LookupOptions {
+        let offline = options.get_offline() || self.is_offline();
+        options.clone().offline(offline).start_deadline()
+    }
+
+    async fn get_records_inner(&self, name: &str, filters: Option<Vec<&str>>, dns_server: Option<&str>, options: &LookupOptions) -> RecordsResponse {
+        let filters = filters.unwrap_or_else(|| self.default_keys());
+        let options = &options.for_call().on_server(dns_server);
+
+        let mut results = RecordsResponse::with_capacity(filters.len());
+
+        let resolver = match dns_server {
+            Some(server) => self.explicit_resolver(server, options).await,
+            None => Ok(self.resolver.clone()),
+        };
+        let identified = resolver.and_then(|resolver| Ok((self.identifier(name, resolver.resolves_onion())?, resolver)));
+
+        let (identifier, resolver) = match identified {
+            Ok(identified) => identified,
+            Err(e) => {
+                error!("Error processing {}: {}", name, e);
+                for key in filters.iter() {
+                    let entry = KeyResult::error(e.clone());
+                    if let Some(reporter) = options.reporter() {
+                        reporter.started(key);
+                        reporter.finished(key, &entry);
+                    }
+                    results.insert(key, entry);
+                }
+                if let Some(reporter) = options.reporter() {
+                    reporter.done(&results);
+                }
+                return results;
+            }
+        };
+
+        #[cfg(feature = "signatures")]
+        let mut verification_keys = None;
+
+        let schemes = self.name_schemes(options);
+        for requested in filters.iter() {
+            if let Some(reporter) = options.reporter() {
+                reporter.started(requested);
+            }
+            // Results stay under the key as passed, queries use it lowercased.
+            let record_key = match RecordKey::custom(requested) {
+                Ok(record_key) => record_key,
+                Err(e) => {
+                    error!("Error processing {}: {}", requested, e);
+                    let entry = KeyResult::error(e);
+                    if let Some(reporter) = options.reporter() {
+                        reporter.finished(requested, &entry);
                     }
+                    results.insert(requested, entry);
+                    continue;
                 }
+            };
+            let key = record_key.as_str();
+            if let Some(value) = self.overrides.get(&identifier, key) {
+                debug!("Using override for {} {}", identifier, key);
+                let mut entry = KeyResult {
+                    value: Some(value.to_string()),
+                    raw_value: Some(value.as_bytes().to_vec()),
+                    raw_records: vec![RawRecord::new([value])],
+                    source: Some(Source::Override),
+                    offline: options.get_offline(),
+                    ..KeyResult::default()
+                };
+                self.parsers.apply(key, &mut entry);
+                if let Some(reporter) = options.reporter() {
+                    reporter.finished(requested, &entry);
+                }
+                results.insert(requested, entry);
+                continue;
+            }
+            let started = Instant::now();
+            let budget = options.get_key_timeout(key);
+            let (route, mut key_resolver) = self.routes.resolve(key, identifier.domain(), &resolver);
+            let mut found = self.resolve_key(key_resolver.as_ref(), &identifier, key, &schemes, budget, options).await;
+            let unreachable = matches!(&found, Ok((_, _, _, resolved)) if resolved.answers.as_ref().is_err_and(SelfieError::is_unreachable));
+            let used_fallback_resolver = unreachable && self.fallback_to_default && dns_server.is_some() && route == Route::Default;
+            if used_fallback_resolver {
+                warn!("{} is unreachable, looking {} up through the default resolver", key_resolver.describe(), key);
+                key_resolver = &self.resolver;
+                found = self.resolve_key(key_resolver.as_ref(), &identifier, key, &schemes, budget, options).await;
+            }
+            let rtt = started.elapsed();
+            let (scheme, domain_name, record_version, resolved) = match found {
+                Ok(found) => found,
                 Err(e) => {
                     error!("Error processing {}: {}", key, e);
-                    results.insert(key.to_string(), self.handle_error(key, &e));
+                    let entry = KeyResult { route: Some(route), resolver: Some(key_resolver.describe()), ..KeyResult::error(e) };
+                    if let Some(reporter) = options.reporter() {
+                        reporter.finished(requested, &entry);
+                    }
+                    results.insert(requested, entry);
+                    continue;
+                }
+            };
+            #[cfg(feature = "dnssec")]
+            let (resolved, authenticated) = match resolved.answers {
+                // The chain of trust cannot be fetched offline.
+                Ok(answers) if self.validation.required && !answers.is_empty() && options.get_offline() => {
+                    (Resolved { answers: Err(SelfieError::Offline), ..resolved }, false)
+                }
+                Ok(answers) if self.validation.required && !answers.is_empty() => match self.validation.validate_txt(&domain_name, &answers).await {
+                    Ok(()) => (Resolved { answers: Ok(answers), ..resolved }, true),
+                    Err(e) => (Resolved { answers: Err(e), ..resolved }, false),
+                },
+                Ok(answers) if self.validation.reported && !answers.is_empty() && !options.get_offline() => {
+                    let authenticated = self.validation.validate_txt(&domain_name, &answers).await.is_ok();
+                    (Resolved { answers: Ok(answers), ..resolved }, authenticated)
+                }
+                answers => (Resolved { answers, ..resolved }, false),
+            };
+            #[cfg(not(feature = "dnssec"))]
+            let authenticated = false;
+            let mut entry = KeyResult {
+                source: Some(resolved.source),
+                stale: resolved.stale,
+                offline: options.get_offline(),
+                attempts: Some(resolved.attempts),
+                backoff: Some(resolved.backoff),
+                wire: resolved.wire,
+                record_version,
+                name_template: schemes[scheme].template(&identifier).map(str::to_string),
+                route: Some(route),
+                resolver: Some(key_resolver.describe()),
+                used_fallback_resolver,
+                answered_by: resolved.wire.and_then(|wire| wire.answered_by()),
+                authenticated,
+                query: Some(QueryMeta {
+                    fqdn: domain_name.clone(),
+                    nameserver: resolved.wire.and_then(|wire| wire.answered_by()).map(|server| server.address).or_else(|| {
+                        // Resolvers over several servers describe themselves as a list.
+                        key_resolver.describe().split(',').next().and_then(|first| first.parse().ok())
+                    }),
+                    rtt,
+                    attempts: resolved.attempts,
+                }),
+                ..KeyResult::default()
+            };
+            match resolved.answers {
+                Ok(answers) if answers.is_empty() => {
+                    let budget = budget.map(|budget| budget.saturating_sub(started.elapsed()));
+                    entry.error = Some(self.not_found(key_resolver.as_ref(), &identifier, key, resolved.nxdomain, budget, options).await);
                 }
+                Ok(records) => {
+                    entry.resolved_at = resolved.resolved_at;
+                    entry.ttl = resolved.ttl.map(|ttl| Duration::from_secs(ttl.into()));
+                    if let Some(checker) = self.cross_check.as_ref().filter(|_| resolved.source == Source::Dns) {
+                        let timeout = match budget {
+                            Some(budget) => budget.saturating_sub(started.elapsed()),
+                            None => options.get_timeout(),
+                        };
+                        let outcome = match options.take_query() {
+                            Ok(()) => checker.check(&domain_name, &encoding::lossy(&records), timeout).await,
+                            Err(e) => cross_check::CrossCheck::Unavailable { reason: e.to_string() },
+                        };
+                        if let cross_check::CrossCheck::Mismatch { other_values } = &outcome {
+                            // A disputed answer must not be served unchecked from the cache later.
+                            self.cache.remove(&options.cache_key(&domain_name));
+                            if checker.strict {
+                                let e = SelfieError::CrossCheckMismatch { other_values: other_values.clone() };
+                                error!("Error processing {}: {}", key, e);
+                                entry.error = Some(e);
+                            }
+                        }
+                        entry.cross_check = Some(outcome);
+                    }
+                    let manifest = self.chunked_keys.iter().any(|chunked| chunked == key).then(|| {
+                        let value = encoding::joined(&records);
+                        chunks::ChunkManifest::parse(&String::from_utf8_lossy(&value))
+                    });
+                    let records = match manifest.flatten() {
+                        None => records,
+                        Some(Err(reason)) => {
+                            let e = SelfieError::InvalidRecord { key: key.to_string(), reason };
+                            error!("Error processing {}: {}", key, e);
+                            entry.error = Some(e);
+                            records
+                        }
+                        Some(Ok(manifest)) => {
+                            let budget = budget.map(|budget| budget.saturating_sub(started.elapsed()));
+                            let names: Result<Vec<String>, SelfieError> = (1..=manifest.count)
+                                .map(|index| self.record_key_with(schemes[scheme], &identifier, &chunks::chunk_key(key, index), record_version))
+                                .collect();
+                            let payload = match names {
+                                Ok(names) => self.fetch_chunks(key_resolver.as_ref(), &identifier, key, &names, budget, options).await,
+                                Err(e) => Err(e),
+                            };
+                            let payload = payload.and_then(|payload| match manifest.matches(&payload) {
+                                true => Ok(payload),
+                                false => Err(SelfieError::ChunkDigestMismatch),
+                            });
+                            match payload {
+                                Ok(payload) => {
+                                    entry.chunked = true;
+                                    vec![RawRecord::new([payload])]
+                                }
+                                Err(e) => {
+                                    error!("Error processing {}: {}", key, e);
+                                    entry.error = Some(e);
+                                    records
+                                }
+                            }
+                        }
+                    };
+                    if records.len() > 1 && encoding::PAYMENT_KEYS.contains(&key) {
+                        warn!("{} of {} has {} records", key, identifier, records.len());
+                    }
+                    // The text is a view of the bytes, which are kept as well.
+                    let raw_value = encoding::joined(&records);
+                    let value = String::from_utf8_lossy(&raw_value).into_owned();
+                    entry.encoding_issue = encoding::ValueEncodingIssue::of(&raw_value, &value);
+                    if let Some(issue) = entry.encoding_issue {
+                        warn!("{} record of {} is {}", key, identifier, issue);
+                        if self.strict_encoding && encoding::PAYMENT_KEYS.contains(&key) {
+                            let e = SelfieError::InvalidRecord { key: key.to_string(), reason: format!("value is {}", issue) };
+                            error!("Error processing {}: {}", key, e);
+                            entry.error = Some(e);
+                        }
+                    }
+                    #[cfg(feature = "signatures")]
+                    if let Some(policy) = options.get_signatures() {
+                        entry.signature = self.check_signature(&resolver, &identifier, &value, &mut verification_keys, options).await;
+                        if policy.strict && entry.signature == Some(signature::SignatureStatus::Invalid) {
+                            let e = SelfieError::InvalidSignature;
+                            error!("Error processing {}: {}", key, e);
+                            entry.error = Some(e);
+                        }
+                    }
+                    match self.sanitization.apply(&raw_value, value) {
+                        Ok(value) if entry.error.is_none() => {
+                            entry.value = Some(value);
+                            entry.raw_value = Some(raw_value);
+                            entry.raw_records = records;
+                        }
+                        Err(issue) if entry.error.is_none() => {
+                            let e = SelfieError::InvalidRecord { key: key.to_string(), reason: format!("value is {}", issue) };
+                            error!("Error processing {}: {}", key, e);
+                            entry.error = Some(e);
+                        }
+                        _ => {}
+                    }
+                }
+                Err(e) => {
+                    error!("Error processing {}: {}", key, e);
+                    entry.error = Some(e);
+                }
+            }
+            self.parsers.apply(key, &mut entry);
+            if let Some(reporter) = options.reporter() {
+                reporter.finished(requested, &entry);
             }
+            results.insert(requested, entry);
         }
 
+        results.queries_issued = options.queries_issued();
+        if let Some(reporter) = options.reporter() {
+            reporter.done(&results);
+        }
         results
     }
 
-    fn resolve_txt(&self, name: &str) -> Result<Vec<String>, String> {
-        match self.resolver.txt_lookup(name) {
-            Ok(lookup) => Ok(lookup.iter().map(|txt| txt.to_string()).collect()),
-            Err(e) => Err(format!("Error resolving TXT record for {}: {:?}", name, e)),
+    /// Traces the DNSSEC chain of trust for `qname` from the configured
+    /// trust anchors.
+    #[cfg(feature = "dnssec")]
+    pub fn dnssec_trace(&self, qname: &str) -> Result<dnssec::DnssecTrace, SelfieError> {
+        if self.is_offline() {
+            return Err(SelfieError::Offline);
+        }
+        let validation = &self.validation;
+        self.runtime
+            .block_on(dnssec::trace(validation.source.as_ref(), qname, &validation.anchors, time::now()))
+    }
+
+    /// Looks up the `key` record of `name`, at its unversioned name, with
+    /// the proof of its chain of trust from the configured trust anchors,
+    /// for checking elsewhere with `dnssec::verify_proof`.
+    #[cfg(feature = "dnssec")]
+    pub fn get_record_with_proof(&self, name: &str, key: &str) -> Result<dnssec::ProvenRecord, dnssec::ProofError> {
+        if self.is_offline() {
+            return Err(SelfieError::Offline.into());
+        }
+        let record_key = RecordKey::custom(key)?;
+        let qname = self.record_key(&self.identifier(name, false)?, record_key.as_str(), None)?;
+        let validation = &self.validation;
+        self.runtime
+            .block_on(dnssec::prove(validation.source.as_ref(), &qname, &validation.anchors, time::now()))
+    }
+
+    /// Checks that `name`'s payment record is signed by the key its `pgp`
+    /// record refers to. Missing or broken pieces are reported per step and
+    /// leave the result `NotAttested`.
+    #[cfg(feature = "signatures")]
+    pub fn verify_linked(&self, name: &str) -> linkage::LinkageReport {
+        self.runtime.block_on(self.verify_linked_inner(name, &self.snapshot(&self.defaults)))
+    }
+
+    #[cfg(feature = "signatures")]
+    async fn verify_linked_inner(&self, name: &str, options: &LookupOptions) -> linkage::LinkageReport {
+        use linkage::{LinkageStatus, StepOutcome};
+
+        let mut report = linkage::LinkageReport::new();
+        let identifier = match self.identifier(name, self.resolver.resolves_onion()) {
+            Ok(identifier) => identifier,
+            Err(e) => {
+                report.payment_record = StepOutcome::Failed(e.to_string());
+                return report;
+            }
+        };
+
+        let payment = match self.fetch_linked(&identifier, linkage::PAYMENT_RECORD, options).await {
+            Ok(answers) => answers.join(" "),
+            Err(e) => {
+                report.payment_record = StepOutcome::Failed(e.to_string());
+                return report;
+            }
+        };
+        report.payment_record = StepOutcome::Passed;
+        report.payment_uri = Some(payment.clone());
+
+        let signature = match self.fetch_linked(&identifier, linkage::PAYMENT_SIGNATURE_RECORD, options).await {
+            Ok(answers) => answers
+                .iter()
+                .find_map(|answer| openpgp::decode_text(answer).and_then(|data| openpgp::parse_signature(&data)).ok()),
+            Err(e) => {
+                report.signature_record = StepOutcome::Failed(e.to_string());
+                return report;
+            }
+        };
+        let Some(signature) = signature else {
+            report.signature_record = StepOutcome::Failed("no OpenPGP signature in record".to_string());
+            return report;
+        };
+        report.signature_record = StepOutcome::Passed;
+
+        let keys = match self.fetch_linked(&identifier, linkage::PGP_RECORD, options).await {
+            Ok(answers) => self.linked_keys(&identifier, &answers, options).await,
+            Err(e) => Err(e.to_string()),
+        };
+        let keys = match keys {
+            Ok(keys) => keys,
+            Err(reason) => {
+                report.pgp_key = StepOutcome::Failed(reason);
+                return report;
+            }
+        };
+        report.pgp_key = StepOutcome::Passed;
+
+        match openpgp::verify(&keys, &signature, payment.as_bytes()) {
+            Some(key) => {
+                report.signature = StepOutcome::Passed;
+                report.status = LinkageStatus::Attested { fingerprint: key.fingerprint_hex() };
+            }
+            None => report.signature = StepOutcome::Failed("signature does not verify with the published key".to_string()),
         }
+        report
     }
 
-    fn get_txt_record_key(&self, name: &str, key: &str) -> String {
-        if name.contains('@') {
-            let parts: Vec<&str> = name.split('@').collect();
-            format!("{}.user._{}.{}", parts[0], key, parts[1])
-        } else {
-            format!("_{}.{}", key, name)
+    #[cfg(feature = "signatures")]
+    async fn fetch_linked(&self, identifier: &Identifier, key: &str, options: &LookupOptions) -> Result<Vec<String>, SelfieError> {
+        if let Some(value) = self.overrides.get(identifier, key) {
+            return Ok(vec![value.to_string()]);
         }
+        let (_, resolver) = self.routes.resolve(key, identifier.domain(), &self.resolver);
+        let schemes = self.name_schemes(options);
+        let (_, _, _, resolved) = self.resolve_key(resolver.as_ref(), identifier, key, &schemes, options.get_key_timeout(key), options).await?;
+        let records = resolved.answers?;
+        if records.is_empty() {
+            return Err(SelfieError::NoRecords);
+        }
+        Ok(encoding::lossy(&records))
     }
 
-    fn validate_email_address(&self, key: &str, name: &str) -> Option<String> {
-        let email_regex = Regex::new(r"^[^\s@]+@[^\s@]+\.[^\s@]+$").unwrap();
-        if !email_regex.is_match(name) {
-            Some(format!("Invalid email name for key: {}", key))
-        } else {
-            None
+    /// Reads the keys a `pgp` record embeds or, through the configured key
+    /// source, refers to.
+    #[cfg(feature = "signatures")]
+    async fn linked_keys(&self, identifier: &Identifier, answers: &[String], options: &LookupOptions) -> Result<Vec<openpgp::PublicKey>, String> {
+        let mut last_error = "no OpenPGP key or key reference in record".to_string();
+        for answer in answers {
+            if let Ok(keys) = openpgp::decode_text(answer).and_then(|data| openpgp::parse_public_keys(&data)) {
+                return Ok(keys);
+            }
+            let Some(locator) = linkage::KeyLocator::parse(answer) else {
+                continue;
+            };
+            let Some(source) = self.pgp_keys.as_ref() else {
+                last_error = "record refers to a key but no key source is configured".to_string();
+                continue;
+            };
+            if options.get_offline() {
+                last_error = SelfieError::Offline.to_string();
+                continue;
+            }
+            let data = match source.fetch_key(&locator, identifier).await {
+                Ok(data) => data,
+                Err(e) => {
+                    last_error = e.to_string();
+                    continue;
+                }
+            };
+            let keys = openpgp::parse_key_block(&data);
+            match (keys, &locator) {
+                (Ok(keys), linkage::KeyLocator::Fingerprint(fingerprint))
+                    if !keys.iter().any(|key| key.fingerprint_hex() == *fingerprint) =>
+                {
+                    last_error = format!("fetched key does not match fingerprint {}", fingerprint);
+                }
+                (Ok(keys), _) => return Ok(keys),
+                (Err(e), _) => last_error = e,
+            }
         }
+        Err(last_error)
     }
 
-    fn validate_domain_or_subdomain(&self, key: &str, name: &str) -> Option<String> {
-        let domain_regex = Regex::new(r"^(?!:\/\/)([a-zA-Z0-9-_]+(\.[a-zA-Z0-9-_]+)+.*)$").unwrap();
-        if !domain_regex.is_match(name) {
-            Some(format!("Invalid domain or subdomain name for key: {}", key))
-        } else {
-            None
+    #[cfg(feature = "signatures")]
+    async fn resolve_txt(&self, resolver: &dyn TxtResolver, identifier: &Identifier, name: &str, budget: Option<Duration>, options: &LookupOptions) -> Result<Vec<String>, SelfieError> {
+        let records = self.resolve_txt_timed(resolver, identifier, signature::VERIFICATION_KEY_RECORD, name, budget, options).await.answers?;
+        Ok(encoding::lossy(&records))
+    }
+
+    /// Resolves `key` of `identifier` under each of `schemes` in turn until
+    /// one has records: at the name for the configured record version,
+    /// then, if that name has no records, at the unversioned name. Errors
+    /// other than a missing record are returned as they are, without
+    /// falling back. Returns the index of the scheme that answered, the
+    /// last one when none had records, the name that answered and its
+    /// version.
+    async fn resolve_key(
+        &self,
+        resolver: &dyn TxtResolver,
+        identifier: &Identifier,
+        key: &str,
+        schemes: &[&dyn NameScheme],
+        budget: Option<Duration>,
+        options: &LookupOptions,
+    ) -> Result<(usize, String, Option<u32>, Resolved), SelfieError> {
+        let started = Instant::now();
+        let (mut attempts, mut backoff) = (0, Duration::ZERO);
+        let mut unanswered = None;
+        let mut invalid = None;
+        for (index, &scheme) in schemes.iter().enumerate() {
+            let mut names = Vec::with_capacity(2);
+            // A versioned name too long to be valid cannot hold the record.
+            if let Some(version) = self.record_version {
+                if let Ok(name) = self.record_key_with(scheme, identifier, key, Some(version)) {
+                    names.push((name, Some(version)));
+                }
+            }
+            match self.record_key_with(scheme, identifier, key, None) {
+                Ok(name) => names.push((name, None)),
+                Err(e) => {
+                    invalid.get_or_insert(e);
+                }
+            }
+            for (name, version) in names {
+                debug!("Resolving TXT record for: {}", name);
+                // The key's budget covers every name.
+                let budget = budget.map(|budget| match unanswered {
+                    Some(_) => budget.saturating_sub(started.elapsed()),
+                    None => budget,
+                });
+                let mut resolved = self.resolve_txt_timed(resolver, identifier, key, &name, budget, options).await;
+                resolved.attempts += attempts;
+                resolved.backoff += backoff;
+                if !matches!(&resolved.answers, Ok(answers) if answers.is_empty()) {
+                    return Ok((index, name, version, resolved));
+                }
+                debug!("No records at {}, trying the next name", name);
+                (attempts, backoff) = (resolved.attempts, resolved.backoff);
+                unanswered = Some((index, name, version, resolved));
+            }
+        }
+        match unanswered {
+            Some(unanswered) => Ok(unanswered),
+            None => Err(invalid.expect("a scheme without names failed to build one")),
         }
     }
 
-    fn handle_error(&self, key: &str, error: &str) -> HashMap<String, Option<String>> {
-        let mut error_map = HashMap::new();
-        error_map.insert("value".to_string(), None);
-        error_map.insert("error".to_string(), Some(error.to_string()));
-        error_map
+    /// The name schemes lookups with `options` try, in order.
+    fn name_schemes<'a>(&'a self, options: &'a LookupOptions) -> Vec<&'a dyn NameScheme> {
+        let templates = match options.get_name_templates() {
+            Some(templates) => return templates.iter().map(|template| template as &dyn NameScheme).collect(),
+            None => self.name_template_fallbacks.iter().map(|template| template as &dyn NameScheme),
+        };
+        std::iter::once(self.name_scheme.as_ref()).chain(templates).collect()
     }
+
+    /// Why a record name has no records: `NxDomain` when it does not exist
+    /// and neither does the name it was looked up for, which takes one more
+    /// query to tell, and `NoRecords` otherwise.
+    async fn not_found(&self, resolver: &dyn TxtResolver, identifier: &Identifier, key: &str, nxdomain: bool, budget: Option<Duration>, options: &LookupOptions) -> SelfieError {
+        if !nxdomain {
+            return SelfieError::NoRecords;
+        }
+        let domain = self.resolve_txt_timed(resolver, identifier, key, identifier.domain(), budget, options).await;
+        match domain.answers {
+            Ok(answers) if answers.is_empty() && domain.nxdomain => SelfieError::NxDomain,
+            _ => SelfieError::NoRecords,
+        }
+    }
+
+    /// Fetches the chunks of `key` at `names`, concurrently, and
+    /// concatenates them.
+    async fn fetch_chunks(&self, resolver: &dyn TxtResolver, identifier: &Identifier, key: &str, names: &[String], budget: Option<Duration>, options: &LookupOptions) -> Result<Vec<u8>, SelfieError> {
+        let lookups = names.iter().map(|name| async move {
+            debug!("Resolving chunk of {}: {}", key, name);
+            self.resolve_txt_timed(resolver, identifier, key, name, budget, options).await.answers
+        });
+        let count = names.len() as u32;
+        let mut payload = Vec::new();
+        for (index, chunk) in (1..).zip(chunks::join_all(lookups.collect()).await) {
+            match chunk?.as_slice() {
+                [] => return Err(SelfieError::MissingChunk { index, count }),
+                [record] => payload.extend_from_slice(record.bytes()),
+                records => {
+                    let reason = format!("chunk {} has {} records", index, records.len());
+                    return Err(SelfieError::InvalidRecord { key: key.to_string(), reason });
+                }
+            }
+        }
+        Ok(payload)
+    }
+
+    /// Answers `name` from the cache while fresh, or offline even when
+    /// stale, and otherwise queries it through `resolver`, sharing the
+    /// result with concurrent callers asking the same resolver for the same
+    /// name. Those callers wait on the first one's lookup and its timeouts.
+    async fn resolve_txt_timed(&self, resolver: &dyn TxtResolver, identifier: &Identifier, record_key: &str, name: &str, budget: Option<Duration>, options: &LookupOptions) -> Resolved {
+        let offline = options.get_offline();
+        let cache_key = options.cache_key(name);
+        match self.cache.get(&cache_key) {
+            Some(hit) if offline || (!hit.stale && !options.get_bypass_cache()) => {
+                debug!("Serving {} from cache{}", name, if hit.stale { " (stale)" } else { "" });
+                self.observer.notify(|observer| observer.on_cache_hit(name));
+                return Resolved {
+                    answers: Ok(hit.answer.records),
+                    resolved_at: hit.answer.resolved_at,
+                    ttl: hit.answer.ttl,
+                    source: Source::Cache,
+                    stale: hit.stale,
+                    nxdomain: hit.answer.nxdomain,
+                    ..Resolved::new()
+                };
+            }
+            _ if offline => return Resolved { answers: Err(SelfieError::Offline), ..Resolved::new() },
+            _ => {}
+        }
+
+        let mut key = String::with_capacity(name.len() + 20);
+        let _ = write!(key, "{:p}/{}", resolver as *const dyn TxtResolver as *const (), name);
+        let mut resolved = self.in_flight.run(&key, || self.resolve_txt_with_retries(resolver, identifier, record_key, name, budget, options)).await;
+        let checked = match &resolved.answers {
+            Ok(answers) => self.response_limits.check(name, answers),
+            Err(_) => Ok(()),
+        };
+        if let Err(e) = checked {
+            // Kept out of the cache too, so it is never served later.
+            warn!("Rejecting the answer for {}: {}", name, e);
+            resolved.answers = Err(e);
+        }
+        if let Ok(answers) = &resolved.answers {
+            let answer = cache::Answer {
+                records: answers.clone(),
+                resolved_at: resolved.resolved_at,
+                ttl: resolved.ttl,
+                nxdomain: resolved.nxdomain,
+            };
+            self.cache.insert(&cache_key, answer);
+        }
+        resolved
+    }
+
+    /// Queries `name`, retrying retryable failures after a jittered
+    /// exponential backoff, or after the delay a rate-limiting server asked
+    /// for. Without a per-key `budget` every attempt gets the global
+    /// timeout; with one, all attempts and the waits between them must
+    /// finish within it. Either way the call's deadline cuts them short.
+    #[cfg_attr(not(feature = "audit"), allow(unused_variables))]
+    async fn resolve_txt_with_retries(&self, resolver: &dyn TxtResolver, identifier: &Identifier, key: &str, name: &str, budget: Option<Duration>, options: &LookupOptions) -> Resolved {
+        let started = Instant::now();
+        let mut resolved = Resolved::new();
+        loop {
+            let attempt_timeout = match budget {
+                Some(budget) => budget.saturating_sub(started.elapsed()),
+                None => options.get_timeout(),
+            };
+            let call_budget = |attempts| TimeoutBudget::Call { deadline: options.get_deadline().unwrap_or_default(), attempts };
+            let (attempt_timeout, cut_by_deadline) = match options.time_left() {
+                Some(left) if left.is_zero() => {
+                    resolved.answers = Err(SelfieError::Timeout(call_budget(resolved.attempts)));
+                    return resolved;
+                }
+                Some(left) if left < attempt_timeout => (left, true),
+                _ => (attempt_timeout, false),
+            };
+            // Waiting for the query limits takes from the attempt's time.
+            let waiting = Instant::now();
+            let permit = match self.limiter.acquire(attempt_timeout).await {
+                Ok(permit) => permit,
+                Err(e) => {
+                    debug!("Not querying {}: {}", name, e);
+                    resolved.answers = Err(e);
+                    return resolved;
+                }
+            };
+            let query_timeout = attempt_timeout.saturating_sub(waiting.elapsed());
+            if let Err(e) = options.take_query() {
+                debug!("Not querying {}: {}", name, e);
+                resolved.answers = Err(e);
+                return resolved;
+            }
+            resolved.attempts += 1;
+            let attempts = resolved.attempts;
+
+            self.observer.notify(|observer| observer.on_query_start(name, key));
+            let (sent_at, attempt_started) = (time::now(), Instant::now());
+            let timed_out = || match budget {
+                _ if cut_by_deadline => SelfieError::Timeout(call_budget(attempts)),
+                Some(budget) => SelfieError::Timeout(TimeoutBudget::Key { budget, attempts }),
+                None => SelfieError::Timeout(TimeoutBudget::Global { timeout: attempt_timeout, attempts }),
+            };
+            let outcome = match time::timeout(query_timeout, resolver.txt_lookup_raw(name)).await {
+                // A resolver giving up on its own timeouts ran out of this
+                // attempt's budget as much as one cut short here.
+                Ok(Err(SelfieError::Timeout(TimeoutBudget::Global { .. }))) | Err(_) => Err(timed_out()),
+                Ok(outcome) => outcome,
+            };
+            drop(permit);
+            self.observer.notify(|observer| {
+                let outcome = match &outcome {
+                    Ok((answers, wire)) if answers.is_empty() && wire.response_code == Some(NXDOMAIN) => QueryOutcome::NxDomain,
+                    Ok((answers, _)) if answers.is_empty() => QueryOutcome::NoData,
+                    Ok((answers, _)) => QueryOutcome::Answered { records: answers.len() },
+                    Err(e) => QueryOutcome::Failed(e.clone()),
+                };
+                observer.on_query_end(name, key, &outcome, attempt_started.elapsed());
+            });
+            #[cfg(feature = "audit")]
+            if let Some(audit) = &self.audit {
+                let identifier = identifier.to_string();
+                audit.record(&identifier, name, &resolver.describe(), sent_at, attempt_started.elapsed(), &outcome);
+            }
+            let err = match outcome {
+                Ok((answers, wire)) => {
+                    resolved.answers = Ok(answers);
+                    resolved.resolved_at = Some(time::now());
+                    resolved.ttl = wire.ttl;
+                    resolved.nxdomain = wire.response_code == Some(NXDOMAIN);
+                    resolved.wire = Some(wire);
+                    return resolved;
+                }
+                Err(e) => e,
+            };
+
+            let delay = match &err {
+                SelfieError::RateLimited { retry_after: Some(retry_after), .. } => *retry_after,
+                _ => options.get_backoff().jittered(attempts),
+            };
+            let budget_left = budget.is_none_or(|budget| started.elapsed() + delay < budget)
+                && options.time_left().is_none_or(|left| delay < left);
+            if !err.is_retryable() || attempts >= options.get_attempts() || !budget_left || !options.queries_left() {
+                resolved.answers = Err(err);
+                return resolved;
+            }
+            debug!("Retrying {} in {:?} after attempt {} failed: {}", name, delay, attempts, err);
+            if let Some(reporter) = options.reporter() {
+                reporter.retrying(attempts + 1);
+            }
+            time::sleep(delay).await;
+            resolved.backoff += delay;
+        }
+    }
+
+    /// Checks the inline signature of `value`, if it carries one. The
+    /// domain's verification keys are fetched on first use and reused for the
+    /// remaining keys of the same call.
+    #[cfg(feature = "signatures")]
+    async fn check_signature(&self, resolver: &Arc<dyn TxtResolver>, identifier: &Identifier, value: &str, keys: &mut Option<Vec<[u8; 32]>>, options: &LookupOptions) -> Option<signature::SignatureStatus> {
+        let policy = options.get_signatures()?;
+        if let Ok(None) = signature::split_signed_value(value, &policy.field) {
+            return None;
+        }
+        if keys.is_none() {
+            let domain = identifier.domain();
+            let key_name = self.name_scheme.domain_name(signature::VERIFICATION_KEY_RECORD, domain);
+            let (_, resolver) = self.routes.resolve(signature::VERIFICATION_KEY_RECORD, domain, resolver);
+            debug!("Resolving verification key for: {}", key_name);
+            let answers = match name::validate_dns_name(&key_name) {
+                Ok(()) => self.resolve_txt(resolver.as_ref(), identifier, &key_name, None, options).await.unwrap_or_default(),
+                Err(_) => Vec::new(),
+            };
+            *keys = Some(answers.iter().filter_map(|answer| signature::parse_public_key(answer).ok()).collect());
+        }
+        signature::check_record(value, &policy.field, keys.as_deref().unwrap_or_default())
+    }
+
+    /// Asks every authoritative nameserver of the zone holding `key` of
+    /// `name` for the record directly, bypassing the resolver and cache.
+    /// Servers that cannot be reached are reported without failing the check.
+    pub fn check_authoritative_consensus(&self, name: &str, key: &str) -> Result<consensus::ConsensusReport, SelfieError> {
+        if self.is_offline() {
+            return Err(SelfieError::Offline);
+        }
+        // Authoritative servers are queried directly, never over Tor.
+        let identifier = self.identifier(name, false)?;
+        let qname = self.record_key(&identifier, key, None)?;
+        self.runtime.block_on(async {
+            let servers = self.nameservers.discover(&qname).await?;
+            debug!("Checking {} on {} nameserver(s) of {}", qname, servers.nameservers.len(), servers.zone);
+            Ok(consensus::check(servers, &qname, self.authoritative_transport.as_ref()).await)
+        })
+    }
+
+    /// Publishes `record` through `provider`, in the zone of the record's
+    /// domain, under the name this SDK's scheme looks it up at, lowercased.
+    pub fn publish_via(&self, provider: &dyn publish::DnsProvider, record: &publish::SelfieRecord) -> Result<(), publish::ProviderError> {
+        let invalid = |e: &dyn std::fmt::Display| publish::ProviderError::InvalidRecord(e.to_string());
+        let identifier: Identifier = name::validate_name_with(&record.identifier, &self.local_part_policy).map_err(|e| invalid(&e))?.into();
+        let zone = match &identifier {
+            Identifier::Domain(domain) | Identifier::Email { domain, .. } => domain.to_ascii_lowercase(),
+        };
+        let name = self.record_key(&identifier, &record.key, record.version).map_err(|e| invalid(&e))?.to_ascii_lowercase();
+        if record.values.is_empty() {
+            return Err(invalid(&"no values to publish"));
+        }
+        self.runtime.block_on(provider.upsert_txt(&zone, &name, &record.values, record.ttl))
+    }
+
+    /// Parses `name`, applying public-only mode; `onion` tells whether the
+    /// lookup's transport can resolve `.onion` names.
+    fn identifier(&self, name: &str, onion: bool) -> Result<Identifier, SelfieError> {
+        let invalid = |reason| SelfieError::InvalidName { name: name.to_string(), reason };
+        let identifier = name::validate_name_with(name, &self.local_part_policy).map_err(invalid)?.into();
+        if let Some(public_only) = &self.public_only {
+            public_only.check(&identifier, onion).map_err(invalid)?;
+        }
+        Ok(identifier)
+    }
+
+    fn record_key(&self, identifier: &Identifier, key: &str, version: Option<u32>) -> Result<String, SelfieError> {
+        self.record_key_with(self.name_scheme.as_ref(), identifier, key, version)
+    }
+
+    fn record_key_with(&self, scheme: &dyn NameScheme, identifier: &Identifier, key: &str, version: Option<u32>) -> Result<String, SelfieError> {
+        let record_key = name::build_record_key(scheme, identifier, key, version);
+        name::validate_dns_name(&record_key)?;
+        Ok(record_key)
+    }
+
+}
+
+/// Outcome of a lookup together with how much retrying it took.
+#[derive(Debug, Clone)]
+struct Resolved {
+    answers: Result<Vec<RawRecord>, SelfieError>,
+    attempts: u32,
+    /// Total time spent waiting between attempts.
+    backoff: Duration,
+    /// How the answer arrived, when there was one.
+    wire: Option<WireInfo>,
+    resolved_at: Option<SystemTime>,
+    ttl: Option<u32>,
+    source: Source,
+    /// Served from an expired cache entry.
+    stale: bool,
+    /// The name did not exist.
+    nxdomain: bool,
+}
+
+impl Resolved {
+    fn new() -> Self {
+        Resolved {
+            answers: Ok(Vec::new()),
+            attempts: 0,
+            backoff: Duration::ZERO,
+            wire: None,
+            resolved_at: None,
+            ttl: None,
+            source: Source::Dns,
+            stale: false,
+            nxdomain: false,
+        }
+    }
+}
+
+/// The runtime the blocking calls run on. It is shut down without waiting
+/// for its tasks, so that an SDK used through the async calls can be
+/// dropped on another runtime, where waiting is not allowed.
+struct OwnRuntime(Option<Runtime>);
+
+impl std::ops::Deref for OwnRuntime {
+    type Target = Runtime;
+
+    fn deref(&self) -> &Runtime {
+        self.0.as_ref().expect("the runtime lives as long as the SDK")
+    }
+}
+
+impl Drop for OwnRuntime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Installs a logger writing records up to `level` to stderr, for
+/// applications that have none of their own. Fails when a logger is
+/// already installed.
+pub fn init_logging(level: LevelFilter) -> Result<(), SetLoggerError> {
+    SimpleLogger::new().with_level(level).init()
+}
+
+/// Without its I/O and timer drivers on wasm32, where neither can run.
+fn new_runtime() -> Runtime {
+    let mut builder = tokio::runtime::Builder::new_current_thread();
+    #[cfg(not(target_arch = "wasm32"))]
+    builder.enable_all();
+    builder.build().unwrap()
 }
@@ -0,0 +1,176 @@
+//! Checks that a name's `bitcoin-payment` record is attested by the key its
+//! `pgp` record points at: `_bitcoin-payment-sig` holds a detached OpenPGP
+//! signature over the payment URI, made by that key.
+
+use std::fmt;
+#[cfg(feature = "http")]
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::error::SelfieError;
+#[cfg(feature = "http")]
+use crate::http::Endpoint;
+use crate::name::Identifier;
+
+pub const PAYMENT_RECORD: &str = "bitcoin-payment";
+pub const PAYMENT_SIGNATURE_RECORD: &str = "bitcoin-payment-sig";
+pub const PGP_RECORD: &str = "pgp";
+
+/// Where the `pgp` record says the key lives, when it doesn't carry the key itself.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum KeyLocator {
+    Url(String),
+    /// A fingerprint to look up, e.g. through WKD for email identifiers.
+    Fingerprint(String),
+}
+
+impl KeyLocator {
+    /// Classifies a `pgp` record value carrying no inline key.
+    pub fn parse(value: &str) -> Option<KeyLocator> {
+        let value = value.trim();
+        if value.starts_with("https://") || value.starts_with("http://") {
+            return Some(KeyLocator::Url(value.to_string()));
+        }
+        let fingerprint: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+        let fingerprint = fingerprint.strip_prefix("0x").unwrap_or(&fingerprint);
+        (fingerprint.len() == 40 && fingerprint.chars().all(|c| c.is_ascii_hexdigit()))
+            .then(|| KeyLocator::Fingerprint(fingerprint.to_ascii_uppercase()))
+    }
+}
+
+/// Fetches OpenPGP key blocks, armored or binary, for `pgp` records that
+/// reference a key instead of embedding it.
+#[async_trait]
+pub trait PgpKeySource: Send + Sync {
+    async fn fetch_key(&self, locator: &KeyLocator, identifier: &Identifier) -> Result<Vec<u8>, SelfieError>;
+}
+
+/// Downloads `Url` locators over HTTP, refusing key blocks larger than
+/// `max_size` and giving up after `timeout`.
+#[cfg(feature = "http")]
+#[derive(Debug, Clone)]
+pub struct HttpPgpKeySource {
+    max_size: usize,
+    timeout: Duration,
+}
+
+#[cfg(feature = "http")]
+impl HttpPgpKeySource {
+    /// At most 64 KiB, within 5 seconds.
+    pub fn new() -> Self {
+        HttpPgpKeySource { max_size: 64 * 1024, timeout: Duration::from_secs(5) }
+    }
+
+    /// The largest response accepted, headers included.
+    pub fn max_size(mut self, bytes: usize) -> Self {
+        self.max_size = bytes;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[cfg(feature = "http")]
+impl Default for HttpPgpKeySource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "http")]
+#[async_trait]
+impl PgpKeySource for HttpPgpKeySource {
+    async fn fetch_key(&self, locator: &KeyLocator, _identifier: &Identifier) -> Result<Vec<u8>, SelfieError> {
+        let KeyLocator::Url(url) = locator else {
+            return Err(SelfieError::Resolver(format!("Error fetching key {:?}: not a URL", locator)));
+        };
+        let failed = |reason: &dyn fmt::Display| SelfieError::Resolver(format!("Error fetching key from {}: {}", url, reason));
+        let endpoint = Endpoint::parse(url, "/").map_err(|e| failed(&e))?;
+        let response = match crate::time::timeout(self.timeout, endpoint.get_limited(&endpoint.path, self.max_size)).await {
+            Ok(response) => response.map_err(|e| failed(&e))?,
+            Err(_) => return Err(failed(&format!("timed out after {}ms", self.timeout.as_millis()))),
+        };
+        if response.status != 200 {
+            return Err(failed(&format!("HTTP {}", response.status)));
+        }
+        Ok(response.body)
+    }
+}
+
+/// Result of `SelfieRecordsSDK::fetch_and_verify_pgp`.
+#[cfg(feature = "http")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgpVerification {
+    /// The key URL the keys were fetched from.
+    pub url: String,
+    /// Fingerprints of the fetched keys, uppercase hex.
+    pub fingerprints: Vec<String>,
+    /// The fingerprint record a fetched key matched, `None` when the name
+    /// publishes no fingerprint to check against.
+    pub matched: Option<String>,
+}
+
+/// Outcome of one step of the linkage check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepOutcome {
+    Passed,
+    Failed(String),
+    /// Not attempted because an earlier step failed.
+    Skipped,
+}
+
+impl StepOutcome {
+    pub fn is_passed(&self) -> bool {
+        matches!(self, StepOutcome::Passed)
+    }
+}
+
+impl fmt::Display for StepOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepOutcome::Passed => f.write_str("passed"),
+            StepOutcome::Failed(reason) => write!(f, "failed ({})", reason),
+            StepOutcome::Skipped => f.write_str("skipped"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkageStatus {
+    /// The payment URI is signed by the key with this fingerprint.
+    Attested { fingerprint: String },
+    NotAttested,
+}
+
+/// Result of `SelfieRecordsSDK::verify_linked`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkageReport {
+    pub payment_record: StepOutcome,
+    pub signature_record: StepOutcome,
+    pub pgp_key: StepOutcome,
+    pub signature: StepOutcome,
+    /// The payment URI that was checked, when one was found.
+    pub payment_uri: Option<String>,
+    pub status: LinkageStatus,
+}
+
+impl LinkageReport {
+    pub(crate) fn new() -> Self {
+        LinkageReport {
+            payment_record: StepOutcome::Skipped,
+            signature_record: StepOutcome::Skipped,
+            pgp_key: StepOutcome::Skipped,
+            signature: StepOutcome::Skipped,
+            payment_uri: None,
+            status: LinkageStatus::NotAttested,
+        }
+    }
+
+    pub fn is_attested(&self) -> bool {
+        matches!(self.status, LinkageStatus::Attested { .. })
+    }
+}
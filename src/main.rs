@@ -0,0 +1,887 @@
+use std::collections::BTreeSet;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use clap::{Parser, Subcommand};
+use selfie_records_sdk::bench::Bench;
+use selfie_records_sdk::config::{Config, ConfigError, Transport};
+use selfie_records_sdk::consensus::{Consensus, RecursiveDiscovery, ServerOutcome};
+use selfie_records_sdk::health::{Finding, HealthCheck, HealthReport, Status};
+use selfie_records_sdk::publish::RecordInstruction;
+use selfie_records_sdk::verify::{Manifest, Outcome, Verification};
+use selfie_records_sdk::watch::{ChangeDetector, ChangeSink};
+use selfie_records_sdk::{
+    escape_controls, DirectResolver, KeyResult, PgpRecord, RecordEntry, SdkBuilder, SelfieError, SelfieProfile,
+    SelfieRecordsSDK, TxtResolver, DEFAULT_RECORDS, EXTENDED_RECORDS,
+};
+
+/// Environment variable holding the shared secret webhook bodies are signed with.
+#[cfg(feature = "webhook")]
+const WEBHOOK_SECRET_VAR: &str = "SELFIE_WEBHOOK_SECRET";
+
+#[derive(Parser)]
+#[command(name = "selfie", about = "Look up and inspect selfie records")]
+struct Cli {
+    /// Config file to read instead of $SELFIE_CONFIG or
+    /// ~/.config/selfie-records/config.toml. Flags beat SELFIE_*
+    /// variables, which beat the file.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Log each query and its outcome to stderr.
+    #[arg(long, global = true)]
+    debug: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Walk the DNSSEC chain of trust from the root down to a record name.
+    #[cfg(feature = "dnssec")]
+    DnssecTrace {
+        /// Record name, e.g. _bitcoin-payment.example.com
+        qname: String,
+        /// Recursive resolver to fetch the chain from, the system's first
+        /// nameserver by default.
+        #[arg(long)]
+        dns: Option<IpAddr>,
+        /// BIND-format trust anchor file to use instead of the IANA root keys.
+        #[arg(long)]
+        trust_anchor: Option<std::path::PathBuf>,
+    },
+    /// Ask every authoritative nameserver of a zone for a record and check
+    /// that they agree.
+    NsCheck {
+        /// Domain or address, e.g. alice@example.com
+        name: String,
+        /// Record key, e.g. bitcoin-payment
+        key: String,
+        /// Recursive resolver used to find the nameservers, the system's
+        /// first nameserver by default.
+        #[arg(long)]
+        dns: Option<IpAddr>,
+    },
+    /// Print a record's value and nothing else, one line per TXT record,
+    /// for use in scripts. Exits with 2 when the record does not exist and
+    /// 1 on any other error, reported on stderr.
+    Get {
+        /// Domain or address, e.g. alice@example.com
+        name: String,
+        /// Record key, e.g. bitcoin-payment
+        key: String,
+        /// Print only the first value.
+        #[arg(long)]
+        first: bool,
+        /// Also say on stderr which nameserver answered.
+        #[arg(long)]
+        verbose: bool,
+        #[command(flatten)]
+        resolver: ResolverArgs,
+    },
+    /// Look up a name's records and print one aligned line per record,
+    /// or every result as JSON. Exits with 0 when any key resolved, or
+    /// with --strict when every key did, and 1 otherwise.
+    Lookup {
+        /// Domain or address, e.g. alice@example.com
+        name: String,
+        /// Record keys to look up instead of the default ones, comma-separated.
+        #[arg(long, value_delimiter = ',')]
+        keys: Option<Vec<String>>,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+        /// Fail unless every key resolved.
+        #[arg(long)]
+        strict: bool,
+        /// Also say on stderr which nameserver answered each key.
+        #[arg(long)]
+        verbose: bool,
+        #[command(flatten)]
+        resolver: ResolverArgs,
+    },
+    /// Print the DNS record to create for a name's key: its host, type,
+    /// value and TTL, a zone-file line or Cloudflare API JSON.
+    Publish {
+        /// Domain or address, e.g. alice@example.com
+        name: String,
+        /// Record key, e.g. bitcoin-payment
+        key: String,
+        value: String,
+        #[arg(long, value_enum, default_value_t = PublishFormat::Fields)]
+        format: PublishFormat,
+        #[arg(long, default_value_t = 3600)]
+        ttl: u32,
+    },
+    /// Look up all of a name's records and print them parsed.
+    Profile {
+        /// Domain or address, e.g. alice@example.com
+        name: String,
+        /// Record keys to look up instead of the well-known ones, comma-separated.
+        #[arg(long, value_delimiter = ',')]
+        keys: Option<Vec<String>>,
+        #[command(flatten)]
+        resolver: ResolverArgs,
+    },
+    /// Poll a name's records and report every change, one JSON object per line.
+    Watch {
+        /// Domain or address, e.g. alice@example.com
+        name: String,
+        /// Record keys to watch, comma-separated.
+        #[arg(long, value_delimiter = ',')]
+        keys: Option<Vec<String>>,
+        /// Seconds between polls.
+        #[arg(long, default_value_t = 300)]
+        interval: u64,
+        #[command(flatten)]
+        resolver: ResolverArgs,
+        /// Also POST change events here, signed with the secret in
+        /// SELFIE_WEBHOOK_SECRET.
+        #[cfg(feature = "webhook")]
+        #[arg(long)]
+        webhook: Option<String>,
+        /// File events that could not be delivered are appended to.
+        #[cfg(feature = "webhook")]
+        #[arg(long, default_value = "selfie-dead-letter.jsonl")]
+        dead_letter: std::path::PathBuf,
+    },
+    /// Time repeated uncached lookups of a name against several nameservers
+    /// and check that they give the same answers.
+    Bench {
+        /// Domain or address, e.g. alice@example.com
+        name: String,
+        /// Record keys to look up, comma-separated.
+        #[arg(long, value_delimiter = ',')]
+        keys: Option<Vec<String>>,
+        /// Nameservers to compare, comma-separated, e.g. 1.1.1.1,8.8.8.8
+        #[arg(long, value_delimiter = ',', value_parser = parse_server, required = true)]
+        servers: Vec<SocketAddr>,
+        /// Measured lookups per server.
+        #[arg(long, default_value_t = 20)]
+        iterations: u32,
+        /// Lookups per server made first and not measured.
+        #[arg(long, default_value_t = 2)]
+        warmup: u32,
+        /// Seconds the whole run may take.
+        #[arg(long, default_value_t = 60)]
+        budget: u64,
+        /// Print the results as one JSON object.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check that a name's records are found, valid and served alike by
+    /// public resolvers. Exits with 0 for PASS, 1 for WARN and 2 for FAIL.
+    Check {
+        /// Domain or address, e.g. alice@example.com
+        name: String,
+        /// Record keys to check instead of the default ones, comma-separated.
+        #[arg(long, value_delimiter = ',')]
+        keys: Option<Vec<String>>,
+        /// Resolvers to compare answers with, comma-separated. Defaults to
+        /// 1.1.1.1,8.8.8.8
+        #[arg(long, value_delimiter = ',', value_parser = parse_server)]
+        servers: Option<Vec<SocketAddr>>,
+        /// Print the report as one JSON object.
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        resolver: ResolverArgs,
+    },
+    /// Check live records against a manifest of the values each name
+    /// should have. Exits with 0 when every record matches, 1 when any
+    /// differs or is missing and 2 when the manifest cannot be read or a
+    /// lookup fails.
+    Verify {
+        /// Manifest of names, keys and expected values, which may start
+        /// with prefix: or regex:, in JSON or TOML.
+        #[arg(required_unless_present = "manifest_flag", conflicts_with = "manifest_flag")]
+        manifest: Option<PathBuf>,
+        /// The manifest, as a flag.
+        #[arg(long = "manifest", value_name = "MANIFEST", hide = true)]
+        manifest_flag: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+        /// Write the observed values back into the manifest, dropping
+        /// missing records, and exit with 0 unless a lookup failed.
+        #[arg(long)]
+        update: bool,
+        #[command(flatten)]
+        resolver: ResolverArgs,
+    },
+    /// Answer lookups over HTTP: GET /v1/records/{name}?keys=&dns=&timeout=
+    /// returns the results as JSON, and GET /healthz reports the service up.
+    #[cfg(feature = "server")]
+    Serve {
+        /// Address to listen on, e.g. 0.0.0.0:8080
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+        /// Lookups run at once; more are answered 503.
+        #[arg(long, default_value_t = 64)]
+        max_lookups: usize,
+        /// Connections handled at once; more are answered 503.
+        #[arg(long, default_value_t = 256)]
+        max_connections: usize,
+        /// Requests each client IP may make per minute; more are answered 429.
+        #[arg(long)]
+        rate_limit: Option<u32>,
+        #[command(flatten)]
+        resolver: ResolverArgs,
+    },
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum PublishFormat {
+    /// Host, type, value and TTL, as registrar UIs ask for them.
+    Fields,
+    Zone,
+    Cloudflare,
+}
+
+/// Where and how long to look a record up.
+#[derive(clap::Args)]
+struct ResolverArgs {
+    /// Nameserver to query instead of the system's resolver, e.g. 9.9.9.9
+    /// or 127.0.0.1:5353.
+    #[arg(long, value_parser = parse_server)]
+    dns: Option<SocketAddr>,
+    /// DNS-over-HTTPS endpoint to query instead, e.g.
+    /// https://cloudflare-dns.com/dns-query, which needs the tls feature, or
+    /// http://127.0.0.1:8053/dns-query
+    #[arg(long, conflicts_with = "dns")]
+    doh: Option<String>,
+    /// Seconds the whole lookup may take.
+    #[arg(long)]
+    timeout: Option<u64>,
+}
+
+impl ResolverArgs {
+    /// The settings in effect: the flags on top of the environment and the
+    /// config file.
+    fn settings(&self, path: Option<&Path>) -> Result<Config, ConfigError> {
+        let mut flags = Config { timeout: self.timeout.map(Duration::from_secs), ..Config::default() };
+        if let Some(server) = self.dns {
+            flags.nameservers = Some(vec![server]);
+            flags.transport = Some(Transport::Dns);
+        }
+        if let Some(url) = &self.doh {
+            flags.doh_url = Some(url.clone());
+            flags.transport = Some(Transport::Doh);
+        }
+        Ok(Config::discover(path)?.merge(flags))
+    }
+}
+
+/// What the settings query, for output saying where answers came from.
+fn resolver_label(config: &Config) -> String {
+    match (config.transport, &config.doh_url, &config.nameservers) {
+        (Some(Transport::Doh) | None, Some(url), _) => url.clone(),
+        (_, _, Some(servers)) if !servers.is_empty() => {
+            servers.iter().map(SocketAddr::to_string).collect::<Vec<_>>().join(",")
+        }
+        _ => "system".to_string(),
+    }
+}
+
+/// The SDK for `args` and its settings, `None` after reporting invalid
+/// settings on stderr.
+fn configured_sdk(path: Option<&Path>, args: &ResolverArgs) -> Option<(SelfieRecordsSDK, Config)> {
+    let config = match args.settings(path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return None;
+        }
+    };
+    match SdkBuilder::from_config(config.clone()).build() {
+        Ok(sdk) => Some((sdk, config)),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            None
+        }
+    }
+}
+
+fn parse_server(server: &str) -> Result<SocketAddr, String> {
+    server
+        .parse()
+        .or_else(|_| server.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| format!("invalid nameserver address {:?}", server))
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if cli.debug {
+        selfie_records_sdk::init_logging(log::LevelFilter::Debug).expect("no logger is installed yet");
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    match cli.command {
+        #[cfg(feature = "dnssec")]
+        Command::DnssecTrace { qname, dns, trust_anchor } => {
+            runtime.block_on(dnssec_trace(&qname, dns.map(|dns| SocketAddr::new(dns, 53)), trust_anchor.as_deref()))
+        }
+        Command::NsCheck { name, key, dns } => ns_check(&name, &key, dns.map(|dns| SocketAddr::new(dns, 53))),
+        Command::Get { name, key, first, verbose, resolver } => match configured_sdk(cli.config.as_deref(), &resolver) {
+            Some((sdk, config)) => get(&sdk, &config, &name, &key, first, verbose),
+            None => ExitCode::FAILURE,
+        },
+        Command::Lookup { name, keys, format, strict, verbose, resolver } => {
+            match configured_sdk(cli.config.as_deref(), &resolver) {
+                Some((sdk, config)) => lookup(&sdk, &config, &name, keys, format, strict, verbose),
+                None => ExitCode::from(2),
+            }
+        }
+        Command::Publish { name, key, value, format, ttl } => publish(&name, &key, &value, format, ttl),
+        Command::Profile { name, keys, resolver } => match configured_sdk(cli.config.as_deref(), &resolver) {
+            Some((sdk, config)) => profile(&sdk, &config, &name, keys),
+            None => ExitCode::from(2),
+        },
+        #[cfg(feature = "webhook")]
+        Command::Watch { name, keys, interval, resolver, webhook, dead_letter } => {
+            let Some((sdk, config)) = configured_sdk(cli.config.as_deref(), &resolver) else {
+                return ExitCode::from(2);
+            };
+            let sink = match webhook.map(|url| webhook_sink(&url, dead_letter)).transpose() {
+                Ok(sink) => sink,
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    return ExitCode::from(2);
+                }
+            };
+            watch(&sdk, &config, &name, keys, interval, sink.as_ref().map(|sink| sink as &dyn ChangeSink), &runtime)
+        }
+        #[cfg(not(feature = "webhook"))]
+        Command::Watch { name, keys, interval, resolver } => match configured_sdk(cli.config.as_deref(), &resolver) {
+            Some((sdk, config)) => watch(&sdk, &config, &name, keys, interval, None, &runtime),
+            None => ExitCode::from(2),
+        },
+        Command::Bench { name, keys, servers, iterations, warmup, budget, json } => {
+            let keys = match keys {
+                Some(keys) => keys,
+                None => match Config::discover(cli.config.as_deref()) {
+                    Ok(config) => config.keys.unwrap_or_else(|| vec!["bitcoin-payment".to_string()]),
+                    Err(e) => {
+                        eprintln!("error: {}", e);
+                        return ExitCode::FAILURE;
+                    }
+                },
+            };
+            let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+            let bench = Bench::new(&name, &keys).iterations(iterations).warmup(warmup).budget(Duration::from_secs(budget));
+            bench_servers(&bench, &servers, json)
+        }
+        Command::Check { name, keys, servers, json, resolver } => match configured_sdk(cli.config.as_deref(), &resolver) {
+            Some((sdk, config)) => {
+                let keys = keys.or_else(|| config.keys.clone());
+                let mut check = HealthCheck::new(&name).lookup_options(config.lookup_options());
+                if let Some(keys) = &keys {
+                    check = check.keys(&keys.iter().map(String::as_str).collect::<Vec<_>>());
+                }
+                if let Some(servers) = servers {
+                    check = check.propagation_servers(servers);
+                }
+                health_check(&sdk, &check, json)
+            }
+            None => ExitCode::from(2),
+        },
+        Command::Verify { manifest, manifest_flag, format, update, resolver } => match configured_sdk(cli.config.as_deref(), &resolver) {
+            Some((sdk, config)) => {
+                let manifest = manifest.or(manifest_flag).expect("clap requires a manifest");
+                verify(&sdk, &config, &manifest, format, update)
+            }
+            None => ExitCode::from(2),
+        },
+        #[cfg(feature = "server")]
+        Command::Serve { listen, max_lookups, max_connections, rate_limit, resolver } => {
+            match configured_sdk(cli.config.as_deref(), &resolver) {
+                Some((sdk, config)) => serve(sdk, &config, listen, (max_lookups, max_connections), rate_limit),
+                None => ExitCode::FAILURE,
+            }
+        }
+    }
+}
+
+fn ns_check(name: &str, key: &str, server: Option<SocketAddr>) -> ExitCode {
+    let discovery = server.map(RecursiveDiscovery::new).unwrap_or_default();
+    let sdk = SelfieRecordsSDK::builder()
+        .nameserver_discovery(Arc::new(discovery))
+        .build()
+        .expect("default configuration is valid");
+    let report = match sdk.check_authoritative_consensus(name, key) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    println!(";; {} in zone {}", report.qname, report.zone);
+    for server in &report.servers {
+        match &server.outcome {
+            ServerOutcome::Answered(values) if values.is_empty() => println!("{:<48} no records", server.to_string()),
+            ServerOutcome::Answered(values) => println!("{:<48} {}", server.to_string(), escape_controls(&values.join(" | "))),
+            ServerOutcome::Unreachable(reason) => println!("{:<48} unreachable: {}", server.to_string(), reason),
+        }
+    }
+    let verdict = report.verdict();
+    if let Consensus::Divergent(groups) = &verdict {
+        for group in groups {
+            println!(";; {} server(s) answer [{}]", group.servers.len(), escape_controls(&group.values.join(" | ")));
+        }
+    }
+    println!(";; verdict: {}", verdict);
+
+    match verdict {
+        Consensus::Consistent(_) => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    }
+}
+
+fn get(sdk: &SelfieRecordsSDK, config: &Config, name: &str, key: &str, first: bool, verbose: bool) -> ExitCode {
+    let response = sdk.get_records_response(name, Some(vec![key]), None, &config.lookup_options());
+    let result = response.get(key).expect("the requested key is in the response");
+    if verbose {
+        match (result.answered_by, &result.resolver) {
+            (Some(server), _) => eprintln!(";; answered by {}", server),
+            (None, Some(resolver)) => eprintln!(";; answered through {}", resolver),
+            (None, None) => {}
+        }
+    }
+    match (&result.error, &result.value) {
+        (None, Some(value)) => {
+            // Overrides have no records, only their value.
+            let values: Vec<_> = match result.raw_records.is_empty() {
+                true => vec![value.clone()],
+                false => result.raw_records.iter().map(|record| record.to_string_lossy().into_owned()).collect(),
+            };
+            let count = if first { 1 } else { values.len() };
+            for value in &values[..count] {
+                println!("{}", escape_controls(value));
+            }
+            ExitCode::SUCCESS
+        }
+        (Some(SelfieError::NxDomain), _) => {
+            eprintln!("error: {} does not exist", escape_controls(name));
+            ExitCode::from(2)
+        }
+        (Some(SelfieError::NoRecords) | None, _) => {
+            eprintln!("error: no {} record for {}", escape_controls(key), escape_controls(name));
+            ExitCode::from(2)
+        }
+        (Some(e), _) => {
+            eprintln!("error: {}", escape_controls(&e.to_string()));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn lookup(sdk: &SelfieRecordsSDK, config: &Config, name: &str, keys: Option<Vec<String>>, format: Format, strict: bool, verbose: bool) -> ExitCode {
+    let keys = keys.or_else(|| config.keys.clone()).unwrap_or_else(|| DEFAULT_RECORDS.map(String::from).to_vec());
+    let response = sdk.get_records_response(name, Some(keys.iter().map(String::as_str).collect()), None, &config.lookup_options());
+    let results: Vec<(&str, &KeyResult)> = keys.iter().map(|key| (key.as_str(), response.get(key).expect("every key is looked up"))).collect();
+    if verbose {
+        for (key, result) in &results {
+            match (result.answered_by, &result.resolver) {
+                (Some(server), _) => eprintln!(";; {} answered by {}", escape_controls(key), server),
+                (None, Some(resolver)) => eprintln!(";; {} answered through {}", escape_controls(key), resolver),
+                (None, None) => {}
+            }
+        }
+    }
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&response).expect("responses serialize")),
+        Format::Text => print_lookup(&results),
+    }
+
+    let resolved = results.iter().filter(|(_, result)| matches!(result.entry(), RecordEntry::Found { .. })).count();
+    match (resolved, strict) {
+        (0, _) => ExitCode::FAILURE,
+        (resolved, true) if resolved < results.len() => ExitCode::FAILURE,
+        _ => ExitCode::SUCCESS,
+    }
+}
+
+/// One line per record, keys padded to the longest; failed keys say so
+/// with the error's code.
+fn print_lookup(results: &[(&str, &KeyResult)]) {
+    let width = results.iter().map(|(key, _)| key.len()).max().unwrap_or(0) + 2;
+    for (key, result) in results {
+        match result.entry() {
+            RecordEntry::Found { value } => {
+                // Overrides have no records, only their value.
+                let values = match result.raw_records.is_empty() {
+                    true => vec![value.to_string()],
+                    false => result.values(),
+                };
+                for (i, value) in values.iter().enumerate() {
+                    let key = if i == 0 { key } else { "" };
+                    println!("{:<width$}{}", escape_controls(key), escape_controls(value));
+                }
+            }
+            RecordEntry::NotFound => println!("{:<width$}not found", escape_controls(key)),
+            RecordEntry::Error(e) => {
+                println!("{:<width$}error [{}]: {}", escape_controls(key), e.code(), escape_controls(&e.to_string()))
+            }
+        }
+    }
+}
+
+fn publish(name: &str, key: &str, value: &str, format: PublishFormat, ttl: u32) -> ExitCode {
+    let instruction = match RecordInstruction::for_key(name, key, value) {
+        Ok(instruction) => instruction.ttl(ttl),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    for warning in &instruction.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    match format {
+        PublishFormat::Fields => {
+            println!("{:<7}{}", "Host", instruction.host());
+            println!("{:<7}TXT", "Type");
+            println!("{:<7}{}", "Value", escape_controls(&instruction.value));
+            println!("{:<7}{}", "TTL", instruction.ttl);
+        }
+        PublishFormat::Zone => println!("{}", instruction.to_zone_line()),
+        PublishFormat::Cloudflare => println!("{}", instruction.to_cloudflare_json()),
+    }
+    ExitCode::SUCCESS
+}
+
+fn bench_servers(bench: &Bench, servers: &[SocketAddr], json: bool) -> ExitCode {
+    let resolvers: Vec<(String, Arc<dyn TxtResolver>)> = servers
+        .iter()
+        .map(|server| (server.to_string(), Arc::new(DirectResolver::new(*server)) as Arc<dyn TxtResolver>))
+        .collect();
+    let report = bench.run(&resolvers);
+    if json {
+        println!("{}", report.to_json());
+    } else {
+        let millis = |d: Duration| format!("{:.1}ms", d.as_secs_f64() * 1000.0);
+        println!("{:<24} {:>9} {:>9} {:>9} {:>9} {:>8}", "server", "min", "median", "p95", "max", "success");
+        for server in &report.servers {
+            let stats = match server.stats() {
+                Some(s) => format!("{:>9} {:>9} {:>9} {:>9}", millis(s.min), millis(s.median), millis(s.p95), millis(s.max)),
+                None => format!("{:>9} {:>9} {:>9} {:>9}", "-", "-", "-", "-"),
+            };
+            let success = format!("{}/{}", server.samples.len(), server.lookups());
+            println!("{:<24} {} {:>8}", server.server, stats, success);
+        }
+        if report.budget_exhausted {
+            println!(";; time budget ran out before every iteration was done");
+        }
+        println!(";; answers: {}", if report.consistent() { "consistent" } else { "divergent" });
+    }
+    if report.consistent() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn health_check(sdk: &SelfieRecordsSDK, check: &HealthCheck, json: bool) -> ExitCode {
+    let report = check.run(sdk);
+    if json {
+        println!("{}", report.to_json());
+    } else {
+        print_health(&report);
+    }
+    match report.status() {
+        Status::Pass => ExitCode::SUCCESS,
+        Status::Warn => ExitCode::FAILURE,
+        Status::Fail => ExitCode::from(2),
+    }
+}
+
+/// Values come from DNS and are escaped so they cannot drive the terminal.
+fn print_health(report: &HealthReport) {
+    let print_findings = |findings: &[Finding]| {
+        for finding in findings {
+            println!("  {}  {:<12}{}", finding.status, finding.check, escape_controls(&finding.detail));
+        }
+    };
+    println!(";; health of {}", report.name);
+    for key in &report.keys {
+        match &key.qname {
+            Some(qname) => println!("{} ({}): {}", key.key, qname, key.status()),
+            None => println!("{}: {}", key.key, key.status()),
+        }
+        if let Some(value) = &key.value {
+            println!("  {:<18}{}", "value", escape_controls(value));
+        }
+        print_findings(&key.findings);
+    }
+    print_findings(&report.findings);
+    println!(";; overall: {}", report.status());
+}
+
+fn verify(sdk: &SelfieRecordsSDK, config: &Config, path: &Path, format: Format, update: bool) -> ExitCode {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("error: cannot read {}: {}", path.display(), e);
+            return ExitCode::from(2);
+        }
+    };
+    // JSON manifests are objects, which TOML documents cannot start with.
+    let toml = !text.trim_start().starts_with('{');
+    let manifest = match toml {
+        true => Manifest::parse_toml(&text),
+        false => Manifest::parse(&text),
+    };
+    let mut manifest = match manifest {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    // Likely typos, which would otherwise only show up as missing records.
+    for key in manifest.names.values().flat_map(|keys| keys.keys()).collect::<BTreeSet<_>>() {
+        if !EXTENDED_RECORDS.contains(&key.as_str()) {
+            eprintln!("warning: {} is not a well-known record key", key);
+        }
+    }
+    let verification = manifest.verify(sdk, &config.lookup_options());
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&verification.to_json()).expect("reports serialize")),
+        Format::Text => print_verification(&verification),
+    }
+    if verification.failed() {
+        ExitCode::from(2)
+    } else if update {
+        let changed = manifest.update(&verification);
+        let text = match toml {
+            true => manifest.to_toml(),
+            false => serde_json::to_string_pretty(&manifest.to_json()).expect("manifests serialize") + "\n",
+        };
+        if let Err(e) = std::fs::write(path, text) {
+            eprintln!("error: cannot write {}: {}", path.display(), e);
+            return ExitCode::from(2);
+        }
+        let note = format!(";; updated {} records in {}", changed, path.display());
+        // Keeps JSON output a single document.
+        match format {
+            Format::Json => eprintln!("{}", note),
+            Format::Text => println!("{}", note),
+        }
+        ExitCode::SUCCESS
+    } else if verification.passed() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Values come from DNS and are escaped so they cannot drive the terminal.
+fn print_verification(verification: &Verification) {
+    let mut counts = [0; 4];
+    for entry in &verification.entries {
+        let (label, index) = match &entry.outcome {
+            Outcome::Match => ("MATCH", 0),
+            Outcome::Mismatch { .. } => ("MISMATCH", 1),
+            Outcome::Missing => ("MISSING", 2),
+            Outcome::Failed(_) => ("FAILED", 3),
+        };
+        counts[index] += 1;
+        println!("{:<10}{} {}", label, entry.name, entry.key);
+        match &entry.outcome {
+            Outcome::Mismatch { actual } => {
+                println!("  expected  {}", escape_controls(&entry.expected.to_string()));
+                println!("  actual    {}", escape_controls(actual));
+            }
+            Outcome::Missing => println!("  expected  {}", escape_controls(&entry.expected.to_string())),
+            Outcome::Failed(e) => println!("  error     {}", escape_controls(&e.to_string())),
+            Outcome::Match => {}
+        }
+    }
+    println!(";; {} match, {} mismatch, {} missing, {} failed", counts[0], counts[1], counts[2], counts[3]);
+}
+
+#[cfg(feature = "server")]
+/// `limits` is the most lookups and connections handled at once.
+fn serve(sdk: SelfieRecordsSDK, config: &Config, addr: SocketAddr, limits: (usize, usize), rate_limit: Option<u32>) -> ExitCode {
+    let (max_lookups, max_connections) = limits;
+    let mut server = selfie_records_sdk::server::Server::new(Arc::new(sdk))
+        .lookup_options(config.lookup_options())
+        .max_lookups(max_lookups)
+        .max_connections(max_connections);
+    if let Some(requests) = rate_limit {
+        server = server.rate_limit(requests, Duration::from_secs(60));
+    }
+    let listening = match server.listen(addr) {
+        Ok(listening) => listening,
+        Err(e) => {
+            eprintln!("error: cannot listen on {}: {}", addr, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Ok(addr) = listening.local_addr() {
+        eprintln!(";; listening on http://{} (via {})", addr, resolver_label(config));
+    }
+    match listening.run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn profile(sdk: &SelfieRecordsSDK, config: &Config, name: &str, keys: Option<Vec<String>>) -> ExitCode {
+    let keys = keys.or_else(|| config.keys.clone());
+    let filters: Option<Vec<&str>> = keys.as_ref().map(|keys| keys.iter().map(String::as_str).collect());
+    let profile = sdk.resolve_profile_with(name, filters, None, &config.lookup_options());
+    print_profile(&profile);
+    if profile.is_empty() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Values come from DNS and are escaped so they cannot drive the terminal.
+fn print_profile(profile: &SelfieProfile) {
+    println!("{} (via {}, DNSSEC {})", profile.name, profile.resolver, profile.dnssec);
+    if let Some(uri) = &profile.bitcoin_payment {
+        println!("  {:<18}{}", "bitcoin-payment", escape_controls(&uri.to_string()));
+    }
+    if let Some(pgp) = &profile.pgp {
+        let pgp = match pgp {
+            PgpRecord::Fingerprint(fingerprint) => format!("fingerprint {}", fingerprint),
+            PgpRecord::Url(url) => format!("key at {}", url),
+            PgpRecord::Key(_) => "inline key".to_string(),
+        };
+        println!("  {:<18}{}", "pgp", escape_controls(&pgp));
+    }
+    if let Some(nostr) = &profile.nostr {
+        println!("  {:<18}{}", "nostr", escape_controls(&nostr.npub()));
+        for relay in &nostr.relays {
+            println!("  {:<18}relay {}", "", escape_controls(&relay.to_string()));
+        }
+    }
+    if let Some(node) = &profile.node_uri {
+        println!("  {:<18}{}", "node-uri", escape_controls(&node.to_string()));
+    }
+    if let Some(code) = &profile.bip47 {
+        println!("  {:<18}{}", "bip47", code);
+        println!("  {:<18}version {}, key {}", "", code.version, code.public_key_hex());
+    }
+    if let Some(did) = &profile.did {
+        println!("  {:<18}{}", "did", escape_controls(&did.to_string()));
+    }
+    for (key, lnurl) in [("lnurl", &profile.lnurl), ("lightning-address", &profile.lightning_address)] {
+        if let Some(lnurl) = lnurl {
+            println!("  {:<18}{}", key, escape_controls(&lnurl.url()));
+        }
+    }
+    let mut extra: Vec<_> = profile.extra.iter().collect();
+    extra.sort();
+    for (key, value) in extra {
+        println!("  {:<18}{}", escape_controls(key), escape_controls(value));
+    }
+    if profile.is_empty() {
+        println!("  no records found");
+    }
+    for (key, e) in &profile.problems {
+        println!("  {:<18}error: {}", escape_controls(key), escape_controls(&e.to_string()));
+    }
+}
+
+#[cfg(feature = "webhook")]
+fn webhook_sink(url: &str, dead_letter: std::path::PathBuf) -> Result<selfie_records_sdk::watch::WebhookSink, String> {
+    let secret = std::env::var(WEBHOOK_SECRET_VAR).map_err(|_| format!("{} must be set to sign webhook requests", WEBHOOK_SECRET_VAR))?;
+    let sink = selfie_records_sdk::watch::WebhookSink::new(url).map_err(|e| e.to_string())?;
+    Ok(sink.secret(secret.as_bytes()).dead_letter(dead_letter))
+}
+
+fn watch(
+    sdk: &SelfieRecordsSDK,
+    config: &Config,
+    name: &str,
+    keys: Option<Vec<String>>,
+    interval: u64,
+    sink: Option<&dyn ChangeSink>,
+    runtime: &tokio::runtime::Runtime,
+) -> ExitCode {
+    let keys = keys.or_else(|| config.keys.clone());
+    let filters: Option<Vec<&str>> = keys.as_ref().map(|keys| keys.iter().map(String::as_str).collect());
+    let mut detector = ChangeDetector::new(name, &resolver_label(config));
+    let options = config.lookup_options();
+    loop {
+        let response = sdk.get_records_response(name, filters.clone(), None, &options);
+        for event in detector.observe(&response, SystemTime::now()) {
+            println!("{}", event.to_json());
+            if let Some(Err(e)) = sink.map(|sink| runtime.block_on(sink.deliver(&event))) {
+                eprintln!("error: {}", e);
+            }
+        }
+        std::thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+#[cfg(feature = "dnssec")]
+async fn dnssec_trace(qname: &str, server: Option<SocketAddr>, trust_anchor: Option<&std::path::Path>) -> ExitCode {
+    use selfie_records_sdk::dnssec::{self, NetworkSource, TrustAnchor};
+
+    let anchors = match trust_anchor.map(dnssec::load_trust_anchors) {
+        Some(Ok(anchors)) => anchors,
+        Some(Err(e)) => {
+            eprintln!("error: {}", e);
+            return ExitCode::from(2);
+        }
+        None => TrustAnchor::iana_root(),
+    };
+    let source = server.map(NetworkSource::new).unwrap_or_default();
+    let trace = match dnssec::trace(&source, qname, &anchors, SystemTime::now()).await {
+        Ok(trace) => trace,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    match server {
+        Some(server) => println!(";; DNSSEC trace for {} via {}", trace.qname, server),
+        None => println!(";; DNSSEC trace for {}", trace.qname),
+    }
+    let mut link = Some(&trace);
+    while let Some(traced) = link {
+        for level in &traced.zones {
+            let ds = join_tags(&level.ds_key_tags);
+            let keys = join_tags(&level.dnskey_tags);
+            println!("{:<32} DS [{}] DNSKEY [{}]  {}", level.zone, ds, keys, level.verdict);
+        }
+        match (&traced.answer, &traced.alias) {
+            (Some(answer), _) => {
+                println!("{:<32} TXT  {}", traced.qname, answer.verdict);
+                for value in &answer.values {
+                    println!("    {}", escape_controls(value));
+                }
+            }
+            (None, Some(alias)) => println!("{:<32} CNAME {}  {}", traced.qname, alias.target, alias.verdict),
+            (None, None) => println!("{:<32} TXT  no records", traced.qname),
+        }
+        link = traced.alias.as_ref().map(|alias| alias.trace.as_ref());
+    }
+    println!(";; verdict: {}", trace.verdict());
+
+    if trace.verdict().is_secure() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[cfg(feature = "dnssec")]
+fn join_tags(tags: &[u16]) -> String {
+    tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>().join(",")
+}
@@ -0,0 +1,221 @@
+//! Looks up the same keys for many names at once, behind
+//! `SelfieRecordsSDK::resolve_matrix` and `get_records_batch`.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
+
+use crate::error::SelfieError;
+use crate::options::LookupOptions;
+use crate::response::{KeyResult, RecordsResponse};
+use crate::SelfieRecordsSDK;
+
+/// One name and key of a matrix with its result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatrixCell {
+    pub name: String,
+    pub key: String,
+    pub result: KeyResult,
+}
+
+/// One lookup, shared by the names whose `key` record has the same owner
+/// name.
+struct Job {
+    key: String,
+    names: Vec<String>,
+}
+
+type Running<'a> = Pin<Box<dyn Future<Output = (Job, KeyResult)> + Send + 'a>>;
+
+/// The cells of a matrix in the order they complete. Lookups only run
+/// while the iterator is advanced.
+pub struct MatrixCells<'a> {
+    sdk: &'a SelfieRecordsSDK,
+    options: LookupOptions,
+    queue: VecDeque<Job>,
+    running: Vec<Running<'a>>,
+    finished: VecDeque<MatrixCell>,
+    cells: usize,
+    lookups: usize,
+}
+
+/// The lookups for `keys` of `names`, duplicates dropped, and the number
+/// of cells they fill.
+fn jobs(sdk: &SelfieRecordsSDK, names: &[&str], keys: &[&str]) -> (Vec<Job>, usize) {
+    let mut queue: Vec<Job> = Vec::new();
+    let mut shared: HashMap<(&str, String), usize> = HashMap::new();
+    let mut seen = HashSet::new();
+    for &name in names {
+        for &key in keys {
+            if !seen.insert((name, key)) {
+                continue;
+            }
+            match sdk.query_name(name, key) {
+                Some(qname) => match shared.get(&(key, qname.clone())) {
+                    Some(&index) => queue[index].names.push(name.to_string()),
+                    None => {
+                        shared.insert((key, qname), queue.len());
+                        queue.push(Job { key: key.to_string(), names: vec![name.to_string()] });
+                    }
+                },
+                None => queue.push(Job { key: key.to_string(), names: vec![name.to_string()] }),
+            }
+        }
+    }
+    (queue, seen.len())
+}
+
+fn start<'a>(sdk: &'a SelfieRecordsSDK, job: Job, options: LookupOptions) -> Running<'a> {
+    Box::pin(async move {
+        let response = sdk.get_records_inner(&job.names[0], Some(vec![job.key.as_str()]), None, &options).await;
+        let result = response.get(&job.key).cloned().expect("the requested key is in the response");
+        (job, result)
+    })
+}
+
+/// Waits for the first of `running` to finish and removes it.
+async fn next_finished(running: &mut Vec<Running<'_>>) -> (Job, KeyResult) {
+    std::future::poll_fn(|cx| {
+        for index in 0..running.len() {
+            if let Poll::Ready(done) = running[index].as_mut().poll(cx) {
+                drop(running.swap_remove(index));
+                return Poll::Ready(done);
+            }
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+impl<'a> MatrixCells<'a> {
+    pub(crate) fn new(sdk: &'a SelfieRecordsSDK, names: &[&str], keys: &[&str], options: LookupOptions) -> Self {
+        let (queue, cells) = jobs(sdk, names, keys);
+        MatrixCells { sdk, options, queue: queue.into(), running: Vec::new(), finished: VecDeque::new(), cells, lookups: 0 }
+    }
+
+    /// Number of cells, duplicates in the names or keys counted once.
+    pub fn cells(&self) -> usize {
+        self.cells
+    }
+
+    /// Lookups started so far.
+    pub fn lookups(&self) -> usize {
+        self.lookups
+    }
+}
+
+impl Iterator for MatrixCells<'_> {
+    type Item = MatrixCell;
+
+    fn next(&mut self) -> Option<MatrixCell> {
+        loop {
+            if let Some(cell) = self.finished.pop_front() {
+                return Some(cell);
+            }
+            while self.running.len() < self.options.get_concurrency() {
+                let Some(job) = self.queue.pop_front() else { break };
+                self.lookups += 1;
+                self.running.push(start(self.sdk, job, self.options.clone()));
+            }
+            if self.running.is_empty() {
+                return None;
+            }
+
+            let (job, result) = self.sdk.runtime.block_on(next_finished(&mut self.running));
+            let key = job.key;
+            self.finished
+                .extend(job.names.into_iter().map(|name| MatrixCell { name, key: key.clone(), result: result.clone() }));
+        }
+    }
+}
+
+/// The results of `get_records_batch` for `keys` of `names`, with at most
+/// `options.get_concurrency()` lookups running. `on_progress(done, total)`
+/// is called each time the results of another name are complete.
+pub(crate) async fn batch(
+    sdk: &SelfieRecordsSDK,
+    names: &[&str],
+    keys: &[&str],
+    options: &LookupOptions,
+    on_progress: &mut (dyn FnMut(usize, usize) + Send),
+) -> HashMap<String, RecordsResponse> {
+    let (queue, _) = jobs(sdk, names, keys);
+    let mut queue: VecDeque<Job> = queue.into();
+    let keys = keys.iter().collect::<HashSet<_>>().len();
+    let mut results: HashMap<String, RecordsResponse> =
+        names.iter().map(|&name| (name.to_string(), RecordsResponse::with_capacity(keys))).collect();
+    let (total, mut done) = (results.len(), 0);
+    let mut running = Vec::new();
+    loop {
+        while running.len() < options.get_concurrency().max(1) {
+            let Some(job) = queue.pop_front() else { break };
+            running.push(start(sdk, job, options.clone()));
+        }
+        if running.is_empty() {
+            return results;
+        }
+        let (job, result) = next_finished(&mut running).await;
+        for name in job.names {
+            let response = results.get_mut(&name).expect("every name has a response");
+            response.insert(&job.key, result.clone());
+            if response.len() == keys {
+                done += 1;
+                on_progress(done, total);
+            }
+        }
+    }
+}
+
+/// The results of `resolve_matrix`, by name and key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MatrixResponse {
+    rows: HashMap<String, RecordsResponse>,
+    /// Lookups made, fewer than the cells when names share owner names.
+    pub lookups: usize,
+    pub duration: Duration,
+}
+
+impl MatrixResponse {
+    pub fn get(&self, name: &str, key: &str) -> Option<&KeyResult> {
+        self.rows.get(name)?.get(key)
+    }
+
+    /// The results for `name`, by key.
+    pub fn row(&self, name: &str) -> Option<&RecordsResponse> {
+        self.rows.get(name)
+    }
+
+    pub fn insert(&mut self, cell: MatrixCell) {
+        self.rows.entry(cell.name).or_default().insert(&cell.key, cell.result);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str, &KeyResult)> {
+        self.rows.iter().flat_map(|(name, row)| row.iter().map(move |(key, result)| (name.as_str(), key, result)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.values().map(RecordsResponse::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Cells with a value.
+    pub fn found(&self) -> usize {
+        self.iter().filter(|(_, _, result)| result.value.is_some()).count()
+    }
+
+    /// Cells whose record does not exist.
+    pub fn missing(&self) -> usize {
+        self.iter().filter(|(_, _, result)| matches!(result.error, Some(SelfieError::NoRecords | SelfieError::NxDomain))).count()
+    }
+
+    /// Cells that failed for any other reason.
+    pub fn failed(&self) -> usize {
+        let failed = |result: &KeyResult| !matches!(result.error, None | Some(SelfieError::NoRecords | SelfieError::NxDomain));
+        self.iter().filter(|(_, _, result)| failed(result)).count()
+    }
+}
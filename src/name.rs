@@ -0,0 +1,682 @@
+use std::fmt;
+
+use thiserror::Error;
+use trust_dns_proto::rr::domain::Label;
+
+use crate::error::SelfieError;
+use crate::key::RecordKey;
+
+mod pictographic;
+
+const MAX_NAME_LENGTH: usize = 253;
+const MAX_LABEL_LENGTH: usize = 63;
+const MAX_LOCAL_PART_LENGTH: usize = 64;
+const MAX_ADDRESS_LENGTH: usize = 254;
+
+/// Characters allowed in an unquoted local part besides letters and digits.
+const ATEXT_SPECIALS: &str = "!#$%&'*+-/=?^_`{|}~";
+
+/// A name selfie records can be looked up for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Identifier {
+    /// A bare domain such as `example.com`.
+    Domain(String),
+    /// An address such as `alice@example.com`.
+    Email { local: String, domain: String },
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Identifier::Domain(domain) => f.write_str(domain),
+            Identifier::Email { local, domain } => write!(f, "{}@{}", local, domain),
+        }
+    }
+}
+
+impl Identifier {
+    pub fn domain(&self) -> &str {
+        match self {
+            Identifier::Domain(domain) | Identifier::Email { domain, .. } => domain,
+        }
+    }
+}
+
+impl From<NameKind> for Identifier {
+    fn from(kind: NameKind) -> Self {
+        match kind {
+            NameKind::Domain(domain) => Identifier::Domain(domain.0),
+            NameKind::Email { local, domain } => Identifier::Email { local, domain: domain.0 },
+        }
+    }
+}
+
+/// A domain as it is queried: lowercase ASCII, internationalized labels
+/// in their `xn--` form, without a trailing root dot.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NormalizedDomain(String);
+
+impl NormalizedDomain {
+    fn new(domain: &str) -> Result<Self, NameError> {
+        let labels = domain.split('.').map(|label| match label.is_ascii() {
+            true => Ok(label.to_ascii_lowercase()),
+            false if label.chars().any(is_pictographic) => Err(NameError::InvalidLabel { label: label.to_string() }),
+            // Nontransitional, as IDNA2008 has it: ß stays itself rather
+            // than becoming ss.
+            false => idna::domain_to_ascii_strict(label).map_err(|_| NameError::InvalidLabel { label: label.to_string() }),
+        });
+        let normalized = labels.collect::<Result<Vec<_>, _>>()?.join(".");
+        // Punycode is longer than the text it encodes.
+        validate_domain(&normalized)?;
+        Ok(NormalizedDomain(normalized))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The domain for display, `xn--` labels decoded back to Unicode.
+    pub fn to_unicode(&self) -> String {
+        let labels = self.0.split('.').map(|label| match Label::from_ascii(label) {
+            Ok(decoded) if label.starts_with("xn--") => decoded.to_utf8(),
+            _ => label.to_string(),
+        });
+        labels.collect::<Vec<_>>().join(".")
+    }
+}
+
+/// Emoji, which IDNA2008 disallows although UTS-46 mapping lets them
+/// through: characters with the Extended_Pictographic property, and the
+/// emoji presentation selector U+FE0F and tags U+E0020 to U+E007F that
+/// only occur in emoji sequences and that mapping would otherwise drop.
+fn is_pictographic(c: char) -> bool {
+    pictographic::is_extended_pictographic(c) || matches!(c as u32, 0xFE0F | 0xE0020..=0xE007F)
+}
+
+impl fmt::Display for NormalizedDomain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<NormalizedDomain> for String {
+    fn from(domain: NormalizedDomain) -> Self {
+        domain.0
+    }
+}
+
+/// What `validate_name` found a name to be.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NameKind {
+    Domain(NormalizedDomain),
+    Email { local: String, domain: NormalizedDomain },
+}
+
+/// Why `validate_name` rejected a name; lookups report the same reason in
+/// `SelfieError::InvalidName`.
+pub type ValidationError = NameError;
+
+/// Why a name could not be used for a lookup. Serialized as `{ "kind" }`
+/// with the variant's fields, e.g. `{ "kind": "too_long", "max": 253 }`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "kind", rename_all = "snake_case")
+)]
+pub enum NameError {
+    Empty,
+    TooLong { max: usize },
+    EmptyLabel,
+    LabelTooLong,
+    InvalidLabel { label: String },
+    SingleLabelDomain,
+    AddressLiteral,
+    MultipleAt,
+    EmptyLocalPart,
+    LocalPartTooLong,
+    InvalidLocalPart { position: usize },
+    UnterminatedQuote,
+    MissingDomain,
+    /// Rejected by public-only mode: a special-use or private suffix, an
+    /// IP address or a single-label domain.
+    SpecialUseDomain,
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NameError::Empty => f.write_str("name is empty"),
+            NameError::TooLong { max } => write!(f, "name is longer than {} characters", max),
+            NameError::EmptyLabel => f.write_str("name contains an empty label"),
+            NameError::LabelTooLong => write!(f, "name has a label longer than {} characters", MAX_LABEL_LENGTH),
+            NameError::InvalidLabel { label } => write!(f, "label {:?} contains invalid characters", label),
+            NameError::SingleLabelDomain => f.write_str("domain must have at least two labels"),
+            NameError::AddressLiteral => f.write_str("address literals cannot be looked up"),
+            NameError::MultipleAt => f.write_str("address contains more than one unquoted '@'"),
+            NameError::EmptyLocalPart => f.write_str("local part is empty"),
+            NameError::LocalPartTooLong => {
+                write!(f, "local part is longer than {} characters", MAX_LOCAL_PART_LENGTH)
+            }
+            NameError::InvalidLocalPart { position } => {
+                write!(f, "local part has an invalid character at position {}", position)
+            }
+            NameError::UnterminatedQuote => f.write_str("quoted local part is not terminated"),
+            NameError::MissingDomain => f.write_str("address has no domain"),
+            NameError::SpecialUseDomain => f.write_str("special-use and private names are not looked up in public-only mode"),
+        }
+    }
+}
+
+/// Checks and normalizes `input` exactly as a lookup of it would with the
+/// default `LocalPartPolicy`, so that input can be validated before
+/// anything is looked up.
+///
+/// On top of `parse_identifier`, a leading `₿` (as in `₿alice@example.com`)
+/// is dropped, the domain becomes a `NormalizedDomain` and the local part
+/// is normalized by the policy. Local parts with characters that cannot
+/// be queried, such as spaces, `/` or the quotes of a quoted local part,
+/// are rejected.
+///
+/// ```
+/// use selfie_records_sdk::{validate_name, NameError, NameKind};
+///
+/// match validate_name("₿Alice@Bücher.example.").unwrap() {
+///     NameKind::Email { local, domain } => assert_eq!((local.as_str(), domain.as_str()), ("alice", "xn--bcher-kva.example")),
+///     other => panic!("{:?}", other),
+/// }
+/// assert_eq!(validate_name("localhost"), Err(NameError::SingleLabelDomain));
+/// ```
+pub fn validate_name(input: &str) -> Result<NameKind, ValidationError> {
+    validate_name_with(input, &LocalPartPolicy::default())
+}
+
+/// Like `validate_name`, normalizing local parts by `policy`.
+pub fn validate_name_with(input: &str, policy: &LocalPartPolicy) -> Result<NameKind, ValidationError> {
+    let input = input.strip_prefix('₿').unwrap_or(input);
+    Ok(match parse_identifier(input)? {
+        Identifier::Domain(domain) => NameKind::Domain(NormalizedDomain::new(&domain)?),
+        Identifier::Email { local, domain } => {
+            if let Some((position, _)) = local.char_indices().find(|&(_, c)| !is_queryable(c)) {
+                return Err(NameError::InvalidLocalPart { position });
+            }
+            NameKind::Email { local: policy.apply(&local), domain: NormalizedDomain::new(&domain)? }
+        }
+    })
+}
+
+/// Whether `c` can be part of a local part that is looked up.
+fn is_queryable(c: char) -> bool {
+    !(c.is_control() || c.is_whitespace() || matches!(c, '/' | '\\' | '"'))
+}
+
+/// How the local part of an address becomes the first labels of its
+/// owner names. Domains are always lowercased.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalPartPolicy {
+    lowercase: bool,
+    strip_plus_suffix: bool,
+}
+
+impl Default for LocalPartPolicy {
+    fn default() -> Self {
+        LocalPartPolicy { lowercase: true, strip_plus_suffix: false }
+    }
+}
+
+impl LocalPartPolicy {
+    /// Lowercases, and keeps `+` suffixes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lowercases local parts, on by default: `Alice` and `alice` are
+    /// the same owner name to DNS.
+    pub fn lowercase(mut self, lowercase: bool) -> Self {
+        self.lowercase = lowercase;
+        self
+    }
+
+    /// Drops everything from the first `+`, so that `alice+tag` looks up
+    /// `alice`. Off by default.
+    pub fn strip_plus_suffix(mut self, strip: bool) -> Self {
+        self.strip_plus_suffix = strip;
+        self
+    }
+
+    /// `local` normalized. A local part starting with `+` keeps it.
+    pub fn apply(&self, local: &str) -> String {
+        let local = match local.find('+') {
+            Some(plus) if self.strip_plus_suffix && plus > 0 => &local[..plus],
+            _ => local,
+        };
+        match self.lowercase {
+            true => local.to_lowercase(),
+            false => local.to_string(),
+        }
+    }
+}
+
+/// Parses `name` as either a bare domain or an RFC 5321 `local@domain`
+/// address.
+///
+/// Local parts may be dot-atoms or quoted strings (kept verbatim, quotes
+/// included); UTF-8 is allowed as in RFC 6531. Exactly one unquoted `@` is
+/// accepted, the local part is limited to 64 octets and the address to 254.
+/// Address domains may be single-label (`alice@intranet`) but bare domains
+/// need at least two labels. A trailing root dot on the domain is dropped.
+pub fn parse_identifier(name: &str) -> Result<Identifier, NameError> {
+    if name.is_empty() {
+        return Err(NameError::Empty);
+    }
+    match split_address(name)? {
+        Some((local, domain)) => {
+            if name.len() > MAX_ADDRESS_LENGTH {
+                return Err(NameError::TooLong { max: MAX_ADDRESS_LENGTH });
+            }
+            validate_local_part(local)?;
+            let domain = domain.strip_suffix('.').unwrap_or(domain);
+            if domain.is_empty() {
+                return Err(NameError::MissingDomain);
+            }
+            validate_domain(domain)?;
+            Ok(Identifier::Email { local: local.to_string(), domain: domain.to_string() })
+        }
+        None => {
+            let domain = name.strip_suffix('.').unwrap_or(name);
+            validate_domain(domain)?;
+            if !domain.contains('.') {
+                return Err(NameError::SingleLabelDomain);
+            }
+            Ok(Identifier::Domain(domain.to_string()))
+        }
+    }
+}
+
+/// Splits an address at its single unquoted `@`, or returns `None` when
+/// `name` has no local part.
+fn split_address(name: &str) -> Result<Option<(&str, &str)>, NameError> {
+    if name.starts_with('"') {
+        let end = quoted_string_end(name)?;
+        return match name[end..].strip_prefix('@') {
+            Some(domain) if domain.contains('@') => Err(NameError::MultipleAt),
+            Some(domain) => Ok(Some((&name[..end], domain))),
+            None if end == name.len() => Err(NameError::MissingDomain),
+            None => Err(NameError::InvalidLocalPart { position: end }),
+        };
+    }
+    match name.matches('@').count() {
+        0 => Ok(None),
+        1 => Ok(name.split_once('@')),
+        _ => Err(NameError::MultipleAt),
+    }
+}
+
+/// Byte offset just past the closing quote of the quoted string starting `name`.
+fn quoted_string_end(name: &str) -> Result<usize, NameError> {
+    let mut chars = name.char_indices().skip(1);
+    while let Some((position, c)) = chars.next() {
+        match c {
+            '"' => return Ok(position + 1),
+            '\\' => match chars.next() {
+                Some((_, escaped)) if (' '..='~').contains(&escaped) => {}
+                Some((position, _)) => return Err(NameError::InvalidLocalPart { position }),
+                None => return Err(NameError::UnterminatedQuote),
+            },
+            ' ' | '!' | '#'..='[' | ']'..='~' => {}
+            c if !c.is_ascii() => {}
+            _ => return Err(NameError::InvalidLocalPart { position }),
+        }
+    }
+    Err(NameError::UnterminatedQuote)
+}
+
+fn validate_local_part(local: &str) -> Result<(), NameError> {
+    if local.is_empty() {
+        return Err(NameError::EmptyLocalPart);
+    }
+    if local.len() > MAX_LOCAL_PART_LENGTH {
+        return Err(NameError::LocalPartTooLong);
+    }
+    if local.starts_with('"') {
+        return Ok(());
+    }
+    let mut previous = '.';
+    for (position, c) in local.char_indices() {
+        let valid = match c {
+            '.' => previous != '.',
+            c => c.is_ascii_alphanumeric() || ATEXT_SPECIALS.contains(c) || !c.is_ascii(),
+        };
+        if !valid {
+            return Err(NameError::InvalidLocalPart { position });
+        }
+        previous = c;
+    }
+    if previous == '.' {
+        return Err(NameError::InvalidLocalPart { position: local.len() - 1 });
+    }
+    Ok(())
+}
+
+fn validate_domain(domain: &str) -> Result<(), NameError> {
+    if domain.starts_with('[') {
+        return Err(NameError::AddressLiteral);
+    }
+    if domain.len() > MAX_NAME_LENGTH {
+        return Err(NameError::TooLong { max: MAX_NAME_LENGTH });
+    }
+    for label in domain.split('.') {
+        if label.is_empty() {
+            return Err(NameError::EmptyLabel);
+        }
+        if label.len() > MAX_LABEL_LENGTH {
+            return Err(NameError::LabelTooLong);
+        }
+        let valid_chars = label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || !c.is_ascii());
+        if !valid_chars || label.starts_with('-') || label.ends_with('-') {
+            return Err(NameError::InvalidLabel { label: label.to_string() });
+        }
+    }
+    Ok(())
+}
+
+/// Returns the owner name the `key` record for `name` is published under,
+/// using the selfie/BIP-353 layout: `_{key}.{domain}` for domains and
+/// `{local}.user._{key}.{domain}` for email-style names.
+///
+/// `name` is validated as by `validate_name`, which lowercases the domain
+/// with internationalized labels in their `xn--` form and normalizes the
+/// local part by the default `LocalPartPolicy`, and `key` as by
+/// `RecordKey::custom`. Either being invalid, or the owner name too long,
+/// fails with `InvalidName` or `InvalidKey`.
+///
+/// ```
+/// use selfie_records_sdk::get_txt_record_key;
+///
+/// assert_eq!(get_txt_record_key("example.com", "pgp").unwrap(), "_pgp.example.com");
+/// assert_eq!(
+///     get_txt_record_key("alice@example.com", "bitcoin-payment").unwrap(),
+///     "alice.user._bitcoin-payment.example.com"
+/// );
+/// assert_eq!(get_txt_record_key("anna@bücher.example", "nostr").unwrap(), "anna.user._nostr.xn--bcher-kva.example");
+/// assert!(get_txt_record_key("a@b@c.d", "nostr").is_err());
+/// ```
+pub fn get_txt_record_key(name: &str, key: &str) -> Result<String, SelfieError> {
+    let identifier = validate_name(name).map_err(|reason| SelfieError::InvalidName { name: name.to_string(), reason })?.into();
+    let key = RecordKey::custom(key)?;
+    let record_key = build_record_key(&SelfieNameScheme, &identifier, key.as_str(), None);
+    validate_dns_name(&record_key)?;
+    Ok(record_key)
+}
+
+/// Inverse of `get_txt_record_key`: recovers the identifier and key from a
+/// query name such as `alice.user._bitcoin-payment.example.com`. A trailing
+/// root dot is accepted, and so is a record version label such as the
+/// `_v2` of `_v2._bitcoin-payment.example.com`, which is dropped. Returns
+/// `None` for names outside the selfie layout.
+pub fn parse_txt_record_key(qname: &str) -> Option<(Identifier, String)> {
+    let qname = qname.strip_suffix('.').unwrap_or(qname);
+    let labels: Vec<&str> = qname.split('.').collect();
+    let version_index = labels.iter().position(|label| label.starts_with('_'))?;
+    let is_version = labels[version_index]
+        .strip_prefix("_v")
+        .is_some_and(|version| !version.is_empty() && version.bytes().all(|b| b.is_ascii_digit()));
+    let key_index = match labels.get(version_index + 1) {
+        Some(next) if is_version && next.starts_with('_') => version_index + 1,
+        _ => version_index,
+    };
+
+    let key = &labels[key_index][1..];
+    let domain = labels[key_index + 1..].join(".");
+    if key.is_empty() || domain.is_empty() || labels[key_index + 1..].iter().any(|l| l.is_empty()) {
+        return None;
+    }
+
+    let identifier = match &labels[..version_index] {
+        [] => Identifier::Domain(domain),
+        [local @ .., "user"] if !local.is_empty() && local.iter().all(|l| !l.is_empty()) => Identifier::Email {
+            local: local.join("."),
+            domain,
+        },
+        _ => return None,
+    };
+    Some((identifier, key.to_string()))
+}
+
+/// Builds the owner name of `key` for `identifier`. A record `version`
+/// adds a `_v{version}` label in front of the key's, as in
+/// `_v2._bitcoin-payment.example.com` and
+/// `alice.user._v2._bitcoin-payment.example.com`.
+pub(crate) fn build_record_key(scheme: &dyn NameScheme, identifier: &Identifier, key: &str, version: Option<u32>) -> String {
+    let versioned;
+    let key = match version {
+        // Schemes put the underscore in front of the key themselves.
+        Some(version) => {
+            versioned = format!("v{}._{}", version, key);
+            versioned.as_str()
+        }
+        None => key,
+    };
+    match identifier {
+        Identifier::Domain(domain) => scheme.domain_name(key, domain),
+        Identifier::Email { local, domain } => scheme.email_name(local, key, domain),
+    }
+}
+
+/// Builds the DNS owner names that selfie records are published under.
+pub trait NameScheme: Send + Sync {
+    /// Owner name for `key` published by a bare domain.
+    fn domain_name(&self, key: &str, domain: &str) -> String;
+    /// Owner name for `key` published for the address `local@domain`.
+    fn email_name(&self, local: &str, key: &str, domain: &str) -> String;
+    /// The template names of `identifier` follow, which
+    /// `KeyResult::name_template` reports; `None` unless template-driven.
+    fn template(&self, _identifier: &Identifier) -> Option<&str> {
+        None
+    }
+}
+
+/// The selfie/BIP-353 layout: `_{key}.{domain}` and `{local}.user._{key}.{domain}`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SelfieNameScheme;
+
+impl NameScheme for SelfieNameScheme {
+    fn domain_name(&self, key: &str, domain: &str) -> String {
+        concat(&["_", key, ".", domain])
+    }
+
+    fn email_name(&self, local: &str, key: &str, domain: &str) -> String {
+        concat(&[local, ".user._", key, ".", domain])
+    }
+}
+
+/// Joins `parts` into a string allocated once at its final size.
+fn concat(parts: &[&str]) -> String {
+    let mut name = String::with_capacity(parts.iter().map(|part| part.len()).sum());
+    parts.iter().for_each(|part| name.push_str(part));
+    name
+}
+
+/// The templates of the selfie/BIP-353 layout.
+pub const DEFAULT_DOMAIN_TEMPLATE: &str = "_{key}.{domain}";
+pub const DEFAULT_EMAIL_TEMPLATE: &str = "{user}.user._{key}.{domain}";
+
+/// A scheme built from template strings using the `{key}`, `{domain}` and
+/// `{user}` placeholders, e.g. `{user}._{key}.{domain}` or
+/// `_{key}._selfie.{domain}`. `{local}` is another name for `{user}`.
+/// The default is the selfie/BIP-353 layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateNameScheme {
+    domain_template: String,
+    email_template: String,
+}
+
+impl TemplateNameScheme {
+    /// Takes the templates as they are; see `parse` to check them.
+    pub fn new(domain_template: &str, email_template: &str) -> Self {
+        TemplateNameScheme {
+            domain_template: domain_template.to_string(),
+            email_template: email_template.to_string(),
+        }
+    }
+
+    /// Like `new`, rejecting templates with unknown placeholders or without
+    /// `{key}` and `{domain}`, and email templates without `{user}`.
+    pub fn parse(domain_template: &str, email_template: &str) -> Result<Self, TemplateError> {
+        check_template(domain_template, false)?;
+        check_template(email_template, true)?;
+        Ok(Self::new(domain_template, email_template))
+    }
+
+    pub fn domain_template(&self) -> &str {
+        &self.domain_template
+    }
+
+    pub fn email_template(&self) -> &str {
+        &self.email_template
+    }
+}
+
+impl Default for TemplateNameScheme {
+    fn default() -> Self {
+        Self::new(DEFAULT_DOMAIN_TEMPLATE, DEFAULT_EMAIL_TEMPLATE)
+    }
+}
+
+impl NameScheme for TemplateNameScheme {
+    fn domain_name(&self, key: &str, domain: &str) -> String {
+        fill(&self.domain_template, "", key, domain)
+    }
+
+    fn email_name(&self, local: &str, key: &str, domain: &str) -> String {
+        fill(&self.email_template, local, key, domain)
+    }
+
+    fn template(&self, identifier: &Identifier) -> Option<&str> {
+        Some(match identifier {
+            Identifier::Domain(_) => &self.domain_template,
+            Identifier::Email { .. } => &self.email_template,
+        })
+    }
+}
+
+/// Replaces the placeholders of `template` in one pass, so that braces in
+/// a local part are left alone.
+fn fill(template: &str, user: &str, key: &str, domain: &str) -> String {
+    let mut name = String::with_capacity(template.len() + user.len() + key.len() + domain.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = ["{user}", "{local}", "{key}", "{domain}"]
+            .into_iter()
+            .zip([user, user, key, domain])
+            .find(|(placeholder, _)| rest.starts_with(placeholder));
+        match value {
+            Some((placeholder, value)) => {
+                name.push_str(value);
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                name.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    name.push_str(rest);
+    name
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid name template {template:?}: {reason}")]
+pub struct TemplateError {
+    pub template: String,
+    pub reason: String,
+}
+
+/// Checks the placeholders of `template`, a domain or, with `email`, an
+/// address template.
+pub(crate) fn check_template(template: &str, email: bool) -> Result<(), TemplateError> {
+    let invalid = |reason: &str| Err(TemplateError { template: template.to_string(), reason: reason.to_string() });
+    let mut placeholders = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        let Some(end) = rest[start..].find('}').filter(|_| rest[start..].starts_with('{')) else {
+            return invalid("unbalanced brace");
+        };
+        let placeholder = &rest[start + 1..start + end];
+        match placeholder {
+            "key" | "domain" => {}
+            "user" | "local" if email => {}
+            "user" | "local" => return invalid("domains have no {user}"),
+            _ => return invalid(&format!("unknown placeholder {{{}}}", placeholder)),
+        }
+        placeholders.push(placeholder);
+        rest = &rest[start + end + 1..];
+    }
+    if !placeholders.contains(&"key") {
+        return invalid("missing {key}");
+    }
+    if !placeholders.contains(&"domain") {
+        return invalid("missing {domain}");
+    }
+    if email && !placeholders.iter().any(|placeholder| ["user", "local"].contains(placeholder)) {
+        return invalid("missing {user}");
+    }
+    Ok(())
+}
+
+/// Suffixes public-only mode rejects: the special-use names of RFC 6761,
+/// RFC 8375 and RFC 9476 that public resolvers cannot answer, and
+/// `internal`, reserved for private use.
+const SPECIAL_USE_SUFFIXES: [&str; 6] = ["local", "internal", "home.arpa", "test", "invalid", "localhost"];
+
+/// Rejects names whose queries would leak internal hostnames to public
+/// resolvers.
+#[derive(Debug, Clone)]
+pub(crate) struct PublicOnly {
+    suffixes: Vec<String>,
+}
+
+impl PublicOnly {
+    pub(crate) fn new(extra_suffixes: &[String]) -> Self {
+        let suffixes = SPECIAL_USE_SUFFIXES.iter().map(|suffix| suffix.to_string());
+        let extra = extra_suffixes.iter().map(|suffix| suffix.trim_matches('.').to_ascii_lowercase());
+        PublicOnly { suffixes: suffixes.chain(extra).collect() }
+    }
+
+    /// `onion` tells whether `.onion` names can be resolved, which only a
+    /// Tor transport can do.
+    pub(crate) fn check(&self, identifier: &Identifier, onion: bool) -> Result<(), NameError> {
+        let domain = match identifier {
+            Identifier::Domain(domain) | Identifier::Email { domain, .. } => domain.trim_end_matches('.').to_ascii_lowercase(),
+        };
+        let has_suffix = |suffix: &str| domain == suffix || domain.ends_with(&format!(".{}", suffix));
+        let blocked = self.suffixes.iter().any(|suffix| has_suffix(suffix))
+            || (has_suffix("onion") && !onion)
+            || domain.parse::<std::net::IpAddr>().is_ok()
+            || !domain.contains('.');
+        match blocked {
+            true => Err(NameError::SpecialUseDomain),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Checks a generated owner name against the DNS length and label limits.
+pub(crate) fn validate_dns_name(name: &str) -> Result<(), SelfieError> {
+    let invalid = |reason| SelfieError::InvalidName { name: name.to_string(), reason };
+    let trimmed = name.strip_suffix('.').unwrap_or(name);
+    if trimmed.len() > MAX_NAME_LENGTH {
+        return Err(invalid(NameError::TooLong { max: MAX_NAME_LENGTH }));
+    }
+    for label in trimmed.split('.') {
+        if label.is_empty() {
+            return Err(invalid(NameError::EmptyLabel));
+        }
+        if label.len() > MAX_LABEL_LENGTH {
+            return Err(invalid(NameError::LabelTooLong));
+        }
+    }
+    Ok(())
+}
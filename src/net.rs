@@ -0,0 +1,76 @@
+//! The sockets under the transports: tokio's, or on wasm32, which has
+//! none, stand-ins that fail to open so only `fetch` reaches the network.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use tokio::net::{lookup_host, TcpStream, UdpSocket};
+#[cfg(target_arch = "wasm32")]
+pub(crate) use unsupported::{lookup_host, TcpStream, UdpSocket};
+
+#[cfg(target_arch = "wasm32")]
+mod unsupported {
+    use std::io;
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    fn unsupported() -> io::Error {
+        io::Error::new(io::ErrorKind::Unsupported, "sockets are not available on wasm32, only DNS-over-HTTPS lookups are")
+    }
+
+    /// Never opened, so never read from or written to.
+    #[derive(Debug)]
+    pub(crate) enum TcpStream {}
+
+    impl TcpStream {
+        pub(crate) async fn connect<A>(_: A) -> io::Result<TcpStream> {
+            Err(unsupported())
+        }
+    }
+
+    impl AsyncRead for TcpStream {
+        fn poll_read(self: Pin<&mut Self>, _: &mut Context<'_>, _: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+            match *self.get_mut() {}
+        }
+    }
+
+    impl AsyncWrite for TcpStream {
+        fn poll_write(self: Pin<&mut Self>, _: &mut Context<'_>, _: &[u8]) -> Poll<io::Result<usize>> {
+            match *self.get_mut() {}
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            match *self.get_mut() {}
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            match *self.get_mut() {}
+        }
+    }
+
+    #[derive(Debug)]
+    pub(crate) enum UdpSocket {}
+
+    impl UdpSocket {
+        pub(crate) async fn bind(_: SocketAddr) -> io::Result<UdpSocket> {
+            Err(unsupported())
+        }
+
+        pub(crate) async fn connect(&self, _: SocketAddr) -> io::Result<()> {
+            match *self {}
+        }
+
+        pub(crate) async fn send(&self, _: &[u8]) -> io::Result<usize> {
+            match *self {}
+        }
+
+        pub(crate) async fn recv(&self, _: &mut [u8]) -> io::Result<usize> {
+            match *self {}
+        }
+    }
+
+    pub(crate) async fn lookup_host<A>(_: A) -> io::Result<std::vec::IntoIter<SocketAddr>> {
+        Err(unsupported())
+    }
+}
@@ -0,0 +1,103 @@
+//! Compares a name's `nostr` record with its NIP-05 entry, the
+//! `names` map of `https://<domain>/.well-known/nostr.json`, so that a
+//! compromised web server or stale DNS shows up as a disagreement.
+
+use std::fmt;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::error::SelfieError;
+use crate::http::Endpoint;
+use crate::records::{from_hex, percent_encode, to_hex};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Fetches the `nostr.json` document of a domain.
+#[async_trait]
+pub trait Nip05Source: Send + Sync {
+    /// The document served for `name` at `domain`, as JSON text.
+    async fn fetch(&self, domain: &str, name: &str) -> Result<String, SelfieError>;
+}
+
+/// Fetches `/.well-known/nostr.json?name=<name>` over HTTP.
+#[derive(Debug, Clone, Default)]
+pub struct HttpNip05Source {
+    base_url: Option<String>,
+}
+
+impl HttpNip05Source {
+    /// Fetches from `https://<domain>`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fetches from `base_url`, such as `http://127.0.0.1:8080`, for every
+    /// domain, e.g. a proxy that terminates TLS.
+    pub fn with_base_url(base_url: &str) -> Self {
+        HttpNip05Source { base_url: Some(base_url.trim_end_matches('/').to_string()) }
+    }
+}
+
+#[async_trait]
+impl Nip05Source for HttpNip05Source {
+    async fn fetch(&self, domain: &str, name: &str) -> Result<String, SelfieError> {
+        let base = self.base_url.clone().unwrap_or_else(|| format!("https://{}", domain));
+        let failed = |reason: &dyn fmt::Display| SelfieError::Resolver(format!("Error fetching nostr.json from {}: {}", base, reason));
+        let endpoint = Endpoint::parse(&base, "/").map_err(|e| failed(&e))?;
+        let path = format!("/.well-known/nostr.json?name={}", percent_encode(name));
+        let response = match crate::time::timeout(TIMEOUT, endpoint.request("GET", &path, &[("Accept", "application/json")], None)).await {
+            Ok(response) => response.map_err(|e| failed(&e))?,
+            Err(_) => return Err(failed(&format!("timed out after {}s", TIMEOUT.as_secs()))),
+        };
+        if response.status != 200 {
+            return Err(failed(&format!("HTTP {}", response.status)));
+        }
+        String::from_utf8(response.body).map_err(|_| failed(&"body is not UTF-8"))
+    }
+}
+
+/// How the `nostr` record and the NIP-05 entry of a name compare.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Nip05Check {
+    /// Both name `public_key`.
+    Agree { public_key: [u8; 32] },
+    /// `nip05` is `None` when `nostr.json` has no entry for the name.
+    Disagree { dns: [u8; 32], nip05: Option<[u8; 32]> },
+    /// The name is a bare domain, which NIP-05 does not cover.
+    NotApplicable,
+}
+
+impl fmt::Display for Nip05Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Nip05Check::Agree { public_key } => write!(f, "agree ({})", to_hex(public_key)),
+            Nip05Check::Disagree { dns, nip05: Some(nip05) } => {
+                write!(f, "disagree (DNS {}, NIP-05 {})", to_hex(dns), to_hex(nip05))
+            }
+            Nip05Check::Disagree { dns, nip05: None } => write!(f, "disagree (DNS {}, no NIP-05 entry)", to_hex(dns)),
+            Nip05Check::NotApplicable => f.write_str("not applicable"),
+        }
+    }
+}
+
+/// Compares `dns` with the entry for `name` in the `nostr.json` `document`.
+pub(crate) fn compare(dns: [u8; 32], document: &str, name: &str) -> Result<Nip05Check, SelfieError> {
+    let invalid = |reason: &str| SelfieError::InvalidRecord { key: "nostr.json".to_string(), reason: reason.to_string() };
+    let document: Value = serde_json::from_str(document).map_err(|e| invalid(&e.to_string()))?;
+    let names = document.get("names").and_then(Value::as_object).ok_or_else(|| invalid("no names object"))?;
+    let entry = names.get(name).or_else(|| names.get(&name.to_ascii_lowercase()));
+    let nip05 = match entry {
+        Some(entry) => {
+            let hex = entry.as_str().ok_or_else(|| invalid("public key is not a string"))?;
+            let bytes = from_hex(hex).and_then(|bytes| bytes.try_into().ok());
+            Some(bytes.ok_or_else(|| invalid("public key is not 32 bytes of hex"))?)
+        }
+        None => None,
+    };
+    Ok(match nip05 {
+        Some(public_key) if public_key == dns => Nip05Check::Agree { public_key },
+        nip05 => Nip05Check::Disagree { dns, nip05 },
+    })
+}
@@ -0,0 +1,71 @@
+//! Hooks for metrics and tracing: an `Observer` hears of every TXT query
+//! the SDK sends for a lookup and of every answer served from its cache;
+//! see `SelfieRecordsSDK::set_observer`.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use log::warn;
+
+use crate::error::SelfieError;
+
+/// Called from the tasks running lookups, so methods should return
+/// quickly. A method that panics is logged and otherwise ignored. Every
+/// method does nothing unless implemented.
+pub trait Observer: Send + Sync {
+    /// A query for `fqdn`, looked up for record key `key`, is about to be
+    /// sent. Retries are queries of their own.
+    fn on_query_start(&self, _fqdn: &str, _key: &str) {}
+
+    /// The query `on_query_start` announced ended after `duration`.
+    fn on_query_end(&self, _fqdn: &str, _key: &str, _outcome: &QueryOutcome, _duration: Duration) {}
+
+    /// `fqdn` was answered from the cache without a query.
+    fn on_cache_hit(&self, _fqdn: &str) {}
+}
+
+/// How a query ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryOutcome {
+    /// The server answered with `records` TXT records, at least one.
+    Answered { records: usize },
+    /// The name does not exist.
+    NxDomain,
+    /// The name exists but holds no TXT records.
+    NoData,
+    Failed(SelfieError),
+}
+
+impl QueryOutcome {
+    /// `answered`, `nxdomain`, `nodata`, or the error's code such as
+    /// `E_TIMEOUT`, e.g. for a metric label.
+    pub fn label(&self) -> &'static str {
+        match self {
+            QueryOutcome::Answered { .. } => "answered",
+            QueryOutcome::NxDomain => "nxdomain",
+            QueryOutcome::NoData => "nodata",
+            QueryOutcome::Failed(e) => e.code(),
+        }
+    }
+}
+
+/// The SDK's observer, if it has one.
+#[derive(Default)]
+pub(crate) struct Observers(RwLock<Option<Arc<dyn Observer>>>);
+
+impl Observers {
+    pub(crate) fn set(&self, observer: Arc<dyn Observer>) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = Some(observer);
+    }
+
+    /// Calls `event` on the observer, if there is one, catching a panic.
+    pub(crate) fn notify(&self, event: impl FnOnce(&dyn Observer)) {
+        let Some(observer) = self.0.read().unwrap_or_else(PoisonError::into_inner).clone() else {
+            return;
+        };
+        if catch_unwind(AssertUnwindSafe(|| event(observer.as_ref()))).is_err() {
+            warn!("The observer panicked; ignoring it");
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! Just enough OpenPGP (RFC 4880) to check a detached Ed25519 signature
+//! against a published public key: ASCII armor, packet framing, v4 EdDSA
+//! keys and v4 binary/text signatures. Subkey binding signatures are not
+//! checked; the key block is trusted as published.
+
+use data_encoding::{BASE64, HEXUPPER};
+use ring::digest;
+use ring::signature::{UnparsedPublicKey, ED25519};
+
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_PUBLIC_SUBKEY: u8 = 14;
+
+const ALGO_EDDSA_LEGACY: u8 = 22;
+const ALGO_ED25519: u8 = 27;
+const ED25519_OID: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xda, 0x47, 0x0f, 0x01];
+
+const SIG_BINARY: u8 = 0x00;
+const SIG_TEXT: u8 = 0x01;
+
+const SUBPACKET_ISSUER: u8 = 16;
+const SUBPACKET_ISSUER_FINGERPRINT: u8 = 33;
+
+/// An Ed25519 key, primary or subkey, taken from a key block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PublicKey {
+    pub(crate) fingerprint: [u8; 20],
+    key: [u8; 32],
+}
+
+impl PublicKey {
+    pub(crate) fn fingerprint_hex(&self) -> String {
+        HEXUPPER.encode(&self.fingerprint)
+    }
+
+    fn key_id(&self) -> &[u8] {
+        &self.fingerprint[12..]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Signature {
+    sig_type: u8,
+    hash: &'static digest::Algorithm,
+    hashed: Vec<u8>,
+    hash_prefix: [u8; 2],
+    issuer: Option<Vec<u8>>,
+    signature: [u8; 64],
+}
+
+/// Decodes armored or bare base64 text, as published in a TXT record.
+pub(crate) fn decode_text(text: &str) -> Result<Vec<u8>, String> {
+    if text.contains("-----BEGIN PGP") {
+        return dearmor(text);
+    }
+    let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    BASE64.decode(compact.as_bytes()).map_err(|e| format!("invalid base64: {}", e))
+}
+
+/// Reads the keys of a key block, binary or as `decode_text` takes it.
+pub(crate) fn parse_key_block(data: &[u8]) -> Result<Vec<PublicKey>, String> {
+    parse_public_keys(data).or_else(|_| parse_public_keys(&decode_text(&String::from_utf8_lossy(data))?))
+}
+
+fn dearmor(text: &str) -> Result<Vec<u8>, String> {
+    let mut lines = text.lines().map(str::trim).skip_while(|line| !line.starts_with("-----BEGIN PGP"));
+    lines.next();
+    // Armor headers end at the first blank line; TXT records often lose it.
+    let mut body: Vec<&str> = lines.take_while(|line| !line.starts_with("-----END PGP")).collect();
+    if let Some(blank) = body.iter().position(|line| line.is_empty()) {
+        body.drain(..=blank);
+    }
+    body.retain(|line| !line.contains(": "));
+
+    let (checksum, body): (Vec<&str>, Vec<&str>) = body.into_iter().partition(|line| line.starts_with('='));
+    let data = BASE64
+        .decode(body.concat().as_bytes())
+        .map_err(|e| format!("invalid armor: {}", e))?;
+    if let Some(checksum) = checksum.first() {
+        let expected = BASE64
+            .decode(&checksum.as_bytes()[1..])
+            .map_err(|e| format!("invalid armor checksum: {}", e))?;
+        if expected != crc24(&data).to_be_bytes()[1..] {
+            return Err("armor checksum mismatch".to_string());
+        }
+    }
+    Ok(data)
+}
+
+fn crc24(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xb7_04ce;
+    for byte in data {
+        crc ^= (*byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x100_0000 != 0 {
+                crc ^= 0x186_4cfb;
+            }
+        }
+    }
+    crc & 0xff_ffff
+}
+
+fn packets(mut data: &[u8]) -> Result<Vec<(u8, &[u8])>, String> {
+    let mut packets = Vec::new();
+    while let Some((&header, rest)) = data.split_first() {
+        if header & 0x80 == 0 {
+            return Err("not an OpenPGP packet".to_string());
+        }
+        let (tag, len, rest) = if header & 0x40 != 0 {
+            let (len, rest) = match rest {
+                [first @ 0..=191, rest @ ..] => (*first as usize, rest),
+                [first @ 192..=223, second, rest @ ..] => ((((*first as usize) - 192) << 8) + *second as usize + 192, rest),
+                [255, a, b, c, d, rest @ ..] => (u32::from_be_bytes([*a, *b, *c, *d]) as usize, rest),
+                _ => return Err("unsupported packet length".to_string()),
+            };
+            (header & 0x3f, len, rest)
+        } else {
+            let (len, rest) = match (header & 0x03, rest) {
+                (0, [a, rest @ ..]) => (*a as usize, rest),
+                (1, [a, b, rest @ ..]) => (u16::from_be_bytes([*a, *b]) as usize, rest),
+                (2, [a, b, c, d, rest @ ..]) => (u32::from_be_bytes([*a, *b, *c, *d]) as usize, rest),
+                (3, rest) => (rest.len(), rest),
+                _ => return Err("truncated packet header".to_string()),
+            };
+            ((header >> 2) & 0x0f, len, rest)
+        };
+        if rest.len() < len {
+            return Err("truncated packet".to_string());
+        }
+        packets.push((tag, &rest[..len]));
+        data = &rest[len..];
+    }
+    Ok(packets)
+}
+
+/// Collects the Ed25519 primary key and subkeys of a key block.
+pub(crate) fn parse_public_keys(data: &[u8]) -> Result<Vec<PublicKey>, String> {
+    let keys: Vec<PublicKey> = packets(data)?
+        .into_iter()
+        .filter(|(tag, _)| *tag == TAG_PUBLIC_KEY || *tag == TAG_PUBLIC_SUBKEY)
+        .filter_map(|(_, body)| parse_public_key(body))
+        .collect();
+    if keys.is_empty() {
+        return Err("no Ed25519 key in key block".to_string());
+    }
+    Ok(keys)
+}
+
+fn parse_public_key(body: &[u8]) -> Option<PublicKey> {
+    let (&version, rest) = body.split_first()?;
+    if version != 4 || rest.len() < 5 {
+        return None;
+    }
+    let key = match (rest[4], &rest[5..]) {
+        (ALGO_EDDSA_LEGACY, [oid_len, rest @ ..]) => {
+            let (oid, mpi) = rest.split_at_checked(*oid_len as usize)?;
+            if oid != ED25519_OID {
+                return None;
+            }
+            match read_mpi(mpi)? {
+                ([0x40, key @ ..], _) => key.try_into().ok()?,
+                _ => return None,
+            }
+        }
+        (ALGO_ED25519, key) => key.get(..32)?.try_into().ok()?,
+        _ => return None,
+    };
+
+    let mut context = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+    context.update(&[0x99]);
+    context.update(&(body.len() as u16).to_be_bytes());
+    context.update(body);
+    let fingerprint = context.finish().as_ref().try_into().ok()?;
+    Some(PublicKey { fingerprint, key })
+}
+
+fn read_mpi(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (bits, rest) = data.split_at_checked(2)?;
+    let len = (u16::from_be_bytes([bits[0], bits[1]]) as usize).div_ceil(8);
+    rest.split_at_checked(len)
+}
+
+/// Parses the first signature packet of a detached signature.
+pub(crate) fn parse_signature(data: &[u8]) -> Result<Signature, String> {
+    let body = packets(data)?
+        .into_iter()
+        .find(|(tag, _)| *tag == TAG_SIGNATURE)
+        .map(|(_, body)| body)
+        .ok_or_else(|| "no signature packet".to_string())?;
+    parse_signature_packet(body).ok_or_else(|| "unsupported or malformed signature packet".to_string())
+}
+
+fn parse_signature_packet(body: &[u8]) -> Option<Signature> {
+    let [4, sig_type, algorithm, hash, hashed_len_hi, hashed_len_lo, rest @ ..] = body else {
+        return None;
+    };
+    let hash = match hash {
+        8 => &digest::SHA256,
+        9 => &digest::SHA384,
+        10 => &digest::SHA512,
+        _ => return None,
+    };
+    let (hashed_subpackets, rest) = rest.split_at_checked(u16::from_be_bytes([*hashed_len_hi, *hashed_len_lo]) as usize)?;
+    let hashed = body[..6 + hashed_subpackets.len()].to_vec();
+    let (unhashed_len, rest) = rest.split_at_checked(2)?;
+    let (unhashed_subpackets, rest) = rest.split_at_checked(u16::from_be_bytes([unhashed_len[0], unhashed_len[1]]) as usize)?;
+    let (hash_prefix, rest) = rest.split_at_checked(2)?;
+
+    let mut signature = [0; 64];
+    match *algorithm {
+        ALGO_EDDSA_LEGACY => {
+            let (r, rest) = read_mpi(rest)?;
+            let (s, _) = read_mpi(rest)?;
+            if r.len() > 32 || s.len() > 32 {
+                return None;
+            }
+            signature[32 - r.len()..32].copy_from_slice(r);
+            signature[64 - s.len()..].copy_from_slice(s);
+        }
+        ALGO_ED25519 => signature.copy_from_slice(rest.get(..64)?),
+        _ => return None,
+    }
+
+    let issuer = subpackets(hashed_subpackets)
+        .chain(subpackets(unhashed_subpackets))
+        .find_map(|(kind, data)| match (kind, data) {
+            (SUBPACKET_ISSUER_FINGERPRINT, [4, fingerprint @ ..]) if fingerprint.len() == 20 => Some(fingerprint[12..].to_vec()),
+            (SUBPACKET_ISSUER, key_id) if key_id.len() == 8 => Some(key_id.to_vec()),
+            _ => None,
+        });
+
+    Some(Signature {
+        sig_type: *sig_type,
+        hash,
+        hashed,
+        hash_prefix: [hash_prefix[0], hash_prefix[1]],
+        issuer,
+        signature,
+    })
+}
+
+fn subpackets(mut data: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    std::iter::from_fn(move || {
+        let (len, rest) = match data {
+            [first @ 0..=191, rest @ ..] => (*first as usize, rest),
+            [first @ 192..=254, second, rest @ ..] => ((((*first as usize) - 192) << 8) + *second as usize + 192, rest),
+            [255, a, b, c, d, rest @ ..] => (u32::from_be_bytes([*a, *b, *c, *d]) as usize, rest),
+            _ => return None,
+        };
+        let (subpacket, rest) = rest.split_at_checked(len)?;
+        data = rest;
+        let (kind, body) = subpacket.split_first()?;
+        Some((kind & 0x7f, body))
+    })
+}
+
+/// Checks `signature` over `message` against `keys`, returning the key that made it.
+pub(crate) fn verify<'a>(keys: &'a [PublicKey], signature: &Signature, message: &[u8]) -> Option<&'a PublicKey> {
+    let message = match signature.sig_type {
+        SIG_BINARY => message.to_vec(),
+        SIG_TEXT => String::from_utf8_lossy(message).replace("\r\n", "\n").replace('\n', "\r\n").into_bytes(),
+        _ => return None,
+    };
+
+    let mut context = digest::Context::new(signature.hash);
+    context.update(&message);
+    context.update(&signature.hashed);
+    context.update(&[4, 0xff]);
+    context.update(&(signature.hashed.len() as u32).to_be_bytes());
+    let hash = context.finish();
+    if hash.as_ref()[..2] != signature.hash_prefix {
+        return None;
+    }
+
+    keys.iter()
+        .filter(|key| signature.issuer.as_deref().is_none_or(|issuer| issuer == key.key_id()))
+        .find(|key| UnparsedPublicKey::new(&ED25519, key.key).verify(hash.as_ref(), &signature.signature).is_ok())
+}
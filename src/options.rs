@@ -0,0 +1,341 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::error::SelfieError;
+use crate::key::RecordKey;
+use crate::name::TemplateNameScheme;
+use crate::progress::{ProgressEvent, ProgressHook, Reporter};
+use crate::time::Instant;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_ATTEMPTS: u32 = 2;
+const DEFAULT_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+const DEFAULT_CONCURRENCY: usize = 16;
+const DEFAULT_MAX_QUERIES: u32 = 32;
+
+/// Per-call knobs for `get_records_with`.
+#[derive(Debug, Clone)]
+pub struct LookupOptions {
+    timeout: Duration,
+    attempts: u32,
+    backoff: Backoff,
+    key_timeouts: HashMap<String, Duration>,
+    offline: bool,
+    bypass_cache: bool,
+    name_templates: Option<Vec<TemplateNameScheme>>,
+    concurrency: usize,
+    max_queries: u32,
+    deadline: Option<Duration>,
+    /// When the deadline of the call these options were fixed for passes.
+    ends_at: Option<Instant>,
+    /// The `dns_server` of the call these options were fixed for, which
+    /// keeps its cached answers apart from other servers'.
+    server: Option<String>,
+    /// The queries of the call these options were fixed for.
+    queries: Option<Arc<QueryBudget>>,
+    progress: Option<ProgressHook>,
+    /// The progress events of the call these options were fixed for.
+    reporter: Option<Arc<Reporter>>,
+    #[cfg(feature = "signatures")]
+    signatures: Option<SignaturePolicy>,
+}
+
+/// Counts the queries one call sends, up to its `max_queries`.
+#[derive(Debug)]
+pub(crate) struct QueryBudget {
+    max: u32,
+    issued: AtomicU32,
+}
+
+impl QueryBudget {
+    fn take(&self) -> Result<(), SelfieError> {
+        self.issued
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |issued| (issued < self.max).then_some(issued + 1))
+            .map(drop)
+            .map_err(|_| SelfieError::BudgetExceeded { max_queries: self.max })
+    }
+}
+
+#[cfg(feature = "signatures")]
+#[derive(Debug, Clone)]
+pub(crate) struct SignaturePolicy {
+    pub(crate) field: String,
+    pub(crate) strict: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Backoff {
+    base: Duration,
+}
+
+impl Backoff {
+    pub(crate) fn new(base: Duration) -> Self {
+        Backoff { base }
+    }
+
+    /// Delay before the retry following failed attempt number `attempt`.
+    pub(crate) fn jittered(self, attempt: u32) -> Duration {
+        let ceiling = self.base.saturating_mul(1 << attempt.saturating_sub(1).min(16)).min(MAX_BACKOFF);
+        rand::thread_rng().gen_range(Duration::ZERO..=ceiling)
+    }
+}
+
+impl Default for LookupOptions {
+    fn default() -> Self {
+        LookupOptions {
+            timeout: DEFAULT_TIMEOUT,
+            attempts: DEFAULT_ATTEMPTS,
+            backoff: Backoff::new(DEFAULT_BACKOFF),
+            key_timeouts: HashMap::new(),
+            offline: false,
+            bypass_cache: false,
+            name_templates: None,
+            concurrency: DEFAULT_CONCURRENCY,
+            max_queries: DEFAULT_MAX_QUERIES,
+            deadline: None,
+            ends_at: None,
+            server: None,
+            queries: None,
+            progress: None,
+            reporter: None,
+            #[cfg(feature = "signatures")]
+            signatures: None,
+        }
+    }
+}
+
+impl LookupOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Global timeout applied to each attempt of a key's lookup.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Number of attempts per key; values below one are treated as one.
+    pub fn attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    /// Base delay between attempts. The n-th retry waits a random time up to
+    /// `base * 2^(n-1)`, capped at five seconds ("full jitter"), so that
+    /// batch jobs started together do not retry in lockstep.
+    pub fn backoff(mut self, base: Duration) -> Self {
+        self.backoff = Backoff::new(base);
+        self
+    }
+
+    /// Overrides the global timeout for `key`, which is matched like the
+    /// keys of a call, ignoring case; a key `RecordKey` rejects fails with
+    /// `InvalidKey`. The budget covers every attempt made for that key, not
+    /// each attempt individually.
+    pub fn key_timeout(mut self, key: &str, budget: Duration) -> Result<Self, SelfieError> {
+        let key = RecordKey::custom(key)?;
+        self.key_timeouts.insert(key.as_str().to_string(), budget);
+        Ok(self)
+    }
+
+    /// Serves this call from overrides and the cache only, as if the SDK
+    /// were offline.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Queries every name again instead of serving fresh answers from the
+    /// cache, negative ones included, e.g. to see whether a record has
+    /// propagated. The answers still replace the cached ones. Offline calls
+    /// are served from the cache regardless.
+    pub fn bypass_cache(mut self, bypass: bool) -> Self {
+        self.bypass_cache = bypass;
+        self
+    }
+
+    /// Builds owner names from `templates` instead of the SDK's name
+    /// scheme and fallbacks, trying them in order until one has records;
+    /// see `SdkBuilder::name_template_fallbacks`. Templates are used as
+    /// they are: build them with `TemplateNameScheme::parse` to check them.
+    pub fn name_templates(mut self, templates: Vec<TemplateNameScheme>) -> Self {
+        self.name_templates = Some(templates).filter(|templates| !templates.is_empty());
+        self
+    }
+
+    /// Most lookups `resolve_matrix` runs at once; values below one are
+    /// treated as one. Defaults to 16.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Most DNS queries one call may send, counting retries, versioned
+    /// names, cross-checks, chunks and verification key lookups. Keys
+    /// reached after the budget is spent fail with
+    /// `SelfieError::BudgetExceeded`; the count is reported as
+    /// `RecordsResponse::queries_issued`. Defaults to 32.
+    pub fn max_queries(mut self, max_queries: u32) -> Self {
+        self.max_queries = max_queries;
+        self
+    }
+
+    /// Bounds the whole call, every key and attempt included: lookups still
+    /// running when it passes fail with `TimeoutBudget::Call`, and no retry
+    /// is started that could not finish before it. Matrix and batch calls
+    /// share one deadline. Unbounded by default.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Calls `callback` with the progress of `get_records*` and
+    /// `resolve_profile*` lookups: `Started` and `Finished` for each key,
+    /// `Retrying` before each retry, then `AllDone`. Matrix lookups do not
+    /// report progress.
+    ///
+    /// Events are delivered in order on a thread started for each call, so
+    /// the callback may block without holding up the lookup, and may still
+    /// be running when the call returns. While it is behind, up to 64 events
+    /// wait for it and later ones are dropped, counted in the summary;
+    /// `AllDone` is always delivered, last.
+    pub fn on_progress(mut self, callback: impl Fn(ProgressEvent) + Send + Sync + 'static) -> Self {
+        self.progress = Some(ProgressHook::new(callback));
+        self
+    }
+
+    /// Verifies inline signatures found in the `sig` field of record values
+    /// against the domain's `_selfie-key` record.
+    #[cfg(feature = "signatures")]
+    pub fn verify_signatures(mut self, verify: bool) -> Self {
+        self.signatures = verify.then(|| SignaturePolicy {
+            field: crate::signature::DEFAULT_SIGNATURE_FIELD.to_string(),
+            strict: false,
+        });
+        self
+    }
+
+    /// Reads inline signatures from `field` instead of `sig`. Enables verification.
+    #[cfg(feature = "signatures")]
+    pub fn signature_field(mut self, field: &str) -> Self {
+        let policy = self.signatures.get_or_insert_with(|| SignaturePolicy { field: String::new(), strict: false });
+        policy.field = field.to_string();
+        self
+    }
+
+    /// Turns a signature that fails to verify into an error for that key
+    /// instead of a flag on the result. Enables verification.
+    #[cfg(feature = "signatures")]
+    pub fn strict_signatures(mut self, strict: bool) -> Self {
+        self = match self.signatures {
+            Some(_) => self,
+            None => self.verify_signatures(true),
+        };
+        if let Some(policy) = self.signatures.as_mut() {
+            policy.strict = strict;
+        }
+        self
+    }
+
+    #[cfg(feature = "signatures")]
+    pub(crate) fn get_signatures(&self) -> Option<&SignaturePolicy> {
+        self.signatures.as_ref()
+    }
+
+    pub(crate) fn get_offline(&self) -> bool {
+        self.offline
+    }
+
+    pub(crate) fn get_name_templates(&self) -> Option<&[TemplateNameScheme]> {
+        self.name_templates.as_deref()
+    }
+
+    pub(crate) fn get_bypass_cache(&self) -> bool {
+        self.bypass_cache
+    }
+
+    pub(crate) fn get_timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub(crate) fn get_attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub(crate) fn get_backoff(&self) -> Backoff {
+        self.backoff
+    }
+
+    pub(crate) fn get_concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    pub(crate) fn get_key_timeout(&self, key: &str) -> Option<Duration> {
+        self.key_timeouts.get(key).copied()
+    }
+
+    pub(crate) fn get_deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    /// Time left before the call's deadline, `None` without one.
+    pub(crate) fn time_left(&self) -> Option<Duration> {
+        self.ends_at.map(|ends_at| ends_at.saturating_duration_since(Instant::now()))
+    }
+
+    /// Starts the deadline running, unless it already is.
+    pub(crate) fn start_deadline(self) -> LookupOptions {
+        let ends_at = self.ends_at.or_else(|| self.deadline.map(|deadline| Instant::now() + deadline));
+        LookupOptions { ends_at, ..self }
+    }
+
+    /// These options with a query budget and progress reporting of their
+    /// own, for one call.
+    pub(crate) fn for_call(&self) -> LookupOptions {
+        let queries = QueryBudget { max: self.max_queries, issued: AtomicU32::new(0) };
+        let reporter = self.progress.as_ref().and_then(Reporter::start).map(Arc::new);
+        LookupOptions { queries: Some(Arc::new(queries)), reporter, ..self.clone().start_deadline() }
+    }
+
+    /// These options for a call sent to `server`, `None` for the SDK's
+    /// own resolver.
+    pub(crate) fn on_server(self, server: Option<&str>) -> LookupOptions {
+        LookupOptions { server: server.map(str::to_string), ..self }
+    }
+
+    /// The key `name` is cached under.
+    pub(crate) fn cache_key<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        match &self.server {
+            Some(server) => Cow::Owned(format!("{} {}", server, name)),
+            None => Cow::Borrowed(name),
+        }
+    }
+
+    pub(crate) fn without_progress(self) -> LookupOptions {
+        LookupOptions { progress: None, ..self }
+    }
+
+    pub(crate) fn reporter(&self) -> Option<&Reporter> {
+        self.reporter.as_deref()
+    }
+
+    /// Counts a query against the call's budget, failing once it is spent.
+    pub(crate) fn take_query(&self) -> Result<(), SelfieError> {
+        self.queries.as_ref().map_or(Ok(()), |queries| queries.take())
+    }
+
+    pub(crate) fn queries_left(&self) -> bool {
+        self.queries.as_ref().is_none_or(|queries| queries.issued.load(Ordering::SeqCst) < queries.max)
+    }
+
+    pub(crate) fn queries_issued(&self) -> u32 {
+        self.queries.as_ref().map_or(0, |queries| queries.issued.load(Ordering::SeqCst))
+    }
+}
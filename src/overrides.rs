@@ -0,0 +1,222 @@
+//! Locally pinned record values, consulted before the network: `/etc/hosts`
+//! for selfie records, for demos, integration environments and air-gapped
+//! setups.
+//!
+//! Override files map identifiers to record keys and values, in JSON
+//!
+//! ```json
+//! { "alice@example.com": { "bitcoin-payment": "bitcoin:bc1q..." } }
+//! ```
+//!
+//! or in the equivalent TOML tables:
+//!
+//! ```toml
+//! ["*@example.com"]
+//! nostr = "npub1..."
+//! ```
+//!
+//! `*@domain` matches every address at that domain; an exact identifier
+//! wins over the wildcard.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::name::{validate_name, Identifier, NameError, NameKind};
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum OverrideError {
+    #[error("Invalid override identifier {identifier:?}: {reason}")]
+    InvalidIdentifier { identifier: String, reason: NameError },
+    #[error("Invalid override file: {0}")]
+    Parse(String),
+    #[error("Cannot read override file {path}: {reason}")]
+    Io { path: String, reason: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Pattern {
+    Exact(Identifier),
+    /// Every address at this domain.
+    AnyAddress(String),
+}
+
+/// A set of pinned record values.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordOverrides {
+    records: HashMap<(Pattern, String), String>,
+}
+
+impl RecordOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pins `key` of `identifier`, an address, a domain or `*@domain`, to `value`.
+    pub fn insert(&mut self, identifier: &str, key: &str, value: &str) -> Result<(), OverrideError> {
+        let pattern = pattern(identifier)?;
+        self.records.insert((pattern, key.to_string()), value.to_string());
+        Ok(())
+    }
+
+    /// Reads an override file, as JSON when its name ends in `.json` and as
+    /// TOML otherwise.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, OverrideError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| OverrideError::Io { path: path.display().to_string(), reason: e.to_string() })?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::parse_json(&text),
+            _ => Self::parse_toml(&text),
+        }
+    }
+
+    pub fn parse_json(text: &str) -> Result<Self, OverrideError> {
+        let document: serde_json::Value =
+            serde_json::from_str(text).map_err(|e| OverrideError::Parse(e.to_string()))?;
+        let identifiers = document
+            .as_object()
+            .ok_or_else(|| OverrideError::Parse("expected an object of identifiers".to_string()))?;
+        let mut overrides = Self::new();
+        for (identifier, records) in identifiers {
+            let records = records
+                .as_object()
+                .ok_or_else(|| OverrideError::Parse(format!("{:?} must map record keys to values", identifier)))?;
+            for (key, value) in records {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| OverrideError::Parse(format!("value of {:?} for {:?} must be a string", key, identifier)))?;
+                overrides.insert(identifier, key, value)?;
+            }
+        }
+        Ok(overrides)
+    }
+
+    /// Parses the subset of TOML override files need: one table per
+    /// identifier, with string values.
+    pub fn parse_toml(text: &str) -> Result<Self, OverrideError> {
+        let mut overrides = Self::new();
+        let mut identifier = None;
+        for (number, line) in text.lines().enumerate() {
+            let parse_error = |reason: &str| OverrideError::Parse(format!("line {}: {}", number + 1, reason));
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let (name, rest) = toml_key(header.trim_start()).ok_or_else(|| parse_error("invalid table name"))?;
+                if !rest.trim_start().starts_with(']') || !is_comment(&rest.trim_start()[1..]) {
+                    return Err(parse_error("expected ']' after table name"));
+                }
+                identifier = Some(name);
+                continue;
+            }
+            let Some(identifier) = identifier.as_deref() else {
+                return Err(parse_error("record outside of an identifier table"));
+            };
+            let (key, rest) = toml_key(line).ok_or_else(|| parse_error("invalid record key"))?;
+            let rest = rest.trim_start().strip_prefix('=').ok_or_else(|| parse_error("expected '='"))?;
+            let (value, rest) = toml_string(rest.trim_start()).ok_or_else(|| parse_error("expected a string value"))?;
+            if !is_comment(rest) {
+                return Err(parse_error("unexpected text after value"));
+            }
+            overrides.insert(identifier, &key, &value)?;
+        }
+        Ok(overrides)
+    }
+
+    /// Adds every entry of `other`, replacing values for the same identifier and key.
+    pub fn extend(&mut self, other: RecordOverrides) {
+        self.records.extend(other.records);
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub(crate) fn get(&self, identifier: &Identifier, key: &str) -> Option<&str> {
+        // Most SDKs have no overrides; skip building the lookup keys.
+        if self.records.is_empty() {
+            return None;
+        }
+        let identifier = normalize(identifier.clone());
+        let exact = self.records.get(&(Pattern::Exact(identifier.clone()), key.to_string()));
+        let wildcard = || match identifier {
+            Identifier::Email { domain, .. } => self.records.get(&(Pattern::AnyAddress(domain), key.to_string())),
+            Identifier::Domain(_) => None,
+        };
+        exact.or_else(wildcard).map(String::as_str)
+    }
+}
+
+fn pattern(identifier: &str) -> Result<Pattern, OverrideError> {
+    let invalid = |reason| OverrideError::InvalidIdentifier { identifier: identifier.to_string(), reason };
+    if let Some(domain) = identifier.strip_prefix("*@") {
+        return match validate_name(&format!("wildcard@{}", domain)).map_err(invalid)? {
+            NameKind::Email { domain, .. } => Ok(Pattern::AnyAddress(domain.into())),
+            NameKind::Domain(_) => unreachable!("an address parses as an address"),
+        };
+    }
+    Ok(Pattern::Exact(validate_name(identifier).map_err(invalid)?.into()))
+}
+
+/// Domains compare case-insensitively; local parts are kept as written.
+fn normalize(identifier: Identifier) -> Identifier {
+    match identifier {
+        Identifier::Domain(domain) => Identifier::Domain(domain.to_ascii_lowercase()),
+        Identifier::Email { local, domain } => Identifier::Email { local, domain: domain.to_ascii_lowercase() },
+    }
+}
+
+pub(crate) fn is_comment(rest: &str) -> bool {
+    let rest = rest.trim();
+    rest.is_empty() || rest.starts_with('#')
+}
+
+/// Reads a bare or quoted key, returning it and the remaining text.
+pub(crate) fn toml_key(text: &str) -> Option<(String, &str)> {
+    if text.starts_with('"') || text.starts_with('\'') {
+        return toml_string(text);
+    }
+    let end = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .unwrap_or(text.len());
+    (end > 0).then(|| (text[..end].to_string(), &text[end..]))
+}
+
+/// Reads a basic (`"..."`, with escapes) or literal (`'...'`) string.
+pub(crate) fn toml_string(text: &str) -> Option<(String, &str)> {
+    if let Some(literal) = text.strip_prefix('\'') {
+        let end = literal.find('\'')?;
+        return Some((literal[..end].to_string(), &literal[end + 1..]));
+    }
+    let mut chars = text.strip_prefix('"')?.char_indices();
+    let mut value = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &text[i + 2..])),
+            '\\' => {
+                let escaped = match chars.next()?.1 {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    '"' => '"',
+                    '\\' => '\\',
+                    'u' => {
+                        let hex: String = (0..4).map(|_| chars.next().map(|(_, c)| c)).collect::<Option<_>>()?;
+                        char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                    }
+                    _ => return None,
+                };
+                value.push(escaped);
+            }
+            c => value.push(c),
+        }
+    }
+    None
+}
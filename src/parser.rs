@@ -0,0 +1,116 @@
+//! Typed parsing of record values, one `RecordParser` per key. The SDK
+//! has parsers for the keys of `EXTENDED_RECORDS`; applications register
+//! their own with `SdkBuilder::register_parser`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use log::debug;
+use thiserror::Error;
+
+use crate::error::SelfieError;
+use crate::records::{Bip21Uri, Did, Lnurl, NodeUri, NostrKey, PaymentCode, PgpRecord};
+use crate::response::KeyResult;
+
+/// A record value parsed by its key's `RecordParser`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "kind", content = "value", rename_all = "snake_case")
+)]
+pub enum ParsedValue {
+    BitcoinPayment(Bip21Uri),
+    Pgp(PgpRecord),
+    Nostr(NostrKey),
+    NodeUri(NodeUri),
+    Bip47(PaymentCode),
+    Did(Did),
+    Lnurl(Lnurl),
+    LightningAddress(Lnurl),
+    /// The output of an application's parser.
+    Custom(serde_json::Value),
+}
+
+/// Why a record value does not parse. The lookup itself still succeeded.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{reason}")]
+pub struct ParseError {
+    pub reason: String,
+}
+
+impl ParseError {
+    pub fn new(reason: impl Into<String>) -> Self {
+        ParseError { reason: reason.into() }
+    }
+}
+
+impl From<SelfieError> for ParseError {
+    fn from(error: SelfieError) -> Self {
+        match error {
+            SelfieError::InvalidRecord { reason, .. } => ParseError { reason },
+            other => ParseError { reason: other.to_string() },
+        }
+    }
+}
+
+/// Parses the values of one record key.
+pub trait RecordParser: Send + Sync {
+    /// The key whose values this parses, matched case-insensitively.
+    fn key(&self) -> &str;
+
+    fn parse(&self, raw: &str) -> Result<ParsedValue, ParseError>;
+}
+
+/// The parser of a well-known key: `FromStr` of its record type.
+struct Builtin {
+    key: &'static str,
+    parse: fn(&str) -> Result<ParsedValue, SelfieError>,
+}
+
+impl RecordParser for Builtin {
+    fn key(&self) -> &str {
+        self.key
+    }
+
+    fn parse(&self, raw: &str) -> Result<ParsedValue, ParseError> {
+        Ok((self.parse)(raw)?)
+    }
+}
+
+const BUILTINS: [Builtin; 8] = [
+    Builtin { key: "bitcoin-payment", parse: |raw| raw.parse().map(ParsedValue::BitcoinPayment) },
+    Builtin { key: "pgp", parse: |raw| raw.parse().map(ParsedValue::Pgp) },
+    Builtin { key: "nostr", parse: |raw| raw.parse().map(ParsedValue::Nostr) },
+    Builtin { key: "node-uri", parse: |raw| raw.parse().map(ParsedValue::NodeUri) },
+    Builtin { key: "bip47", parse: |raw| raw.parse().map(ParsedValue::Bip47) },
+    Builtin { key: "did", parse: |raw| raw.parse().map(ParsedValue::Did) },
+    Builtin { key: "lnurl", parse: |raw| raw.parse().map(ParsedValue::Lnurl) },
+    Builtin { key: "lightning-address", parse: |raw| raw.parse().map(ParsedValue::LightningAddress) },
+];
+
+/// The parser of each key: the built-in ones, replaced by registered ones
+/// for the same key.
+pub(crate) struct Parsers(HashMap<String, Arc<dyn RecordParser>>);
+
+impl Parsers {
+    pub(crate) fn new(registered: Vec<Arc<dyn RecordParser>>) -> Self {
+        let builtins = BUILTINS.into_iter().map(|builtin| Arc::new(builtin) as Arc<dyn RecordParser>);
+        let parsers = builtins.chain(registered).map(|parser| (parser.key().to_ascii_lowercase(), parser));
+        Parsers(parsers.collect())
+    }
+
+    /// Parses the value of `entry`, if it has one and `key` has a parser.
+    pub(crate) fn apply(&self, key: &str, entry: &mut KeyResult) {
+        let (Some(value), Some(parser)) = (&entry.value, self.0.get(key)) else {
+            return;
+        };
+        match parser.parse(value) {
+            Ok(parsed) => entry.parsed = Some(parsed),
+            Err(e) => {
+                debug!("Value of {} does not parse: {}", key, e);
+                entry.parse_error = Some(e);
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! All of a name's records in one typed struct.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::SystemTime;
+
+use crate::error::SelfieError;
+use crate::records::{Bip21Uri, Did, Lnurl, NodeUri, NostrKey, PaymentCode, PgpRecord};
+use crate::response::{RecordsResponse, Source};
+
+/// Whether a profile's values were authenticated with DNSSEC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "snake_case"))]
+pub enum DnssecStatus {
+    /// DNSSEC is required by the SDK and every value came from DNS, so each
+    /// one was served with a secure chain of trust.
+    Validated,
+    NotValidated,
+}
+
+impl fmt::Display for DnssecStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnssecStatus::Validated => f.write_str("validated"),
+            DnssecStatus::NotValidated => f.write_str("not validated"),
+        }
+    }
+}
+
+/// A name's well-known records, parsed, plus any other keys looked up.
+/// Missing records leave their field `None`; records that failed to
+/// resolve or to parse are listed in `problems` instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SelfieProfile {
+    pub name: String,
+    pub bitcoin_payment: Option<Bip21Uri>,
+    pub pgp: Option<PgpRecord>,
+    pub nostr: Option<NostrKey>,
+    pub node_uri: Option<NodeUri>,
+    /// Only set when `bip47` is among the keys looked up.
+    pub bip47: Option<PaymentCode>,
+    /// Only set when `did` is among the keys looked up.
+    pub did: Option<Did>,
+    /// Only set when `lnurl` is among the keys looked up.
+    pub lnurl: Option<Lnurl>,
+    /// Only set when `lightning-address` is among the keys looked up.
+    pub lightning_address: Option<Lnurl>,
+    /// Raw values of keys outside the well-known set.
+    pub extra: HashMap<String, String>,
+    /// Per-key failures, sorted by key.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_problems"))]
+    pub problems: Vec<(String, SelfieError)>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_time"))]
+    pub resolved_at: SystemTime,
+    /// The resolver the lookups went through, e.g. `8.8.8.8` or `system`.
+    pub resolver: String,
+    pub dnssec: DnssecStatus,
+}
+
+impl SelfieProfile {
+    pub(crate) fn from_response(
+        name: &str,
+        response: &RecordsResponse,
+        resolver: &str,
+        dnssec_required: bool,
+        resolved_at: SystemTime,
+    ) -> Self {
+        let mut profile = SelfieProfile {
+            name: name.to_string(),
+            bitcoin_payment: None,
+            pgp: None,
+            nostr: None,
+            node_uri: None,
+            bip47: None,
+            did: None,
+            lnurl: None,
+            lightning_address: None,
+            extra: HashMap::new(),
+            problems: Vec::new(),
+            resolved_at,
+            resolver: resolver.to_string(),
+            dnssec: DnssecStatus::NotValidated,
+        };
+
+        let mut validated = dnssec_required;
+        for (key, result) in response.iter() {
+            let value = match (&result.value, &result.error) {
+                (Some(value), _) => value,
+                (None, Some(SelfieError::NoRecords) | None) => continue,
+                (None, Some(e)) => {
+                    profile.problems.push((key.to_string(), e.clone()));
+                    continue;
+                }
+            };
+            validated &= result.source != Some(Source::Override);
+            let parsed = match key {
+                "bitcoin-payment" => value.parse().map(|uri| profile.bitcoin_payment = Some(uri)),
+                "pgp" => value.parse().map(|pgp| profile.pgp = Some(pgp)),
+                "nostr" => value.parse().map(|key| profile.nostr = Some(key)),
+                "node-uri" => value.parse().map(|uri| profile.node_uri = Some(uri)),
+                "bip47" => value.parse().map(|code| profile.bip47 = Some(code)),
+                "did" => value.parse().map(|did| profile.did = Some(did)),
+                "lnurl" => value.parse().map(|lnurl| profile.lnurl = Some(lnurl)),
+                "lightning-address" => value.parse().map(|address| profile.lightning_address = Some(address)),
+                _ => {
+                    profile.extra.insert(key.to_string(), value.clone());
+                    Ok(())
+                }
+            };
+            if let Err(e) = parsed {
+                profile.problems.push((key.to_string(), e));
+            }
+        }
+        profile.problems.sort_by(|a, b| a.0.cmp(&b.0));
+        if validated && !profile.is_empty() {
+            profile.dnssec = DnssecStatus::Validated;
+        }
+        profile
+    }
+
+    /// Whether no record was found at all.
+    pub fn is_empty(&self) -> bool {
+        self.bitcoin_payment.is_none()
+            && self.pgp.is_none()
+            && self.nostr.is_none()
+            && self.node_uri.is_none()
+            && self.bip47.is_none()
+            && self.did.is_none()
+            && self.lnurl.is_none()
+            && self.lightning_address.is_none()
+            && self.extra.is_empty()
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_problems<S: serde::Serializer>(problems: &[(String, SelfieError)], serializer: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeSeq;
+
+    let mut seq = serializer.serialize_seq(Some(problems.len()))?;
+    for (key, error) in problems {
+        seq.serialize_element(&serde_json::json!({
+            "key": key,
+            "error": error.to_string(),
+            "code": error.code(),
+            "numeric_code": error.numeric_code(),
+        }))?;
+    }
+    seq.end()
+}
+
+/// Seconds since the epoch.
+#[cfg(feature = "serde")]
+fn serialize_time<S: serde::Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    let seconds = time.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    serializer.serialize_u64(seconds)
+}
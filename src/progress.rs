@@ -0,0 +1,159 @@
+//! Progress events of a running lookup, for UIs that show each key as it
+//! resolves.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::SelfieError;
+use crate::response::{KeyResult, RecordEntry, RecordsResponse};
+use crate::time::Instant;
+
+/// Events kept for a callback that has not caught up; later ones are dropped.
+const QUEUE_LENGTH: usize = 64;
+
+/// One step of a lookup, see `LookupOptions::on_progress`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    Started { key: String },
+    /// An attempt failed and attempt number `attempt`, counting from one,
+    /// is about to be made.
+    Retrying { key: String, attempt: u32 },
+    Finished { key: String, outcome: KeyOutcome },
+    /// Always the last event of a call.
+    AllDone { summary: ProgressSummary },
+}
+
+/// How the lookup of a key ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyOutcome {
+    Found,
+    NotFound,
+    Failed(SelfieError),
+}
+
+impl KeyOutcome {
+    fn of(result: &KeyResult) -> Self {
+        match result.entry() {
+            RecordEntry::Found { .. } => KeyOutcome::Found,
+            RecordEntry::NotFound => KeyOutcome::NotFound,
+            RecordEntry::Error(e) => KeyOutcome::Failed(e.clone()),
+        }
+    }
+}
+
+/// Totals of a call, sent with `ProgressEvent::AllDone`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProgressSummary {
+    pub found: usize,
+    pub not_found: usize,
+    pub failed: usize,
+    pub queries_issued: u32,
+    pub duration: Duration,
+    /// Events left out because the callback fell behind.
+    pub dropped: u32,
+}
+
+/// The callback passed to `LookupOptions::on_progress`.
+#[derive(Clone)]
+pub(crate) struct ProgressHook(Arc<dyn Fn(ProgressEvent) + Send + Sync>);
+
+impl ProgressHook {
+    pub(crate) fn new(callback: impl Fn(ProgressEvent) + Send + Sync + 'static) -> Self {
+        ProgressHook(Arc::new(callback))
+    }
+}
+
+impl fmt::Debug for ProgressHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressHook")
+    }
+}
+
+/// Queues one call's events for a thread that hands them to the callback,
+/// so that a slow or blocking callback never holds up the lookup.
+#[derive(Debug)]
+pub(crate) struct Reporter {
+    queue: Mutex<Option<SyncSender<ProgressEvent>>>,
+    /// The key being looked up, which retries are reported for.
+    key: Mutex<String>,
+    started: Instant,
+    dropped: AtomicU32,
+    /// `AllDone`, when the queue had no room left for it.
+    last: Arc<Mutex<Option<ProgressEvent>>>,
+}
+
+impl Reporter {
+    /// `None` when no thread could be started for the callback.
+    pub(crate) fn start(hook: &ProgressHook) -> Option<Self> {
+        let (queue, events) = mpsc::sync_channel(QUEUE_LENGTH);
+        let last = Arc::new(Mutex::new(None));
+        let (callback, pending) = (hook.0.clone(), last.clone());
+        let spawned = thread::Builder::new().name("selfie-progress".to_string()).spawn(move || {
+            // Ends once the reporter has sent `AllDone` and let go of the queue.
+            for event in events {
+                callback(event);
+            }
+            if let Some(event) = pending.lock().unwrap_or_else(PoisonError::into_inner).take() {
+                callback(event);
+            }
+        });
+        spawned.ok()?;
+        Some(Reporter {
+            queue: Mutex::new(Some(queue)),
+            key: Mutex::new(String::new()),
+            started: Instant::now(),
+            dropped: AtomicU32::new(0),
+            last,
+        })
+    }
+
+    pub(crate) fn started(&self, key: &str) {
+        key.clone_into(&mut self.key.lock().unwrap_or_else(PoisonError::into_inner));
+        self.send(ProgressEvent::Started { key: key.to_string() });
+    }
+
+    pub(crate) fn retrying(&self, attempt: u32) {
+        let key = self.key.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        self.send(ProgressEvent::Retrying { key, attempt });
+    }
+
+    pub(crate) fn finished(&self, key: &str, result: &KeyResult) {
+        self.send(ProgressEvent::Finished { key: key.to_string(), outcome: KeyOutcome::of(result) });
+    }
+
+    /// Sends `AllDone` for `response`; nothing is sent after it.
+    pub(crate) fn done(&self, response: &RecordsResponse) {
+        let Some(queue) = self.queue.lock().unwrap_or_else(PoisonError::into_inner).take() else {
+            return;
+        };
+        let mut summary = ProgressSummary {
+            queries_issued: response.queries_issued,
+            duration: self.started.elapsed(),
+            ..ProgressSummary::default()
+        };
+        for (_, result) in response.iter() {
+            match KeyOutcome::of(result) {
+                KeyOutcome::Found => summary.found += 1,
+                KeyOutcome::NotFound => summary.not_found += 1,
+                KeyOutcome::Failed(_) => summary.failed += 1,
+            }
+        }
+        summary.dropped = self.dropped.load(Ordering::SeqCst);
+        if let Err(TrySendError::Full(event)) = queue.try_send(ProgressEvent::AllDone { summary }) {
+            // Delivered by the thread once the queue is drained, when
+            // dropping `queue` below ends it.
+            *self.last.lock().unwrap_or_else(PoisonError::into_inner) = Some(event);
+        }
+    }
+
+    fn send(&self, event: ProgressEvent) {
+        let queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(Err(TrySendError::Full(_))) = queue.as_ref().map(|queue| queue.try_send(event)) {
+            self.dropped.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
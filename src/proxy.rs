@@ -0,0 +1,203 @@
+//! SOCKS5 (RFC 1928) connections, so that the SDK's DNS traffic can leave
+//! through a proxy such as Tor's; see `SdkBuilder::proxy`. Only CONNECT is
+//! used, since few proxies support UDP ASSOCIATE: queries go over TCP.
+
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+
+use async_trait::async_trait;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::net::TcpStream;
+use crate::transport::{self, DnsTransport, TransportError};
+use crate::wire::Transport;
+
+/// A SOCKS5 proxy and the credentials it wants, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Socks5Config {
+    pub addr: SocketAddr,
+    pub auth: Option<Socks5Auth>,
+}
+
+impl Socks5Config {
+    pub fn new(addr: SocketAddr) -> Self {
+        Socks5Config { addr, auth: None }
+    }
+
+    /// Tor's default SOCKS port, 127.0.0.1:9050.
+    pub fn tor() -> Self {
+        Self::new(([127, 0, 0, 1], 9050).into())
+    }
+
+    /// Authenticates with `username` and `password` (RFC 1929). Tor keeps
+    /// connections with different credentials on different circuits.
+    pub fn auth(mut self, username: &str, password: &str) -> Self {
+        self.auth = Some(Socks5Auth { username: username.to_string(), password: password.to_string() });
+        self
+    }
+}
+
+/// Each at most 255 bytes.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Socks5Auth {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for Socks5Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Socks5Auth").field("username", &self.username).finish_non_exhaustive()
+    }
+}
+
+/// Where a connection through the proxy goes. Host names are resolved by
+/// the proxy, so that looking them up leaks nothing either.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Target {
+    Address(SocketAddr),
+    Host(String, u16),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Address(address) => write!(f, "{}", address),
+            Target::Host(host, port) => write!(f, "{}:{}", host, port),
+        }
+    }
+}
+
+/// Opens a connection to `target` through `proxy`. Failing to reach the
+/// proxy, or to get past its greeting, is `ProxyUnreachable`; the proxy
+/// failing to reach `target` is an I/O error.
+pub(crate) async fn connect(proxy: &Socks5Config, target: &Target) -> Result<TcpStream, TransportError> {
+    let unreachable = |reason: String| TransportError::ProxyUnreachable { proxy: proxy.addr, reason };
+    let mut stream = TcpStream::connect(proxy.addr).await.map_err(|e| unreachable(e.to_string()))?;
+    greet(&mut stream, proxy).await.map_err(unreachable)?;
+
+    let mut request = vec![5, 1, 0];
+    match target {
+        Target::Address(address) => {
+            match address.ip() {
+                IpAddr::V4(ip) => request.extend([1].into_iter().chain(ip.octets())),
+                IpAddr::V6(ip) => request.extend([4].into_iter().chain(ip.octets())),
+            }
+            request.extend(address.port().to_be_bytes());
+        }
+        Target::Host(host, port) => {
+            let len = u8::try_from(host.len()).map_err(|_| TransportError::Io(format!("host name {} is too long", host)))?;
+            request.extend([3, len]);
+            request.extend(host.as_bytes());
+            request.extend(port.to_be_bytes());
+        }
+    }
+    stream.write_all(&request).await?;
+
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != 5 {
+        return Err(TransportError::Io(format!("SOCKS5 proxy {} sent an invalid reply", proxy.addr)));
+    }
+    if reply[1] != 0 {
+        return Err(TransportError::Io(format!("SOCKS5 proxy {} could not reach {}: {}", proxy.addr, target, reply_error(reply[1]))));
+    }
+    // The address the proxy connected from, which is of no use here.
+    let bound = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => stream.read_u8().await? as usize,
+        _ => return Err(TransportError::Io(format!("SOCKS5 proxy {} sent an invalid reply", proxy.addr))),
+    };
+    let mut skipped = vec![0; bound + 2];
+    stream.read_exact(&mut skipped).await?;
+    Ok(stream)
+}
+
+/// Agrees on an authentication method and authenticates.
+async fn greet(stream: &mut TcpStream, proxy: &Socks5Config) -> Result<(), String> {
+    let io = |e: std::io::Error| e.to_string();
+    let greeting: &[u8] = match proxy.auth {
+        Some(_) => &[5, 2, 0, 2],
+        None => &[5, 1, 0],
+    };
+    stream.write_all(greeting).await.map_err(io)?;
+    let mut choice = [0; 2];
+    stream.read_exact(&mut choice).await.map_err(io)?;
+    match (choice, &proxy.auth) {
+        ([5, 0], _) => Ok(()),
+        ([5, 2], Some(auth)) => {
+            let (username, password) = (auth.username.as_bytes(), auth.password.as_bytes());
+            let (Ok(username_len), Ok(password_len)) = (u8::try_from(username.len()), u8::try_from(password.len())) else {
+                return Err("credentials longer than 255 bytes".to_string());
+            };
+            let mut request = vec![1, username_len];
+            request.extend(username);
+            request.push(password_len);
+            request.extend(password);
+            stream.write_all(&request).await.map_err(io)?;
+            let mut status = [0; 2];
+            stream.read_exact(&mut status).await.map_err(io)?;
+            match status[1] {
+                0 => Ok(()),
+                _ => Err("credentials rejected".to_string()),
+            }
+        }
+        ([5, _], _) => Err("no acceptable authentication method".to_string()),
+        _ => Err("not a SOCKS5 proxy".to_string()),
+    }
+}
+
+fn reply_error(code: u8) -> &'static str {
+    match code {
+        1 => "general failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+/// DNS over TCP through a SOCKS5 proxy, a new connection per message.
+#[derive(Debug, Clone)]
+pub struct Socks5Transport {
+    proxy: Socks5Config,
+    server: Target,
+}
+
+impl Socks5Transport {
+    pub fn new(proxy: Socks5Config, server: SocketAddr) -> Self {
+        Socks5Transport { proxy, server: Target::Address(server) }
+    }
+
+    /// Connects to `host`, which the proxy resolves.
+    pub fn to_host(proxy: Socks5Config, host: &str, port: u16) -> Self {
+        Socks5Transport { proxy, server: Target::Host(host.to_string(), port) }
+    }
+}
+
+#[async_trait]
+impl DnsTransport for Socks5Transport {
+    async fn exchange(&self, request_wire: &[u8]) -> Result<Vec<u8>, TransportError> {
+        let mut stream = connect(&self.proxy, &self.server).await?;
+        transport::exchange_framed(&mut stream, request_wire).await
+    }
+
+    fn kind(&self) -> Transport {
+        Transport::Tcp
+    }
+
+    fn describe(&self) -> String {
+        format!("{} via socks5://{}", self.server, self.proxy.addr)
+    }
+
+    fn server(&self) -> Option<SocketAddr> {
+        match self.server {
+            Target::Address(address) => Some(address),
+            Target::Host(..) => None,
+        }
+    }
+}
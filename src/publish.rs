@@ -0,0 +1,226 @@
+//! Creating and updating selfie records through a DNS provider's API, or
+//! by hand from a `RecordInstruction`.
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use thiserror::Error;
+
+use crate::chunks::{self, ChunkManifest};
+use crate::name::{self, Identifier, SelfieNameScheme};
+
+#[cfg(feature = "cloudflare")]
+mod cloudflare;
+#[cfg(feature = "cloudflare")]
+pub use cloudflare::CloudflareProvider;
+
+const DEFAULT_TTL: u32 = 3600;
+/// Longest character-string a TXT record can hold.
+const MAX_STRING_LENGTH: usize = 255;
+/// Longest rdata, length bytes of the character-strings included.
+const MAX_RDATA_LENGTH: usize = 65535;
+
+/// The TXT values published under one owner name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxtRecordSet {
+    /// Fully-qualified owner name, without the trailing dot.
+    pub name: String,
+    pub values: Vec<String>,
+    /// The smallest TTL when the provider stores one per value.
+    pub ttl: u32,
+}
+
+/// Why a provider operation failed.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ProviderError {
+    #[error("Provider rejected the credentials: {0}")]
+    Unauthorized(String),
+    #[error("Zone {0} not found at provider")]
+    ZoneNotFound(String),
+    #[error("Rate limited by provider{}", retry_after.map(|d| format!(" (retry after {}s)", d.as_secs())).unwrap_or_default())]
+    RateLimited { retry_after: Option<Duration> },
+    /// An error the provider reported in its response.
+    #[error("Provider error {code}: {message}")]
+    Api { code: i64, message: String },
+    #[error("Invalid provider response: {0}")]
+    InvalidResponse(String),
+    #[error("Invalid record: {0}")]
+    InvalidRecord(String),
+    #[error("{0}")]
+    Transport(String),
+}
+
+/// A DNS hosting API able to manage TXT records. Names are fully-qualified,
+/// without the trailing dot, and lie within `zone`.
+#[async_trait]
+pub trait DnsProvider: Send + Sync {
+    /// Makes `values` the only TXT values at `name`, each with `ttl`.
+    /// Values already published are left untouched.
+    async fn upsert_txt(&self, zone: &str, name: &str, values: &[String], ttl: u32) -> Result<(), ProviderError>;
+
+    /// Removes every TXT value at `name`.
+    async fn delete_txt(&self, zone: &str, name: &str) -> Result<(), ProviderError>;
+
+    /// Lists every TXT record set in `zone`.
+    async fn list_txt(&self, zone: &str) -> Result<Vec<TxtRecordSet>, ProviderError>;
+}
+
+/// A selfie record to publish for an identifier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfieRecord {
+    pub identifier: String,
+    pub key: String,
+    pub values: Vec<String>,
+    pub ttl: u32,
+    /// Publishes under the `_v{version}` name versioned lookups try first.
+    pub version: Option<u32>,
+}
+
+impl SelfieRecord {
+    pub fn new(identifier: &str, key: &str, value: &str) -> Self {
+        SelfieRecord {
+            identifier: identifier.to_string(),
+            key: key.to_string(),
+            values: vec![value.to_string()],
+            ttl: DEFAULT_TTL,
+            version: None,
+        }
+    }
+
+    /// The records publishing `payload` in chunks of at most `chunk_size`
+    /// bytes: first the manifest for `key`, then the chunks for `{key}.1`
+    /// and up, which SDKs built with `SdkBuilder::chunked_key(key)`
+    /// reassemble. Fails when more than 64 chunks would be needed.
+    pub fn chunked(identifier: &str, key: &str, payload: &str, chunk_size: usize) -> Result<Vec<SelfieRecord>, ProviderError> {
+        let pieces = chunks::split(payload, chunk_size);
+        let count = match u32::try_from(pieces.len()) {
+            Ok(count @ 1..=chunks::MAX_CHUNKS) => count,
+            _ => {
+                let reason = format!("{} chunks of {} bytes, from 1 to {} allowed", pieces.len(), chunk_size, chunks::MAX_CHUNKS);
+                return Err(ProviderError::InvalidRecord(reason));
+            }
+        };
+        let manifest = ChunkManifest::new(payload.as_bytes(), count);
+        let mut records = vec![SelfieRecord::new(identifier, key, &manifest.to_string())];
+        for (index, piece) in (1..).zip(pieces) {
+            records.push(SelfieRecord::new(identifier, &chunks::chunk_key(key, index), piece));
+        }
+        Ok(records)
+    }
+
+    pub fn ttl(mut self, ttl: u32) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = Some(version);
+        self
+    }
+}
+
+/// What to enter in a DNS panel to publish `value` for `key`: the owner
+/// name `get_txt_record_key` gives and the value split into
+/// character-strings of at most 255 bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordInstruction {
+    /// Fully-qualified owner name, lowercased, without the trailing dot.
+    pub name: String,
+    /// The identifier's domain, which `name` lies in.
+    pub zone: String,
+    pub value: String,
+    /// `value` split at character boundaries.
+    pub strings: Vec<String>,
+    pub ttl: u32,
+    /// Things worth telling the user, e.g. that the key is not one lookups
+    /// ask for by default.
+    pub warnings: Vec<String>,
+}
+
+impl RecordInstruction {
+    /// Fails for invalid names and for values longer than a TXT record
+    /// can hold.
+    pub fn for_key(name: &str, key: &str, value: &str) -> Result<RecordInstruction, ProviderError> {
+        let invalid = |e: &dyn std::fmt::Display| ProviderError::InvalidRecord(e.to_string());
+        let identifier: Identifier = name::validate_name(name).map_err(|e| invalid(&e))?.into();
+        let zone = match &identifier {
+            Identifier::Domain(domain) | Identifier::Email { domain, .. } => domain.to_ascii_lowercase(),
+        };
+        let owner = name::build_record_key(&SelfieNameScheme, &identifier, key, None).to_ascii_lowercase();
+        name::validate_dns_name(&owner).map_err(|e| invalid(&e))?;
+
+        let strings = split_strings(value);
+        let length: usize = strings.iter().map(|string| string.len() + 1).sum();
+        if length > MAX_RDATA_LENGTH {
+            return Err(invalid(&format!("{} bytes of TXT data, at most {} fit in a record", length, MAX_RDATA_LENGTH)));
+        }
+        let mut warnings = Vec::new();
+        if !crate::EXTENDED_RECORDS.contains(&key) {
+            warnings.push(format!("{} is not a well-known record key; lookups only ask for it by name", key));
+        }
+        if value.trim() != value {
+            warnings.push("the value starts or ends with whitespace".to_string());
+        }
+        Ok(RecordInstruction { name: owner, zone, value: value.to_string(), strings, ttl: DEFAULT_TTL, warnings })
+    }
+
+    pub fn ttl(mut self, ttl: u32) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// `name` relative to the zone, as registrar UIs ask for it.
+    pub fn host(&self) -> &str {
+        self.name.strip_suffix(&self.zone).and_then(|host| host.strip_suffix('.')).unwrap_or("@")
+    }
+
+    /// A BIND zone-file line, each character-string quoted, with `"` and
+    /// `\` escaped and other bytes outside printable ASCII as `\DDD`.
+    pub fn to_zone_line(&self) -> String {
+        let mut line = format!("{}. {} IN TXT", self.name, self.ttl);
+        for string in &self.strings {
+            line.push_str(" \"");
+            for byte in string.bytes() {
+                match byte {
+                    b'"' | b'\\' => {
+                        line.push('\\');
+                        line.push(byte as char);
+                    }
+                    0x20..=0x7e => line.push(byte as char),
+                    _ => write!(line, "\\{:03}", byte).expect("writing to a string cannot fail"),
+                }
+            }
+            line.push('"');
+        }
+        line
+    }
+
+    /// The body of a Cloudflare v4 `POST /zones/{zone_id}/dns_records`.
+    /// Values of several character-strings are given quoted.
+    pub fn to_cloudflare_json(&self) -> serde_json::Value {
+        let quoted = |string: &String| format!("\"{}\"", string.replace('\\', "\\\\").replace('"', "\\\""));
+        let content = match self.strings.as_slice() {
+            [_] => self.value.clone(),
+            strings => strings.iter().map(quoted).collect::<Vec<_>>().join(" "),
+        };
+        serde_json::json!({ "type": "TXT", "name": self.name, "content": content, "ttl": self.ttl })
+    }
+}
+
+/// Splits `value` into pieces of at most 255 bytes, never inside a
+/// character. An empty value is one empty string.
+fn split_strings(value: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut rest = value;
+    while rest.len() > MAX_STRING_LENGTH {
+        let mut end = MAX_STRING_LENGTH;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        strings.push(rest[..end].to_string());
+        rest = &rest[end..];
+    }
+    strings.push(rest.to_string());
+    strings
+}
@@ -0,0 +1,234 @@
+//! The Cloudflare v4 API, authenticated with an API token that has the
+//! `Zone:Read` and `DNS:Edit` permissions.
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use log::debug;
+use serde_json::{json, Value};
+
+use super::{DnsProvider, ProviderError, TxtRecordSet};
+use crate::doh::parse_retry_after;
+use crate::http::Endpoint;
+
+const DEFAULT_BASE_URL: &str = "https://api.cloudflare.com/client/v4";
+const PER_PAGE: u32 = 100;
+/// Cloudflare's code for a missing or invalid token.
+const AUTHENTICATION_ERROR: i64 = 10000;
+
+/// A `DnsProvider` managing records through the Cloudflare API.
+#[derive(Clone)]
+pub struct CloudflareProvider {
+    token: String,
+    base_url: String,
+}
+
+impl std::fmt::Debug for CloudflareProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CloudflareProvider").field("base_url", &self.base_url).finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct DnsRecord {
+    id: String,
+    name: String,
+    content: String,
+    ttl: u32,
+}
+
+impl DnsRecord {
+    fn from_json(record: &Value) -> Result<Self, ProviderError> {
+        let field = |name: &str| {
+            record[name]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| ProviderError::InvalidResponse(format!("DNS record without {}", name)))
+        };
+        Ok(DnsRecord {
+            id: field("id")?,
+            name: field("name")?,
+            content: unquote(&field("content")?).to_string(),
+            ttl: record["ttl"].as_u64().and_then(|ttl| u32::try_from(ttl).ok()).unwrap_or(1),
+        })
+    }
+}
+
+impl CloudflareProvider {
+    pub fn new(token: &str) -> Self {
+        CloudflareProvider { token: token.to_string(), base_url: DEFAULT_BASE_URL.to_string() }
+    }
+
+    /// Sends requests to `url` instead of the public API.
+    pub fn base_url(mut self, url: &str) -> Self {
+        self.base_url = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    async fn call(&self, method: &str, path: &str, body: Option<Value>) -> Result<Value, ProviderError> {
+        let endpoint = Endpoint::parse(&self.base_url, "")
+            .map_err(|reason| ProviderError::Transport(format!("Invalid Cloudflare API URL {}: {}", self.base_url, reason)))?;
+        let path = format!("{}{}", endpoint.path, path);
+        let authorization = format!("Bearer {}", self.token);
+        let headers = [("Authorization", authorization.as_str()), ("Accept", "application/json")];
+        let body = body.map(|body| body.to_string());
+
+        debug!("Cloudflare {} {}", method, path);
+        let response = endpoint
+            .request(method, &path, &headers, body.as_deref().map(|body| ("application/json", body.as_bytes())))
+            .await
+            .map_err(|e| ProviderError::Transport(format!("Error calling {}: {}", self.base_url, e)))?;
+        let json: Option<Value> = serde_json::from_slice(&response.body).ok();
+
+        match response.status {
+            429 => {
+                return Err(ProviderError::RateLimited {
+                    retry_after: response
+                        .header("retry-after")
+                        .and_then(|value| parse_retry_after(value, crate::time::now())),
+                })
+            }
+            401 | 403 => {
+                let message = json.as_ref().and_then(first_error).map(|(_, message)| message);
+                return Err(ProviderError::Unauthorized(message.unwrap_or_else(|| format!("HTTP status {}", response.status))));
+            }
+            _ => {}
+        }
+        let json = json.ok_or_else(|| ProviderError::InvalidResponse(format!("HTTP status {} without a JSON body", response.status)))?;
+        if json["success"].as_bool() != Some(true) {
+            return Err(match first_error(&json) {
+                Some((AUTHENTICATION_ERROR, message)) => ProviderError::Unauthorized(message),
+                Some((code, message)) => ProviderError::Api { code, message },
+                None => ProviderError::Api { code: i64::from(response.status), message: format!("HTTP status {}", response.status) },
+            });
+        }
+        Ok(json)
+    }
+
+    async fn zone_id(&self, zone: &str) -> Result<String, ProviderError> {
+        let json = self.call("GET", &format!("/zones?name={}", encode(zone)), None).await?;
+        match json["result"].as_array().and_then(|zones| zones.first()) {
+            Some(found) => found["id"]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| ProviderError::InvalidResponse("zone without id".to_string())),
+            None => Err(ProviderError::ZoneNotFound(zone.to_string())),
+        }
+    }
+
+    /// Every TXT record in the zone, or only those at `name`, across all pages.
+    async fn records(&self, zone_id: &str, name: Option<&str>) -> Result<Vec<DnsRecord>, ProviderError> {
+        let filter = name.map(|name| format!("&name={}", encode(name))).unwrap_or_default();
+        let mut records = Vec::new();
+        let mut page = 1;
+        loop {
+            let path = format!("/zones/{}/dns_records?type=TXT{}&page={}&per_page={}", zone_id, filter, page, PER_PAGE);
+            let json = self.call("GET", &path, None).await?;
+            let result = json["result"]
+                .as_array()
+                .ok_or_else(|| ProviderError::InvalidResponse("DNS record list without result".to_string()))?;
+            for record in result {
+                records.push(DnsRecord::from_json(record)?);
+            }
+            if page >= json["result_info"]["total_pages"].as_u64().unwrap_or(1) {
+                return Ok(records);
+            }
+            page += 1;
+        }
+    }
+
+    /// Creates a record, or overwrites the one with `id`.
+    async fn write(&self, zone_id: &str, id: Option<&str>, name: &str, content: &str, ttl: u32) -> Result<(), ProviderError> {
+        let body = json!({ "type": "TXT", "name": name, "content": content, "ttl": ttl });
+        match id {
+            Some(id) => self.call("PUT", &format!("/zones/{}/dns_records/{}", zone_id, id), Some(body)).await?,
+            None => self.call("POST", &format!("/zones/{}/dns_records", zone_id), Some(body)).await?,
+        };
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DnsProvider for CloudflareProvider {
+    /// Keeps records already holding a wanted value, rewrites the others
+    /// with the values still missing, then creates or deletes records for
+    /// what is left. Publishing the same set twice makes no changes.
+    async fn upsert_txt(&self, zone: &str, name: &str, values: &[String], ttl: u32) -> Result<(), ProviderError> {
+        let zone_id = self.zone_id(zone).await?;
+        let mut wanted: Vec<&String> = Vec::new();
+        for value in values {
+            if !wanted.contains(&value) {
+                wanted.push(value);
+            }
+        }
+
+        let mut spare = Vec::new();
+        for record in self.records(&zone_id, Some(name)).await? {
+            match wanted.iter().position(|value| **value == record.content) {
+                Some(index) => {
+                    wanted.remove(index);
+                    if record.ttl != ttl {
+                        self.write(&zone_id, Some(&record.id), name, &record.content, ttl).await?;
+                    }
+                }
+                None => spare.push(record),
+            }
+        }
+        for value in wanted {
+            let reused = spare.pop();
+            self.write(&zone_id, reused.as_ref().map(|record| record.id.as_str()), name, value, ttl).await?;
+        }
+        for record in spare {
+            self.call("DELETE", &format!("/zones/{}/dns_records/{}", zone_id, record.id), None).await?;
+        }
+        Ok(())
+    }
+
+    async fn delete_txt(&self, zone: &str, name: &str) -> Result<(), ProviderError> {
+        let zone_id = self.zone_id(zone).await?;
+        for record in self.records(&zone_id, Some(name)).await? {
+            self.call("DELETE", &format!("/zones/{}/dns_records/{}", zone_id, record.id), None).await?;
+        }
+        Ok(())
+    }
+
+    async fn list_txt(&self, zone: &str) -> Result<Vec<TxtRecordSet>, ProviderError> {
+        let zone_id = self.zone_id(zone).await?;
+        let mut sets: BTreeMap<String, TxtRecordSet> = BTreeMap::new();
+        for record in self.records(&zone_id, None).await? {
+            let set = sets.entry(record.name.clone()).or_insert_with(|| TxtRecordSet {
+                name: record.name.clone(),
+                values: Vec::new(),
+                ttl: record.ttl,
+            });
+            set.ttl = set.ttl.min(record.ttl);
+            set.values.push(record.content);
+        }
+        Ok(sets.into_values().collect())
+    }
+}
+
+fn first_error(json: &Value) -> Option<(i64, String)> {
+    let error = json["errors"].as_array()?.first()?;
+    Some((error["code"].as_i64().unwrap_or(0), error["message"].as_str().unwrap_or_default().to_string()))
+}
+
+/// Cloudflare may return TXT content wrapped in quotes.
+fn unquote(content: &str) -> &str {
+    content
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .filter(|inner| !inner.contains('"'))
+        .unwrap_or(content)
+}
+
+fn encode(component: &str) -> String {
+    let mut encoded = String::new();
+    for byte in component.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
@@ -0,0 +1,251 @@
+//! Python bindings, behind the `python` feature, built into the
+//! `selfie_records` extension module with maturin (see `pyproject.toml`).
+//! Results are the serde JSON of the SDK's types as Python objects, and
+//! failures raise a subclass of `selfie_records.SelfieError` per error
+//! kind, e.g. `NxDomain` or `Timeout`, carrying the kind's `code`,
+//! `numeric_code` and `fields`. Lookups release the GIL.
+//!
+//! ```python
+//! from selfie_records import SelfieRecords, NxDomain
+//!
+//! sdk = SelfieRecords({"nameservers": ["9.9.9.9"]})
+//! records = sdk.get_records("example.com", keys=["bitcoin-payment"])
+//! try:
+//!     sdk.get_record("example.com", "nostr")
+//! except NxDomain:
+//!     ...
+//! ```
+
+use std::ffi::CString;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use pyo3::exceptions::{PyException, PyTimeoutError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{PyDict, PyType};
+
+use crate::builder::SdkBuilder;
+use crate::config::Config;
+use crate::error::{SelfieError, TimeoutBudget};
+use crate::resolver::TxtResolver;
+use crate::verify::VerificationResult;
+use crate::wire::WireInfo;
+use crate::SelfieRecordsSDK;
+
+/// The exception class of each error code, in `numeric_code` order from 1.
+const CLASSES: [(&str, &str); 27] = [
+    ("E_INVALID_NAME", "InvalidName"),
+    ("E_NO_RECORDS", "NoRecords"),
+    ("E_TIMEOUT", "Timeout"),
+    ("E_RATE_LIMITED", "RateLimited"),
+    ("E_DNSSEC_BOGUS", "DnssecBogus"),
+    ("E_OFFLINE", "Offline"),
+    ("E_INVALID_RECORD", "InvalidRecord"),
+    ("E_INVALID_SIGNATURE", "InvalidSignature"),
+    ("E_CROSS_CHECK_MISMATCH", "CrossCheckMismatch"),
+    ("E_CNAME_LOOP", "CnameLoop"),
+    ("E_CNAME_CHAIN_TOO_LONG", "CnameChainTooLong"),
+    ("E_RESOLVER", "ResolverError"),
+    ("E_BUDGET_EXCEEDED", "BudgetExceeded"),
+    ("E_MISSING_CHUNK", "MissingChunk"),
+    ("E_CHUNK_DIGEST_MISMATCH", "ChunkDigestMismatch"),
+    ("E_NXDOMAIN", "NxDomain"),
+    ("E_INVALID_NAMESERVER", "InvalidNameserver"),
+    ("E_DISAGREEMENT", "Disagreement"),
+    ("E_MULTIPLE_RECORDS", "MultipleRecords"),
+    ("E_NOT_A_BITCOIN_URI", "NotABitcoinUri"),
+    ("E_FINGERPRINT_MISMATCH", "FingerprintMismatch"),
+    ("E_INVALID_KEY", "InvalidKey"),
+    ("E_NAMESERVER_RESOLUTION_FAILED", "NameserverResolutionFailed"),
+    ("E_PROXY_UNREACHABLE", "ProxyUnreachable"),
+    ("E_RESPONSE_TOO_LARGE", "ResponseTooLarge"),
+    ("E_TLS_CERTIFICATE", "TlsCertificate"),
+    ("E_DNSSEC_INSECURE", "DnssecInsecure"),
+];
+
+struct Exceptions {
+    base: Py<PyType>,
+    kinds: Vec<(&'static str, Py<PyType>)>,
+}
+
+static EXCEPTIONS: GILOnceCell<Exceptions> = GILOnceCell::new();
+
+fn exceptions(py: Python<'_>) -> PyResult<&'static Exceptions> {
+    EXCEPTIONS.get_or_try_init(py, || {
+        let base = PyErr::new_type(
+            py,
+            c"selfie_records.SelfieError",
+            Some(c"An error of the SDK; its subclasses are the error kinds."),
+            Some(&py.get_type::<PyException>()),
+            None,
+        )?;
+        let mut kinds = Vec::new();
+        for (numeric_code, (code, class)) in (1u16..).zip(CLASSES) {
+            let name = CString::new(format!("selfie_records.{}", class)).expect("class names have no NUL");
+            let kind = PyErr::new_type(py, &name, None, Some(base.bind(py)), None)?;
+            kind.setattr(py, "code", code)?;
+            kind.setattr(py, "numeric_code", numeric_code)?;
+            kinds.push((code, kind));
+        }
+        Ok(Exceptions { base, kinds })
+    })
+}
+
+/// `error` as an instance of its kind's class, with `fields` as in its
+/// serde JSON.
+fn to_py_err(py: Python<'_>, error: &SelfieError) -> PyErr {
+    let exceptions = match exceptions(py) {
+        Ok(exceptions) => exceptions,
+        Err(e) => return e,
+    };
+    let class = exceptions.kinds.iter().find(|(code, _)| *code == error.code()).map_or(&exceptions.base, |(_, class)| class);
+    let err = PyErr::from_type(class.bind(py).clone(), error.to_string());
+    let json = serde_json::to_value(error).expect("errors serialize");
+    let fields = json.get("fields").cloned().unwrap_or_else(|| serde_json::json!({}));
+    if let Err(e) = from_json(py, &fields).and_then(|fields| err.value(py).setattr("fields", fields)) {
+        return e;
+    }
+    err
+}
+
+/// `value` as the Python object `json.loads` makes of it.
+fn from_json(py: Python<'_>, value: &serde_json::Value) -> PyResult<PyObject> {
+    Ok(py.import("json")?.call_method1("loads", (value.to_string(),))?.unbind())
+}
+
+/// A resolver in Python: a callable of a TXT name returning its records as
+/// a list of strings, or `None` when the name does not exist. A
+/// `TimeoutError` it raises is a timeout, any other exception a resolver
+/// error. Tests stand in for DNS with one.
+struct PyResolver(PyObject);
+
+impl PyResolver {
+    fn answer(&self, name: &str) -> Result<(Vec<String>, WireInfo), SelfieError> {
+        Python::with_gil(|py| match self.0.call1(py, (name,)) {
+            Ok(records) if records.is_none(py) => Ok((Vec::new(), WireInfo { response_code: Some(3), ..WireInfo::default() })),
+            Ok(records) => {
+                let records = records.extract(py).map_err(|e| SelfieError::Resolver(format!("Invalid answer for {}: {}", name, e)))?;
+                Ok((records, WireInfo::default()))
+            }
+            Err(e) if e.is_instance_of::<PyTimeoutError>(py) => {
+                Err(SelfieError::Timeout(TimeoutBudget::Global { timeout: Duration::ZERO, attempts: 1 }))
+            }
+            Err(e) => Err(SelfieError::Resolver(format!("Error resolving TXT record for {}: {}", name, e))),
+        })
+    }
+}
+
+#[async_trait]
+impl TxtResolver for PyResolver {
+    async fn txt_lookup(&self, name: &str) -> Result<Vec<String>, SelfieError> {
+        Ok(self.answer(name)?.0)
+    }
+
+    async fn txt_lookup_with_info(&self, name: &str) -> Result<(Vec<String>, WireInfo), SelfieError> {
+        self.answer(name)
+    }
+
+    fn describe(&self) -> String {
+        "python".to_string()
+    }
+}
+
+/// The SDK: `SelfieRecords(config=None, resolver=None)`, where `config` is
+/// a dict with the keys of a config file, e.g. `{"cache": {"ttl": 300}}`,
+/// and `resolver` a callable answering lookups instead of DNS.
+#[pyclass(name = "SelfieRecords", module = "selfie_records", frozen)]
+struct PySelfieRecords {
+    sdk: SelfieRecordsSDK,
+}
+
+#[pymethods]
+impl PySelfieRecords {
+    #[new]
+    #[pyo3(signature = (config=None, resolver=None))]
+    fn new(py: Python<'_>, config: Option<&Bound<'_, PyDict>>, resolver: Option<PyObject>) -> PyResult<Self> {
+        let config = match config {
+            None => Config::default(),
+            Some(config) => {
+                let text: String = py.import("json")?.call_method1("dumps", (config,))?.extract()?;
+                Config::from_json(&text).map_err(|e| PyValueError::new_err(e.to_string()))?
+            }
+        };
+        let mut builder = SdkBuilder::from_config(config.clone());
+        builder.lookup_options = config.lookup_options();
+        builder.default_keys = config.keys.clone();
+        if let Some(resolver) = resolver {
+            builder = builder.resolver(Arc::new(PyResolver(resolver)));
+        }
+        let sdk = builder.build().map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PySelfieRecords { sdk })
+    }
+
+    /// Looks up `keys`, or the default keys, of `name`, returning a dict
+    /// of each key's result. A key's failure is in its result; only an
+    /// invalid name or `dns_server` raises.
+    #[pyo3(signature = (name, keys=None, dns_server=None))]
+    fn get_records(&self, py: Python<'_>, name: &str, keys: Option<Vec<String>>, dns_server: Option<&str>) -> PyResult<PyObject> {
+        let response = py.allow_threads(|| {
+            let keys = keys.as_ref().map(|keys| keys.iter().map(String::as_str).collect());
+            self.sdk.get_records_response(name, keys, dns_server, &self.sdk.defaults)
+        });
+        let invalid = response.iter().find_map(|(_, result)| match &result.error {
+            Some(e @ (SelfieError::InvalidName { .. } | SelfieError::InvalidNameserver { .. })) => Some(e),
+            _ => None,
+        });
+        if let Some(e) = invalid {
+            return Err(to_py_err(py, e));
+        }
+        from_json(py, &serde_json::to_value(&response).expect("responses serialize"))
+    }
+
+    /// The value of `name`'s `key` record, raising the kind of error the
+    /// lookup failed with, `NoRecords` and `NxDomain` included.
+    fn get_record(&self, py: Python<'_>, name: &str, key: &str) -> PyResult<String> {
+        let response = py.allow_threads(|| self.sdk.get_records_response(name, Some(vec![key]), None, &self.sdk.defaults));
+        let result = response.get(key).expect("every requested key has a result");
+        match (&result.error, &result.value) {
+            (Some(e), _) => Err(to_py_err(py, e)),
+            (None, Some(value)) => Ok(value.clone()),
+            (None, None) => Err(to_py_err(py, &SelfieError::NoRecords)),
+        }
+    }
+
+    /// Checks that `name` publishes `expected` as one of its `key` records,
+    /// returning `{"status": "match" | "mismatch" | "not_found", "actual":
+    /// [...]}` with the records found on a mismatch.
+    fn verify_record(&self, py: Python<'_>, name: &str, key: &str, expected: &str) -> PyResult<PyObject> {
+        let (status, actual) = match py.allow_threads(|| self.sdk.verify_record(name, key, expected)) {
+            VerificationResult::Match => ("match", Vec::new()),
+            VerificationResult::MismatchFound { actual } => ("mismatch", actual),
+            VerificationResult::NotFound => ("not_found", Vec::new()),
+            VerificationResult::Error(e) => return Err(to_py_err(py, &e)),
+        };
+        from_json(py, &serde_json::json!({ "status": status, "actual": actual }))
+    }
+}
+
+/// The TXT name `key` of `name` is published at.
+#[pyfunction]
+#[pyo3(name = "get_txt_record_key")]
+fn txt_record_key(py: Python<'_>, name: &str, key: &str) -> PyResult<String> {
+    crate::name::get_txt_record_key(name, key).map_err(|e| to_py_err(py, &e))
+}
+
+/// The module's initializer, for embedding it in an interpreter with
+/// `pyo3::append_to_inittab!(selfie_records)`.
+#[pymodule]
+#[pyo3(name = "selfie_records")]
+pub fn selfie_records(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = module.py();
+    module.add_class::<PySelfieRecords>()?;
+    module.add_function(wrap_pyfunction!(txt_record_key, module)?)?;
+    let exceptions = exceptions(py)?;
+    module.add("SelfieError", exceptions.base.clone_ref(py))?;
+    for ((_, class), (_, exception)) in CLASSES.iter().zip(&exceptions.kinds) {
+        module.add(*class, exception.clone_ref(py))?;
+    }
+    Ok(())
+}
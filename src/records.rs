@@ -0,0 +1,598 @@
+//! Typed forms of the well-known selfie record values.
+
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use crate::error::SelfieError;
+use crate::{base58, bech32};
+
+const DEFAULT_NODE_PORT: u16 = 9735;
+/// Version byte in front of a base58check payment code, giving the `PM8T` prefix.
+const PAYMENT_CODE_PREFIX: u8 = 0x47;
+const PAYMENT_CODE_LENGTH: usize = 80;
+
+fn invalid(key: &str, reason: impl Into<String>) -> SelfieError {
+    SelfieError::InvalidRecord { key: key.to_string(), reason: reason.into() }
+}
+
+/// A BIP-21 `bitcoin:` URI. The address may be empty when only payment
+/// instructions such as `lightning` or `lno` are given, as BIP-353 allows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bip21Uri {
+    pub address: String,
+    /// Query parameters in order, values percent-decoded.
+    pub params: Vec<(String, String)>,
+}
+
+impl Bip21Uri {
+    /// The first parameter called `name`, ignoring case.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    pub fn amount(&self) -> Option<&str> {
+        self.param("amount")
+    }
+}
+
+impl FromStr for Bip21Uri {
+    type Err = SelfieError;
+
+    fn from_str(value: &str) -> Result<Self, SelfieError> {
+        let key = "bitcoin-payment";
+        let (scheme, rest) = value.trim().split_once(':').ok_or_else(|| invalid(key, "not a URI"))?;
+        if !scheme.eq_ignore_ascii_case("bitcoin") {
+            return Err(invalid(key, format!("expected a bitcoin: URI, got {}:", scheme)));
+        }
+        let (address, query) = rest.split_once('?').unwrap_or((rest, ""));
+        if !address.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(invalid(key, "address contains invalid characters"));
+        }
+        let params: Vec<(String, String)> = query
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .map(|param| {
+                let (name, value) = param.split_once('=').unwrap_or((param, ""));
+                (percent_decode(name), percent_decode(value))
+            })
+            .collect();
+        if address.is_empty() && params.is_empty() {
+            return Err(invalid(key, "URI has neither an address nor parameters"));
+        }
+        Ok(Bip21Uri { address: address.to_string(), params })
+    }
+}
+
+impl fmt::Display for Bip21Uri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bitcoin:{}", self.address)?;
+        for (i, (name, value)) in self.params.iter().enumerate() {
+            write!(f, "{}{}={}", if i == 0 { '?' } else { '&' }, percent_encode(name), percent_encode(value))?;
+        }
+        Ok(())
+    }
+}
+
+/// The payment instruction of a BIP-353 name, from
+/// `SelfieRecordsSDK::get_bip353`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bip353Instruction {
+    /// The record as published.
+    pub uri: String,
+    pub payment: Bip21Uri,
+    /// Whether the record's chain of trust validated; see
+    /// `KeyResult::authenticated`. BIP 353 expects wallets to require it.
+    pub authenticated: bool,
+}
+
+impl Bip353Instruction {
+    /// Picks the one `bitcoin:` URI out of a name's payment `records`,
+    /// ignoring records that are not, as BIP 353 asks.
+    pub(crate) fn from_records(records: Vec<String>, authenticated: bool) -> Result<Self, SelfieError> {
+        let is_uri = |record: &String| record.get(..8).is_some_and(|scheme| scheme.eq_ignore_ascii_case("bitcoin:"));
+        let count = records.iter().filter(|record| is_uri(record)).count();
+        let uri = match count {
+            0 => return Err(SelfieError::NotABitcoinUri { values: records }),
+            1 => records.into_iter().find(is_uri).expect("one record is a URI"),
+            count => return Err(SelfieError::MultipleRecords { count: count as u32 }),
+        };
+        Ok(Bip353Instruction { payment: uri.parse()?, uri, authenticated })
+    }
+}
+
+/// A `pgp` record: the key itself, or where to find it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgpRecord {
+    /// A 40-digit fingerprint, uppercased.
+    Fingerprint(String),
+    Url(String),
+    /// An inline key block, armored or base64.
+    Key(String),
+}
+
+impl FromStr for PgpRecord {
+    type Err = SelfieError;
+
+    fn from_str(value: &str) -> Result<Self, SelfieError> {
+        let value = value.trim();
+        if value.starts_with("https://") || value.starts_with("http://") {
+            return Ok(PgpRecord::Url(value.to_string()));
+        }
+        let compact: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+        let fingerprint = compact.strip_prefix("0x").unwrap_or(&compact);
+        if fingerprint.len() == 40 && fingerprint.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(PgpRecord::Fingerprint(fingerprint.to_ascii_uppercase()));
+        }
+        let is_base64 = compact.len() >= 64 && compact.chars().all(|c| c.is_ascii_alphanumeric() || "+/=".contains(c));
+        if value.contains("-----BEGIN PGP PUBLIC KEY BLOCK-----") || is_base64 {
+            return Ok(PgpRecord::Key(value.to_string()));
+        }
+        Err(invalid("pgp", "neither a fingerprint, a key URL nor a key block"))
+    }
+}
+
+impl fmt::Display for PgpRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgpRecord::Fingerprint(value) | PgpRecord::Url(value) | PgpRecord::Key(value) => f.write_str(value),
+        }
+    }
+}
+
+/// A nostr public key, given as an `npub` or as 64 hex digits, optionally
+/// followed by relay URLs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NostrKey {
+    pub public_key: [u8; 32],
+    pub relays: Vec<String>,
+}
+
+impl NostrKey {
+    pub fn npub(&self) -> String {
+        bech32::encode("npub", &self.public_key)
+    }
+
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.public_key)
+    }
+}
+
+impl FromStr for NostrKey {
+    type Err = SelfieError;
+
+    fn from_str(value: &str) -> Result<Self, SelfieError> {
+        let key = "nostr";
+        let mut tokens = value.split_whitespace();
+        let encoded = tokens.next().ok_or_else(|| invalid(key, "empty value"))?;
+        let bytes = if encoded.len() == 64 {
+            from_hex(encoded).ok_or_else(|| invalid(key, "invalid hex public key"))?
+        } else {
+            match bech32::decode(encoded).map_err(|reason| invalid(key, reason))? {
+                (hrp, bytes) if hrp == "npub" => bytes,
+                (hrp, _) => return Err(invalid(key, format!("expected an npub, got {}", hrp))),
+            }
+        };
+        let public_key = bytes.try_into().map_err(|_| invalid(key, "public key is not 32 bytes"))?;
+        let relays: Vec<String> = tokens.map(str::to_string).collect();
+        if let Some(relay) = relays.iter().find(|relay| !relay.starts_with("wss://") && !relay.starts_with("ws://")) {
+            return Err(invalid(key, format!("relay {} is not a websocket URL", relay)));
+        }
+        Ok(NostrKey { public_key, relays })
+    }
+}
+
+impl fmt::Display for NostrKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.npub())?;
+        for relay in &self.relays {
+            write!(f, " {}", relay)?;
+        }
+        Ok(())
+    }
+}
+
+/// A Lightning node as `pubkey@host:port`; the port defaults to 9735.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeUri {
+    /// Compressed secp256k1 public key.
+    pub pubkey: [u8; 33],
+    pub host: Host,
+    pub port: u16,
+}
+
+/// Where a Lightning node listens. Displays without brackets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Host {
+    /// A DNS hostname, lowercased, without a trailing dot.
+    Name(String),
+    Ipv4(Ipv4Addr),
+    Ipv6(Ipv6Addr),
+    /// A Tor v3 onion address, lowercased, `.onion` included.
+    Onion(String),
+}
+
+impl Host {
+    /// Reads a host as it appears in a node URI, IPv6 addresses bracketed.
+    fn parse(host: &str) -> Result<Host, String> {
+        if let Some(address) = host.strip_prefix('[').and_then(|host| host.strip_suffix(']')) {
+            return address.parse().map(Host::Ipv6).map_err(|_| format!("invalid IPv6 address {}", address));
+        }
+        if host.contains(':') {
+            return Err(format!("IPv6 address {} must be bracketed", host));
+        }
+        let host = host.strip_suffix('.').unwrap_or(host).to_ascii_lowercase();
+        if let Some(service) = host.strip_suffix(".onion") {
+            let is_v3 = service.len() == 56 && service.bytes().all(|b| b.is_ascii_lowercase() || (b'2'..=b'7').contains(&b));
+            return match is_v3 {
+                true => Ok(Host::Onion(host)),
+                false => Err(format!("{} is not a v3 onion address", host)),
+            };
+        }
+        if host.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+            return host.parse().map(Host::Ipv4).map_err(|_| format!("invalid IPv4 address {}", host));
+        }
+        let valid_label = |label: &str| {
+            (1..=63).contains(&label.len())
+                && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+        };
+        if host.len() > 253 || !host.split('.').all(valid_label) {
+            return Err(format!("invalid hostname {}", host));
+        }
+        Ok(Host::Name(host))
+    }
+}
+
+impl fmt::Display for Host {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Host::Name(host) | Host::Onion(host) => f.write_str(host),
+            Host::Ipv4(address) => address.fmt(f),
+            Host::Ipv6(address) => address.fmt(f),
+        }
+    }
+}
+
+impl FromStr for NodeUri {
+    type Err = SelfieError;
+
+    fn from_str(value: &str) -> Result<Self, SelfieError> {
+        let key = "node-uri";
+        let (pubkey, address) = value.trim().split_once('@').ok_or_else(|| invalid(key, "expected pubkey@host:port"))?;
+        let pubkey: [u8; 33] = from_hex(pubkey)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| invalid(key, "public key is not 33 bytes of hex"))?;
+        if pubkey[0] != 2 && pubkey[0] != 3 {
+            return Err(invalid(key, "public key is not compressed"));
+        }
+
+        let (host, port) = match address.find(']') {
+            Some(end) if address.starts_with('[') => {
+                let (host, rest) = address.split_at(end + 1);
+                let port = match rest {
+                    "" => None,
+                    rest => Some(rest.strip_prefix(':').ok_or_else(|| invalid(key, format!("unexpected {} after the host", rest)))?),
+                };
+                (host, port)
+            }
+            _ if address.starts_with('[') => return Err(invalid(key, "unterminated IPv6 address")),
+            // A bare IPv6 address is left for `Host::parse` to reject.
+            _ if address.matches(':').count() > 1 => (address, None),
+            _ => match address.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (address, None),
+            },
+        };
+        let port = match port {
+            Some(port) => port.parse().map_err(|_| invalid(key, format!("invalid port {}", port)))?,
+            None => DEFAULT_NODE_PORT,
+        };
+        if host.is_empty() {
+            return Err(invalid(key, "missing host"));
+        }
+        let host = Host::parse(host).map_err(|reason| invalid(key, reason))?;
+        Ok(NodeUri { pubkey, host, port })
+    }
+}
+
+impl fmt::Display for NodeUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Host::Ipv6(_) = self.host {
+            write!(f, "{}@[{}]:{}", to_hex(&self.pubkey), self.host, self.port)
+        } else {
+            write!(f, "{}@{}:{}", to_hex(&self.pubkey), self.host, self.port)
+        }
+    }
+}
+
+/// A BIP-47 reusable payment code (PayNym), the `PM8T...` base58check
+/// form of an 80-byte payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentCode {
+    /// 1 or 2.
+    pub version: u8,
+    /// Feature bits; bit 0 asks for Bitmessage notifications.
+    pub features: u8,
+    /// Compressed secp256k1 public key.
+    pub public_key: [u8; 33],
+    pub chain_code: [u8; 32],
+}
+
+impl PaymentCode {
+    pub fn public_key_hex(&self) -> String {
+        to_hex(&self.public_key)
+    }
+
+    /// The 80-byte payload, reserved bytes zeroed.
+    pub fn to_bytes(&self) -> [u8; PAYMENT_CODE_LENGTH] {
+        let mut payload = [0; PAYMENT_CODE_LENGTH];
+        payload[0] = self.version;
+        payload[1] = self.features;
+        payload[2..35].copy_from_slice(&self.public_key);
+        payload[35..67].copy_from_slice(&self.chain_code);
+        payload
+    }
+}
+
+impl FromStr for PaymentCode {
+    type Err = SelfieError;
+
+    fn from_str(value: &str) -> Result<Self, SelfieError> {
+        let key = "bip47";
+        let decoded = base58::decode_check(value.trim()).map_err(|reason| invalid(key, reason))?;
+        let payload = match decoded.split_first() {
+            Some((&PAYMENT_CODE_PREFIX, payload)) => payload,
+            _ => return Err(invalid(key, "not a payment code: version byte is not 0x47")),
+        };
+        if payload.len() != PAYMENT_CODE_LENGTH {
+            return Err(invalid(key, format!("payment code is {} bytes, expected 80", payload.len())));
+        }
+        if !matches!(payload[0], 1 | 2) {
+            return Err(invalid(key, format!("unsupported payment code version {}", payload[0])));
+        }
+        if payload[2] != 2 && payload[2] != 3 {
+            return Err(invalid(key, "public key is not compressed"));
+        }
+        Ok(PaymentCode {
+            version: payload[0],
+            features: payload[1],
+            public_key: payload[2..35].try_into().expect("33 bytes"),
+            chain_code: payload[35..67].try_into().expect("32 bytes"),
+        })
+    }
+}
+
+impl fmt::Display for PaymentCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut data = vec![PAYMENT_CODE_PREFIX];
+        data.extend_from_slice(&self.to_bytes());
+        f.write_str(&base58::encode_check(&data))
+    }
+}
+
+/// A decentralized identifier, `did:<method>:<method-specific-id>`, as
+/// the DID Core ABNF allows it. Parsing takes one DID; see
+/// `Did::parse_list` for records holding several.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Did {
+    /// Lowercase letters and digits, e.g. `key`, `web` or `plc`.
+    pub method: String,
+    /// Everything after the method's colon, percent-encoding kept.
+    pub id: String,
+}
+
+impl Did {
+    /// Parses a record holding one or more DIDs separated by whitespace.
+    /// Positions in errors are byte offsets into `value`.
+    pub fn parse_list(value: &str) -> Result<Vec<Did>, SelfieError> {
+        let mut dids = Vec::new();
+        let mut rest = value;
+        while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+            let end = rest[start..].find(char::is_whitespace).map_or(rest.len(), |end| start + end);
+            let offset = value.len() - rest.len() + start;
+            dids.push(Did::parse_at(&rest[start..end], offset)?);
+            rest = &rest[end..];
+        }
+        if dids.is_empty() {
+            return Err(invalid("did", "empty value"));
+        }
+        Ok(dids)
+    }
+
+    /// Parses `value`, which starts at byte `offset` of the record.
+    fn parse_at(value: &str, offset: usize) -> Result<Did, SelfieError> {
+        let key = "did";
+        let at = |position: usize, what: &str| invalid(key, format!("{} at position {}", what, offset + position));
+        let unexpected = |position: usize| {
+            let c = value[position..].chars().next().expect("position is inside the value");
+            at(position, &format!("invalid character {:?}", c))
+        };
+        let rest = value.strip_prefix("did:").ok_or_else(|| invalid(key, "not a did: URI"))?;
+        let method_end = rest.find(':').ok_or_else(|| at(value.len(), "missing method-specific id"))?;
+        let method = &rest[..method_end];
+        if method.is_empty() {
+            return Err(at(4, "empty method name"));
+        }
+        if let Some(i) = method.bytes().position(|b| !b.is_ascii_lowercase() && !b.is_ascii_digit()) {
+            return Err(unexpected(4 + i));
+        }
+
+        let id_start = 4 + method_end + 1;
+        let id = &value[id_start..];
+        let bytes = id.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'%' => {
+                    let escaped = bytes.get(i + 1..i + 3).filter(|hex| hex.iter().all(u8::is_ascii_hexdigit));
+                    if escaped.is_none() {
+                        return Err(at(id_start + i, "incomplete percent-encoding"));
+                    }
+                    i += 3;
+                }
+                b if b.is_ascii_alphanumeric() || b".-_:".contains(&b) => i += 1,
+                _ => return Err(unexpected(id_start + i)),
+            }
+        }
+        if id.is_empty() || id.ends_with(':') {
+            return Err(at(value.len(), "method-specific id must end with an id character"));
+        }
+        Ok(Did { method: method.to_string(), id: id.to_string() })
+    }
+}
+
+impl FromStr for Did {
+    type Err = SelfieError;
+
+    fn from_str(value: &str) -> Result<Self, SelfieError> {
+        let offset = value.len() - value.trim_start().len();
+        Did::parse_at(value.trim(), offset)
+    }
+}
+
+impl fmt::Display for Did {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "did:{}:{}", self.method, self.id)
+    }
+}
+
+/// Where a wallet fetches LNURL-pay parameters: an `lnurl` record's
+/// bech32 `lnurl1...` string (LUD-01) or a lightning address (LUD-16).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lnurl {
+    /// The decoded URL, `https://` or a plain-HTTP onion service.
+    Bech32 { url: String },
+    /// `user@domain`, lowercased.
+    Address { user: String, domain: String },
+}
+
+impl Lnurl {
+    /// The URL of the LNURL endpoint.
+    pub fn url(&self) -> String {
+        match self {
+            Lnurl::Bech32 { url } => url.clone(),
+            Lnurl::Address { user, domain } => format!("https://{}/.well-known/lnurlp/{}", domain, user),
+        }
+    }
+}
+
+impl FromStr for Lnurl {
+    type Err = SelfieError;
+
+    fn from_str(value: &str) -> Result<Self, SelfieError> {
+        let key = "lnurl";
+        let value = value.trim();
+        let value = value.strip_prefix("lightning:").or_else(|| value.strip_prefix("LIGHTNING:")).unwrap_or(value);
+        if let Some((user, domain)) = value.split_once('@') {
+            let user = user.to_ascii_lowercase();
+            if user.is_empty() || !user.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_.+".contains(&b)) {
+                return Err(invalid(key, format!("invalid lightning address user {:?}", user)));
+            }
+            let domain = match Host::parse(domain).map_err(|reason| invalid(key, reason))? {
+                Host::Name(domain) | Host::Onion(domain) => domain,
+                _ => return Err(invalid(key, "lightning address domain is an IP address")),
+            };
+            return Ok(Lnurl::Address { user, domain });
+        }
+
+        let (hrp, payload) = bech32::decode(value).map_err(|reason| invalid(key, reason))?;
+        if hrp != "lnurl" {
+            return Err(invalid(key, format!("expected an lnurl prefix, found {}", hrp)));
+        }
+        let url = String::from_utf8(payload).map_err(|_| invalid(key, "decoded URL is not UTF-8"))?;
+        let onion = url
+            .strip_prefix("http://")
+            .and_then(|rest| rest.split(['/', '?', ':']).next())
+            .is_some_and(|host| host.ends_with(".onion"));
+        if !url.starts_with("https://") && !onion {
+            return Err(invalid(key, format!("decoded URL {} is not https", url)));
+        }
+        Ok(Lnurl::Bech32 { url })
+    }
+}
+
+/// The record form: a lowercase `lnurl1...` string or `user@domain`.
+impl fmt::Display for Lnurl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lnurl::Bech32 { url } => f.write_str(&bech32::encode("lnurl", url.as_bytes())),
+            Lnurl::Address { user, domain } => write!(f, "{}@{}", user, domain),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use std::str::FromStr;
+
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Bip21Uri, Did, Lnurl, NodeUri, NostrKey, PaymentCode, PgpRecord};
+
+    // Each type travels as its record text.
+    macro_rules! as_record_text {
+        ($($ty:ty),*) => {$(
+            impl Serialize for $ty {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.collect_str(self)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $ty {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let text = String::deserialize(deserializer)?;
+                    <$ty>::from_str(&text).map_err(D::Error::custom)
+                }
+            }
+        )*};
+    }
+
+    as_record_text!(Bip21Uri, PgpRecord, NostrKey, NodeUri, PaymentCode, Did, Lnurl);
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%').then(|| value.get(i + 1..i + 3)).flatten();
+        match escaped.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+pub(crate) fn percent_encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b':' | b'/' | b'@' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+pub(crate) fn from_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) || !value.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..value.len()).step_by(2).map(|i| u8::from_str_radix(&value[i..i + 2], 16).ok()).collect()
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
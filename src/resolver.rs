@@ -0,0 +1,191 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+use async_trait::async_trait;
+#[cfg(not(target_arch = "wasm32"))]
+use log::warn;
+#[cfg(not(target_arch = "wasm32"))]
+use trust_dns_resolver::config::{Protocol, ResolverConfig, ResolverOpts};
+#[cfg(not(target_arch = "wasm32"))]
+use trust_dns_resolver::error::ResolveErrorKind;
+#[cfg(not(target_arch = "wasm32"))]
+use trust_dns_resolver::{system_conf, TokioAsyncResolver};
+
+use crate::builder::TransportPreference;
+#[cfg(target_arch = "wasm32")]
+use crate::doh::DohResolver;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::encoding;
+use crate::encoding::RawRecord;
+use crate::error::SelfieError;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::error::TimeoutBudget;
+use crate::wire::WireInfo;
+
+/// Backend that answers TXT queries for fully-qualified names.
+#[async_trait]
+pub trait TxtResolver: Send + Sync {
+    async fn txt_lookup(&self, name: &str) -> Result<Vec<String>, SelfieError>;
+
+    /// Like `txt_lookup`, also describing the exchange the answer came from.
+    /// Backends that don't see the raw message can keep the default.
+    async fn txt_lookup_with_info(&self, name: &str) -> Result<(Vec<String>, WireInfo), SelfieError> {
+        Ok((self.txt_lookup(name).await?, WireInfo::default()))
+    }
+
+    /// Like `txt_lookup_with_info`, with the records' rdata as received,
+    /// before any conversion to text. The SDK looks records up through
+    /// this. Backends that only have text can keep the default, which
+    /// makes each value a single character-string.
+    async fn txt_lookup_raw(&self, name: &str) -> Result<(Vec<RawRecord>, WireInfo), SelfieError> {
+        let (values, info) = self.txt_lookup_with_info(name).await?;
+        Ok((values.iter().map(|value| RawRecord::new([value])).collect(), info))
+    }
+
+    /// Names the backend in audit logs, e.g. the server address.
+    fn describe(&self) -> String {
+        "custom".to_string()
+    }
+
+    /// Opens the connections later lookups will use and, given a `probe`
+    /// name, sends a TXT query for it, for `SelfieRecordsSDK::warm_up`.
+    /// Backends without connections to keep can keep the default, which
+    /// does nothing and returns `None`.
+    async fn warm_up(&self, probe: Option<&str>) -> Option<Readiness> {
+        let _ = probe;
+        None
+    }
+
+    /// Whether `.onion` names can be resolved through this backend, which
+    /// public-only mode otherwise rejects. Only a Tor transport can.
+    fn resolves_onion(&self) -> bool {
+        false
+    }
+}
+
+/// How warming up one endpoint went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Readiness {
+    pub endpoint: String,
+    /// Whether a connection was opened; `false` when one was already open.
+    pub connected: bool,
+    pub elapsed: Duration,
+    pub error: Option<SelfieError>,
+}
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait]
+impl TxtResolver for TokioAsyncResolver {
+    async fn txt_lookup(&self, name: &str) -> Result<Vec<String>, SelfieError> {
+        Ok(self.txt_lookup_with_info(name).await?.0)
+    }
+
+    fn describe(&self) -> String {
+        "system".to_string()
+    }
+
+    async fn txt_lookup_with_info(&self, name: &str) -> Result<(Vec<String>, WireInfo), SelfieError> {
+        let (records, info) = self.txt_lookup_raw(name).await?;
+        Ok((encoding::lossy(&records), info))
+    }
+
+    async fn txt_lookup_raw(&self, name: &str) -> Result<(Vec<RawRecord>, WireInfo), SelfieError> {
+        let started = Instant::now();
+        match TokioAsyncResolver::txt_lookup(self, name).await {
+            Ok(lookup) => {
+                let ttl = lookup.valid_until().saturating_duration_since(Instant::now()).as_secs();
+                let info = WireInfo { ttl: Some(ttl.try_into().unwrap_or(u32::MAX)), ..WireInfo::default() };
+                Ok((lookup.iter().map(|txt| RawRecord::new(txt.txt_data())).collect(), info))
+            }
+            Err(e) => match e.kind() {
+                // The name has no TXT records, or does not exist at all.
+                ResolveErrorKind::NoRecordsFound { response_code, negative_ttl, .. } => {
+                    let info = WireInfo { ttl: *negative_ttl, response_code: Some((*response_code).into()), ..WireInfo::default() };
+                    Ok((Vec::new(), info))
+                }
+                // The resolver's own budget is not exposed; the time it
+                // took to give up stands in for it.
+                ResolveErrorKind::Timeout => Err(SelfieError::Timeout(TimeoutBudget::Global { timeout: started.elapsed(), attempts: 1 })),
+                _ => Err(SelfieError::Resolver(format!(
+                    "Error resolving TXT record for {}: {:?}",
+                    name, e
+                ))),
+            },
+        }
+    }
+}
+
+/// The operating system's resolver configuration, or Google Public DNS's
+/// when it cannot be read or names no nameservers.
+#[cfg(not(target_arch = "wasm32"))]
+fn system_config() -> (ResolverConfig, ResolverOpts) {
+    match system_conf::read_system_conf() {
+        Ok((config, opts)) if !config.name_servers().is_empty() => (config, opts),
+        Ok(_) => {
+            warn!("The system resolver configuration names no nameservers, using Google Public DNS");
+            (ResolverConfig::default(), ResolverOpts::default())
+        }
+        Err(e) => {
+            warn!("Cannot read the system resolver configuration, using Google Public DNS: {}", e);
+            (ResolverConfig::default(), ResolverOpts::default())
+        }
+    }
+}
+
+/// A resolver using the operating system's nameservers, or Google's public
+/// ones when its configuration cannot be read or names none, giving each
+/// `timeout` to answer. Must be called within a tokio runtime.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn system_resolver(transport: TransportPreference, timeout: Duration) -> TokioAsyncResolver {
+    let (config, mut opts) = system_config();
+    // The SDK caches answers by its own rules, and `bypass_cache` must
+    // reach the servers.
+    opts.cache_size = 0;
+    // Nor does it retry for the SDK, which makes its own attempts.
+    opts.timeout = timeout;
+    opts.attempts = 1;
+    // Both configurations name each server over UDP and over TCP, which
+    // the resolver falls back to for truncated answers.
+    let config = match transport {
+        TransportPreference::UdpThenTcp => config,
+        TransportPreference::TcpOnly => {
+            let tcp = config.name_servers().iter().filter(|server| server.protocol == Protocol::Tcp).cloned().collect::<Vec<_>>();
+            ResolverConfig::from_parts(config.domain().cloned(), config.search().to_vec(), tcp)
+        }
+    };
+    TokioAsyncResolver::tokio(config, opts).expect("the tokio connection provider cannot fail")
+}
+
+/// The addresses of the nameservers `system_resolver` queries, in order,
+/// never empty.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn system_nameservers() -> Vec<SocketAddr> {
+    let mut servers = Vec::new();
+    for server in system_config().0.name_servers() {
+        if !servers.contains(&server.socket_addr) {
+            servers.push(server.socket_addr);
+        }
+    }
+    servers
+}
+
+/// On wasm32, which has no system configuration to read nor sockets to
+/// use it with, Cloudflare's DoH resolver, over whatever transport.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn system_resolver(_: TransportPreference, _: Duration) -> DohResolver {
+    DohResolver::cloudflare()
+}
+
+/// On wasm32, Cloudflare's, the servers behind `system_resolver`.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn system_nameservers() -> Vec<SocketAddr> {
+    vec![([1, 1, 1, 1], 53).into()]
+}
@@ -0,0 +1,532 @@
+//! Typed per-key results and their conversion to the string map
+//! `get_records` returns.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+use crate::cross_check::CrossCheck;
+use crate::encoding::{self, RawRecord, ValueEncodingIssue};
+use crate::error::SelfieError;
+use crate::parser::{ParseError, ParsedValue};
+use crate::routing::Route;
+use crate::wire::{ServerInfo, WireInfo};
+
+/// Where a record value came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// A locally configured override; no query was made.
+    Override,
+    /// An earlier answer kept in the SDK's cache.
+    Cache,
+    Dns,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Override => f.write_str("override"),
+            Source::Cache => f.write_str("cache"),
+            Source::Dns => f.write_str("dns"),
+        }
+    }
+}
+
+/// What a `KeyResult` says about its record, for matching on instead of
+/// checking `value` and `error` by hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordEntry<'a> {
+    Found { value: &'a str },
+    /// The name has no such record, or does not exist at all.
+    NotFound,
+    Error(&'a SelfieError),
+}
+
+/// How a key's record was queried, for diagnostics such as "resolved via
+/// 1.1.1.1:53 in 43 ms".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryMeta {
+    /// The name queried, e.g. `alice.user._pgp.example.com`.
+    pub fqdn: String,
+    /// The server that answered when known, otherwise the first one the
+    /// resolver is configured with; `None` when a resolver library chose.
+    pub nameserver: Option<SocketAddr>,
+    /// Time spent looking the name up, every attempt and backoff included.
+    pub rtt: Duration,
+    pub attempts: u32,
+}
+
+/// Outcome of looking up one record key.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct KeyResult {
+    pub value: Option<String>,
+    pub error: Option<SelfieError>,
+    pub source: Option<Source>,
+    /// Served from an expired cache entry.
+    pub stale: bool,
+    /// Served while offline mode was on.
+    pub offline: bool,
+    pub attempts: Option<u32>,
+    /// Total time spent waiting between attempts.
+    pub backoff: Option<Duration>,
+    pub wire: Option<WireInfo>,
+    #[cfg(feature = "signatures")]
+    pub signature: Option<crate::signature::SignatureStatus>,
+    /// When the answer was received from DNS. Cached answers keep the
+    /// time of the original lookup.
+    pub resolved_at: Option<SystemTime>,
+    /// TTL of the answer; the smallest one when the records disagree.
+    pub ttl: Option<Duration>,
+    /// Set for answers fetched from DNS while cross-checking is on.
+    pub cross_check: Option<CrossCheck>,
+    /// Which name was used: the configured record version when its name
+    /// had records, `None` for the unversioned name.
+    pub record_version: Option<u32>,
+    /// The template the name that was used follows, when names are built
+    /// from templates; see `SdkBuilder::name_template_fallbacks`.
+    pub name_template: Option<String>,
+    /// Set when the value should not be taken at face value.
+    pub encoding_issue: Option<ValueEncodingIssue>,
+    /// The bytes `value` was read from, records joined by spaces.
+    pub raw_value: Option<Vec<u8>>,
+    /// The records behind `value`, with their character-strings.
+    pub raw_records: Vec<RawRecord>,
+    /// The routing rule that picked the resolver; `None` for overrides.
+    pub route: Option<Route>,
+    /// The resolver the key was looked up through, as named in audit logs.
+    pub resolver: Option<String>,
+    /// Set when the server passed to the call was unreachable and the
+    /// SDK's resolver answered instead; see `SdkBuilder::fallback_to_default`.
+    pub used_fallback_resolver: bool,
+    /// The nameserver that answered, where the SDK picked it: a server
+    /// passed to the call, a routing group or a `DirectResolver`. `None`
+    /// when a resolver library chose the server.
+    pub answered_by: Option<ServerInfo>,
+    /// Set when `value` was reassembled from chunk records; see
+    /// `SdkBuilder::chunked_key`.
+    pub chunked: bool,
+    /// Set when the answer's chain of trust validated, which only
+    /// happens with `SdkBuilder::require_dnssec` or `report_dnssec`.
+    pub authenticated: bool,
+    /// Set for every key whose record name was looked up, including
+    /// failed lookups; `None` for overrides and invalid names or keys.
+    pub query: Option<QueryMeta>,
+    /// `value` parsed by its key's `RecordParser`; see
+    /// `SdkBuilder::register_parser`.
+    pub parsed: Option<ParsedValue>,
+    /// Set instead of `parsed` when `value` does not parse. `value` and
+    /// `error` are unaffected.
+    pub parse_error: Option<ParseError>,
+}
+
+impl KeyResult {
+    pub(crate) fn error(error: SelfieError) -> Self {
+        KeyResult { error: Some(error), ..KeyResult::default() }
+    }
+
+    /// A value wins over an error, which some results carry alongside
+    /// theirs, e.g. a signature that does not verify.
+    pub fn entry(&self) -> RecordEntry<'_> {
+        match (&self.value, &self.error) {
+            (Some(value), _) => RecordEntry::Found { value },
+            (None, Some(SelfieError::NoRecords | SelfieError::NxDomain) | None) => RecordEntry::NotFound,
+            (None, Some(e)) => RecordEntry::Error(e),
+        }
+    }
+
+    /// The text of each record behind `value`, with the character-strings
+    /// of a record concatenated; `value` joins the records with spaces.
+    /// BIP 353 treats a payment record that has more than one as invalid.
+    pub fn values(&self) -> Vec<String> {
+        encoding::lossy(&self.raw_records)
+    }
+
+    /// The value exactly as published. `value` is its text, with invalid
+    /// UTF-8 replaced.
+    pub fn raw_bytes(&self) -> Option<&[u8]> {
+        self.raw_value.as_deref()
+    }
+
+    /// When the value should be re-resolved. `None` for errors, missing
+    /// records and values without a TTL, such as overrides.
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.value.as_ref()?;
+        Some(self.resolved_at? + self.ttl?)
+    }
+
+    /// Whether the value is still within its TTL at `now`. Values without
+    /// an expiry are never fresh.
+    pub fn is_fresh(&self, now: SystemTime) -> bool {
+        self.expires_at().is_some_and(|expires_at| now < expires_at)
+    }
+
+    /// Time left until `expires_at`, zero once it has passed.
+    pub fn remaining_ttl(&self, now: SystemTime) -> Option<Duration> {
+        Some(self.expires_at()?.duration_since(now).unwrap_or(Duration::ZERO))
+    }
+
+    fn to_map(&self) -> HashMap<String, Option<String>> {
+        self.map_with(self.value.clone())
+    }
+
+    /// The string map of this result, with `value` moved in so callers
+    /// that own the result need not copy it.
+    fn map_with(&self, value: Option<String>) -> HashMap<String, Option<String>> {
+        // Room for every field, so the map never grows.
+        let mut map = HashMap::with_capacity(25);
+        map.insert("value".to_string(), value);
+        map.insert("error".to_string(), self.error.as_ref().map(ToString::to_string));
+        if let Some(error) = &self.error {
+            map.insert("error_code".to_string(), Some(error.code().to_string()));
+        }
+        let mut insert = |key: &str, value: String| map.insert(key.to_string(), Some(value));
+        #[cfg(feature = "signatures")]
+        if let Some(status) = self.signature {
+            insert("signature", status.to_string());
+        }
+        if let Some(source) = self.source {
+            insert("source", source.to_string());
+        }
+        if self.stale {
+            insert("stale", "true".to_string());
+        }
+        if self.offline {
+            insert("offline", "true".to_string());
+        }
+        if let Some(attempts) = self.attempts {
+            insert("attempts", attempts.to_string());
+        }
+        if let Some(backoff) = self.backoff {
+            insert("backoff_ms", backoff.as_millis().to_string());
+        }
+        if let Some(wire) = self.wire {
+            insert("wire", wire.to_string());
+        }
+        if let Some(ttl) = self.ttl {
+            insert("ttl", ttl.as_secs().to_string());
+        }
+        if self.raw_records.len() > 1 {
+            insert("record_count", self.raw_records.len().to_string());
+        }
+        if let Some(cross_check) = &self.cross_check {
+            insert("cross_check", cross_check.to_string());
+        }
+        if let Some(version) = self.record_version {
+            insert("record_version", version.to_string());
+        }
+        if let Some(template) = &self.name_template {
+            insert("name_template", template.clone());
+        }
+        if let Some(issue) = self.encoding_issue {
+            insert("encoding_issue", issue.to_string());
+        }
+        if let Some(route) = &self.route {
+            insert("route", route.to_string());
+        }
+        if let Some(resolver) = &self.resolver {
+            insert("resolver", resolver.clone());
+        }
+        if self.used_fallback_resolver {
+            insert("used_fallback_resolver", "true".to_string());
+        }
+        if let Some(server) = self.answered_by {
+            insert("answered_by", server.to_string());
+        }
+        if self.chunked {
+            insert("chunked", "true".to_string());
+        }
+        if self.authenticated {
+            insert("authenticated", "true".to_string());
+        }
+        if let Some(query) = &self.query {
+            insert("fqdn", query.fqdn.clone());
+            if let Some(nameserver) = query.nameserver {
+                insert("nameserver", nameserver.to_string());
+            }
+            insert("rtt_ms", query.rtt.as_millis().to_string());
+        }
+        if let Some(e) = &self.parse_error {
+            insert("parse_error", e.to_string());
+        }
+        map
+    }
+}
+
+/// Serialized with a fixed set of keys, absent values as `null`. Bytes are
+/// in standard base64, so `raw_value` and `raw_records` keep the rdata
+/// exactly. Times and TTLs are in seconds.
+///
+/// The keys are the field names, except `backoff_ms` for `backoff` and
+/// `rtt_ms` in `query`, plus `values` for `values()`. `error` is `{ "code",
+/// "numeric_code", "message", "fields" }`, see `SelfieError`, and `parsed` is `{ "kind", "value" }`, the
+/// record text as `value` for the built-in kinds; `source`, `signature`,
+/// `encoding_issue`, `parse_error` and the `answered_by` transport are their
+/// `Display` text. Keys are only ever
+/// added, so readers should ignore ones they do not know.
+#[cfg(feature = "serde")]
+impl serde::Serialize for KeyResult {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use data_encoding::BASE64;
+
+        let seconds = |time: SystemTime| time.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let mut json = serde_json::json!({
+            "value": self.value,
+            "raw_value": self.raw_value.as_deref().map(|bytes| BASE64.encode(bytes)),
+            "raw_records": self.raw_records,
+            "values": self.values(),
+            "error": self.error,
+            "source": self.source.map(|source| source.to_string()),
+            "stale": self.stale,
+            "offline": self.offline,
+            "attempts": self.attempts,
+            "backoff_ms": self.backoff.map(|backoff| backoff.as_millis() as u64),
+            "wire": self.wire.map(|wire| wire.to_string()),
+            "resolved_at": self.resolved_at.map(seconds),
+            "ttl": self.ttl.map(|ttl| ttl.as_secs()),
+            "cross_check": self.cross_check.as_ref().map(ToString::to_string),
+            "record_version": self.record_version,
+            "encoding_issue": self.encoding_issue.map(|issue| issue.to_string()),
+            "route": self.route.as_ref().map(ToString::to_string),
+            "resolver": self.resolver,
+            "used_fallback_resolver": self.used_fallback_resolver,
+            "answered_by": self.answered_by.map(|server| serde_json::json!({
+                "address": server.address.to_string(),
+                "transport": server.transport.to_string(),
+            })),
+            "chunked": self.chunked,
+            "authenticated": self.authenticated,
+            "query": self.query.as_ref().map(|query| serde_json::json!({
+                "fqdn": query.fqdn,
+                "nameserver": query.nameserver.map(|nameserver| nameserver.to_string()),
+                "rtt_ms": query.rtt.as_millis() as u64,
+                "attempts": query.attempts,
+            })),
+            "parsed": self.parsed,
+            "parse_error": self.parse_error.as_ref().map(ToString::to_string),
+        });
+        #[cfg(feature = "signatures")]
+        {
+            json["signature"] = self.signature.map(|status| status.to_string()).into();
+        }
+        // Only there with templates, so that other documents keep their shape.
+        if let Some(template) = &self.name_template {
+            json["name_template"] = template.as_str().into();
+        }
+        json.serialize(serializer)
+    }
+}
+
+/// Reads the serialized form back. `wire`, `cross_check` and `route`
+/// are descriptions for people and are left `None`, `resolved_at` keeps
+/// whole seconds only and `query.rtt` whole milliseconds; see
+/// `SelfieError` for how errors come back.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for KeyResult {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use data_encoding::BASE64;
+        use serde::de::Error as _;
+
+        #[derive(serde::Deserialize)]
+        struct Json {
+            value: Option<String>,
+            raw_value: Option<String>,
+            #[serde(default)]
+            raw_records: Vec<RawRecord>,
+            error: Option<SelfieError>,
+            source: Option<String>,
+            #[serde(default)]
+            stale: bool,
+            #[serde(default)]
+            offline: bool,
+            attempts: Option<u32>,
+            backoff_ms: Option<u64>,
+            resolved_at: Option<u64>,
+            ttl: Option<u64>,
+            record_version: Option<u32>,
+            name_template: Option<String>,
+            encoding_issue: Option<String>,
+            resolver: Option<String>,
+            #[serde(default)]
+            used_fallback_resolver: bool,
+            answered_by: Option<ServerJson>,
+            #[serde(default)]
+            chunked: bool,
+            #[serde(default)]
+            authenticated: bool,
+            query: Option<QueryJson>,
+            parsed: Option<ParsedValue>,
+            parse_error: Option<String>,
+            #[cfg(feature = "signatures")]
+            signature: Option<String>,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct QueryJson {
+            fqdn: String,
+            nameserver: Option<SocketAddr>,
+            rtt_ms: u64,
+            attempts: u32,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct ServerJson {
+            address: SocketAddr,
+            transport: String,
+        }
+
+        let json = Json::deserialize(deserializer)?;
+        let unknown = |what: &str, value: &str| D::Error::custom(format!("unknown {} {:?}", what, value));
+        let source = match json.source.as_deref() {
+            None => None,
+            Some("override") => Some(Source::Override),
+            Some("cache") => Some(Source::Cache),
+            Some("dns") => Some(Source::Dns),
+            Some(other) => return Err(unknown("source", other)),
+        };
+        let encoding_issue = match json.encoding_issue.as_deref() {
+            None => None,
+            Some("not_utf8") => Some(ValueEncodingIssue::NotUtf8),
+            Some("control_characters") => Some(ValueEncodingIssue::ControlCharacters),
+            Some(other) => return Err(unknown("encoding issue", other)),
+        };
+        let answered_by = match json.answered_by {
+            None => None,
+            Some(server) => {
+                let transport = match server.transport.as_str() {
+                    "udp" => crate::wire::Transport::Udp,
+                    "tcp" => crate::wire::Transport::Tcp,
+                    "doh" => crate::wire::Transport::Doh,
+                    "tls" => crate::wire::Transport::Tls,
+                    "resolver" => crate::wire::Transport::Resolver,
+                    other => return Err(unknown("transport", other)),
+                };
+                Some(ServerInfo { address: server.address, transport })
+            }
+        };
+        #[cfg(feature = "signatures")]
+        let signature = {
+            use crate::signature::SignatureStatus;
+            match json.signature.as_deref() {
+                None => None,
+                Some("verified") => Some(SignatureStatus::Verified),
+                Some("invalid") => Some(SignatureStatus::Invalid),
+                Some("no-key") => Some(SignatureStatus::NoKey),
+                Some(other) => return Err(unknown("signature status", other)),
+            }
+        };
+        Ok(KeyResult {
+            value: json.value,
+            error: json.error,
+            source,
+            stale: json.stale,
+            offline: json.offline,
+            attempts: json.attempts,
+            backoff: json.backoff_ms.map(Duration::from_millis),
+            wire: None,
+            #[cfg(feature = "signatures")]
+            signature,
+            resolved_at: json.resolved_at.map(|seconds| std::time::UNIX_EPOCH + Duration::from_secs(seconds)),
+            ttl: json.ttl.map(Duration::from_secs),
+            cross_check: None,
+            record_version: json.record_version,
+            name_template: json.name_template,
+            encoding_issue,
+            raw_value: json.raw_value.map(|value| BASE64.decode(value.as_bytes())).transpose().map_err(D::Error::custom)?,
+            raw_records: json.raw_records,
+            route: None,
+            resolver: json.resolver,
+            used_fallback_resolver: json.used_fallback_resolver,
+            answered_by,
+            chunked: json.chunked,
+            authenticated: json.authenticated,
+            query: json.query.map(|query| QueryMeta {
+                fqdn: query.fqdn,
+                nameserver: query.nameserver,
+                rtt: Duration::from_millis(query.rtt_ms),
+                attempts: query.attempts,
+            }),
+            parsed: json.parsed,
+            parse_error: json.parse_error.map(ParseError::new),
+        })
+    }
+}
+
+/// Serialized as a map from record key to result, in key order.
+/// `queries_issued` is not part of it.
+#[cfg(feature = "serde")]
+impl serde::Serialize for RecordsResponse {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|(key, _)| *key);
+        serializer.collect_map(entries)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for RecordsResponse {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = HashMap::<String, KeyResult>::deserialize(deserializer)?;
+        Ok(RecordsResponse { entries, queries_issued: 0 })
+    }
+}
+
+/// The results of one lookup, keyed by record key.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RecordsResponse {
+    entries: HashMap<String, KeyResult>,
+    /// DNS queries the call sent; see `LookupOptions::max_queries`.
+    pub queries_issued: u32,
+}
+
+impl RecordsResponse {
+    pub fn get(&self, key: &str) -> Option<&KeyResult> {
+        self.entries.get(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &KeyResult)> {
+        self.entries.iter().map(|(key, result)| (key.as_str(), result))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(crate) fn with_capacity(keys: usize) -> Self {
+        RecordsResponse { entries: HashMap::with_capacity(keys), queries_issued: 0 }
+    }
+
+    pub fn insert(&mut self, key: &str, result: KeyResult) {
+        self.entries.insert(key.to_string(), result);
+    }
+
+    pub(crate) fn take(&mut self, key: &str) -> Option<KeyResult> {
+        self.entries.remove(key)
+    }
+
+    /// The earliest expiry of any key, for scheduling a single refresh.
+    pub fn min_expiry(&self) -> Option<SystemTime> {
+        self.entries.values().filter_map(KeyResult::expires_at).min()
+    }
+
+    /// The results as maps of `value`, `error` and the other fields to
+    /// strings, the shape `get_records` returned before it was typed.
+    pub fn to_map(&self) -> HashMap<String, HashMap<String, Option<String>>> {
+        self.entries.iter().map(|(key, result)| (key.clone(), result.to_map())).collect()
+    }
+
+    /// Like `to_map`, reusing the keys and values instead of copying them.
+    pub fn into_map(self) -> HashMap<String, HashMap<String, Option<String>>> {
+        self.entries
+            .into_iter()
+            .map(|(key, mut result)| {
+                let value = result.value.take();
+                (key, result.map_with(value))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,356 @@
+//! Which resolver handles a key's queries: rules for particular keys and
+//! domain suffixes in front of the default resolver.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::builder::TransportPreference;
+use crate::chunks;
+use crate::encoding::{self, RawRecord};
+use crate::error::SelfieError;
+use crate::proxy::{Socks5Config, Socks5Transport};
+use crate::resolver::TxtResolver;
+use crate::transport::{TcpTransport, TlsNameserver};
+use crate::wire::{DirectResolver, WireInfo};
+
+/// Nameservers that share a view of DNS, asked in order: the next one is
+/// only asked when a server fails with a retryable error.
+///
+/// Built from a comma-separated list of addresses, e.g.
+/// `"10.0.0.53, 10.0.0.54:5353"`, with port 53 when none is given, or from
+/// any `TxtResolver`. Addresses are checked by `SdkBuilder::build`.
+#[derive(Clone)]
+pub struct NameServerGroup {
+    members: Result<Arc<dyn TxtResolver>, String>,
+}
+
+impl NameServerGroup {
+    pub(crate) fn into_resolver(self) -> Result<Arc<dyn TxtResolver>, String> {
+        self.members
+    }
+}
+
+/// An address with an optional port, 53 by default. IPv6 addresses may be
+/// bracketed, as they must be with a port.
+pub(crate) fn parse_nameserver(server: &str) -> Result<SocketAddr, String> {
+    let unbracketed = server.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')).unwrap_or(server);
+    SocketAddr::from_str(server)
+        .or_else(|_| IpAddr::from_str(unbracketed).map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| format!("invalid nameserver address {:?}", server))
+}
+
+/// What a call's `dns_server` names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum NameserverSpec {
+    Address(SocketAddr),
+    /// A host name, looked up before the first query, with port 53 unless
+    /// one is given.
+    Host { host: String, port: u16 },
+    Tls(TlsNameserver),
+    Doh(String),
+}
+
+impl FromStr for NameserverSpec {
+    type Err = SelfieError;
+
+    fn from_str(server: &str) -> Result<Self, SelfieError> {
+        if server.starts_with("tls://") {
+            return server.parse().map(NameserverSpec::Tls).map_err(SelfieError::Resolver);
+        }
+        if server.starts_with("http://") || server.starts_with("https://") {
+            return Ok(NameserverSpec::Doh(server.to_string()));
+        }
+        let invalid = || SelfieError::InvalidNameserver { server: server.to_string() };
+        if let Ok(address) = parse_nameserver(server) {
+            return Ok(NameserverSpec::Address(address));
+        }
+        let (host, port) = match server.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (server, 53),
+        };
+        let host = host.strip_suffix('.').unwrap_or(host);
+        let valid_label = |label: &str| {
+            !label.is_empty() && label.len() <= 63 && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') && !label.starts_with('-') && !label.ends_with('-')
+        };
+        // A last label of digits only would make an address, not a name.
+        let numeric = host.rsplit('.').next().is_some_and(|label| label.bytes().all(|b| b.is_ascii_digit()));
+        if host.len() > 253 || numeric || !host.split('.').all(valid_label) {
+            return Err(invalid());
+        }
+        Ok(NameserverSpec::Host { host: host.to_ascii_lowercase(), port })
+    }
+}
+
+/// The addresses of `host`, IPv4 and IPv6 alike, from the system resolver.
+pub(crate) async fn bootstrap(host: &str, port: u16, timeout: Duration) -> Result<Vec<SocketAddr>, SelfieError> {
+    let failed = |reason: String| SelfieError::NameserverResolutionFailed { host: host.to_string(), reason };
+    let addresses = match crate::time::timeout(timeout, crate::net::lookup_host((host, port))).await {
+        Ok(Ok(addresses)) => addresses,
+        Ok(Err(e)) => return Err(failed(e.to_string())),
+        Err(_) => return Err(failed(format!("timed out after {:?}", timeout))),
+    };
+    let mut found: Vec<SocketAddr> = Vec::new();
+    for address in addresses {
+        if !found.contains(&address) {
+            found.push(address);
+        }
+    }
+    match found.is_empty() {
+        true => Err(failed("no addresses".to_string())),
+        false => Ok(found),
+    }
+}
+
+/// Stands in for a host name's servers while offline, when it is not
+/// looked up and nothing is queried.
+pub(crate) struct UnresolvedHost {
+    pub(crate) host: String,
+    pub(crate) port: u16,
+}
+
+#[async_trait]
+impl TxtResolver for UnresolvedHost {
+    async fn txt_lookup(&self, _name: &str) -> Result<Vec<String>, SelfieError> {
+        Err(SelfieError::Offline)
+    }
+
+    fn describe(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+impl From<&str> for NameServerGroup {
+    fn from(servers: &str) -> Self {
+        let servers: Result<Vec<SocketAddr>, String> = servers.split(',').map(|server| parse_nameserver(server.trim())).collect();
+        NameServerGroup { members: servers.map(|servers| GroupResolver::resolver(&servers)) }
+    }
+}
+
+impl From<&[SocketAddr]> for NameServerGroup {
+    fn from(servers: &[SocketAddr]) -> Self {
+        match servers {
+            [] => NameServerGroup { members: Err("no nameservers in group".to_string()) },
+            _ => NameServerGroup { members: Ok(GroupResolver::resolver(servers)) },
+        }
+    }
+}
+
+impl From<SocketAddr> for NameServerGroup {
+    fn from(server: SocketAddr) -> Self {
+        NameServerGroup { members: Ok(Arc::new(DirectResolver::new(server))) }
+    }
+}
+
+impl From<IpAddr> for NameServerGroup {
+    fn from(ip: IpAddr) -> Self {
+        SocketAddr::new(ip, 53).into()
+    }
+}
+
+impl From<Arc<dyn TxtResolver>> for NameServerGroup {
+    fn from(resolver: Arc<dyn TxtResolver>) -> Self {
+        NameServerGroup { members: Ok(resolver) }
+    }
+}
+
+/// Asks its servers in order until one answers or fails for good.
+struct GroupResolver {
+    servers: Vec<DirectResolver>,
+}
+
+impl GroupResolver {
+    fn resolver(servers: &[SocketAddr]) -> Arc<dyn TxtResolver> {
+        Self::of(servers.iter().copied().map(DirectResolver::new).collect())
+    }
+
+    fn of(mut servers: Vec<DirectResolver>) -> Arc<dyn TxtResolver> {
+        match servers.len() {
+            1 => Arc::new(servers.remove(0)),
+            _ => Arc::new(GroupResolver { servers }),
+        }
+    }
+}
+
+/// A group of `servers`, queried as `transport` says, or over TCP through
+/// `proxy`, each within `timeout` when one is given. With a `quorum` the
+/// servers are all asked at once instead of in order.
+pub(crate) fn nameserver_resolver(
+    servers: &[SocketAddr],
+    transport: TransportPreference,
+    proxy: Option<&Socks5Config>,
+    timeout: Option<Duration>,
+    quorum: Option<usize>,
+) -> Arc<dyn TxtResolver> {
+    let servers = servers.iter().map(|&server| {
+        let resolver = match (proxy, transport) {
+            (Some(proxy), _) => DirectResolver::with_transport(Arc::new(Socks5Transport::new(proxy.clone(), server))),
+            (None, TransportPreference::UdpThenTcp) => DirectResolver::new(server),
+            (None, TransportPreference::TcpOnly) => DirectResolver::with_transport(Arc::new(TcpTransport::new(server))),
+        };
+        match timeout {
+            Some(timeout) => resolver.timeout(timeout),
+            None => resolver,
+        }
+    });
+    match quorum {
+        Some(quorum) => Arc::new(QuorumResolver { servers: servers.collect(), quorum }),
+        None => GroupResolver::of(servers.collect()),
+    }
+}
+
+/// Why `quorum` cannot be reached with `servers` nameservers, if it cannot.
+pub(crate) fn check_quorum(quorum: usize, servers: usize) -> Result<(), String> {
+    match quorum {
+        0 => Err("a quorum must be at least 1".to_string()),
+        _ if quorum > servers => Err(format!("a quorum of {} needs as many nameservers, not {}", quorum, servers)),
+        _ => Ok(()),
+    }
+}
+
+#[async_trait]
+impl TxtResolver for GroupResolver {
+    async fn txt_lookup(&self, name: &str) -> Result<Vec<String>, SelfieError> {
+        Ok(self.txt_lookup_with_info(name).await?.0)
+    }
+
+    async fn txt_lookup_with_info(&self, name: &str) -> Result<(Vec<String>, WireInfo), SelfieError> {
+        let (records, info) = self.txt_lookup_raw(name).await?;
+        Ok((encoding::lossy(&records), info))
+    }
+
+    async fn txt_lookup_raw(&self, name: &str) -> Result<(Vec<RawRecord>, WireInfo), SelfieError> {
+        let mut last_error = None;
+        for server in &self.servers {
+            match server.txt_lookup_raw(name).await {
+                Err(e) if e.is_retryable() => last_error = Some(e),
+                outcome => return outcome,
+            }
+        }
+        Err(last_error.unwrap_or_else(|| SelfieError::Resolver("no nameservers in group".to_string())))
+    }
+
+    fn describe(&self) -> String {
+        self.servers.iter().map(TxtResolver::describe).collect::<Vec<_>>().join(",")
+    }
+}
+
+/// Asks all of its servers at once and answers with the record set at
+/// least `quorum` of them returned, in any order. Fewer agreeing servers
+/// fail the lookup with `SelfieError::Disagreement`, unless every server
+/// failed, which fails it with the first server's error.
+struct QuorumResolver {
+    servers: Vec<DirectResolver>,
+    quorum: usize,
+}
+
+#[async_trait]
+impl TxtResolver for QuorumResolver {
+    async fn txt_lookup(&self, name: &str) -> Result<Vec<String>, SelfieError> {
+        Ok(self.txt_lookup_with_info(name).await?.0)
+    }
+
+    async fn txt_lookup_with_info(&self, name: &str) -> Result<(Vec<String>, WireInfo), SelfieError> {
+        let (records, info) = self.txt_lookup_raw(name).await?;
+        Ok((encoding::lossy(&records), info))
+    }
+
+    async fn txt_lookup_raw(&self, name: &str) -> Result<(Vec<RawRecord>, WireInfo), SelfieError> {
+        let mut outcomes = chunks::join_all(self.servers.iter().map(|server| server.txt_lookup_raw(name)).collect()).await;
+        let same = |a: &[RawRecord], b: &[RawRecord]| a.len() == b.len() && a.iter().all(|record| b.contains(record));
+        let agreeing = |records: &[RawRecord]| {
+            outcomes.iter().filter(|outcome| matches!(outcome, Ok((other, _)) if same(records, other))).count()
+        };
+        if let Some(at) = outcomes.iter().position(|outcome| matches!(outcome, Ok((records, _)) if agreeing(records) >= self.quorum)) {
+            return outcomes.swap_remove(at);
+        }
+        if outcomes.iter().all(Result::is_err) {
+            return outcomes.swap_remove(0);
+        }
+        let answers = self.servers.iter().zip(&outcomes).map(|(server, outcome)| match outcome {
+            Ok((records, _)) => format!("{}: {:?}", server.describe(), encoding::lossy(records)),
+            Err(e) => format!("{}: {}", server.describe(), e),
+        });
+        Err(SelfieError::Disagreement { quorum: self.quorum as u32, answers: answers.collect() })
+    }
+
+    fn describe(&self) -> String {
+        let servers: Vec<String> = self.servers.iter().map(TxtResolver::describe).collect();
+        format!("{} of {}", self.quorum, servers.join(","))
+    }
+}
+
+/// The rule that picked the resolver of a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Route {
+    /// No rule matched; the SDK's resolver, or the server passed to the call.
+    Default,
+    /// A `SdkBuilder::route_key` rule for the key.
+    Key,
+    /// A `SdkBuilder::route_suffix` rule for this domain suffix.
+    Suffix(String),
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Route::Default => f.write_str("default"),
+            Route::Key => f.write_str("key"),
+            Route::Suffix(suffix) => write!(f, "suffix {}", suffix),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Routes {
+    keys: HashMap<String, Arc<dyn TxtResolver>>,
+    /// Longest suffix first, so the first match is the most specific.
+    suffixes: Vec<(String, Arc<dyn TxtResolver>)>,
+}
+
+impl Routes {
+    pub(crate) fn insert_key(&mut self, key: &str, resolver: Arc<dyn TxtResolver>) {
+        self.keys.insert(key.to_string(), resolver);
+    }
+
+    pub(crate) fn insert_suffix(&mut self, suffix: &str, resolver: Arc<dyn TxtResolver>) {
+        let suffix = suffix.trim_end_matches('.').to_ascii_lowercase();
+        self.suffixes.retain(|(existing, _)| *existing != suffix);
+        let at = self.suffixes.partition_point(|(existing, _)| existing.len() >= suffix.len());
+        self.suffixes.insert(at, (suffix, resolver));
+    }
+
+    /// Every resolver a rule sends lookups to.
+    pub(crate) fn resolvers(&self) -> impl Iterator<Item = &Arc<dyn TxtResolver>> {
+        self.keys.values().chain(self.suffixes.iter().map(|(_, resolver)| resolver))
+    }
+
+    /// The resolver for `key` of an identifier at `domain`: a key rule, then
+    /// the longest matching suffix rule, then `default`.
+    pub(crate) fn resolve<'a>(
+        &'a self,
+        key: &str,
+        domain: &str,
+        default: &'a Arc<dyn TxtResolver>,
+    ) -> (Route, &'a Arc<dyn TxtResolver>) {
+        if let Some(resolver) = self.keys.get(key) {
+            return (Route::Key, resolver);
+        }
+        let domain = domain.trim_end_matches('.').as_bytes();
+        let matches = |suffix: &str| match domain.len().checked_sub(suffix.len()) {
+            Some(start) => {
+                domain[start..].eq_ignore_ascii_case(suffix.as_bytes()) && (start == 0 || domain[start - 1] == b'.')
+            }
+            None => false,
+        };
+        match self.suffixes.iter().find(|(suffix, _)| matches(suffix)) {
+            Some((suffix, resolver)) => (Route::Suffix(suffix.clone()), resolver),
+            None => (Route::Default, default),
+        }
+    }
+}
@@ -0,0 +1,381 @@
+//! A small HTTP/1.1 service answering lookups as JSON, for callers that
+//! cannot link the SDK. Behind `selfie serve`.
+//!
+//! - `GET /v1/records/{name}?keys=bitcoin-payment,nostr&dns=9.9.9.9&timeout=5`
+//!   answers 200 with the serialized `RecordsResponse`, 400 when the name
+//!   or a parameter is invalid, and 502 with `{"error", "records"}` when
+//!   no key was found because the resolver failed. `dns` takes an IP
+//!   address, with port 53 unless one is given, so that a request cannot
+//!   make the service look up or connect to host names and URLs.
+//! - `GET /healthz` answers 200 `{"status":"ok"}`.
+//!
+//! Each connection carries one request and is handled on a thread of its
+//! own, all sharing one SDK and so its cache. Connections beyond
+//! `max_connections` are answered 503 before a thread is started for them,
+//! and must send their request head within ten seconds. Lookups beyond
+//! `max_lookups` are answered 503 and clients over their rate limit 429,
+//! both without a lookup.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::error::SelfieError;
+use crate::options::LookupOptions;
+use crate::routing::NameserverSpec;
+use crate::SelfieRecordsSDK;
+
+const DEFAULT_MAX_LOOKUPS: usize = 64;
+const DEFAULT_MAX_CONNECTIONS: usize = 256;
+/// Longest request head read; anything longer is answered 431.
+const MAX_HEAD: usize = 8 * 1024;
+/// How long a client has to send its whole request head.
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the reply to a connection over the limit may take to write.
+const REJECT_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long the request of a connection over the limit is waited for, to
+/// be read and discarded.
+const REJECT_DRAIN: Duration = Duration::from_millis(100);
+/// Most seconds a request's `timeout` parameter may ask for.
+const MAX_TIMEOUT: f64 = 60.0;
+/// Clients tracked by the rate limiter before windows that have ended are
+/// forgotten.
+const TRACKED_CLIENTS: usize = 4096;
+
+/// At most `requests` requests per client IP in each window of `per`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RateLimit {
+    requests: u32,
+    per: Duration,
+}
+
+/// The service's settings, see `listen`.
+#[derive(Debug)]
+pub struct Server {
+    sdk: Arc<SelfieRecordsSDK>,
+    options: LookupOptions,
+    max_lookups: usize,
+    max_connections: usize,
+    rate_limit: Option<RateLimit>,
+}
+
+impl Server {
+    pub fn new(sdk: Arc<SelfieRecordsSDK>) -> Self {
+        Server {
+            sdk,
+            options: LookupOptions::default(),
+            max_lookups: DEFAULT_MAX_LOOKUPS,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            rate_limit: None,
+        }
+    }
+
+    /// Options every lookup starts from; a request's `timeout` replaces
+    /// the timeout.
+    pub fn lookup_options(mut self, options: LookupOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Lookups run at once. Defaults to 64.
+    pub fn max_lookups(mut self, max_lookups: usize) -> Self {
+        self.max_lookups = max_lookups.max(1);
+        self
+    }
+
+    /// Connections handled at once, each on a thread of its own. Defaults
+    /// to 256.
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections.max(1);
+        self
+    }
+
+    /// Lets each client IP make at most `requests` requests in each window
+    /// of `per`. Off by default.
+    pub fn rate_limit(mut self, requests: u32, per: Duration) -> Self {
+        self.rate_limit = Some(RateLimit { requests, per });
+        self
+    }
+
+    pub fn listen(self, addr: impl ToSocketAddrs) -> io::Result<Listening> {
+        let listener = TcpListener::bind(addr)?;
+        let state = State { server: self, connections: AtomicUsize::new(0), lookups: AtomicUsize::new(0), windows: Mutex::default() };
+        Ok(Listening { listener, state: Arc::new(state) })
+    }
+}
+
+struct State {
+    server: Server,
+    /// Connections being handled.
+    connections: AtomicUsize,
+    /// Lookups running.
+    lookups: AtomicUsize,
+    /// Start and count of each client's current rate-limit window.
+    windows: Mutex<HashMap<IpAddr, (Instant, u32)>>,
+}
+
+/// A bound server, not yet accepting connections.
+pub struct Listening {
+    listener: TcpListener,
+    state: Arc<State>,
+}
+
+impl Listening {
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections until accepting fails.
+    pub fn run(self) -> io::Result<()> {
+        loop {
+            let (stream, peer) = self.listener.accept()?;
+            let connections = &self.state.connections;
+            if connections.fetch_add(1, Ordering::SeqCst) >= self.state.server.max_connections {
+                connections.fetch_sub(1, Ordering::SeqCst);
+                reject(stream);
+                continue;
+            }
+            let state = self.state.clone();
+            let handler = move || {
+                let _ = state.handle(stream, peer.ip());
+                state.connections.fetch_sub(1, Ordering::SeqCst);
+            };
+            // A connection no thread can be started for is dropped.
+            if thread::Builder::new().name("selfie-serve".to_string()).spawn(handler).is_err() {
+                connections.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+/// Answers a connection over the limit 503 from the accepting thread,
+/// without reading its request.
+fn reject(stream: TcpStream) {
+    let _ = stream.set_write_timeout(Some(REJECT_TIMEOUT));
+    if respond(&stream, &Reply::error(503, "too many connections")).is_ok() {
+        let _ = stream.shutdown(Shutdown::Write);
+        // Closing with the request unread would reset the connection,
+        // possibly before the client read the reply.
+        let _ = stream.set_read_timeout(Some(REJECT_DRAIN));
+        let _ = (&stream).read(&mut [0; MAX_HEAD]);
+    }
+}
+
+struct Reply {
+    status: u16,
+    body: Value,
+    retry_after: Option<u64>,
+}
+
+impl Reply {
+    fn new(status: u16, body: Value) -> Self {
+        Reply { status, body, retry_after: None }
+    }
+
+    /// An error of the service itself rather than of a lookup.
+    fn error(status: u16, message: &str) -> Self {
+        Reply::new(status, json!({ "error": { "message": message } }))
+    }
+}
+
+impl State {
+    fn handle(&self, mut stream: TcpStream, client: IpAddr) -> io::Result<()> {
+        let reply = match read_head(&mut stream)? {
+            Some(head) => match self.admit(client) {
+                Ok(()) => self.route(&head),
+                Err(retry_after) => Reply { retry_after: Some(retry_after), ..Reply::error(429, "rate limit exceeded") },
+            },
+            None => Reply::error(431, "request head too large"),
+        };
+        respond(&stream, &reply)
+    }
+
+    /// Counts a request against `client`'s rate limit, or returns the
+    /// seconds until its window ends.
+    fn admit(&self, client: IpAddr) -> Result<(), u64> {
+        let Some(RateLimit { requests, per }) = self.server.rate_limit else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap_or_else(PoisonError::into_inner);
+        if windows.len() >= TRACKED_CLIENTS {
+            windows.retain(|_, (start, _)| now.duration_since(*start) < per);
+        }
+        let (start, count) = windows.entry(client).or_insert((now, 0));
+        if now.duration_since(*start) >= per {
+            (*start, *count) = (now, 0);
+        }
+        if *count >= requests {
+            return Err((per - now.duration_since(*start)).as_secs().max(1));
+        }
+        *count += 1;
+        Ok(())
+    }
+
+    fn route(&self, head: &str) -> Reply {
+        let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+        let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+            return Reply::error(400, "malformed request line");
+        };
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let route = match path {
+            "/healthz" => None,
+            path => match path.strip_prefix("/v1/records/") {
+                Some(name) if !name.is_empty() && !name.contains('/') => Some(name),
+                _ => return Reply::error(404, "not found"),
+            },
+        };
+        if method != "GET" {
+            return Reply::error(405, "only GET is supported");
+        }
+        match route {
+            None => Reply::new(200, json!({ "status": "ok" })),
+            Some(name) => match percent_decode(name) {
+                Some(name) => self.records(&name, query),
+                None => Reply::error(400, "name is not valid percent-encoded UTF-8"),
+            },
+        }
+    }
+
+    fn records(&self, name: &str, query: &str) -> Reply {
+        if self.lookups.fetch_add(1, Ordering::SeqCst) >= self.server.max_lookups {
+            self.lookups.fetch_sub(1, Ordering::SeqCst);
+            return Reply::error(503, "too many lookups running");
+        }
+        let reply = self.lookup(name, query);
+        self.lookups.fetch_sub(1, Ordering::SeqCst);
+        reply
+    }
+
+    fn lookup(&self, name: &str, query: &str) -> Reply {
+        let sdk = &self.server.sdk;
+        let mut options = self.server.options.clone();
+        let (mut keys, mut dns) = (None, None);
+        for (param, value) in query.split('&').filter(|pair| !pair.is_empty()).map(|pair| pair.split_once('=').unwrap_or((pair, ""))) {
+            let Some(value) = query_decode(value) else {
+                return Reply::error(400, &format!("{} is not valid percent-encoded UTF-8", param));
+            };
+            match param {
+                "keys" => keys = Some(value.split(',').filter(|key| !key.is_empty()).map(str::to_string).collect::<Vec<_>>()),
+                "dns" => match value.parse::<NameserverSpec>() {
+                    Ok(NameserverSpec::Address(_)) => dns = Some(value),
+                    Ok(_) => return Reply::error(400, "dns must be an IP address, with an optional port"),
+                    Err(e) => return Reply::new(400, json!({ "error": e })),
+                },
+                "timeout" => match value.parse::<f64>() {
+                    Ok(seconds) if seconds > 0.0 && seconds <= MAX_TIMEOUT => {
+                        options = options.timeout(Duration::from_secs_f64(seconds));
+                    }
+                    _ => return Reply::error(400, &format!("timeout must be a number of seconds from 0 to {}", MAX_TIMEOUT)),
+                },
+                param => return Reply::error(400, &format!("unknown parameter {:?}", param)),
+            }
+        }
+        if let Err(e) = sdk.identifier(name, sdk.resolver.resolves_onion()) {
+            return Reply::new(400, json!({ "error": e }));
+        }
+        let filters = keys.as_ref().map(|keys| keys.iter().map(String::as_str).collect());
+        let response = sdk.get_records_response(name, filters, dns.as_deref(), &options);
+        let found = response.iter().any(|(_, result)| result.value.is_some());
+        let failure = response.iter().filter_map(|(_, result)| result.error.as_ref()).find(|e| is_resolver_failure(e));
+        match failure {
+            Some(error) if !found => Reply::new(502, json!({ "error": error, "records": response })),
+            _ => Reply::new(200, serde_json::to_value(&response).expect("responses serialize")),
+        }
+    }
+}
+
+/// Whether `error` says the resolver could not answer, rather than
+/// anything about the record.
+fn is_resolver_failure(error: &SelfieError) -> bool {
+    error.is_unreachable() || matches!(error, SelfieError::RateLimited { .. })
+}
+
+/// Reads up to the blank line ending the head, `None` when it is too long.
+/// A client trickling its head in fails when `HEAD_TIMEOUT` runs out.
+fn read_head(stream: &mut TcpStream) -> io::Result<Option<String>> {
+    let deadline = Instant::now() + HEAD_TIMEOUT;
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() > MAX_HEAD {
+            return Ok(None);
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        stream.set_read_timeout(Some(left))?;
+        let read = stream.read(&mut buf)?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        head.extend_from_slice(&buf[..read]);
+        // A head split across reads may end inside this read.
+        if let Some(end) = head.windows(4).position(|window| window == b"\r\n\r\n") {
+            head.truncate(end + 4);
+        }
+    }
+    Ok(Some(String::from_utf8_lossy(&head).into_owned()))
+}
+
+fn respond(mut stream: &TcpStream, reply: &Reply) -> io::Result<()> {
+    let body = reply.body.to_string();
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        reply.status,
+        reason(reply.status),
+        body.len()
+    );
+    if let Some(seconds) = reply.retry_after {
+        head.push_str(&format!("Retry-After: {}\r\n", seconds));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+/// Decodes `%XX` escapes, `None` for malformed ones or invalid UTF-8.
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match byte {
+            b'%' => {
+                let hex = tail.get(..2).filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+                bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+                rest = &tail[2..];
+                continue;
+            }
+            byte => bytes.push(byte),
+        }
+        rest = tail;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Like `percent_decode`, with `+` as a space, as in query strings.
+fn query_decode(text: &str) -> Option<String> {
+    percent_decode(&text.replace('+', " "))
+}
@@ -0,0 +1,114 @@
+//! Inline signatures carried inside record values, such as
+//! `value=bitcoin:bc1...; sig=<base64 ed25519 signature>`, made by a key the
+//! domain publishes under its `_selfie-key` record.
+//!
+//! The signed message is the canonical form of the record without its
+//! signature field: the record is split into `;`-separated fields,
+//! whitespace around each field and around its first `=` is removed, the
+//! signature field is dropped and the remaining fields are joined with `;`
+//! in their original order. `value=a ; note = b; sig=...` therefore signs
+//! `value=a;note=b`.
+
+use std::fmt;
+
+use data_encoding::{BASE64, BASE64_NOPAD, HEXLOWER_PERMISSIVE};
+use ring::signature::{UnparsedPublicKey, ED25519};
+use thiserror::Error;
+
+/// Field holding the signature unless configured otherwise.
+pub const DEFAULT_SIGNATURE_FIELD: &str = "sig";
+/// Record key the verification key is published under.
+pub const VERIFICATION_KEY_RECORD: &str = "selfie-key";
+
+const PUBLIC_KEY_LENGTH: usize = 32;
+
+/// Outcome of verifying a record's inline signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureStatus {
+    Verified,
+    Invalid,
+    /// The domain publishes no usable verification key.
+    NoKey,
+}
+
+impl fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureStatus::Verified => f.write_str("verified"),
+            SignatureStatus::Invalid => f.write_str("invalid"),
+            SignatureStatus::NoKey => f.write_str("no-key"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SignatureError {
+    #[error("verification key is not a 32-byte ed25519 key in hex or base64")]
+    InvalidKey,
+    #[error("signature is not valid base64")]
+    InvalidSignatureEncoding,
+}
+
+/// A record split into its canonical signed message and the signature bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedValue {
+    pub message: String,
+    pub signature: Vec<u8>,
+}
+
+/// Parses an ed25519 public key given as 64 hex digits or as base64.
+pub fn parse_public_key(text: &str) -> Result<[u8; PUBLIC_KEY_LENGTH], SignatureError> {
+    let text = text.trim();
+    let bytes = if text.len() == PUBLIC_KEY_LENGTH * 2 {
+        HEXLOWER_PERMISSIVE.decode(text.as_bytes()).ok()
+    } else {
+        None
+    };
+    let bytes = bytes
+        .or_else(|| BASE64.decode(text.as_bytes()).ok())
+        .or_else(|| BASE64_NOPAD.decode(text.as_bytes()).ok())
+        .ok_or(SignatureError::InvalidKey)?;
+    bytes.try_into().map_err(|_| SignatureError::InvalidKey)
+}
+
+/// Splits `record` into its canonical message and the signature held in
+/// `field`. Returns `None` when the record carries no such field.
+pub fn split_signed_value(record: &str, field: &str) -> Result<Option<SignedValue>, SignatureError> {
+    let mut signature = None;
+    let mut fields = Vec::new();
+    for part in record.split(';').map(str::trim).filter(|part| !part.is_empty()) {
+        match part.split_once('=') {
+            Some((name, value)) if name.trim() == field => signature = Some(value.trim()),
+            Some((name, value)) => fields.push(format!("{}={}", name.trim(), value.trim())),
+            None => fields.push(part.to_string()),
+        }
+    }
+
+    let Some(signature) = signature else {
+        return Ok(None);
+    };
+    let signature = BASE64
+        .decode(signature.as_bytes())
+        .or_else(|_| BASE64_NOPAD.decode(signature.as_bytes()))
+        .map_err(|_| SignatureError::InvalidSignatureEncoding)?;
+    Ok(Some(SignedValue { message: fields.join(";"), signature }))
+}
+
+/// Checks an ed25519 `signature` over `message`.
+pub fn verify(public_key: &[u8; PUBLIC_KEY_LENGTH], message: &[u8], signature: &[u8]) -> bool {
+    UnparsedPublicKey::new(&ED25519, public_key).verify(message, signature).is_ok()
+}
+
+/// Verifies `record` against any of `keys`.
+pub(crate) fn check_record(record: &str, field: &str, keys: &[[u8; PUBLIC_KEY_LENGTH]]) -> Option<SignatureStatus> {
+    let signed = match split_signed_value(record, field) {
+        Ok(Some(signed)) => signed,
+        Ok(None) => return None,
+        Err(_) => return Some(SignatureStatus::Invalid),
+    };
+    if keys.is_empty() {
+        return Some(SignatureStatus::NoKey);
+    }
+    let valid = keys.iter().any(|key| verify(key, signed.message.as_bytes(), &signed.signature));
+    Some(if valid { SignatureStatus::Verified } else { SignatureStatus::Invalid })
+}
@@ -0,0 +1,176 @@
+//! In-memory backend for exercising the SDK without network access.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::encoding::{self, RawRecord};
+use crate::error::SelfieError;
+use crate::resolver::TxtResolver;
+use crate::wire::WireInfo;
+
+/// A `TxtResolver` answering from a fixed table of names.
+#[derive(Debug, Default)]
+pub struct MockTxtResolver {
+    records: HashMap<String, Vec<RawRecord>>,
+    delays: HashMap<String, Duration>,
+    ttls: HashMap<String, u32>,
+    nxdomains: HashSet<String>,
+    unanswered: HashSet<String>,
+    calls: AtomicUsize,
+}
+
+impl MockTxtResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers `name` with the given TXT values.
+    pub fn with_record(mut self, name: &str, values: &[&str]) -> Self {
+        self.records
+            .insert(name.to_string(), values.iter().map(|v| RawRecord::new([v])).collect());
+        self
+    }
+
+    /// Answers `name` with TXT records holding arbitrary bytes, each a
+    /// single character-string.
+    pub fn with_raw_record(self, name: &str, records: &[&[u8]]) -> Self {
+        self.with_rdata(name, records.iter().map(|record| RawRecord::new([record])).collect())
+    }
+
+    /// Answers `name` with the given rdata, character-strings included.
+    pub fn with_rdata(mut self, name: &str, records: Vec<RawRecord>) -> Self {
+        self.records.insert(name.to_string(), records);
+        self
+    }
+
+    /// Waits `delay` before answering each query for `name`.
+    pub fn with_delay(mut self, name: &str, delay: Duration) -> Self {
+        self.delays.insert(name.to_string(), delay);
+        self
+    }
+
+    /// Reports `ttl` seconds for the answers to `name`, as the SOA minimum
+    /// for answers without records.
+    pub fn with_ttl(mut self, name: &str, ttl: u32) -> Self {
+        self.ttls.insert(name.to_string(), ttl);
+        self
+    }
+
+    /// Answers `name` with NXDOMAIN, as for a name that does not exist.
+    pub fn with_nxdomain(mut self, name: &str) -> Self {
+        self.nxdomains.insert(name.to_string());
+        self
+    }
+
+    /// Never answers `name`, so that its lookups time out.
+    pub fn with_timeout(mut self, name: &str) -> Self {
+        self.unanswered.insert(name.to_string());
+        self
+    }
+
+    /// Number of queries received so far.
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl TxtResolver for MockTxtResolver {
+    async fn txt_lookup(&self, name: &str) -> Result<Vec<String>, SelfieError> {
+        Ok(self.txt_lookup_with_info(name).await?.0)
+    }
+
+    async fn txt_lookup_with_info(&self, name: &str) -> Result<(Vec<String>, WireInfo), SelfieError> {
+        let (records, info) = self.txt_lookup_raw(name).await?;
+        Ok((encoding::lossy(&records), info))
+    }
+
+    async fn txt_lookup_raw(&self, name: &str) -> Result<(Vec<RawRecord>, WireInfo), SelfieError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if let Some(delay) = self.delays.get(name) {
+            crate::time::sleep(*delay).await;
+        }
+        if self.unanswered.contains(name) {
+            std::future::pending::<()>().await;
+        }
+        if self.nxdomains.contains(name) {
+            return Ok((Vec::new(), WireInfo { response_code: Some(3), ttl: self.ttls.get(name).copied(), ..WireInfo::default() }));
+        }
+        match self.records.get(name) {
+            Some(records) => Ok((records.clone(), WireInfo { ttl: self.ttls.get(name).copied(), ..WireInfo::default() })),
+            None => Err(SelfieError::Resolver(format!(
+                "Error resolving TXT record for {}: no mock record",
+                name
+            ))),
+        }
+    }
+}
+
+/// A `RecordSource` serving a fixed set of records, RRSIGs included, as a
+/// resolver would: the name's CNAME when it has none of the type asked for.
+#[cfg(feature = "dnssec")]
+#[derive(Debug, Default)]
+pub struct MockRecordSource {
+    records: Vec<trust_dns_proto::rr::Record>,
+}
+
+#[cfg(feature = "dnssec")]
+impl MockRecordSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_records(mut self, records: impl IntoIterator<Item = trust_dns_proto::rr::Record>) -> Self {
+        self.records.extend(records);
+        self
+    }
+}
+
+#[cfg(feature = "dnssec")]
+#[async_trait]
+impl crate::dnssec::RecordSource for MockRecordSource {
+    async fn fetch(
+        &self,
+        name: &trust_dns_proto::rr::Name,
+        rtype: trust_dns_proto::rr::RecordType,
+    ) -> Result<Vec<trust_dns_proto::rr::Record>, SelfieError> {
+        Ok(crate::dnssec::answer_from(&self.records, name, rtype))
+    }
+}
+
+/// A `PgpKeySource` serving fixed key blocks.
+#[cfg(feature = "signatures")]
+#[derive(Debug, Default)]
+pub struct MockPgpKeySource {
+    keys: HashMap<crate::linkage::KeyLocator, Vec<u8>>,
+}
+
+#[cfg(feature = "signatures")]
+impl MockPgpKeySource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_key(mut self, locator: crate::linkage::KeyLocator, key_block: &[u8]) -> Self {
+        self.keys.insert(locator, key_block.to_vec());
+        self
+    }
+}
+
+#[cfg(feature = "signatures")]
+#[async_trait]
+impl crate::linkage::PgpKeySource for MockPgpKeySource {
+    async fn fetch_key(
+        &self,
+        locator: &crate::linkage::KeyLocator,
+        _identifier: &crate::name::Identifier,
+    ) -> Result<Vec<u8>, SelfieError> {
+        self.keys
+            .get(locator)
+            .cloned()
+            .ok_or_else(|| SelfieError::Resolver(format!("Error fetching key {:?}: no mock key", locator)))
+    }
+}
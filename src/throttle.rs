@@ -0,0 +1,61 @@
+//! Limits on the lookups an SDK sends: how many per second and how many
+//! at once; see `SdkBuilder::max_queries_per_second`.
+
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::error::SelfieError;
+use crate::time::Instant;
+
+/// Named as the server in the errors of queries the limits hold back.
+const LIMITER: &str = "the SDK's query limits";
+
+#[derive(Debug)]
+pub(crate) struct QueryLimiter {
+    /// Time between two queries.
+    interval: Option<Duration>,
+    /// When the next query may be sent.
+    next: Mutex<Instant>,
+    in_flight: Option<Semaphore>,
+}
+
+impl QueryLimiter {
+    pub(crate) fn new(per_second: Option<u32>, in_flight: Option<usize>) -> Self {
+        QueryLimiter {
+            interval: per_second.map(|per_second| Duration::from_secs(1) / per_second),
+            next: Mutex::new(Instant::now()),
+            in_flight: in_flight.map(Semaphore::new),
+        }
+    }
+
+    /// Waits until a query may be sent, holding the returned permit while
+    /// it is in flight. Fails with `RateLimited`, at once, when that would
+    /// take longer than `limit`.
+    pub(crate) async fn acquire(&self, limit: Duration) -> Result<Option<SemaphorePermit<'_>>, SelfieError> {
+        let started = Instant::now();
+        let permit = match &self.in_flight {
+            None => None,
+            Some(permits) => match crate::time::timeout(limit, permits.acquire()).await {
+                Ok(permit) => Some(permit.expect("the semaphore is never closed")),
+                Err(_) => return Err(SelfieError::RateLimited { server: LIMITER.to_string(), retry_after: None }),
+            },
+        };
+        // Taken after the permit, so that queries leave at their slots.
+        if let Some(interval) = self.interval {
+            let slot = {
+                let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+                let now = Instant::now();
+                let slot = (*next).max(now);
+                if started.elapsed() + (slot - now) > limit {
+                    return Err(SelfieError::RateLimited { server: LIMITER.to_string(), retry_after: Some(slot - now) });
+                }
+                *next = slot + interval;
+                slot
+            };
+            crate::time::sleep_until(slot).await;
+        }
+        Ok(permit)
+    }
+}
@@ -0,0 +1,50 @@
+//! Clocks and timers. On wasm32 the standard clocks panic and tokio's
+//! timers have no driver to run them, so there both come from JavaScript.
+
+#[cfg(target_arch = "wasm32")]
+use std::future::Future;
+#[cfg(target_arch = "wasm32")]
+use std::time::Duration;
+use std::time::SystemTime;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::Instant;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use tokio::time::{sleep, timeout};
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) use crate::wasm::sleep;
+
+/// The current wall-clock time.
+pub(crate) fn now() -> SystemTime {
+    #[cfg(not(target_arch = "wasm32"))]
+    return SystemTime::now();
+    #[cfg(target_arch = "wasm32")]
+    return std::time::UNIX_EPOCH + Duration::from_millis(js_sys::Date::now() as u64);
+}
+
+/// Waits until `deadline`.
+pub(crate) async fn sleep_until(deadline: Instant) {
+    sleep(deadline.saturating_duration_since(Instant::now())).await
+}
+
+/// `future` did not complete in time.
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug)]
+pub(crate) struct Elapsed;
+
+/// Runs `future` for at most `duration`, as `tokio::time::timeout` does.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> Result<F::Output, Elapsed> {
+    use std::task::Poll;
+
+    let mut future = std::pin::pin!(future);
+    let mut timer = std::pin::pin!(sleep(duration));
+    std::future::poll_fn(|cx| match future.as_mut().poll(cx) {
+        Poll::Ready(output) => Poll::Ready(Ok(output)),
+        Poll::Pending => timer.as_mut().poll(cx).map(|()| Err(Elapsed)),
+    })
+    .await
+}
@@ -0,0 +1,108 @@
+//! TLS under the DoH and DoT transports, with rustls. Behind the `tls`
+//! feature.
+
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+
+use crate::error::SelfieError;
+use crate::net::TcpStream;
+use crate::transport::TransportError;
+
+/// The certificates servers reached over TLS are checked against. Sessions
+/// are resumed from tickets of earlier connections made with the same
+/// config, clones included, so a connection from a warm-up makes the ones
+/// after it cheaper even once it is closed.
+#[derive(Clone)]
+pub struct TlsConfig {
+    client: Arc<ClientConfig>,
+    /// The same, offering HTTP/2 before HTTP/1.1 over ALPN.
+    http: Arc<ClientConfig>,
+}
+
+impl TlsConfig {
+    /// Trusts Mozilla's root certificates, as bundled by `webpki-roots`.
+    /// Every config made this way shares one session cache.
+    pub fn new() -> Self {
+        static PUBLIC: OnceLock<TlsConfig> = OnceLock::new();
+        PUBLIC
+            .get_or_init(|| {
+                let mut roots = RootCertStore::empty();
+                roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+                    OwnedTrustAnchor::from_subject_spki_name_constraints(anchor.subject, anchor.spki, anchor.name_constraints)
+                }));
+                TlsConfig::trusting(roots)
+            })
+            .clone()
+    }
+
+    /// Trusts only `certificates`, DER-encoded, such as a private CA's.
+    pub fn with_root_certificates(certificates: &[&[u8]]) -> Result<Self, SelfieError> {
+        let mut roots = RootCertStore::empty();
+        for der in certificates {
+            roots
+                .add(&Certificate(der.to_vec()))
+                .map_err(|e| SelfieError::Resolver(format!("Invalid root certificate: {}", e)))?;
+        }
+        Ok(TlsConfig::trusting(roots))
+    }
+
+    fn trusting(roots: RootCertStore) -> Self {
+        let client = ClientConfig::builder().with_safe_defaults().with_root_certificates(roots).with_no_client_auth();
+        // Clones share the session cache.
+        let mut http = client.clone();
+        http.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        TlsConfig { client: Arc::new(client), http: Arc::new(http) }
+    }
+
+    /// Opens a session over `stream` with the server `name`, a host name or
+    /// an IP address its certificate must be valid for.
+    pub(crate) async fn connect(&self, stream: TcpStream, name: &str) -> Result<TlsStream<TcpStream>, TransportError> {
+        handshake(&self.client, stream, name).await
+    }
+
+    /// Like `connect`, offering HTTP/2, and telling whether the server
+    /// chose it.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub(crate) async fn connect_http(&self, stream: TcpStream, name: &str) -> Result<(TlsStream<TcpStream>, bool), TransportError> {
+        let stream = handshake(&self.http, stream, name).await?;
+        let http2 = stream.get_ref().1.alpn_protocol() == Some(b"h2");
+        Ok((stream, http2))
+    }
+}
+
+async fn handshake(client: &Arc<ClientConfig>, stream: TcpStream, name: &str) -> Result<TlsStream<TcpStream>, TransportError> {
+    let server_name = ServerName::try_from(name).map_err(|_| TransportError::Io(format!("invalid TLS server name {:?}", name)))?;
+    TlsConnector::from(client.clone()).connect(server_name, stream).await.map_err(|e| {
+        match e.get_ref().and_then(|inner| inner.downcast_ref::<rustls::Error>()) {
+            Some(reason @ rustls::Error::InvalidCertificate(_)) => {
+                TransportError::Certificate { name: name.to_string(), reason: reason.to_string() }
+            }
+            _ => TransportError::Io(format!("TLS handshake with {} failed: {}", name, e)),
+        }
+    })
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        TlsConfig::new()
+    }
+}
+
+/// Configs are equal when they share a session cache.
+impl PartialEq for TlsConfig {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.client, &other.client)
+    }
+}
+
+impl Eq for TlsConfig {}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TlsConfig")
+    }
+}
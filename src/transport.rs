@@ -0,0 +1,475 @@
+//! The byte-moving layer under the SDK's own DNS exchanges. The SDK builds
+//! the queries and parses, follows and validates the answers; a
+//! `DnsTransport` only carries wire-format messages to a server and back,
+//! so environments without sockets can plug in whatever channel they have.
+
+#[cfg(feature = "tls")]
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+#[cfg(feature = "tls")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "tls")]
+use std::sync::{Mutex, PoisonError};
+
+use async_trait::async_trait;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+#[cfg(feature = "tls")]
+use tokio::sync::{mpsc, oneshot};
+
+#[cfg(feature = "tls")]
+use crate::http::{Pool, PoolStats, Shared};
+use crate::net::{TcpStream, UdpSocket};
+#[cfg(feature = "tls")]
+use crate::proxy::{self, Socks5Config, Target};
+#[cfg(feature = "tls")]
+use crate::tls::TlsConfig;
+use crate::wire::{Transport, MAX_UDP_PAYLOAD};
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TransportError {
+    #[error("request timed out")]
+    Timeout,
+    #[error("{0}")]
+    Io(String),
+    /// The SOCKS5 proxy at `proxy` could not be connected to, or refused
+    /// the SDK.
+    #[error("SOCKS5 proxy {proxy} unreachable: {reason}")]
+    ProxyUnreachable { proxy: SocketAddr, reason: String },
+    /// The server's certificate is not valid for `name`, the name it was
+    /// reached by.
+    #[error("certificate for {name} rejected: {reason}")]
+    Certificate { name: String, reason: String },
+}
+
+impl From<std::io::Error> for TransportError {
+    fn from(e: std::io::Error) -> Self {
+        TransportError::Io(e.to_string())
+    }
+}
+
+/// Carries DNS messages to one server. Implementations need not check
+/// the response; the SDK matches it to its query.
+#[async_trait]
+pub trait DnsTransport: Send + Sync {
+    /// Sends the query `request_wire` and returns the server's response.
+    async fn exchange(&self, request_wire: &[u8]) -> Result<Vec<u8>, TransportError>;
+
+    /// Reported as `WireInfo::transport_used`.
+    fn kind(&self) -> Transport {
+        Transport::Resolver
+    }
+
+    /// Names the server in errors and `KeyResult::resolver`.
+    fn describe(&self) -> String {
+        "custom transport".to_string()
+    }
+
+    /// Reported as `KeyResult::answered_by`, for transports talking to one
+    /// known address.
+    fn server(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    /// Opens a connection to keep for later exchanges, unless one is
+    /// open, telling whether it opened one; see `SelfieRecordsSDK::warm_up`.
+    /// Transports without connections to keep return `None`.
+    async fn preconnect(&self) -> Option<Result<bool, TransportError>> {
+        None
+    }
+}
+
+/// Opens a transport to an address the SDK found itself, such as an
+/// authoritative nameserver; see `SdkBuilder::authoritative_transport`.
+pub type TransportFactory = Arc<dyn Fn(SocketAddr) -> Arc<dyn DnsTransport> + Send + Sync>;
+
+/// One datagram each way.
+#[derive(Debug, Clone, Copy)]
+pub struct UdpTransport {
+    server: SocketAddr,
+}
+
+impl UdpTransport {
+    pub fn new(server: SocketAddr) -> Self {
+        UdpTransport { server }
+    }
+}
+
+#[async_trait]
+impl DnsTransport for UdpTransport {
+    async fn exchange(&self, request_wire: &[u8]) -> Result<Vec<u8>, TransportError> {
+        let bind: SocketAddr = if self.server.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+        let socket = UdpSocket::bind(bind).await?;
+        socket.connect(self.server).await?;
+        socket.send(request_wire).await?;
+
+        let mut buffer = vec![0; MAX_UDP_PAYLOAD as usize];
+        let len = socket.recv(&mut buffer).await?;
+        buffer.truncate(len);
+        Ok(buffer)
+    }
+
+    fn kind(&self) -> Transport {
+        Transport::Udp
+    }
+
+    fn describe(&self) -> String {
+        self.server.to_string()
+    }
+
+    fn server(&self) -> Option<SocketAddr> {
+        Some(self.server)
+    }
+}
+
+/// A new connection per message, each framed with its length.
+#[derive(Debug, Clone, Copy)]
+pub struct TcpTransport {
+    server: SocketAddr,
+}
+
+impl TcpTransport {
+    pub fn new(server: SocketAddr) -> Self {
+        TcpTransport { server }
+    }
+}
+
+#[async_trait]
+impl DnsTransport for TcpTransport {
+    async fn exchange(&self, request_wire: &[u8]) -> Result<Vec<u8>, TransportError> {
+        let mut stream = TcpStream::connect(self.server).await?;
+        exchange_framed(&mut stream, request_wire).await
+    }
+
+    fn kind(&self) -> Transport {
+        Transport::Tcp
+    }
+
+    fn describe(&self) -> String {
+        self.server.to_string()
+    }
+
+    fn server(&self) -> Option<SocketAddr> {
+        Some(self.server)
+    }
+}
+
+/// Sends `request_wire` over `stream` framed with its length, and reads
+/// the response framed the same way.
+pub(crate) async fn exchange_framed(stream: &mut (impl AsyncRead + AsyncWrite + Unpin), request_wire: &[u8]) -> Result<Vec<u8>, TransportError> {
+    let len = u16::try_from(request_wire.len()).map_err(|_| TransportError::Io("query too long".to_string()))?;
+    let mut framed = len.to_be_bytes().to_vec();
+    framed.extend_from_slice(request_wire);
+    stream.write_all(&framed).await?;
+    stream.flush().await?;
+
+    let len = stream.read_u16().await?;
+    let mut buffer = vec![0; len as usize];
+    stream.read_exact(&mut buffer).await?;
+    Ok(buffer)
+}
+
+/// A DNS-over-TLS server and the name its certificate must carry, written
+/// `tls://1.1.1.1@cloudflare-dns.com`, with port 853 unless one is given as
+/// in `tls://[2606:4700::1111]:8853@cloudflare-dns.com`.
+///
+/// `TlsTransport` queries one, with the `tls` feature; without it, queries
+/// to one need a `DnsTransport` of the application's, see
+/// `DirectResolver::with_transport`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsNameserver {
+    pub address: SocketAddr,
+    pub tls_name: String,
+}
+
+impl FromStr for TlsNameserver {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, String> {
+        let invalid = |reason: &str| format!("invalid DNS-over-TLS server {:?}: {}", spec, reason);
+        let rest = spec.strip_prefix("tls://").ok_or_else(|| invalid("expected tls://"))?;
+        let (address, tls_name) = rest.rsplit_once('@').ok_or_else(|| invalid("expected @ and the server's TLS name"))?;
+        let address = SocketAddr::from_str(address)
+            .or_else(|_| IpAddr::from_str(address.trim_start_matches('[').trim_end_matches(']')).map(|ip| SocketAddr::new(ip, 853)))
+            .map_err(|_| invalid("expected an IP address with an optional port"))?;
+        let valid_label = |label: &str| !label.is_empty() && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+        if !tls_name.split('.').all(valid_label) {
+            return Err(invalid("expected a host name after @"));
+        }
+        Ok(TlsNameserver { address, tls_name: tls_name.to_ascii_lowercase() })
+    }
+}
+
+/// The `tls://` form `from_str` reads.
+impl fmt::Display for TlsNameserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tls://{}@{}", self.address, self.tls_name)
+    }
+}
+
+#[cfg(feature = "tls")]
+const DEFAULT_MAX_CONNECTIONS: usize = 2;
+#[cfg(feature = "tls")]
+const DEFAULT_IDLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// DNS over TLS (RFC 7858) to one server, behind the `tls` feature: each
+/// message framed as over TCP, on connections kept open for later
+/// exchanges, which are pipelined: one connection carries any number at
+/// once. An exchange failing on a kept connection, e.g. one the server
+/// closed, is retried once on a new one. A certificate not valid for the
+/// server's TLS name fails with `TransportError::Certificate`.
+#[cfg(feature = "tls")]
+#[derive(Debug, Clone)]
+pub struct TlsTransport {
+    server: TlsNameserver,
+    config: TlsConfig,
+    proxy: Option<Socks5Config>,
+    /// Shared by clones.
+    pool: Arc<Pool<Pipeline>>,
+}
+
+#[cfg(feature = "tls")]
+impl TlsTransport {
+    /// Checks the server's certificate against Mozilla's roots.
+    pub fn new(server: TlsNameserver) -> Self {
+        TlsTransport {
+            server,
+            config: TlsConfig::new(),
+            proxy: None,
+            pool: Arc::new(Pool::new(DEFAULT_MAX_CONNECTIONS, DEFAULT_IDLE_TIMEOUT)),
+        }
+    }
+
+    pub fn tls_config(mut self, config: TlsConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Connects through `proxy`.
+    pub fn proxy(mut self, proxy: Socks5Config) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Connections kept open, and the most opened at once; exchanges
+    /// waiting for one to open share it. Defaults to 2.
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.pool = Arc::new(Pool::new(max.max(1), DEFAULT_IDLE_TIMEOUT));
+        self
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        self.pool.stats()
+    }
+
+    /// A kept connection, telling that it was kept, or a new one. Exchanges
+    /// waiting for their turn to open one take one opened meanwhile.
+    async fn pipeline(&self) -> Result<(Pipeline, bool), TransportError> {
+        if let Some(pipeline) = self.pool.take() {
+            return Ok((pipeline, true));
+        }
+        let _permit = self.pool.permits.acquire().await.map_err(|_| TransportError::Io("connection pool closed".to_string()))?;
+        if let Some(pipeline) = self.pool.take() {
+            return Ok((pipeline, true));
+        }
+        let pipeline = self.connect().await?;
+        self.pool.put(pipeline.clone());
+        Ok((pipeline, false))
+    }
+
+    async fn connect(&self) -> Result<Pipeline, TransportError> {
+        let stream = match &self.proxy {
+            Some(proxy) => proxy::connect(proxy, &Target::Address(self.server.address)).await?,
+            None => TcpStream::connect(self.server.address).await?,
+        };
+        let stream = self.config.connect(stream, &self.server.tls_name).await?;
+        Ok(self.pool.opened(Pipeline::new(stream)))
+    }
+}
+
+/// A DoT connection carrying any number of exchanges at once (RFC 7766
+/// section 6.2.1.1). Each query goes out under an ID no other one on the
+/// connection has, and a task reading the responses hands each to the
+/// exchange with its ID, in whatever order they come. Another task writes
+/// the queries, so that an exchange given up on cannot leave half of one
+/// on the connection. The connection is closed once no handle is left.
+#[cfg(feature = "tls")]
+#[derive(Debug, Clone)]
+pub(crate) struct Pipeline {
+    queries: mpsc::UnboundedSender<Vec<u8>>,
+    state: Arc<PipelineState>,
+}
+
+#[cfg(feature = "tls")]
+#[derive(Debug, Default)]
+struct PipelineState {
+    waiting: Mutex<Waiting>,
+    closed: AtomicBool,
+}
+
+/// The exchanges waiting for a response, by the ID their query went out
+/// under.
+#[cfg(feature = "tls")]
+#[derive(Debug, Default)]
+struct Waiting {
+    responses: HashMap<u16, oneshot::Sender<Vec<u8>>>,
+    next_id: u16,
+}
+
+#[cfg(feature = "tls")]
+impl Pipeline {
+    fn new(stream: tokio_rustls::client::TlsStream<TcpStream>) -> Self {
+        let (mut reader, mut writer) = tokio::io::split(stream);
+        let (queries, mut outgoing) = mpsc::unbounded_channel::<Vec<u8>>();
+        let state = Arc::new(PipelineState::default());
+
+        let writing = state.clone();
+        tokio::spawn(async move {
+            while let Some(query) = outgoing.recv().await {
+                if writer.write_all(&query).await.is_err() || writer.flush().await.is_err() {
+                    writing.close();
+                    return;
+                }
+            }
+            let _ = writer.shutdown().await;
+        });
+        let reading = state.clone();
+        tokio::spawn(async move {
+            while let Ok(len) = reader.read_u16().await {
+                let mut response = vec![0; len as usize];
+                if len < 2 || reader.read_exact(&mut response).await.is_err() {
+                    break;
+                }
+                let id = u16::from_be_bytes([response[0], response[1]]);
+                let waiting = reading.waiting.lock().unwrap_or_else(PoisonError::into_inner).responses.remove(&id);
+                if let Some(waiting) = waiting {
+                    let _ = waiting.send(response);
+                }
+            }
+            reading.close();
+        });
+        Pipeline { queries, state }
+    }
+
+    /// Sends `request_wire` under an ID of the connection's and returns
+    /// the response under the query's own ID again.
+    async fn exchange(&self, request_wire: &[u8]) -> Result<Vec<u8>, TransportError> {
+        let len = u16::try_from(request_wire.len()).map_err(|_| TransportError::Io("query too long".to_string()))?;
+        if len < 2 {
+            return Err(TransportError::Io("query too short".to_string()));
+        }
+        let closed = || TransportError::Io("connection closed".to_string());
+        let mut answer = self.state.wait().ok_or_else(closed)?;
+
+        let mut framed = len.to_be_bytes().to_vec();
+        framed.extend_from_slice(&answer.id.to_be_bytes());
+        framed.extend_from_slice(&request_wire[2..]);
+        self.queries.send(framed).map_err(|_| closed())?;
+        let mut response = (&mut answer.response).await.map_err(|_| closed())?;
+        response[..2].copy_from_slice(&request_wire[..2]);
+        Ok(response)
+    }
+}
+
+#[cfg(feature = "tls")]
+impl Shared for Pipeline {
+    fn share(&self) -> Option<Self> {
+        Some(self.clone())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.closed.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(feature = "tls")]
+impl PipelineState {
+    /// An ID for a query and where its response will arrive, `None` once
+    /// the connection is closed or has every ID in use.
+    fn wait(&self) -> Option<Answer<'_>> {
+        let mut waiting = self.waiting.lock().unwrap_or_else(PoisonError::into_inner);
+        if self.closed.load(Ordering::Relaxed) || waiting.responses.len() > usize::from(u16::MAX) {
+            return None;
+        }
+        let mut id = waiting.next_id;
+        while waiting.responses.contains_key(&id) {
+            id = id.wrapping_add(1);
+        }
+        waiting.next_id = id.wrapping_add(1);
+        let (sender, response) = oneshot::channel();
+        waiting.responses.insert(id, sender);
+        Some(Answer { state: self, id, response })
+    }
+
+    /// Marks the connection closed, failing the exchanges waiting on it.
+    fn close(&self) {
+        let mut waiting = self.waiting.lock().unwrap_or_else(PoisonError::into_inner);
+        self.closed.store(true, Ordering::Relaxed);
+        waiting.responses.clear();
+    }
+}
+
+/// Where the response to a query will arrive. Dropping it, answered or
+/// given up on, frees the query's ID.
+#[cfg(feature = "tls")]
+struct Answer<'a> {
+    state: &'a PipelineState,
+    id: u16,
+    response: oneshot::Receiver<Vec<u8>>,
+}
+
+#[cfg(feature = "tls")]
+impl Drop for Answer<'_> {
+    fn drop(&mut self) {
+        self.response.close();
+        let mut waiting = self.state.waiting.lock().unwrap_or_else(PoisonError::into_inner);
+        // Unless the ID went to another query once the reader took it.
+        if waiting.responses.get(&self.id).is_some_and(oneshot::Sender::is_closed) {
+            waiting.responses.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+#[async_trait]
+impl DnsTransport for TlsTransport {
+    async fn exchange(&self, request_wire: &[u8]) -> Result<Vec<u8>, TransportError> {
+        let (pipeline, kept) = self.pipeline().await?;
+        match pipeline.exchange(request_wire).await {
+            Err(_) if kept => {
+                self.pool.retrying();
+                self.pipeline().await?.0.exchange(request_wire).await
+            }
+            exchanged => exchanged,
+        }
+    }
+
+    fn kind(&self) -> Transport {
+        Transport::Tls
+    }
+
+    fn describe(&self) -> String {
+        self.server.to_string()
+    }
+
+    fn server(&self) -> Option<SocketAddr> {
+        Some(self.server.address)
+    }
+
+    async fn preconnect(&self) -> Option<Result<bool, TransportError>> {
+        let Ok(_permit) = self.pool.permits.acquire().await else {
+            return Some(Err(TransportError::Io("connection pool closed".to_string())));
+        };
+        if self.pool.has_idle() {
+            return Some(Ok(false));
+        }
+        Some(self.connect().await.map(|pipeline| {
+            self.pool.put(pipeline);
+            true
+        }))
+    }
+}
@@ -0,0 +1,418 @@
+//! Checks live records against a manifest of the values they should have,
+//! for CI that fails when DNS drifts from what is declared. Behind
+//! `selfie verify`.
+//!
+//! A manifest is a JSON object mapping names to objects mapping keys to
+//! expected values:
+//!
+//! ```json
+//! {
+//!   "alice@example.com": {
+//!     "bitcoin-payment": "bitcoin:bc1qexample",
+//!     "nostr": "prefix:npub1",
+//!     "pgp": "regex:^https://example\\.com/.+\\.asc$"
+//!   }
+//! }
+//! ```
+//!
+//! or, listing one record per entry, an array of tables in TOML
+//!
+//! ```toml
+//! [[records]]
+//! name = "alice@example.com"
+//! key = "bitcoin-payment"
+//! value = "bitcoin:bc1qexample"
+//! ```
+//!
+//! or the same `records` array in JSON. A name and key listed twice, and
+//! keys that are not record keys, are rejected before anything is looked
+//! up.
+//!
+//! A value is matched exactly unless it starts with `prefix:`, which the
+//! record must start with, or `regex:`, which must match somewhere in the
+//! record (see `Pattern` for the syntax). `exact:` spells out a value
+//! that itself starts with one of these.
+
+mod pattern;
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::error::SelfieError;
+use crate::key::RecordKey;
+use crate::options::LookupOptions;
+use crate::overrides::{is_comment, toml_key, toml_string};
+use crate::response::RecordEntry;
+use crate::SelfieRecordsSDK;
+
+pub use pattern::Pattern;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid manifest: {0}")]
+pub struct ManifestError(String);
+
+/// What a record's value must be.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Matcher {
+    Exact(String),
+    Prefix(String),
+    Regex(Pattern),
+}
+
+impl Matcher {
+    pub fn parse(expected: &str) -> Result<Self, String> {
+        if let Some(value) = expected.strip_prefix("exact:") {
+            Ok(Matcher::Exact(value.to_string()))
+        } else if let Some(prefix) = expected.strip_prefix("prefix:") {
+            Ok(Matcher::Prefix(prefix.to_string()))
+        } else if let Some(pattern) = expected.strip_prefix("regex:") {
+            Pattern::new(pattern).map(Matcher::Regex)
+        } else {
+            Ok(Matcher::Exact(expected.to_string()))
+        }
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            Matcher::Exact(expected) => value == expected,
+            Matcher::Prefix(prefix) => value.starts_with(prefix.as_str()),
+            Matcher::Regex(pattern) => pattern.is_match(value),
+        }
+    }
+}
+
+/// The manifest form, which `Matcher::parse` reads back.
+impl fmt::Display for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Matcher::Exact(value) if ["exact:", "prefix:", "regex:"].iter().any(|tag| value.starts_with(tag)) => {
+                write!(f, "exact:{}", value)
+            }
+            Matcher::Exact(value) => f.write_str(value),
+            Matcher::Prefix(prefix) => write!(f, "prefix:{}", prefix),
+            Matcher::Regex(pattern) => write!(f, "regex:{}", pattern),
+        }
+    }
+}
+
+/// The records each name should have, by name and then key.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Manifest {
+    pub names: BTreeMap<String, BTreeMap<String, Matcher>>,
+}
+
+impl Manifest {
+    /// Parses a JSON manifest, in either form.
+    pub fn parse(json: &str) -> Result<Self, ManifestError> {
+        let value: Value = serde_json::from_str(json).map_err(|e| ManifestError(e.to_string()))?;
+        let Value::Object(names) = value else {
+            return Err(ManifestError("expected an object of names".to_string()));
+        };
+        let mut manifest = Manifest::default();
+        if let Some(Value::Array(records)) = names.get("records").filter(|_| names.len() == 1) {
+            for (index, record) in (1..).zip(records) {
+                let field = |field: &str| match record.get(field) {
+                    Some(Value::String(value)) => Ok(value.as_str()),
+                    Some(_) => Err(ManifestError(format!("record {}: {} must be a string", index, field))),
+                    None => Err(ManifestError(format!("record {}: missing {}", index, field))),
+                };
+                let Value::Object(fields) = record else {
+                    return Err(ManifestError(format!("record {}: expected an object", index)));
+                };
+                if let Some(unknown) = fields.keys().find(|field| !RECORD_FIELDS.contains(&field.as_str())) {
+                    return Err(ManifestError(format!("record {}: unknown field {}", index, unknown)));
+                }
+                manifest.insert(field("name")?, field("key")?, field("value")?)?;
+            }
+            return Ok(manifest);
+        }
+        for (name, keys) in names {
+            let Value::Object(keys) = keys else {
+                return Err(ManifestError(format!("{}: expected an object of keys", name)));
+            };
+            for (key, expected) in keys {
+                let Value::String(expected) = expected else {
+                    return Err(ManifestError(format!("{} {}: expected a string", name, key)));
+                };
+                manifest.insert(&name, &key, &expected)?;
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Parses a TOML manifest of `[[records]]` tables, with string values
+    /// on one line.
+    pub fn parse_toml(text: &str) -> Result<Self, ManifestError> {
+        let mut manifest = Manifest::default();
+        // The record being read, with the line its table starts on.
+        let mut record: Option<(usize, BTreeMap<String, String>)> = None;
+        for (number, line) in (1..).zip(text.lines()) {
+            let parse_error = |reason: &str| ManifestError(format!("line {}: {}", number, reason));
+            let line = line.trim();
+            if is_comment(line) {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix("[[") {
+                let (name, rest) = toml_key(header.trim_start()).ok_or_else(|| parse_error("invalid table name"))?;
+                if !rest.trim_start().starts_with("]]") || !is_comment(&rest.trim_start()[2..]) {
+                    return Err(parse_error("expected ']]' after table name"));
+                }
+                if name != "records" {
+                    return Err(parse_error(&format!("unknown table {}, expected [[records]]", name)));
+                }
+                if let Some((line, fields)) = record.replace((number, BTreeMap::new())) {
+                    manifest.insert_table(line, &fields)?;
+                }
+                continue;
+            }
+            if line.starts_with('[') {
+                return Err(parse_error("expected [[records]]"));
+            }
+            let Some((_, fields)) = record.as_mut() else {
+                return Err(parse_error("field outside of a [[records]] table"));
+            };
+            let (field, rest) = toml_key(line).ok_or_else(|| parse_error("invalid field name"))?;
+            let rest = rest.trim_start().strip_prefix('=').ok_or_else(|| parse_error("expected '='"))?;
+            let (value, rest) = toml_string(rest.trim_start()).ok_or_else(|| parse_error("expected a string value"))?;
+            if !is_comment(rest) {
+                return Err(parse_error("unexpected text after value"));
+            }
+            if !RECORD_FIELDS.contains(&field.as_str()) {
+                return Err(parse_error(&format!("unknown field {}", field)));
+            }
+            if fields.insert(field.clone(), value).is_some() {
+                return Err(parse_error(&format!("{} is set twice", field)));
+            }
+        }
+        if let Some((line, fields)) = record {
+            manifest.insert_table(line, &fields)?;
+        }
+        Ok(manifest)
+    }
+
+    /// Adds the record of the `[[records]]` table starting on `line`.
+    fn insert_table(&mut self, line: usize, fields: &BTreeMap<String, String>) -> Result<(), ManifestError> {
+        let field = |field: &str| {
+            fields.get(field).map(String::as_str).ok_or_else(|| ManifestError(format!("line {}: record without {}", line, field)))
+        };
+        self.insert(field("name")?, field("key")?, field("value")?)
+    }
+
+    /// Adds the record `key` of `name`, which must not be listed yet.
+    fn insert(&mut self, name: &str, key: &str, expected: &str) -> Result<(), ManifestError> {
+        let invalid = |reason: &str| ManifestError(format!("{} {}: {}", name, key, reason));
+        let record_key = RecordKey::custom(key).map_err(|_| invalid("not a record key"))?;
+        let matcher = Matcher::parse(expected).map_err(|e| invalid(&e))?;
+        let keys = self.names.entry(name.to_string()).or_default();
+        if keys.insert(record_key.to_string(), matcher).is_some() {
+            return Err(invalid("listed twice"));
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Value {
+        let names = self.names.iter().map(|(name, keys)| {
+            let keys = keys.iter().map(|(key, matcher)| (key.clone(), Value::String(matcher.to_string())));
+            (name.clone(), Value::Object(keys.collect()))
+        });
+        Value::Object(names.collect())
+    }
+
+    /// The `[[records]]` form, which `parse_toml` reads back.
+    pub fn to_toml(&self) -> String {
+        let mut toml = String::new();
+        for (name, keys) in &self.names {
+            for (key, matcher) in keys {
+                if !toml.is_empty() {
+                    toml.push('\n');
+                }
+                let _ = write!(toml, "[[records]]\nname = {}\nkey = {}\nvalue = {}\n", quoted(name), quoted(key), quoted(&matcher.to_string()));
+            }
+        }
+        toml
+    }
+
+    /// Looks every record up with `resolve_matrix`, one batch per set of
+    /// keys so that no name is queried for keys it does not declare.
+    pub fn verify(&self, sdk: &SelfieRecordsSDK, options: &LookupOptions) -> Verification {
+        let mut batches: BTreeMap<Vec<&str>, Vec<&str>> = BTreeMap::new();
+        for (name, keys) in &self.names {
+            batches.entry(keys.keys().map(String::as_str).collect()).or_default().push(name);
+        }
+        let mut entries = Vec::new();
+        for (keys, names) in batches {
+            let matrix = sdk.resolve_matrix(&names, &keys, options);
+            for name in names {
+                for &key in &keys {
+                    let expected = self.names[name][key].clone();
+                    let result = matrix.get(name, key).expect("every cell of the matrix is resolved");
+                    let outcome = match result.entry() {
+                        RecordEntry::Found { value } if expected.matches(value) => Outcome::Match,
+                        RecordEntry::Found { value } => Outcome::Mismatch { actual: value.to_string() },
+                        RecordEntry::NotFound => Outcome::Missing,
+                        RecordEntry::Error(e) => Outcome::Failed(e.clone()),
+                    };
+                    entries.push(Entry { name: name.to_string(), key: key.to_string(), expected, outcome });
+                }
+            }
+        }
+        entries.sort_by(|a, b| (&a.name, &a.key).cmp(&(&b.name, &b.key)));
+        Verification { entries }
+    }
+
+    /// Writes what `verification` observed back: mismatched values are
+    /// replaced by the live ones and missing records are removed, with
+    /// names left without keys. Matches and failed lookups are kept as
+    /// they are. Returns the number of records changed.
+    pub fn update(&mut self, verification: &Verification) -> usize {
+        let mut changed = 0;
+        for entry in &verification.entries {
+            let Some(keys) = self.names.get_mut(&entry.name) else { continue };
+            match &entry.outcome {
+                Outcome::Mismatch { actual } => {
+                    keys.insert(entry.key.clone(), Matcher::Exact(actual.clone()));
+                }
+                Outcome::Missing => {
+                    keys.remove(&entry.key);
+                }
+                Outcome::Match | Outcome::Failed(_) => continue,
+            }
+            changed += 1;
+        }
+        self.names.retain(|_, keys| !keys.is_empty());
+        changed
+    }
+}
+
+/// The fields of a record in the `records` form.
+const RECORD_FIELDS: [&str; 3] = ["name", "key", "value"];
+
+/// `value` as a TOML basic string.
+fn quoted(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// How `SelfieRecordsSDK::verify_record_with` compares a record with the
+/// expected value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Comparison {
+    #[default]
+    Exact,
+    /// Ignores leading and trailing whitespace, which registrar UIs tend
+    /// to add.
+    Trimmed,
+    /// Ignores all whitespace.
+    IgnoreWhitespace,
+}
+
+impl Comparison {
+    pub fn matches(self, expected: &str, actual: &str) -> bool {
+        match self {
+            Comparison::Exact => expected == actual,
+            Comparison::Trimmed => expected.trim() == actual.trim(),
+            Comparison::IgnoreWhitespace => {
+                let compact = |value: &str| value.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+                compact(expected) == compact(actual)
+            }
+        }
+    }
+}
+
+/// Result of `SelfieRecordsSDK::verify_record`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationResult {
+    /// One of the records is the expected value.
+    Match,
+    /// The key has records, none of them the expected value.
+    MismatchFound { actual: Vec<String> },
+    /// The name has no such record, or does not exist at all.
+    NotFound,
+    Error(SelfieError),
+}
+
+/// How a live record compared with the manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Match,
+    Mismatch { actual: String },
+    Missing,
+    /// The lookup failed, so nothing is known about the record.
+    Failed(SelfieError),
+}
+
+/// One record of the manifest and how it compared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub key: String,
+    pub expected: Matcher,
+    pub outcome: Outcome,
+}
+
+/// Every record of a manifest compared, ordered by name and key.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Verification {
+    pub entries: Vec<Entry>,
+}
+
+/// What `SelfieRecordsSDK::verify_manifest` reports.
+pub type ManifestReport = Verification;
+
+impl Verification {
+    /// Whether every record matches.
+    pub fn passed(&self) -> bool {
+        self.entries.iter().all(|entry| entry.outcome == Outcome::Match)
+    }
+
+    /// Whether any lookup failed, leaving the outcome unknown.
+    pub fn failed(&self) -> bool {
+        self.entries.iter().any(|entry| matches!(entry.outcome, Outcome::Failed(_)))
+    }
+
+    /// The entries with their outcome, `match`, `mismatch` with the
+    /// `actual` value, `missing` or `error` with the error, and whether
+    /// every record matched.
+    pub fn to_json(&self) -> Value {
+        let entries = self.entries.iter().map(|entry| {
+            let mut fields = serde_json::Map::new();
+            fields.insert("name".to_string(), Value::String(entry.name.clone()));
+            fields.insert("key".to_string(), Value::String(entry.key.clone()));
+            fields.insert("expected".to_string(), Value::String(entry.expected.to_string()));
+            let outcome = match &entry.outcome {
+                Outcome::Match => "match",
+                Outcome::Mismatch { actual } => {
+                    fields.insert("actual".to_string(), Value::String(actual.clone()));
+                    "mismatch"
+                }
+                Outcome::Missing => "missing",
+                Outcome::Failed(e) => {
+                    fields.insert("error".to_string(), Value::String(e.to_string()));
+                    fields.insert("error_code".to_string(), Value::String(e.code().to_string()));
+                    "error"
+                }
+            };
+            fields.insert("outcome".to_string(), Value::String(outcome.to_string()));
+            Value::Object(fields)
+        });
+        serde_json::json!({ "passed": self.passed(), "entries": entries.collect::<Vec<_>>() })
+    }
+}
@@ -0,0 +1,182 @@
+//! The regular expressions of `regex:` matchers: literals, `.`, classes
+//! such as `[a-z0-9]` and `[^;]`, the escapes `\d \w \s` and their
+//! negations, the quantifiers `* + ?` and the anchors `^` and `$`. Groups,
+//! alternation and counted repetition are rejected rather than guessed at.
+//! Matching simulates the pattern's states side by side, so it takes time
+//! linear in the value whatever the pattern.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Atom {
+    Any,
+    Char(char),
+    Class { negated: bool, ranges: Vec<(char, char)> },
+}
+
+impl Atom {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Atom::Any => true,
+            Atom::Char(expected) => c == *expected,
+            Atom::Class { negated, ranges } => ranges.iter().any(|&(low, high)| low <= c && c <= high) != *negated,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Repeat {
+    Once,
+    Optional,
+    Any,
+}
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+
+/// A compiled `regex:` pattern, found anywhere in a value unless anchored.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    source: String,
+    items: Vec<(Atom, Repeat)>,
+    start: bool,
+    end: bool,
+}
+
+impl Pattern {
+    pub fn new(source: &str) -> Result<Self, String> {
+        let mut chars = source.chars().peekable();
+        let start = chars.next_if_eq(&'^').is_some();
+        let mut items: Vec<(Atom, Repeat)> = Vec::new();
+        let mut end = false;
+        while let Some(c) = chars.next() {
+            if end {
+                return Err("$ must end the pattern".to_string());
+            }
+            let atom = match c {
+                '$' => {
+                    end = true;
+                    continue;
+                }
+                '.' => Atom::Any,
+                '[' => class(&mut chars)?,
+                '\\' => escape(chars.next().ok_or("pattern ends with \\")?, false)?,
+                '(' | ')' | '|' | '{' | '}' => return Err(format!("{} is not supported", c)),
+                '*' | '+' | '?' | '^' => return Err(format!("{} has nothing to repeat or anchor", c)),
+                c => Atom::Char(c),
+            };
+            match chars.next_if(|c| matches!(c, '*' | '+' | '?')) {
+                Some('*') => items.push((atom, Repeat::Any)),
+                Some('+') => {
+                    items.push((atom.clone(), Repeat::Once));
+                    items.push((atom, Repeat::Any));
+                }
+                Some(_) => items.push((atom, Repeat::Optional)),
+                None => items.push((atom, Repeat::Once)),
+            }
+            if chars.peek().is_some_and(|c| matches!(c, '*' | '+' | '?')) {
+                return Err("quantifiers cannot be repeated".to_string());
+            }
+        }
+        Ok(Pattern { source: source.to_string(), items, start, end })
+    }
+
+    pub fn is_match(&self, value: &str) -> bool {
+        let mut states = vec![false; self.items.len() + 1];
+        self.enter(&mut states, 0);
+        for c in value.chars() {
+            if states[self.items.len()] && !self.end {
+                return true;
+            }
+            let mut next = vec![false; states.len()];
+            for (index, (atom, repeat)) in self.items.iter().enumerate() {
+                if states[index] && atom.matches(c) {
+                    self.enter(&mut next, if *repeat == Repeat::Any { index } else { index + 1 });
+                }
+            }
+            if !self.start {
+                self.enter(&mut next, 0);
+            }
+            states = next;
+        }
+        states[self.items.len()]
+    }
+
+    /// Marks `index` and the states reachable from it without a character.
+    fn enter(&self, states: &mut [bool], mut index: usize) {
+        while index < states.len() && !states[index] {
+            states[index] = true;
+            match self.items.get(index) {
+                Some((_, Repeat::Optional | Repeat::Any)) => index += 1,
+                _ => break,
+            }
+        }
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for Pattern {}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn escape(c: char, in_class: bool) -> Result<Atom, String> {
+    let class = |negated, ranges: &[(char, char)]| Atom::Class { negated, ranges: ranges.to_vec() };
+    Ok(match c {
+        'd' => class(false, DIGIT),
+        'D' => class(true, DIGIT),
+        'w' => class(false, WORD),
+        'W' => class(true, WORD),
+        's' => class(false, SPACE),
+        'S' => class(true, SPACE),
+        'n' => Atom::Char('\n'),
+        't' => Atom::Char('\t'),
+        c if c.is_ascii_punctuation() || (in_class && c == ' ') => Atom::Char(c),
+        c => return Err(format!("\\{} is not supported", c)),
+    })
+}
+
+fn class(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Result<Atom, String> {
+    let negated = chars.next_if_eq(&'^').is_some();
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let c = chars.next().ok_or("[ is not closed")?;
+        if c == ']' && !first {
+            return Ok(Atom::Class { negated, ranges });
+        }
+        first = false;
+        let low = match c {
+            '\\' => match escape(chars.next().ok_or("pattern ends with \\")?, true)? {
+                Atom::Char(c) => c,
+                Atom::Class { negated: false, ranges: escaped } => {
+                    ranges.extend(escaped);
+                    continue;
+                }
+                _ => return Err("negated escapes are not supported in classes".to_string()),
+            },
+            c => c,
+        };
+        let mut lookahead = chars.clone();
+        match (lookahead.next(), lookahead.next()) {
+            (Some('-'), Some(high)) if high != ']' => {
+                chars.next();
+                chars.next();
+                if high < low {
+                    return Err(format!("range {}-{} is reversed", low, high));
+                }
+                ranges.push((low, high));
+            }
+            _ => ranges.push((low, low)),
+        }
+    }
+}
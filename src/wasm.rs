@@ -0,0 +1,103 @@
+//! The JavaScript side of wasm32 builds, behind the `wasm` feature: HTTP
+//! requests go through `fetch` and timers through `setTimeout`, both
+//! looked up on the global object, so browsers, workers and Node.js all
+//! provide them.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use js_sys::{Array, Function, Promise, Uint8Array};
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Headers, Request, RequestInit, RequestRedirect, ResponseType};
+
+use crate::http::Response;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch_with_request(request: &Request) -> Promise;
+
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(callback: &Function, millis: i32);
+}
+
+/// A future holding JavaScript values, which are not `Send`, made so for
+/// the SDK's `Send` futures.
+pub(crate) struct Local<F>(F);
+
+// Without atomics a wasm32 module runs on one thread, so nothing can be
+// sent to another one.
+#[cfg(not(target_feature = "atomics"))]
+unsafe impl<F> Send for Local<F> {}
+
+impl<F: Future + Unpin> Future for Local<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
+
+/// Resolves after `duration`.
+pub(crate) fn sleep(duration: Duration) -> impl Future<Output = ()> + Send {
+    let millis = duration.as_millis().min(i32::MAX as u128) as i32;
+    let timer = Local(JsFuture::from(Promise::new(&mut |resolve, _| set_timeout(&resolve, millis))));
+    async move {
+        let _ = timer.await;
+    }
+}
+
+/// Sends a request to `url` and reads the whole response. Redirects are
+/// returned rather than followed; browsers hide where they lead, so there
+/// they fail the request.
+pub(crate) fn fetch(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: Option<(&str, &[u8])>,
+) -> impl Future<Output = Result<Response, String>> + Send {
+    let request = new_request(method, url, headers, body);
+    Local(Box::pin(async move {
+        let response = JsFuture::from(fetch_with_request(&request?)).await.map_err(describe)?;
+        let response: web_sys::Response = response.dyn_into().map_err(describe)?;
+        if response.type_() == ResponseType::Opaqueredirect {
+            return Err("redirected to a location fetch does not reveal".to_string());
+        }
+        let mut headers = Vec::new();
+        for entry in js_sys::try_iter(&response.headers()).map_err(describe)?.ok_or("unreadable response headers")? {
+            let entry = Array::from(&entry.map_err(describe)?);
+            let text = |i| entry.get(i).as_string().unwrap_or_default();
+            headers.push((text(0).to_ascii_lowercase(), text(1)));
+        }
+        let body = JsFuture::from(response.array_buffer().map_err(describe)?).await.map_err(describe)?;
+        Ok(Response { status: response.status(), headers, body: Uint8Array::new(&body).to_vec() })
+    }))
+}
+
+fn new_request(method: &str, url: &str, headers: &[(&str, &str)], body: Option<(&str, &[u8])>) -> Result<Request, String> {
+    let request_headers = Headers::new().map_err(describe)?;
+    for (name, value) in headers {
+        request_headers.set(name, value).map_err(describe)?;
+    }
+    let init = RequestInit::new();
+    init.set_method(method);
+    init.set_redirect(RequestRedirect::Manual);
+    if let Some((content_type, body)) = body {
+        request_headers.set("Content-Type", content_type).map_err(describe)?;
+        init.set_body(&Uint8Array::from(body));
+    }
+    init.set_headers(&request_headers);
+    Request::new_with_str_and_init(url, &init).map_err(describe)
+}
+
+/// The message of a JavaScript exception or rejection.
+fn describe(e: JsValue) -> String {
+    match e.dyn_ref::<js_sys::Error>() {
+        Some(error) => error.message().into(),
+        None => e.as_string().unwrap_or_else(|| format!("{:?}", e)),
+    }
+}
@@ -0,0 +1,414 @@
+//! Change detection for polling a name's records, the pollers behind
+//! `SelfieRecordsSDK::watch` and `watch_async`, and delivery of the
+//! resulting events.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+use crate::error::SelfieError;
+use crate::options::LookupOptions;
+use crate::response::RecordsResponse;
+use crate::SelfieRecordsSDK;
+
+/// The shortest interval a watcher polls at; shorter ones are raised to it.
+pub const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A record value that differs from the previous poll. `None` means the
+/// record did not exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeEvent {
+    pub name: String,
+    pub key: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    /// When the old value was last seen.
+    pub previous_observed_at: SystemTime,
+    pub observed_at: SystemTime,
+    /// The resolver the lookups went through, e.g. `8.8.8.8` or `system`.
+    pub resolver: String,
+}
+
+impl ChangeEvent {
+    /// The event as a JSON object, timestamps in seconds since the epoch.
+    pub fn to_json(&self) -> serde_json::Value {
+        let seconds = |time: SystemTime| time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        serde_json::json!({
+            "name": self.name,
+            "key": self.key,
+            "old_value": self.old_value,
+            "new_value": self.new_value,
+            "previous_observed_at": seconds(self.previous_observed_at),
+            "observed_at": seconds(self.observed_at),
+            "resolver": self.resolver,
+        })
+    }
+}
+
+/// Compares successive lookups of one name. The first lookup only sets the
+/// baseline. Failed lookups other than a missing record say nothing about
+/// the value and are ignored rather than reported as removals, unless
+/// `errors_as_removed` is set.
+#[derive(Debug, Clone)]
+pub struct ChangeDetector {
+    name: String,
+    resolver: String,
+    errors_as_removed: bool,
+    last: HashMap<String, (Option<String>, SystemTime)>,
+}
+
+impl ChangeDetector {
+    pub fn new(name: &str, resolver: &str) -> Self {
+        ChangeDetector { name: name.to_string(), resolver: resolver.to_string(), errors_as_removed: false, last: HashMap::new() }
+    }
+
+    /// Treats a failed lookup like a missing record.
+    pub fn errors_as_removed(mut self, removed: bool) -> Self {
+        self.errors_as_removed = removed;
+        self
+    }
+
+    /// Records the lookup made at `now` and returns the changes since the last one.
+    pub fn observe(&mut self, response: &RecordsResponse, now: SystemTime) -> Vec<ChangeEvent> {
+        let mut events = Vec::new();
+        for (key, result) in response.iter() {
+            let value = match (&result.value, &result.error) {
+                (Some(value), _) => Some(value.clone()),
+                (None, Some(SelfieError::NoRecords | SelfieError::NxDomain)) => None,
+                _ if self.errors_as_removed => None,
+                _ => continue,
+            };
+            match self.last.get(key) {
+                Some((old_value, seen_at)) if *old_value != value => events.push(ChangeEvent {
+                    name: self.name.clone(),
+                    key: key.to_string(),
+                    old_value: old_value.clone(),
+                    new_value: value.clone(),
+                    previous_observed_at: *seen_at,
+                    observed_at: now,
+                    resolver: self.resolver.clone(),
+                }),
+                _ => {}
+            }
+            self.last.insert(key.to_string(), (value, now));
+        }
+        events.sort_by(|a, b| a.key.cmp(&b.key));
+        events
+    }
+}
+
+/// How `SelfieRecordsSDK::watch_with` and `watch_async_with` poll.
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    interval: Duration,
+    errors_as_removed: bool,
+    lookup: Option<LookupOptions>,
+    bypass_cache: bool,
+}
+
+impl WatchOptions {
+    /// Polls every `interval`, at least `MIN_INTERVAL`.
+    pub fn new(interval: Duration) -> Self {
+        WatchOptions { interval: interval.max(MIN_INTERVAL), errors_as_removed: false, lookup: None, bypass_cache: false }
+    }
+
+    /// See `ChangeDetector::errors_as_removed`.
+    pub fn errors_as_removed(mut self, removed: bool) -> Self {
+        self.errors_as_removed = removed;
+        self
+    }
+
+    /// Options of each poll's lookups, instead of the SDK's.
+    pub fn lookup_options(mut self, options: LookupOptions) -> Self {
+        self.lookup = Some(options);
+        self
+    }
+
+    /// Queries DNS on every poll instead of seeing cached answers until
+    /// they expire; see `LookupOptions::bypass_cache`.
+    pub fn bypass_cache(mut self, bypass: bool) -> Self {
+        self.bypass_cache = bypass;
+        self
+    }
+
+    pub fn get_interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+/// What a poller needs, owned so that it can outlive the call starting it.
+struct Poll {
+    sdk: Arc<SelfieRecordsSDK>,
+    name: String,
+    filters: Option<Vec<String>>,
+    interval: Duration,
+    lookup: LookupOptions,
+    detector: ChangeDetector,
+}
+
+impl Poll {
+    fn new(sdk: &Arc<SelfieRecordsSDK>, name: &str, filters: Option<Vec<&str>>, options: &WatchOptions) -> Self {
+        let lookup = options.lookup.clone().unwrap_or_else(|| sdk.defaults.clone());
+        Poll {
+            sdk: sdk.clone(),
+            name: name.to_string(),
+            filters: filters.map(|filters| filters.iter().map(|key| key.to_string()).collect()),
+            interval: options.interval,
+            lookup: match options.bypass_cache {
+                true => lookup.bypass_cache(true),
+                false => lookup,
+            },
+            detector: ChangeDetector::new(name, sdk.resolver_label).errors_as_removed(options.errors_as_removed),
+        }
+    }
+
+    fn filters(&self) -> Option<Vec<&str>> {
+        self.filters.as_ref().map(|filters| filters.iter().map(String::as_str).collect())
+    }
+}
+
+/// A poller running on a thread of its own, calling back with every
+/// change. It stops when `stop` is called or the handle is dropped.
+pub struct RecordWatcher {
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RecordWatcher {
+    pub(crate) fn spawn(
+        sdk: &Arc<SelfieRecordsSDK>,
+        name: &str,
+        filters: Option<Vec<&str>>,
+        options: &WatchOptions,
+        mut on_change: impl FnMut(ChangeEvent) + Send + 'static,
+    ) -> Self {
+        let mut poll = Poll::new(sdk, name, filters, options);
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread = std::thread::spawn({
+            let stopped = stopped.clone();
+            move || loop {
+                let response = poll.sdk.get_records_response(&poll.name, poll.filters(), None, &poll.lookup);
+                for event in poll.detector.observe(&response, crate::time::now()) {
+                    on_change(event);
+                }
+                let (flag, wake) = &*stopped;
+                let flag = flag.lock().unwrap_or_else(PoisonError::into_inner);
+                let (flag, _) = wake.wait_timeout_while(flag, poll.interval, |stopped| !*stopped).unwrap_or_else(PoisonError::into_inner);
+                if *flag {
+                    return;
+                }
+            }
+        });
+        RecordWatcher { stopped, thread: Some(thread) }
+    }
+
+    /// Stops polling, waiting for a lookup under way to finish.
+    pub fn stop(mut self) {
+        self.signal();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    fn signal(&self) {
+        let (flag, wake) = &*self.stopped;
+        *flag.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        wake.notify_all();
+    }
+}
+
+impl Drop for RecordWatcher {
+    fn drop(&mut self) {
+        self.signal();
+    }
+}
+
+impl std::fmt::Debug for RecordWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecordWatcher").finish_non_exhaustive()
+    }
+}
+
+/// A poller running as a task on the caller's tokio runtime, yielding
+/// changes from `next`. It stops when `stop` is called or it is dropped.
+#[derive(Debug)]
+pub struct ChangeStream {
+    events: mpsc::UnboundedReceiver<ChangeEvent>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl ChangeStream {
+    pub(crate) fn spawn(sdk: &Arc<SelfieRecordsSDK>, name: &str, filters: Option<Vec<&str>>, options: &WatchOptions) -> Self {
+        let mut poll = Poll::new(sdk, name, filters, options);
+        let (sender, events) = mpsc::unbounded_channel();
+        let task = tokio::spawn(async move {
+            loop {
+                let response = poll.sdk.get_records_response_async(&poll.name, poll.filters(), None, &poll.lookup).await;
+                for event in poll.detector.observe(&response, crate::time::now()) {
+                    if sender.send(event).is_err() {
+                        return;
+                    }
+                }
+                crate::time::sleep(poll.interval).await;
+            }
+        });
+        ChangeStream { events, task }
+    }
+
+    /// The next change, waiting for it. `None` once stopped.
+    pub async fn next(&mut self) -> Option<ChangeEvent> {
+        self.events.recv().await
+    }
+
+    pub fn stop(&mut self) {
+        self.task.abort();
+        self.events.close();
+    }
+}
+
+impl Drop for ChangeStream {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Delivering change event failed: {0}")]
+pub struct DeliveryError(pub String);
+
+/// Destination for change events.
+#[async_trait]
+pub trait ChangeSink: Send + Sync {
+    async fn deliver(&self, event: &ChangeEvent) -> Result<(), DeliveryError>;
+}
+
+#[cfg(feature = "webhook")]
+pub use webhook::{WebhookSink, SIGNATURE_HEADER};
+
+#[cfg(feature = "webhook")]
+mod webhook {
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use data_encoding::HEXLOWER;
+    use log::{debug, error};
+    use ring::hmac;
+
+    use super::{ChangeEvent, ChangeSink, DeliveryError};
+    use crate::http::Endpoint;
+    use crate::options::Backoff;
+
+    /// Header carrying `sha256=<hex HMAC-SHA256 of the body>`.
+    pub const SIGNATURE_HEADER: &str = "X-Selfie-Signature";
+
+    const DEFAULT_RETRIES: u32 = 3;
+    const DEFAULT_BACKOFF: Duration = Duration::from_millis(500);
+
+    /// Delivers events as JSON POSTs, retrying server errors with a jittered
+    /// backoff. Events that still fail are appended to the dead-letter
+    /// file, one JSON object per line, when one is configured.
+    #[derive(Debug, Clone)]
+    pub struct WebhookSink {
+        url: String,
+        endpoint: Endpoint,
+        key: Option<hmac::Key>,
+        retries: u32,
+        backoff: Duration,
+        dead_letter: Option<PathBuf>,
+    }
+
+    impl WebhookSink {
+        pub fn new(url: &str) -> Result<Self, DeliveryError> {
+            let endpoint = Endpoint::parse(url, "/")
+                .map_err(|reason| DeliveryError(format!("invalid webhook URL {}: {}", url, reason)))?;
+            Ok(WebhookSink {
+                url: url.to_string(),
+                endpoint,
+                key: None,
+                retries: DEFAULT_RETRIES,
+                backoff: DEFAULT_BACKOFF,
+                dead_letter: None,
+            })
+        }
+
+        /// Signs every body with HMAC-SHA256 under `secret`.
+        pub fn secret(mut self, secret: &[u8]) -> Self {
+            self.key = Some(hmac::Key::new(hmac::HMAC_SHA256, secret));
+            self
+        }
+
+        /// Retries after a 5xx status or a connection failure, `retries`
+        /// times at most.
+        pub fn retries(mut self, retries: u32) -> Self {
+            self.retries = retries;
+            self
+        }
+
+        /// Base delay between retries.
+        pub fn backoff(mut self, base: Duration) -> Self {
+            self.backoff = base;
+            self
+        }
+
+        pub fn dead_letter(mut self, path: impl Into<PathBuf>) -> Self {
+            self.dead_letter = Some(path.into());
+            self
+        }
+
+        async fn post(&self, body: &[u8]) -> Result<(), (bool, String)> {
+            let signature = self.key.as_ref().map(|key| format!("sha256={}", HEXLOWER.encode(hmac::sign(key, body).as_ref())));
+            let headers: Vec<(&str, &str)> = signature.iter().map(|value| (SIGNATURE_HEADER, value.as_str())).collect();
+            match self.endpoint.post("application/json", &headers, body).await {
+                Ok(response) if (200..300).contains(&response.status) => Ok(()),
+                Ok(response) => Err((response.status >= 500, format!("HTTP status {}", response.status))),
+                Err(e) => Err((true, e.to_string())),
+            }
+        }
+
+        fn write_dead_letter(&self, event: &ChangeEvent, reason: &str) {
+            let Some(path) = &self.dead_letter else {
+                return;
+            };
+            let line = serde_json::json!({ "webhook": self.url, "error": reason, "event": event.to_json() });
+            let written = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{}", line));
+            if let Err(e) = written {
+                error!("Cannot write dead letter to {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ChangeSink for WebhookSink {
+        async fn deliver(&self, event: &ChangeEvent) -> Result<(), DeliveryError> {
+            let body = event.to_json().to_string();
+            let backoff = Backoff::new(self.backoff);
+            let mut attempt = 0;
+            loop {
+                attempt += 1;
+                let reason = match self.post(body.as_bytes()).await {
+                    Ok(()) => return Ok(()),
+                    Err((true, reason)) if attempt <= self.retries => reason,
+                    Err((_, reason)) => {
+                        let reason = format!("{} after {} attempt(s)", reason, attempt);
+                        self.write_dead_letter(event, &reason);
+                        return Err(DeliveryError(reason));
+                    }
+                };
+                let delay = backoff.jittered(attempt);
+                debug!("Retrying webhook {} in {:?} after attempt {} failed: {}", self.url, delay, attempt, reason);
+                crate::time::sleep(delay).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,405 @@
+//! Direct DNS exchanges for the paths where the SDK needs the full message
+//! rather than what the recursive resolver chooses to hand back.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use trust_dns_proto::op::{Edns, Message, MessageType, OpCode, Query, ResponseCode};
+use trust_dns_proto::rr::{Name, RData, RecordType};
+
+use crate::encoding::{self, RawRecord};
+use crate::error::SelfieError;
+use crate::resolver::{Readiness, TxtResolver};
+use crate::time::Instant;
+use crate::transport::{DnsTransport, TcpTransport, TransportError, UdpTransport};
+
+pub(crate) const MAX_UDP_PAYLOAD: u16 = 4096;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+/// CNAME hops followed before a lookup gives up.
+pub(crate) const DEFAULT_MAX_CNAME_CHAIN: usize = 8;
+
+/// How an answer reached the SDK.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transport {
+    Udp,
+    Tcp,
+    Doh,
+    /// DNS over TLS.
+    Tls,
+    /// A resolver library or custom backend that does not say.
+    #[default]
+    Resolver,
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transport::Udp => f.write_str("udp"),
+            Transport::Tcp => f.write_str("tcp"),
+            Transport::Doh => f.write_str("doh"),
+            Transport::Tls => f.write_str("tls"),
+            Transport::Resolver => f.write_str("resolver"),
+        }
+    }
+}
+
+/// The server an answer came from and how it was reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerInfo {
+    pub address: SocketAddr,
+    pub transport: Transport,
+}
+
+impl fmt::Display for ServerInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.transport)
+    }
+}
+
+/// What is known about the message an answer came from. Size, truncation
+/// and EDNS details are only available where the SDK handles the raw
+/// message itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WireInfo {
+    /// Size in bytes of the message the answer was read from.
+    pub response_size: Option<usize>,
+    /// Whether a response had the TC bit set, including a UDP response that
+    /// was then retried over TCP.
+    pub truncated: bool,
+    pub transport_used: Transport,
+    /// UDP payload size the server advertised in its EDNS OPT record.
+    pub edns_udp_size: Option<u16>,
+    /// Smallest TTL of the answer records, or for a response without any,
+    /// the negative caching TTL of its SOA record (RFC 2308).
+    pub ttl: Option<u32>,
+    /// The response's RCODE, e.g. 0 for NOERROR or 3 for NXDOMAIN.
+    pub response_code: Option<u16>,
+    /// Address of the server that sent the message, when the SDK picked
+    /// the server itself.
+    pub server: Option<SocketAddr>,
+}
+
+impl WireInfo {
+    pub(crate) fn from_message(message: &Message, size: usize, transport_used: Transport) -> Self {
+        WireInfo {
+            response_size: Some(size),
+            truncated: message.truncated(),
+            transport_used,
+            edns_udp_size: message.edns().map(|edns| edns.max_payload()),
+            ttl: message.answers().iter().map(|record| record.ttl()).min().or_else(|| negative_ttl(message)),
+            response_code: Some(message.response_code().into()),
+            server: None,
+        }
+    }
+
+    /// `server` with the transport it was reached over.
+    pub fn answered_by(&self) -> Option<ServerInfo> {
+        self.server.map(|address| ServerInfo { address, transport: self.transport_used })
+    }
+}
+
+/// The lesser of the SOA record's own TTL and its MINIMUM field.
+fn negative_ttl(message: &Message) -> Option<u32> {
+    message.name_servers().iter().find_map(|record| match record.rdata() {
+        RData::SOA(soa) => Some(record.ttl().min(soa.minimum())),
+        _ => None,
+    })
+}
+
+impl fmt::Display for WireInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "transport={} truncated={}", self.transport_used, self.truncated)?;
+        if let Some(size) = self.response_size {
+            write!(f, " response_size={}", size)?;
+        }
+        if let Some(size) = self.edns_udp_size {
+            write!(f, " edns_udp_size={}", size)?;
+        }
+        Ok(())
+    }
+}
+
+/// The transport queries go out on, and the one asked again when an answer
+/// comes back truncated.
+#[derive(Clone)]
+pub(crate) struct Transports {
+    primary: Arc<dyn DnsTransport>,
+    fallback: Option<Arc<dyn DnsTransport>>,
+}
+
+impl Transports {
+    /// UDP to `server`, then TCP for truncated answers.
+    pub(crate) fn direct(server: SocketAddr) -> Self {
+        Transports { primary: Arc::new(UdpTransport::new(server)), fallback: Some(Arc::new(TcpTransport::new(server))) }
+    }
+
+    pub(crate) fn custom(transport: Arc<dyn DnsTransport>) -> Self {
+        Transports { primary: transport, fallback: None }
+    }
+
+    fn describe(&self) -> String {
+        self.primary.describe()
+    }
+}
+
+impl fmt::Debug for Transports {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.describe())
+    }
+}
+
+/// A `TxtResolver` querying one server directly over UDP, falling back to
+/// TCP for truncated answers, or over a `DnsTransport`.
+#[derive(Debug, Clone)]
+pub struct DirectResolver {
+    transports: Transports,
+    timeout: Duration,
+    max_cname_chain: usize,
+}
+
+impl DirectResolver {
+    pub fn new(server: SocketAddr) -> Self {
+        Self::with_transports(Transports::direct(server))
+    }
+
+    /// Sends queries over `transport`. Truncated answers are used as they
+    /// are unless `truncation_fallback` names a transport to ask again.
+    pub fn with_transport(transport: Arc<dyn DnsTransport>) -> Self {
+        Self::with_transports(Transports::custom(transport))
+    }
+
+    fn with_transports(transports: Transports) -> Self {
+        DirectResolver { transports, timeout: DEFAULT_TIMEOUT, max_cname_chain: DEFAULT_MAX_CNAME_CHAIN }
+    }
+
+    /// Repeats queries whose answer is truncated over `transport`, as TCP
+    /// does for UDP.
+    pub fn truncation_fallback(mut self, transport: Arc<dyn DnsTransport>) -> Self {
+        self.transports.fallback = Some(transport);
+        self
+    }
+
+    /// CNAME hops followed, within an answer and by re-querying the target
+    /// of an alias the server did not resolve, before the lookup fails with
+    /// `SelfieError::CnameChainTooLong`. Defaults to 8.
+    pub fn max_cname_chain(mut self, max: usize) -> Self {
+        self.max_cname_chain = max;
+        self
+    }
+
+    /// Timeout for each exchange, such as the UDP and the TCP one.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[async_trait]
+impl TxtResolver for DirectResolver {
+    async fn txt_lookup(&self, name: &str) -> Result<Vec<String>, SelfieError> {
+        Ok(self.txt_lookup_with_info(name).await?.0)
+    }
+
+    fn describe(&self) -> String {
+        self.transports.describe()
+    }
+
+    async fn txt_lookup_with_info(&self, name: &str) -> Result<(Vec<String>, WireInfo), SelfieError> {
+        let (records, info) = self.txt_lookup_raw(name).await?;
+        Ok((encoding::lossy(&records), info))
+    }
+
+    async fn txt_lookup_raw(&self, name: &str) -> Result<(Vec<RawRecord>, WireInfo), SelfieError> {
+        let mut qname = Name::from_utf8(name).map_err(|e| transport_error(self.describe(), e))?;
+        qname.set_fqdn(true);
+        // An authoritative server hands back the alias alone when the target
+        // is outside its zone, so the chain is followed one query at a time.
+        let mut chain = CnameChain::new(qname, self.max_cname_chain);
+        loop {
+            let (response, info) = exchange(&self.transports, chain.last(), RecordType::TXT, false, self.timeout).await?;
+            if let Some(answers) = txt_answers(&response, name, &mut chain)? {
+                return Ok((answers, info));
+            }
+        }
+    }
+
+    /// Opens a connection over a transport that keeps them, such as
+    /// `TlsTransport`, and sends `probe` over it when given.
+    async fn warm_up(&self, probe: Option<&str>) -> Option<Readiness> {
+        let started = Instant::now();
+        let connected = match crate::time::timeout(self.timeout, self.transports.primary.preconnect()).await {
+            Ok(connected) => connected?,
+            Err(_) => Err(TransportError::Timeout),
+        };
+        let (connected, mut error) = match connected {
+            Ok(connected) => (connected, None),
+            Err(TransportError::Certificate { reason, .. }) => (false, Some(SelfieError::TlsCertificate { server: self.describe(), reason })),
+            Err(e) => (false, Some(transport_error(self.describe(), e))),
+        };
+        if let (None, Some(probe)) = (&error, probe) {
+            error = self.txt_lookup_raw(probe).await.err();
+        }
+        Some(Readiness { endpoint: self.describe(), connected, elapsed: started.elapsed(), error })
+    }
+}
+
+/// Sends one query for `name`/`rtype` over `transports`, repeating it over
+/// the fallback when the answer comes back truncated.
+pub(crate) async fn query(
+    transports: &Transports,
+    name: &Name,
+    rtype: RecordType,
+    dnssec_ok: bool,
+    timeout: Duration,
+) -> Result<Message, SelfieError> {
+    Ok(exchange(transports, name, rtype, dnssec_ok, timeout).await?.0)
+}
+
+async fn exchange(
+    transports: &Transports,
+    name: &Name,
+    rtype: RecordType,
+    dnssec_ok: bool,
+    timeout: Duration,
+) -> Result<(Message, WireInfo), SelfieError> {
+    let request = build_query(name, rtype, dnssec_ok);
+    let bytes = request.to_vec().map_err(|e| transport_error(transports.describe(), e))?;
+
+    let primary = transports.primary.as_ref();
+    let (response, size) = exchange_over(primary, &request, &bytes, timeout).await?;
+    match &transports.fallback {
+        Some(fallback) if response.truncated() => {
+            let (response, size) = exchange_over(fallback.as_ref(), &request, &bytes, timeout).await?;
+            let info = WireInfo {
+                truncated: true,
+                server: fallback.server(),
+                ..WireInfo::from_message(&response, size, fallback.kind())
+            };
+            Ok((response, info))
+        }
+        _ => {
+            let info = WireInfo { server: primary.server(), ..WireInfo::from_message(&response, size, primary.kind()) };
+            Ok((response, info))
+        }
+    }
+}
+
+/// Sends `bytes`, the wire form of `request`, and parses the response,
+/// which must answer that request.
+async fn exchange_over(
+    transport: &dyn DnsTransport,
+    request: &Message,
+    bytes: &[u8],
+    timeout: Duration,
+) -> Result<(Message, usize), SelfieError> {
+    let error = |e: &dyn fmt::Display| transport_error(transport.describe(), e);
+    let response = match crate::time::timeout(timeout, transport.exchange(bytes)).await {
+        Ok(response) => response,
+        Err(_) => Err(TransportError::Timeout),
+    }
+    .map_err(|e| match e {
+        TransportError::ProxyUnreachable { proxy, reason } => SelfieError::ProxyUnreachable { proxy: proxy.to_string(), reason },
+        TransportError::Certificate { reason, .. } => SelfieError::TlsCertificate { server: transport.describe(), reason },
+        e => error(&e),
+    })?;
+    let message = Message::from_vec(&response).map_err(|e| error(&e))?;
+    if message.id() != request.id() || message.message_type() != MessageType::Response {
+        return Err(error(&"response does not answer the query"));
+    }
+    Ok((message, response.len()))
+}
+
+pub(crate) fn build_query(name: &Name, rtype: RecordType, dnssec_ok: bool) -> Message {
+    let mut message = Message::new();
+    message
+        .set_id(rand::random())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(true)
+        .add_query(Query::query(name.clone(), rtype));
+    let mut edns = Edns::new();
+    edns.set_max_payload(MAX_UDP_PAYLOAD).set_dnssec_ok(dnssec_ok);
+    message.set_edns(edns);
+    message
+}
+
+/// The owner names a lookup has passed through, starting with the name
+/// queried.
+pub(crate) struct CnameChain {
+    names: Vec<Name>,
+    max: usize,
+}
+
+impl CnameChain {
+    pub(crate) fn new(qname: Name, max: usize) -> Self {
+        CnameChain { names: vec![qname], max }
+    }
+
+    pub(crate) fn last(&self) -> &Name {
+        self.names.last().expect("chain starts with the query name")
+    }
+
+    pub(crate) fn push(&mut self, target: Name) -> Result<(), SelfieError> {
+        let looped = self.names.contains(&target);
+        self.names.push(target);
+        if looped {
+            return Err(SelfieError::CnameLoop { chain: self.owners() });
+        }
+        if self.names.len() - 1 > self.max {
+            return Err(SelfieError::CnameChainTooLong { chain: self.owners() });
+        }
+        Ok(())
+    }
+
+    fn owners(&self) -> Vec<String> {
+        self.names.iter().map(|name| name.to_string().trim_end_matches('.').to_string()).collect()
+    }
+}
+
+/// Reads the TXT records of a response, following any CNAMEs in the answer from the end of `chain`. NXDOMAIN
+/// counts as an empty answer. `None` means the answer stops at an alias
+/// without resolving it, and the new end of `chain` is the name to ask for.
+pub(crate) fn txt_answers(
+    response: &Message,
+    name: &str,
+    chain: &mut CnameChain,
+) -> Result<Option<Vec<RawRecord>>, SelfieError> {
+    match response.response_code() {
+        ResponseCode::NoError | ResponseCode::NXDomain => {}
+        code => {
+            return Err(SelfieError::Resolver(format!(
+                "Error resolving TXT record for {}: server answered {}",
+                name, code
+            )))
+        }
+    }
+
+    let mut aliased = false;
+    while let Some(target) = response.answers().iter().find_map(|record| match record.rdata() {
+        RData::CNAME(target) if record.name() == chain.last() => Some(target.clone()),
+        _ => None,
+    }) {
+        chain.push(target)?;
+        aliased = true;
+    }
+    let answers: Vec<RawRecord> = response
+        .answers()
+        .iter()
+        .filter(|record| record.name() == chain.last())
+        .filter_map(|record| match record.rdata() {
+            RData::TXT(txt) => Some(RawRecord::new(txt.txt_data())),
+            _ => None,
+        })
+        .collect();
+    if answers.is_empty() && aliased && response.response_code() == ResponseCode::NoError {
+        return Ok(None);
+    }
+    Ok(Some(answers))
+}
+
+fn transport_error(server: impl fmt::Display, e: impl fmt::Display) -> SelfieError {
+    SelfieError::Resolver(format!("Error querying {}: {}", server, e))
+}
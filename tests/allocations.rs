@@ -0,0 +1,66 @@
+//! Allocation budget of the lookup path. Kept in its own test binary since
+//! it installs a counting global allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::Arc;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{LookupOptions, SelfieRecordsSDK};
+
+/// Counts allocations made on the current thread, which is the one the SDK
+/// runs its lookups on.
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn lookups_stay_within_the_allocation_budget() {
+    let resolver = MockTxtResolver::new()
+        .with_record("alice.user._bitcoin-payment.example.com", &["bitcoin:bc1qalice"])
+        .with_record("alice.user._nostr.example.com", &["npub1alice"])
+        .with_record("alice.user._pgp.example.com", &[]);
+    let sdk = SelfieRecordsSDK::builder().resolver(Arc::new(resolver)).build().unwrap();
+    let options = LookupOptions::new().attempts(1);
+    let keys = || Some(vec!["bitcoin-payment", "nostr", "pgp"]);
+    // Warm up lazily initialised state.
+    sdk.get_records_response("alice@example.com", keys(), None, &options);
+
+    let typed = allocations(|| {
+        sdk.get_records_response("alice@example.com", keys(), None, &options);
+    });
+    let map = allocations(|| {
+        sdk.get_records_with("alice@example.com", keys(), None, &options).to_map();
+    });
+    // Generous budgets, about twice the current counts, meant to catch
+    // regressions such as per-key copies of the whole answer set.
+    assert!(typed <= 70, "typed lookup of 3 keys made {} allocations", typed);
+    assert!(map <= 150, "map lookup of 3 keys made {} allocations", map);
+}
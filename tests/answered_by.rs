@@ -0,0 +1,106 @@
+mod common;
+
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::Arc;
+
+use common::server::dns_server;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{DirectResolver, KeyResult, LookupOptions, SelfieRecordsSDK, ServerInfo, Transport};
+
+fn lookup(sdk: &SelfieRecordsSDK, name: &str, key: &str, dns_server: Option<&str>) -> KeyResult {
+    let options = LookupOptions::new().attempts(1);
+    sdk.get_records_response(name, Some(vec![key]), dns_server, &options).get(key).unwrap().clone()
+}
+
+fn udp(address: SocketAddr) -> Option<ServerInfo> {
+    Some(ServerInfo { address, transport: Transport::Udp })
+}
+
+#[test]
+fn each_key_names_the_server_of_its_route() {
+    let (default, corp) = (dns_server("bitcoin:bc1qdefault", false), dns_server("https://corp.example.com/key.asc", false));
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(Arc::new(DirectResolver::new(default)))
+        .route_key("pgp", corp)
+        .build()
+        .unwrap();
+
+    let pgp = lookup(&sdk, "routed.example.com", "pgp", None);
+    assert_eq!(pgp.value.as_deref(), Some("https://corp.example.com/key.asc"));
+    assert_eq!(pgp.answered_by, udp(corp));
+    let payment = lookup(&sdk, "routed.example.com", "bitcoin-payment", None);
+    assert_eq!(payment.value.as_deref(), Some("bitcoin:bc1qdefault"));
+    assert_eq!(payment.answered_by, udp(default));
+}
+
+#[test]
+fn groups_name_the_member_that_answered() {
+    // Nothing listens on this port any more, so queries to it are refused.
+    let dead = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let (first, second) = (dns_server("npub1first", false), dns_server("npub1second", false));
+    let failing_over = format!("{}, {}", dead, second);
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(Arc::new(MockTxtResolver::new()))
+        .route_key("nostr", failing_over.as_str())
+        .route_key("pgp", [first, second].as_slice())
+        .build()
+        .unwrap();
+
+    let nostr = lookup(&sdk, "group.example.com", "nostr", None);
+    assert_eq!(nostr.value.as_deref(), Some("npub1second"));
+    assert_eq!(nostr.answered_by, udp(second));
+    assert_eq!(nostr.resolver, Some(format!("{},{}", dead, second)));
+
+    let pgp = lookup(&sdk, "group.example.com", "pgp", None);
+    assert_eq!(pgp.value.as_deref(), Some("npub1first"));
+    assert_eq!(pgp.answered_by, udp(first));
+}
+
+#[test]
+fn truncated_answers_name_the_tcp_server() {
+    let server = dns_server("bitcoin:bc1qtruncated", true);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DirectResolver::new(server)));
+
+    let payment = lookup(&sdk, "truncated.example.com", "bitcoin-payment", None);
+    assert_eq!(payment.answered_by, Some(ServerInfo { address: server, transport: Transport::Tcp }));
+    assert_eq!(payment.answered_by.unwrap().to_string(), format!("{}/tcp", server));
+}
+
+#[test]
+fn the_fallback_resolver_is_named_when_it_answers() {
+    let default = dns_server("bitcoin:bc1qfallback", false);
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(Arc::new(DirectResolver::new(default)))
+        .fallback_to_default(true)
+        .build()
+        .unwrap();
+
+    let payment = lookup(&sdk, "fallback.example.com", "bitcoin-payment", Some("127.0.0.2"));
+    assert!(payment.used_fallback_resolver);
+    assert_eq!(payment.answered_by, udp(default));
+}
+
+#[test]
+fn backends_that_pick_their_own_server_leave_it_unset() {
+    let mock = MockTxtResolver::new().with_record("_nostr.mock.example.com", &["npub1mock"]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let nostr = lookup(&sdk, "mock.example.com", "nostr", None);
+    assert_eq!(nostr.value.as_deref(), Some("npub1mock"));
+    assert_eq!(nostr.answered_by, None);
+    let map = sdk.get_records_with("mock.example.com", Some(vec!["nostr"]), None, &LookupOptions::new().attempts(1)).to_map();
+    assert!(!map["nostr"].contains_key("answered_by"));
+}
+
+#[test]
+fn the_server_is_in_the_string_map_and_json() {
+    let server = dns_server("bitcoin:bc1qmap", false);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DirectResolver::new(server)));
+
+    let map = sdk.get_records_with("map.example.com", Some(vec!["bitcoin-payment"]), None, &LookupOptions::new().attempts(1)).to_map();
+    assert_eq!(map["bitcoin-payment"]["answered_by"].as_deref(), Some(&*format!("{}/udp", server)));
+    let payment = lookup(&sdk, "map.example.com", "bitcoin-payment", None);
+    let json = serde_json::to_value(&payment).unwrap();
+    assert_eq!(json["answered_by"]["address"], server.to_string());
+    assert_eq!(json["answered_by"]["transport"], "udp");
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use common::server::records_server;
+use common::txt_record;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{DirectResolver, LookupOptions, SelfieError, SelfieRecordsSDK};
+
+fn mock() -> MockTxtResolver {
+    MockTxtResolver::new()
+        .with_record("_bitcoin-payment.example.com", &["bitcoin:bc1qasync"])
+        .with_record("_nostr.example.com", &["npub1async"])
+        .with_record("_node-uri.example.com", &[])
+}
+
+#[tokio::test]
+async fn results_match_the_blocking_calls() {
+    // Backoff is jittered, so no key may need a retry here.
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock().with_record("_pgp.example.com", &[])));
+
+    let records = sdk.get_records_async("example.com", None, None).await.to_map();
+
+    assert_eq!(records["bitcoin-payment"]["value"].as_deref(), Some("bitcoin:bc1qasync"));
+    assert_eq!(records.len(), 4);
+    // The blocking calls cannot run on this thread's runtime.
+    let sdk = Arc::new(sdk);
+    let blocking = tokio::task::spawn_blocking({
+        let sdk = sdk.clone();
+        move || sdk.get_records("example.com", None, None).to_map()
+    });
+    assert_eq!(blocking.await.unwrap(), records);
+}
+
+#[tokio::test]
+async fn a_failing_key_does_not_stop_the_others() {
+    let server = records_server(vec![
+        txt_record("_bitcoin-payment.example.com.", "bitcoin:bc1qserved"),
+        txt_record("_nostr.example.com.", "npub1served"),
+    ]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DirectResolver::new(server)));
+
+    let response = sdk.get_records_response_async("example.com", Some(vec!["bitcoin-payment", "pgp", "nostr"]), None, &LookupOptions::new()).await;
+
+    assert_eq!(response.get("bitcoin-payment").unwrap().value.as_deref(), Some("bitcoin:bc1qserved"));
+    assert_eq!(response.get("nostr").unwrap().value.as_deref(), Some("npub1served"));
+    assert_eq!(response.get("pgp").unwrap().error, Some(SelfieError::NoRecords));
+    assert_eq!(response.queries_issued, 3);
+}
+
+#[tokio::test]
+async fn errors_are_reported_per_key() {
+    // `pgp` is not registered, so its lookup fails.
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock()));
+
+    let response = sdk.get_records_response_async("example.com", None, None, &LookupOptions::new().attempts(1)).await;
+
+    assert!(matches!(response.get("pgp").unwrap().error, Some(SelfieError::Resolver(_))));
+    assert_eq!(response.get("node-uri").unwrap().error, Some(SelfieError::NoRecords));
+    assert_eq!(response.get("nostr").unwrap().value.as_deref(), Some("npub1async"));
+}
+
+#[tokio::test]
+async fn keys_are_looked_up_concurrently() {
+    let delay = Duration::from_millis(200);
+    let mock = ["bitcoin-payment", "pgp", "nostr", "node-uri"].iter().fold(MockTxtResolver::new(), |mock, key| {
+        let name = format!("_{}.slow.example", key);
+        mock.with_record(&name, &[key]).with_delay(&name, delay)
+    });
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let started = Instant::now();
+    let records = sdk.get_records_async("slow.example", None, None).await.to_map();
+    assert!(started.elapsed() < delay * 2, "{:?}", started.elapsed());
+    assert!(records.values().all(|record| record["value"].is_some()));
+
+    let started = Instant::now();
+    sdk.get_records_response_async("slow.example", None, None, &LookupOptions::new().concurrency(1)).await;
+    assert!(started.elapsed() >= delay * 4, "{:?}", started.elapsed());
+}
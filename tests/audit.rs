@@ -0,0 +1,177 @@
+#![cfg(feature = "audit")]
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use selfie_records_sdk::audit::{AuditEntry, AuditSink, FileAuditSink};
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{BuildError, LookupOptions, SelfieError, SelfieRecordsSDK};
+use serde_json::Value;
+
+const KEYS: [&str; 11] = [
+    "timestamp_ms",
+    "identifier",
+    "qname",
+    "resolver",
+    "transport",
+    "outcome",
+    "answers",
+    "error",
+    "rcode",
+    "ttl",
+    "duration_us",
+];
+
+fn log_path(test: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("selfie-audit-{}-{}.jsonl", test, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn read_lines(path: &PathBuf) -> Vec<Value> {
+    let contents = std::fs::read_to_string(path).unwrap();
+    assert!(contents.ends_with('\n'));
+    contents.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+}
+
+/// Checks the keys and value types every line must have.
+fn assert_schema(line: &Value) {
+    let object = line.as_object().unwrap();
+    let mut keys: Vec<&str> = object.keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    let mut expected = KEYS.to_vec();
+    expected.sort_unstable();
+    assert_eq!(keys, expected);
+
+    assert!(line["timestamp_ms"].as_u64().unwrap() > 1_600_000_000_000);
+    assert!(line["duration_us"].is_u64());
+    for key in ["identifier", "qname", "resolver"] {
+        assert!(line[key].is_string(), "{}: {}", key, line);
+    }
+    let outcome = line["outcome"].as_str().unwrap();
+    assert!(["answered", "no_records", "error"].contains(&outcome), "{}", outcome);
+    assert_eq!(line["error"].is_string(), outcome == "error");
+    assert_eq!(line["answers"].is_array(), outcome != "error");
+    assert!(line["transport"].is_null() || line["transport"].is_string());
+    assert!(line["rcode"].is_null() || line["rcode"].is_u64());
+    assert!(line["ttl"].is_null() || line["ttl"].is_u64());
+}
+
+#[test]
+fn every_query_is_logged() {
+    let path = log_path("queries");
+    let resolver = MockTxtResolver::new()
+        .with_record("alice.user._bitcoin-payment.example.com", &["bitcoin:bc1qalice"])
+        .with_ttl("alice.user._bitcoin-payment.example.com", 300)
+        .with_record("alice.user._nostr.example.com", &[]);
+    let sdk = SelfieRecordsSDK::builder().resolver(Arc::new(resolver)).audit_log(&path).build().unwrap();
+
+    let options = LookupOptions::new().attempts(2).backoff(std::time::Duration::from_millis(1));
+    sdk.get_records_with("alice@example.com", Some(vec!["bitcoin-payment", "nostr", "pgp"]), None, &options).to_map();
+
+    let lines = read_lines(&path);
+    lines.iter().for_each(assert_schema);
+    let for_qname = |qname: &str| lines.iter().filter(|line| line["qname"] == qname).collect::<Vec<_>>();
+
+    let payment = for_qname("alice.user._bitcoin-payment.example.com");
+    assert_eq!(payment.len(), 1);
+    assert_eq!(payment[0]["identifier"], "alice@example.com");
+    assert_eq!(payment[0]["resolver"], "custom");
+    assert_eq!(payment[0]["outcome"], "answered");
+    assert_eq!(payment[0]["answers"], serde_json::json!(["bitcoin:bc1qalice"]));
+    assert_eq!(payment[0]["transport"], "resolver");
+    assert_eq!(payment[0]["ttl"], 300);
+
+    let nostr = for_qname("alice.user._nostr.example.com");
+    assert_eq!((nostr.len(), nostr[0]["outcome"].as_str()), (1, Some("no_records")));
+
+    // An unregistered name fails, and each of the two attempts is a query.
+    let pgp = for_qname("alice.user._pgp.example.com");
+    assert_eq!(pgp.len(), 2);
+    assert!(pgp.iter().all(|line| line["outcome"] == "error" && line["transport"].is_null()));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn identifiers_can_be_hashed() {
+    let path = log_path("hashed");
+    let resolver = MockTxtResolver::new().with_record("_bitcoin-payment.example.com", &["bitcoin:bc1qexample"]);
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(Arc::new(resolver))
+        .audit_log(&path)
+        .hash_audit_identifiers(b"audit key")
+        .build()
+        .unwrap();
+
+    sdk.get_records("example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+    sdk.get_records("example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+
+    let lines = read_lines(&path);
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, b"audit key");
+    let expected = format!("hmac-sha256:{}", data_encoding::HEXLOWER.encode(ring::hmac::sign(&key, b"example.com").as_ref()));
+    assert_eq!(lines[0]["identifier"], expected.as_str());
+    assert_eq!(lines[1]["identifier"], lines[0]["identifier"]);
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("\"example.com\""));
+    std::fs::remove_file(&path).unwrap();
+}
+
+fn entry(n: usize) -> AuditEntry {
+    AuditEntry {
+        timestamp: SystemTime::now(),
+        identifier: format!("user{}@example.com", n),
+        qname: format!("user{}.user._bitcoin-payment.example.com", n),
+        resolver: "8.8.8.8:53".to_string(),
+        transport: None,
+        outcome: Err(SelfieError::Resolver("x".repeat(n % 700))),
+        rcode: None,
+        ttl: None,
+        duration: std::time::Duration::from_micros(n as u64),
+    }
+}
+
+#[test]
+fn concurrent_writes_keep_lines_whole() {
+    let path = log_path("concurrent");
+    let sink = Arc::new(FileAuditSink::open(&path).unwrap());
+
+    let writers: Vec<_> = (0..8)
+        .map(|thread| {
+            let sink = sink.clone();
+            thread::spawn(move || (0..200).for_each(|i| sink.record(&entry(thread * 1000 + i))))
+        })
+        .collect();
+    writers.into_iter().for_each(|writer| writer.join().unwrap());
+
+    let lines = read_lines(&path);
+    assert_eq!(lines.len(), 1600);
+    lines.iter().for_each(assert_schema);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn the_file_is_recreated_after_rotation() {
+    let path = log_path("rotation");
+    let rotated = path.with_extension("jsonl.1");
+    let sink = FileAuditSink::open(&path).unwrap();
+
+    sink.record(&entry(1));
+    std::fs::rename(&path, &rotated).unwrap();
+    sink.record(&entry(2));
+
+    assert_eq!(read_lines(&rotated)[0]["identifier"], "user1@example.com");
+    let lines = read_lines(&path);
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["identifier"], "user2@example.com");
+    let timestamp = lines[0]["timestamp_ms"].as_u64().unwrap();
+    assert!(timestamp <= SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64);
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&rotated).unwrap();
+}
+
+#[test]
+fn an_unwritable_log_fails_the_build() {
+    let error = SelfieRecordsSDK::builder().audit_log(std::env::temp_dir()).build().unwrap_err();
+    assert!(matches!(error, BuildError::AuditLog(_)), "{:?}", error);
+}
@@ -0,0 +1,95 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use selfie_records_sdk::{LookupOptions, SelfieError, SelfieRecordsSDK, TxtResolver};
+
+/// Answers after a short wait with the queried name, failing names of
+/// users 7 and 31, and counts the lookups running at once.
+#[derive(Default)]
+struct Directory {
+    running: AtomicUsize,
+    most: AtomicUsize,
+    calls: AtomicUsize,
+}
+
+#[async_trait]
+impl TxtResolver for Directory {
+    async fn txt_lookup(&self, name: &str) -> Result<Vec<String>, SelfieError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.most.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        match name {
+            "user7.user._nostr.example.com" | "user31.user._nostr.example.com" => Err(SelfieError::Resolver(format!("{} refused", name))),
+            _ => Ok(vec![name.to_string()]),
+        }
+    }
+}
+
+fn handles() -> Vec<String> {
+    (0..50).map(|i| format!("user{}@example.com", i)).collect()
+}
+
+#[test]
+fn every_name_gets_its_own_results() {
+    let resolver = Arc::new(Directory::default());
+    let sdk = SelfieRecordsSDK::with_resolver(resolver.clone());
+    let handles = handles();
+    let mut names: Vec<&str> = handles.iter().map(String::as_str).collect();
+    names.extend(["user3@example.com", "user7@example.com", "not a name"]);
+    let progress = Mutex::new(Vec::new());
+
+    let results = sdk.get_records_batch_with_progress(
+        &names,
+        Some(vec!["nostr"]),
+        &LookupOptions::new().attempts(1).concurrency(4),
+        |done, total| progress.lock().unwrap().push((done, total)),
+    );
+
+    assert_eq!(results.len(), 51);
+    assert_eq!(resolver.calls.load(Ordering::SeqCst), 50);
+    assert_eq!(resolver.most.load(Ordering::SeqCst), 4);
+    for (i, handle) in handles.iter().enumerate() {
+        let result = results[handle].get("nostr").unwrap();
+        match i {
+            7 | 31 => assert!(matches!(&result.error, Some(SelfieError::Resolver(m)) if m.contains(&format!("user{}.user", i))), "{:?}", result),
+            _ => assert_eq!(result.value, Some(format!("user{}.user._nostr.example.com", i))),
+        }
+    }
+    assert!(matches!(results["not a name"].get("nostr").unwrap().error, Some(SelfieError::InvalidName { .. })));
+    assert_eq!(progress.into_inner().unwrap(), (1..=51).map(|done| (done, 51)).collect::<Vec<_>>());
+}
+
+#[test]
+fn without_filters_every_default_key_is_looked_up() {
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(Directory::default()));
+
+    let results = sdk.get_records_batch(&["user1@example.com", "user2@example.com"], None, &LookupOptions::new());
+
+    for response in results.values() {
+        let mut keys: Vec<&str> = response.iter().map(|(key, _)| key).collect();
+        keys.sort();
+        assert_eq!(keys, ["bitcoin-payment", "node-uri", "nostr", "pgp"]);
+    }
+}
+
+#[tokio::test]
+async fn the_async_batch_runs_on_the_callers_runtime() {
+    let resolver = Arc::new(Directory::default());
+    let sdk = Arc::new(SelfieRecordsSDK::with_resolver(resolver.clone()));
+    let handles = handles();
+
+    let results = tokio::spawn(async move {
+        let names: Vec<&str> = handles.iter().map(String::as_str).collect();
+        sdk.get_records_batch_async(&names, Some(vec!["nostr"]), &LookupOptions::new().attempts(1).concurrency(4)).await
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(results.len(), 50);
+    assert_eq!(results.values().filter(|response| response.get("nostr").unwrap().value.is_some()).count(), 48);
+    assert_eq!(resolver.most.load(Ordering::SeqCst), 4);
+}
@@ -0,0 +1,144 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use selfie_records_sdk::bench::{Bench, BenchReport, LatencyStats, ServerBench};
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{KeyResult, RecordsResponse, SelfieError, TxtResolver};
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+fn response(answers: &[(&str, Result<Option<&str>, SelfieError>)]) -> RecordsResponse {
+    let mut response = RecordsResponse::default();
+    for (key, answer) in answers {
+        let result = match answer {
+            Ok(value) => KeyResult {
+                value: value.map(str::to_string),
+                error: value.is_none().then_some(SelfieError::NoRecords),
+                ..KeyResult::default()
+            },
+            Err(e) => KeyResult { error: Some(e.clone()), ..KeyResult::default() },
+        };
+        response.insert(key, result);
+    }
+    response
+}
+
+fn timed_out() -> Result<Option<&'static str>, SelfieError> {
+    Err(SelfieError::Resolver("timed out".to_string()))
+}
+
+fn answering(server: &str, values: &[Option<&str>]) -> ServerBench {
+    let mut bench = ServerBench::new(server);
+    for (i, value) in values.iter().enumerate() {
+        bench.record(ms(i as u64 + 1), &response(&[("bitcoin-payment", Ok(*value))]));
+    }
+    bench
+}
+
+fn report(servers: Vec<ServerBench>) -> BenchReport {
+    BenchReport { name: "example.com".to_string(), servers, budget_exhausted: false }
+}
+
+#[test]
+fn latencies_are_summarised_by_nearest_rank() {
+    let samples: Vec<Duration> = [7, 20, 1, 13, 4, 19, 10, 2, 16, 5, 11, 3, 18, 8, 14, 6, 17, 9, 15, 12].map(ms).to_vec();
+
+    let stats = LatencyStats::from_samples(&samples).unwrap();
+    assert_eq!(stats, LatencyStats { min: ms(1), median: ms(10), p95: ms(19), max: ms(20) });
+
+    let one = LatencyStats::from_samples(&[ms(42)]).unwrap();
+    assert_eq!(one, LatencyStats { min: ms(42), median: ms(42), p95: ms(42), max: ms(42) });
+    assert_eq!(LatencyStats::from_samples(&[]), None);
+}
+
+#[test]
+fn failed_lookups_count_against_success_but_not_latency() {
+    let mut bench = ServerBench::new("192.0.2.1:53");
+    bench.record(ms(10), &response(&[("bitcoin-payment", Ok(Some("bitcoin:bc1q"))), ("nostr", Ok(None))]));
+    bench.record(ms(900), &response(&[("bitcoin-payment", Ok(Some("bitcoin:bc1q"))), ("nostr", timed_out())]));
+    bench.record(ms(30), &response(&[("bitcoin-payment", Ok(Some("bitcoin:bc1q"))), ("nostr", Ok(None))]));
+
+    assert_eq!(bench.lookups(), 3);
+    assert_eq!(bench.failures, 1);
+    assert_eq!(bench.success_rate(), 2.0 / 3.0);
+    assert_eq!(bench.stats().unwrap().max, ms(30));
+    assert_eq!(bench.answers["nostr"].len(), 1);
+    assert_eq!(ServerBench::new("192.0.2.2:53").success_rate(), 0.0);
+}
+
+#[test]
+fn answers_are_compared_across_servers() {
+    let same = |server| answering(server, &[Some("bitcoin:bc1q"), Some("bitcoin:bc1q")]);
+    assert!(report(vec![same("a"), same("b")]).consistent());
+
+    let other = answering("c", &[Some("bitcoin:bc1qother")]);
+    assert!(!report(vec![same("a"), other]).consistent());
+
+    let missing = answering("c", &[None]);
+    assert!(!report(vec![same("a"), missing]).consistent());
+
+    // One server changing its answer between lookups is divergent too.
+    let flapping = answering("a", &[Some("bitcoin:bc1q"), Some("bitcoin:bc1qother")]);
+    assert!(!report(vec![flapping]).consistent());
+
+    // Servers that never answered have nothing to disagree with.
+    let mut unreachable = ServerBench::new("d");
+    unreachable.record(ms(5000), &response(&[("bitcoin-payment", timed_out())]));
+    assert!(report(vec![same("a"), unreachable, same("b")]).consistent());
+}
+
+#[test]
+fn reports_serialise_to_json() {
+    let json = report(vec![answering("192.0.2.1:53", &[Some("bitcoin:bc1q")]), ServerBench::new("192.0.2.2:53")]).to_json();
+
+    assert_eq!(json["name"], "example.com");
+    assert_eq!(json["consistent"], true);
+    assert_eq!(json["budget_exhausted"], false);
+    assert_eq!(json["servers"][0]["server"], "192.0.2.1:53");
+    assert_eq!(json["servers"][0]["lookups"], 1);
+    assert_eq!(json["servers"][0]["success_rate"], 1.0);
+    assert_eq!(json["servers"][0]["latency"]["median_ms"], 1.0);
+    assert!(json["servers"][1]["latency"].is_null());
+}
+
+#[test]
+fn warm_up_lookups_are_not_measured() {
+    let delayed = Arc::new(
+        MockTxtResolver::new()
+            .with_record("_bitcoin-payment.bench-warmup.example", &["bitcoin:bc1qslow"])
+            .with_delay("_bitcoin-payment.bench-warmup.example", ms(20)),
+    );
+    let missing = Arc::new(MockTxtResolver::new().with_record("_bitcoin-payment.bench-warmup.example", &[]));
+    let servers: Vec<(String, Arc<dyn TxtResolver>)> =
+        vec![("delayed".to_string(), delayed.clone()), ("missing".to_string(), missing.clone())];
+
+    let report = Bench::new("bench-warmup.example", &["bitcoin-payment"]).iterations(3).warmup(2).run(&servers);
+
+    assert!(!report.budget_exhausted);
+    assert_eq!(report.servers[0].server, "delayed");
+    assert_eq!(report.servers[0].samples.len(), 3);
+    assert!(report.servers[0].stats().unwrap().min >= ms(20));
+    assert_eq!(report.servers[1].lookups(), 3);
+    // Every lookup reaches the resolver: nothing is cached.
+    assert_eq!(delayed.calls(), 5);
+    assert_eq!(missing.calls(), 5);
+    assert!(!report.consistent());
+}
+
+#[test]
+fn the_run_stops_when_the_budget_is_spent() {
+    let qname = "_bitcoin-payment.bench-budget.example";
+    let slow = Arc::new(MockTxtResolver::new().with_record(qname, &["bitcoin:bc1q"]).with_delay(qname, ms(50)));
+    let servers: Vec<(String, Arc<dyn TxtResolver>)> = vec![("slow".to_string(), slow)];
+
+    let report = Bench::new("bench-budget.example", &["bitcoin-payment"])
+        .iterations(100)
+        .warmup(0)
+        .budget(ms(200))
+        .run(&servers);
+
+    assert!(report.budget_exhausted);
+    assert!(report.servers[0].lookups() < 100, "{}", report.servers[0].lookups());
+}
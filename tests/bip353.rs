@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{Bip353Instruction, SelfieError, SelfieRecordsSDK};
+
+const NAME: &str = "alice.user._bitcoin-payment.example.com";
+
+fn get_bip353(records: &[&str], address: &str) -> Result<Bip353Instruction, SelfieError> {
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new().with_record(NAME, records)));
+    sdk.get_bip353(address)
+}
+
+#[test]
+fn the_payment_instruction_is_parsed() {
+    let uri = "bitcoin:bc1qexample?lno=lno1offer&sp=sp1qsilent&pj=https://pj.example/";
+
+    for address in ["alice@example.com", "₿alice@example.com"] {
+        let instruction = get_bip353(&[uri], address).unwrap();
+        assert_eq!(instruction.uri, uri);
+        assert_eq!(instruction.payment.address, "bc1qexample");
+        assert_eq!(instruction.payment.param("lno"), Some("lno1offer"));
+        assert_eq!(instruction.payment.param("sp"), Some("sp1qsilent"));
+        assert_eq!(instruction.payment.param("pj"), Some("https://pj.example/"));
+        assert!(!instruction.authenticated);
+    }
+}
+
+#[test]
+fn records_that_are_not_bitcoin_uris_are_ignored() {
+    let instruction = get_bip353(&["v=spf1 -all", "BITCOIN:?lno=lno1offer"], "alice@example.com").unwrap();
+
+    assert_eq!(instruction.payment.address, "");
+    assert_eq!(instruction.payment.param("lno"), Some("lno1offer"));
+}
+
+#[test]
+fn two_bitcoin_uris_fail_the_lookup() {
+    let error = get_bip353(&["bitcoin:bc1qfirst", "bitcoin:bc1qsecond"], "alice@example.com").unwrap_err();
+
+    assert_eq!(error, SelfieError::MultipleRecords { count: 2 });
+    assert_eq!(error.code(), "E_MULTIPLE_RECORDS");
+}
+
+#[test]
+fn a_value_that_is_not_a_bitcoin_uri_fails_the_lookup() {
+    let error = get_bip353(&["lightning:lnbc1invoice"], "alice@example.com").unwrap_err();
+
+    assert_eq!(error, SelfieError::NotABitcoinUri { values: vec!["lightning:lnbc1invoice".to_string()] });
+    assert_eq!(error.code(), "E_NOT_A_BITCOIN_URI");
+    assert!(matches!(get_bip353(&["bitcoin:bc1q invalid"], "alice@example.com"), Err(SelfieError::InvalidRecord { .. })));
+}
+
+#[test]
+fn lookup_failures_are_passed_on() {
+    assert_eq!(get_bip353(&[], "alice@example.com").unwrap_err(), SelfieError::NoRecords);
+    assert!(matches!(get_bip353(&["bitcoin:bc1q"], "not a name"), Err(SelfieError::InvalidName { .. })));
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{CacheConfig, KeyResult, LookupOptions, SelfieRecordsSDK, Source};
+
+const NAME: &str = "alice.user._bitcoin-payment.example.com";
+
+fn sdk(mock: MockTxtResolver, config: CacheConfig) -> (SelfieRecordsSDK, Arc<MockTxtResolver>) {
+    let mock = Arc::new(mock);
+    (SelfieRecordsSDK::builder().resolver(mock.clone()).cache(config).build().unwrap(), mock)
+}
+
+fn lookup(sdk: &SelfieRecordsSDK, name: &str) -> KeyResult {
+    let response = sdk.get_records_response(name, Some(vec!["bitcoin-payment"]), None, &LookupOptions::new());
+    response.get("bitcoin-payment").unwrap().clone()
+}
+
+#[test]
+fn answers_are_served_from_the_cache_within_their_ttl() {
+    let mock = MockTxtResolver::new().with_record(NAME, &["bitcoin:bc1qcached"]).with_ttl(NAME, 300);
+    let (sdk, mock) = sdk(mock, CacheConfig::default());
+
+    assert_eq!(lookup(&sdk, "alice@example.com").source, Some(Source::Dns));
+    let cached = lookup(&sdk, "alice@example.com");
+
+    assert_eq!(cached.source, Some(Source::Cache));
+    assert_eq!(cached.value.as_deref(), Some("bitcoin:bc1qcached"));
+    assert_eq!(mock.calls(), 1);
+    let records = sdk.get_records("alice@example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+    assert_eq!(records["bitcoin-payment"]["source"].as_deref(), Some("cache"));
+}
+
+#[test]
+fn expired_answers_are_queried_again() {
+    // A TTL of zero, raised to `min_ttl`.
+    let mock = MockTxtResolver::new().with_record(NAME, &["bitcoin:bc1qcached"]).with_ttl(NAME, 0);
+    let config = CacheConfig { min_ttl: Duration::from_millis(50), ..CacheConfig::default() };
+    let (sdk, mock) = sdk(mock, config);
+
+    lookup(&sdk, "alice@example.com");
+    assert_eq!(lookup(&sdk, "alice@example.com").source, Some(Source::Cache));
+    thread::sleep(Duration::from_millis(80));
+
+    assert_eq!(lookup(&sdk, "alice@example.com").source, Some(Source::Dns));
+    assert_eq!(mock.calls(), 2);
+}
+
+#[test]
+fn max_ttl_caps_long_ttls() {
+    let mock = MockTxtResolver::new().with_record(NAME, &["bitcoin:bc1qcached"]).with_ttl(NAME, 86400);
+    let config = CacheConfig { max_ttl: Duration::from_millis(50), ..CacheConfig::default() };
+    let (sdk, mock) = sdk(mock, config);
+
+    lookup(&sdk, "alice@example.com");
+    thread::sleep(Duration::from_millis(80));
+
+    assert_eq!(lookup(&sdk, "alice@example.com").source, Some(Source::Dns));
+    assert_eq!(mock.calls(), 2);
+}
+
+#[test]
+fn the_entry_expiring_first_makes_room() {
+    let mock = ["alice", "bob", "carol"].iter().zip([60, 30, 90]).fold(MockTxtResolver::new(), |mock, (user, ttl)| {
+        let name = format!("{}.user._bitcoin-payment.example.com", user);
+        mock.with_record(&name, &[user]).with_ttl(&name, ttl)
+    });
+    let (sdk, mock) = sdk(mock, CacheConfig { max_entries: 2, ..CacheConfig::default() });
+
+    for name in ["alice@example.com", "bob@example.com", "carol@example.com"] {
+        lookup(&sdk, name);
+    }
+
+    assert_eq!(lookup(&sdk, "alice@example.com").source, Some(Source::Cache));
+    assert_eq!(lookup(&sdk, "carol@example.com").source, Some(Source::Cache));
+    assert_eq!(lookup(&sdk, "bob@example.com").source, Some(Source::Dns));
+    assert_eq!(mock.calls(), 4);
+}
+
+#[test]
+fn clear_cache_forgets_every_answer() {
+    let mock = MockTxtResolver::new().with_record(NAME, &["bitcoin:bc1qcached"]).with_ttl(NAME, 300);
+    let (sdk, mock) = sdk(mock, CacheConfig::default());
+
+    lookup(&sdk, "alice@example.com");
+    sdk.clear_cache();
+
+    assert_eq!(lookup(&sdk, "alice@example.com").source, Some(Source::Dns));
+    assert_eq!(mock.calls(), 2);
+}
+
+#[test]
+fn a_disabled_cache_keeps_nothing() {
+    let mock = MockTxtResolver::new().with_record(NAME, &["bitcoin:bc1qcached"]).with_ttl(NAME, 300);
+    let (sdk, mock) = sdk(mock, CacheConfig { enabled: false, ..CacheConfig::default() });
+
+    lookup(&sdk, "alice@example.com");
+
+    assert_eq!(lookup(&sdk, "alice@example.com").source, Some(Source::Dns));
+    assert_eq!(mock.calls(), 2);
+}
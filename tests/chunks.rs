@@ -0,0 +1,160 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use selfie_records_sdk::publish::{ProviderError, SelfieRecord};
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{KeyResult, LookupOptions, SelfieError, SelfieRecordsSDK};
+
+/// An armored key far longer than one TXT record can hold.
+fn armored_key() -> String {
+    let body: String = (0..1200).map(|i| char::from(b'A' + (i % 26) as u8)).collect();
+    format!("-----BEGIN PGP PUBLIC KEY BLOCK-----\n\n{}\n-----END PGP PUBLIC KEY BLOCK-----", body)
+}
+
+/// A mock serving `records` at the names the default scheme gives them.
+fn serving(records: &[SelfieRecord], domain: &str) -> MockTxtResolver {
+    records.iter().fold(MockTxtResolver::new(), |mock, record| {
+        let values: Vec<&str> = record.values.iter().map(String::as_str).collect();
+        mock.with_record(&format!("_{}.{}", record.key, domain), &values)
+    })
+}
+
+fn sdk(mock: Arc<MockTxtResolver>) -> SelfieRecordsSDK {
+    SelfieRecordsSDK::builder().resolver(mock).chunked_key("pgp").build().unwrap()
+}
+
+fn lookup(sdk: &SelfieRecordsSDK, name: &str) -> KeyResult {
+    let options = LookupOptions::new().attempts(1);
+    sdk.get_records_response(name, Some(vec!["pgp"]), None, &options).get("pgp").unwrap().clone()
+}
+
+#[test]
+fn chunked_records_round_trip() {
+    let key = armored_key();
+    let records = SelfieRecord::chunked("example.com", "pgp", &key, 400).unwrap();
+    let keys: Vec<&str> = records.iter().map(|record| record.key.as_str()).collect();
+    assert_eq!(keys, ["pgp", "pgp.1", "pgp.2", "pgp.3", "pgp.4"]);
+    assert!(records[0].values[0].starts_with("chunks=4;sha256="));
+    let mock = Arc::new(serving(&records, "example.com"));
+
+    let pgp = lookup(&sdk(mock.clone()), "example.com");
+
+    assert_eq!(pgp.error, None);
+    assert_eq!(pgp.value.as_deref(), Some(key.as_str()));
+    assert_eq!(pgp.raw_bytes(), Some(key.as_bytes()));
+    assert!(pgp.chunked);
+    assert_eq!(mock.calls(), 5);
+}
+
+#[test]
+fn chunks_follow_the_name_scheme() {
+    let key = armored_key();
+    let records = SelfieRecord::chunked("alice@example.com", "pgp", &key, 800).unwrap();
+    let mock = records.iter().fold(MockTxtResolver::new(), |mock, record| {
+        mock.with_record(&format!("alice.user._{}.example.com", record.key), &[&record.values[0]])
+    });
+    let sdk = sdk(Arc::new(mock));
+
+    let pgp = lookup(&sdk, "alice@example.com");
+    assert_eq!(pgp.value.as_deref(), Some(key.as_str()));
+    let map = sdk.get_records_with("alice@example.com", Some(vec!["pgp"]), None, &LookupOptions::new()).to_map();
+    assert_eq!(map["pgp"]["chunked"].as_deref(), Some("true"));
+}
+
+#[test]
+fn chunks_are_fetched_concurrently() {
+    let records = SelfieRecord::chunked("example.com", "pgp", &armored_key(), 200).unwrap();
+    let chunks = records.len() - 1;
+    let delay = Duration::from_millis(100);
+    let mock = records[1..].iter().fold(serving(&records, "example.com"), |mock, record| {
+        mock.with_delay(&format!("_{}.example.com", record.key), delay)
+    });
+
+    let started = Instant::now();
+    let pgp = lookup(&sdk(Arc::new(mock)), "example.com");
+
+    assert!(pgp.chunked);
+    assert!(started.elapsed() < delay * (chunks as u32) / 2, "{} chunks took {:?}", chunks, started.elapsed());
+}
+
+#[test]
+fn missing_chunks_fail() {
+    let records = SelfieRecord::chunked("example.com", "pgp", &armored_key(), 500).unwrap();
+    let mock = serving(&records, "example.com").with_record("_pgp.2.example.com", &[]);
+
+    let pgp = lookup(&sdk(Arc::new(mock)), "example.com");
+
+    assert_eq!(pgp.error, Some(SelfieError::MissingChunk { index: 2, count: 3 }));
+    assert_eq!(pgp.value, None);
+    assert!(!pgp.chunked);
+}
+
+#[test]
+fn chunks_that_do_not_match_the_digest_fail() {
+    let records = SelfieRecord::chunked("example.com", "pgp", &armored_key(), 500).unwrap();
+    let mock = serving(&records, "example.com").with_record("_pgp.3.example.com", &["tampered"]);
+
+    let pgp = lookup(&sdk(Arc::new(mock)), "example.com");
+
+    assert_eq!(pgp.error, Some(SelfieError::ChunkDigestMismatch));
+    assert_eq!(pgp.value, None);
+}
+
+#[test]
+fn chunks_with_several_records_fail() {
+    let records = SelfieRecord::chunked("example.com", "pgp", &armored_key(), 500).unwrap();
+    let mock = serving(&records, "example.com").with_record("_pgp.1.example.com", &["one", "two"]);
+
+    let pgp = lookup(&sdk(Arc::new(mock)), "example.com");
+
+    let reason = "chunk 1 has 2 records".to_string();
+    assert_eq!(pgp.error, Some(SelfieError::InvalidRecord { key: "pgp".to_string(), reason }));
+}
+
+#[test]
+fn malformed_manifests_fail() {
+    for (manifest, reason) in [
+        ("chunks=0;sha256=00", "chunk count must be from 1 to 64"),
+        ("chunks=65;sha256=00", "chunk count must be from 1 to 64"),
+        ("chunks=2;sha256=abc", "sha256 must be 64 hex digits"),
+        ("chunks=2", "sha256 must be 64 hex digits"),
+        ("chunks=2;md5=00", "unknown manifest field \"md5=00\""),
+    ] {
+        let mock = MockTxtResolver::new().with_record("_pgp.example.com", &[manifest]);
+        let pgp = lookup(&sdk(Arc::new(mock)), "example.com");
+        assert_eq!(pgp.error, Some(SelfieError::InvalidRecord { key: "pgp".to_string(), reason: reason.to_string() }), "{}", manifest);
+    }
+}
+
+#[test]
+fn only_designated_keys_are_reassembled() {
+    let records = SelfieRecord::chunked("example.com", "pgp", &armored_key(), 500).unwrap();
+    let mock = Arc::new(serving(&records, "example.com"));
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+
+    let pgp = lookup(&sdk, "example.com");
+
+    assert_eq!(pgp.value.as_ref(), Some(&records[0].values[0]));
+    assert!(!pgp.chunked);
+    assert_eq!(mock.calls(), 1);
+    // Ordinary values of a designated key are left alone.
+    let mock = MockTxtResolver::new().with_record("_pgp.example.com", &["https://example.com/key.asc"]);
+    assert_eq!(lookup(&self::sdk(Arc::new(mock)), "example.com").value.as_deref(), Some("https://example.com/key.asc"));
+}
+
+#[test]
+fn payloads_split_on_character_boundaries() {
+    let payload = "ü".repeat(5);
+    let records = SelfieRecord::chunked("example.com", "note", &payload, 3).unwrap();
+
+    let chunks: Vec<&str> = records[1..].iter().map(|record| record.values[0].as_str()).collect();
+    assert_eq!(chunks, ["ü", "ü", "ü", "ü", "ü"]);
+    assert_eq!(chunks.concat(), payload);
+}
+
+#[test]
+fn payloads_needing_too_many_chunks_are_rejected() {
+    assert!(matches!(SelfieRecord::chunked("example.com", "pgp", &"a".repeat(65), 1), Err(ProviderError::InvalidRecord(_))));
+    assert!(matches!(SelfieRecord::chunked("example.com", "pgp", "", 100), Err(ProviderError::InvalidRecord(_))));
+    assert_eq!(SelfieRecord::chunked("example.com", "pgp", &"a".repeat(64), 1).unwrap().len(), 65);
+}
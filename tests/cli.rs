@@ -0,0 +1,373 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::str::FromStr;
+
+use common::server::{dns_server, doh_server, records_server, DohReply, DOH_VALUE};
+use selfie_records_sdk::config::{CONFIG_VAR, SETTINGS};
+use trust_dns_proto::rr::rdata::TXT;
+use trust_dns_proto::rr::{Name, RData, Record};
+
+/// Runs the CLI with `env` as its only settings, so that the config and
+/// variables of whoever runs the tests do not apply.
+fn selfie_with(env: &[(&str, &str)], args: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_selfie"));
+    command.env("XDG_CONFIG_HOME", "/nonexistent").env_remove(CONFIG_VAR);
+    for (_, var) in SETTINGS {
+        command.env_remove(var);
+    }
+    command.envs(env.iter().copied()).args(args).output().unwrap()
+}
+
+fn selfie(args: &[&str]) -> Output {
+    selfie_with(&[], args)
+}
+
+fn config_file(name: &str, text: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("selfie-cli-{}-{}.toml", name, std::process::id()));
+    std::fs::write(&path, text).unwrap();
+    path
+}
+
+fn txt(owner: &str, value: &str) -> Record {
+    Record::from_rdata(Name::from_str(owner).unwrap(), 300, RData::TXT(TXT::new(vec![value.to_string()])))
+}
+
+#[test]
+fn get_prints_only_the_value() {
+    let server = dns_server("bitcoin:bc1qexample", false).to_string();
+
+    let output = selfie(&["get", "alice@example.com", "bitcoin-payment", "--dns", &server]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, b"bitcoin:bc1qexample\n");
+    assert_eq!(output.stderr, b"");
+}
+
+#[test]
+fn debug_logs_go_to_stderr() {
+    let server = dns_server("bitcoin:bc1qdebug", false).to_string();
+
+    let output = selfie(&["get", "alice@example.com", "bitcoin-payment", "--dns", &server, "--debug"]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, b"bitcoin:bc1qdebug\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Resolving TXT record for: alice.user._bitcoin-payment.example.com"), "{}", stderr);
+}
+
+#[test]
+fn get_names_the_answering_server_when_verbose() {
+    let server = dns_server("bitcoin:bc1qverbose", false).to_string();
+
+    let output = selfie(&["get", "alice@example.com", "bitcoin-payment", "--dns", &server, "--verbose"]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, b"bitcoin:bc1qverbose\n");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), format!(";; answered by {}/udp\n", server));
+}
+
+#[test]
+fn get_prints_one_line_per_record() {
+    let owner = "_nostr.example.com.";
+    let server = records_server(vec![txt(owner, "npub1first"), txt(owner, "npub1second")]).to_string();
+
+    let output = selfie(&["get", "example.com", "nostr", "--dns", &server]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, b"npub1first\nnpub1second\n");
+
+    let output = selfie(&["get", "example.com", "nostr", "--dns", &server, "--first"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, b"npub1first\n");
+}
+
+#[test]
+fn get_exits_with_2_when_not_found() {
+    let server = records_server(Vec::new()).to_string();
+
+    let output = selfie(&["get", "example.com", "bitcoin-payment", "--dns", &server, "--timeout", "5"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(output.stdout, b"");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "error: no bitcoin-payment record for example.com\n");
+}
+
+#[test]
+fn get_reports_other_errors_on_stderr() {
+    let output = selfie(&["get", "not a name", "bitcoin-payment", "--dns", "127.0.0.1:9"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, b"");
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("error: Invalid name"));
+}
+
+#[test]
+fn get_escapes_control_characters() {
+    let server = dns_server("bitcoin:bc1q\u{1b}[2J", false).to_string();
+
+    let output = selfie(&["get", "example.com", "bitcoin-payment", "--dns", &server]);
+
+    assert_eq!(output.stdout, b"bitcoin:bc1q\\u{1b}[2J\n");
+}
+
+#[test]
+fn get_queries_doh_endpoints() {
+    let (url, _) = doh_server(vec![DohReply::ok()]);
+
+    let output = selfie(&["get", "example.com", "bitcoin-payment", "--doh", &url]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, format!("{}\n", DOH_VALUE).into_bytes());
+}
+
+#[test]
+fn settings_come_from_flags_then_variables_then_the_config_file() {
+    let file = dns_server("bitcoin:bc1qfile", false).to_string();
+    let env = dns_server("bitcoin:bc1qenv", false).to_string();
+    let flag = dns_server("bitcoin:bc1qflag", false).to_string();
+    let path = config_file("precedence", &format!("nameservers = [\"{}\"]\n", file));
+    let path = path.to_str().unwrap();
+
+    let get = |env: &[(&str, &str)], args: &[&str]| {
+        let args = [&["--config", path, "get", "example.com", "bitcoin-payment"], args].concat();
+        String::from_utf8(selfie_with(env, &args).stdout).unwrap()
+    };
+    assert_eq!(get(&[], &[]), "bitcoin:bc1qfile\n");
+    assert_eq!(get(&[("SELFIE_DNS", &env)], &[]), "bitcoin:bc1qenv\n");
+    assert_eq!(get(&[("SELFIE_DNS", &env)], &["--dns", &flag]), "bitcoin:bc1qflag\n");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn the_config_file_can_be_named_by_a_variable() {
+    let server = dns_server("bitcoin:bc1qnamed", false).to_string();
+    let path = config_file("named", &format!("nameservers = [\"{}\"]\n", server));
+
+    let output = selfie_with(&[(CONFIG_VAR, path.to_str().unwrap())], &["get", "example.com", "bitcoin-payment"]);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(output.stdout, b"bitcoin:bc1qnamed\n");
+}
+
+#[test]
+fn invalid_settings_are_reported() {
+    let path = config_file("invalid", "[cache]\nttl = \"long\"\n");
+
+    let output = selfie(&["get", "example.com", "bitcoin-payment", "--config", path.to_str().unwrap()]);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: Invalid value for cache.ttl: expected a non-negative number of seconds\n"
+    );
+
+    let output = selfie_with(&[("SELFIE_TIMEOUT", "soon")], &["get", "example.com", "bitcoin-payment"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("SELFIE_TIMEOUT"));
+}
+
+#[test]
+fn bench_compares_nameservers() {
+    let first = dns_server("bitcoin:bc1qbench", false).to_string();
+    let second = dns_server("bitcoin:bc1qbench", false).to_string();
+    let servers = format!("{},{}", first, second);
+
+    let output = selfie(&["bench", "example.com", "--servers", &servers, "--iterations", "3", "--warmup", "1", "--json"]);
+    assert_eq!(output.status.code(), Some(0));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["consistent"], true);
+    assert_eq!(report["servers"][1]["server"], second.as_str());
+    assert_eq!(report["servers"][1]["lookups"], 3);
+
+    let other = dns_server("bitcoin:bc1qother", false).to_string();
+    let output = selfie(&["bench", "example.com", "--servers", &format!("{},{}", first, other), "--iterations", "2"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stdout).unwrap().ends_with(";; answers: divergent\n"));
+}
+
+#[test]
+fn check_reports_and_exits_with_the_overall_status() {
+    let server = records_server(vec![txt("_bitcoin-payment.example.com.", "bitcoin:bc1qcheck")]).to_string();
+    let args = ["check", "example.com", "--keys", "bitcoin-payment", "--dns", &server, "--servers", &server];
+
+    let output = selfie(&[&args[..], &["--json"]].concat());
+    // Nothing is validated with DNSSEC here.
+    assert_eq!(output.status.code(), Some(1));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["status"], "WARN");
+    assert_eq!(report["keys"][0]["value"], "bitcoin:bc1qcheck");
+
+    let stdout = String::from_utf8(selfie(&args).stdout).unwrap();
+    assert!(stdout.starts_with(";; health of example.com\nbitcoin-payment (_bitcoin-payment.example.com): WARN\n"), "{}", stdout);
+    assert!(stdout.contains(&format!("  PASS  propagation {} agrees\n", server)), "{}", stdout);
+    assert!(stdout.ends_with(";; overall: WARN\n"), "{}", stdout);
+
+    let empty = records_server(Vec::new()).to_string();
+    let output = selfie(&["check", "example.com", "--dns", &empty, "--servers", &empty]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stdout).unwrap().ends_with("  FAIL  records     no records found\n;; overall: FAIL\n"));
+}
+
+#[test]
+fn verify_compares_records_with_a_manifest() {
+    let server = records_server(vec![
+        txt("_bitcoin-payment.example.com.", "bitcoin:bc1qverify"),
+        txt("_nostr.example.com.", "npub1drifted"),
+    ])
+    .to_string();
+    let manifest = config_file(
+        "verify",
+        r#"{"example.com": {"bitcoin-payment": "prefix:bitcoin:", "nostr": "npub1declared", "pgp": "https://example.com/key.asc"}}"#,
+    );
+    let path = manifest.to_str().unwrap();
+
+    let output = selfie(&["verify", "--manifest", path, "--dns", &server]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "MATCH     example.com bitcoin-payment\n\
+         MISMATCH  example.com nostr\n  expected  npub1declared\n  actual    npub1drifted\n\
+         MISSING   example.com pgp\n  expected  https://example.com/key.asc\n\
+         ;; 1 match, 1 mismatch, 1 missing, 0 failed\n"
+    );
+
+    let output = selfie(&["verify", "--manifest", path, "--dns", &server, "--update"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8(output.stdout).unwrap().ends_with(&format!(";; updated 2 records in {}\n", path)));
+    let updated: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    assert_eq!(updated, serde_json::json!({"example.com": {"bitcoin-payment": "prefix:bitcoin:", "nostr": "npub1drifted"}}));
+
+    let output = selfie(&["verify", "--manifest", path, "--dns", &server]);
+    assert_eq!(output.status.code(), Some(0));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn verify_reads_toml_manifests_and_reports_in_json() {
+    let server = records_server(vec![txt("_nostr.example.com.", "npub1drifted")]).to_string();
+    let manifest = config_file(
+        "verify-toml",
+        "[[records]]\nname = \"example.com\"\nkey = \"nostr\"\nvalue = \"npub1declared\"\n\n\
+         [[records]]\nname = \"example.com\"\nkey = \"nostr-relay\"\nvalue = \"wss://relay.example.com\"\n",
+    );
+    let path = manifest.to_str().unwrap();
+
+    let output = selfie(&["verify", path, "--dns", &server, "--format", "json"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "warning: nostr-relay is not a well-known record key\n");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["passed"], false);
+    assert_eq!(report["entries"][0], serde_json::json!({"name": "example.com", "key": "nostr", "expected": "npub1declared", "outcome": "mismatch", "actual": "npub1drifted"}));
+    assert_eq!(report["entries"][1]["outcome"], "missing");
+
+    let output = selfie(&["verify", path, "--dns", &server, "--update"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(std::fs::read_to_string(path).unwrap(), "[[records]]\nname = \"example.com\"\nkey = \"nostr\"\nvalue = \"npub1drifted\"\n");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn verify_exits_with_2_for_unusable_manifests_and_failed_lookups() {
+    let output = selfie(&["verify", "--manifest", "/nonexistent/records.json"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("error: cannot read /nonexistent/records.json: "));
+
+    let manifest = config_file("verify-invalid", r#"{"example.com": {"nostr": "regex:a{2}"}}"#);
+    let output = selfie(&["verify", "--manifest", manifest.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "error: Invalid manifest: example.com nostr: { is not supported\n");
+    std::fs::remove_file(&manifest).unwrap();
+
+    let dead = std::net::UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+    let manifest = config_file("verify-failed", r#"{"example.com": {"nostr": "npub1declared"}}"#);
+    let output = selfie(&["verify", "--manifest", manifest.to_str().unwrap(), "--dns", &dead, "--timeout", "2", "--update"]);
+    std::fs::remove_file(&manifest).unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("FAILED    example.com nostr\n  error     "));
+}
+
+#[test]
+fn lookup_prints_aligned_lines_and_marks_failures() {
+    let records = vec![
+        txt("_bitcoin-payment.example.com.", "bitcoin:bc1qlookup"),
+        txt("_nostr.example.com.", "npub1a"),
+        txt("_nostr.example.com.", "npub1b"),
+    ];
+    let server = records_server(records).to_string();
+
+    let output = selfie(&["lookup", "example.com", "--keys", "bitcoin-payment,nostr,pgp", "--dns", &server]);
+
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "bitcoin-payment  bitcoin:bc1qlookup\nnostr            npub1a\n                 npub1b\npgp              not found\n");
+}
+
+#[test]
+fn lookup_prints_json_results() {
+    let server = records_server(vec![txt("_nostr.example.com.", "npub1json")]).to_string();
+
+    let output = selfie(&["lookup", "example.com", "--keys", "nostr,pgp", "--format", "json", "--dns", &server]);
+
+    assert_eq!(output.status.code(), Some(0));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["nostr"]["value"], "npub1json");
+    assert_eq!(json["pgp"]["value"], serde_json::Value::Null);
+    assert!(json["pgp"]["error"].is_object(), "{}", json);
+}
+
+#[test]
+fn lookup_fails_when_no_key_resolves_or_with_strict_when_any_fails() {
+    let server = records_server(vec![txt("_nostr.example.com.", "npub1strict")]).to_string();
+    let lookup = |keys: &str, strict: bool| {
+        let mut args = vec!["lookup", "example.com", "--keys", keys, "--dns", &server];
+        if strict {
+            args.push("--strict");
+        }
+        selfie(&args).status.code()
+    };
+
+    assert_eq!(lookup("nostr,pgp", false), Some(0));
+    assert_eq!(lookup("nostr,pgp", true), Some(1));
+    assert_eq!(lookup("nostr", true), Some(0));
+    assert_eq!(lookup("pgp,did", false), Some(1));
+}
+
+#[test]
+fn lookup_reports_errors_with_their_code() {
+    let output = selfie(&["lookup", "not a name", "--keys", "nostr", "--dns", "127.0.0.1:9"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("nostr  error [E_INVALID_NAME]: "), "{}", stdout);
+}
+
+#[test]
+fn lookup_rejects_unknown_formats_and_servers() {
+    let output = selfie(&["lookup", "example.com", "--format", "yaml"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("possible values: text, json"));
+
+    let output = selfie(&["lookup", "example.com", "--dns", "dns.example"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("invalid nameserver address"));
+}
+
+#[test]
+fn publish_prints_the_record_to_create() {
+    let output = selfie(&["publish", "alice@example.com", "bitcoin-payment", "bitcoin:bc1qalice"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "Host   alice.user._bitcoin-payment\nType   TXT\nValue  bitcoin:bc1qalice\nTTL    3600\n"
+    );
+
+    let output = selfie(&["publish", "example.com", "custom", "say \"hi\"", "--format", "zone", "--ttl", "60"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "_custom.example.com. 60 IN TXT \"say \\\"hi\\\"\"\n");
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("warning: custom is not a well-known record key"));
+
+    let output = selfie(&["publish", "not a name", "nostr", "npub1"]);
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,201 @@
+#![cfg(feature = "cloudflare")]
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::server::{replay_server, CapturedRequest, HttpReply};
+use selfie_records_sdk::publish::{CloudflareProvider, DnsProvider, ProviderError, SelfieRecord, TxtRecordSet};
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::SelfieRecordsSDK;
+
+const ZONES: &str = include_str!("fixtures/cloudflare/zones.json");
+const ZONES_EMPTY: &str = include_str!("fixtures/cloudflare/zones_empty.json");
+const RECORDS_EMPTY: &str = include_str!("fixtures/cloudflare/records_empty.json");
+const RECORDS_CURRENT: &str = include_str!("fixtures/cloudflare/records_current.json");
+const RECORDS_STALE: &str = include_str!("fixtures/cloudflare/records_stale.json");
+const RECORDS_PAGE1: &str = include_str!("fixtures/cloudflare/records_page1.json");
+const RECORDS_PAGE2: &str = include_str!("fixtures/cloudflare/records_page2.json");
+const RECORD: &str = include_str!("fixtures/cloudflare/record.json");
+const DELETED: &str = include_str!("fixtures/cloudflare/deleted.json");
+const AUTH_ERROR: &str = include_str!("fixtures/cloudflare/auth_error.json");
+const IDENTICAL_RECORD: &str = include_str!("fixtures/cloudflare/identical_record.json");
+const RATE_LIMITED: &str = include_str!("fixtures/cloudflare/rate_limited.json");
+
+const TOKEN: &str = "cf-test-token";
+const ZONE_ID: &str = "023e105f4ecef8ad9ca31a8372d0c353";
+const NAME: &str = "alice.user._bitcoin-payment.example.com";
+
+type Captured = Arc<Mutex<Vec<CapturedRequest>>>;
+
+fn provider(script: Vec<HttpReply>) -> (CloudflareProvider, Captured) {
+    let (base, requests) = replay_server(script);
+    (CloudflareProvider::new(TOKEN).base_url(&format!("{}/client/v4", base)), requests)
+}
+
+fn request_lines(requests: &Captured) -> Vec<String> {
+    requests
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|request| request.request_line().trim_end_matches(" HTTP/1.1").to_string())
+        .collect()
+}
+
+fn records_path(name: &str) -> String {
+    format!("GET /client/v4/zones/{}/dns_records?type=TXT&name={}&page=1&per_page=100", ZONE_ID, name)
+}
+
+fn body(request: &CapturedRequest) -> serde_json::Value {
+    serde_json::from_slice(&request.body).unwrap()
+}
+
+#[test]
+fn publish_via_creates_the_record_under_the_scheme_name() {
+    let (cloudflare, requests) = provider(vec![
+        HttpReply::json(200, ZONES),
+        HttpReply::json(200, RECORDS_EMPTY),
+        HttpReply::json(200, RECORD),
+    ]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new()));
+
+    let record = SelfieRecord::new("alice@Example.com", "bitcoin-payment", "bitcoin:bc1qalice");
+    sdk.publish_via(&cloudflare, &record).unwrap();
+
+    assert_eq!(
+        request_lines(&requests),
+        [
+            "GET /client/v4/zones?name=example.com".to_string(),
+            records_path("alice.user._bitcoin-payment.example.com"),
+            format!("POST /client/v4/zones/{}/dns_records", ZONE_ID),
+        ]
+    );
+    let requests = requests.lock().unwrap();
+    assert!(requests.iter().all(|request| request.header("authorization") == Some("Bearer cf-test-token")));
+    assert_eq!(
+        body(&requests[2]),
+        serde_json::json!({ "type": "TXT", "name": NAME, "content": "bitcoin:bc1qalice", "ttl": 3600 })
+    );
+}
+
+#[tokio::test]
+async fn upsert_of_the_published_value_makes_no_changes() {
+    let (cloudflare, requests) = provider(vec![HttpReply::json(200, ZONES), HttpReply::json(200, RECORDS_CURRENT)]);
+
+    cloudflare.upsert_txt("example.com", NAME, &["bitcoin:bc1qalice".to_string()], 3600).await.unwrap();
+
+    assert_eq!(request_lines(&requests), ["GET /client/v4/zones?name=example.com".to_string(), records_path(NAME)]);
+}
+
+#[tokio::test]
+async fn upsert_rewrites_stale_records_and_deletes_leftovers() {
+    let (cloudflare, requests) = provider(vec![
+        HttpReply::json(200, ZONES),
+        HttpReply::json(200, RECORDS_STALE),
+        HttpReply::json(200, RECORD),
+        HttpReply::json(200, DELETED),
+    ]);
+
+    cloudflare.upsert_txt("example.com", NAME, &["bitcoin:bc1qalice".to_string()], 3600).await.unwrap();
+
+    assert_eq!(
+        request_lines(&requests),
+        [
+            "GET /client/v4/zones?name=example.com".to_string(),
+            records_path(NAME),
+            format!("PUT /client/v4/zones/{}/dns_records/9a7806061c88ada191ed06f989cc3dac", ZONE_ID),
+            format!("DELETE /client/v4/zones/{}/dns_records/372e67954025e0ba6aaa6d586b9e0b59", ZONE_ID),
+        ]
+    );
+    assert_eq!(body(&requests.lock().unwrap()[2])["content"], "bitcoin:bc1qalice");
+}
+
+#[tokio::test]
+async fn list_txt_follows_pagination_and_groups_by_name() {
+    let (cloudflare, requests) = provider(vec![
+        HttpReply::json(200, ZONES),
+        HttpReply::json(200, RECORDS_PAGE1),
+        HttpReply::json(200, RECORDS_PAGE2),
+    ]);
+
+    let sets = cloudflare.list_txt("example.com").await.unwrap();
+
+    assert_eq!(
+        sets,
+        [
+            TxtRecordSet { name: "_nostr.example.com".to_string(), values: vec!["npub1example".to_string()], ttl: 1 },
+            TxtRecordSet {
+                name: "_pgp.example.com".to_string(),
+                values: vec!["openpgp4fpr:0123456789abcdef".to_string(), "openpgp4fpr:fedcba9876543210".to_string()],
+                ttl: 300,
+            },
+        ]
+    );
+    assert_eq!(
+        request_lines(&requests)[2],
+        format!("GET /client/v4/zones/{}/dns_records?type=TXT&page=2&per_page=100", ZONE_ID)
+    );
+}
+
+#[tokio::test]
+async fn delete_txt_removes_every_value() {
+    let (cloudflare, requests) = provider(vec![
+        HttpReply::json(200, ZONES),
+        HttpReply::json(200, RECORDS_STALE),
+        HttpReply::json(200, DELETED),
+        HttpReply::json(200, DELETED),
+    ]);
+
+    cloudflare.delete_txt("example.com", NAME).await.unwrap();
+
+    let lines = request_lines(&requests);
+    assert_eq!(lines.len(), 4);
+    assert!(lines[2..].iter().all(|line| line.starts_with("DELETE ")));
+}
+
+#[tokio::test]
+async fn unknown_zone_is_reported() {
+    let (cloudflare, _) = provider(vec![HttpReply::json(200, ZONES_EMPTY)]);
+
+    let error = cloudflare.list_txt("example.org").await.unwrap_err();
+    assert_eq!(error, ProviderError::ZoneNotFound("example.org".to_string()));
+}
+
+#[tokio::test]
+async fn rejected_token_is_unauthorized() {
+    let (cloudflare, _) = provider(vec![HttpReply::json(403, AUTH_ERROR)]);
+
+    let error = cloudflare.list_txt("example.com").await.unwrap_err();
+    assert_eq!(error, ProviderError::Unauthorized("Authentication error".to_string()));
+}
+
+#[tokio::test]
+async fn rate_limit_carries_retry_after() {
+    let (cloudflare, _) = provider(vec![HttpReply::json(429, RATE_LIMITED).header("Retry-After", "30")]);
+
+    let error = cloudflare.list_txt("example.com").await.unwrap_err();
+    assert_eq!(error, ProviderError::RateLimited { retry_after: Some(Duration::from_secs(30)) });
+}
+
+#[tokio::test]
+async fn api_errors_keep_the_cloudflare_code() {
+    let (cloudflare, _) = provider(vec![
+        HttpReply::json(200, ZONES),
+        HttpReply::json(200, RECORDS_EMPTY),
+        HttpReply::json(400, IDENTICAL_RECORD),
+    ]);
+
+    let error = cloudflare.upsert_txt("example.com", NAME, &["bitcoin:bc1qalice".to_string()], 3600).await.unwrap_err();
+    assert_eq!(error, ProviderError::Api { code: 81058, message: "An identical record already exists.".to_string() });
+}
+
+#[test]
+fn publish_via_rejects_invalid_identifiers_before_calling_the_provider() {
+    let (cloudflare, requests) = provider(Vec::new());
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new()));
+
+    let error = sdk.publish_via(&cloudflare, &SelfieRecord::new("localhost", "pgp", "openpgp4fpr:00")).unwrap_err();
+    assert!(matches!(error, ProviderError::InvalidRecord(_)));
+    assert!(requests.lock().unwrap().is_empty());
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use common::server::records_server;
+use selfie_records_sdk::{DirectResolver, LookupOptions, SelfieError, SelfieRecordsSDK};
+use trust_dns_proto::rr::rdata::TXT;
+use trust_dns_proto::rr::{Name, RData, Record};
+
+const QNAME: &str = "_bitcoin-payment.example.com";
+
+fn name(name: &str) -> Name {
+    Name::from_str(&format!("{}.", name)).unwrap()
+}
+
+fn alias(owner: &str, target: &str) -> Record {
+    Record::from_rdata(name(owner), 300, RData::CNAME(name(target)))
+}
+
+fn txt(owner: &str, value: &str) -> Record {
+    Record::from_rdata(name(owner), 300, RData::TXT(TXT::new(vec![value.to_string()])))
+}
+
+/// `QNAME` aliased through `hops` names `0.example.net`, `1.example.net`, ...
+/// with the last one holding a TXT record.
+fn chain(hops: usize) -> (Vec<String>, Vec<Record>) {
+    let names: Vec<String> =
+        std::iter::once(QNAME.to_string()).chain((0..hops).map(|i| format!("{}.example.net", i))).collect();
+    let mut records: Vec<Record> = names.windows(2).map(|pair| alias(&pair[0], &pair[1])).collect();
+    records.push(txt(names.last().unwrap(), "bitcoin:bc1qexample"));
+    (names, records)
+}
+
+fn lookup(resolver: DirectResolver) -> Result<Vec<String>, SelfieError> {
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(resolver));
+    let options = LookupOptions::new().attempts(3);
+    let response = sdk.get_records_response("example.com", Some(vec!["bitcoin-payment"]), None, &options);
+    let result = response.get("bitcoin-payment").unwrap().clone();
+    match result.error {
+        Some(error) => {
+            assert_eq!(result.attempts, Some(1), "{} should not be retried", error);
+            Err(error)
+        }
+        None => Ok(result.value.into_iter().collect()),
+    }
+}
+
+#[test]
+fn aliases_are_followed_to_the_record() {
+    let (_, records) = chain(3);
+    let server = records_server(records);
+
+    assert_eq!(lookup(DirectResolver::new(server)), Ok(vec!["bitcoin:bc1qexample".to_string()]));
+}
+
+#[test]
+fn a_loop_reports_the_whole_chain() {
+    let server = records_server(vec![
+        alias(QNAME, "a.example.net"),
+        alias("a.example.net", "b.example.net"),
+        alias("b.example.net", "a.example.net"),
+        txt("a.example.net", "never reached"),
+    ]);
+
+    let error = lookup(DirectResolver::new(server)).unwrap_err();
+    let chain = [QNAME, "a.example.net", "b.example.net", "a.example.net"].map(String::from).to_vec();
+    assert_eq!(error, SelfieError::CnameLoop { chain });
+    assert_eq!(
+        error.to_string(),
+        "CNAME loop: _bitcoin-payment.example.com -> a.example.net -> b.example.net -> a.example.net"
+    );
+}
+
+#[test]
+fn a_name_aliased_to_itself_is_a_loop() {
+    let server = records_server(vec![alias(QNAME, QNAME)]);
+
+    let error = lookup(DirectResolver::new(server)).unwrap_err();
+    assert_eq!(error, SelfieError::CnameLoop { chain: vec![QNAME.to_string(), QNAME.to_string()] });
+}
+
+#[test]
+fn chains_past_the_default_limit_fail() {
+    let (_, records) = chain(8);
+    assert!(lookup(DirectResolver::new(records_server(records))).is_ok());
+
+    let (names, records) = chain(9);
+    let error = lookup(DirectResolver::new(records_server(records))).unwrap_err();
+    assert_eq!(error, SelfieError::CnameChainTooLong { chain: names });
+}
+
+#[test]
+fn the_limit_is_configurable() {
+    let (names, records) = chain(2);
+    let server = records_server(records);
+
+    let error = lookup(DirectResolver::new(server).max_cname_chain(1)).unwrap_err();
+    assert_eq!(error, SelfieError::CnameChainTooLong { chain: names });
+    assert!(lookup(DirectResolver::new(server).max_cname_chain(2)).is_ok());
+}
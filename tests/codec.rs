@@ -0,0 +1,331 @@
+#![cfg(all(feature = "cbor", feature = "msgpack", feature = "signatures"))]
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use selfie_records_sdk::signature::SignatureStatus;
+use selfie_records_sdk::{
+    CrossCheck, KeyResult, NameError, ParseError, QueryMeta, RawRecord, RecordsResponse, Route, SelfieError, ServerInfo, Source,
+    TimeoutBudget, Transport, ValueEncodingIssue, WireInfo,
+};
+
+// Fixtures are never regenerated: when the encoder starts writing more,
+// the encoding goes into the next numbered fixture, and every earlier one
+// must keep decoding.
+/// `first_response()` as the first release of the format wrote it.
+const FIRST_CBOR: &[u8] = include_bytes!("fixtures/codec/response.cbor");
+const FIRST_MSGPACK: &[u8] = include_bytes!("fixtures/codec/response.msgpack");
+/// `response()` as the encoder writes it now.
+const GOLDEN_CBOR: &[u8] = include_bytes!("fixtures/codec/response-2.cbor");
+const GOLDEN_MSGPACK: &[u8] = include_bytes!("fixtures/codec/response-2.msgpack");
+
+fn error(error: SelfieError) -> KeyResult {
+    KeyResult { error: Some(error), source: Some(Source::Dns), attempts: Some(3), ..KeyResult::default() }
+}
+
+/// A response that sets every field at least once.
+fn response() -> RecordsResponse {
+    let mut response = RecordsResponse::default();
+    response.insert(
+        "bitcoin-payment",
+        KeyResult {
+            value: Some("bitcoin:bc1qexample?amount=0.01".to_string()),
+            raw_value: Some(b"bitcoin:bc1qexample?amount=0.01".to_vec()),
+            raw_records: vec![RawRecord::new(["bitcoin:bc1qexample", "?amount=0.01"])],
+            source: Some(Source::Dns),
+            attempts: Some(1),
+            wire: Some(WireInfo {
+                response_size: Some(612),
+                truncated: true,
+                transport_used: Transport::Tcp,
+                edns_udp_size: Some(1232),
+                ttl: Some(300),
+                response_code: Some(0),
+                server: None,
+            }),
+            signature: Some(SignatureStatus::Verified),
+            resolved_at: Some(UNIX_EPOCH + Duration::from_nanos(1_700_000_000_123_456_789)),
+            ttl: Some(Duration::from_secs(300)),
+            record_version: Some(2),
+            route: Some(Route::Key),
+            resolver: Some("10.0.0.53:53".to_string()),
+            query: Some(QueryMeta {
+                fqdn: "_bitcoin-payment.example.com".to_string(),
+                nameserver: Some("10.0.0.53:53".parse().unwrap()),
+                rtt: Duration::from_micros(43_210),
+                attempts: 1,
+            }),
+            ..KeyResult::default()
+        },
+    );
+    response.insert(
+        "nostr",
+        KeyResult {
+            value: Some("npub1example".to_string()),
+            source: Some(Source::Cache),
+            stale: true,
+            offline: true,
+            backoff: Some(Duration::from_millis(250)),
+            signature: Some(SignatureStatus::NoKey),
+            route: Some(Route::Suffix("corp.example.com".to_string())),
+            ..KeyResult::default()
+        },
+    );
+    response.insert("pgp", KeyResult { value: Some("https://example.com/key.asc".to_string()), source: Some(Source::Override), ..KeyResult::default() });
+    response.insert("node-uri", error(SelfieError::NoRecords));
+    response.insert(
+        "lightning",
+        KeyResult {
+            value: Some("lnurl1\u{fffd}".to_string()),
+            source: Some(Source::Dns),
+            route: Some(Route::Default),
+            encoding_issue: Some(ValueEncodingIssue::NotUtf8),
+            raw_value: Some(b"lnurl1\xff".to_vec()),
+            raw_records: vec![RawRecord::new([&b"lnurl1\xff"[..]])],
+            parse_error: Some(ParseError::new("not bech32")),
+            ..KeyResult::default()
+        },
+    );
+    response.insert(
+        "note",
+        KeyResult {
+            value: Some("hello\u{1b}[2J".to_string()),
+            encoding_issue: Some(ValueEncodingIssue::ControlCharacters),
+            ..KeyResult::default()
+        },
+    );
+    response.insert(
+        "lnurl",
+        error(SelfieError::Timeout(TimeoutBudget::Key { budget: Duration::from_secs(2), attempts: 2 })),
+    );
+    response.insert(
+        "dnssec",
+        error(SelfieError::RateLimited { server: "https://dns.example/dns-query".to_string(), retry_after: Some(Duration::from_secs(30)) }),
+    );
+    response.insert(
+        "bad",
+        error(SelfieError::InvalidName { name: "-bad.example.com".to_string(), reason: NameError::InvalidLabel { label: "-bad".to_string() } }),
+    );
+    response.insert("record", error(SelfieError::InvalidRecord { key: "record".to_string(), reason: "not a URI".to_string() }));
+    response
+}
+
+#[test]
+fn responses_round_trip() {
+    let response = response();
+    assert_eq!(RecordsResponse::from_cbor(&response.to_cbor()).unwrap(), response);
+    assert_eq!(RecordsResponse::from_msgpack(&response.to_msgpack()).unwrap(), response);
+
+    let empty = RecordsResponse::default();
+    assert_eq!(RecordsResponse::from_cbor(&empty.to_cbor()).unwrap(), empty);
+    assert_eq!(RecordsResponse::from_msgpack(&empty.to_msgpack()).unwrap(), empty);
+}
+
+#[test]
+fn every_error_kind_round_trips() {
+    let errors = [
+        SelfieError::InvalidName { name: "a".repeat(300), reason: NameError::TooLong { max: 253 } },
+        SelfieError::InvalidName { name: "x@".to_string(), reason: NameError::MissingDomain },
+        SelfieError::Timeout(TimeoutBudget::Global { timeout: Duration::from_secs(5), attempts: 4 }),
+        SelfieError::Timeout(TimeoutBudget::Call { deadline: Duration::from_secs(3), attempts: 0 }),
+        SelfieError::RateLimited { server: "8.8.8.8".to_string(), retry_after: None },
+        SelfieError::Dnssec("bogus signature".to_string()),
+        SelfieError::Offline,
+        SelfieError::InvalidSignature,
+        SelfieError::CrossCheckMismatch { other_values: vec!["bitcoin:bc1qother".to_string()] },
+        SelfieError::CnameLoop { chain: vec!["a.example.com".to_string(), "b.example.com".to_string(), "a.example.com".to_string()] },
+        SelfieError::CnameChainTooLong { chain: (0..10).map(|i| format!("{}.example.com", i)).collect() },
+        SelfieError::Resolver("connection refused".to_string()),
+        SelfieError::BudgetExceeded { max_queries: 32 },
+        SelfieError::MissingChunk { index: 2, count: 4 },
+        SelfieError::ChunkDigestMismatch,
+        SelfieError::NxDomain,
+        SelfieError::InvalidNameserver { server: "dns.example".to_string() },
+        SelfieError::Disagreement { quorum: 2, answers: vec!["1.1.1.1:53: [\"a\"]".to_string(), "8.8.8.8:53: [\"b\"]".to_string()] },
+        SelfieError::MultipleRecords { count: 2 },
+        SelfieError::NotABitcoinUri { values: vec!["lightning:lnbc1".to_string()] },
+        SelfieError::FingerprintMismatch { expected: vec!["59E1187D54EA5CF2FCD2919B9B34E6136E5F37D6".to_string()], fetched: Vec::new() },
+        SelfieError::InvalidKey { key: "my key!".to_string() },
+        SelfieError::NameserverResolutionFailed { host: "dns.example".to_string(), reason: "no addresses".to_string() },
+        SelfieError::ProxyUnreachable { proxy: "127.0.0.1:9050".to_string(), reason: "connection refused".to_string() },
+        SelfieError::ResponseTooLarge { name: "_pgp.example.com".to_string(), reason: "12 records, at most 8".to_string() },
+        SelfieError::TlsCertificate { server: "https://dns.example/dns-query".to_string(), reason: "unknown issuer".to_string() },
+        SelfieError::DnssecInsecure("insecure (no DS for example.com. at its parent)".to_string()),
+    ];
+    for error in errors {
+        let mut response = RecordsResponse::default();
+        response.insert("key", KeyResult { error: Some(error.clone()), ..KeyResult::default() });
+        let decoded = RecordsResponse::from_cbor(&response.to_cbor()).unwrap();
+        assert_eq!(decoded.get("key").unwrap().error, Some(error.clone()));
+        let decoded = RecordsResponse::from_msgpack(&response.to_msgpack()).unwrap();
+        assert_eq!(decoded.get("key").unwrap().error, Some(error));
+    }
+}
+
+#[test]
+fn cross_check_outcomes_round_trip() {
+    for outcome in [
+        CrossCheck::Verified,
+        CrossCheck::Mismatch { other_values: vec!["a".to_string(), "b".to_string()] },
+        CrossCheck::Mismatch { other_values: Vec::new() },
+        CrossCheck::Unavailable { reason: "timed out after 5000ms".to_string() },
+    ] {
+        let mut response = RecordsResponse::default();
+        response.insert("key", KeyResult { value: Some("a".to_string()), cross_check: Some(outcome), ..KeyResult::default() });
+        assert_eq!(RecordsResponse::from_cbor(&response.to_cbor()).unwrap(), response);
+        assert_eq!(RecordsResponse::from_msgpack(&response.to_msgpack()).unwrap(), response);
+    }
+}
+
+#[test]
+fn fallback_results_round_trip() {
+    let mut response = RecordsResponse::default();
+    let result = KeyResult { value: Some("a".to_string()), used_fallback_resolver: true, ..KeyResult::default() };
+    response.insert("key", result);
+    assert_eq!(RecordsResponse::from_cbor(&response.to_cbor()).unwrap(), response);
+    assert_eq!(RecordsResponse::from_msgpack(&response.to_msgpack()).unwrap(), response);
+}
+
+#[test]
+fn chunked_results_round_trip() {
+    let mut response = RecordsResponse::default();
+    response.insert("pgp", KeyResult { value: Some("a".repeat(600)), chunked: true, ..KeyResult::default() });
+    assert_eq!(RecordsResponse::from_cbor(&response.to_cbor()).unwrap(), response);
+    assert_eq!(RecordsResponse::from_msgpack(&response.to_msgpack()).unwrap(), response);
+}
+
+#[test]
+fn authenticated_results_round_trip() {
+    let mut response = RecordsResponse::default();
+    response.insert("pgp", KeyResult { value: Some("a".to_string()), authenticated: true, ..KeyResult::default() });
+    assert_eq!(RecordsResponse::from_cbor(&response.to_cbor()).unwrap(), response);
+    assert_eq!(RecordsResponse::from_msgpack(&response.to_msgpack()).unwrap(), response);
+}
+
+#[test]
+fn answering_servers_round_trip() {
+    let address = "[2001:db8::53]:5353".parse().unwrap();
+    let mut response = RecordsResponse::default();
+    let result = KeyResult {
+        value: Some("a".to_string()),
+        wire: Some(WireInfo { transport_used: Transport::Tcp, server: Some(address), ..WireInfo::default() }),
+        answered_by: Some(ServerInfo { address, transport: Transport::Tcp }),
+        ..KeyResult::default()
+    };
+    response.insert("key", result);
+    assert_eq!(RecordsResponse::from_cbor(&response.to_cbor()).unwrap(), response);
+    assert_eq!(RecordsResponse::from_msgpack(&response.to_msgpack()).unwrap(), response);
+}
+
+#[test]
+fn query_counts_round_trip() {
+    let mut response = RecordsResponse::default();
+    response.insert("key", KeyResult { value: Some("a".to_string()), ..KeyResult::default() });
+    response.queries_issued = 7;
+    assert_eq!(RecordsResponse::from_cbor(&response.to_cbor()).unwrap(), response);
+    assert_eq!(RecordsResponse::from_msgpack(&response.to_msgpack()).unwrap(), response);
+}
+
+#[test]
+fn encodings_match_the_golden_fixtures() {
+    assert_eq!(response().to_cbor(), GOLDEN_CBOR);
+    assert_eq!(response().to_msgpack(), GOLDEN_MSGPACK);
+    assert_eq!(RecordsResponse::from_cbor(GOLDEN_CBOR).unwrap(), response());
+    assert_eq!(RecordsResponse::from_msgpack(GOLDEN_MSGPACK).unwrap(), response());
+}
+
+/// The response of the first fixtures.
+fn first_response() -> RecordsResponse {
+    let mut response = RecordsResponse::default();
+    response.insert(
+        "bitcoin-payment",
+        KeyResult {
+            value: Some("bitcoin:bc1qexample?amount=0.01".to_string()),
+            source: Some(Source::Dns),
+            attempts: Some(1),
+            wire: Some(WireInfo {
+                response_size: Some(612),
+                truncated: true,
+                transport_used: Transport::Tcp,
+                edns_udp_size: Some(1232),
+                ttl: Some(300),
+                ..WireInfo::default()
+            }),
+            signature: Some(SignatureStatus::Verified),
+            resolved_at: Some(UNIX_EPOCH + Duration::from_nanos(1_700_000_000_123_456_789)),
+            ttl: Some(Duration::from_secs(300)),
+            ..KeyResult::default()
+        },
+    );
+    response.insert(
+        "nostr",
+        KeyResult {
+            value: Some("npub1example".to_string()),
+            source: Some(Source::Cache),
+            stale: true,
+            offline: true,
+            backoff: Some(Duration::from_millis(250)),
+            signature: Some(SignatureStatus::NoKey),
+            ..KeyResult::default()
+        },
+    );
+    response.insert("pgp", KeyResult { value: Some("https://example.com/key.asc".to_string()), source: Some(Source::Override), ..KeyResult::default() });
+    response.insert("node-uri", error(SelfieError::NoRecords));
+    response.insert(
+        "lnurl",
+        error(SelfieError::Timeout(TimeoutBudget::Key { budget: Duration::from_secs(2), attempts: 2 })),
+    );
+    response.insert(
+        "dnssec",
+        error(SelfieError::RateLimited { server: "https://dns.example/dns-query".to_string(), retry_after: Some(Duration::from_secs(30)) }),
+    );
+    response.insert(
+        "bad",
+        error(SelfieError::InvalidName { name: "-bad.example.com".to_string(), reason: NameError::InvalidLabel { label: "-bad".to_string() } }),
+    );
+    response.insert("record", error(SelfieError::InvalidRecord { key: "record".to_string(), reason: "not a URI".to_string() }));
+    response
+}
+
+#[test]
+fn the_first_fixtures_still_decode() {
+    assert_eq!(RecordsResponse::from_cbor(FIRST_CBOR).unwrap(), first_response());
+    assert_eq!(RecordsResponse::from_msgpack(FIRST_MSGPACK).unwrap(), first_response());
+}
+
+#[test]
+fn documents_start_with_the_version() {
+    // {0: 1, 1: {}}
+    assert_eq!(RecordsResponse::default().to_cbor(), [0xa2, 0x00, 0x01, 0x01, 0xa0]);
+    assert_eq!(RecordsResponse::default().to_msgpack(), [0x82, 0x00, 0x01, 0x01, 0x80]);
+}
+
+#[test]
+fn malformed_input_is_rejected() {
+    let cbor = response().to_cbor();
+    assert!(RecordsResponse::from_cbor(&cbor[..cbor.len() - 1]).is_err());
+    assert!(RecordsResponse::from_cbor(&[cbor.as_slice(), &[0x00]].concat()).is_err());
+    assert!(RecordsResponse::from_cbor(&[]).is_err());
+    // Version 2.
+    assert!(RecordsResponse::from_cbor(&[0xa2, 0x00, 0x02, 0x01, 0xa0]).is_err());
+    // A map claiming far more entries than there are bytes.
+    assert!(RecordsResponse::from_cbor(&[0xbb, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).is_err());
+
+    let msgpack = response().to_msgpack();
+    assert!(RecordsResponse::from_msgpack(&msgpack[..msgpack.len() - 1]).is_err());
+    assert!(RecordsResponse::from_msgpack(&[msgpack.as_slice(), &[0xc0]].concat()).is_err());
+    assert!(RecordsResponse::from_msgpack(&[0x82, 0x00, 0x02, 0x01, 0x80]).is_err());
+    assert!(RecordsResponse::from_msgpack(&[0xdf, 0xff, 0xff, 0xff, 0xff]).is_err());
+}
+
+#[test]
+fn unknown_fields_and_error_kinds_are_tolerated() {
+    // {0: 1, 1: {"k": {0: "v", 99: true, 1: {0: "quota"}}}, 7: "later"}
+    let cbor = [
+        0xa3, 0x00, 0x01, 0x01, 0xa1, 0x61, b'k', 0xa3, 0x00, 0x61, b'v', 0x18, 99, 0xf5, 0x01, 0xa1, 0x00, 0x65,
+        b'q', b'u', b'o', b't', b'a', 0x07, 0x65, b'l', b'a', b't', b'e', b'r',
+    ];
+    let response = RecordsResponse::from_cbor(&cbor).unwrap();
+    let result = response.get("k").unwrap();
+    assert_eq!(result.value.as_deref(), Some("v"));
+    assert!(matches!(&result.error, Some(SelfieError::Resolver(message)) if message.contains("quota")));
+}
@@ -0,0 +1,124 @@
+//! Helpers for building signed DNSSEC fixtures with throwaway ed25519 keys.
+
+#![allow(dead_code)]
+
+pub mod server;
+#[cfg(feature = "tls")]
+pub mod tls;
+
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use trust_dns_proto::rr::dnssec::rdata::{DNSSECRData, DNSSECRecordType, DNSKEY, DS, SIG};
+use selfie_records_sdk::dnssec::TrustAnchor;
+use trust_dns_proto::rr::dnssec::{tbs, Algorithm, DigestType};
+use trust_dns_proto::rr::{DNSClass, Name, RData, Record, RecordType};
+
+pub const INCEPTION: u32 = 1_700_000_000;
+pub const EXPIRATION: u32 = 4_000_000_000;
+pub const TTL: u32 = 3600;
+
+pub struct ZoneKey {
+    pub zone: Name,
+    pair: Ed25519KeyPair,
+    pub dnskey: DNSKEY,
+}
+
+impl ZoneKey {
+    pub fn new(zone: &str, seed: u8) -> Self {
+        let pair = Ed25519KeyPair::from_seed_unchecked(&[seed; 32]).unwrap();
+        let dnskey = DNSKEY::new(true, true, false, Algorithm::ED25519, pair.public_key().as_ref().to_vec());
+        ZoneKey { zone: Name::from_ascii(zone).unwrap(), pair, dnskey }
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        self.dnskey.calculate_key_tag().unwrap()
+    }
+
+    pub fn dnskey_record(&self) -> Record {
+        Record::from_rdata(self.zone.clone(), TTL, RData::DNSSEC(DNSSECRData::DNSKEY(self.dnskey.clone())))
+    }
+
+    /// The DS record the parent zone publishes for this key.
+    pub fn ds_record(&self) -> Record {
+        let digest = self.dnskey.to_digest(&self.zone, DigestType::SHA256).unwrap();
+        let ds = DS::new(self.key_tag(), Algorithm::ED25519, DigestType::SHA256, digest.as_ref().to_vec());
+        Record::from_rdata(self.zone.clone(), TTL, RData::DNSSEC(DNSSECRData::DS(ds)))
+    }
+
+    /// An RRSIG over `records`, which must form a single RRset.
+    pub fn sign(&self, records: &[Record]) -> Record {
+        self.sign_valid_between(records, INCEPTION, EXPIRATION)
+    }
+
+    pub fn sign_valid_between(&self, records: &[Record], inception: u32, expiration: u32) -> Record {
+        let name = records[0].name().clone();
+        let rtype = records[0].record_type();
+        let num_labels = name.num_labels();
+        let tbs = tbs::rrset_tbs(
+            &name,
+            DNSClass::IN,
+            num_labels,
+            rtype,
+            Algorithm::ED25519,
+            TTL,
+            expiration,
+            inception,
+            self.key_tag(),
+            &self.zone,
+            records,
+        )
+        .unwrap();
+        let signature = self.pair.sign(tbs.as_ref()).as_ref().to_vec();
+        let sig = SIG::new(
+            rtype,
+            Algorithm::ED25519,
+            num_labels,
+            TTL,
+            expiration,
+            inception,
+            self.key_tag(),
+            self.zone.clone(),
+            signature,
+        );
+        let mut record = Record::from_rdata(name, TTL, RData::DNSSEC(DNSSECRData::SIG(sig)));
+        record.set_rr_type(RecordType::DNSSEC(DNSSECRecordType::RRSIG));
+        record
+    }
+
+    /// A trust anchor set containing only this key.
+    pub fn anchor(&self) -> Vec<TrustAnchor> {
+        vec![TrustAnchor::from_dnskey(self.dnskey.clone())]
+    }
+}
+
+pub fn txt_record(name: &str, value: &str) -> Record {
+    let txt = trust_dns_proto::rr::rdata::TXT::new(vec![value.to_string()]);
+    Record::from_rdata(Name::from_ascii(name).unwrap(), TTL, RData::TXT(txt))
+}
+
+/// A TXT record with arbitrary bytes, one character-string per item.
+pub fn txt_bytes_record(name: &str, strings: &[&[u8]]) -> Record {
+    let txt = trust_dns_proto::rr::rdata::TXT::from_bytes(strings.to_vec());
+    Record::from_rdata(Name::from_ascii(name).unwrap(), TTL, RData::TXT(txt))
+}
+
+pub fn cname_record(name: &str, target: &str) -> Record {
+    Record::from_rdata(Name::from_ascii(name).unwrap(), TTL, RData::CNAME(Name::from_ascii(target).unwrap()))
+}
+
+pub fn ns_record(zone: &str, target: &str) -> Record {
+    Record::from_rdata(Name::from_ascii(zone).unwrap(), TTL, RData::NS(Name::from_ascii(target).unwrap()))
+}
+
+/// Records for the zone's DNSKEY set, self-signed.
+pub fn signed_keys(key: &ZoneKey) -> Vec<Record> {
+    let keys = vec![key.dnskey_record()];
+    let sig = key.sign(&keys);
+    keys.into_iter().chain([sig]).collect()
+}
+
+/// The child's DS record signed by its parent.
+pub fn signed_delegation(parent: &ZoneKey, child: &ZoneKey) -> Vec<Record> {
+    let ds = vec![child.ds_record()];
+    let sig = parent.sign(&ds);
+    ds.into_iter().chain([sig]).collect()
+}
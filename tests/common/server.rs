@@ -0,0 +1,413 @@
+//! Local DNS, DoH, webhook and provider API servers answering from a script.
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use trust_dns_proto::op::{Message, MessageType, ResponseCode};
+use trust_dns_proto::rr::rdata::TXT;
+use trust_dns_proto::rr::{RData, Record, RecordType};
+
+/// The answer to `query` with one TXT record holding `value`. A truncated
+/// answer carries the TC bit and no records, as a server whose answer did
+/// not fit would send.
+pub fn txt_response(query: &[u8], value: &str, truncated: bool) -> Vec<u8> {
+    let mut response = Message::from_vec(query).unwrap();
+    response.set_message_type(MessageType::Response).set_truncated(truncated);
+    if !truncated {
+        let name = response.queries()[0].name().clone();
+        // A TXT string holds at most 255 bytes.
+        let strings = value.as_bytes().chunks(255).map(|chunk| String::from_utf8(chunk.to_vec()).unwrap()).collect();
+        response.add_answer(Record::from_rdata(name, 300, RData::TXT(TXT::new(strings))));
+    }
+    response.to_vec().unwrap()
+}
+
+/// A DNS server on UDP and TCP answering every query with `value`, over UDP
+/// with the TC bit set when `truncate_udp` is on.
+pub fn dns_server(value: &str, truncate_udp: bool) -> SocketAddr {
+    dns_server_at(([127, 0, 0, 1], 0).into(), value, truncate_udp)
+}
+
+/// Like `dns_server`, listening on `addr`.
+pub fn dns_server_at(addr: SocketAddr, value: &str, truncate_udp: bool) -> SocketAddr {
+    let udp = UdpSocket::bind(addr).unwrap();
+    let addr = udp.local_addr().unwrap();
+    let tcp = TcpListener::bind(addr).unwrap();
+
+    let udp_value = value.to_string();
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        while let Ok((len, peer)) = udp.recv_from(&mut buffer) {
+            udp.send_to(&txt_response(&buffer[..len], &udp_value, truncate_udp), peer).unwrap();
+        }
+    });
+    let tcp_value = value.to_string();
+    thread::spawn(move || {
+        for mut stream in tcp.incoming().flatten() {
+            let mut len = [0; 2];
+            stream.read_exact(&mut len).unwrap();
+            let mut query = vec![0; u16::from_be_bytes(len) as usize];
+            stream.read_exact(&mut query).unwrap();
+            let response = txt_response(&query, &tcp_value, false);
+            stream.write_all(&(response.len() as u16).to_be_bytes()).unwrap();
+            stream.write_all(&response).unwrap();
+        }
+    });
+    addr
+}
+
+/// A DNS server on UDP answering each query with those of `records` that
+/// match its name and type, and with an empty answer otherwise. Like an
+/// authoritative server, it answers with a CNAME at the name instead and
+/// leaves following it to the client.
+pub fn records_server(records: Vec<Record>) -> SocketAddr {
+    serve_records(records, false)
+}
+
+/// Like `records_server`, answering NXDOMAIN for names that neither hold
+/// a record nor have one below them.
+pub fn zone_server(records: Vec<Record>) -> SocketAddr {
+    serve_records(records, true)
+}
+
+/// A DNS server on UDP answering every query with SERVFAIL.
+pub fn servfail_server() -> SocketAddr {
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = udp.local_addr().unwrap();
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        while let Ok((len, peer)) = udp.recv_from(&mut buffer) {
+            let mut response = Message::from_vec(&buffer[..len]).unwrap();
+            response.set_message_type(MessageType::Response).set_response_code(ResponseCode::ServFail);
+            udp.send_to(&response.to_vec().unwrap(), peer).unwrap();
+        }
+    });
+    addr
+}
+
+fn serve_records(records: Vec<Record>, nxdomain: bool) -> SocketAddr {
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = udp.local_addr().unwrap();
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        while let Ok((len, peer)) = udp.recv_from(&mut buffer) {
+            let mut response = Message::from_vec(&buffer[..len]).unwrap();
+            response.set_message_type(MessageType::Response);
+            let query = response.queries()[0].clone();
+            for record in &records {
+                let alias = record.record_type() == RecordType::CNAME;
+                if record.name() == query.name() && (alias || record.record_type() == query.query_type()) {
+                    response.add_answer(record.clone());
+                }
+            }
+            if nxdomain && !records.iter().any(|record| query.name().zone_of(record.name())) {
+                response.set_response_code(ResponseCode::NXDomain);
+            }
+            udp.send_to(&response.to_vec().unwrap(), peer).unwrap();
+        }
+    });
+    addr
+}
+
+/// One scripted DoH reply.
+#[derive(Debug, Clone, Copy)]
+pub struct DohReply {
+    pub status: u16,
+    pub headers: &'static str,
+    pub truncated: bool,
+}
+
+impl DohReply {
+    pub fn ok() -> Self {
+        DohReply { status: 200, headers: "", truncated: false }
+    }
+
+    pub fn status(status: u16, headers: &'static str) -> Self {
+        DohReply { status, headers, truncated: false }
+    }
+}
+
+pub const DOH_VALUE: &str = "bitcoin:bc1qexample";
+
+/// A DoH endpoint answering each connection with the next scripted reply,
+/// recording when every request arrived.
+pub fn doh_server(script: Vec<DohReply>) -> (String, Arc<Mutex<Vec<Instant>>>) {
+    let arrivals = Arc::new(Mutex::new(Vec::new()));
+    let recorded = arrivals.clone();
+    let url = serve_doh(script, move |request| recorded.lock().unwrap().push(request.0));
+    (url, arrivals)
+}
+
+/// Like `doh_server`, capturing each request instead. GET queries are
+/// answered too, from their `dns` parameter.
+pub fn doh_recording_server(script: Vec<DohReply>) -> (String, Arc<Mutex<Vec<CapturedRequest>>>) {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let captured = requests.clone();
+    let url = serve_doh(script, move |request| captured.lock().unwrap().push(request.1));
+    (url, requests)
+}
+
+fn serve_doh(script: Vec<DohReply>, mut record: impl FnMut((Instant, CapturedRequest)) + Send + 'static) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/dns-query", listener.local_addr().unwrap());
+
+    thread::spawn(move || {
+        for reply in script {
+            let (mut stream, _) = listener.accept().unwrap();
+            let arrived = Instant::now();
+            let (head, body) = read_http_request(&mut stream);
+            let request = CapturedRequest { head, body };
+            let query = match request.request_line().split_once("dns=") {
+                Some((_, param)) => {
+                    let param = param.split([' ', '&']).next().unwrap();
+                    data_encoding::BASE64URL_NOPAD.decode(param.as_bytes()).unwrap()
+                }
+                None => request.body.clone(),
+            };
+            record((arrived, request));
+
+            let body = match reply.status {
+                200 => txt_response(&query, DOH_VALUE, reply.truncated),
+                _ => Vec::new(),
+            };
+            let head = format!(
+                "HTTP/1.1 {} Scripted\r\nContent-Type: application/dns-message\r\nContent-Length: {}\r\n{}\r\n",
+                reply.status,
+                body.len(),
+                reply.headers
+            );
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(&body).unwrap();
+        }
+    });
+    url
+}
+
+/// A DoH endpoint keeping connections open unless the client asks to
+/// close them, answering every request on them with `DOH_VALUE`. The response to request number `cut_off`, counted
+/// from 0 across connections, stops halfway and its connection is closed.
+/// Returns the URL and the number of connections accepted.
+pub fn doh_keepalive_server(cut_off: Option<usize>) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/dns-query", listener.local_addr().unwrap());
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = connections.clone();
+    let requests = Arc::new(AtomicUsize::new(0));
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            accepted.fetch_add(1, Ordering::SeqCst);
+            let requests = requests.clone();
+            thread::spawn(move || answer_doh_keepalive(&mut stream, &requests, cut_off));
+        }
+    });
+    (url, connections)
+}
+
+/// Answers the requests on `stream` as `doh_keepalive_server` does.
+pub(crate) fn answer_doh_keepalive(stream: &mut (impl Read + Write), requests: &AtomicUsize, cut_off: Option<usize>) {
+    while let Some((head, query)) = try_read_http_request(stream) {
+        let body = txt_response(&query, DOH_VALUE, false);
+        let mut response =
+            format!("HTTP/1.1 200 OK\r\nContent-Type: application/dns-message\r\nContent-Length: {}\r\n\r\n", body.len()).into_bytes();
+        response.extend_from_slice(&body);
+        if Some(requests.fetch_add(1, Ordering::SeqCst)) == cut_off {
+            let _ = stream.write_all(&response[..response.len() / 2]);
+            return;
+        }
+        if stream.write_all(&response).and_then(|()| stream.flush()).is_err() || head.contains("connection: close") {
+            return;
+        }
+    }
+}
+
+/// A request received by `http_server`, `replay_server` or
+/// `doh_recording_server`.
+#[derive(Debug, Clone)]
+pub struct CapturedRequest {
+    /// Request line and headers, header names lowercased.
+    pub head: String,
+    pub body: Vec<u8>,
+}
+
+impl CapturedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.head.lines().find_map(|line| {
+            let (n, value) = line.split_once(':')?;
+            (n == name).then(|| value.trim())
+        })
+    }
+
+    /// The request line, e.g. `GET /zones?name=example.com HTTP/1.1`.
+    pub fn request_line(&self) -> &str {
+        self.head.lines().next().unwrap_or_default()
+    }
+}
+
+/// One scripted HTTP reply.
+#[derive(Debug, Clone)]
+pub struct HttpReply {
+    pub status: u16,
+    pub headers: String,
+    pub body: String,
+}
+
+impl HttpReply {
+    pub fn status(status: u16) -> Self {
+        HttpReply { status, headers: String::new(), body: String::new() }
+    }
+
+    pub fn json(status: u16, body: &str) -> Self {
+        HttpReply { status, headers: "Content-Type: application/json\r\n".to_string(), body: body.to_string() }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push_str(&format!("{}: {}\r\n", name, value));
+        self
+    }
+
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = body.to_string();
+        self
+    }
+}
+
+/// An HTTP endpoint answering each request with the next status of the
+/// script and an empty body, capturing what it received.
+pub fn http_server(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<CapturedRequest>>>) {
+    let (base, requests) = replay_server(statuses.into_iter().map(HttpReply::status).collect());
+    (format!("{}/hooks/selfie", base), requests)
+}
+
+/// An HTTP server replaying `script`, one reply per request, capturing what
+/// it received. Returns the server's base URL, without a path.
+pub fn replay_server(script: Vec<HttpReply>) -> (String, Arc<Mutex<Vec<CapturedRequest>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let captured = requests.clone();
+
+    thread::spawn(move || {
+        for reply in script {
+            let (mut stream, _) = listener.accept().unwrap();
+            let (head, body) = read_http_request(&mut stream);
+            captured.lock().unwrap().push(CapturedRequest { head, body });
+            let head = format!(
+                "HTTP/1.1 {} Scripted\r\nContent-Length: {}\r\n{}\r\n",
+                reply.status,
+                reply.body.len(),
+                reply.headers
+            );
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(reply.body.as_bytes()).unwrap();
+        }
+    });
+    (base, requests)
+}
+
+fn read_http_request(stream: &mut impl Read) -> (String, Vec<u8>) {
+    try_read_http_request(stream).expect("connection closed before the request was complete")
+}
+
+/// The next request on `stream`, `None` once the client closed it.
+pub(crate) fn try_read_http_request(stream: &mut impl Read) -> Option<(String, Vec<u8>)> {
+    let mut raw = Vec::new();
+    let mut buffer = [0; 1024];
+    loop {
+        let len = stream.read(&mut buffer).ok().filter(|&len| len > 0)?;
+        raw.extend_from_slice(&buffer[..len]);
+        if let Some(split) = raw.windows(4).position(|window| window == b"\r\n\r\n") {
+            let head: String = String::from_utf8_lossy(&raw[..split])
+                .lines()
+                .map(|line| match line.split_once(':') {
+                    Some((name, value)) => format!("{}:{}\n", name.to_ascii_lowercase(), value),
+                    None => format!("{}\n", line),
+                })
+                .collect();
+            let length: usize = head
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .map(|value| value.trim().parse().unwrap())
+                .unwrap_or(0);
+            if raw.len() >= split + 4 + length {
+                return Some((head, raw[split + 4..split + 4 + length].to_vec()));
+            }
+        }
+    }
+}
+
+/// A SOCKS5 proxy relaying CONNECT requests, asking for `credentials` when
+/// given, and recording each target as it was asked for: an address, or a
+/// host name the proxy resolves itself.
+pub fn socks5_proxy(credentials: Option<(&str, &str)>) -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let targets = Arc::new(Mutex::new(Vec::new()));
+    let recorded = targets.clone();
+    let credentials = credentials.map(|(username, password)| (username.as_bytes().to_vec(), password.as_bytes().to_vec()));
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let recorded = recorded.clone();
+            let credentials = credentials.clone();
+            thread::spawn(move || {
+                let _ = relay(stream, credentials, recorded);
+            });
+        }
+    });
+    (addr, targets)
+}
+
+fn relay(mut client: TcpStream, credentials: Option<(Vec<u8>, Vec<u8>)>, targets: Arc<Mutex<Vec<String>>>) -> std::io::Result<()> {
+    let read = |stream: &mut TcpStream, len: usize| -> std::io::Result<Vec<u8>> {
+        let mut buffer = vec![0; len];
+        stream.read_exact(&mut buffer)?;
+        Ok(buffer)
+    };
+    let greeting = read(&mut client, 2)?;
+    let methods = read(&mut client, greeting[1] as usize)?;
+    match &credentials {
+        Some((username, password)) if methods.contains(&2) => {
+            client.write_all(&[5, 2])?;
+            let ulen = read(&mut client, 2)?[1] as usize;
+            let offered_username = read(&mut client, ulen)?;
+            let plen = read(&mut client, 1)?[0] as usize;
+            let offered_password = read(&mut client, plen)?;
+            if offered_username != *username || offered_password != *password {
+                return client.write_all(&[1, 1]);
+            }
+            client.write_all(&[1, 0])?;
+        }
+        Some(_) => return client.write_all(&[5, 0xff]),
+        None => client.write_all(&[5, 0])?,
+    }
+
+    let request = read(&mut client, 4)?;
+    let target = match request[3] {
+        1 => std::net::IpAddr::from(<[u8; 4]>::try_from(read(&mut client, 4)?).unwrap()).to_string(),
+        4 => format!("[{}]", std::net::IpAddr::from(<[u8; 16]>::try_from(read(&mut client, 16)?).unwrap())),
+        _ => {
+            let len = read(&mut client, 1)?[0] as usize;
+            String::from_utf8(read(&mut client, len)?).unwrap()
+        }
+    };
+    let port = u16::from_be_bytes(<[u8; 2]>::try_from(read(&mut client, 2)?).unwrap());
+    let target = format!("{}:{}", target, port);
+    targets.lock().unwrap().push(target.clone());
+    let Ok(mut server) = TcpStream::connect(target.as_str()) else {
+        return client.write_all(&[5, 5, 0, 1, 0, 0, 0, 0, 0, 0]);
+    };
+    client.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0])?;
+
+    let (mut client_reader, mut server_writer) = (client.try_clone()?, server.try_clone()?);
+    thread::spawn(move || {
+        let _ = std::io::copy(&mut client_reader, &mut server_writer);
+        let _ = server_writer.shutdown(std::net::Shutdown::Write);
+    });
+    std::io::copy(&mut server, &mut client)?;
+    client.shutdown(std::net::Shutdown::Write)
+}
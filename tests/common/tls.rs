@@ -0,0 +1,182 @@
+//! TLS endpoints with a certificate for `localhost` and 127.0.0.1, issued
+//! by a throwaway CA that only `tls_config` trusts.
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use bytes::Bytes;
+use rustls::{Certificate, PrivateKey, ServerConfig, ServerConnection, StreamOwned};
+use selfie_records_sdk::TlsConfig;
+use tokio::sync::Semaphore;
+use tokio_rustls::TlsAcceptor;
+
+use super::server::{answer_doh_keepalive, txt_response, DOH_VALUE};
+
+pub const CA: &[u8] = include_bytes!("../fixtures/tls/ca.der");
+const CERTIFICATE: &[u8] = include_bytes!("../fixtures/tls/localhost.der");
+const KEY: &[u8] = include_bytes!("../fixtures/tls/localhost.key.der");
+
+pub type TlsStream = StreamOwned<ServerConnection, TcpStream>;
+
+/// Trusts the CA of the servers here, and nothing else.
+pub fn tls_config() -> TlsConfig {
+    TlsConfig::with_root_certificates(&[CA]).unwrap()
+}
+
+/// Accepts connections on 127.0.0.1 and hands each, once its handshake is
+/// done, to `handle` on a thread of its own. Returns the address and the
+/// number of handshakes completed.
+pub fn serve_tls(handle: impl Fn(&mut TlsStream) + Send + Sync + 'static) -> (SocketAddr, Arc<AtomicUsize>) {
+    let config = Arc::new(server_config());
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handshakes = Arc::new(AtomicUsize::new(0));
+    let completed = handshakes.clone();
+    let handle = Arc::new(handle);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let (config, completed, handle) = (config.clone(), completed.clone(), handle.clone());
+            thread::spawn(move || {
+                let mut stream = StreamOwned::new(ServerConnection::new(config).unwrap(), stream.unwrap());
+                while stream.conn.is_handshaking() {
+                    if stream.conn.complete_io(&mut stream.sock).is_err() {
+                        return;
+                    }
+                }
+                completed.fetch_add(1, Ordering::SeqCst);
+                handle(&mut stream);
+                stream.conn.send_close_notify();
+                let _ = stream.flush();
+            });
+        }
+    });
+    (addr, handshakes)
+}
+
+fn server_config() -> ServerConfig {
+    ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(vec![Certificate(CERTIFICATE.to_vec())], PrivateKey(KEY.to_vec()))
+        .unwrap()
+}
+
+/// Like `doh_keepalive_server`, over TLS at `https://localhost:{port}`.
+/// Returns the URL and the number of handshakes completed.
+pub fn doh_tls_server() -> (String, Arc<AtomicUsize>) {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let (addr, handshakes) = serve_tls(move |stream| answer_doh_keepalive(stream, &requests, None));
+    (format!("https://localhost:{}/dns-query", addr.port()), handshakes)
+}
+
+/// A DoT server answering every query on a connection with one TXT
+/// record holding `value`. Returns the address and the number of
+/// handshakes completed.
+pub fn dot_server(value: &'static str) -> (SocketAddr, Arc<AtomicUsize>) {
+    serve_tls(move |stream| loop {
+        let mut len = [0; 2];
+        if stream.read_exact(&mut len).is_err() {
+            return;
+        }
+        let mut query = vec![0; u16::from_be_bytes(len) as usize];
+        if stream.read_exact(&mut query).is_err() {
+            return;
+        }
+        let response = txt_response(&query, value, false);
+        let mut framed = (response.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(&response);
+        if stream.write_all(&framed).and_then(|()| stream.flush()).is_err() {
+            return;
+        }
+    })
+}
+
+/// A DoT server that holds the first `together` queries on a connection
+/// and answers them last first, then answers each as it comes, with one
+/// TXT record holding `value`. Returns the address and the number of
+/// handshakes completed.
+pub fn dot_pipelining_server(together: usize, value: &'static str) -> (SocketAddr, Arc<AtomicUsize>) {
+    serve_tls(move |stream| {
+        let (mut held, mut together) = (Vec::new(), together);
+        loop {
+            let mut len = [0; 2];
+            if stream.read_exact(&mut len).is_err() {
+                return;
+            }
+            let mut query = vec![0; u16::from_be_bytes(len) as usize];
+            if stream.read_exact(&mut query).is_err() {
+                return;
+            }
+            held.push(query);
+            if held.len() < together {
+                continue;
+            }
+            together = 1;
+            for query in held.drain(..).rev() {
+                let response = txt_response(&query, value, false);
+                let mut framed = (response.len() as u16).to_be_bytes().to_vec();
+                framed.extend_from_slice(&response);
+                if stream.write_all(&framed).and_then(|()| stream.flush()).is_err() {
+                    return;
+                }
+            }
+        }
+    })
+}
+
+/// A DoH server at `https://localhost:{port}` speaking only HTTP/2, which
+/// holds the first `together` requests on a connection until they have
+/// all arrived, and answers those after them at once, each with `DOH_VALUE`.
+/// Returns the URL and the number of handshakes completed.
+pub fn doh_http2_server(together: usize) -> (String, Arc<AtomicUsize>) {
+    let mut config = server_config();
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handshakes = Arc::new(AtomicUsize::new(0));
+    let completed = handshakes.clone();
+
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            while let Ok((stream, _)) = listener.accept().await {
+                let (acceptor, completed) = (acceptor.clone(), completed.clone());
+                tokio::spawn(async move {
+                    let Ok(stream) = acceptor.accept(stream).await else { return };
+                    completed.fetch_add(1, Ordering::SeqCst);
+                    let Ok(mut connection) = h2::server::handshake(stream).await else { return };
+                    // Closed once `together` requests have arrived.
+                    let arrived = Arc::new((AtomicUsize::new(0), Semaphore::new(0)));
+                    while let Some(Ok((request, mut respond))) = connection.accept().await {
+                        let arrived = arrived.clone();
+                        tokio::spawn(async move {
+                            let mut body = request.into_body();
+                            let mut query = Vec::new();
+                            while let Some(Ok(data)) = body.data().await {
+                                let _ = body.flow_control().release_capacity(data.len());
+                                query.extend_from_slice(&data);
+                            }
+                            let (count, gate) = &*arrived;
+                            if count.fetch_add(1, Ordering::SeqCst) + 1 == together {
+                                gate.close();
+                            }
+                            let _ = gate.acquire().await;
+
+                            let head = http::Response::builder().status(200).header("content-type", "application/dns-message").body(()).unwrap();
+                            let Ok(mut send) = respond.send_response(head, false) else { return };
+                            let _ = send.send_data(Bytes::from(txt_response(&query, DOH_VALUE, false)), true);
+                        });
+                    }
+                });
+            }
+        });
+    });
+    (format!("https://localhost:{}/dns-query", port), handshakes)
+}
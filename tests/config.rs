@@ -0,0 +1,163 @@
+mod common;
+
+use std::time::Duration;
+
+use common::server::dns_server;
+use selfie_records_sdk::config::{Config, ConfigError, DnssecMode, Transport};
+use selfie_records_sdk::{BuildError, NameserverSource, SdkBuilder};
+
+const FILE: &str = r#"
+# Shared settings for every host.
+nameservers = ["9.9.9.9", "149.112.112.112:5353"]
+timeout = 2.5
+attempts = 3
+keys = ['bitcoin-payment', "nostr"] # the ones we use
+
+[cache]
+ttl = 300
+offline = false
+
+[dnssec]
+mode = "require"
+"#;
+
+fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+}
+
+fn invalid_value(key: &str) -> impl Fn(&ConfigError) -> bool + '_ {
+    move |e| matches!(e, ConfigError::InvalidValue { key: k, .. } if k == key)
+}
+
+#[test]
+fn files_are_parsed() {
+    let config = Config::parse(FILE).unwrap();
+
+    assert_eq!(config.nameservers, Some(vec!["9.9.9.9:53".parse().unwrap(), "149.112.112.112:5353".parse().unwrap()]));
+    assert_eq!(config.transport, None);
+    assert_eq!(config.timeout, Some(Duration::from_millis(2500)));
+    assert_eq!(config.attempts, Some(3));
+    assert_eq!(config.keys, Some(vec!["bitcoin-payment".to_string(), "nostr".to_string()]));
+    assert_eq!(config.cache_ttl, Some(Duration::from_secs(300)));
+    assert_eq!(config.offline, Some(false));
+    assert_eq!(config.dnssec, Some(DnssecMode::Require));
+    assert_eq!(Config::parse("").unwrap(), Config::default());
+}
+
+#[test]
+fn environment_variables_are_parsed() {
+    let config = Config::from_vars(vars(&[
+        ("SELFIE_DNS", "9.9.9.9, 1.1.1.1"),
+        ("SELFIE_TIMEOUT", "10"),
+        ("SELFIE_KEYS", "pgp"),
+        ("SELFIE_OFFLINE", "1"),
+        ("SELFIE_TRANSPORT", "doh"),
+        ("SELFIE_DOH_URL", "http://127.0.0.1:8053/dns-query"),
+        ("SELFIE_DNSSEC", "report"),
+        ("SELFIE_WEBHOOK_SECRET", "not a setting"),
+        ("PATH", "/usr/bin"),
+    ]))
+    .unwrap();
+
+    assert_eq!(config.nameservers, Some(vec!["9.9.9.9:53".parse().unwrap(), "1.1.1.1:53".parse().unwrap()]));
+    assert_eq!(config.timeout, Some(Duration::from_secs(10)));
+    assert_eq!(config.keys, Some(vec!["pgp".to_string()]));
+    assert_eq!(config.offline, Some(true));
+    assert_eq!(config.transport, Some(Transport::Doh));
+    assert_eq!(config.doh_url.as_deref(), Some("http://127.0.0.1:8053/dns-query"));
+    assert_eq!(config.dnssec, Some(DnssecMode::Report));
+}
+
+#[test]
+fn flags_beat_variables_beat_the_file() {
+    let file = Config::parse(FILE).unwrap();
+    let env = Config::from_vars(vars(&[("SELFIE_TIMEOUT", "7"), ("SELFIE_ATTEMPTS", "1")])).unwrap();
+    let flags = Config { timeout: Some(Duration::from_secs(1)), ..Config::default() };
+
+    let config = file.clone().merge(env.clone()).merge(flags);
+    assert_eq!(config.timeout, Some(Duration::from_secs(1)));
+    assert_eq!(config.attempts, Some(1));
+    assert_eq!(config.cache_ttl, Some(Duration::from_secs(300)));
+
+    let config = file.merge(env);
+    assert_eq!(config.timeout, Some(Duration::from_secs(7)));
+    // Unset everywhere, so the built-in default applies.
+    assert_eq!(config.doh_url, None);
+}
+
+#[test]
+fn discover_reads_the_given_file() {
+    let path = std::env::temp_dir().join(format!("selfie-config-{}.toml", std::process::id()));
+    std::fs::write(&path, "attempts = 4\n").unwrap();
+
+    let config = Config::discover(Some(&path));
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(config.unwrap().attempts, Some(4));
+    assert!(matches!(Config::discover(Some(&path)), Err(ConfigError::Io { .. })));
+}
+
+#[test]
+fn invalid_values_name_their_key() {
+    let cases = [
+        ("timeout = \"soon\"", "timeout"),
+        ("timeout = -1", "timeout"),
+        ("attempts = 1.5", "attempts"),
+        ("nameservers = [\"dns.example\"]", "nameservers"),
+        ("nameservers = \"9.9.9.9\"", "nameservers"),
+        ("transport = \"quic\"", "transport"),
+        ("doh_url = \"ftp://dns.example/dns-query\"", "doh_url"),
+        ("[cache]\nttl = true", "cache.ttl"),
+        ("[dnssec]\nmode = \"maybe\"", "dnssec.mode"),
+    ];
+    for (text, key) in cases {
+        let error = Config::parse(text).unwrap_err();
+        assert!(invalid_value(key)(&error), "{}: {:?}", text, error);
+        assert!(error.to_string().starts_with(&format!("Invalid value for {}: ", key)), "{}", error);
+    }
+
+    let error = Config::from_vars(vars(&[("SELFIE_ATTEMPTS", "many")])).unwrap_err();
+    assert!(invalid_value("SELFIE_ATTEMPTS")(&error), "{:?}", error);
+}
+
+#[test]
+fn unknown_keys_and_bad_syntax_are_rejected() {
+    assert_eq!(Config::parse("[cache]\nsize = 10"), Err(ConfigError::UnknownKey("cache.size".to_string())));
+    assert_eq!(Config::parse("dns = \"9.9.9.9\""), Err(ConfigError::UnknownKey("dns".to_string())));
+    let syntax = |text| match Config::parse(text) {
+        Err(ConfigError::Parse { line, .. }) => line,
+        other => panic!("{:?} parsed as {:?}", text, other),
+    };
+    assert_eq!(syntax("attempts = 1\ntimeout"), 2);
+    assert_eq!(syntax("keys = [\"pgp\""), 1);
+    assert_eq!(syntax("attempts = 1 2"), 1);
+    assert_eq!(syntax("attempts = 1\nattempts = 2"), 2);
+}
+
+#[test]
+fn the_builder_applies_the_transport() {
+    let server = dns_server("bitcoin:bc1qconfig", false);
+    let config = Config::parse(&format!("nameservers = [\"{}\"]\ntimeout = 5", server)).unwrap();
+
+    let sdk = SdkBuilder::from_config(config.clone()).build().unwrap();
+    let response = sdk.get_records_response("example.com", Some(vec!["bitcoin-payment"]), None, &config.lookup_options());
+    let result = response.get("bitcoin-payment").unwrap();
+    assert_eq!(result.value.as_deref(), Some("bitcoin:bc1qconfig"), "{:?}", result.error);
+    assert_eq!(result.resolver, Some(server.to_string()));
+    assert_eq!(config.nameserver_source(), NameserverSource::Static(vec![server]));
+    assert_eq!(Config::default().nameserver_source(), NameserverSource::System);
+}
+
+#[test]
+fn settings_the_builder_cannot_apply_fail_the_build() {
+    let config = Config { transport: Some(Transport::Doh), ..Config::default() };
+    match SdkBuilder::from_config(config).build() {
+        Err(BuildError::InvalidConfig(e)) => assert!(invalid_value("doh_url")(&e), "{:?}", e),
+        other => panic!("{:?}", other.map(|_| ())),
+    }
+
+    let config = Config::parse("[dnssec]\ntrust_anchor = \"/nonexistent/root.key\"").unwrap();
+    match SdkBuilder::from_config(config).build() {
+        Err(BuildError::InvalidConfig(e)) => assert!(invalid_value("dnssec.trust_anchor")(&e), "{:?}", e),
+        other => panic!("{:?}", other.map(|_| ())),
+    }
+}
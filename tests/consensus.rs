@@ -0,0 +1,132 @@
+mod common;
+
+use std::net::{SocketAddr, UdpSocket};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common::server::{dns_server, dns_server_at, records_server};
+use selfie_records_sdk::consensus::{
+    Consensus, Nameserver, NameserverDiscovery, RecursiveDiscovery, ServerOutcome, ZoneServers,
+};
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{SelfieError, SelfieRecordsSDK};
+use trust_dns_proto::rr::{Name, RData, Record};
+
+/// Hands out a fixed set of nameservers, whatever the name.
+struct StaticDiscovery(Vec<Nameserver>);
+
+#[async_trait]
+impl NameserverDiscovery for StaticDiscovery {
+    async fn discover(&self, _qname: &str) -> Result<ZoneServers, SelfieError> {
+        Ok(ZoneServers { zone: "example.com".to_string(), nameservers: self.0.clone() })
+    }
+}
+
+fn nameserver(host: &str, addrs: &[SocketAddr]) -> Nameserver {
+    Nameserver { host: host.to_string(), addrs: addrs.to_vec() }
+}
+
+fn sdk(discovery: impl NameserverDiscovery + 'static) -> SelfieRecordsSDK {
+    SelfieRecordsSDK::builder()
+        .resolver(Arc::new(MockTxtResolver::new()))
+        .nameserver_discovery(Arc::new(discovery))
+        .build()
+        .unwrap()
+}
+
+/// An address nothing listens on.
+fn closed_port() -> SocketAddr {
+    UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+}
+
+#[test]
+fn agreeing_servers_are_consistent() {
+    let servers = vec![
+        nameserver("ns1.example.com", &[dns_server("bitcoin:bc1qalice", false)]),
+        nameserver("ns2.example.com", &[dns_server("bitcoin:bc1qalice", false)]),
+    ];
+
+    let report = sdk(StaticDiscovery(servers)).check_authoritative_consensus("alice@example.com", "bitcoin-payment").unwrap();
+
+    assert_eq!(report.qname, "alice.user._bitcoin-payment.example.com");
+    assert_eq!(report.servers.len(), 2);
+    assert_eq!(report.verdict(), Consensus::Consistent(vec!["bitcoin:bc1qalice".to_string()]));
+}
+
+#[test]
+fn disagreeing_servers_are_grouped_by_value() {
+    let odd = dns_server("bitcoin:bc1qmallory", false);
+    let servers = vec![
+        nameserver("ns1.example.com", &[dns_server("bitcoin:bc1qalice", false)]),
+        nameserver("ns2.example.com", &[odd]),
+        nameserver("ns3.example.com", &[dns_server("bitcoin:bc1qalice", false)]),
+    ];
+
+    let report = sdk(StaticDiscovery(servers)).check_authoritative_consensus("alice@example.com", "bitcoin-payment").unwrap();
+
+    let Consensus::Divergent(groups) = report.verdict() else {
+        panic!("expected a divergent verdict, got {:?}", report.verdict());
+    };
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0].values, ["bitcoin:bc1qalice"]);
+    assert_eq!(groups[0].servers.len(), 2);
+    assert_eq!(groups[1].values, ["bitcoin:bc1qmallory"]);
+    assert_eq!(groups[1].servers, [format!("ns2.example.com ({})", odd)]);
+}
+
+#[test]
+fn unreachable_servers_are_reported_without_failing_the_check() {
+    let servers = vec![
+        nameserver("ns1.example.com", &[dns_server("bitcoin:bc1qalice", false)]),
+        nameserver("ns2.example.com", &[closed_port()]),
+        nameserver("ns3.example.com", &[]),
+    ];
+
+    let report = sdk(StaticDiscovery(servers)).check_authoritative_consensus("alice@example.com", "bitcoin-payment").unwrap();
+
+    assert_eq!(report.verdict(), Consensus::Consistent(vec!["bitcoin:bc1qalice".to_string()]));
+    assert!(matches!(report.servers[1].outcome, ServerOutcome::Unreachable(_)));
+    assert_eq!(report.servers[2].address, None);
+    assert!(matches!(report.servers[2].outcome, ServerOutcome::Unreachable(_)));
+}
+
+#[test]
+fn no_reachable_server_means_no_answers() {
+    let servers = vec![nameserver("ns1.example.com", &[closed_port()])];
+
+    let report = sdk(StaticDiscovery(servers)).check_authoritative_consensus("example.com", "pgp").unwrap();
+    assert_eq!(report.verdict(), Consensus::NoAnswers);
+}
+
+#[test]
+fn recursive_discovery_finds_the_zone_apex_and_its_servers() {
+    let ns1 = dns_server_at(([127, 0, 0, 2], 0).into(), "bitcoin:bc1qalice", false);
+    dns_server_at(SocketAddr::new([127, 0, 0, 3].into(), ns1.port()), "bitcoin:bc1qalice", false);
+
+    let apex = Name::from_str("example.com.").unwrap();
+    let host = |name: &str| Name::from_str(name).unwrap();
+    let recursive = records_server(vec![
+        Record::from_rdata(apex.clone(), 3600, RData::NS(host("ns1.example.com."))),
+        Record::from_rdata(apex, 3600, RData::NS(host("ns2.example.com."))),
+        Record::from_rdata(host("ns1.example.com."), 3600, RData::A([127, 0, 0, 2].into())),
+        Record::from_rdata(host("ns2.example.com."), 3600, RData::A([127, 0, 0, 3].into())),
+    ]);
+
+    let discovery = RecursiveDiscovery::new(recursive).port(ns1.port());
+    let report = sdk(discovery).check_authoritative_consensus("alice@example.com", "bitcoin-payment").unwrap();
+
+    assert_eq!(report.zone, "example.com");
+    let hosts: Vec<&str> = report.servers.iter().map(|server| server.nameserver.as_str()).collect();
+    assert_eq!(hosts, ["ns1.example.com", "ns2.example.com"]);
+    assert_eq!(report.verdict(), Consensus::Consistent(vec!["bitcoin:bc1qalice".to_string()]));
+}
+
+#[test]
+fn offline_sdk_does_not_check() {
+    let sdk = sdk(StaticDiscovery(Vec::new()));
+    sdk.set_offline(true);
+
+    let error = sdk.check_authoritative_consensus("example.com", "pgp").unwrap_err();
+    assert_eq!(error, SelfieError::Offline);
+}
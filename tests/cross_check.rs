@@ -0,0 +1,88 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{CrossCheck, LookupOptions, SelfieError, SelfieRecordsSDK, Source};
+
+const NAME: &str = "_bitcoin-payment.example.com";
+const GENUINE: &str = "bitcoin:bc1qgenuine";
+
+fn sdk(primary: MockTxtResolver, second: Arc<MockTxtResolver>, strict: bool) -> SelfieRecordsSDK {
+    SelfieRecordsSDK::builder()
+        .resolver(Arc::new(primary))
+        .cross_check_resolver(second)
+        .strict_cross_check(strict)
+        .cache_ttl(Duration::from_secs(60))
+        .build()
+        .unwrap()
+}
+
+fn lookup(sdk: &SelfieRecordsSDK) -> selfie_records_sdk::KeyResult {
+    let response = sdk.get_records_response("example.com", Some(vec!["bitcoin-payment"]), None, &LookupOptions::default());
+    response.get("bitcoin-payment").unwrap().clone()
+}
+
+#[test]
+fn agreeing_resolvers_verify_the_answer() {
+    let primary = MockTxtResolver::new().with_record(NAME, &["b", "a"]);
+    let second = Arc::new(MockTxtResolver::new().with_record(NAME, &["a", "b"]));
+    let result = lookup(&sdk(primary, second, false));
+
+    assert_eq!(result.cross_check, Some(CrossCheck::Verified));
+    assert_eq!(result.value.as_deref(), Some("b a"));
+}
+
+#[test]
+fn an_injected_answer_is_flagged() {
+    let primary = MockTxtResolver::new().with_record(NAME, &["bitcoin:bc1qattacker"]);
+    let second = Arc::new(MockTxtResolver::new().with_record(NAME, &[GENUINE]));
+    let result = lookup(&sdk(primary, second, false));
+
+    assert_eq!(result.cross_check, Some(CrossCheck::Mismatch { other_values: vec![GENUINE.to_string()] }));
+    assert_eq!(result.value.as_deref(), Some("bitcoin:bc1qattacker"));
+    assert_eq!(result.error, None);
+}
+
+#[test]
+fn strict_mode_turns_a_mismatch_into_an_error() {
+    let primary = MockTxtResolver::new().with_record(NAME, &["bitcoin:bc1qattacker"]);
+    let second = Arc::new(MockTxtResolver::new().with_record(NAME, &[]));
+    let sdk = sdk(primary, second, true);
+
+    let result = lookup(&sdk);
+    assert_eq!(result.value, None);
+    assert_eq!(result.error, Some(SelfieError::CrossCheckMismatch { other_values: Vec::new() }));
+    let records = sdk.get_records("example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+    assert!(records["bitcoin-payment"]["cross_check"].as_deref().unwrap().starts_with("mismatch"));
+}
+
+#[test]
+fn cache_hits_are_not_rechecked() {
+    let primary = MockTxtResolver::new().with_record(NAME, &[GENUINE]);
+    let second = Arc::new(MockTxtResolver::new().with_record(NAME, &[GENUINE]));
+    let sdk = sdk(primary, second.clone(), false);
+
+    assert_eq!(lookup(&sdk).cross_check, Some(CrossCheck::Verified));
+    let cached = lookup(&sdk);
+    assert_eq!(cached.source, Some(Source::Cache));
+    assert_eq!(cached.cross_check, None);
+    assert_eq!(second.calls(), 1);
+}
+
+#[test]
+fn the_second_query_shares_the_key_deadline() {
+    let primary = MockTxtResolver::new().with_record(NAME, &[GENUINE]).with_delay(NAME, Duration::from_millis(150));
+    let second = Arc::new(
+        MockTxtResolver::new().with_record(NAME, &[GENUINE]).with_delay(NAME, Duration::from_millis(500)),
+    );
+    let sdk = sdk(primary, second, true);
+
+    let options = LookupOptions::new().key_timeout("bitcoin-payment", Duration::from_millis(300)).unwrap();
+    let started = std::time::Instant::now();
+    let response = sdk.get_records_response("example.com", Some(vec!["bitcoin-payment"]), None, &options);
+    let result = response.get("bitcoin-payment").unwrap();
+
+    assert!(started.elapsed() < Duration::from_millis(450), "{:?}", started.elapsed());
+    assert!(matches!(result.cross_check, Some(CrossCheck::Unavailable { .. })), "{:?}", result.cross_check);
+    assert_eq!(result.value.as_deref(), Some(GENUINE));
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use common::server::records_server;
+use common::txt_record;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{LookupOptions, SelfieError, SelfieRecordsSDK};
+
+fn sdk() -> SelfieRecordsSDK {
+    SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new().with_record("_nostr.example.com", &["npub1default"])))
+}
+
+/// The resolver a call naming `server` goes to, without sending a query.
+fn resolver_for(server: &str) -> Result<String, SelfieError> {
+    let response = sdk().get_records_response("example.com", Some(vec!["nostr"]), Some(server), &LookupOptions::new().offline(true));
+    let result = response.get("nostr").unwrap().clone();
+    match result.error {
+        Some(SelfieError::Offline) => Ok(result.resolver.unwrap()),
+        Some(e) => Err(e),
+        None => panic!("{}: {:?}", server, result),
+    }
+}
+
+#[test]
+fn addresses_parse_with_an_optional_port() {
+    for (server, resolver) in [
+        ("9.9.9.9", "9.9.9.9:53"),
+        ("8.8.8.8:5353", "8.8.8.8:5353"),
+        ("2606:4700:4700::1111", "[2606:4700:4700::1111]:53"),
+        ("[2606:4700:4700::1111]", "[2606:4700:4700::1111]:53"),
+        ("[2001:4860:4860::8888]:5353", "[2001:4860:4860::8888]:5353"),
+    ] {
+        assert_eq!(resolver_for(server).as_deref(), Ok(resolver), "{}", server);
+    }
+}
+
+#[test]
+fn host_names_parse_with_an_optional_port() {
+    // Offline calls name the host without looking it up.
+    for (server, resolver) in [("dns.quad9.net", "dns.quad9.net:53"), ("My-PiHole.lan:5353", "my-pihole.lan:5353"), ("dns.example.", "dns.example:53")] {
+        assert_eq!(resolver_for(server).as_deref(), Ok(resolver), "{}", server);
+    }
+}
+
+#[test]
+fn anything_else_fails_instead_of_using_the_default_resolver() {
+    for server in ["not a server", "-dns.example", "dns..example", "8.8.8.8:dns", "dns.example:99999", "2606::4700::1111", ""] {
+        assert_eq!(resolver_for(server), Err(SelfieError::InvalidNameserver { server: server.to_string() }), "{}", server);
+    }
+    let records = sdk().get_records("example.com", Some(vec!["nostr"]), Some("8.8.8.8.8")).to_map();
+    assert_eq!(records["nostr"]["value"], None);
+    assert_eq!(records["nostr"]["error_code"].as_deref(), Some("E_INVALID_NAMESERVER"));
+}
+
+#[test]
+fn the_named_server_answers() {
+    let server = records_server(vec![txt_record("_nostr.example.com.", "npub1named")]);
+
+    let records = sdk().get_records("example.com", Some(vec!["nostr"]), Some(&server.to_string())).to_map();
+
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("npub1named"));
+}
+
+#[test]
+fn a_host_name_with_a_port_is_looked_up_and_queried() {
+    let server = records_server(vec![txt_record("_nostr.example.com.", "npub1named")]);
+    let spec = format!("localhost:{}", server.port());
+
+    let response = sdk().get_records_response("example.com", Some(vec!["nostr"]), Some(&spec), &LookupOptions::new());
+    let result = response.get("nostr").unwrap();
+
+    assert_eq!(result.value.as_deref(), Some("npub1named"));
+    assert!(result.resolver.as_deref().unwrap().contains(&server.to_string()), "{:?}", result.resolver);
+}
+
+#[test]
+fn a_bare_host_name_uses_port_53() {
+    let options = LookupOptions::new().timeout(Duration::from_millis(200));
+    let response = sdk().get_records_response("example.com", Some(vec!["nostr"]), Some("localhost"), &options);
+    let result = response.get("nostr").unwrap();
+
+    assert!(result.resolver.as_deref().unwrap().contains("127.0.0.1:53"), "{:?}", result.resolver);
+    assert_ne!(result.value.as_deref(), Some("npub1default"));
+}
+
+#[test]
+fn an_unresolvable_host_name_fails_without_falling_back() {
+    let mock = Arc::new(MockTxtResolver::new().with_record("_nostr.example.com", &["npub1default"]));
+    let sdk = SelfieRecordsSDK::builder().resolver(mock.clone()).fallback_to_default(true).build().unwrap();
+
+    let response = sdk.get_records_response("example.com", Some(vec!["nostr", "pgp"]), Some("no-such-host.invalid"), &LookupOptions::new());
+
+    for (_, result) in response.iter() {
+        assert!(matches!(&result.error, Some(SelfieError::NameserverResolutionFailed { host, .. }) if host == "no-such-host.invalid"), "{:?}", result);
+        assert_eq!(result.value, None);
+    }
+    let records = sdk.get_records("example.com", Some(vec!["nostr"]), Some("no-such-host.invalid:5353")).to_map();
+    assert_eq!(records["nostr"]["error_code"].as_deref(), Some("E_NAMESERVER_RESOLUTION_FAILED"));
+    assert_eq!(mock.calls(), 0);
+}
+
+#[test]
+fn concurrent_calls_each_reach_their_own_server() {
+    fn shareable<T: Send + Sync>(_: &T) {}
+    let mock = MockTxtResolver::new().with_record("_nostr.example.com", &["npub1default"]);
+    let sdk = Arc::new(SelfieRecordsSDK::builder().resolver(Arc::new(mock)).cache_ttl(Duration::from_secs(60)).build().unwrap());
+    shareable(&sdk);
+    let servers: Vec<(Option<String>, String)> = (0..4)
+        .map(|index| {
+            let value = format!("npub1server{}", index);
+            (Some(records_server(vec![txt_record("_nostr.example.com.", &value)]).to_string()), value)
+        })
+        .chain([(None, "npub1default".to_string())])
+        .collect();
+
+    let threads: Vec<_> = servers
+        .into_iter()
+        .map(|(server, value)| {
+            let sdk = sdk.clone();
+            thread::spawn(move || {
+                for _ in 0..10 {
+                    let records = sdk.get_records("example.com", Some(vec!["nostr"]), server.as_deref()).to_map();
+                    assert_eq!(records["nostr"]["value"].as_deref(), Some(value.as_str()), "{:?}", server);
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+#![cfg(feature = "dnssec")]
+
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use common::{signed_delegation, signed_keys, txt_record, ZoneKey, INCEPTION};
+use selfie_records_sdk::dnssec::{prove, verify_proof, BogusReason, ProofError};
+use selfie_records_sdk::testing::MockRecordSource;
+use selfie_records_sdk::SelfieRecordsSDK;
+use trust_dns_proto::rr::rdata::TXT;
+use trust_dns_proto::rr::{RData, Record};
+
+const QNAME: &str = "_bitcoin-payment.example.com.";
+
+/// A proof captured from `signed_records()`, whose keys are fixed, so it
+/// must keep verifying.
+const GOLDEN_PROOF: &[u8] = include_bytes!("fixtures/dnssec/proof.bin");
+
+fn now() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(INCEPTION as u64 + 86_400)
+}
+
+fn keys() -> (ZoneKey, ZoneKey, ZoneKey) {
+    (ZoneKey::new(".", 1), ZoneKey::new("com.", 2), ZoneKey::new("example.com.", 3))
+}
+
+fn signed_records() -> Vec<Record> {
+    let (root, com, example) = keys();
+    let txt = vec![txt_record(QNAME, "bitcoin:bc1qexample")];
+    let txt_sig = example.sign(&txt);
+    [signed_keys(&root), signed_delegation(&root, &com), signed_keys(&com), signed_delegation(&com, &example), signed_keys(&example), txt, vec![txt_sig]]
+        .concat()
+}
+
+#[tokio::test]
+async fn proofs_verify_offline() {
+    let (root, ..) = keys();
+    let source = MockRecordSource::new().with_records(signed_records());
+
+    let proven = prove(&source, QNAME, &root.anchor(), now()).await.unwrap();
+
+    assert_eq!(proven.values, ["bitcoin:bc1qexample"]);
+    assert_eq!(proven.proof, GOLDEN_PROOF);
+    let verified = verify_proof(QNAME, GOLDEN_PROOF, &root.anchor(), now()).await.unwrap();
+    assert_eq!(verified, proven);
+    // Every record of the chain, and nothing else.
+    let records = proven.proof_records().unwrap();
+    let expected = signed_records();
+    assert_eq!(records.len(), expected.len());
+    assert!(expected.iter().all(|record| records.contains(record)));
+}
+
+#[tokio::test]
+async fn tampered_proofs_are_bogus() {
+    let (root, ..) = keys();
+    let at = GOLDEN_PROOF.windows(9).position(|window| window == b"bc1qexamp").unwrap();
+    let mut tampered = GOLDEN_PROOF.to_vec();
+    tampered[at] = b'B';
+
+    let error = verify_proof(QNAME, &tampered, &root.anchor(), now()).await.unwrap_err();
+    assert!(matches!(error, ProofError::BogusChain(BogusReason::InvalidSignature { .. })), "{:?}", error);
+    // Other anchors, or a later time, do not cover it either.
+    let other = ZoneKey::new(".", 9).anchor();
+    assert_eq!(verify_proof(QNAME, GOLDEN_PROOF, &other, now()).await.unwrap_err(), ProofError::BogusChain(BogusReason::NoTrustedKey));
+    let late = UNIX_EPOCH + Duration::from_secs(u32::MAX as u64 + 1);
+    assert!(matches!(verify_proof(QNAME, GOLDEN_PROOF, &root.anchor(), late).await, Err(ProofError::BogusChain(_))));
+    assert!(matches!(verify_proof(QNAME, &GOLDEN_PROOF[..GOLDEN_PROOF.len() - 1], &root.anchor(), now()).await, Err(ProofError::Malformed(_))));
+}
+
+#[tokio::test]
+async fn unsigned_zones_and_broken_chains_give_no_proof() {
+    let (root, com, example) = keys();
+    let txt = vec![txt_record(QNAME, "bitcoin:bc1qexample")];
+    let unsigned = [signed_keys(&root), signed_delegation(&root, &com), signed_keys(&com), signed_keys(&example), txt.clone()].concat();
+    let source = MockRecordSource::new().with_records(unsigned);
+
+    let error = prove(&source, QNAME, &root.anchor(), now()).await.unwrap_err();
+    assert_eq!(error, ProofError::Unsigned { zone: "example.com.".to_string() });
+
+    let tampered = signed_records()
+        .into_iter()
+        .map(|record| match record.rdata() {
+            RData::TXT(_) => Record::from_rdata(record.name().clone(), record.ttl(), RData::TXT(TXT::new(vec!["bitcoin:bc1qmallory".to_string()]))),
+            _ => record,
+        })
+        .collect::<Vec<_>>();
+    let source = MockRecordSource::new().with_records(tampered);
+    let error = prove(&source, QNAME, &root.anchor(), now()).await.unwrap_err();
+    assert!(matches!(error, ProofError::BogusChain(BogusReason::InvalidSignature { .. })), "{:?}", error);
+}
+
+#[test]
+fn the_sdk_proves_records_by_key() {
+    let (root, ..) = keys();
+    let sdk = SelfieRecordsSDK::builder()
+        .dnssec_source(Arc::new(MockRecordSource::new().with_records(signed_records())))
+        .trust_anchors(root.anchor())
+        .build()
+        .unwrap();
+
+    let proven = sdk.get_record_with_proof("example.com", "bitcoin-payment").unwrap();
+    assert_eq!(proven.qname, QNAME);
+    assert_eq!(proven.proof, GOLDEN_PROOF);
+    assert!(matches!(sdk.get_record_with_proof("-x.com", "bitcoin-payment"), Err(ProofError::Lookup(_))));
+    sdk.set_offline(true);
+    assert!(matches!(sdk.get_record_with_proof("example.com", "bitcoin-payment"), Err(ProofError::Lookup(_))));
+}
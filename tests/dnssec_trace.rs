@@ -0,0 +1,237 @@
+#![cfg(feature = "dnssec")]
+
+mod common;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use common::{cname_record, ns_record, signed_delegation, signed_keys, txt_record, ZoneKey, INCEPTION};
+use selfie_records_sdk::dnssec::{trace, BogusReason, InsecureReason, RecordSource, Verdict};
+use selfie_records_sdk::testing::MockRecordSource;
+use selfie_records_sdk::SelfieError;
+use trust_dns_proto::rr::rdata::TXT;
+use trust_dns_proto::rr::{Name, RData, Record, RecordType};
+
+const QNAME: &str = "_bitcoin-payment.example.com.";
+
+fn now() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(INCEPTION as u64 + 86_400)
+}
+
+struct Chain {
+    root: ZoneKey,
+    com: ZoneKey,
+    example: ZoneKey,
+}
+
+impl Chain {
+    fn new() -> Self {
+        Chain { root: ZoneKey::new(".", 1), com: ZoneKey::new("com.", 2), example: ZoneKey::new("example.com.", 3) }
+    }
+
+    fn signed_records(&self) -> Vec<Record> {
+        [self.signed_zones(), self.signed(vec![txt_record(QNAME, "bitcoin:bc1qexample")])].concat()
+    }
+
+    fn signed_zones(&self) -> Vec<Record> {
+        [
+            signed_keys(&self.root),
+            signed_delegation(&self.root, &self.com),
+            signed_keys(&self.com),
+            signed_delegation(&self.com, &self.example),
+            signed_keys(&self.example),
+        ]
+        .concat()
+    }
+
+    /// `rrset` with its RRSIG by example.com.
+    fn signed(&self, rrset: Vec<Record>) -> Vec<Record> {
+        let sig = self.example.sign(&rrset);
+        rrset.into_iter().chain([sig]).collect()
+    }
+}
+
+/// Adds `extra` to every TXT answer of `source`, like a resolver adding the
+/// records of an alias target, or an attacker adding their own.
+struct Padded {
+    source: MockRecordSource,
+    extra: Vec<Record>,
+}
+
+#[async_trait]
+impl RecordSource for Padded {
+    async fn fetch(&self, name: &Name, rtype: RecordType) -> Result<Vec<Record>, SelfieError> {
+        let mut records = self.source.fetch(name, rtype).await?;
+        if rtype == RecordType::TXT {
+            records.extend(self.extra.iter().cloned());
+        }
+        Ok(records)
+    }
+}
+
+#[tokio::test]
+async fn test_secure_chain() {
+    let chain = Chain::new();
+    let source = MockRecordSource::new().with_records(chain.signed_records());
+
+    let trace = trace(&source, QNAME, &chain.root.anchor(), now()).await.unwrap();
+
+    let zones: Vec<&str> = trace.zones.iter().map(|level| level.zone.as_str()).collect();
+    assert_eq!(zones, [".", "com.", "example.com."]);
+    assert!(trace.zones.iter().all(|level| level.verdict == Verdict::Secure));
+    assert_eq!(trace.zones[2].ds_key_tags, vec![chain.example.key_tag()]);
+    let answer = trace.answer.as_ref().unwrap();
+    assert_eq!(answer.values, vec!["bitcoin:bc1qexample".to_string()]);
+    assert_eq!(answer.verdict, Verdict::Secure);
+    assert_eq!(trace.verdict(), &Verdict::Secure);
+}
+
+#[tokio::test]
+async fn test_unsigned_delegation_is_insecure() {
+    let chain = Chain::new();
+    let records = [
+        signed_keys(&chain.root),
+        signed_delegation(&chain.root, &chain.com),
+        signed_keys(&chain.com),
+        vec![ns_record("example.com.", "ns1.example.net."), txt_record(QNAME, "bitcoin:bc1qexample")],
+    ]
+    .concat();
+    let source = MockRecordSource::new().with_records(records);
+
+    let trace = trace(&source, QNAME, &chain.root.anchor(), now()).await.unwrap();
+
+    let missing_ds = Verdict::Insecure(InsecureReason::MissingDs { zone: "example.com.".to_string() });
+    assert_eq!(trace.zones.len(), 3);
+    assert_eq!(trace.zones[2].verdict, missing_ds);
+    assert_eq!(trace.answer.as_ref().unwrap().verdict, missing_ds);
+    assert_eq!(trace.verdict(), &missing_ds);
+}
+
+#[tokio::test]
+async fn test_tampered_answer_is_bogus() {
+    let chain = Chain::new();
+    let records: Vec<Record> = chain
+        .signed_records()
+        .into_iter()
+        .map(|mut record| {
+            if matches!(record.rdata(), RData::TXT(_)) {
+                record.set_rdata(RData::TXT(TXT::new(vec!["bitcoin:bc1qattacker".to_string()])));
+            }
+            record
+        })
+        .collect();
+    let source = MockRecordSource::new().with_records(records);
+
+    let trace = trace(&source, QNAME, &chain.root.anchor(), now()).await.unwrap();
+
+    assert!(trace.zones.iter().all(|level| level.verdict == Verdict::Secure));
+    let expected = Verdict::Bogus(BogusReason::InvalidSignature {
+        rtype: trust_dns_proto::rr::RecordType::TXT,
+        key_tag: chain.example.key_tag(),
+    });
+    assert_eq!(trace.answer.unwrap().verdict, expected);
+}
+
+#[tokio::test]
+async fn test_untrusted_root_is_bogus() {
+    let chain = Chain::new();
+    let source = MockRecordSource::new().with_records(chain.signed_records());
+
+    let trace = trace(&source, QNAME, &ZoneKey::new(".", 9).anchor(), now()).await.unwrap();
+
+    assert_eq!(trace.zones.len(), 1);
+    assert_eq!(trace.verdict(), &Verdict::Bogus(BogusReason::NoTrustedKey));
+}
+
+#[tokio::test]
+async fn test_missing_ds_match_is_bogus() {
+    let chain = Chain::new();
+    let impostor = ZoneKey::new("example.com.", 7);
+    let records = [
+        signed_keys(&chain.root),
+        signed_delegation(&chain.root, &chain.com),
+        signed_keys(&chain.com),
+        signed_delegation(&chain.com, &chain.example),
+        signed_keys(&impostor),
+    ]
+    .concat();
+    let source = MockRecordSource::new().with_records(records);
+
+    let trace = trace(&source, QNAME, &chain.root.anchor(), now()).await.unwrap();
+
+    assert_eq!(trace.zones[2].verdict, Verdict::Bogus(BogusReason::DsMismatch));
+}
+
+#[tokio::test]
+async fn test_records_of_other_owners_are_ignored() {
+    let chain = Chain::new();
+    let extra = vec![txt_record("_bitcoin-payment.evil.example.com.", "bitcoin:bc1qattacker")];
+    let source = Padded { source: MockRecordSource::new().with_records(chain.signed_records()), extra };
+
+    let trace = trace(&source, QNAME, &chain.root.anchor(), now()).await.unwrap();
+
+    assert_eq!(trace.answer.as_ref().unwrap().values, ["bitcoin:bc1qexample"]);
+    assert_eq!(trace.verdict(), &Verdict::Secure);
+}
+
+#[tokio::test]
+async fn test_signed_alias_to_a_forged_record_is_bogus() {
+    let chain = Chain::new();
+    let records = [chain.signed_zones(), chain.signed(vec![cname_record(QNAME, "pay.example.com.")])].concat();
+    // The resolver answers with the target's records, which are not signed.
+    let forged = txt_record("pay.example.com.", "bitcoin:bc1qattacker");
+    let source = Padded { source: MockRecordSource::new().with_records(records), extra: vec![forged] };
+
+    let trace = trace(&source, QNAME, &chain.root.anchor(), now()).await.unwrap();
+
+    assert_eq!(trace.answer, None);
+    let alias = trace.alias.as_ref().unwrap();
+    assert_eq!((alias.target.as_str(), &alias.verdict), ("pay.example.com.", &Verdict::Secure));
+    assert_eq!(trace.final_answer().unwrap().values, ["bitcoin:bc1qattacker"]);
+    assert_eq!(trace.verdict(), &Verdict::Bogus(BogusReason::MissingSignature { rtype: RecordType::TXT }));
+}
+
+#[tokio::test]
+async fn test_aliases_are_followed_and_checked_link_by_link() {
+    let chain = Chain::new();
+    let records = [
+        chain.signed_zones(),
+        chain.signed(vec![cname_record(QNAME, "pay.example.com.")]),
+        chain.signed(vec![txt_record("pay.example.com.", "bitcoin:bc1qexample")]),
+    ]
+    .concat();
+    let source = MockRecordSource::new().with_records(records);
+
+    let aliased = trace(&source, QNAME, &chain.root.anchor(), now()).await.unwrap();
+
+    assert_eq!(aliased.verdict(), &Verdict::Secure);
+    let target = &aliased.alias.as_ref().unwrap().trace;
+    assert_eq!(target.qname, "pay.example.com.");
+    assert_eq!(target.zones.len(), 3);
+    assert_eq!(aliased.final_answer().unwrap().values, ["bitcoin:bc1qexample"]);
+
+    // An unsigned alias is bogus even when its target is signed.
+    let unsigned = [
+        chain.signed_zones(),
+        vec![cname_record(QNAME, "pay.example.com.")],
+        chain.signed(vec![txt_record("pay.example.com.", "bitcoin:bc1qexample")]),
+    ]
+    .concat();
+    let unsigned = trace(&MockRecordSource::new().with_records(unsigned), QNAME, &chain.root.anchor(), now()).await.unwrap();
+    assert_eq!(unsigned.verdict(), &Verdict::Bogus(BogusReason::MissingSignature { rtype: RecordType::CNAME }));
+}
+
+#[tokio::test]
+async fn test_alias_loops_fail() {
+    let chain = Chain::new();
+    let records = [
+        chain.signed_zones(),
+        chain.signed(vec![cname_record(QNAME, "pay.example.com.")]),
+        chain.signed(vec![cname_record("pay.example.com.", QNAME)]),
+    ]
+    .concat();
+    let source = MockRecordSource::new().with_records(records);
+
+    let error = trace(&source, QNAME, &chain.root.anchor(), now()).await.unwrap_err();
+    assert!(matches!(error, SelfieError::CnameLoop { .. }), "{:?}", error);
+}
@@ -0,0 +1,220 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use common::server::{doh_recording_server, doh_server, CapturedRequest, DohReply, DOH_VALUE};
+use selfie_records_sdk::doh::{parse_retry_after, DohMethod, DohResolver};
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{LookupOptions, SelfieRecordsSDK, TxtResolver};
+use trust_dns_proto::op::Message;
+use trust_dns_proto::rr::RecordType;
+
+const NAME: &str = "_bitcoin-payment.example.com";
+
+#[test]
+fn test_retry_after_is_honored() {
+    let (url, arrivals) = doh_server(vec![DohReply::status(429, "Retry-After: 1\r\n"), DohReply::ok()]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DohResolver::new(&url).unwrap()));
+
+    let options = LookupOptions::new().attempts(3);
+    let records = sdk.get_records_with("example.com", Some(vec!["bitcoin-payment"]), None, &options).to_map();
+
+    let payment = &records["bitcoin-payment"];
+    assert_eq!(payment["value"].as_deref(), Some(DOH_VALUE), "{:?}", payment);
+    assert_eq!(payment["attempts"].as_deref(), Some("2"));
+    assert_eq!(payment["backoff_ms"].as_deref(), Some("1000"));
+
+    let arrivals = arrivals.lock().unwrap();
+    assert!(arrivals[1] - arrivals[0] >= Duration::from_secs(1));
+}
+
+#[test]
+fn test_retry_after_beyond_budget_gives_up() {
+    let (url, arrivals) = doh_server(vec![DohReply::status(503, "Retry-After: 30\r\n")]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DohResolver::new(&url).unwrap()));
+
+    let options = LookupOptions::new().attempts(3).key_timeout("bitcoin-payment", Duration::from_secs(2)).unwrap();
+    let started = Instant::now();
+    let records = sdk.get_records_with("example.com", Some(vec!["bitcoin-payment"]), None, &options).to_map();
+
+    let payment = &records["bitcoin-payment"];
+    assert!(payment["error"].as_deref().unwrap().contains("retry after 30s"), "{:?}", payment);
+    assert_eq!(payment["attempts"].as_deref(), Some("1"));
+    assert_eq!(payment["backoff_ms"].as_deref(), Some("0"));
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(arrivals.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn test_rate_limit_without_retry_after_uses_backoff() {
+    let (url, _) = doh_server(vec![DohReply::status(429, ""), DohReply::status(429, ""), DohReply::ok()]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DohResolver::new(&url).unwrap()));
+
+    let options = LookupOptions::new().attempts(3).backoff(Duration::from_millis(40));
+    let records = sdk.get_records_with("example.com", Some(vec!["bitcoin-payment"]), None, &options).to_map();
+
+    let payment = &records["bitcoin-payment"];
+    assert_eq!(payment["value"].as_deref(), Some(DOH_VALUE));
+    assert_eq!(payment["attempts"].as_deref(), Some("3"));
+    let backoff: u64 = payment["backoff_ms"].as_deref().unwrap().parse().unwrap();
+    assert!(backoff <= 40 + 80, "{}", backoff);
+}
+
+#[test]
+fn test_parse_retry_after() {
+    let now = UNIX_EPOCH + Duration::from_secs(1_445_412_000); // Wed, 21 Oct 2015 07:20:00 GMT
+
+    assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+    assert_eq!(parse_retry_after(" 0 ", now), Some(Duration::ZERO));
+    assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now), Some(Duration::from_secs(480)));
+    assert_eq!(parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT", now), Some(Duration::ZERO));
+    assert_eq!(
+        parse_retry_after("Thu, 29 Feb 2024 00:00:00 GMT", UNIX_EPOCH),
+        Some(Duration::from_secs(1_709_164_800))
+    );
+
+    for invalid in ["", "-5", "soon", "Wed, 21 Oct 2015 07:28:00 PST", "Wed, 32 Oct 2015 07:28:00 GMT"] {
+        assert_eq!(parse_retry_after(invalid, SystemTime::now()), None, "{:?}", invalid);
+    }
+}
+
+#[test]
+fn test_endpoint_parsing() {
+    assert!(DohResolver::new("http://127.0.0.1:8053/dns-query").is_ok());
+    assert!(DohResolver::new("http://[::1]/dns-query").is_ok());
+    assert_eq!(DohResolver::new("https://dns.google/dns-query").is_ok(), cfg!(feature = "tls"));
+    assert!(DohResolver::new("dns.google").is_err());
+    assert!(DohResolver::new("http://:53/").is_err());
+}
+
+#[test]
+fn test_doh_reports_wire_info() {
+    let (url, _) = doh_server(vec![DohReply { truncated: true, ..DohReply::ok() }, DohReply::ok()]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DohResolver::new(&url).unwrap()));
+
+    let records = sdk.get_records("example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+    let wire = records["bitcoin-payment"]["wire"].as_deref().unwrap();
+    assert!(wire.starts_with("transport=doh truncated=true response_size="), "{}", wire);
+    assert!(wire.ends_with("edns_udp_size=4096"), "{}", wire);
+
+    let records = sdk.get_records("example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+    let wire = records["bitcoin-payment"]["wire"].as_deref().unwrap();
+    assert!(wire.starts_with("transport=doh truncated=false"), "{}", wire);
+}
+
+/// A resolver for the recording server at `url`, moved to the proxy path.
+fn proxied(url: &str, method: DohMethod) -> DohResolver {
+    DohResolver::new(&url.replace("/dns-query", "/secure/dns-query?tenant=7"))
+        .unwrap()
+        .method(method)
+        .header("Authorization", "Bearer s3cret")
+        .unwrap()
+        .user_agent("wallet/2.1")
+        .unwrap()
+}
+
+fn host(url: &str) -> &str {
+    url.trim_start_matches("http://").trim_end_matches("/dns-query")
+}
+
+fn assert_query(wire: &[u8]) {
+    let message = Message::from_vec(wire).unwrap();
+    assert_eq!(message.id(), 0);
+    let query = &message.queries()[0];
+    assert_eq!(query.name().to_ascii(), format!("{}.", NAME));
+    assert_eq!(query.query_type(), RecordType::TXT);
+}
+
+#[tokio::test]
+async fn test_post_request_format() {
+    let (url, requests) = doh_recording_server(vec![DohReply::ok()]);
+    let resolver = proxied(&url, DohMethod::Post);
+
+    assert_eq!(resolver.txt_lookup(NAME).await.unwrap(), [DOH_VALUE]);
+
+    let request: CapturedRequest = requests.lock().unwrap()[0].clone();
+    assert_eq!(request.request_line(), "POST /secure/dns-query?tenant=7 HTTP/1.1");
+    assert_eq!(request.header("host"), Some(host(&url)));
+    assert_eq!(request.header("content-type"), Some("application/dns-message"));
+    assert_eq!(request.header("content-length"), Some(request.body.len().to_string().as_str()));
+    assert_eq!(request.header("accept"), Some("application/dns-message"));
+    assert_eq!(request.header("user-agent"), Some("wallet/2.1"));
+    assert_eq!(request.header("authorization"), Some("Bearer s3cret"));
+    assert_query(&request.body);
+}
+
+#[tokio::test]
+async fn test_get_request_format() {
+    let (url, requests) = doh_recording_server(vec![DohReply::ok(), DohReply::ok()]);
+
+    assert_eq!(proxied(&url, DohMethod::Post).txt_lookup(NAME).await.unwrap(), [DOH_VALUE]);
+    assert_eq!(proxied(&url, DohMethod::Get).txt_lookup(NAME).await.unwrap(), [DOH_VALUE]);
+
+    let requests = requests.lock().unwrap();
+    let (post, get) = (&requests[0], &requests[1]);
+    let encoded = data_encoding::BASE64URL_NOPAD.encode(&post.body);
+    assert_eq!(get.request_line(), format!("GET /secure/dns-query?tenant=7&dns={} HTTP/1.1", encoded));
+    assert!(!encoded.contains(['=', '+', '/']));
+    assert!(get.body.is_empty());
+    assert_eq!(get.header("content-type"), None);
+    assert_eq!(get.header("content-length"), None);
+    assert_eq!(get.header("accept"), Some("application/dns-message"));
+    assert_eq!(get.header("user-agent"), Some("wallet/2.1"));
+    assert_eq!(get.header("authorization"), Some("Bearer s3cret"));
+}
+
+#[tokio::test]
+async fn test_default_user_agent_and_path() {
+    let (url, requests) = doh_recording_server(vec![DohReply::ok()]);
+    let resolver = DohResolver::new(&url.replace("/dns-query", "")).unwrap().method(DohMethod::Get);
+
+    resolver.txt_lookup(NAME).await.unwrap();
+
+    let request = requests.lock().unwrap()[0].clone();
+    assert!(request.request_line().starts_with("GET /dns-query?dns="), "{}", request.request_line());
+    let user_agent = request.header("user-agent").unwrap();
+    assert!(user_agent.starts_with("selfie-records-sdk/"), "{}", user_agent);
+}
+
+#[tokio::test]
+async fn test_redirects_are_refused_by_default() {
+    let redirect = DohReply::status(307, "Location: /elsewhere\r\n");
+    let (url, requests) = doh_recording_server(vec![redirect, redirect, DohReply::ok()]);
+
+    let error = DohResolver::new(&url).unwrap().txt_lookup(NAME).await.unwrap_err();
+    assert!(error.to_string().contains("refusing HTTP 307 redirect to /elsewhere"), "{}", error);
+    assert_eq!(requests.lock().unwrap().len(), 1);
+
+    let resolver = DohResolver::new(&url).unwrap().follow_redirects(true);
+    assert_eq!(resolver.txt_lookup(NAME).await.unwrap(), [DOH_VALUE]);
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests[2].request_line(), "POST /elsewhere HTTP/1.1");
+    assert_query(&requests[2].body);
+}
+
+#[test]
+fn test_header_injection_is_rejected() {
+    let resolver = DohResolver::new("http://127.0.0.1:8053/dns-query").unwrap();
+    assert!(resolver.clone().header("X-Tenant", "a\r\nX-Evil: 1").is_err());
+    assert!(resolver.clone().header("Bad Name", "1").is_err());
+    assert!(resolver.clone().header("", "1").is_err());
+    assert!(resolver.clone().user_agent("ua\n").is_err());
+    assert!(resolver.header("X-Tenant", "7").is_ok());
+}
+
+#[test]
+fn a_call_can_name_a_doh_endpoint() {
+    let (url, arrivals) = doh_server(vec![DohReply::ok()]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new()));
+
+    let records = sdk.get_records("example.com", Some(vec!["bitcoin-payment"]), Some(&url)).to_map();
+
+    assert_eq!(records["bitcoin-payment"]["value"].as_deref(), Some(DOH_VALUE));
+    assert_eq!(arrivals.lock().unwrap().len(), 1);
+    let records = sdk.get_records("example.com", Some(vec!["bitcoin-payment"]), Some("https://dns.example/dns-query")).to_map();
+    let error = records["bitcoin-payment"]["error"].as_deref().unwrap();
+    if !cfg!(feature = "tls") {
+        assert!(error.contains("https needs the tls feature"), "{}", error);
+    }
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::server::{doh_keepalive_server, doh_server, DohReply, DOH_VALUE};
+use selfie_records_sdk::doh::{DohResolver, PoolStats};
+use selfie_records_sdk::{LookupOptions, SelfieRecordsSDK};
+
+/// Looks up the payment record of `example{i}.com` for each `i` in `names`,
+/// so that no answer comes from the cache.
+fn lookups(resolver: &Arc<DohResolver>, names: std::ops::Range<usize>) {
+    let sdk = SelfieRecordsSDK::with_resolver(resolver.clone());
+    let options = LookupOptions::new().attempts(1);
+    for i in names {
+        let records = sdk.get_records_with(&format!("example{}.com", i), Some(vec!["bitcoin-payment"]), None, &options).to_map();
+        let payment = &records["bitcoin-payment"];
+        assert_eq!(payment["value"].as_deref(), Some(DOH_VALUE), "{:?}", payment);
+    }
+}
+
+#[test]
+fn sequential_lookups_share_one_connection() {
+    let (url, connections) = doh_keepalive_server(None);
+    let resolver = Arc::new(DohResolver::new(&url).unwrap());
+
+    lookups(&resolver, 0..5);
+
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+    assert_eq!(resolver.pool_stats(), PoolStats { opened: 1, reused: 4, retried: 0, idle: 1 });
+}
+
+#[test]
+fn connections_closed_by_the_server_are_replaced() {
+    // Answers one request per connection, then closes it without saying so.
+    let (url, arrivals) = doh_server(vec![DohReply::ok(); 3]);
+    let resolver = Arc::new(DohResolver::new(&url).unwrap());
+
+    lookups(&resolver, 0..3);
+
+    assert_eq!(arrivals.lock().unwrap().len(), 3);
+    assert_eq!(resolver.pool_stats(), PoolStats { opened: 3, reused: 2, retried: 2, idle: 1 });
+}
+
+#[test]
+fn a_response_cut_off_is_retried_on_a_new_connection() {
+    let (url, connections) = doh_keepalive_server(Some(1));
+    let resolver = Arc::new(DohResolver::new(&url).unwrap());
+
+    lookups(&resolver, 0..3);
+
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+    assert_eq!(resolver.pool_stats(), PoolStats { opened: 2, reused: 2, retried: 1, idle: 1 });
+}
+
+#[test]
+fn connection_close_is_honoured() {
+    let (url, _) = doh_server(vec![DohReply::status(200, "Connection: close\r\n"); 2]);
+    let resolver = Arc::new(DohResolver::new(&url).unwrap());
+
+    lookups(&resolver, 0..2);
+
+    assert_eq!(resolver.pool_stats(), PoolStats { opened: 2, reused: 0, retried: 0, idle: 0 });
+}
+
+#[test]
+fn idle_connections_expire() {
+    let (url, connections) = doh_keepalive_server(None);
+    let resolver = Arc::new(DohResolver::new(&url).unwrap().idle_timeout(Duration::ZERO));
+
+    lookups(&resolver, 0..3);
+
+    assert_eq!(connections.load(Ordering::SeqCst), 3);
+    assert_eq!(resolver.pool_stats().reused, 0);
+}
+
+#[test]
+fn concurrent_queries_wait_for_the_connection_limit() {
+    let (url, connections) = doh_keepalive_server(None);
+    let resolver = Arc::new(DohResolver::new(&url).unwrap().max_connections(1));
+    let sdk = SelfieRecordsSDK::with_resolver(resolver.clone());
+
+    let keys = vec!["bitcoin-payment", "nostr", "pgp", "lightning"];
+    let records = sdk.get_records_with("example.com", Some(keys), None, &LookupOptions::new().attempts(1)).to_map();
+
+    assert_eq!(records["nostr"]["value"].as_deref(), Some(DOH_VALUE), "{:?}", records);
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+    assert_eq!(resolver.pool_stats().opened, 1);
+}
+
+#[test]
+fn pooling_can_be_disabled() {
+    let (url, connections) = doh_keepalive_server(None);
+    let resolver = Arc::new(DohResolver::new(&url).unwrap().max_connections(0));
+
+    lookups(&resolver, 0..2);
+
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+    assert_eq!(resolver.pool_stats(), PoolStats::default());
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::server::records_server;
+use common::txt_bytes_record;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{
+    escape_controls, DirectResolver, KeyResult, LookupOptions, SelfieError, SelfieRecordsSDK, TxtResolver,
+    ValueEncodingIssue,
+};
+
+fn lookup(resolver: Arc<dyn TxtResolver>, strict: bool, key: &str) -> KeyResult {
+    let sdk = SelfieRecordsSDK::builder().resolver(resolver).strict_encoding(strict).build().unwrap();
+    let options = LookupOptions::new().attempts(1);
+    let response = sdk.get_records_response("example.com", Some(vec![key]), None, &options);
+    response.get(key).unwrap().clone()
+}
+
+#[test]
+fn invalid_utf8_is_flagged_and_the_bytes_kept() {
+    let server = records_server(vec![txt_bytes_record("_nostr.example.com.", &[b"npub1\xff\xfe"])]);
+
+    let result = lookup(Arc::new(DirectResolver::new(server)), false, "nostr");
+    assert_eq!(result.error, None);
+    assert_eq!(result.value.as_deref(), Some("npub1\u{fffd}\u{fffd}"));
+    assert_eq!(result.encoding_issue, Some(ValueEncodingIssue::NotUtf8));
+    assert_eq!(result.raw_bytes(), Some(&b"npub1\xff\xfe"[..]));
+}
+
+#[test]
+fn records_split_into_several_strings_are_checked_whole() {
+    // The invalid sequence only shows up once the strings are concatenated.
+    let server = records_server(vec![txt_bytes_record("_nostr.example.com.", &[b"npub1\xc3", b"\xa9x"])]);
+
+    let result = lookup(Arc::new(DirectResolver::new(server)), false, "nostr");
+    assert_eq!(result.value.as_deref(), Some("npub1\u{e9}x"));
+    assert_eq!(result.encoding_issue, None);
+}
+
+#[test]
+fn control_characters_are_flagged() {
+    let resolver = MockTxtResolver::new()
+        .with_record("_nostr.example.com", &["npub1\u{1b}[2J"])
+        .with_record("_pgp.example.com", &["https://example.com/key\0.asc"]);
+    let resolver = Arc::new(resolver);
+
+    let nostr = lookup(resolver.clone(), false, "nostr");
+    assert_eq!(nostr.value.as_deref(), Some("npub1\u{1b}[2J"));
+    assert_eq!(nostr.encoding_issue, Some(ValueEncodingIssue::ControlCharacters));
+    let pgp = lookup(resolver, false, "pgp");
+    assert_eq!(pgp.encoding_issue, Some(ValueEncodingIssue::ControlCharacters));
+}
+
+#[test]
+fn clean_values_have_no_issue() {
+    let resolver = MockTxtResolver::new().with_record("_bitcoin-payment.example.com", &["bitcoin:bc1q\u{e9}"]);
+
+    let result = lookup(Arc::new(resolver), true, "bitcoin-payment");
+    assert_eq!(result.value.as_deref(), Some("bitcoin:bc1q\u{e9}"));
+    assert_eq!(result.encoding_issue, None);
+}
+
+#[test]
+fn strict_mode_rejects_payment_values_only() {
+    let resolver = MockTxtResolver::new()
+        .with_raw_record("_bitcoin-payment.example.com", &[b"bitcoin:bc1q\xff"])
+        .with_record("_nostr.example.com", &["npub1\u{0}"]);
+    let resolver = Arc::new(resolver);
+
+    let payment = lookup(resolver.clone(), true, "bitcoin-payment");
+    assert_eq!(payment.value, None);
+    assert!(
+        matches!(&payment.error, Some(SelfieError::InvalidRecord { key, .. }) if key == "bitcoin-payment"),
+        "{:?}",
+        payment.error
+    );
+    assert_eq!(payment.encoding_issue, Some(ValueEncodingIssue::NotUtf8));
+    assert_eq!(payment.raw_bytes(), None);
+
+    let nostr = lookup(resolver.clone(), true, "nostr");
+    assert_eq!(nostr.error, None);
+    assert_eq!(nostr.encoding_issue, Some(ValueEncodingIssue::ControlCharacters));
+
+    let lenient = lookup(resolver, false, "bitcoin-payment");
+    assert_eq!(lenient.error, None);
+    assert_eq!(lenient.value.as_deref(), Some("bitcoin:bc1q\u{fffd}"));
+}
+
+#[test]
+fn the_issue_is_in_the_string_map() {
+    let resolver = MockTxtResolver::new().with_raw_record("_nostr.example.com", &[b"\xffnpub"]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(resolver));
+
+    let map = sdk.get_records_with("example.com", Some(vec!["nostr"]), None, &LookupOptions::new().attempts(1)).to_map();
+    assert_eq!(map["nostr"]["encoding_issue"].as_deref(), Some("not_utf8"));
+}
+
+#[test]
+fn escaping_neutralises_terminal_sequences() {
+    assert_eq!(escape_controls("a\u{1b}[2Jb\0c\u{9b}"), "a\\u{1b}[2Jb\\u{0}c\\u{9b}");
+    assert_eq!(escape_controls("bitcoin:bc1q \u{e9}"), "bitcoin:bc1q \u{e9}");
+}
@@ -0,0 +1,142 @@
+use std::net::UdpSocket;
+use std::sync::Arc;
+use std::time::Duration;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{LookupOptions, NameError, SelfieError, SelfieRecordsSDK, TimeoutBudget};
+use trust_dns_resolver::config::{NameServerConfig, Protocol, ResolverConfig, ResolverOpts};
+use trust_dns_resolver::TokioAsyncResolver;
+
+/// One error of every kind, in code order.
+fn every_kind() -> Vec<SelfieError> {
+    vec![
+        SelfieError::InvalidName { name: "-x.com".to_string(), reason: NameError::InvalidLabel { label: "-x".to_string() } },
+        SelfieError::NoRecords,
+        SelfieError::Timeout(TimeoutBudget::Global { timeout: Duration::from_secs(5), attempts: 1 }),
+        SelfieError::RateLimited { server: "8.8.8.8".to_string(), retry_after: None },
+        SelfieError::Dnssec("bogus".to_string()),
+        SelfieError::Offline,
+        SelfieError::InvalidRecord { key: "pgp".to_string(), reason: "bad".to_string() },
+        SelfieError::InvalidSignature,
+        SelfieError::CrossCheckMismatch { other_values: Vec::new() },
+        SelfieError::CnameLoop { chain: Vec::new() },
+        SelfieError::CnameChainTooLong { chain: Vec::new() },
+        SelfieError::Resolver("refused".to_string()),
+        SelfieError::BudgetExceeded { max_queries: 32 },
+        SelfieError::MissingChunk { index: 2, count: 4 },
+        SelfieError::ChunkDigestMismatch,
+        SelfieError::NxDomain,
+        SelfieError::InvalidNameserver { server: "dns.example".to_string() },
+        SelfieError::Disagreement { quorum: 2, answers: vec!["1.1.1.1:53: [\"a\"]".to_string(), "8.8.8.8:53: [\"b\"]".to_string()] },
+        SelfieError::MultipleRecords { count: 2 },
+        SelfieError::NotABitcoinUri { values: vec!["lightning:lnbc1".to_string()] },
+        SelfieError::FingerprintMismatch { expected: vec!["59E1187D54EA5CF2FCD2919B9B34E6136E5F37D6".to_string()], fetched: Vec::new() },
+        SelfieError::InvalidKey { key: "my key!".to_string() },
+        SelfieError::NameserverResolutionFailed { host: "dns.example".to_string(), reason: "no addresses".to_string() },
+        SelfieError::ProxyUnreachable { proxy: "127.0.0.1:9050".to_string(), reason: "connection refused".to_string() },
+        SelfieError::ResponseTooLarge { name: "_pgp.example.com".to_string(), reason: "12 records, at most 8".to_string() },
+        SelfieError::TlsCertificate { server: "https://dns.example/dns-query".to_string(), reason: "unknown issuer".to_string() },
+        SelfieError::DnssecInsecure("insecure (no DS for example.com. at its parent)".to_string()),
+    ]
+}
+
+/// Codes are append-only. If this test fails because a code changed,
+/// restore it; a new kind gets the next number at the end of the table.
+#[test]
+fn the_code_table_is_stable() {
+    let table: Vec<(u16, &str)> = every_kind().iter().map(|e| (e.numeric_code(), e.code())).collect();
+    assert_eq!(
+        table,
+        [
+            (1, "E_INVALID_NAME"),
+            (2, "E_NO_RECORDS"),
+            (3, "E_TIMEOUT"),
+            (4, "E_RATE_LIMITED"),
+            (5, "E_DNSSEC_BOGUS"),
+            (6, "E_OFFLINE"),
+            (7, "E_INVALID_RECORD"),
+            (8, "E_INVALID_SIGNATURE"),
+            (9, "E_CROSS_CHECK_MISMATCH"),
+            (10, "E_CNAME_LOOP"),
+            (11, "E_CNAME_CHAIN_TOO_LONG"),
+            (12, "E_RESOLVER"),
+            (13, "E_BUDGET_EXCEEDED"),
+            (14, "E_MISSING_CHUNK"),
+            (15, "E_CHUNK_DIGEST_MISMATCH"),
+            (16, "E_NXDOMAIN"),
+            (17, "E_INVALID_NAMESERVER"),
+            (18, "E_DISAGREEMENT"),
+            (19, "E_MULTIPLE_RECORDS"),
+            (20, "E_NOT_A_BITCOIN_URI"),
+            (21, "E_FINGERPRINT_MISMATCH"),
+            (22, "E_INVALID_KEY"),
+            (23, "E_NAMESERVER_RESOLUTION_FAILED"),
+            (24, "E_PROXY_UNREACHABLE"),
+            (25, "E_RESPONSE_TOO_LARGE"),
+            (26, "E_TLS_CERTIFICATE"),
+            (27, "E_DNSSEC_INSECURE"),
+        ]
+    );
+}
+
+#[test]
+fn the_string_map_carries_the_code() {
+    let resolver = MockTxtResolver::new().with_record("_nostr.example.com", &[]);
+    let records = SelfieRecordsSDK::with_resolver(Arc::new(resolver)).get_records("example.com", Some(vec!["nostr"]), None).to_map();
+
+    assert_eq!(records["nostr"]["error_code"].as_deref(), Some("E_NO_RECORDS"));
+
+    let records = SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new())).get_records("-x.com", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(records["nostr"]["error_code"].as_deref(), Some("E_INVALID_NAME"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn errors_serialize_with_their_codes() {
+    let json = serde_json::to_value(SelfieError::Offline).unwrap();
+    assert_eq!(json, serde_json::json!({ "code": "E_OFFLINE", "numeric_code": 6, "message": "Offline: no cached answer" }));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn every_kind_comes_back_from_json() {
+    for error in every_kind() {
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(serde_json::from_str::<SelfieError>(&json).unwrap(), error, "{}", json);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn timeouts_serialize_their_budget() {
+    let error = SelfieError::Timeout(TimeoutBudget::Key { budget: Duration::from_millis(1500), attempts: 2 });
+    assert_eq!(
+        serde_json::to_value(&error).unwrap()["fields"],
+        serde_json::json!({ "budget": "key", "duration_ns": 1_500_000_000u64, "attempts": 2 })
+    );
+}
+
+#[test]
+fn resolver_library_timeouts_are_timeouts() {
+    // A server that never answers, and a resolver giving up well before
+    // the SDK's attempt timeout.
+    let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut config = ResolverConfig::new();
+    config.add_name_server(NameServerConfig {
+        socket_addr: silent.local_addr().unwrap(),
+        protocol: Protocol::Udp,
+        tls_dns_name: None,
+        trust_nx_responses: false,
+    });
+    let opts = ResolverOpts { timeout: Duration::from_millis(100), attempts: 0, ..ResolverOpts::default() };
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let resolver = runtime.block_on(async { TokioAsyncResolver::tokio(config, opts).unwrap() });
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(resolver));
+
+    let options = LookupOptions::new().attempts(1).timeout(Duration::from_secs(5));
+    let response = sdk.get_records_response("example.com", Some(vec!["nostr"]), None, &options);
+
+    let error = response.get("nostr").unwrap().error.clone().unwrap();
+    assert_eq!(error.code(), "E_TIMEOUT");
+    assert_eq!(error, SelfieError::Timeout(TimeoutBudget::Global { timeout: Duration::from_secs(5), attempts: 1 }));
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use common::server::records_server;
+use common::txt_record;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{DirectResolver, KeyResult, LookupOptions, RecordsResponse, SelfieError, SelfieRecordsSDK};
+
+const PAYMENT_NAME: &str = "alice.user._bitcoin-payment.example.com";
+
+fn found(resolved_at: u64, ttl: u64) -> KeyResult {
+    KeyResult {
+        value: Some("bitcoin:bc1qexample".to_string()),
+        resolved_at: Some(UNIX_EPOCH + Duration::from_secs(resolved_at)),
+        ttl: Some(Duration::from_secs(ttl)),
+        ..KeyResult::default()
+    }
+}
+
+#[test]
+fn test_expiry_boundary() {
+    let result = found(1_000, 300);
+    let expires_at = UNIX_EPOCH + Duration::from_secs(1_300);
+    assert_eq!(result.expires_at(), Some(expires_at));
+
+    let just_before = expires_at - Duration::from_nanos(1);
+    assert!(result.is_fresh(just_before));
+    assert_eq!(result.remaining_ttl(just_before), Some(Duration::from_nanos(1)));
+
+    assert!(!result.is_fresh(expires_at));
+    assert_eq!(result.remaining_ttl(expires_at), Some(Duration::ZERO));
+    assert!(!result.is_fresh(expires_at + Duration::from_secs(60)));
+    assert_eq!(result.remaining_ttl(expires_at + Duration::from_secs(60)), Some(Duration::ZERO));
+
+    let start = UNIX_EPOCH + Duration::from_secs(1_000);
+    assert_eq!(result.remaining_ttl(start), Some(Duration::from_secs(300)));
+}
+
+#[test]
+fn test_no_expiry_without_value() {
+    let now = SystemTime::now();
+    let not_found = KeyResult { ttl: Some(Duration::from_secs(60)), resolved_at: Some(now), ..KeyResult::default() };
+    let failed = KeyResult { error: Some(SelfieError::NoRecords), ..not_found.clone() };
+    let no_ttl = KeyResult { value: Some("npub1".to_string()), ..KeyResult::default() };
+    for result in [not_found, failed, no_ttl] {
+        assert_eq!(result.expires_at(), None);
+        assert!(!result.is_fresh(now));
+        assert_eq!(result.remaining_ttl(now), None);
+    }
+}
+
+#[test]
+fn test_min_expiry() {
+    let mut response = RecordsResponse::default();
+    assert_eq!(response.min_expiry(), None);
+    response.insert("pgp", KeyResult::default());
+    assert_eq!(response.min_expiry(), None);
+
+    response.insert("bitcoin-payment", found(1_000, 3_600));
+    response.insert("nostr", found(1_200, 60));
+    response.insert("node-uri", found(900, 600));
+    assert_eq!(response.min_expiry(), Some(UNIX_EPOCH + Duration::from_secs(1_260)));
+}
+
+#[test]
+fn test_lookup_reports_ttl() {
+    let mock = MockTxtResolver::new()
+        .with_record(PAYMENT_NAME, &["bitcoin:bc1qexample"])
+        .with_ttl(PAYMENT_NAME, 300);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let before = SystemTime::now();
+    let response = sdk.get_records_response("alice@example.com", Some(vec!["bitcoin-payment"]), None, &LookupOptions::new());
+    let result = response.get("bitcoin-payment").unwrap();
+    assert_eq!(result.ttl, Some(Duration::from_secs(300)));
+    let expires_at = result.expires_at().unwrap();
+    assert!(expires_at >= before + Duration::from_secs(300));
+    assert!(result.is_fresh(before));
+    assert_eq!(response.min_expiry(), Some(expires_at));
+
+    let records = sdk.get_records("alice@example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+    assert_eq!(records["bitcoin-payment"]["ttl"].as_deref(), Some("300"));
+}
+
+#[test]
+fn test_ttl_is_the_smallest_of_the_answer() {
+    let mut short = txt_record("_nostr.example.com.", "npub1short");
+    short.set_ttl(120);
+    let mut long = txt_record("_nostr.example.com.", "npub1long");
+    long.set_ttl(600);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DirectResolver::new(records_server(vec![long, short]))));
+
+    let response = sdk.get_records_response("example.com", Some(vec!["nostr"]), None, &LookupOptions::new());
+
+    assert_eq!(response.get("nostr").unwrap().ttl, Some(Duration::from_secs(120)));
+}
+
+#[test]
+fn test_stale_answer_keeps_original_expiry() {
+    let mock = MockTxtResolver::new()
+        .with_record(PAYMENT_NAME, &["bitcoin:bc1qexample"])
+        .with_ttl(PAYMENT_NAME, 300);
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(Arc::new(mock))
+        .cache_ttl(Duration::from_millis(10))
+        .build()
+        .unwrap();
+
+    let options = LookupOptions::new();
+    let first = sdk.get_records_response("alice@example.com", Some(vec!["bitcoin-payment"]), None, &options);
+    std::thread::sleep(Duration::from_millis(20));
+    let stale = sdk.get_records_response("alice@example.com", Some(vec!["bitcoin-payment"]), None, &options.offline(true));
+
+    let stale = stale.get("bitcoin-payment").unwrap();
+    assert!(stale.stale);
+    assert_eq!(stale.expires_at(), first.get("bitcoin-payment").unwrap().expires_at());
+}
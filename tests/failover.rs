@@ -0,0 +1,101 @@
+mod common;
+
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use common::server::{records_server, servfail_server};
+use common::txt_record;
+use selfie_records_sdk::config::Config;
+use selfie_records_sdk::{BuildError, KeyResult, LookupOptions, SdkBuilder, SelfieError, SelfieRecordsSDK};
+
+fn server(value: &str) -> SocketAddr {
+    records_server(vec![txt_record("_bitcoin-payment.example.com.", value)])
+}
+
+fn lookup(builder: SdkBuilder) -> KeyResult {
+    let sdk = builder.build().unwrap();
+    let response = sdk.get_records_response("example.com", Some(vec!["bitcoin-payment"]), None, &LookupOptions::new().attempts(1));
+    response.get("bitcoin-payment").unwrap().clone()
+}
+
+#[test]
+fn a_silent_server_hands_over_to_the_next() {
+    // Bound but never read, so queries to it time out.
+    let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let servers = [silent.local_addr().unwrap(), server("bitcoin:bc1qsecond")];
+    let builder = SelfieRecordsSDK::builder().nameservers(&servers).nameserver_timeout(Duration::from_millis(100));
+
+    let started = Instant::now();
+    let result = lookup(builder);
+
+    assert_eq!(result.value.as_deref(), Some("bitcoin:bc1qsecond"));
+    assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+}
+
+#[test]
+fn servfail_hands_over_to_the_next() {
+    let servers = [servfail_server(), server("bitcoin:bc1qsecond")];
+
+    let result = lookup(SelfieRecordsSDK::builder().nameservers(&servers));
+
+    assert_eq!(result.value.as_deref(), Some("bitcoin:bc1qsecond"));
+}
+
+#[test]
+fn failover_starts_over_with_every_query() {
+    let servfail = servfail_server();
+    let sdk = SelfieRecordsSDK::builder().nameservers(&[servfail, server("bitcoin:bc1qsecond")]).build().unwrap();
+
+    for _ in 0..2 {
+        let records = sdk.get_records("example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+        assert_eq!(records["bitcoin-payment"]["value"].as_deref(), Some("bitcoin:bc1qsecond"));
+    }
+}
+
+#[test]
+fn a_quorum_outvotes_a_dissenting_server() {
+    let servers = [server("bitcoin:bc1qforged"), server("bitcoin:bc1qreal"), server("bitcoin:bc1qreal")];
+
+    let result = lookup(SelfieRecordsSDK::builder().nameservers(&servers).quorum(2));
+
+    assert_eq!(result.value.as_deref(), Some("bitcoin:bc1qreal"));
+    assert_eq!(result.resolver, Some(format!("2 of {},{},{}", servers[0], servers[1], servers[2])));
+}
+
+#[test]
+fn servers_that_disagree_fail_the_lookup() {
+    let servers = [server("bitcoin:bc1qfirst"), server("bitcoin:bc1qsecond"), servfail_server()];
+
+    let result = lookup(SelfieRecordsSDK::builder().nameservers(&servers).quorum(2));
+
+    let Some(SelfieError::Disagreement { quorum, answers }) = result.error else { panic!("{:?}", result) };
+    assert_eq!(quorum, 2);
+    assert_eq!(answers[0], format!("{}: [\"bitcoin:bc1qfirst\"]", servers[0]));
+    assert_eq!(answers[1], format!("{}: [\"bitcoin:bc1qsecond\"]", servers[1]));
+    assert!(answers[2].starts_with(&format!("{}: ", servers[2])), "{}", answers[2]);
+    assert_eq!(result.value, None);
+}
+
+#[test]
+fn a_quorum_needs_enough_nameservers() {
+    let servers = [server("bitcoin:bc1qfirst"), server("bitcoin:bc1qsecond")];
+    let error = |builder: SdkBuilder| builder.build().unwrap_err();
+
+    assert!(matches!(error(SelfieRecordsSDK::builder().nameservers(&servers).quorum(3)), BuildError::InvalidQuorum(_)));
+    assert!(matches!(error(SelfieRecordsSDK::builder().nameservers(&servers).quorum(0)), BuildError::InvalidQuorum(_)));
+    assert!(matches!(error(SelfieRecordsSDK::builder().quorum(1)), BuildError::InvalidQuorum(_)));
+    assert_eq!(error(SelfieRecordsSDK::builder().nameserver_timeout(Duration::ZERO)), BuildError::ZeroTimeout);
+}
+
+#[test]
+fn the_quorum_can_come_from_config() {
+    let servers = [server("bitcoin:bc1qreal"), server("bitcoin:bc1qforged")];
+    let config = Config::parse(&format!("nameservers = [\"{}\", \"{}\"]\nquorum = 2\nnameserver_timeout = 0.5\n", servers[0], servers[1])).unwrap();
+    assert_eq!(config.quorum, Some(2));
+    assert_eq!(config.nameserver_timeout, Some(Duration::from_millis(500)));
+
+    let result = lookup(SdkBuilder::from_config(config));
+
+    assert!(matches!(result.error, Some(SelfieError::Disagreement { .. })), "{:?}", result);
+    assert!(SdkBuilder::from_config(Config::parse("quorum = 1").unwrap()).build().is_err());
+}
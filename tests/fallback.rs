@@ -0,0 +1,66 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::server::dns_server;
+use selfie_records_sdk::{DirectResolver, KeyResult, LookupOptions, RecordsResponse, SdkBuilder, SelfieError, TxtResolver};
+
+/// Nothing listens here, so queries are refused.
+const DEAD_SERVER: &str = "127.0.0.2";
+
+fn live() -> (Arc<dyn TxtResolver>, String) {
+    let server = dns_server("bitcoin:bc1qlive", false);
+    (Arc::new(DirectResolver::new(server)), server.to_string())
+}
+
+fn lookup(builder: SdkBuilder, name: &str, key: &str) -> KeyResult {
+    let sdk = builder.build().unwrap();
+    let options = LookupOptions::new().attempts(2).backoff(Duration::ZERO).timeout(Duration::from_secs(2));
+    let response = sdk.get_records_response(name, Some(vec![key]), Some(DEAD_SERVER), &options);
+    response.get(key).unwrap().clone()
+}
+
+/// The `used_fallback_resolver` entry of `result`'s string map.
+fn mapped_flag(result: KeyResult) -> Option<String> {
+    let mut response = RecordsResponse::default();
+    response.insert("key", result);
+    response.to_map()["key"].get("used_fallback_resolver").cloned().flatten()
+}
+
+#[test]
+fn an_unreachable_server_falls_back_to_the_default_resolver() {
+    let (resolver, label) = live();
+
+    let result = lookup(SdkBuilder::new().resolver(resolver).fallback_to_default(true), "fallback.example.com", "bitcoin-payment");
+
+    assert_eq!(result.value.as_deref(), Some("bitcoin:bc1qlive"), "{:?}", result.error);
+    assert!(result.used_fallback_resolver);
+    assert_eq!(result.resolver, Some(label));
+    assert_eq!(mapped_flag(result).as_deref(), Some("true"));
+}
+
+#[test]
+fn falling_back_is_opt_in() {
+    let (resolver, _) = live();
+
+    let result = lookup(SdkBuilder::new().resolver(resolver), "strict.example.com", "bitcoin-payment");
+
+    assert!(matches!(result.error, Some(SelfieError::Resolver(_))), "{:?}", result.error);
+    assert!(!result.used_fallback_resolver);
+    assert_eq!(result.resolver.as_deref(), Some("127.0.0.2:53"));
+}
+
+#[test]
+fn routed_keys_do_not_fall_back() {
+    let (resolver, _) = live();
+    let builder = SdkBuilder::new()
+        .resolver(resolver)
+        .fallback_to_default(true)
+        .route_key("bitcoin-payment", "127.0.0.2:9");
+
+    let result = lookup(builder, "routed.example.com", "bitcoin-payment");
+
+    assert!(result.error.is_some());
+    assert!(!result.used_fallback_resolver);
+}
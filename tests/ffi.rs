@@ -0,0 +1,163 @@
+#![cfg(feature = "ffi")]
+
+mod common;
+
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+use common::server::records_server;
+use common::txt_record;
+use selfie_records_sdk::ffi::{
+    selfie_sdk_free, selfie_sdk_get_records, selfie_sdk_new, selfie_string_free, SELFIE_E_INVALID_UTF8, SELFIE_E_NULL_POINTER,
+    SELFIE_E_UNKNOWN_HANDLE, SELFIE_OK,
+};
+use selfie_records_sdk::SelfieRecordsSDK;
+
+const HEADER: &str = include_str!("../include/selfie_records.h");
+/// The header build.rs generates from src/ffi.rs.
+const GENERATED: &str = include_str!(concat!(env!("OUT_DIR"), "/selfie_records.h"));
+
+fn c(text: &str) -> CString {
+    CString::new(text).unwrap()
+}
+
+/// An SDK whose nameserver holds a `nostr` record for example.com.
+fn sdk() -> *mut SelfieRecordsSDK {
+    let server = records_server(vec![txt_record("_nostr.example.com.", "npub1example")]);
+    let config = c(&format!(r#"{{"nameservers": ["{}"], "attempts": 1, "keys": ["nostr"]}}"#, server));
+    let sdk = unsafe { selfie_sdk_new(config.as_ptr()) };
+    assert!(!sdk.is_null());
+    sdk
+}
+
+/// Calls `selfie_sdk_get_records` and takes the JSON it returns.
+fn get_records(sdk: *mut SelfieRecordsSDK, name: &CStr, keys: Option<&CStr>) -> (i32, serde_json::Value) {
+    let mut out: *mut c_char = ptr::null_mut();
+    let code = unsafe { selfie_sdk_get_records(sdk, name.as_ptr(), keys.map_or(ptr::null(), CStr::as_ptr), &mut out) };
+    assert!(!out.is_null(), "code {}", code);
+    let json = serde_json::from_str(unsafe { CStr::from_ptr(out) }.to_str().unwrap()).unwrap();
+    assert_eq!(selfie_string_free(out), SELFIE_OK);
+    (code, json)
+}
+
+#[test]
+fn records_come_back_as_json() {
+    let sdk = sdk();
+
+    let (code, json) = get_records(sdk, &c("example.com"), None);
+    assert_eq!(code, SELFIE_OK);
+    assert_eq!(json["nostr"]["value"], "npub1example");
+
+    // A failed key names its error in the code.
+    let (code, json) = get_records(sdk, &c("-x.com"), Some(&c("nostr, pgp")));
+    assert_eq!(code, 1);
+    assert_eq!(json["pgp"]["error"]["code"], "E_INVALID_NAME");
+
+    assert_eq!(selfie_sdk_free(sdk), SELFIE_OK);
+}
+
+#[test]
+fn invalid_configs_give_no_sdk() {
+    for config in ["not json", "[]", r#"{"colour": "blue"}"#, r#"{"attempts": "many"}"#, r#"{"cache": {"ttl": [1]}}"#] {
+        assert!(unsafe { selfie_sdk_new(c(config).as_ptr()) }.is_null(), "{}", config);
+    }
+    let sdk = unsafe { selfie_sdk_new(ptr::null()) };
+    assert!(!sdk.is_null());
+    assert_eq!(selfie_sdk_free(sdk), SELFIE_OK);
+}
+
+#[test]
+fn null_pointers_are_rejected() {
+    let sdk = sdk();
+    let name = c("example.com");
+    let mut out: *mut c_char = ptr::null_mut();
+
+    unsafe {
+        assert_eq!(selfie_sdk_get_records(ptr::null(), name.as_ptr(), ptr::null(), &mut out), SELFIE_E_NULL_POINTER);
+        assert_eq!(selfie_sdk_get_records(sdk, ptr::null(), ptr::null(), &mut out), SELFIE_E_NULL_POINTER);
+        assert_eq!(selfie_sdk_get_records(sdk, name.as_ptr(), ptr::null(), ptr::null_mut()), SELFIE_E_NULL_POINTER);
+    }
+    assert!(out.is_null());
+    assert_eq!(selfie_sdk_free(ptr::null_mut()), SELFIE_OK);
+    assert_eq!(selfie_string_free(ptr::null_mut()), SELFIE_OK);
+    assert_eq!(selfie_sdk_free(sdk), SELFIE_OK);
+}
+
+#[test]
+fn invalid_utf8_is_rejected() {
+    let sdk = sdk();
+    let invalid = CString::new(vec![b'a', 0xff, b'@', b'x']).unwrap();
+    let name = c("example.com");
+    let mut out: *mut c_char = ptr::null_mut();
+
+    unsafe {
+        assert_eq!(selfie_sdk_get_records(sdk, invalid.as_ptr(), ptr::null(), &mut out), SELFIE_E_INVALID_UTF8);
+        assert_eq!(selfie_sdk_get_records(sdk, name.as_ptr(), invalid.as_ptr(), &mut out), SELFIE_E_INVALID_UTF8);
+        assert!(selfie_sdk_new(invalid.as_ptr()).is_null());
+    }
+    assert!(out.is_null());
+    assert_eq!(selfie_sdk_free(sdk), SELFIE_OK);
+}
+
+#[test]
+fn freeing_twice_is_refused() {
+    let sdk = sdk();
+    let name = c("example.com");
+    let mut out: *mut c_char = ptr::null_mut();
+    unsafe { selfie_sdk_get_records(sdk, name.as_ptr(), ptr::null(), &mut out) };
+
+    assert_eq!(selfie_string_free(out), SELFIE_OK);
+    assert_eq!(selfie_string_free(out), SELFIE_E_UNKNOWN_HANDLE);
+    // A string the SDK never returned is not freed either.
+    let foreign = c("foreign");
+    assert_eq!(selfie_string_free(foreign.as_ptr() as *mut c_char), SELFIE_E_UNKNOWN_HANDLE);
+
+    assert_eq!(selfie_sdk_free(sdk), SELFIE_OK);
+    assert_eq!(selfie_sdk_free(sdk), SELFIE_E_UNKNOWN_HANDLE);
+    assert_eq!(unsafe { selfie_sdk_get_records(sdk, name.as_ptr(), ptr::null(), &mut out) }, SELFIE_E_UNKNOWN_HANDLE);
+}
+
+#[test]
+fn freed_handles_never_name_a_later_sdk() {
+    let freed = sdk();
+    assert_eq!(selfie_sdk_free(freed), SELFIE_OK);
+    let sdk = sdk();
+
+    assert_ne!(sdk, freed);
+    assert_eq!(selfie_sdk_free(freed), SELFIE_E_UNKNOWN_HANDLE);
+    let (code, json) = get_records(sdk, &c("example.com"), None);
+    assert_eq!(code, SELFIE_OK);
+    assert_eq!(json["nostr"]["value"], "npub1example");
+    assert_eq!(selfie_sdk_free(sdk), SELFIE_OK);
+}
+
+#[test]
+fn the_first_failed_key_asked_for_decides_the_code() {
+    // Nothing listens there, so every lookup fails.
+    let sdk = unsafe { selfie_sdk_new(c(r#"{"nameservers": ["127.0.0.1:1"], "attempts": 1, "timeout": 1}"#).as_ptr()) };
+
+    let (code, json) = get_records(sdk, &c("example.com"), Some(&c("~invalid, nostr")));
+    assert_eq!(json["~invalid"]["error"]["code"], "E_INVALID_KEY");
+    assert_eq!(code, 22);
+    let (code, json) = get_records(sdk, &c("example.com"), Some(&c("nostr, ~invalid")));
+    assert_ne!(code, 22);
+    assert_eq!(json["nostr"]["error"]["numeric_code"], code);
+
+    assert_eq!(selfie_sdk_free(sdk), SELFIE_OK);
+}
+
+#[test]
+fn the_header_is_generated_from_the_source() {
+    let generated = concat!(env!("OUT_DIR"), "/selfie_records.h");
+    assert!(HEADER == GENERATED, "include/selfie_records.h is out of date, copy {} over it", generated);
+}
+
+#[test]
+fn the_header_declares_every_function() {
+    for function in ["selfie_sdk_new(", "selfie_sdk_get_records(", "selfie_sdk_free(", "selfie_string_free("] {
+        assert!(HEADER.contains(function), "{}", function);
+    }
+    for (name, value) in [("SELFIE_OK", SELFIE_OK), ("SELFIE_E_NULL_POINTER", SELFIE_E_NULL_POINTER), ("SELFIE_E_UNKNOWN_HANDLE", SELFIE_E_UNKNOWN_HANDLE)] {
+        assert!(HEADER.contains(&format!("#define {} {}", name, value)), "{}", name);
+    }
+}
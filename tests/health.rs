@@ -0,0 +1,209 @@
+mod common;
+
+use std::net::{SocketAddr, UdpSocket};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::server::records_server;
+use selfie_records_sdk::health::{Finding, HealthCheck, HealthReport, KeyHealth, Status};
+use selfie_records_sdk::{DirectResolver, SelfieRecordsSDK};
+use trust_dns_proto::rr::rdata::TXT;
+use trust_dns_proto::rr::{Name, RData, Record};
+
+const PAYMENT: &str = "bitcoin:bc1qhealth";
+const NPUB: &str = "npub180cvv07tjdrrgpa0j7j7tmnyl2yr6yr7l8j4s3evf6u64th6gkwsyjh6w6";
+
+fn name(name: &str) -> Name {
+    Name::from_str(&format!("{}.", name)).unwrap()
+}
+
+fn txt(owner: &str, ttl: u32, value: &str) -> Record {
+    Record::from_rdata(name(owner), ttl, RData::TXT(TXT::new(vec![value.to_string()])))
+}
+
+fn alias(owner: &str, target: &str) -> Record {
+    Record::from_rdata(name(owner), 300, RData::CNAME(name(target)))
+}
+
+/// Checks `keys` of `domain` looked up on `server`, which is also the one
+/// propagation server unless others are given.
+fn check(server: SocketAddr, domain: &str, keys: &[&str], others: Option<Vec<SocketAddr>>) -> HealthReport {
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DirectResolver::new(server)));
+    HealthCheck::new(domain)
+        .keys(keys)
+        .propagation_servers(others.unwrap_or(vec![server]))
+        .timeout(Duration::from_secs(1))
+        .run(&sdk)
+}
+
+fn finding<'a>(key: &'a KeyHealth, check: &str) -> Vec<&'a Finding> {
+    key.findings.iter().filter(|finding| finding.check == check).collect()
+}
+
+fn key<'a>(report: &'a HealthReport, key: &str) -> &'a KeyHealth {
+    report.keys.iter().find(|health| health.key == key).unwrap()
+}
+
+#[test]
+fn published_records_pass_every_check_but_dnssec() {
+    let server = records_server(vec![
+        txt("_bitcoin-payment.healthy.example", 300, PAYMENT),
+        txt("_nostr.healthy.example", 3600, NPUB),
+    ]);
+
+    let report = check(server, "healthy.example", &["bitcoin-payment", "nostr", "pgp"], None);
+
+    let keys: Vec<&str> = report.keys.iter().map(|key| key.key.as_str()).collect();
+    assert_eq!(keys, ["bitcoin-payment", "nostr", "pgp"]);
+    let payment = key(&report, "bitcoin-payment");
+    assert_eq!(payment.qname.as_deref(), Some("_bitcoin-payment.healthy.example"));
+    assert_eq!(payment.value.as_deref(), Some(PAYMENT));
+    for check in ["found", "value", "ttl", "propagation"] {
+        assert_eq!(finding(payment, check)[0].status, Status::Pass, "{}: {:?}", check, payment.findings);
+    }
+    assert_eq!(finding(payment, "ttl")[0].detail, "300s");
+    assert_eq!(finding(payment, "propagation")[0].detail, format!("{} agrees", server));
+    assert_eq!(finding(payment, "dnssec")[0].status, Status::Warn);
+    assert!(finding(payment, "cname").is_empty());
+    assert_eq!(key(&report, "nostr").status(), Status::Warn);
+
+    let pgp = key(&report, "pgp");
+    assert_eq!(pgp.findings, [Finding { check: "found", status: Status::Pass, detail: "not published".to_string() }]);
+    assert_eq!(pgp.status(), Status::Pass);
+    assert!(report.findings.is_empty());
+    assert_eq!(report.status(), Status::Warn);
+}
+
+#[test]
+fn values_that_do_not_parse_fail() {
+    let server = records_server(vec![
+        txt("_bitcoin-payment.invalid.example", 300, "lightning:lnbc1"),
+        txt("_nostr.invalid.example", 300, "npub1notakey"),
+    ]);
+
+    let report = check(server, "invalid.example", &["bitcoin-payment", "nostr"], None);
+
+    for key in &report.keys {
+        let value = finding(key, "value");
+        assert_eq!(value[0].status, Status::Fail, "{}: {:?}", key.key, key.findings);
+        assert!(!value[0].detail.is_empty());
+    }
+    assert_eq!(report.status(), Status::Fail);
+}
+
+#[test]
+fn several_records_conflict() {
+    let server = records_server(vec![
+        txt("_bitcoin-payment.conflict.example", 300, "bitcoin:bc1qone"),
+        txt("_bitcoin-payment.conflict.example", 300, "bitcoin:bc1qtwo"),
+        txt("_custom.conflict.example", 300, "one"),
+        txt("_custom.conflict.example", 300, "two"),
+    ]);
+
+    let report = check(server, "conflict.example", &["bitcoin-payment", "custom"], None);
+
+    let payment = finding(key(&report, "bitcoin-payment"), "records");
+    assert_eq!(payment[0].status, Status::Fail);
+    assert_eq!(payment[0].detail, "2 records, joined into one value");
+    let custom = key(&report, "custom");
+    assert_eq!(finding(custom, "records")[0].status, Status::Warn);
+    // Keys without a record type are not parsed.
+    assert!(finding(custom, "value").is_empty());
+    // Propagation compares the records, whatever order they come in.
+    assert_eq!(finding(custom, "propagation")[0].status, Status::Pass);
+}
+
+#[test]
+fn ttls_outside_the_usual_range_warn() {
+    let server = records_server(vec![
+        txt("_bitcoin-payment.ttl.example", 30, PAYMENT),
+        txt("_nostr.ttl.example", 604800, NPUB),
+    ]);
+
+    let report = check(server, "ttl.example", &["bitcoin-payment", "nostr"], None);
+
+    let short = finding(key(&report, "bitcoin-payment"), "ttl");
+    assert_eq!((short[0].status, short[0].detail.as_str()), (Status::Warn, "30s is short"));
+    let long = finding(key(&report, "nostr"), "ttl");
+    assert_eq!((long[0].status, long[0].detail.as_str()), (Status::Warn, "604800s is long"));
+}
+
+#[test]
+fn resolvers_that_disagree_or_fail_warn() {
+    let server = records_server(vec![txt("_bitcoin-payment.spread.example", 300, PAYMENT)]);
+    let stale = records_server(vec![txt("_bitcoin-payment.spread.example", 300, "bitcoin:bc1qstale")]);
+    let empty = records_server(Vec::new());
+    // Nothing listens on this port any more, so queries to it are refused.
+    let dead = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+    let report = check(server, "spread.example", &["bitcoin-payment"], Some(vec![server, stale, empty, dead]));
+
+    let propagation = finding(&report.keys[0], "propagation");
+    let statuses: Vec<Status> = propagation.iter().map(|finding| finding.status).collect();
+    assert_eq!(statuses, [Status::Pass, Status::Warn, Status::Warn, Status::Warn]);
+    assert_eq!(propagation[1].detail, format!("{} answers bitcoin:bc1qstale", stale));
+    assert_eq!(propagation[2].detail, format!("{} has no record", empty));
+    assert!(propagation[3].detail.starts_with(&format!("{} failed: ", dead)), "{}", propagation[3].detail);
+}
+
+#[test]
+fn cname_delegation_is_reported() {
+    let server = records_server(vec![
+        alias("_bitcoin-payment.delegated.example", "pay.provider.example"),
+        txt("pay.provider.example", 300, PAYMENT),
+    ]);
+
+    let report = check(server, "delegated.example", &["bitcoin-payment"], None);
+
+    let payment = &report.keys[0];
+    assert_eq!(payment.value.as_deref(), Some(PAYMENT));
+    let cname = finding(payment, "cname");
+    assert_eq!((cname[0].status, cname[0].detail.as_str()), (Status::Pass, "delegated to pay.provider.example"));
+    assert_eq!(finding(payment, "propagation")[0].status, Status::Pass);
+}
+
+#[test]
+fn names_without_records_fail() {
+    let server = records_server(Vec::new());
+
+    let report = check(server, "empty.example", &["bitcoin-payment", "nostr"], None);
+
+    assert!(report.keys.iter().all(|key| key.status() == Status::Pass));
+    assert_eq!(report.findings, [Finding { check: "records", status: Status::Fail, detail: "no records found".to_string() }]);
+    assert_eq!(report.status(), Status::Fail);
+}
+
+#[test]
+fn lookup_errors_fail() {
+    let dead = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+    let report = check(dead, "unreachable.example", &["bitcoin-payment"], None);
+
+    let found = finding(&report.keys[0], "found");
+    assert_eq!(found[0].status, Status::Fail);
+    assert_eq!(report.keys[0].findings.len(), 1);
+}
+
+#[test]
+fn reports_convert_to_json() {
+    let server = records_server(vec![txt("_bitcoin-payment.json.example", 300, PAYMENT)]);
+
+    let json = check(server, "json.example", &["bitcoin-payment"], None).to_json();
+
+    assert_eq!(json["name"], "json.example");
+    assert_eq!(json["status"], "WARN");
+    assert_eq!(json["findings"], serde_json::json!([]));
+    let payment = &json["keys"][0];
+    assert_eq!(payment["key"], "bitcoin-payment");
+    assert_eq!(payment["qname"], "_bitcoin-payment.json.example");
+    assert_eq!(payment["value"], PAYMENT);
+    assert_eq!(payment["findings"][0], serde_json::json!({"check": "found", "status": "PASS", "detail": "found"}));
+}
+
+#[test]
+fn statuses_order_from_pass_to_fail() {
+    assert!(Status::Pass < Status::Warn && Status::Warn < Status::Fail);
+    let report = HealthReport { name: "example.com".to_string(), keys: Vec::new(), findings: Vec::new() };
+    assert_eq!(report.status(), Status::Pass);
+}
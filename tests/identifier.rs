@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{parse_identifier, Identifier, NameError, SelfieRecordsSDK};
+
+fn email(local: &str, domain: &str) -> Identifier {
+    Identifier::Email { local: local.to_string(), domain: domain.to_string() }
+}
+
+#[test]
+fn test_valid_identifiers() {
+    let cases = [
+        ("example.com", Identifier::Domain("example.com".to_string())),
+        ("sub.domain.example.com", Identifier::Domain("sub.domain.example.com".to_string())),
+        ("example.com.", Identifier::Domain("example.com".to_string())),
+        ("alice@example.com", email("alice", "example.com")),
+        ("alice@intranet", email("alice", "intranet")),
+        ("first.last@example.com", email("first.last", "example.com")),
+        ("alice+tag@example.com", email("alice+tag", "example.com")),
+        ("o'brien@example.ie", email("o'brien", "example.ie")),
+        ("!#$%&'*+-/=?^_`{|}~@example.com", email("!#$%&'*+-/=?^_`{|}~", "example.com")),
+        ("\"john doe\"@example.com", email("\"john doe\"", "example.com")),
+        ("\"a@b\"@example.com", email("\"a@b\"", "example.com")),
+        ("\"a\\\"b\"@example.com", email("\"a\\\"b\"", "example.com")),
+        ("josé@example.com", email("josé", "example.com")),
+        (&format!("{}@example.com", "a".repeat(64)), email(&"a".repeat(64), "example.com")),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(parse_identifier(input), Ok(expected), "{}", input);
+    }
+}
+
+#[test]
+fn test_invalid_identifiers() {
+    let long_address = format!("{}@{}.com", "a".repeat(60), ["b".repeat(63), "c".repeat(63), "d".repeat(63)].join("."));
+    let cases = [
+        ("", NameError::Empty),
+        ("a@b@c.d", NameError::MultipleAt),
+        ("\"a\"@b@c.d", NameError::MultipleAt),
+        ("@example.com", NameError::EmptyLocalPart),
+        ("alice@", NameError::MissingDomain),
+        ("\"alice\"", NameError::MissingDomain),
+        ("\"alice", NameError::UnterminatedQuote),
+        ("\"alice\"x@example.com", NameError::InvalidLocalPart { position: 7 }),
+        (".alice@example.com", NameError::InvalidLocalPart { position: 0 }),
+        ("alice.@example.com", NameError::InvalidLocalPart { position: 5 }),
+        ("al..ice@example.com", NameError::InvalidLocalPart { position: 3 }),
+        ("al ice@example.com", NameError::InvalidLocalPart { position: 2 }),
+        ("al(ice@example.com", NameError::InvalidLocalPart { position: 2 }),
+        (&format!("{}@example.com", "a".repeat(65)), NameError::LocalPartTooLong),
+        (&long_address, NameError::TooLong { max: 254 }),
+        ("alice@[192.0.2.1]", NameError::AddressLiteral),
+        ("alice@example..com", NameError::EmptyLabel),
+        ("alice@-example.com", NameError::InvalidLabel { label: "-example".to_string() }),
+        ("alice@exa mple.com", NameError::InvalidLabel { label: "exa mple".to_string() }),
+        ("localhost", NameError::SingleLabelDomain),
+        ("not a domain", NameError::InvalidLabel { label: "not a domain".to_string() }),
+        (&format!("{}.com", "a".repeat(64)), NameError::LabelTooLong),
+        (".example.com", NameError::EmptyLabel),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(parse_identifier(input), Err(expected), "{}", input);
+    }
+}
+
+#[test]
+fn test_invalid_name_flows_into_records() {
+    let mock = Arc::new(MockTxtResolver::new());
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+
+    let records = sdk.get_records("a@b@c.d", Some(vec!["pgp", "nostr"]), None).to_map();
+    for key in ["pgp", "nostr"] {
+        let error = records[key]["error"].clone().unwrap();
+        assert!(error.contains("more than one unquoted '@'"), "{}", error);
+    }
+    assert_eq!(mock.calls(), 0);
+}
+
+#[test]
+fn test_single_label_address_domain_is_queried() {
+    let mock = MockTxtResolver::new().with_record("alice.user._pgp.intranet", &["ABCD"]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let records = sdk.get_records("alice@intranet", Some(vec!["pgp"]), None).to_map();
+    assert_eq!(records["pgp"]["value"].as_deref(), Some("ABCD"));
+}
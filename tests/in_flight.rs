@@ -0,0 +1,96 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{LookupOptions, SelfieError, SelfieRecordsSDK, TxtResolver};
+
+#[test]
+fn test_concurrent_lookups_share_one_query() {
+    let mock = Arc::new(
+        MockTxtResolver::new()
+            .with_record("_bitcoin-payment.example.com", &["bitcoin:bc1qexample"])
+            .with_delay("_bitcoin-payment.example.com", Duration::from_millis(200)),
+    );
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+    let barrier = Barrier::new(50);
+
+    let values: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..50)
+            .map(|_| {
+                scope.spawn(|| {
+                    barrier.wait();
+                    let records = sdk.get_records("example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+                    records["bitcoin-payment"]["value"].clone()
+                })
+            })
+            .collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    });
+
+    assert_eq!(mock.calls(), 1);
+    assert!(values.iter().all(|value| value.as_deref() == Some("bitcoin:bc1qexample")));
+
+    sdk.get_records("example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+    assert_eq!(mock.calls(), 2);
+}
+
+#[test]
+fn test_concurrent_lookups_share_errors() {
+    let mock = Arc::new(MockTxtResolver::new().with_delay("_pgp.example.com", Duration::from_millis(200)));
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+    let options = LookupOptions::new().attempts(1);
+    let barrier = Barrier::new(10);
+
+    let errors: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..10)
+            .map(|_| {
+                scope.spawn(|| {
+                    barrier.wait();
+                    sdk.get_records_with("example.com", Some(vec!["pgp"]), None, &options).to_map()["pgp"]["error"].clone()
+                })
+            })
+            .collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    });
+
+    assert_eq!(mock.calls(), 1);
+    assert!(errors.iter().all(|error| error.as_deref().is_some_and(|e| e.contains("no mock record"))));
+}
+
+/// Panics on its first query and answers every later one.
+#[derive(Default)]
+struct PanicsOnce {
+    calls: AtomicUsize,
+}
+
+#[async_trait]
+impl TxtResolver for PanicsOnce {
+    async fn txt_lookup(&self, _name: &str) -> Result<Vec<String>, SelfieError> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        if call == 0 {
+            panic!("backend failure");
+        }
+        Ok(vec!["ABCD1234".to_string()])
+    }
+}
+
+#[test]
+fn test_waiting_caller_takes_over_after_a_panic() {
+    let resolver = Arc::new(PanicsOnce::default());
+    let sdk = SelfieRecordsSDK::with_resolver(resolver.clone());
+
+    let (first, second) = thread::scope(|scope| {
+        let first = scope.spawn(|| sdk.get_records("example.com", Some(vec!["pgp"]), None).to_map());
+        thread::sleep(Duration::from_millis(20));
+        let second = scope.spawn(|| sdk.get_records("example.com", Some(vec!["pgp"]), None).to_map());
+        (first.join(), second.join())
+    });
+
+    assert!(first.is_err());
+    assert_eq!(second.unwrap()["pgp"]["value"].as_deref(), Some("ABCD1234"));
+    assert_eq!(resolver.calls.load(Ordering::SeqCst), 2);
+}
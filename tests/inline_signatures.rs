@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use data_encoding::{BASE64, HEXLOWER};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use selfie_records_sdk::signature::{self, SignatureError, SignatureStatus};
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{LookupOptions, SelfieRecordsSDK};
+
+// RFC 8032, section 7.1, tests 1 and 2.
+const RFC8032_SECRET_1: &str = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+const RFC8032_PUBLIC_1: &str = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+const RFC8032_SIGNATURE_1: &str = "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b";
+const RFC8032_PUBLIC_2: &str = "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c";
+const RFC8032_SIGNATURE_2: &str = "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00";
+
+fn hex(text: &str) -> Vec<u8> {
+    HEXLOWER.decode(text.as_bytes()).unwrap()
+}
+
+fn rfc8032_pair() -> Ed25519KeyPair {
+    Ed25519KeyPair::from_seed_unchecked(&hex(RFC8032_SECRET_1)).unwrap()
+}
+
+fn signed_record(pair: &Ed25519KeyPair, fields: &str, message: &str) -> String {
+    format!("{}; sig={}", fields, BASE64.encode(pair.sign(message.as_bytes()).as_ref()))
+}
+
+#[test]
+fn test_verify_rfc8032_vectors() {
+    let key_1 = signature::parse_public_key(RFC8032_PUBLIC_1).unwrap();
+    assert!(signature::verify(&key_1, b"", &hex(RFC8032_SIGNATURE_1)));
+    assert!(!signature::verify(&key_1, b"\x72", &hex(RFC8032_SIGNATURE_1)));
+
+    let key_2 = signature::parse_public_key(RFC8032_PUBLIC_2).unwrap();
+    assert!(signature::verify(&key_2, b"\x72", &hex(RFC8032_SIGNATURE_2)));
+    assert!(!signature::verify(&key_1, b"\x72", &hex(RFC8032_SIGNATURE_2)));
+}
+
+#[test]
+fn test_parse_public_key_encodings() {
+    let expected: [u8; 32] = hex(RFC8032_PUBLIC_1).try_into().unwrap();
+    let base64 = "11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=";
+
+    assert_eq!(signature::parse_public_key(RFC8032_PUBLIC_1), Ok(expected));
+    assert_eq!(signature::parse_public_key(&RFC8032_PUBLIC_1.to_uppercase()), Ok(expected));
+    assert_eq!(signature::parse_public_key(base64), Ok(expected));
+    assert_eq!(signature::parse_public_key(base64.trim_end_matches('=')), Ok(expected));
+    assert_eq!(signature::parse_public_key(&format!(" {} ", base64)), Ok(expected));
+
+    for invalid in ["", "not a key", &RFC8032_PUBLIC_1[..62], "AAAA"] {
+        assert_eq!(signature::parse_public_key(invalid), Err(SignatureError::InvalidKey), "{:?}", invalid);
+    }
+}
+
+#[test]
+fn test_canonicalization() {
+    let cases = [
+        ("value=bitcoin:bc1qexample; sig=AAAA", "value=bitcoin:bc1qexample"),
+        ("value=bitcoin:bc1qexample;sig=AAAA", "value=bitcoin:bc1qexample"),
+        ("  value = bitcoin:bc1qexample ;  note = tip jar ; sig = AAAA ", "value=bitcoin:bc1qexample;note=tip jar"),
+        ("sig=AAAA; value=a; note=b", "value=a;note=b"),
+        ("value=a;; flag ;sig=AAAA;", "value=a;flag"),
+        ("value=a=b; sig=AAAA", "value=a=b"),
+    ];
+    for (record, message) in cases {
+        let signed = signature::split_signed_value(record, "sig").unwrap().unwrap();
+        assert_eq!(signed.message, message, "{:?}", record);
+        assert_eq!(signed.signature, vec![0, 0, 0]);
+    }
+
+    assert_eq!(signature::split_signed_value("value=a; note=sig", "sig"), Ok(None));
+    assert_eq!(signature::split_signed_value("value=a; signature=AAAA", "sig"), Ok(None));
+    assert!(signature::split_signed_value("value=a; signature=AAAA", "signature").unwrap().is_some());
+    assert_eq!(
+        signature::split_signed_value("value=a; sig=!!", "sig"),
+        Err(SignatureError::InvalidSignatureEncoding)
+    );
+}
+
+#[test]
+fn test_lookup_reports_signature_status() {
+    let pair = rfc8032_pair();
+    let valid = signed_record(&pair, "value=bitcoin:bc1qexample", "value=bitcoin:bc1qexample");
+    let tampered = signed_record(&pair, "value=bitcoin:bc1qattacker", "value=bitcoin:bc1qexample");
+    let mock = Arc::new(
+        MockTxtResolver::new()
+            .with_record("_bitcoin-payment.example.com", &[&valid])
+            .with_record("_nostr.example.com", &[&tampered])
+            .with_record("_pgp.example.com", &["ABCD1234"])
+            .with_record("_selfie-key.example.com", &["unrelated", RFC8032_PUBLIC_1]),
+    );
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+
+    let options = LookupOptions::new().verify_signatures(true);
+    let records = sdk.get_records_with("example.com", Some(vec!["bitcoin-payment", "nostr", "pgp"]), None, &options).to_map();
+
+    assert_eq!(records["bitcoin-payment"]["value"].as_deref(), Some(valid.as_str()));
+    assert_eq!(records["bitcoin-payment"]["signature"].as_deref(), Some("verified"));
+    assert_eq!(records["nostr"]["signature"], Some(SignatureStatus::Invalid.to_string()));
+    assert!(!records["pgp"].contains_key("signature"));
+    assert_eq!(mock.calls(), 4);
+
+    let records = sdk.get_records("example.com", Some(vec!["nostr"]), None).to_map();
+    assert!(!records["nostr"].contains_key("signature"));
+}
+
+#[test]
+fn test_lookup_without_published_key() {
+    let pair = rfc8032_pair();
+    let record = signed_record(&pair, "value=bitcoin:bc1qexample", "value=bitcoin:bc1qexample");
+    let mock = MockTxtResolver::new().with_record("alice.user._bitcoin-payment.example.com", &[&record]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let options = LookupOptions::new().strict_signatures(true);
+    let records = sdk.get_records_with("alice@example.com", Some(vec!["bitcoin-payment"]), None, &options).to_map();
+
+    assert_eq!(records["bitcoin-payment"]["signature"].as_deref(), Some("no-key"));
+}
+
+#[test]
+fn test_strict_signatures_fail_the_key() {
+    let pair = rfc8032_pair();
+    let tampered = signed_record(&pair, "value=bc1qattacker", "value=bc1qexample");
+    let valid = format!("value=bc1qexample; proof={}", BASE64.encode(pair.sign(b"value=bc1qexample").as_ref()));
+    let mock = MockTxtResolver::new()
+        .with_record("_bitcoin-payment.example.com", &[&tampered])
+        .with_record("_nostr.example.com", &[&valid])
+        .with_record("_selfie-key.example.com", &[&BASE64.encode(pair.public_key().as_ref())]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let options = LookupOptions::new().signature_field("proof").strict_signatures(true);
+    let records = sdk.get_records_with("example.com", Some(vec!["bitcoin-payment", "nostr"]), None, &options).to_map();
+    assert!(!records["bitcoin-payment"].contains_key("signature"));
+    assert_eq!(records["nostr"]["signature"].as_deref(), Some("verified"));
+
+    let options = LookupOptions::new().strict_signatures(true);
+    let records = sdk.get_records_with("example.com", Some(vec!["bitcoin-payment"]), None, &options).to_map();
+    assert_eq!(records["bitcoin-payment"]["value"], None);
+    assert_eq!(records["bitcoin-payment"]["error"].as_deref(), Some("Inline signature does not verify"));
+}
@@ -1,8 +1,41 @@
-use selfie_records_sdk::SelfieRecordsSDK;
+use std::sync::Arc;
+use std::time::Duration;
 
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{LookupOptions, SelfieError, SelfieRecordsSDK};
+
+/// Run with `cargo test -- --ignored`.
 #[test]
+#[ignore = "queries 8.8.8.8"]
 fn test_get_records() {
-    let sdk = SelfieRecordsSDK::new(true);
-    let records = sdk.get_records("example.com", None, Some("8.8.8.8"));
+    let sdk = SelfieRecordsSDK::new();
+    let records = sdk.get_records("example.com", None, Some("8.8.8.8")).to_map();
     assert!(!records.is_empty());
 }
+
+#[test]
+fn default_records_and_record_keys_are_exported() {
+    assert_eq!(selfie_records_sdk::DEFAULT_RECORDS, ["bitcoin-payment", "pgp", "nostr", "node-uri"]);
+    assert_eq!(selfie_records_sdk::get_txt_record_key("user@example.com", "nostr").unwrap(), "user.user._nostr.example.com");
+    assert!(selfie_records_sdk::validate_name("user@example.com").is_ok());
+}
+
+#[test]
+fn mock_answers_cover_every_outcome() {
+    let mock = MockTxtResolver::new()
+        .with_record("_bitcoin-payment.example.com", &["bitcoin:bc1q", "second"])
+        .with_record("_pgp.example.com", &[])
+        .with_nxdomain("_nostr.example.com")
+        .with_record("example.com", &[])
+        .with_timeout("_node-uri.example.com");
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+    let options = LookupOptions::new().timeout(Duration::from_millis(50)).attempts(1);
+
+    let response = sdk.get_records_response("example.com", None, None, &options);
+
+    // Multiple records are joined with spaces.
+    assert_eq!(response.get("bitcoin-payment").unwrap().value.as_deref(), Some("bitcoin:bc1q second"));
+    assert_eq!(response.get("pgp").unwrap().error, Some(SelfieError::NoRecords));
+    assert_eq!(response.get("nostr").unwrap().error, Some(SelfieError::NoRecords));
+    assert_eq!(response.get("node-uri").unwrap().error.as_ref().map(SelfieError::code), Some("E_TIMEOUT"));
+}
@@ -0,0 +1,143 @@
+#![cfg(all(feature = "serde", feature = "signatures"))]
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use selfie_records_sdk::signature::SignatureStatus;
+use selfie_records_sdk::{
+    KeyResult, ParseError, ParsedValue, QueryMeta, RawRecord, RecordsResponse, Route, SelfieError, ServerInfo, Source, Transport,
+    ValueEncodingIssue, WireInfo,
+};
+
+/// The JSON contract. If this test fails because the output changed on
+/// purpose, update the fixture and say so in the release notes.
+const GOLDEN_JSON: &str = include_str!("fixtures/json/response.json");
+
+/// A response that sets every field read back from JSON.
+fn response() -> RecordsResponse {
+    let mut response = RecordsResponse::default();
+    response.insert(
+        "bitcoin-payment",
+        KeyResult {
+            value: Some("bitcoin:bc1qexample?amount=0.01".to_string()),
+            raw_value: Some(b"bitcoin:bc1qexample?amount=0.01".to_vec()),
+            raw_records: vec![RawRecord::new(["bitcoin:bc1qexample", "?amount=0.01"])],
+            source: Some(Source::Dns),
+            attempts: Some(2),
+            backoff: Some(Duration::from_millis(250)),
+            signature: Some(SignatureStatus::Verified),
+            resolved_at: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            ttl: Some(Duration::from_secs(300)),
+            record_version: Some(2),
+            resolver: Some("10.0.0.53:53".to_string()),
+            answered_by: Some(ServerInfo { address: "10.0.0.53:53".parse().unwrap(), transport: Transport::Udp }),
+            authenticated: true,
+            query: Some(QueryMeta {
+                fqdn: "_bitcoin-payment.example.com".to_string(),
+                nameserver: Some("10.0.0.53:53".parse().unwrap()),
+                rtt: Duration::from_millis(43),
+                attempts: 2,
+            }),
+            parsed: Some(ParsedValue::BitcoinPayment("bitcoin:bc1qexample?amount=0.01".parse().unwrap())),
+            ..KeyResult::default()
+        },
+    );
+    response.insert(
+        "nostr",
+        KeyResult {
+            value: Some("npub1example".to_string()),
+            raw_value: Some(b"npub1example".to_vec()),
+            raw_records: vec![RawRecord::new(["npub1example"])],
+            source: Some(Source::Cache),
+            stale: true,
+            offline: true,
+            used_fallback_resolver: true,
+            chunked: true,
+            signature: Some(SignatureStatus::NoKey),
+            ..KeyResult::default()
+        },
+    );
+    response.insert(
+        "lightning",
+        KeyResult {
+            value: Some("lnurl1\u{fffd}".to_string()),
+            raw_value: Some(b"lnurl1\xff".to_vec()),
+            raw_records: vec![RawRecord::new([&b"lnurl1\xff"[..]])],
+            source: Some(Source::Override),
+            encoding_issue: Some(ValueEncodingIssue::NotUtf8),
+            parse_error: Some(ParseError::new("not bech32")),
+            ..KeyResult::default()
+        },
+    );
+    response.insert("node-uri", KeyResult { error: Some(SelfieError::NoRecords), source: Some(Source::Dns), ..KeyResult::default() });
+    response.insert("pgp", KeyResult { error: Some(SelfieError::NxDomain), attempts: Some(1), ..KeyResult::default() });
+    response
+}
+
+#[test]
+fn the_json_shape_is_stable() {
+    let json = serde_json::to_string_pretty(&response()).unwrap();
+
+    assert_eq!(json, GOLDEN_JSON.trim_end());
+}
+
+#[test]
+fn responses_round_trip() {
+    let response = response();
+
+    let json = serde_json::to_string(&response).unwrap();
+
+    assert_eq!(serde_json::from_str::<RecordsResponse>(&json).unwrap(), response);
+    assert_eq!(serde_json::from_str::<RecordsResponse>(GOLDEN_JSON).unwrap(), response);
+}
+
+#[test]
+fn descriptive_fields_are_not_read_back() {
+    let mut response = RecordsResponse::default();
+    let wire = WireInfo { response_size: Some(612), truncated: false, transport_used: Transport::Tcp, edns_udp_size: None, ttl: Some(300), response_code: Some(0), server: None };
+    response.insert(
+        "nostr",
+        KeyResult {
+            value: Some("npub1example".to_string()),
+            wire: Some(wire),
+            route: Some(Route::Suffix("corp.example.com".to_string())),
+            resolved_at: Some(UNIX_EPOCH + Duration::from_millis(1_700_000_000_750)),
+            ..KeyResult::default()
+        },
+    );
+
+    let json = serde_json::to_value(&response).unwrap();
+    assert_eq!(json["nostr"]["route"], "suffix corp.example.com");
+    let decoded: RecordsResponse = serde_json::from_value(json).unwrap();
+
+    let result = decoded.get("nostr").unwrap();
+    assert_eq!((result.wire, &result.route), (None, &None));
+    assert_eq!(result.resolved_at, Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)));
+}
+
+#[test]
+fn errors_come_back_by_their_code() {
+    let decode = |error: SelfieError| serde_json::from_value::<SelfieError>(serde_json::to_value(&error).unwrap()).unwrap();
+
+    for error in [SelfieError::NoRecords, SelfieError::Offline, SelfieError::InvalidSignature, SelfieError::ChunkDigestMismatch, SelfieError::NxDomain] {
+        assert_eq!(decode(error.clone()), error);
+    }
+    let invalid = SelfieError::InvalidRecord { key: "pgp".to_string(), reason: "not a URI".to_string() };
+    assert_eq!(decode(invalid.clone()), invalid);
+    assert_eq!(
+        serde_json::to_value(&invalid).unwrap()["fields"],
+        serde_json::json!({ "key": "pgp", "reason": "not a URI" })
+    );
+
+    // Written without fields, a kind that has some is only known by its message.
+    let legacy = r#"{ "code": "E_INVALID_RECORD", "numeric_code": 7, "message": "Invalid pgp record: not a URI" }"#;
+    assert_eq!(serde_json::from_str::<SelfieError>(legacy).unwrap(), SelfieError::Resolver(invalid.to_string()));
+}
+
+#[test]
+fn unknown_values_are_rejected() {
+    let error = serde_json::from_str::<KeyResult>(r#"{"value": "x", "source": "carrier pigeon"}"#).unwrap_err();
+    assert!(error.to_string().contains("unknown source \"carrier pigeon\""), "{}", error);
+
+    let error = serde_json::from_str::<KeyResult>(r#"{"raw_value": "not base64!"}"#).unwrap_err();
+    assert!(error.to_string().contains("invalid"), "{}", error);
+}
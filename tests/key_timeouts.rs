@@ -0,0 +1,123 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{LookupOptions, SelfieError, SelfieRecordsSDK, TimeoutBudget};
+
+#[test]
+fn test_key_timeout_only_affects_that_key() {
+    let mock = MockTxtResolver::new()
+        .with_record("_bitcoin-payment.example.com", &["bitcoin:bc1qexample"])
+        .with_delay("_bitcoin-payment.example.com", Duration::from_millis(300))
+        .with_record("_pgp.example.com", &["ABCD1234"])
+        .with_delay("_pgp.example.com", Duration::from_millis(100));
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let options = LookupOptions::new()
+        .timeout(Duration::from_secs(2))
+        .key_timeout("bitcoin-payment", Duration::from_millis(50)).unwrap();
+    let records = sdk.get_records_with("example.com", Some(vec!["bitcoin-payment", "pgp"]), None, &options).to_map();
+
+    let payment = &records["bitcoin-payment"];
+    assert_eq!(payment["value"], None);
+    assert!(payment["error"].as_ref().unwrap().contains("per-key budget of 50ms"));
+
+    assert_eq!(records["pgp"]["value"].as_deref(), Some("ABCD1234"));
+}
+
+#[test]
+fn test_key_timeout_caps_total_across_attempts() {
+    let mock = Arc::new(
+        MockTxtResolver::new()
+            .with_record("_nostr.example.com", &["npub1example"])
+            .with_delay("_nostr.example.com", Duration::from_millis(200)),
+    );
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+
+    let options = LookupOptions::new()
+        .attempts(5)
+        .key_timeout("nostr", Duration::from_millis(60)).unwrap();
+    let records = sdk.get_records_with("example.com", Some(vec!["nostr"]), None, &options).to_map();
+
+    let error = records["nostr"]["error"].clone().unwrap();
+    assert!(error.contains("per-key budget of 60ms exceeded after 1 attempt(s)"), "{}", error);
+    assert_eq!(mock.calls(), 1);
+}
+
+#[test]
+fn test_global_timeout_retries_each_attempt() {
+    let mock = Arc::new(
+        MockTxtResolver::new()
+            .with_record("_nostr.example.com", &["npub1example"])
+            .with_delay("_nostr.example.com", Duration::from_millis(200)),
+    );
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+
+    let options = LookupOptions::new().timeout(Duration::from_millis(20)).attempts(3);
+    let records = sdk.get_records_with("example.com", Some(vec!["nostr"]), None, &options).to_map();
+
+    let error = records["nostr"]["error"].clone().unwrap();
+    assert!(error.contains("global timeout of 20ms exceeded after 3 attempt(s)"), "{}", error);
+    assert_eq!(mock.calls(), 3);
+}
+
+#[test]
+fn test_missing_records_are_not_retried() {
+    let mock = Arc::new(MockTxtResolver::new().with_nxdomain("_nostr.example.com").with_record("example.com", &[]).with_record("_pgp.example.com", &[]));
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+
+    let options = LookupOptions::new().attempts(5).backoff(Duration::ZERO);
+    let response = sdk.get_records_response("example.com", Some(vec!["nostr", "pgp"]), None, &options);
+
+    for key in ["nostr", "pgp"] {
+        let result = response.get(key).unwrap();
+        assert_eq!((result.error.clone(), result.attempts), (Some(SelfieError::NoRecords), Some(1)), "{}", key);
+    }
+    // One query per key, and one asking whether example.com exists.
+    assert_eq!(mock.calls(), 3);
+}
+
+#[test]
+fn test_deadline_bounds_the_whole_call() {
+    let mock = MockTxtResolver::new()
+        .with_record("_bitcoin-payment.example.com", &["bitcoin:bc1q"])
+        .with_delay("_bitcoin-payment.example.com", Duration::from_millis(100))
+        .with_timeout("_nostr.example.com")
+        .with_record("_pgp.example.com", &["ABCD"]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let options = LookupOptions::new().timeout(Duration::from_secs(5)).deadline(Duration::from_millis(250));
+    let started = Instant::now();
+    let response = sdk.get_records_response("example.com", Some(vec!["bitcoin-payment", "nostr", "pgp"]), None, &options);
+
+    assert!(started.elapsed() < Duration::from_millis(400), "{:?}", started.elapsed());
+    assert_eq!(response.get("bitcoin-payment").unwrap().value.as_deref(), Some("bitcoin:bc1q"));
+    let deadline = Duration::from_millis(250);
+    let nostr = response.get("nostr").unwrap();
+    assert_eq!(nostr.error, Some(SelfieError::Timeout(TimeoutBudget::Call { deadline, attempts: 1 })));
+    // Reached after the deadline passed, so never queried.
+    let pgp = response.get("pgp").unwrap();
+    assert_eq!(pgp.error, Some(SelfieError::Timeout(TimeoutBudget::Call { deadline, attempts: 0 })));
+    let message = pgp.error.as_ref().unwrap().to_string();
+    assert!(message.contains("call deadline of 250ms exceeded after 0 attempt(s)"), "{}", message);
+}
+
+#[test]
+fn test_key_timeouts_match_keys_ignoring_case() {
+    let mock = MockTxtResolver::new()
+        .with_record("_nostr.example.com", &["npub1example"])
+        .with_delay("_nostr.example.com", Duration::from_millis(300));
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let options = LookupOptions::new().timeout(Duration::from_secs(2)).key_timeout("Nostr", Duration::from_millis(50)).unwrap();
+    let response = sdk.get_records_response("example.com", Some(vec!["nostr"]), None, &options);
+
+    let error = response.get("nostr").unwrap().error.clone();
+    assert!(matches!(error, Some(SelfieError::Timeout(TimeoutBudget::Key { budget, .. })) if budget == Duration::from_millis(50)), "{:?}", error);
+}
+
+#[test]
+fn test_key_timeouts_reject_invalid_keys() {
+    let result = LookupOptions::new().key_timeout("my key!", Duration::from_millis(50));
+    assert!(matches!(result, Err(SelfieError::InvalidKey { key }) if key == "my key!"));
+}
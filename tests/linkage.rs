@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use selfie_records_sdk::linkage::{KeyLocator, LinkageStatus, StepOutcome};
+use selfie_records_sdk::testing::{MockPgpKeySource, MockTxtResolver};
+use selfie_records_sdk::SelfieRecordsSDK;
+
+// Generated with GnuPG 2.2: ed25519 keys for Alice and Mallory, each signing
+// PAYMENT_URI (no trailing newline) as a detached signature.
+const ALICE_KEY: &str = include_str!("fixtures/linkage/alice.asc");
+const ALICE_SIG: &str = include_str!("fixtures/linkage/alice.sig");
+const ALICE_SIG_SHA512: &str = include_str!("fixtures/linkage/alice512.sig");
+const MALLORY_KEY: &str = include_str!("fixtures/linkage/mallory.asc");
+const MALLORY_SIG: &str = include_str!("fixtures/linkage/mallory.sig");
+const ALICE_FINGERPRINT: &str = "59E1187D54EA5CF2FCD2919B9B34E6136E5F37D6";
+const MALLORY_FINGERPRINT: &str = "8AC607F2E406AE208BF480E30B7B9A3783F1AB00";
+const PAYMENT_URI: &str = "bitcoin:bc1qexamplepaymentaddress";
+
+/// The base64 body of an armored block, as it would fit in a TXT record.
+fn unarmored(armored: &str) -> String {
+    armored
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with("-----") && !line.starts_with('='))
+        .collect()
+}
+
+fn records(sig: &str, pgp: &str) -> MockTxtResolver {
+    MockTxtResolver::new()
+        .with_record("_bitcoin-payment.example.com", &[PAYMENT_URI])
+        .with_record("_bitcoin-payment-sig.example.com", &[sig])
+        .with_record("_pgp.example.com", &[pgp])
+}
+
+#[test]
+fn test_linked_payment_is_attested() {
+    for (sig, key) in [
+        (ALICE_SIG.to_string(), ALICE_KEY.to_string()),
+        (unarmored(ALICE_SIG), unarmored(ALICE_KEY)),
+        (unarmored(ALICE_SIG_SHA512), ALICE_KEY.to_string()),
+    ] {
+        let sdk = SelfieRecordsSDK::with_resolver(Arc::new(records(&sig, &key)));
+        let report = sdk.verify_linked("example.com");
+
+        assert_eq!(report.payment_uri.as_deref(), Some(PAYMENT_URI));
+        assert_eq!(report.signature, StepOutcome::Passed, "{:?}", report);
+        assert_eq!(report.status, LinkageStatus::Attested { fingerprint: ALICE_FINGERPRINT.to_string() });
+    }
+}
+
+#[test]
+fn test_signature_by_another_key_is_not_attested() {
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(records(MALLORY_SIG, ALICE_KEY)));
+    let report = sdk.verify_linked("example.com");
+
+    assert!(report.pgp_key.is_passed());
+    assert!(matches!(report.signature, StepOutcome::Failed(_)));
+    assert_eq!(report.status, LinkageStatus::NotAttested);
+}
+
+#[test]
+fn test_tampered_payment_is_not_attested() {
+    let mock = records(ALICE_SIG, ALICE_KEY).with_record("_bitcoin-payment.example.com", &["bitcoin:bc1qattacker"]);
+    let report = SelfieRecordsSDK::with_resolver(Arc::new(mock)).verify_linked("example.com");
+
+    assert!(report.signature_record.is_passed());
+    assert!(matches!(report.signature, StepOutcome::Failed(_)));
+    assert!(!report.is_attested());
+}
+
+#[test]
+fn test_missing_pieces_degrade_to_not_attested() {
+    let mock = MockTxtResolver::new()
+        .with_record("_bitcoin-payment.example.com", &[PAYMENT_URI])
+        .with_record("_pgp.example.com", &[ALICE_KEY]);
+    let report = SelfieRecordsSDK::with_resolver(Arc::new(mock)).verify_linked("example.com");
+    assert!(report.payment_record.is_passed());
+    assert!(matches!(report.signature_record, StepOutcome::Failed(_)));
+    assert_eq!(report.pgp_key, StepOutcome::Skipped);
+    assert_eq!(report.status, LinkageStatus::NotAttested);
+
+    let report = SelfieRecordsSDK::with_resolver(Arc::new(records("not a signature", ALICE_KEY))).verify_linked("example.com");
+    assert_eq!(report.signature_record, StepOutcome::Failed("no OpenPGP signature in record".to_string()));
+
+    let report = SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new())).verify_linked("example.com");
+    assert!(matches!(report.payment_record, StepOutcome::Failed(_)));
+    assert_eq!(report.signature_record, StepOutcome::Skipped);
+
+    let report = SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new())).verify_linked("not a name");
+    assert!(matches!(report.payment_record, StepOutcome::Failed(_)));
+}
+
+#[test]
+fn test_key_fetched_through_key_source() {
+    let url = "https://example.com/alice.asc";
+    let keys = MockPgpKeySource::new()
+        .with_key(KeyLocator::Url(url.to_string()), ALICE_KEY.as_bytes())
+        .with_key(KeyLocator::Fingerprint(MALLORY_FINGERPRINT.to_string()), ALICE_KEY.as_bytes());
+    let keys = Arc::new(keys);
+
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(Arc::new(records(ALICE_SIG, url)))
+        .pgp_key_source(keys.clone())
+        .build()
+        .unwrap();
+    assert!(sdk.verify_linked("example.com").is_attested());
+
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(Arc::new(records(ALICE_SIG, &MALLORY_FINGERPRINT.to_lowercase())))
+        .pgp_key_source(keys)
+        .build()
+        .unwrap();
+    let report = sdk.verify_linked("example.com");
+    assert_eq!(
+        report.pgp_key,
+        StepOutcome::Failed(format!("fetched key does not match fingerprint {}", MALLORY_FINGERPRINT))
+    );
+
+    let report = SelfieRecordsSDK::with_resolver(Arc::new(records(ALICE_SIG, url))).verify_linked("example.com");
+    assert_eq!(
+        report.pgp_key,
+        StepOutcome::Failed("record refers to a key but no key source is configured".to_string())
+    );
+}
+
+#[test]
+fn test_linked_records_for_email_identifier() {
+    let mock = MockTxtResolver::new()
+        .with_record("mallory.user._bitcoin-payment.example.com", &[PAYMENT_URI])
+        .with_record("mallory.user._bitcoin-payment-sig.example.com", &[MALLORY_SIG])
+        .with_record("mallory.user._pgp.example.com", &[MALLORY_KEY]);
+    let report = SelfieRecordsSDK::with_resolver(Arc::new(mock)).verify_linked("mallory@example.com");
+
+    assert_eq!(report.status, LinkageStatus::Attested { fingerprint: MALLORY_FINGERPRINT.to_string() });
+}
@@ -0,0 +1,14 @@
+use log::LevelFilter;
+use selfie_records_sdk::{init_logging, SelfieRecordsSDK};
+
+#[test]
+fn the_sdk_leaves_the_logger_to_the_application() {
+    let first = SelfieRecordsSDK::new();
+    let second = SelfieRecordsSDK::new();
+    drop((first, second));
+
+    // No SDK installed a logger, so the application still can, once.
+    assert!(init_logging(LevelFilter::Warn).is_ok());
+    assert!(init_logging(LevelFilter::Warn).is_err());
+    let _ = SelfieRecordsSDK::default();
+}
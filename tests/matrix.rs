@@ -0,0 +1,111 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{LookupOptions, SelfieError, SelfieRecordsSDK, TemplateNameScheme, TxtResolver};
+
+fn options() -> LookupOptions {
+    LookupOptions::new().attempts(1)
+}
+
+#[test]
+fn names_sharing_an_owner_name_share_a_lookup() {
+    let mock = Arc::new(
+        MockTxtResolver::new()
+            .with_record("_nostr.shared.example", &["npub1shared"])
+            .with_record("_pgp.shared.example", &["ABCD"]),
+    );
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(mock.clone())
+        .name_scheme(TemplateNameScheme::new("_{key}.{domain}", "_{key}.{domain}"))
+        .build()
+        .unwrap();
+    let names = ["alice@shared.example", "bob@shared.example", "shared.example", "Shared.Example."];
+
+    let matrix = sdk.resolve_matrix(&names, &["nostr", "pgp"], &options());
+
+    assert_eq!(mock.calls(), 2);
+    assert_eq!(matrix.lookups, 2);
+    assert_eq!(matrix.len(), 8);
+    for name in names {
+        assert_eq!(matrix.get(name, "nostr").unwrap().value.as_deref(), Some("npub1shared"), "{}", name);
+        assert_eq!(matrix.get(name, "pgp").unwrap().value.as_deref(), Some("ABCD"), "{}", name);
+    }
+}
+
+#[test]
+fn cells_are_indexed_by_name_and_key() {
+    let mock = MockTxtResolver::new()
+        .with_record("_nostr.one.example", &["npub1one"])
+        .with_record("_pgp.one.example", &[])
+        .with_record("_nostr.two.example", &["npub1two"])
+        .with_record("_pgp.two.example", &["TWO"]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+    let names = ["one.example", "two.example", "not a name", "two.example"];
+
+    let matrix = sdk.resolve_matrix(&names, &["nostr", "pgp"], &options());
+
+    assert_eq!(matrix.len(), 6);
+    assert_eq!(matrix.get("one.example", "nostr").unwrap().value.as_deref(), Some("npub1one"));
+    assert_eq!(matrix.get("one.example", "pgp").unwrap().error, Some(SelfieError::NoRecords));
+    assert_eq!(matrix.get("two.example", "nostr").unwrap().value.as_deref(), Some("npub1two"));
+    assert_eq!(matrix.get("two.example", "pgp").unwrap().value.as_deref(), Some("TWO"));
+    assert!(matches!(matrix.get("not a name", "pgp").unwrap().error, Some(SelfieError::InvalidName { .. })));
+    assert_eq!(matrix.row("two.example").unwrap().len(), 2);
+    assert_eq!(matrix.get("three.example", "nostr"), None);
+    assert_eq!(matrix.get("one.example", "node-uri"), None);
+    assert_eq!((matrix.found(), matrix.missing(), matrix.failed()), (3, 1, 2));
+}
+
+#[test]
+fn cells_are_yielded_as_they_finish() {
+    let mock = MockTxtResolver::new()
+        .with_record("_nostr.slow.example", &["npub1slow"])
+        .with_delay("_nostr.slow.example", Duration::from_millis(100))
+        .with_record("_nostr.fast.example", &["npub1fast"]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let mut cells = sdk.matrix_cells(&["slow.example", "fast.example"], &["nostr"], &options());
+    assert_eq!(cells.cells(), 2);
+
+    let order: Vec<String> = cells.by_ref().map(|cell| cell.name).collect();
+    assert_eq!(order, ["fast.example", "slow.example"]);
+    assert_eq!(cells.lookups(), 2);
+}
+
+/// Answers every name after a delay, recording how many lookups overlap.
+#[derive(Default)]
+struct Overlapping {
+    running: AtomicUsize,
+    most: AtomicUsize,
+}
+
+#[async_trait]
+impl TxtResolver for Overlapping {
+    async fn txt_lookup(&self, _name: &str) -> Result<Vec<String>, SelfieError> {
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.most.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        Ok(vec!["value".to_string()])
+    }
+}
+
+#[test]
+fn concurrency_is_bounded() {
+    let names: Vec<String> = (0..12).map(|i| format!("name{}.bounded.example", i)).collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+
+    for concurrency in [1, 3, 16] {
+        let resolver = Arc::new(Overlapping::default());
+        let sdk = SelfieRecordsSDK::with_resolver(resolver.clone());
+
+        let matrix = sdk.resolve_matrix(&names, &["nostr"], &options().concurrency(concurrency));
+
+        assert_eq!(matrix.found(), 12);
+        assert_eq!(resolver.most.load(Ordering::SeqCst), concurrency.min(12));
+        assert!(matrix.duration >= Duration::from_millis(10) * (12 / concurrency.min(12)) as u32);
+    }
+}
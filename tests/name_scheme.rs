@@ -0,0 +1,166 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use selfie_records_sdk::config::Config;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{
+    get_txt_record_key, BuildError, LookupOptions, NameScheme, SdkBuilder, SelfieError, SelfieNameScheme, SelfieRecordsSDK, TemplateNameScheme,
+    TxtResolver, DEFAULT_DOMAIN_TEMPLATE, DEFAULT_EMAIL_TEMPLATE,
+};
+
+#[test]
+fn test_default_scheme_matches_selfie_layout() {
+    assert_eq!(SelfieNameScheme.domain_name("pgp", "example.com"), "_pgp.example.com");
+    assert_eq!(
+        SelfieNameScheme.email_name("alice", "bitcoin-payment", "example.com"),
+        "alice.user._bitcoin-payment.example.com"
+    );
+}
+
+#[test]
+fn test_template_scheme_end_to_end() {
+    let mock = MockTxtResolver::new()
+        .with_record("nostr._id.example.com", &["npub1domain"])
+        .with_record("alice._id.nostr.example.com", &["npub1alice"]);
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(Arc::new(mock))
+        .name_scheme(TemplateNameScheme::new("{key}._id.{domain}", "{local}._id.{key}.{domain}"))
+        .build()
+        .unwrap();
+
+    let records = sdk.get_records("example.com", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("npub1domain"));
+
+    let records = sdk.get_records("alice@example.com", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("npub1alice"));
+}
+
+#[test]
+fn test_generated_names_are_length_checked() {
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(Arc::new(MockTxtResolver::new()))
+        .name_scheme(TemplateNameScheme::new("{key}{key}{key}{key}{key}{key}{key}.{domain}", "{local}.{key}.{domain}"))
+        .build()
+        .unwrap();
+
+    let records = sdk.get_records("example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+    let error = records["bitcoin-payment"]["error"].clone().unwrap();
+    assert!(error.contains("label longer than 63 characters"), "{}", error);
+}
+
+/// Answers `records` and logs every name asked, in order.
+#[derive(Default)]
+struct Logged {
+    records: Vec<(&'static str, &'static str)>,
+    asked: Mutex<Vec<String>>,
+}
+
+#[async_trait]
+impl TxtResolver for Logged {
+    async fn txt_lookup(&self, name: &str) -> Result<Vec<String>, SelfieError> {
+        self.asked.lock().unwrap().push(name.to_string());
+        Ok(self.records.iter().filter(|(owner, _)| *owner == name).map(|(_, value)| value.to_string()).collect())
+    }
+}
+
+fn logged(records: Vec<(&'static str, &'static str)>) -> Arc<Logged> {
+    Arc::new(Logged { records, ..Logged::default() })
+}
+
+#[test]
+fn test_default_templates_match_the_selfie_layout() {
+    let scheme = TemplateNameScheme::default();
+    assert_eq!(scheme.domain_template(), DEFAULT_DOMAIN_TEMPLATE);
+    assert_eq!(scheme.domain_name("pgp", "example.com"), "_pgp.example.com");
+    assert_eq!(scheme.email_name("alice", "bitcoin-payment", "example.com"), "alice.user._bitcoin-payment.example.com");
+    assert_eq!(scheme.email_name("alice", "nostr", "example.com"), get_txt_record_key("alice@example.com", "nostr").unwrap());
+    // Braces in a local part are not placeholders.
+    assert_eq!(scheme.email_name("{key}", "nostr", "example.com"), "{key}.user._nostr.example.com");
+    assert_eq!(TemplateNameScheme::parse(DEFAULT_DOMAIN_TEMPLATE, DEFAULT_EMAIL_TEMPLATE), Ok(scheme));
+}
+
+#[test]
+fn test_templates_come_from_the_builder_and_the_config() {
+    let resolver = logged(vec![("alice._nostr.example.com", "npub1alice"), ("_nostr._selfie.example.com", "npub1domain")]);
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(resolver.clone())
+        .domain_template("_{key}._selfie.{domain}")
+        .email_template("{user}._{key}.{domain}")
+        .build()
+        .unwrap();
+
+    let alice = sdk.get_records_response("alice@example.com", Some(vec!["nostr"]), None, &LookupOptions::new());
+    let alice = alice.get("nostr").unwrap();
+    assert_eq!(alice.value.as_deref(), Some("npub1alice"));
+    assert_eq!(alice.name_template.as_deref(), Some("{user}._{key}.{domain}"));
+    let records = sdk.get_records("example.com", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("npub1domain"));
+    assert_eq!(records["nostr"]["name_template"].as_deref(), Some("_{key}._selfie.{domain}"));
+    assert_eq!(*resolver.asked.lock().unwrap(), ["alice._nostr.example.com", "_nostr._selfie.example.com"]);
+
+    let vars = [("SELFIE_EMAIL_TEMPLATE".to_string(), "{user}._{key}.{domain}".to_string())];
+    let config = Config::from_vars(vars).unwrap();
+    assert_eq!(config.email_template.as_deref(), Some("{user}._{key}.{domain}"));
+    let resolver = logged(vec![("alice._nostr.example.com", "npub1alice")]);
+    let sdk = SdkBuilder::from_config(config).resolver(resolver).build().unwrap();
+    assert_eq!(sdk.get_records("alice@example.com", Some(vec!["nostr"]), None).to_map()["nostr"]["value"].as_deref(), Some("npub1alice"));
+    // Names of the default layout report no template.
+    let sdk = SelfieRecordsSDK::with_resolver(logged(vec![("_nostr.example.com", "npub1")]));
+    assert_eq!(sdk.get_records("example.com", Some(vec!["nostr"]), None).to_map()["nostr"].get("name_template"), None);
+}
+
+#[test]
+fn test_invalid_templates_are_rejected_at_build_time() {
+    let error = |builder: SdkBuilder| match builder.build().unwrap_err() {
+        BuildError::InvalidNameTemplate(e) => e.to_string(),
+        other => panic!("{:?}", other),
+    };
+    let builder = || SelfieRecordsSDK::builder().resolver(logged(vec![]));
+    assert_eq!(error(builder().domain_template("_selfie.{domain}")), "Invalid name template \"_selfie.{domain}\": missing {key}");
+    assert_eq!(error(builder().domain_template("_{key}")), "Invalid name template \"_{key}\": missing {domain}");
+    assert_eq!(error(builder().email_template("_{key}.{domain}")), "Invalid name template \"_{key}.{domain}\": missing {user}");
+    assert_eq!(error(builder().domain_template("{user}._{key}.{domain}")), "Invalid name template \"{user}._{key}.{domain}\": domains have no {user}");
+    assert_eq!(error(builder().domain_template("_{kee}.{domain}")), "Invalid name template \"_{kee}.{domain}\": unknown placeholder {kee}");
+    assert_eq!(error(builder().domain_template("_{key.{domain}")), "Invalid name template \"_{key.{domain}\": unknown placeholder {key.{domain}");
+    assert_eq!(error(builder().domain_template("_}{key}.{domain}")), "Invalid name template \"_}{key}.{domain}\": unbalanced brace");
+    let fallback = TemplateNameScheme::new("_{key}.{domain}", "{user}.{domain}");
+    assert_eq!(error(builder().name_template_fallbacks(vec![fallback])), "Invalid name template \"{user}.{domain}\": missing {key}");
+
+    let vars = [("SELFIE_DOMAIN_TEMPLATE".to_string(), "_selfie.{domain}".to_string())];
+    assert_eq!(Config::from_vars(vars).unwrap_err().to_string(), "Invalid value for SELFIE_DOMAIN_TEMPLATE: missing {key}");
+}
+
+#[test]
+fn test_fallback_templates_are_tried_in_order_until_one_answers() {
+    let resolver = logged(vec![
+        ("alice._nostr.example.com", "npub1second"),
+        ("_nostr.alice._selfie.example.com", "npub1third"),
+    ]);
+    let fallbacks = vec![
+        TemplateNameScheme::parse("_{key}.{domain}", "{user}._{key}.{domain}").unwrap(),
+        TemplateNameScheme::parse("_{key}._selfie.{domain}", "_{key}.{user}._selfie.{domain}").unwrap(),
+    ];
+    let sdk = SelfieRecordsSDK::builder().resolver(resolver.clone()).name_template_fallbacks(fallbacks.clone()).build().unwrap();
+
+    let response = sdk.get_records_response("alice@example.com", Some(vec!["nostr"]), None, &LookupOptions::new());
+    let result = response.get("nostr").unwrap();
+    assert_eq!(result.value.as_deref(), Some("npub1second"));
+    assert_eq!(result.name_template.as_deref(), Some("{user}._{key}.{domain}"));
+    assert_eq!(result.query.as_ref().unwrap().fqdn, "alice._nostr.example.com");
+    assert_eq!(result.attempts, Some(2));
+    assert_eq!(*resolver.asked.lock().unwrap(), ["alice.user._nostr.example.com", "alice._nostr.example.com"]);
+
+    // Per call, in the order given.
+    resolver.asked.lock().unwrap().clear();
+    let options = LookupOptions::new().name_templates(vec![fallbacks[1].clone(), fallbacks[0].clone()]);
+    let response = sdk.get_records_response("alice@example.com", Some(vec!["nostr"]), None, &options);
+    assert_eq!(response.get("nostr").unwrap().value.as_deref(), Some("npub1third"));
+    assert_eq!(*resolver.asked.lock().unwrap(), ["_nostr.alice._selfie.example.com"]);
+
+    // Without records anywhere, every template is tried and the last reported.
+    let response = sdk.get_records_response("bob@example.com", Some(vec!["nostr"]), None, &LookupOptions::new());
+    let result = response.get("nostr").unwrap();
+    assert_eq!(result.error, Some(SelfieError::NoRecords));
+    assert_eq!(result.name_template.as_deref(), Some("_{key}.{user}._selfie.{domain}"));
+    assert_eq!(result.attempts, Some(3));
+}
@@ -0,0 +1,128 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::verify::{Comparison, VerificationResult};
+use selfie_records_sdk::{CacheConfig, KeyResult, LookupOptions, SelfieError, SelfieRecordsSDK, Source, TxtResolver};
+
+const NAME: &str = "_pgp.example.com";
+
+fn sdk(resolver: Arc<dyn TxtResolver>, negative_ttl: Duration) -> SelfieRecordsSDK {
+    let config = CacheConfig { negative_ttl, ..CacheConfig::default() };
+    SelfieRecordsSDK::builder().resolver(resolver).cache(config).build().unwrap()
+}
+
+fn lookup(sdk: &SelfieRecordsSDK, options: &LookupOptions) -> KeyResult {
+    sdk.get_records_response("example.com", Some(vec!["pgp"]), None, options).get("pgp").unwrap().clone()
+}
+
+/// Answers `NAME` with whatever `values` holds at the time.
+#[derive(Default)]
+struct Publishing {
+    values: Mutex<Vec<String>>,
+    calls: AtomicUsize,
+}
+
+#[async_trait]
+impl TxtResolver for Publishing {
+    async fn txt_lookup(&self, _name: &str) -> Result<Vec<String>, SelfieError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(self.values.lock().unwrap().clone())
+    }
+}
+
+#[test]
+fn empty_answers_are_cached_for_the_negative_ttl() {
+    let mock = Arc::new(MockTxtResolver::new().with_record(NAME, &[]));
+    let sdk = sdk(mock.clone(), Duration::from_millis(100));
+
+    assert_eq!(lookup(&sdk, &LookupOptions::new()).error, Some(SelfieError::NoRecords));
+    let cached = lookup(&sdk, &LookupOptions::new());
+
+    assert_eq!(cached.source, Some(Source::Cache));
+    assert_eq!(cached.error, Some(SelfieError::NoRecords));
+    assert_eq!(mock.calls(), 1);
+    thread::sleep(Duration::from_millis(150));
+    assert_eq!(lookup(&sdk, &LookupOptions::new()).source, Some(Source::Dns));
+    assert_eq!(mock.calls(), 2);
+}
+
+#[test]
+fn nxdomain_is_cached_and_keeps_its_error() {
+    let mock = Arc::new(MockTxtResolver::new().with_nxdomain(NAME).with_nxdomain("example.com"));
+    let sdk = sdk(mock.clone(), Duration::from_secs(30));
+
+    assert_eq!(lookup(&sdk, &LookupOptions::new()).error, Some(SelfieError::NxDomain));
+    let calls = mock.calls();
+    let cached = lookup(&sdk, &LookupOptions::new());
+
+    assert_eq!(cached.source, Some(Source::Cache));
+    assert_eq!(cached.error, Some(SelfieError::NxDomain));
+    assert_eq!(mock.calls(), calls);
+    let records = sdk.get_records("example.com", Some(vec!["pgp"]), None).to_map();
+    assert_eq!(records["pgp"]["source"].as_deref(), Some("cache"));
+}
+
+#[test]
+fn the_soa_minimum_overrides_the_negative_ttl() {
+    // The mock reports the SOA minimum as the answer's TTL.
+    let mock = Arc::new(MockTxtResolver::new().with_nxdomain(NAME).with_nxdomain("example.com").with_ttl(NAME, 0));
+    let sdk = sdk(mock.clone(), Duration::from_secs(30));
+
+    lookup(&sdk, &LookupOptions::new());
+    let calls = mock.calls();
+    assert_eq!(lookup(&sdk, &LookupOptions::new()).source, Some(Source::Dns));
+    assert!(mock.calls() > calls);
+}
+
+#[test]
+fn a_lapsed_negative_answer_does_not_shadow_a_new_record() {
+    let resolver = Arc::new(Publishing::default());
+    let sdk = sdk(resolver.clone(), Duration::from_millis(100));
+
+    assert_eq!(lookup(&sdk, &LookupOptions::new()).error, Some(SelfieError::NoRecords));
+    *resolver.values.lock().unwrap() = vec!["-----BEGIN PGP PUBLIC KEY BLOCK-----".to_string()];
+    assert_eq!(lookup(&sdk, &LookupOptions::new()).source, Some(Source::Cache));
+    thread::sleep(Duration::from_millis(150));
+
+    let published = lookup(&sdk, &LookupOptions::new());
+    assert_eq!(published.source, Some(Source::Dns));
+    assert_eq!(published.value.as_deref(), Some("-----BEGIN PGP PUBLIC KEY BLOCK-----"));
+    assert_eq!(resolver.calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn bypass_cache_forces_a_fresh_query() {
+    let resolver = Arc::new(Publishing::default());
+    // Keeps the records, which carry no TTL, as well.
+    let config = CacheConfig { min_ttl: Duration::from_secs(30), ..CacheConfig::default() };
+    let sdk = SelfieRecordsSDK::builder().resolver(resolver.clone()).cache(config).build().unwrap();
+    let fresh = LookupOptions::new().bypass_cache(true);
+
+    lookup(&sdk, &LookupOptions::new());
+    *resolver.values.lock().unwrap() = vec!["pgp-key".to_string()];
+    assert_eq!(lookup(&sdk, &fresh).value.as_deref(), Some("pgp-key"));
+    assert_eq!(resolver.calls.load(Ordering::SeqCst), 2);
+    // The fresh answer replaced the cached one.
+    assert_eq!(lookup(&sdk, &LookupOptions::new()).value.as_deref(), Some("pgp-key"));
+    assert_eq!(resolver.calls.load(Ordering::SeqCst), 2);
+
+    *resolver.values.lock().unwrap() = vec!["rotated-key".to_string()];
+    let cached = sdk.verify_record_with("example.com", "pgp", "rotated-key", Comparison::Exact);
+    assert!(matches!(cached, VerificationResult::MismatchFound { .. }), "{:?}", cached);
+    let verified = sdk.verify_record_with_options("example.com", "pgp", "rotated-key", Comparison::Exact, &fresh);
+    assert_eq!(verified, VerificationResult::Match);
+}
+
+#[test]
+fn a_zero_negative_ttl_caches_nothing() {
+    let mock = Arc::new(MockTxtResolver::new().with_record(NAME, &[]));
+    let sdk = sdk(mock.clone(), Duration::ZERO);
+
+    lookup(&sdk, &LookupOptions::new());
+    assert_eq!(lookup(&sdk, &LookupOptions::new()).source, Some(Source::Dns));
+    assert_eq!(mock.calls(), 2);
+}
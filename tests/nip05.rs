@@ -0,0 +1,76 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::server::{replay_server, CapturedRequest, HttpReply};
+use selfie_records_sdk::nip05::{HttpNip05Source, Nip05Check};
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{SelfieError, SelfieRecordsSDK};
+
+const NOSTR_HEX: &str = "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d";
+const NPUB: &str = "npub180cvv07tjdrrgpa0j7j7tmnyl2yr6yr7l8j4s3evf6u64th6gkwsyjh6w6";
+const OTHER_HEX: &str = "82341f882b6eabcd2ba7f1ef90aad961cf074af15b9ef44a09f9d2a8fbfbe6a2";
+
+fn public_key(hex: &str) -> [u8; 32] {
+    let bytes: Vec<u8> = (0..64).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect();
+    bytes.try_into().unwrap()
+}
+
+fn serving(nostr_json: &str) -> (SelfieRecordsSDK, Arc<Mutex<Vec<CapturedRequest>>>) {
+    let (base, requests) = replay_server(vec![HttpReply::json(200, nostr_json)]);
+    let mock = MockTxtResolver::new().with_record("alice.user._nostr.example.com", &[&format!("{} wss://relay.example.com", NPUB)]);
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(Arc::new(mock))
+        .nip05_source(Arc::new(HttpNip05Source::with_base_url(&base)))
+        .build()
+        .unwrap();
+    (sdk, requests)
+}
+
+#[test]
+fn matching_keys_agree() {
+    let (sdk, requests) = serving(&format!(r#"{{"names": {{"alice": "{}"}}}}"#, NOSTR_HEX));
+
+    let check = sdk.cross_check_nip05("alice@example.com").unwrap();
+
+    assert_eq!(check, Nip05Check::Agree { public_key: public_key(NOSTR_HEX) });
+    let requests = requests.lock().unwrap();
+    assert!(requests[0].request_line().starts_with("GET /.well-known/nostr.json?name=alice "), "{}", requests[0].request_line());
+}
+
+#[test]
+fn a_different_or_missing_entry_disagrees() {
+    let (sdk, _) = serving(&format!(r#"{{"names": {{"alice": "{}"}}}}"#, OTHER_HEX));
+    let check = sdk.cross_check_nip05("alice@example.com").unwrap();
+    assert_eq!(check, Nip05Check::Disagree { dns: public_key(NOSTR_HEX), nip05: Some(public_key(OTHER_HEX)) });
+
+    let (sdk, _) = serving(&format!(r#"{{"names": {{"bob": "{}"}}}}"#, NOSTR_HEX));
+    let check = sdk.cross_check_nip05("alice@example.com").unwrap();
+    assert_eq!(check, Nip05Check::Disagree { dns: public_key(NOSTR_HEX), nip05: None });
+}
+
+#[test]
+fn bare_domains_are_not_covered() {
+    let (sdk, requests) = serving("{}");
+
+    assert_eq!(sdk.cross_check_nip05("example.com"), Ok(Nip05Check::NotApplicable));
+    assert!(requests.lock().unwrap().is_empty());
+}
+
+#[test]
+fn unreadable_documents_are_errors() {
+    let (sdk, _) = serving(r#"{"names": {"alice": "npub1notahexkey"}}"#);
+
+    assert!(matches!(sdk.cross_check_nip05("alice@example.com"), Err(SelfieError::InvalidRecord { .. })));
+}
+
+#[test]
+#[cfg(not(feature = "tls"))]
+fn https_needs_tls() {
+    let mock = MockTxtResolver::new().with_record("alice.user._nostr.example.com", &[NPUB]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let error = sdk.cross_check_nip05("alice@example.com").unwrap_err();
+
+    assert!(error.to_string().contains("https needs the tls feature"), "{}", error);
+}
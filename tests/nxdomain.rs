@@ -0,0 +1,61 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::server::zone_server;
+use common::txt_record;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{DirectResolver, LookupOptions, RecordEntry, SelfieError, SelfieRecordsSDK};
+
+fn lookup(mock: MockTxtResolver, name: &str) -> (Option<SelfieError>, u32, usize) {
+    let mock = Arc::new(mock);
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+    let response = sdk.get_records_response(name, Some(vec!["bitcoin-payment"]), None, &LookupOptions::new().attempts(1));
+    let result = response.get("bitcoin-payment").unwrap();
+    (result.error.clone(), response.queries_issued, mock.calls())
+}
+
+#[test]
+fn a_name_that_does_not_exist_is_nxdomain() {
+    let mock = MockTxtResolver::new().with_nxdomain("alice.user._bitcoin-payment.typo.example").with_nxdomain("typo.example");
+
+    let (error, queries_issued, _) = lookup(mock, "alice@typo.example");
+
+    assert_eq!(error, Some(SelfieError::NxDomain));
+    assert_eq!(error.unwrap().code(), "E_NXDOMAIN");
+    assert_eq!(queries_issued, 2);
+}
+
+#[test]
+fn a_missing_record_of_an_existing_name_is_no_records() {
+    // Most zones answer NXDOMAIN for a record name that holds nothing.
+    let mock = MockTxtResolver::new().with_nxdomain("_bitcoin-payment.example.com").with_record("example.com", &[]);
+    assert_eq!(lookup(mock, "example.com").0, Some(SelfieError::NoRecords));
+
+    // An empty NOERROR answer says the name exists without asking again.
+    let mock = MockTxtResolver::new().with_record("_bitcoin-payment.example.com", &[]);
+    assert_eq!(lookup(mock, "example.com"), (Some(SelfieError::NoRecords), 1, 1));
+}
+
+#[test]
+fn a_failing_probe_leaves_no_records() {
+    // `example.com` is not registered, so probing it fails.
+    let mock = MockTxtResolver::new().with_nxdomain("_bitcoin-payment.example.com");
+
+    assert_eq!(lookup(mock, "example.com").0, Some(SelfieError::NoRecords));
+}
+
+#[test]
+fn nxdomain_answers_from_a_server_are_told_apart() {
+    let server = zone_server(vec![txt_record("_bitcoin-payment.example.com.", "bitcoin:bc1qzone")]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DirectResolver::new(server)));
+
+    let response = sdk.get_records_response("example.com", Some(vec!["bitcoin-payment", "nostr"]), None, &LookupOptions::new());
+    assert_eq!(response.get("bitcoin-payment").unwrap().value.as_deref(), Some("bitcoin:bc1qzone"));
+    assert_eq!(response.get("nostr").unwrap().error, Some(SelfieError::NoRecords));
+
+    let response = sdk.get_records_response("typo.example", Some(vec!["nostr"]), None, &LookupOptions::new());
+    let result = response.get("nostr").unwrap();
+    assert_eq!(result.error, Some(SelfieError::NxDomain));
+    assert_eq!(result.entry(), RecordEntry::NotFound);
+}
@@ -0,0 +1,108 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{CacheConfig, LookupOptions, Observer, QueryOutcome, SelfieError, SelfieRecordsSDK};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Event {
+    Start(String, String),
+    End(String, String, QueryOutcome),
+    CacheHit(String),
+}
+
+#[derive(Default)]
+struct Recording(Mutex<Vec<Event>>);
+
+impl Recording {
+    fn take(&self) -> Vec<Event> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+impl Observer for Recording {
+    fn on_query_start(&self, fqdn: &str, key: &str) {
+        self.0.lock().unwrap().push(Event::Start(fqdn.to_string(), key.to_string()));
+    }
+
+    fn on_query_end(&self, fqdn: &str, key: &str, outcome: &QueryOutcome, _duration: Duration) {
+        self.0.lock().unwrap().push(Event::End(fqdn.to_string(), key.to_string(), outcome.clone()));
+    }
+
+    fn on_cache_hit(&self, fqdn: &str) {
+        self.0.lock().unwrap().push(Event::CacheHit(fqdn.to_string()));
+    }
+}
+
+fn start(fqdn: &str, key: &str) -> Event {
+    Event::Start(fqdn.to_string(), key.to_string())
+}
+
+fn end(fqdn: &str, key: &str, outcome: QueryOutcome) -> Event {
+    Event::End(fqdn.to_string(), key.to_string(), outcome)
+}
+
+fn observed(mock: MockTxtResolver) -> (SelfieRecordsSDK, Arc<Recording>) {
+    let config = CacheConfig { min_ttl: Duration::from_secs(30), ..CacheConfig::default() };
+    let sdk = SelfieRecordsSDK::builder().resolver(Arc::new(mock)).cache(config).build().unwrap();
+    let recording = Arc::new(Recording::default());
+    sdk.set_observer(recording.clone());
+    (sdk, recording)
+}
+
+#[test]
+fn queries_and_cache_hits_are_observed_in_order() {
+    let mock = MockTxtResolver::new().with_record("_nostr.example.com", &["npub1alice"]);
+    let (sdk, recording) = observed(mock);
+
+    sdk.get_records("example.com", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(
+        recording.take(),
+        [start("_nostr.example.com", "nostr"), end("_nostr.example.com", "nostr", QueryOutcome::Answered { records: 1 })]
+    );
+
+    sdk.get_records("example.com", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(recording.take(), [Event::CacheHit("_nostr.example.com".to_string())]);
+}
+
+#[test]
+fn outcomes_keep_the_kind_of_failure() {
+    let mock = MockTxtResolver::new()
+        .with_nxdomain("_pgp.example.com")
+        .with_nxdomain("example.com")
+        .with_timeout("_did.example.com");
+    let (sdk, recording) = observed(mock);
+    let options = LookupOptions::new().attempts(1).timeout(Duration::from_millis(50));
+
+    sdk.get_records_with("example.com", Some(vec!["pgp"]), None, &options).to_map();
+    assert_eq!(
+        recording.take(),
+        [
+            start("_pgp.example.com", "pgp"),
+            end("_pgp.example.com", "pgp", QueryOutcome::NxDomain),
+            start("example.com", "pgp"),
+            end("example.com", "pgp", QueryOutcome::NxDomain),
+        ]
+    );
+
+    sdk.get_records_with("example.com", Some(vec!["did"]), None, &options).to_map();
+    let events = recording.take();
+    assert_eq!(events.len(), 2);
+    assert!(matches!(&events[1], Event::End(_, _, outcome @ QueryOutcome::Failed(SelfieError::Timeout(_))) if outcome.label() == "E_TIMEOUT"));
+}
+
+#[test]
+fn a_panicking_observer_does_not_fail_the_lookup() {
+    struct Panicking;
+    impl Observer for Panicking {
+        fn on_query_start(&self, _fqdn: &str, _key: &str) {
+            panic!("observer bug");
+        }
+    }
+
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new().with_record("_nostr.example.com", &["npub1alice"])));
+    sdk.set_observer(Arc::new(Panicking));
+
+    let records = sdk.get_records("example.com", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("npub1alice"));
+}
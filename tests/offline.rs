@@ -0,0 +1,124 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{LookupOptions, SelfieRecordsSDK};
+
+const PAYMENT_NAME: &str = "alice.user._bitcoin-payment.example.com";
+
+fn network() -> Arc<MockTxtResolver> {
+    Arc::new(
+        MockTxtResolver::new()
+            .with_record(PAYMENT_NAME, &["bitcoin:bc1qnetwork"])
+            .with_record("_nostr.example.com", &["npub1domain"]),
+    )
+}
+
+#[test]
+fn test_offline_never_queries() {
+    let mock = network();
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(mock.clone())
+        .cache_ttl(Duration::from_secs(60))
+        .override_record("alice@example.com", "nostr", "npub1pinned")
+        .offline(true)
+        .build()
+        .unwrap();
+
+    let records = sdk.get_records("alice@example.com", Some(vec!["bitcoin-payment", "nostr"]), None).to_map();
+    assert_eq!(records["bitcoin-payment"]["value"], None);
+    assert_eq!(records["bitcoin-payment"]["error"].as_deref(), Some("Offline: no cached answer"));
+    assert_eq!(records["bitcoin-payment"]["offline"].as_deref(), Some("true"));
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("npub1pinned"));
+    assert_eq!(records["nostr"]["offline"].as_deref(), Some("true"));
+
+    let records = sdk.get_records("example.com", None, None).to_map();
+    assert!(records.values().all(|entry| entry["offline"].as_deref() == Some("true")));
+    assert_eq!(mock.calls(), 0);
+}
+
+#[test]
+fn test_offline_serves_cache() {
+    let mock = network();
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(mock.clone())
+        .cache_ttl(Duration::from_secs(60))
+        .build()
+        .unwrap();
+
+    let records = sdk.get_records("alice@example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+    assert_eq!(records["bitcoin-payment"]["source"].as_deref(), Some("dns"));
+    assert_eq!(records["bitcoin-payment"].get("offline"), None);
+
+    sdk.set_offline(true);
+    assert!(sdk.is_offline());
+    let records = sdk.get_records("alice@example.com", Some(vec!["bitcoin-payment", "nostr"]), None).to_map();
+    assert_eq!(records["bitcoin-payment"]["value"].as_deref(), Some("bitcoin:bc1qnetwork"));
+    assert_eq!(records["bitcoin-payment"]["source"].as_deref(), Some("cache"));
+    assert_eq!(records["bitcoin-payment"]["offline"].as_deref(), Some("true"));
+    assert_eq!(records["bitcoin-payment"].get("stale"), None);
+    assert_eq!(records["nostr"]["error"].as_deref(), Some("Offline: no cached answer"));
+    assert_eq!(mock.calls(), 1);
+}
+
+#[test]
+fn test_offline_serves_stale_cache() {
+    let mock = network();
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(mock.clone())
+        .cache_ttl(Duration::from_millis(20))
+        .build()
+        .unwrap();
+    sdk.get_records("alice@example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+    thread::sleep(Duration::from_millis(40));
+
+    let offline = LookupOptions::new().offline(true);
+    let records = sdk.get_records_with("alice@example.com", Some(vec!["bitcoin-payment"]), None, &offline).to_map();
+    assert_eq!(records["bitcoin-payment"]["value"].as_deref(), Some("bitcoin:bc1qnetwork"));
+    assert_eq!(records["bitcoin-payment"]["stale"].as_deref(), Some("true"));
+    assert_eq!(records["bitcoin-payment"]["offline"].as_deref(), Some("true"));
+    assert_eq!(mock.calls(), 1);
+
+    // Online, a stale entry is refreshed rather than served.
+    let records = sdk.get_records("alice@example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+    assert_eq!(records["bitcoin-payment"]["source"].as_deref(), Some("dns"));
+    assert_eq!(mock.calls(), 2);
+}
+
+#[test]
+fn test_offline_switch_is_per_call() {
+    let mock = network();
+    let sdk = Arc::new(SelfieRecordsSDK::with_resolver(mock.clone()));
+
+    let lookups: Vec<_> = (0..8)
+        .map(|_| {
+            let sdk = sdk.clone();
+            thread::spawn(move || {
+                (0..50)
+                    .map(|_| sdk.get_records("alice@example.com", Some(vec!["bitcoin-payment"]), None).to_map())
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    for i in 0..200 {
+        sdk.set_offline(i % 2 == 0);
+        thread::yield_now();
+    }
+    sdk.set_offline(false);
+
+    let mut online = 0;
+    for lookup in lookups {
+        for records in lookup.join().unwrap() {
+            let entry = &records["bitcoin-payment"];
+            match entry.get("offline") {
+                Some(_) => assert_eq!(entry["error"].as_deref(), Some("Offline: no cached answer")),
+                None => {
+                    assert_eq!(entry["value"].as_deref(), Some("bitcoin:bc1qnetwork"));
+                    online += 1;
+                }
+            }
+        }
+    }
+    assert!(mock.calls() <= online);
+}
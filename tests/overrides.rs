@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{BuildError, OverrideError, RecordOverrides, SelfieRecordsSDK};
+
+fn network() -> Arc<MockTxtResolver> {
+    Arc::new(
+        MockTxtResolver::new()
+            .with_record("alice.user._bitcoin-payment.example.com", &["bitcoin:bc1qnetwork"])
+            .with_record("bob.user._nostr.example.com", &["npub1network"])
+            .with_record("_nostr.example.com", &["npub1domain"]),
+    )
+}
+
+#[test]
+fn test_override_wins_over_network() {
+    let mock = network();
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(mock.clone())
+        .override_record("alice@example.com", "bitcoin-payment", "bitcoin:bc1qpinned")
+        .build()
+        .unwrap();
+
+    let records = sdk.get_records("alice@example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+    assert_eq!(records["bitcoin-payment"]["value"].as_deref(), Some("bitcoin:bc1qpinned"));
+    assert_eq!(records["bitcoin-payment"]["source"].as_deref(), Some("override"));
+    assert_eq!(mock.calls(), 0);
+
+    let records = sdk.get_records("bob@example.com", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("npub1network"));
+    assert_eq!(records["nostr"]["source"].as_deref(), Some("dns"));
+    assert_eq!(mock.calls(), 1);
+}
+
+#[test]
+fn test_wildcard_overrides() {
+    let mock = network();
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(mock.clone())
+        .override_record("*@Example.com", "nostr", "npub1everyone")
+        .override_record("carol@example.com", "nostr", "npub1carol")
+        .build()
+        .unwrap();
+
+    let records = sdk.get_records("bob@EXAMPLE.com", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("npub1everyone"));
+    let records = sdk.get_records("carol@example.com", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("npub1carol"));
+    assert_eq!(mock.calls(), 0);
+
+    // The wildcard covers addresses, not the bare domain.
+    let records = sdk.get_records("example.com", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("npub1domain"));
+    assert_eq!(records["nostr"]["source"].as_deref(), Some("dns"));
+}
+
+#[test]
+fn test_override_files() {
+    let json = r#"{
+        "alice@example.com": { "bitcoin-payment": "bitcoin:bc1qjson" },
+        "*@example.org": { "nostr": "npub1json" }
+    }"#;
+    let toml = r#"
+        # demo identities
+        ["alice@example.com"]
+        bitcoin-payment = "bitcoin:bc1qjson"   # pinned
+
+        ['*@example.org']
+        "nostr" = 'npub1json'
+    "#;
+    let from_json = RecordOverrides::parse_json(json).unwrap();
+    assert_eq!(from_json.len(), 2);
+    assert_eq!(RecordOverrides::parse_toml(toml).unwrap(), from_json);
+
+    let path = std::env::temp_dir().join(format!("selfie-overrides-{}.json", std::process::id()));
+    std::fs::write(&path, json).unwrap();
+    let loaded = RecordOverrides::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let sdk = SelfieRecordsSDK::builder().resolver(network()).overrides(loaded).build().unwrap();
+    let records = sdk.get_records("dave@example.org", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("npub1json"));
+}
+
+#[test]
+fn test_invalid_override_files() {
+    assert!(matches!(RecordOverrides::parse_json("[]"), Err(OverrideError::Parse(_))));
+    assert!(matches!(
+        RecordOverrides::parse_json(r#"{"alice@example.com": {"nostr": 1}}"#),
+        Err(OverrideError::Parse(_))
+    ));
+    assert!(matches!(
+        RecordOverrides::parse_json(r#"{"not a name": {"nostr": "npub1"}}"#),
+        Err(OverrideError::InvalidIdentifier { .. })
+    ));
+    for toml in ["nostr = \"npub1\"", "[\"a@example.com\"]\nnostr = npub1", "[\"a@example.com\"\nnostr = \"x\"", "[\"a@example.com\"]\nnostr = \"x\" y"] {
+        assert!(matches!(RecordOverrides::parse_toml(toml), Err(OverrideError::Parse(_))), "{:?}", toml);
+    }
+    assert!(matches!(RecordOverrides::load("/nonexistent/overrides.toml"), Err(OverrideError::Io { .. })));
+}
+
+#[test]
+fn test_overrides_can_be_forbidden() {
+    let result = SelfieRecordsSDK::builder()
+        .override_record("alice@example.com", "nostr", "npub1")
+        .forbid_overrides(true)
+        .build();
+    assert_eq!(result.unwrap_err(), BuildError::OverridesForbidden);
+    assert!(SelfieRecordsSDK::builder().forbid_overrides(true).build().is_ok());
+
+    let result = SelfieRecordsSDK::builder().override_record("alice@", "nostr", "npub1").build();
+    assert!(matches!(result, Err(BuildError::InvalidOverride(OverrideError::InvalidIdentifier { .. }))));
+}
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{KeyResult, LookupOptions, ParseError, ParsedValue, RecordParser, SelfieRecordsSDK};
+
+const NPUB: &str = "npub180cvv07tjdrrgpa0j7j7tmnyl2yr6yr7l8j4s3evf6u64th6gkwsyjh6w6";
+
+/// Parses `matrix` records of the form `@user:server`.
+struct MatrixParser;
+
+impl RecordParser for MatrixParser {
+    fn key(&self) -> &str {
+        "Matrix"
+    }
+
+    fn parse(&self, raw: &str) -> Result<ParsedValue, ParseError> {
+        let (user, server) = raw.strip_prefix('@').and_then(|id| id.split_once(':')).ok_or_else(|| ParseError::new("expected @user:server"))?;
+        Ok(ParsedValue::Custom(serde_json::json!({ "user": user, "server": server })))
+    }
+}
+
+fn network() -> Arc<MockTxtResolver> {
+    Arc::new(
+        MockTxtResolver::new()
+            .with_record("_nostr.example.com", &[NPUB])
+            .with_record("_bitcoin-payment.example.com", &["bitcoin:bc1qexample?amount=0.01"])
+            .with_record("_matrix.example.com", &["@alice:example.org"])
+            .with_record("_nostr.broken.com", &["npub1broken"])
+            .with_record("_matrix.broken.com", &["alice"]),
+    )
+}
+
+fn lookup(sdk: &SelfieRecordsSDK, name: &str, key: &str) -> KeyResult {
+    sdk.get_records_response(name, Some(vec![key]), None, &LookupOptions::default()).get(key).unwrap().clone()
+}
+
+#[test]
+fn well_known_keys_are_parsed() {
+    let sdk = SelfieRecordsSDK::builder().resolver(network()).build().unwrap();
+
+    let nostr = lookup(&sdk, "example.com", "nostr");
+    assert_eq!(nostr.parsed, Some(ParsedValue::Nostr(NPUB.parse().unwrap())));
+    let payment = lookup(&sdk, "example.com", "bitcoin-payment");
+    match payment.parsed {
+        Some(ParsedValue::BitcoinPayment(uri)) => assert_eq!(uri.amount(), Some("0.01")),
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(payment.parse_error, None);
+    // No parser, no parsed value.
+    assert_eq!(lookup(&sdk, "example.com", "matrix").parsed, None);
+}
+
+#[test]
+fn registered_parsers_run() {
+    let sdk = SelfieRecordsSDK::builder().resolver(network()).register_parser(MatrixParser).build().unwrap();
+
+    let result = lookup(&sdk, "example.com", "matrix");
+    assert_eq!(result.value.as_deref(), Some("@alice:example.org"));
+    assert_eq!(result.parsed, Some(ParsedValue::Custom(serde_json::json!({ "user": "alice", "server": "example.org" }))));
+}
+
+#[test]
+fn values_that_do_not_parse_are_kept() {
+    let sdk = SelfieRecordsSDK::builder().resolver(network()).register_parser(MatrixParser).build().unwrap();
+
+    for (key, value) in [("nostr", "npub1broken"), ("matrix", "alice")] {
+        let result = lookup(&sdk, "broken.com", key);
+        assert_eq!(result.value.as_deref(), Some(value));
+        assert_eq!(result.error, None);
+        assert_eq!(result.parsed, None);
+        assert!(result.parse_error.is_some(), "{}", key);
+    }
+    let records = sdk.get_records("broken.com", Some(vec!["matrix"]), None).to_map();
+    assert_eq!(records["matrix"]["parse_error"].as_deref(), Some("expected @user:server"));
+}
+
+#[test]
+fn registered_parsers_replace_built_in_ones() {
+    struct Raw;
+    impl RecordParser for Raw {
+        fn key(&self) -> &str {
+            "nostr"
+        }
+        fn parse(&self, raw: &str) -> Result<ParsedValue, ParseError> {
+            Ok(ParsedValue::Custom(raw.into()))
+        }
+    }
+    let sdk = SelfieRecordsSDK::builder().resolver(network()).register_parser(Raw).build().unwrap();
+
+    assert_eq!(lookup(&sdk, "broken.com", "nostr").parsed, Some(ParsedValue::Custom("npub1broken".into())));
+}
+
+#[test]
+fn overrides_are_parsed() {
+    let sdk = SelfieRecordsSDK::builder().resolver(network()).override_record("example.com", "nostr", NPUB).build().unwrap();
+
+    assert_eq!(lookup(&sdk, "example.com", "nostr").parsed, Some(ParsedValue::Nostr(NPUB.parse().unwrap())));
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use common::server::{replay_server, HttpReply};
+use selfie_records_sdk::linkage::HttpPgpKeySource;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{PgpRecord, SelfieError, SelfieRecordsSDK};
+
+const ALICE_KEY: &str = include_str!("fixtures/linkage/alice.asc");
+const ALICE_FINGERPRINT: &str = "59E1187D54EA5CF2FCD2919B9B34E6136E5F37D6";
+const MALLORY_FINGERPRINT: &str = "8AC607F2E406AE208BF480E30B7B9A3783F1AB00";
+
+/// An SDK whose `pgp` records for alice@example.com are `records`, with
+/// `{url}` replaced by the URL of a server holding alice's key.
+fn serving(records: &[&str], source: Option<HttpPgpKeySource>) -> SelfieRecordsSDK {
+    let (base, _) = replay_server(vec![HttpReply::status(200).header("Content-Type", "application/pgp-keys").body(ALICE_KEY)]);
+    let url = format!("{}/keys/alice.asc", base);
+    let records: Vec<String> = records.iter().map(|record| record.replace("{url}", &url)).collect();
+    let records: Vec<&str> = records.iter().map(String::as_str).collect();
+    let mock = MockTxtResolver::new().with_record("alice.user._pgp.example.com", &records);
+    let mut builder = SelfieRecordsSDK::builder().resolver(Arc::new(mock));
+    if let Some(source) = source {
+        builder = builder.pgp_key_source(Arc::new(source));
+    }
+    builder.build().unwrap()
+}
+
+#[test]
+fn records_normalize_to_their_kind() {
+    assert_eq!(
+        "0x59e1 187d 54ea 5cf2 fcd2 919b 9b34 e613 6e5f 37d6".parse::<PgpRecord>().unwrap(),
+        PgpRecord::Fingerprint(ALICE_FINGERPRINT.to_string())
+    );
+    assert_eq!(" https://example.com/alice.asc ".parse::<PgpRecord>().unwrap(), PgpRecord::Url("https://example.com/alice.asc".to_string()));
+    assert!("59E1187D".parse::<PgpRecord>().is_err());
+}
+
+#[test]
+fn the_fetched_key_matches_the_fingerprint_record() {
+    let sdk = serving(&["{url}", &ALICE_FINGERPRINT.to_ascii_lowercase()], None);
+
+    let verification = sdk.fetch_and_verify_pgp("alice@example.com").unwrap();
+
+    assert!(verification.url.ends_with("/keys/alice.asc"), "{}", verification.url);
+    assert_eq!(verification.fingerprints, [ALICE_FINGERPRINT]);
+    assert_eq!(verification.matched.as_deref(), Some(ALICE_FINGERPRINT));
+}
+
+#[test]
+fn a_key_with_another_fingerprint_is_a_mismatch() {
+    let sdk = serving(&["{url}", MALLORY_FINGERPRINT], None);
+
+    let error = sdk.fetch_and_verify_pgp("alice@example.com").unwrap_err();
+
+    assert_eq!(
+        error,
+        SelfieError::FingerprintMismatch { expected: vec![MALLORY_FINGERPRINT.to_string()], fetched: vec![ALICE_FINGERPRINT.to_string()] }
+    );
+    assert_eq!(error.code(), "E_FINGERPRINT_MISMATCH");
+}
+
+#[test]
+fn without_a_fingerprint_record_nothing_is_matched() {
+    let sdk = serving(&["{url}"], None);
+
+    let verification = sdk.fetch_and_verify_pgp("alice@example.com").unwrap();
+
+    assert_eq!(verification.fingerprints, [ALICE_FINGERPRINT]);
+    assert_eq!(verification.matched, None);
+}
+
+#[test]
+fn a_key_larger_than_the_limit_is_refused() {
+    let sdk = serving(&["{url}", ALICE_FINGERPRINT], Some(HttpPgpKeySource::new().max_size(256)));
+
+    let error = sdk.fetch_and_verify_pgp("alice@example.com").unwrap_err();
+
+    assert!(matches!(&error, SelfieError::Resolver(message) if message.contains("larger than 256 bytes")), "{:?}", error);
+}
+
+#[test]
+fn a_server_that_never_answers_times_out() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/alice.asc", listener.local_addr().unwrap());
+    let mock = MockTxtResolver::new().with_record("alice.user._pgp.example.com", &[&url]);
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(Arc::new(mock))
+        .pgp_key_source(Arc::new(HttpPgpKeySource::new().timeout(Duration::from_millis(100))))
+        .build()
+        .unwrap();
+
+    let started = Instant::now();
+    let error = sdk.fetch_and_verify_pgp("alice@example.com").unwrap_err();
+
+    assert!(matches!(&error, SelfieError::Resolver(message) if message.contains("timed out")), "{:?}", error);
+    assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+}
+
+#[test]
+fn records_without_a_key_url_cannot_be_fetched() {
+    let sdk = serving(&[ALICE_FINGERPRINT], None);
+
+    let error = sdk.fetch_and_verify_pgp("alice@example.com").unwrap_err();
+
+    assert!(matches!(&error, SelfieError::InvalidRecord { key, .. } if key == "pgp"), "{:?}", error);
+}
+
+#[test]
+#[cfg(not(feature = "tls"))]
+fn https_key_urls_need_tls() {
+    let mock = MockTxtResolver::new().with_record("alice.user._pgp.example.com", &["https://example.com/alice.asc"]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let error = sdk.fetch_and_verify_pgp("alice@example.com").unwrap_err();
+
+    assert!(matches!(&error, SelfieError::Resolver(message) if message.contains("https needs the tls feature")), "{:?}", error);
+}
@@ -0,0 +1,381 @@
+use std::sync::Arc;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{
+    Bip21Uri, Did, DnssecStatus, Host, Lnurl, LookupOptions, NodeUri, NostrKey, PaymentCode, PgpRecord, SelfieError,
+    SelfieRecordsSDK, DEFAULT_RECORDS, EXTENDED_RECORDS,
+};
+
+// The NIP-19 example key.
+const NOSTR_HEX: &str = "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d";
+const NPUB: &str = "npub180cvv07tjdrrgpa0j7j7tmnyl2yr6yr7l8j4s3evf6u64th6gkwsyjh6w6";
+// The LUD-01 example.
+const LNURL: &str = "LNURL1DP68GURN8GHJ7UM9WFMXJCM99E3K7MF0V9CXJ0M385EKVCENXC6R2C35XVUKXEFCV5MKVV34X5EKZD3EV56NYD3HXQURZEPEXEJXXEPNXSCRVWFNV9NXZCN9XQ6XYEFHVGCXXCMYXYMNSERXFQ5FNS";
+const LNURL_URL: &str = "https://service.com/api?q=3fc3645b439ce8e7f2553a69e5267081d96dcd340693afabe04be7b0ccd178df";
+const NODE: &str = "03864ef025fde8fb587d989186ce6a4a186895ee44a926bfc370e2c366597a3f8f@3.33.236.230:9735";
+const FINGERPRINT: &str = "0123456789ABCDEF0123456789ABCDEF01234567";
+// Alice's and Bob's payment codes from the BIP-47 test vectors.
+const ALICE_CODE: &str =
+    "PM8TJTLJbPRGxSbc8EJi42Wrr6QbNSaSSVJ5Y3E4pbCYiTHUskHg13935Ubb7q8tx9GVbh2UuRnBc3WSyJHhUrw8KhprKnn9eDznYGieTzFcwQRya4GA";
+const BOB_CODE: &str =
+    "PM8TJS2JxQ5ztXUpBBRnpTbcUXbUHy2T1abfrb3KkAAtMEGNbey4oumH7Hc578WgQJhPjBxteQ5GHHToTYHE3A1w6p7tU6KSoFmWBVbFGjKPisZDbP97";
+
+/// A resolver where every well-known record is empty unless set with `with_record` afterwards.
+fn records() -> MockTxtResolver {
+    ["bitcoin-payment", "pgp", "nostr", "node-uri"]
+        .iter()
+        .fold(MockTxtResolver::new(), |resolver, key| resolver.with_record(&format!("_{}.example.com", key), &[]))
+}
+
+fn sdk(resolver: MockTxtResolver) -> SelfieRecordsSDK {
+    SelfieRecordsSDK::with_resolver(Arc::new(resolver))
+}
+
+#[test]
+fn well_known_records_are_parsed_into_their_fields() {
+    let resolver = records()
+        .with_record("_bitcoin-payment.example.com", &["bitcoin:bc1qexample?amount=0.01&label=Tip%20jar"])
+        .with_record("_pgp.example.com", &[&FINGERPRINT.to_lowercase()])
+        .with_record("_nostr.example.com", &[&format!("{} wss://relay.example.com", NPUB)])
+        .with_record("_node-uri.example.com", &[NODE]);
+
+    let profile = sdk(resolver).resolve_profile("example.com");
+
+    let uri = profile.bitcoin_payment.unwrap();
+    assert_eq!(uri.address, "bc1qexample");
+    assert_eq!(uri.amount(), Some("0.01"));
+    assert_eq!(uri.param("LABEL"), Some("Tip jar"));
+    assert_eq!(profile.pgp, Some(PgpRecord::Fingerprint(FINGERPRINT.to_string())));
+    let nostr = profile.nostr.unwrap();
+    assert_eq!(nostr.to_hex(), NOSTR_HEX);
+    assert_eq!(nostr.relays, ["wss://relay.example.com"]);
+    assert_eq!(profile.node_uri.unwrap().to_string(), NODE);
+    assert!(profile.problems.is_empty());
+    assert_eq!(profile.resolver, "custom");
+    assert_eq!(profile.dnssec, DnssecStatus::NotValidated);
+}
+
+#[test]
+fn a_field_that_fails_to_parse_does_not_spoil_the_others() {
+    let resolver = records()
+        .with_record("_bitcoin-payment.example.com", &["bitcoin:bc1qexample"])
+        .with_record("_nostr.example.com", &["npub1notakey"]);
+
+    let profile = sdk(resolver).resolve_profile("example.com");
+
+    assert!(profile.bitcoin_payment.is_some());
+    assert_eq!(profile.nostr, None);
+    assert_eq!(profile.problems.len(), 1);
+    let (key, error) = &profile.problems[0];
+    assert_eq!(key, "nostr");
+    assert!(matches!(error, SelfieError::InvalidRecord { key, .. } if key == "nostr"));
+}
+
+#[test]
+fn missing_records_are_not_problems() {
+    let profile = sdk(records()).resolve_profile("example.com");
+
+    assert!(profile.is_empty());
+    assert!(profile.problems.is_empty());
+}
+
+#[test]
+fn other_keys_land_in_extra() {
+    let resolver = records()
+        .with_record("_pgp.example.com", &["https://example.com/key.asc"])
+        .with_record("_custom.example.com", &["anything goes"]);
+
+    let profile = sdk(resolver).resolve_profile_with("example.com", Some(vec!["pgp", "custom"]), None, &Default::default());
+
+    assert_eq!(profile.pgp, Some(PgpRecord::Url("https://example.com/key.asc".to_string())));
+    assert_eq!(profile.extra.get("custom").map(String::as_str), Some("anything goes"));
+}
+
+#[test]
+fn lnurls_decode_to_their_url() {
+    let lnurl: Lnurl = LNURL.parse().unwrap();
+
+    assert_eq!(lnurl.url(), LNURL_URL);
+    assert_eq!(lnurl.to_string(), LNURL.to_ascii_lowercase());
+    assert_eq!(format!("lightning:{}", LNURL.to_ascii_lowercase()).parse::<Lnurl>(), Ok(lnurl));
+}
+
+#[test]
+fn lightning_addresses_are_validated() {
+    let address: Lnurl = "Alice.Payments+tips@Example.com".parse().unwrap();
+    assert_eq!(address, Lnurl::Address { user: "alice.payments+tips".to_string(), domain: "example.com".to_string() });
+    assert_eq!(address.url(), "https://example.com/.well-known/lnurlp/alice.payments+tips");
+    assert_eq!(address.to_string(), "alice.payments+tips@example.com");
+
+    for value in ["@example.com", "al ice@example.com", "alice@", "alice@1.2.3.4", "alice@exa_mple.com"] {
+        assert!(value.parse::<Lnurl>().is_err(), "{}", value);
+    }
+}
+
+#[test]
+fn broken_lnurls_are_errors() {
+    let reason = |value: &str| match value.parse::<Lnurl>() {
+        Err(SelfieError::InvalidRecord { key, reason }) if key == "lnurl" => reason,
+        other => panic!("{}: {:?}", value, other),
+    };
+    let corrupted = format!("{}Q", &LNURL[..LNURL.len() - 1]);
+
+    assert_eq!(reason(&corrupted), "bech32 checksum mismatch");
+    assert_eq!(reason(NPUB), "expected an lnurl prefix, found npub");
+    let http = "lnurl1dp68gup69uhhxetjwe5kxefwvdhk6tmpwp5n7ufaxytyqzuk";
+    assert!(reason(http).ends_with("is not https"), "{}", reason(http));
+}
+
+#[test]
+fn lnurl_records_are_parsed_into_the_profile() {
+    let resolver = records()
+        .with_record("_lnurl.example.com", &[LNURL])
+        .with_record("_lightning-address.example.com", &["alice@example.com"]);
+
+    let keys = EXTENDED_RECORDS[6..].to_vec();
+    assert_eq!(keys, ["lnurl", "lightning-address"]);
+    let profile = sdk(resolver).resolve_profile_with("example.com", Some(keys), None, &Default::default());
+
+    assert_eq!(profile.lnurl.map(|lnurl| lnurl.url()).as_deref(), Some(LNURL_URL));
+    assert_eq!(profile.lightning_address.map(|address| address.url()).as_deref(), Some("https://example.com/.well-known/lnurlp/alice"));
+    assert!(profile.problems.is_empty(), "{:?}", profile.problems);
+}
+
+#[test]
+fn a_corrupted_lnurl_is_a_problem_of_the_profile() {
+    let corrupted = format!("{}Q", &LNURL[..LNURL.len() - 1]);
+    let resolver = records().with_record("_lnurl.example.com", &[&corrupted]);
+
+    let profile = sdk(resolver).resolve_profile_with("example.com", Some(vec!["lnurl"]), None, &Default::default());
+
+    assert_eq!(profile.lnurl, None);
+    assert_eq!(profile.problems.len(), 1);
+    assert_eq!(profile.problems[0].0, "lnurl");
+}
+
+#[test]
+fn the_default_records_are_unchanged() {
+    assert_eq!(DEFAULT_RECORDS, ["bitcoin-payment", "pgp", "nostr", "node-uri"]);
+    assert_eq!(EXTENDED_RECORDS[..4], DEFAULT_RECORDS);
+}
+
+#[test]
+fn bip21_uris_round_trip() {
+    let uri: Bip21Uri = "BITCOIN:?lno=lno1qsgqexample&sp=sp1qexample".parse().unwrap();
+    assert_eq!(uri.address, "");
+    assert_eq!(uri.param("lno"), Some("lno1qsgqexample"));
+    assert_eq!(uri.to_string(), "bitcoin:?lno=lno1qsgqexample&sp=sp1qexample");
+
+    assert!("bitcoin:".parse::<Bip21Uri>().is_err());
+    assert!("lightning:lnbc1".parse::<Bip21Uri>().is_err());
+}
+
+#[test]
+fn nostr_keys_accept_hex_and_check_npub_checksums() {
+    let key: NostrKey = NOSTR_HEX.parse().unwrap();
+    assert_eq!(key.npub(), NPUB);
+
+    let mut corrupted = NPUB.to_string();
+    corrupted.replace_range(10..11, if &NPUB[10..11] == "q" { "p" } else { "q" });
+    assert!(corrupted.parse::<NostrKey>().is_err());
+    assert!(key.to_string().replace("npub", "nsec").parse::<NostrKey>().is_err());
+
+    assert_eq!(NOSTR_HEX.to_ascii_uppercase().parse::<NostrKey>(), Ok(key.clone()));
+    assert_eq!(format!("  {}\t ", NPUB).parse::<NostrKey>(), Ok(key));
+    assert!(NOSTR_HEX[..63].parse::<NostrKey>().is_err());
+    assert!(NOSTR_HEX.replace('3', "g").parse::<NostrKey>().is_err());
+}
+
+#[test]
+fn node_uris_default_the_port_and_bracket_ipv6() {
+    let pubkey = NODE.split('@').next().unwrap();
+
+    let node: NodeUri = format!("{}@node.example.com", pubkey).parse().unwrap();
+    assert_eq!((node.host, node.port), (Host::Name("node.example.com".to_string()), 9735));
+
+    let node: NodeUri = format!("{}@[2001:db8::1]:9736", pubkey).parse().unwrap();
+    assert_eq!((node.host.clone(), node.port), (Host::Ipv6("2001:db8::1".parse().unwrap()), 9736));
+    assert_eq!(node.to_string(), format!("{}@[2001:db8::1]:9736", pubkey));
+
+    assert!(format!("04{}@host:1", &pubkey[2..]).parse::<NodeUri>().is_err());
+}
+
+#[test]
+fn node_uri_hosts_are_typed_and_round_trip() {
+    let pubkey = NODE.split('@').next().unwrap();
+    let onion = "duckduckgogg42xjoc72x3sjasowoarfbgcmvfimaftt6twagswzczad.onion";
+    for (address, host, display) in [
+        ("3.33.236.230:9735", Host::Ipv4("3.33.236.230".parse().unwrap()), "3.33.236.230:9735"),
+        ("[::1]", Host::Ipv6("::1".parse().unwrap()), "[::1]:9735"),
+        ("Node.Example.COM.:9736", Host::Name("node.example.com".to_string()), "node.example.com:9736"),
+        (onion, Host::Onion(onion.to_string()), &format!("{}:9735", onion)),
+    ] {
+        let node: NodeUri = format!("{}@{}", pubkey, address).parse().unwrap();
+        assert_eq!(node.host, host, "{}", address);
+        assert_eq!(node.to_string(), format!("{}@{}", pubkey, display));
+        assert_eq!(node.to_string().parse::<NodeUri>(), Ok(node));
+    }
+}
+
+#[test]
+fn malformed_node_uris_say_what_is_wrong() {
+    let pubkey = NODE.split('@').next().unwrap();
+    let reason = |value: String| match value.parse::<NodeUri>() {
+        Err(SelfieError::InvalidRecord { key, reason }) if key == "node-uri" => reason,
+        other => panic!("{}: {:?}", value, other),
+    };
+
+    assert_eq!(reason(format!("{}@host", &pubkey[..64])), "public key is not 33 bytes of hex");
+    assert_eq!(reason(format!("{}@host", &pubkey[..65])), "public key is not 33 bytes of hex");
+    assert_eq!(reason("@host".to_string()), "public key is not 33 bytes of hex");
+    assert_eq!(reason(pubkey.to_string()), "expected pubkey@host:port");
+    assert_eq!(reason(format!("{}@2001:db8::1", pubkey)), "IPv6 address 2001:db8::1 must be bracketed");
+    assert_eq!(reason(format!("{}@[2001:db8::1", pubkey)), "unterminated IPv6 address");
+    assert_eq!(reason(format!("{}@[node.example.com]:9735", pubkey)), "invalid IPv6 address node.example.com");
+    assert_eq!(reason(format!("{}@1.2.3.400", pubkey)), "invalid IPv4 address 1.2.3.400");
+    assert_eq!(reason(format!("{}@abc.onion", pubkey)), "abc.onion is not a v3 onion address");
+    assert_eq!(reason(format!("{}@node_1.example.com", pubkey)), "invalid hostname node_1.example.com");
+    assert_eq!(reason(format!("{}@node.example.com:lightning", pubkey)), "invalid port lightning");
+}
+
+#[test]
+fn a_malformed_node_uri_is_a_problem_of_the_profile() {
+    let mock = MockTxtResolver::new().with_record("_node-uri.example.com", &["02abcd@node.example.com"]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let profile = sdk.resolve_profile_with("example.com", Some(vec!["node-uri"]), None, &LookupOptions::new());
+
+    assert_eq!(profile.node_uri, None);
+    assert_eq!(profile.problems.len(), 1);
+    assert_eq!(profile.problems[0].0, "node-uri");
+    assert_eq!(profile.problems[0].1.code(), "E_INVALID_RECORD");
+}
+
+#[test]
+fn payment_codes_decode_the_bip47_vectors() {
+    let alice: PaymentCode = ALICE_CODE.parse().unwrap();
+    assert_eq!((alice.version, alice.features), (1, 0));
+    assert_eq!(alice.public_key_hex(), "02b85034fb08a8bfefd22848238257b252721454bbbfba2c3667f168837ea2cdad");
+    assert_eq!(alice.chain_code[..4], [0x67, 0x1a, 0xf9, 0xf6]);
+    assert_eq!(alice.to_string(), ALICE_CODE);
+
+    let bob: PaymentCode = format!(" {} ", BOB_CODE).parse().unwrap();
+    assert_eq!(bob.public_key_hex(), "029d125e1cb89e5a1a108192643ee25370c2e75c192b10aac18de8d5a09b5f48d5");
+    assert_eq!(bob.to_string(), BOB_CODE);
+}
+
+#[test]
+fn malformed_payment_codes_are_invalid_records() {
+    let mut corrupted = ALICE_CODE.to_string();
+    corrupted.replace_range(20..21, if &ALICE_CODE[20..21] == "a" { "b" } else { "a" });
+    for value in [corrupted.as_str(), &ALICE_CODE[..60], "PM8T0OIl", NPUB, ""] {
+        let error = value.parse::<PaymentCode>().unwrap_err();
+        assert!(matches!(&error, SelfieError::InvalidRecord { key, .. } if key == "bip47"), "{:?}: {:?}", value, error);
+    }
+
+    // A valid base58check string with another version byte.
+    let error = "1111111111111111111114oLvT2".parse::<PaymentCode>().unwrap_err();
+    assert_eq!(error.to_string(), "Invalid bip47 record: not a payment code: version byte is not 0x47");
+}
+
+#[test]
+fn payment_codes_are_parsed_into_the_profile() {
+    let resolver = records().with_record("_bip47.example.com", &[ALICE_CODE]);
+
+    let profile = sdk(resolver).resolve_profile_with("example.com", Some(vec!["bip47"]), None, &Default::default());
+
+    assert_eq!(profile.bip47.unwrap().to_string(), ALICE_CODE);
+    assert!(profile.extra.is_empty());
+
+    let resolver = records().with_record("_bip47.example.com", &["PM8Tnotacode"]);
+    let profile = sdk(resolver).resolve_profile_with("example.com", Some(vec!["bip47"]), None, &Default::default());
+    assert_eq!(profile.bip47, None);
+    assert_eq!(profile.problems[0].0, "bip47");
+}
+
+#[test]
+fn dids_expose_their_method_and_id() {
+    let valid = [
+        ("did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK", "key", "z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK"),
+        ("did:web:example.com", "web", "example.com"),
+        ("did:web:w3c-ccg.github.io:user:alice", "web", "w3c-ccg.github.io:user:alice"),
+        ("did:web:example.com%3A3000", "web", "example.com%3A3000"),
+        ("did:plc:ewvi7nxzyoun6zhxrhs64oiz", "plc", "ewvi7nxzyoun6zhxrhs64oiz"),
+        ("did:ethr:0x5:0xb9c5714089478a327f09197987f16f9e5d936e8a", "ethr", "0x5:0xb9c5714089478a327f09197987f16f9e5d936e8a"),
+    ];
+    for (value, method, id) in valid {
+        let did: Did = value.parse().unwrap_or_else(|e| panic!("{}: {}", value, e));
+        assert_eq!((did.method.as_str(), did.id.as_str()), (method, id));
+        assert_eq!(did.to_string(), value);
+    }
+}
+
+#[test]
+fn invalid_dids_name_the_offending_position() {
+    let invalid = [
+        ("did:Key:z6Mk", "invalid character 'K' at position 4"),
+        ("did::z6Mk", "empty method name at position 4"),
+        ("did:web", "missing method-specific id at position 7"),
+        ("did:web:", "method-specific id must end with an id character at position 8"),
+        ("did:web:example.com:", "method-specific id must end with an id character at position 20"),
+        ("did:web:example.com/path", "invalid character '/' at position 19"),
+        ("did:web:example.com%3", "incomplete percent-encoding at position 19"),
+        ("did:web:ex%zzample", "incomplete percent-encoding at position 10"),
+        ("did:web:exämple.com", "invalid character 'ä' at position 10"),
+        ("  did:we-b:x", "invalid character '-' at position 8"),
+        ("DID:web:example.com", "not a did: URI"),
+        ("https://example.com", "not a did: URI"),
+    ];
+    for (value, reason) in invalid {
+        let error = value.parse::<Did>().unwrap_err();
+        assert_eq!(error, SelfieError::InvalidRecord { key: "did".to_string(), reason: reason.to_string() }, "{}", value);
+    }
+    assert!("did:web:a did:web:b".parse::<Did>().is_err());
+}
+
+#[test]
+fn did_lists_are_parsed_when_asked_for() {
+    let dids = Did::parse_list(" did:web:example.com\tdid:plc:ewvi7nxzyoun6zhxrhs64oiz ").unwrap();
+    assert_eq!(dids.iter().map(|did| did.method.as_str()).collect::<Vec<_>>(), ["web", "plc"]);
+
+    let error = Did::parse_list("did:web:example.com did:Web:other.example").unwrap_err();
+    assert_eq!(error.to_string(), "Invalid did record: invalid character 'W' at position 24");
+    assert!(Did::parse_list("   ").is_err());
+}
+
+#[test]
+fn dids_are_looked_up_for_addresses_too() {
+    let resolver = MockTxtResolver::new().with_record("alice.user._did.example.com", &["did:web:example.com:alice"]);
+
+    let profile = sdk(resolver).resolve_profile_with("alice@example.com", Some(vec!["did"]), None, &Default::default());
+
+    assert_eq!(profile.did.unwrap().id, "example.com:alice");
+    assert!(profile.problems.is_empty());
+}
+
+#[test]
+fn pgp_records_are_classified() {
+    let key_block = "mDMEZZZZZZYJKwYBBAHaRw8BAQdAexampleexampleexampleexampleexampleexample";
+    assert_eq!(key_block.parse::<PgpRecord>().unwrap(), PgpRecord::Key(key_block.to_string()));
+    assert!("not a key".parse::<PgpRecord>().is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn profiles_serialize_with_record_text() {
+    let resolver = records()
+        .with_record("_nostr.example.com", &[NOSTR_HEX])
+        .with_record("_node-uri.example.com", &["garbage"]);
+    let profile = sdk(resolver).resolve_profile("example.com");
+
+    let json = serde_json::to_value(&profile).unwrap();
+    assert_eq!(json["nostr"], NPUB);
+    assert_eq!(json["dnssec"], "not_validated");
+    assert_eq!(json["problems"][0]["key"], "node-uri");
+    assert_eq!(json["problems"][0]["code"], "E_INVALID_RECORD");
+    assert_eq!(json["problems"][0]["numeric_code"], 7);
+    assert!(json["resolved_at"].is_u64());
+
+    let key: NostrKey = serde_json::from_value(json["nostr"].clone()).unwrap();
+    assert_eq!(key.to_hex(), NOSTR_HEX);
+}
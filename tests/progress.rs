@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{KeyOutcome, LookupOptions, ProgressEvent, SelfieError, SelfieRecordsSDK, TxtResolver};
+
+/// Answers every name with its own text, failing the first query of each
+/// name listed in `flaky` with a retryable error.
+struct Flaky {
+    flaky: Mutex<HashSet<String>>,
+}
+
+impl Flaky {
+    fn new(flaky: &[&str]) -> Self {
+        Flaky { flaky: Mutex::new(flaky.iter().map(|name| name.to_string()).collect()) }
+    }
+}
+
+#[async_trait]
+impl TxtResolver for Flaky {
+    async fn txt_lookup(&self, name: &str) -> Result<Vec<String>, SelfieError> {
+        if self.flaky.lock().unwrap().remove(name) {
+            return Err(SelfieError::Resolver(format!("{} timed out", name)));
+        }
+        if name.starts_with("_pgp.") {
+            return Ok(Vec::new());
+        }
+        Ok(vec![name.to_string()])
+    }
+}
+
+/// Options sending every event to the returned receiver.
+fn recording() -> (LookupOptions, Receiver<ProgressEvent>) {
+    let (sender, events) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    let options = LookupOptions::new().attempts(3).backoff(Duration::ZERO).on_progress(move |event| {
+        let _ = sender.lock().unwrap().send(event);
+    });
+    (options, events)
+}
+
+/// The events up to and including `AllDone`.
+fn collect(events: &Receiver<ProgressEvent>) -> Vec<ProgressEvent> {
+    let mut collected = Vec::new();
+    loop {
+        let event = events.recv_timeout(Duration::from_secs(5)).expect("AllDone is delivered");
+        let done = matches!(event, ProgressEvent::AllDone { .. });
+        collected.push(event);
+        if done {
+            return collected;
+        }
+    }
+}
+
+fn started(key: &str) -> ProgressEvent {
+    ProgressEvent::Started { key: key.to_string() }
+}
+
+fn finished(key: &str, outcome: KeyOutcome) -> ProgressEvent {
+    ProgressEvent::Finished { key: key.to_string(), outcome }
+}
+
+#[test]
+fn events_follow_each_key_through_its_retries() {
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(Flaky::new(&["_nostr.example.com"])));
+    let (options, events) = recording();
+
+    let response = sdk.get_records_response("example.com", Some(vec!["bitcoin-payment", "nostr", "pgp"]), None, &options);
+
+    assert_eq!(response.get("nostr").unwrap().attempts, Some(2));
+    let mut events = collect(&events);
+    let Some(ProgressEvent::AllDone { summary }) = events.pop() else {
+        panic!("AllDone is the last event");
+    };
+    assert_eq!(
+        events,
+        [
+            started("bitcoin-payment"),
+            finished("bitcoin-payment", KeyOutcome::Found),
+            started("nostr"),
+            ProgressEvent::Retrying { key: "nostr".to_string(), attempt: 2 },
+            finished("nostr", KeyOutcome::Found),
+            started("pgp"),
+            finished("pgp", KeyOutcome::NotFound),
+        ]
+    );
+    assert_eq!((summary.found, summary.not_found, summary.failed), (2, 1, 0));
+    assert_eq!((summary.queries_issued, summary.dropped), (4, 0));
+}
+
+#[test]
+fn every_key_is_started_before_it_finishes_and_all_done_comes_last() {
+    let keys = ["k0", "k1", "k2", "k3", "k4"];
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(Flaky::new(&["_k1.order.example", "_k3.order.example"])));
+    let (options, events) = recording();
+
+    sdk.get_records_response("order.example", Some(keys.to_vec()), None, &options.attempts(2));
+
+    let events = collect(&events);
+    let mut running: Option<String> = None;
+    let mut finished = Vec::new();
+    for event in &events[..events.len() - 1] {
+        match event {
+            ProgressEvent::Started { key } => assert_eq!(running.replace(key.clone()), None, "{:?}", events),
+            ProgressEvent::Retrying { key, attempt } => {
+                assert_eq!(running.as_ref(), Some(key));
+                assert_eq!(*attempt, 2);
+            }
+            ProgressEvent::Finished { key, .. } => {
+                assert_eq!(running.take().as_ref(), Some(key));
+                finished.push(key.as_str());
+            }
+            ProgressEvent::AllDone { .. } => panic!("AllDone before the end: {:?}", events),
+        }
+    }
+    assert_eq!(finished, keys);
+    assert!(matches!(events.last(), Some(ProgressEvent::AllDone { summary }) if summary.found == 5));
+}
+
+#[test]
+fn a_blocked_callback_does_not_hold_up_the_lookup() {
+    let keys: Vec<String> = (0..100).map(|i| format!("k{}", i)).collect();
+    let mock = keys.iter().fold(MockTxtResolver::new(), |mock, key| mock.with_record(&format!("_{}.slow.example", key), &[key]));
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+    let (release, released) = mpsc::channel::<()>();
+    let released = Mutex::new(released);
+    let (sender, events) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    let options = LookupOptions::new().max_queries(100).on_progress(move |event| {
+        // The first event waits until the lookup has returned.
+        let _ = released.lock().unwrap().recv();
+        let _ = sender.lock().unwrap().send(event);
+    });
+
+    let response = sdk.get_records_response("slow.example", Some(keys.iter().map(String::as_str).collect()), None, &options);
+    assert_eq!(response.len(), 100);
+    drop(release);
+
+    let events = collect(&events);
+    let Some(ProgressEvent::AllDone { summary }) = events.last() else {
+        panic!("AllDone is the last event");
+    };
+    assert_eq!(summary.found, 100);
+    assert!(summary.dropped > 0);
+    assert_eq!(events.len() + summary.dropped as usize, 2 * 100 + 1);
+    assert_eq!(events[0], started("k0"));
+}
+
+#[test]
+fn failures_are_reported_as_outcomes() {
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new()));
+    let (options, events) = recording();
+
+    sdk.get_records_response("not a name", Some(vec!["nostr"]), None, &options);
+
+    let events = collect(&events);
+    assert_eq!(events[0], started("nostr"));
+    assert!(matches!(
+        &events[1],
+        ProgressEvent::Finished { key, outcome: KeyOutcome::Failed(SelfieError::InvalidName { .. }) } if key == "nostr"
+    ));
+    assert!(matches!(events[2], ProgressEvent::AllDone { summary } if summary.failed == 1 && summary.queries_issued == 0));
+}
+
+#[test]
+fn profiles_report_progress_but_matrices_do_not() {
+    let mock = MockTxtResolver::new().with_record("_nostr.profile.example", &["npub1profile"]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+    let (options, events) = recording();
+
+    sdk.resolve_profile_with("profile.example", Some(vec!["nostr"]), None, &options);
+    assert_eq!(collect(&events).len(), 3);
+
+    sdk.resolve_matrix(&["profile.example"], &["nostr"], &options);
+    assert!(events.recv_timeout(Duration::from_millis(100)).is_err());
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+
+use common::server::{dns_server, doh_server, socks5_proxy, DohReply, DOH_VALUE};
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{BuildError, KeyResult, LookupOptions, SdkBuilder, SelfieError, SelfieRecordsSDK, Socks5Config, Transport};
+
+fn proxied(server: SocketAddr, proxy: Socks5Config) -> SdkBuilder {
+    SelfieRecordsSDK::builder().nameservers(&[server]).tcp_only(true).proxy(proxy)
+}
+
+fn nostr(sdk: &SelfieRecordsSDK, dns_server: Option<&str>) -> KeyResult {
+    let options = LookupOptions::new().attempts(1);
+    sdk.get_records_response("example.com", Some(vec!["nostr"]), dns_server, &options).get("nostr").unwrap().clone()
+}
+
+#[test]
+fn queries_go_through_the_proxy() {
+    let server = dns_server("npub1proxied", false);
+    let (proxy, targets) = socks5_proxy(None);
+    let sdk = proxied(server, Socks5Config::new(proxy)).build().unwrap();
+
+    let result = nostr(&sdk, None);
+
+    assert_eq!(result.value.as_deref(), Some("npub1proxied"));
+    assert_eq!(result.wire.unwrap().transport_used, Transport::Tcp);
+    assert_eq!(result.resolver.as_deref(), Some(format!("{} via socks5://{}", server, proxy).as_str()));
+    assert_eq!(*targets.lock().unwrap(), [server.to_string()]);
+}
+
+#[test]
+fn the_server_a_call_names_goes_through_the_proxy_by_name() {
+    let server = dns_server("npub1named", false);
+    let (proxy, targets) = socks5_proxy(None);
+    let sdk = proxied(dns_server("npub1default", false), Socks5Config::new(proxy)).build().unwrap();
+
+    let named = format!("localhost:{}", server.port());
+    assert_eq!(nostr(&sdk, Some(&named)).value.as_deref(), Some("npub1named"));
+    assert_eq!(nostr(&sdk, Some(&server.to_string())).value.as_deref(), Some("npub1named"));
+    // The proxy, not the SDK, looked the host name up.
+    assert_eq!(*targets.lock().unwrap(), [named, server.to_string()]);
+}
+
+#[test]
+fn doh_goes_through_the_proxy() {
+    let (url, _) = doh_server(vec![DohReply::ok()]);
+    let (proxy, targets) = socks5_proxy(None);
+    let sdk = proxied(dns_server("npub1default", false), Socks5Config::new(proxy)).build().unwrap();
+
+    let result = sdk.get_records_response("example.com", Some(vec!["bitcoin-payment"]), Some(&url), &LookupOptions::new().attempts(1));
+
+    assert_eq!(result.get("bitcoin-payment").unwrap().value.as_deref(), Some(DOH_VALUE));
+    let authority = url.trim_start_matches("http://").split('/').next().unwrap();
+    assert_eq!(*targets.lock().unwrap(), [authority]);
+}
+
+#[test]
+fn proxies_asking_for_credentials_get_them() {
+    let server = dns_server("npub1proxied", false);
+    let (proxy, _) = socks5_proxy(Some(("alice", "hunter2")));
+
+    let sdk = proxied(server, Socks5Config::new(proxy).auth("alice", "hunter2")).build().unwrap();
+    assert_eq!(nostr(&sdk, None).value.as_deref(), Some("npub1proxied"));
+
+    for config in [Socks5Config::new(proxy).auth("alice", "wrong"), Socks5Config::new(proxy)] {
+        let sdk = proxied(server, config.clone()).build().unwrap();
+        let result = nostr(&sdk, None);
+        assert!(matches!(result.error, Some(SelfieError::ProxyUnreachable { .. })), "{:?}: {:?}", config, result);
+    }
+    // Passwords stay out of logs.
+    assert!(!format!("{:?}", Socks5Config::new(proxy).auth("alice", "hunter2")).contains("hunter2"));
+}
+
+#[test]
+fn an_unreachable_proxy_is_reported_as_such() {
+    let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let sdk = proxied(dns_server("npub1proxied", false), Socks5Config::new(closed)).build().unwrap();
+
+    let result = nostr(&sdk, None);
+
+    assert!(matches!(&result.error, Some(SelfieError::ProxyUnreachable { proxy, .. }) if *proxy == closed.to_string()), "{:?}", result);
+    let records = sdk.get_records("example.com", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(records["nostr"]["error_code"].as_deref(), Some("E_PROXY_UNREACHABLE"));
+}
+
+#[test]
+fn udp_and_the_system_resolver_cannot_use_a_proxy() {
+    let server: SocketAddr = ([127, 0, 0, 1], 53).into();
+    let tor = Socks5Config::tor();
+    assert_eq!(tor.addr, ([127, 0, 0, 1], 9050).into());
+
+    let error = |builder: SdkBuilder| builder.build().unwrap_err();
+    assert_eq!(error(SelfieRecordsSDK::builder().nameservers(&[server]).proxy(tor.clone())), BuildError::ProxyNeedsTcp);
+    assert_eq!(error(SelfieRecordsSDK::builder().tcp_only(true).proxy(tor.clone())), BuildError::ProxyNeedsNameservers);
+    let resolver = Arc::new(MockTxtResolver::new());
+    assert!(SelfieRecordsSDK::builder().resolver(resolver).tcp_only(true).proxy(tor).build().is_ok());
+}
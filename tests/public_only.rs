@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{NameError, SelfieError, SelfieRecordsSDK, TxtResolver};
+
+fn sdk(resolver: Arc<dyn TxtResolver>) -> SelfieRecordsSDK {
+    SelfieRecordsSDK::builder().resolver(resolver).public_only(true).block_suffix(".corp").build().unwrap()
+}
+
+fn assert_rejected(sdk: &SelfieRecordsSDK, name: &str) {
+    let response = sdk.get_records_response(name, Some(vec!["bitcoin-payment"]), None, &Default::default());
+    let error = response.get("bitcoin-payment").unwrap().error.clone();
+    assert!(
+        matches!(error, Some(SelfieError::InvalidName { reason: NameError::SpecialUseDomain, .. })),
+        "{}: {:?}",
+        name,
+        error
+    );
+}
+
+#[test]
+fn special_use_suffixes_are_rejected_without_querying() {
+    let resolver = Arc::new(MockTxtResolver::new());
+    let sdk = sdk(resolver.clone());
+    for name in [
+        "printer.local",
+        "alice@nas.local",
+        "vault.internal",
+        "router.home.arpa",
+        "home.arpa",
+        "example.test",
+        "example.invalid",
+        "app.localhost",
+        "db.LOCALHOST.",
+        "wiki.CORP",
+    ] {
+        assert_rejected(&sdk, name);
+    }
+    assert_eq!(resolver.calls(), 0);
+}
+
+#[test]
+fn ip_literals_and_single_labels_are_rejected() {
+    let resolver = Arc::new(MockTxtResolver::new());
+    let sdk = sdk(resolver.clone());
+    for name in ["192.168.1.10", "alice@10.0.0.1", "alice@intranet"] {
+        assert_rejected(&sdk, name);
+    }
+    assert_eq!(resolver.calls(), 0);
+}
+
+#[test]
+fn onion_names_need_a_tor_transport() {
+    let onion = "duckduckgogg42xjoc72x3sjasowoarfbgcmvfimaftt6twagswzczad.onion";
+    let resolver = || MockTxtResolver::new().with_record(&format!("_bitcoin-payment.{}", onion), &["bitcoin:bc1qexample"]);
+    assert_rejected(&sdk(Arc::new(resolver())), onion);
+
+    struct Tor(MockTxtResolver);
+
+    #[async_trait]
+    impl TxtResolver for Tor {
+        async fn txt_lookup(&self, name: &str) -> Result<Vec<String>, SelfieError> {
+            self.0.txt_lookup(name).await
+        }
+
+        fn resolves_onion(&self) -> bool {
+            true
+        }
+    }
+
+    let sdk = sdk(Arc::new(Tor(resolver())));
+    let records = sdk.get_records(onion, Some(vec!["bitcoin-payment"]), None).to_map();
+    assert_eq!(records["bitcoin-payment"]["value"].as_deref(), Some("bitcoin:bc1qexample"));
+}
+
+#[test]
+fn public_names_pass_through() {
+    let resolver = Arc::new(
+        MockTxtResolver::new()
+            .with_record("_bitcoin-payment.example.com", &["bitcoin:bc1qexample"])
+            .with_record("alice.user._bitcoin-payment.local-shop.com", &["bitcoin:bc1qalice"]),
+    );
+    let sdk = sdk(resolver.clone());
+
+    let records = sdk.get_records("example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+    assert_eq!(records["bitcoin-payment"]["value"].as_deref(), Some("bitcoin:bc1qexample"));
+    let records = sdk.get_records("alice@local-shop.com", Some(vec!["bitcoin-payment"]), None).to_map();
+    assert_eq!(records["bitcoin-payment"]["value"].as_deref(), Some("bitcoin:bc1qalice"));
+    assert_eq!(resolver.calls(), 2);
+}
+
+#[test]
+fn names_are_allowed_when_public_only_is_off() {
+    let resolver = MockTxtResolver::new().with_record("_bitcoin-payment.printer.local", &["bitcoin:bc1qexample"]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(resolver));
+
+    let records = sdk.get_records("printer.local", Some(vec!["bitcoin-payment"]), None).to_map();
+    assert_eq!(records["bitcoin-payment"]["value"].as_deref(), Some("bitcoin:bc1qexample"));
+}
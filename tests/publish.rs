@@ -0,0 +1,87 @@
+use selfie_records_sdk::get_txt_record_key;
+use selfie_records_sdk::publish::{ProviderError, RecordInstruction};
+
+fn strings(instruction: &RecordInstruction) -> Vec<usize> {
+    instruction.strings.iter().map(String::len).collect()
+}
+
+#[test]
+fn names_follow_the_lookup_layout() {
+    let instruction = RecordInstruction::for_key("Alice@Example.com", "bitcoin-payment", "bitcoin:bc1qalice").unwrap();
+    assert_eq!(instruction.name, "alice.user._bitcoin-payment.example.com");
+    assert_eq!(instruction.name, get_txt_record_key("alice@example.com", "bitcoin-payment").unwrap());
+    assert_eq!((instruction.zone.as_str(), instruction.host()), ("example.com", "alice.user._bitcoin-payment"));
+    assert!(instruction.warnings.is_empty(), "{:?}", instruction.warnings);
+
+    let instruction = RecordInstruction::for_key("example.com", "nostr", "npub1example").unwrap();
+    assert_eq!((instruction.name.as_str(), instruction.host()), ("_nostr.example.com", "_nostr"));
+
+    assert!(matches!(RecordInstruction::for_key("not a name", "nostr", "npub1"), Err(ProviderError::InvalidRecord(_))));
+}
+
+#[test]
+fn values_are_split_at_255_bytes() {
+    let split = |length: usize| strings(&RecordInstruction::for_key("example.com", "pgp", &"a".repeat(length)).unwrap());
+
+    assert_eq!(split(0), [0]);
+    assert_eq!(split(255), [255]);
+    assert_eq!(split(256), [255, 1]);
+    assert_eq!(split(510), [255, 255]);
+    assert_eq!(split(511), [255, 255, 1]);
+}
+
+#[test]
+fn splitting_keeps_characters_whole() {
+    // 254 bytes, then a three-byte character that would straddle the boundary.
+    let value = format!("{}€tail", "a".repeat(254));
+
+    let instruction = RecordInstruction::for_key("example.com", "pgp", &value).unwrap();
+
+    assert_eq!(strings(&instruction), [254, 7]);
+    assert_eq!(instruction.strings.concat(), value);
+}
+
+#[test]
+fn values_must_fit_in_one_record() {
+    // Each full string costs 256 bytes of rdata with its length byte.
+    let fits = "a".repeat(255 * 255);
+    assert!(RecordInstruction::for_key("example.com", "pgp", &fits).is_ok());
+
+    let error = RecordInstruction::for_key("example.com", "pgp", &"a".repeat(65535)).unwrap_err();
+    assert!(matches!(&error, ProviderError::InvalidRecord(reason) if reason.contains("at most 65535")), "{:?}", error);
+}
+
+#[test]
+fn zone_lines_quote_and_escape_each_string() {
+    let instruction = RecordInstruction::for_key("example.com", "note", "say \"hi\" \\ bye\u{7}").unwrap().ttl(300);
+
+    assert_eq!(instruction.to_zone_line(), r#"_note.example.com. 300 IN TXT "say \"hi\" \\ bye\007""#);
+
+    let long = RecordInstruction::for_key("example.com", "pgp", &format!("{}\"{}", "a".repeat(254), "b")).unwrap();
+    assert_eq!(long.to_zone_line(), format!("_pgp.example.com. 3600 IN TXT \"{}\\\"\" \"b\"", "a".repeat(254)));
+}
+
+#[test]
+fn cloudflare_json_matches_the_dns_records_api() {
+    let instruction = RecordInstruction::for_key("alice@example.com", "nostr", "npub1alice").unwrap();
+    assert_eq!(
+        instruction.to_cloudflare_json(),
+        serde_json::json!({ "type": "TXT", "name": "alice.user._nostr.example.com", "content": "npub1alice", "ttl": 3600 })
+    );
+
+    let long = RecordInstruction::for_key("example.com", "pgp", &"a".repeat(256)).unwrap();
+    assert_eq!(long.to_cloudflare_json()["content"], format!("\"{}\" \"a\"", "a".repeat(255)));
+}
+
+#[test]
+fn unknown_keys_and_stray_whitespace_are_warned_about() {
+    let instruction = RecordInstruction::for_key("example.com", "bitcoin-paymnet", "bitcoin:bc1q ").unwrap();
+
+    assert_eq!(
+        instruction.warnings,
+        [
+            "bitcoin-paymnet is not a well-known record key; lookups only ask for it by name",
+            "the value starts or ends with whitespace",
+        ]
+    );
+}
@@ -0,0 +1,30 @@
+//! The Python suite in tests/python, run against the `selfie_records`
+//! module in an embedded interpreter, so that `cargo test --features python`
+//! covers it without maturin or pytest.
+#![cfg(feature = "python")]
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use selfie_records_sdk::python::selfie_records;
+
+#[test]
+fn the_python_suite_passes() {
+    pyo3::append_to_inittab!(selfie_records);
+    pyo3::prepare_freethreaded_python();
+
+    Python::with_gil(|py| {
+        let locals = PyDict::new(py);
+        locals.set_item("tests", concat!(env!("CARGO_MANIFEST_DIR"), "/tests/python")).unwrap();
+        py.run(
+            c"import sys, unittest
+sys.path.insert(0, tests)
+suite = unittest.defaultTestLoader.discover(tests)
+passed = unittest.TextTestRunner(verbosity=2).run(suite).wasSuccessful()",
+            None,
+            Some(&locals),
+        )
+        .unwrap_or_else(|e| panic!("{}", e));
+        let passed: bool = locals.get_item("passed").unwrap().unwrap().extract().unwrap();
+        assert!(passed, "the Python suite failed, see its output above");
+    });
+}
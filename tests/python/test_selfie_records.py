@@ -0,0 +1,136 @@
+"""The `selfie_records` extension module, answering lookups from a Python
+resolver so that no test touches the network:
+
+    maturin develop && pytest
+
+`cargo test --features python` runs them as well, in an embedded
+interpreter, see tests/python.rs.
+"""
+
+import unittest
+
+import selfie_records
+from selfie_records import SelfieRecords
+
+PAYMENT = "bitcoin:bc1qpython?amount=0.5"
+
+RECORDS = {
+    "_bitcoin-payment.example.com": [PAYMENT],
+    "_nostr.example.com": ["npub1python"],
+}
+
+
+class Resolver:
+    """Answers from `RECORDS`: `None`, NXDOMAIN, for names under
+    gone.example, `TimeoutError` for ones under slow.example."""
+
+    def __init__(self):
+        self.queries = []
+
+    def __call__(self, name):
+        self.queries.append(name)
+        if name.endswith("slow.example"):
+            raise TimeoutError(name)
+        if name.endswith("gone.example"):
+            return None
+        return RECORDS.get(name, [])
+
+
+class SelfieRecordsTest(unittest.TestCase):
+    def setUp(self):
+        self.resolver = Resolver()
+        self.sdk = SelfieRecords({"attempts": 1}, resolver=self.resolver)
+
+    def test_records_mirror_the_json_shape(self):
+        records = self.sdk.get_records("example.com", keys=["bitcoin-payment", "pgp"])
+
+        self.assertEqual(sorted(records), ["bitcoin-payment", "pgp"])
+        payment = records["bitcoin-payment"]
+        self.assertEqual(payment["value"], PAYMENT)
+        self.assertIsNone(payment["error"])
+        self.assertEqual(payment["resolver"], "python")
+        self.assertIsNone(records["pgp"]["value"])
+        self.assertEqual(records["pgp"]["error"], {"code": "E_NO_RECORDS", "numeric_code": 2, "message": "No TXT records found"})
+        self.assertEqual(sorted(self.resolver.queries), ["_bitcoin-payment.example.com", "_pgp.example.com"])
+
+    def test_default_keys_come_from_the_config(self):
+        sdk = SelfieRecords({"keys": ["nostr"]}, resolver=self.resolver)
+
+        self.assertEqual(list(sdk.get_records("example.com")), ["nostr"])
+        self.assertEqual(self.resolver.queries, ["_nostr.example.com"])
+
+    def test_an_invalid_config_raises_value_error(self):
+        with self.assertRaisesRegex(ValueError, "Unknown config key"):
+            SelfieRecords({"no_such_setting": 1})
+
+    def test_get_record_returns_the_value(self):
+        self.assertEqual(self.sdk.get_record("example.com", "nostr"), "npub1python")
+
+    def test_a_missing_name_raises_nxdomain(self):
+        with self.assertRaises(selfie_records.NxDomain) as raised:
+            self.sdk.get_record("gone.example", "nostr")
+
+        self.assertEqual(raised.exception.code, "E_NXDOMAIN")
+        self.assertEqual(raised.exception.numeric_code, 16)
+        self.assertEqual(raised.exception.fields, {})
+        self.assertIsInstance(raised.exception, selfie_records.SelfieError)
+
+    def test_a_missing_record_raises_no_records(self):
+        with self.assertRaises(selfie_records.NoRecords):
+            self.sdk.get_record("example.com", "pgp")
+
+    def test_resolver_timeouts_raise_timeout_with_the_budget(self):
+        with self.assertRaises(selfie_records.Timeout) as raised:
+            self.sdk.get_record("slow.example", "nostr")
+
+        self.assertEqual(raised.exception.code, "E_TIMEOUT")
+        self.assertEqual(raised.exception.fields["budget"], "global")
+        self.assertEqual(raised.exception.fields["attempts"], 1)
+
+    def test_invalid_names_raise_invalid_name_without_a_query(self):
+        with self.assertRaises(selfie_records.InvalidName) as raised:
+            self.sdk.get_records("not a name")
+
+        self.assertEqual(raised.exception.fields["name"], "not a name")
+        self.assertEqual(self.resolver.queries, [])
+
+    def test_invalid_dns_servers_raise_invalid_nameserver(self):
+        with self.assertRaises(selfie_records.InvalidNameserver) as raised:
+            self.sdk.get_records("example.com", keys=["nostr"], dns_server="not a server")
+
+        self.assertEqual(raised.exception.fields, {"server": "not a server"})
+
+    def test_verify_record_reports_match_mismatch_and_absence(self):
+        self.assertEqual(self.sdk.verify_record("example.com", "nostr", "npub1python"), {"status": "match", "actual": []})
+        self.assertEqual(self.sdk.verify_record("example.com", "nostr", "npub1other"), {"status": "mismatch", "actual": ["npub1python"]})
+        self.assertEqual(self.sdk.verify_record("gone.example", "nostr", "npub1python"), {"status": "not_found", "actual": []})
+
+    def test_verify_record_raises_on_lookup_errors(self):
+        with self.assertRaises(selfie_records.Timeout):
+            self.sdk.verify_record("slow.example", "nostr", "npub1python")
+
+
+class ModuleTest(unittest.TestCase):
+    def test_every_error_kind_has_its_own_code(self):
+        kinds = [value for value in vars(selfie_records).values() if isinstance(value, type) and issubclass(value, selfie_records.SelfieError)]
+        kinds.remove(selfie_records.SelfieError)
+
+        self.assertEqual(len(kinds), 27)
+        self.assertEqual(sorted(kind.numeric_code for kind in kinds), list(range(1, 28)))
+        self.assertEqual(len({kind.code for kind in kinds}), 27)
+        self.assertTrue(all(issubclass(kind, Exception) for kind in kinds))
+
+    def test_get_txt_record_key(self):
+        self.assertEqual(selfie_records.get_txt_record_key("example.com", "pgp"), "_pgp.example.com")
+        self.assertEqual(
+            selfie_records.get_txt_record_key("alice@example.com", "bitcoin-payment"),
+            "alice.user._bitcoin-payment.example.com",
+        )
+        with self.assertRaises(selfie_records.InvalidName):
+            selfie_records.get_txt_record_key("a@b@c.d", "pgp")
+        with self.assertRaises(selfie_records.InvalidKey):
+            selfie_records.get_txt_record_key("example.com", "my key")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{CrossCheck, LookupOptions, SelfieError, SelfieRecordsSDK};
+
+const KEYS: [&str; 6] = ["k0", "k1", "k2", "k3", "k4", "k5"];
+
+fn answering_every_key(domain: &str) -> MockTxtResolver {
+    KEYS.iter().fold(MockTxtResolver::new(), |mock, key| mock.with_record(&format!("_{}.{}", key, domain), &[key]))
+}
+
+#[test]
+fn keys_past_the_budget_are_not_queried() {
+    let mock = Arc::new(answering_every_key("budget.example.com"));
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+    let options = LookupOptions::new().attempts(1).max_queries(4);
+
+    let response = sdk.get_records_response("budget.example.com", Some(KEYS.to_vec()), None, &options);
+
+    assert_eq!(response.queries_issued, 4);
+    assert_eq!(mock.calls(), 4);
+    for key in &KEYS[..4] {
+        assert_eq!(response.get(key).unwrap().value.as_deref(), Some(*key));
+    }
+    for key in &KEYS[4..] {
+        let result = response.get(key).unwrap();
+        assert_eq!(result.error, Some(SelfieError::BudgetExceeded { max_queries: 4 }), "{}", key);
+        assert_eq!(result.attempts, Some(0));
+    }
+    let map = sdk.get_records_with("budget.example.com", Some(KEYS.to_vec()), None, &options).to_map();
+    assert_eq!(map["k5"]["error_code"].as_deref(), Some("E_BUDGET_EXCEEDED"));
+}
+
+#[test]
+fn the_default_budget_covers_ordinary_lookups() {
+    let mock = Arc::new(answering_every_key("default.example.com"));
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+
+    let response = sdk.get_records_response("default.example.com", Some(KEYS.to_vec()), None, &LookupOptions::new());
+
+    assert_eq!(response.queries_issued, 6);
+    assert!(response.iter().all(|(_, result)| result.error.is_none()));
+}
+
+#[test]
+fn retries_count_against_the_budget() {
+    // Unknown names fail with a retryable error.
+    let mock = Arc::new(MockTxtResolver::new().with_record("_k1.retry.example.com", &["k1"]));
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+    let options = LookupOptions::new().attempts(3).backoff(std::time::Duration::ZERO).max_queries(2);
+
+    let response = sdk.get_records_response("retry.example.com", Some(vec!["k0", "k1"]), None, &options);
+
+    // The failing key stops retrying with its own error once the budget is spent.
+    let failed = response.get("k0").unwrap();
+    assert!(matches!(failed.error, Some(SelfieError::Resolver(_))), "{:?}", failed.error);
+    assert_eq!(failed.attempts, Some(2));
+    assert_eq!(response.get("k1").unwrap().error, Some(SelfieError::BudgetExceeded { max_queries: 2 }));
+    assert_eq!((response.queries_issued, mock.calls()), (2, 2));
+}
+
+#[test]
+fn versioned_names_and_cross_checks_are_queries_too() {
+    let mock = Arc::new(
+        MockTxtResolver::new()
+            .with_record("_v2._k0.versioned.example.com", &[])
+            .with_record("_k0.versioned.example.com", &["k0"])
+            .with_record("_k1.versioned.example.com", &["k1"]),
+    );
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(mock.clone())
+        .record_version(Some(2))
+        .cross_check_resolver(mock.clone())
+        .build()
+        .unwrap();
+    let lookup = |max_queries| {
+        let options = LookupOptions::new().attempts(1).max_queries(max_queries);
+        sdk.get_records_response("versioned.example.com", Some(vec!["k0", "k1"]), None, &options)
+    };
+
+    // Both names of k0, then no query is left for its cross-check.
+    let response = lookup(2);
+    let k0 = response.get("k0").unwrap();
+    assert_eq!(k0.value.as_deref(), Some("k0"));
+    assert!(matches!(k0.cross_check, Some(CrossCheck::Unavailable { .. })), "{:?}", k0.cross_check);
+    assert_eq!((response.queries_issued, mock.calls()), (2, 2));
+    assert_eq!(response.get("k1").unwrap().error, Some(SelfieError::BudgetExceeded { max_queries: 2 }));
+
+    let response = lookup(3);
+    assert_eq!(response.get("k0").unwrap().cross_check, Some(CrossCheck::Verified));
+    assert_eq!(response.get("k1").unwrap().error, Some(SelfieError::BudgetExceeded { max_queries: 3 }));
+    assert_eq!(response.queries_issued, 3);
+}
+
+#[test]
+fn each_call_has_its_own_budget() {
+    let mock = Arc::new(answering_every_key("calls.example.com"));
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+    let options = LookupOptions::new().attempts(1).max_queries(2);
+
+    for _ in 0..2 {
+        let response = sdk.get_records_response("calls.example.com", Some(vec!["k0", "k1"]), None, &options);
+        assert_eq!(response.queries_issued, 2);
+        assert!(response.iter().all(|(_, result)| result.error.is_none()));
+    }
+}
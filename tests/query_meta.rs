@@ -0,0 +1,63 @@
+mod common;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::server::records_server;
+use common::txt_record;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{LookupOptions, RecordsResponse, SelfieRecordsSDK};
+
+fn lookup(sdk: &SelfieRecordsSDK, name: &str, keys: Vec<&str>) -> RecordsResponse {
+    sdk.get_records_response(name, Some(keys), None, &LookupOptions::new().attempts(1))
+}
+
+#[test]
+fn found_and_failed_keys_name_their_query() {
+    let mock = MockTxtResolver::new()
+        .with_record("alice.user._nostr.example.com", &["npub1alice"])
+        .with_delay("alice.user._nostr.example.com", Duration::from_millis(20));
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    // `_pgp` is not registered, so its lookup fails.
+    let response = lookup(&sdk, "alice@example.com", vec!["nostr", "pgp"]);
+
+    let nostr = response.get("nostr").unwrap();
+    let query = nostr.query.as_ref().unwrap();
+    assert_eq!(query.fqdn, "alice.user._nostr.example.com");
+    assert!(query.rtt >= Duration::from_millis(20), "{:?}", query.rtt);
+    assert_eq!((query.attempts, query.nameserver), (1, None));
+
+    let pgp = response.get("pgp").unwrap();
+    assert_eq!(pgp.error.as_ref().map(|e| e.code()), Some("E_RESOLVER"));
+    let query = pgp.query.as_ref().unwrap();
+    assert_eq!(query.fqdn, "alice.user._pgp.example.com");
+    assert!(query.rtt > Duration::ZERO);
+}
+
+#[test]
+fn the_configured_nameserver_is_reported() {
+    let server = records_server(vec![txt_record("_nostr.example.com.", "npub1example")]);
+    let sdk = SelfieRecordsSDK::builder().nameservers(&[server]).build().unwrap();
+
+    let response = lookup(&sdk, "example.com", vec!["nostr"]);
+
+    let query = response.get("nostr").unwrap().query.clone().unwrap();
+    assert_eq!(query.nameserver, Some(server));
+    let records: HashMap<String, HashMap<String, Option<String>>> = response.into_map();
+    assert_eq!(records["nostr"]["fqdn"].as_deref(), Some("_nostr.example.com"));
+    assert_eq!(records["nostr"]["nameserver"], Some(server.to_string()));
+    assert!(records["nostr"]["rtt_ms"].is_some());
+}
+
+#[test]
+fn overrides_send_no_query() {
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(Arc::new(MockTxtResolver::new()))
+        .override_record("example.com", "nostr", "npub1override")
+        .build()
+        .unwrap();
+
+    assert_eq!(lookup(&sdk, "example.com", vec!["nostr"]).get("nostr").unwrap().query, None);
+}
@@ -0,0 +1,109 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use selfie_records_sdk::config::Config;
+use selfie_records_sdk::{BuildError, LookupOptions, SdkBuilder, SelfieError, SelfieRecordsSDK, TxtResolver};
+
+/// Answers every name after `delay`, logging when each query arrived and
+/// counting the queries in flight.
+#[derive(Default)]
+struct Logged {
+    delay: Duration,
+    sent: Mutex<Vec<Instant>>,
+    running: AtomicUsize,
+    most: AtomicUsize,
+}
+
+#[async_trait]
+impl TxtResolver for Logged {
+    async fn txt_lookup(&self, name: &str) -> Result<Vec<String>, SelfieError> {
+        self.sent.lock().unwrap().push(Instant::now());
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.most.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(self.delay).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        Ok(vec![name.to_string()])
+    }
+}
+
+fn names(count: usize) -> Vec<String> {
+    (0..count).map(|i| format!("user{}@example.com", i)).collect()
+}
+
+fn batch(sdk: &SelfieRecordsSDK, names: &[String], options: &LookupOptions) {
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let responses = sdk.get_records_batch(&names, Some(vec!["nostr"]), options);
+    for response in responses.values() {
+        assert!(response.get("nostr").unwrap().value.is_some(), "{:?}", response);
+    }
+}
+
+#[test]
+fn queries_are_spaced_by_the_rate() {
+    let resolver = Arc::new(Logged::default());
+    let sdk = SelfieRecordsSDK::builder().resolver(resolver.clone()).max_queries_per_second(50).build().unwrap();
+
+    batch(&sdk, &names(10), &LookupOptions::new().concurrency(10));
+
+    let sent = resolver.sent.lock().unwrap();
+    assert_eq!(sent.len(), 10);
+    // 20 ms apart, give or take the timer's resolution.
+    for pair in sent.windows(2) {
+        assert!(pair[1] - pair[0] >= Duration::from_millis(18), "{:?}", pair[1] - pair[0]);
+    }
+    assert!(sent[9] - sent[0] >= Duration::from_millis(170));
+}
+
+#[test]
+fn the_in_flight_ceiling_holds() {
+    let resolver = Arc::new(Logged { delay: Duration::from_millis(20), ..Logged::default() });
+    let sdk = SelfieRecordsSDK::builder().resolver(resolver.clone()).max_in_flight(3).build().unwrap();
+
+    batch(&sdk, &names(12), &LookupOptions::new().concurrency(12));
+
+    assert_eq!(resolver.sent.lock().unwrap().len(), 12);
+    assert_eq!(resolver.most.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn queries_that_would_miss_the_deadline_are_rate_limited() {
+    let resolver = Arc::new(Logged::default());
+    let sdk = SelfieRecordsSDK::builder().resolver(resolver.clone()).max_queries_per_second(2).build().unwrap();
+    let options = LookupOptions::new().deadline(Duration::from_millis(300));
+
+    let started = Instant::now();
+    let records = sdk.get_records_with("example.com", Some(vec!["nostr", "pgp", "did"]), None, &options).to_map();
+
+    assert!(started.elapsed() < Duration::from_millis(400), "{:?}", started.elapsed());
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("_nostr.example.com"));
+    for key in ["pgp", "did"] {
+        assert_eq!(records[key]["error_code"].as_deref(), Some("E_RATE_LIMITED"), "{}", key);
+    }
+    assert_eq!(resolver.sent.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn async_callers_wait_too() {
+    let resolver = Arc::new(Logged::default());
+    let sdk = SelfieRecordsSDK::builder().resolver(resolver.clone()).max_queries_per_second(50).build().unwrap();
+
+    let response = sdk.get_records_response_async("example.com", Some(vec!["nostr", "pgp", "did"]), None, &LookupOptions::new()).await;
+
+    assert!(response.iter().all(|(_, result)| result.value.is_some()));
+    let sent = resolver.sent.lock().unwrap();
+    assert!(sent[2] - sent[0] >= Duration::from_millis(36), "{:?}", sent[2] - sent[0]);
+}
+
+#[test]
+fn limits_come_from_the_config_and_must_be_positive() {
+    let config = Config::from_vars([("SELFIE_MAX_QUERIES_PER_SECOND".to_string(), "50".to_string())]).unwrap();
+    assert_eq!(config.max_queries_per_second, Some(50));
+    assert!(Config::from_vars([("SELFIE_MAX_IN_FLIGHT".to_string(), "0".to_string())]).is_err());
+    assert!(SdkBuilder::from_config(config).build().is_ok());
+
+    let error = |builder: SdkBuilder| builder.build().unwrap_err();
+    assert_eq!(error(SelfieRecordsSDK::builder().max_queries_per_second(0)), BuildError::ZeroQueryLimit);
+    assert_eq!(error(SelfieRecordsSDK::builder().max_in_flight(0)), BuildError::ZeroQueryLimit);
+}
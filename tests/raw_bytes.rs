@@ -0,0 +1,120 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::server::records_server;
+use common::txt_bytes_record;
+use data_encoding::BASE64;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{DirectResolver, KeyResult, LookupOptions, RawRecord, RecordsResponse, SelfieRecordsSDK, Source};
+
+/// Every byte value, split over character-strings the way a publisher
+/// would split a payload longer than 255 bytes.
+fn payload() -> Vec<u8> {
+    (0..=255u8).chain(0..200).collect()
+}
+
+fn lookup(sdk: &SelfieRecordsSDK) -> KeyResult {
+    let response = sdk.get_records_response("example.com", Some(vec!["nostr"]), None, &LookupOptions::new().attempts(1));
+    response.get("nostr").unwrap().clone()
+}
+
+fn strings(result: &KeyResult) -> Vec<Vec<Vec<u8>>> {
+    result.raw_records.iter().map(|record| record.strings().map(<[u8]>::to_vec).collect()).collect()
+}
+
+#[test]
+fn binary_rdata_survives_the_cache_and_serialization() {
+    let payload = payload();
+    let (first, second) = payload.split_at(255);
+    let server = records_server(vec![txt_bytes_record("_nostr.example.com.", &[first, second])]);
+    let resolver = DirectResolver::new(server);
+    let sdk = SelfieRecordsSDK::builder().resolver(Arc::new(resolver)).cache_ttl(Duration::from_secs(60)).build().unwrap();
+
+    let fetched = lookup(&sdk);
+    assert_eq!(fetched.source, Some(Source::Dns));
+    assert_eq!(fetched.raw_bytes(), Some(payload.as_slice()));
+    assert_eq!(strings(&fetched), [vec![first.to_vec(), second.to_vec()]]);
+    assert_eq!(fetched.value.as_deref(), Some(&*String::from_utf8_lossy(&payload)));
+
+    let cached = lookup(&sdk);
+    assert_eq!(cached.source, Some(Source::Cache));
+    assert_eq!(cached.raw_bytes(), Some(payload.as_slice()));
+    assert_eq!(cached.raw_records, fetched.raw_records);
+
+    let mut response = RecordsResponse::default();
+    response.insert("nostr", cached);
+    for decoded in [
+        RecordsResponse::from_cbor(&response.to_cbor()).unwrap(),
+        RecordsResponse::from_msgpack(&response.to_msgpack()).unwrap(),
+    ] {
+        assert_eq!(decoded.get("nostr").unwrap().raw_bytes(), Some(payload.as_slice()));
+        assert_eq!(decoded, response);
+    }
+
+    let json = serde_json::to_value(&response).unwrap();
+    let raw_value = json["nostr"]["raw_value"].as_str().unwrap();
+    assert_eq!(BASE64.decode(raw_value.as_bytes()).unwrap(), payload);
+    let records: Vec<Vec<Vec<u8>>> = json["nostr"]["raw_records"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|record| {
+            let strings = record.as_array().unwrap();
+            strings.iter().map(|string| BASE64.decode(string.as_str().unwrap().as_bytes()).unwrap()).collect()
+        })
+        .collect();
+    assert_eq!(records, [vec![first.to_vec(), second.to_vec()]]);
+}
+
+#[test]
+fn several_records_are_kept_apart() {
+    let resolver = MockTxtResolver::new().with_rdata(
+        "_nostr.example.com",
+        vec![RawRecord::new([&b"npub1\x00"[..], b"\xff"]), RawRecord::new(["relay"])],
+    );
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(resolver));
+
+    let result = lookup(&sdk);
+    // The value joins the records with a space, like the text form.
+    assert_eq!(result.raw_bytes(), Some(&b"npub1\x00\xff relay"[..]));
+    assert_eq!(strings(&result), [vec![b"npub1\x00".to_vec(), b"\xff".to_vec()], vec![b"relay".to_vec()]]);
+    assert_eq!(result.raw_records[0].bytes(), b"npub1\x00\xff");
+    assert_eq!(result.raw_records[0].to_string_lossy(), "npub1\u{0}\u{fffd}");
+    assert_eq!(result.values(), ["npub1\u{0}\u{fffd}", "relay"]);
+    let records = sdk.get_records("example.com", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(records["nostr"]["record_count"].as_deref(), Some("2"));
+}
+
+#[test]
+fn long_records_are_read_without_separators() {
+    let key = "A".repeat(255) + &"B".repeat(255) + "C";
+    let (first, rest) = key.split_at(255);
+    let (second, third) = rest.split_at(255);
+    let resolver = MockTxtResolver::new().with_rdata("_nostr.example.com", vec![RawRecord::new([first, second, third])]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(resolver));
+
+    let result = lookup(&sdk);
+    assert_eq!(result.value.as_deref(), Some(key.as_str()));
+    assert_eq!(result.values(), [key]);
+    let records = sdk.get_records("example.com", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(records["nostr"].get("record_count"), None);
+}
+
+#[test]
+fn overrides_and_text_resolvers_have_bytes_too() {
+    let resolver = MockTxtResolver::new().with_record("_nostr.example.com", &["npub1example"]);
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(Arc::new(resolver))
+        .override_record("example.com", "pgp", "https://example.com/key.asc")
+        .build()
+        .unwrap();
+
+    let response = sdk.get_records_response("example.com", Some(vec!["nostr", "pgp"]), None, &LookupOptions::new().attempts(1));
+    let nostr = response.get("nostr").unwrap();
+    assert_eq!(nostr.raw_bytes(), Some(&b"npub1example"[..]));
+    assert_eq!(nostr.raw_records, [RawRecord::new(["npub1example"])]);
+    let pgp = response.get("pgp").unwrap();
+    assert_eq!(pgp.raw_bytes(), Some(&b"https://example.com/key.asc"[..]));
+}
@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{KeyResult, LookupOptions, RecordEntry, SelfieError, SelfieRecordsSDK};
+
+#[test]
+fn results_match_as_found_not_found_or_error() {
+    let mock = MockTxtResolver::new()
+        .with_record("_bitcoin-payment.example.com", &["bitcoin:bc1qentry"])
+        .with_record("_nostr.example.com", &[]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let response = sdk.get_records_response("example.com", Some(vec!["bitcoin-payment", "nostr", "pgp"]), None, &LookupOptions::new().attempts(1));
+
+    assert_eq!(response.get("bitcoin-payment").unwrap().entry(), RecordEntry::Found { value: "bitcoin:bc1qentry" });
+    assert_eq!(response.get("nostr").unwrap().entry(), RecordEntry::NotFound);
+    assert!(matches!(response.get("pgp").unwrap().entry(), RecordEntry::Error(SelfieError::Resolver(_))));
+}
+
+#[test]
+fn values_win_over_errors_carried_alongside() {
+    let result = KeyResult { value: Some("npub1both".to_string()), error: Some(SelfieError::InvalidSignature), ..KeyResult::default() };
+    assert_eq!(result.entry(), RecordEntry::Found { value: "npub1both" });
+    assert_eq!(KeyResult::default().entry(), RecordEntry::NotFound);
+    let offline = KeyResult { error: Some(SelfieError::Offline), ..KeyResult::default() };
+    assert_eq!(offline.entry(), RecordEntry::Error(&SelfieError::Offline));
+}
+
+#[test]
+fn get_records_returns_typed_results() {
+    let mock = MockTxtResolver::new().with_record("_nostr.example.com", &["npub1typed"]).with_record("_pgp.example.com", &[]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let records = sdk.get_records("example.com", Some(vec!["nostr", "pgp"]), None);
+
+    assert_eq!(records.get("nostr").map(KeyResult::entry), Some(RecordEntry::Found { value: "npub1typed" }));
+    assert_eq!(records.get("pgp").map(KeyResult::entry), Some(RecordEntry::NotFound));
+    // The string maps are still there for callers written against them.
+    let map = records.to_map();
+    assert_eq!(map["nostr"]["value"].as_deref(), Some("npub1typed"));
+    assert_eq!(map["pgp"]["error_code"].as_deref(), Some("E_NO_RECORDS"));
+}
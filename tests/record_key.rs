@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{get_txt_record_key, parse_txt_record_key, Identifier, RecordKey, SelfieError, SelfieRecordsSDK};
+
+fn corpus() -> Vec<(Identifier, &'static str)> {
+    let domains = ["example.com", "a.b.c.d.example.co.uk", "xn--bcher-kva.example", "x.io"];
+    let locals = ["alice", "first.last", "bob-smith", "x"];
+    let keys = ["bitcoin-payment", "pgp", "nostr", "node-uri", "lightning-address", "a"];
+
+    let mut corpus = Vec::new();
+    for domain in domains {
+        for key in keys {
+            corpus.push((Identifier::Domain(domain.to_string()), key));
+            for local in locals {
+                corpus.push((Identifier::Email { local: local.to_string(), domain: domain.to_string() }, key));
+            }
+        }
+    }
+    corpus
+}
+
+#[test]
+fn test_parse_inverts_build() {
+    for (identifier, key) in corpus() {
+        let qname = get_txt_record_key(&identifier.to_string(), key).unwrap();
+        assert_eq!(parse_txt_record_key(&qname), Some((identifier.clone(), key.to_string())), "{}", qname);
+        assert_eq!(parse_txt_record_key(&format!("{}.", qname)), Some((identifier, key.to_string())));
+    }
+}
+
+#[test]
+fn test_parse_recognizes_both_forms() {
+    assert_eq!(
+        parse_txt_record_key("alice.user._bitcoin-payment.example.com"),
+        Some((
+            Identifier::Email { local: "alice".to_string(), domain: "example.com".to_string() },
+            "bitcoin-payment".to_string()
+        ))
+    );
+    assert_eq!(
+        parse_txt_record_key("_node-uri.lightning.example.org"),
+        Some((Identifier::Domain("lightning.example.org".to_string()), "node-uri".to_string()))
+    );
+}
+
+#[test]
+fn test_parse_rejects_foreign_names() {
+    for qname in ["example.com", "alice._pgp.example.com", "user._pgp.example.com", "_.example.com", "_pgp.", "_pgp..com", ""] {
+        assert_eq!(parse_txt_record_key(qname), None, "{}", qname);
+    }
+}
+
+#[test]
+fn test_keys_must_be_label_fragments() {
+    let longest = "k".repeat(61);
+    for key in ["", "my key!", "a_b", "a.b", "_pgp", "ключ", &format!("{}k", longest)] {
+        assert_eq!(RecordKey::custom(key), Err(SelfieError::InvalidKey { key: key.to_string() }), "{}", key);
+    }
+    assert_eq!(RecordKey::custom(&longest).unwrap().as_str(), longest);
+    assert_eq!(RecordKey::custom("-").unwrap().as_str(), "-");
+}
+
+#[test]
+fn test_keys_are_lowercased() {
+    assert_eq!(RecordKey::custom("Matrix").unwrap().as_str(), "matrix");
+    assert_eq!("BITCOIN-Payment".parse::<RecordKey>(), Ok(RecordKey::BITCOIN_PAYMENT));
+    assert_eq!(RecordKey::LIGHTNING_ADDRESS.to_string(), "lightning-address");
+}
+
+#[test]
+fn test_custom_keys_are_queried_under_their_label() {
+    let mock = MockTxtResolver::new()
+        .with_record("alice.user._matrix.example.com", &["@alice:example.com"])
+        .with_record("alice.user._nostr.example.com", &["npub1alice"]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let keys = [RecordKey::custom("MATRIX").unwrap(), RecordKey::NOSTR];
+    let records = sdk.get_records_for_keys("alice@example.com", &keys, None).to_map();
+    assert_eq!(records["matrix"]["value"].as_deref(), Some("@alice:example.com"));
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("npub1alice"));
+
+    // Results stay under the key as passed.
+    let records = sdk.get_records("alice@example.com", Some(vec!["Matrix"]), None).to_map();
+    assert_eq!(records["Matrix"]["value"].as_deref(), Some("@alice:example.com"));
+    assert_eq!(get_txt_record_key("alice@example.com", RecordKey::custom("Matrix").unwrap().as_str()).unwrap(), "alice.user._matrix.example.com");
+}
+
+#[test]
+fn test_invalid_keys_fail_without_a_query() {
+    let mock = Arc::new(MockTxtResolver::new().with_record("_nostr.example.com", &["npub1example"]));
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+
+    let records = sdk.get_records("example.com", Some(vec!["my key!", ""]), None).to_map();
+
+    assert_eq!(records["my key!"]["error_code"].as_deref(), Some("E_INVALID_KEY"));
+    assert_eq!(records[""]["error_code"].as_deref(), Some("E_INVALID_KEY"));
+    assert_eq!(mock.calls(), 0);
+}
@@ -0,0 +1,144 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use selfie_records_sdk::publish::{DnsProvider, ProviderError, SelfieRecord, TxtRecordSet};
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{parse_txt_record_key, Identifier, LookupOptions, RecordsResponse, SelfieError, SelfieRecordsSDK};
+
+fn lookup(resolver: Arc<MockTxtResolver>, name: &str) -> RecordsResponse {
+    let sdk = SelfieRecordsSDK::builder().resolver(resolver).record_version(Some(2)).build().unwrap();
+    let options = LookupOptions::new().attempts(1);
+    sdk.get_records_response(name, Some(vec!["bitcoin-payment"]), None, &options)
+}
+
+#[test]
+fn the_versioned_name_is_preferred() {
+    let resolver = MockTxtResolver::new()
+        .with_record("_v2._bitcoin-payment.example.com", &["bitcoin:bc1qv2"])
+        .with_record("_bitcoin-payment.example.com", &["bitcoin:bc1qv1"]);
+
+    let response = lookup(Arc::new(resolver), "example.com");
+    let result = response.get("bitcoin-payment").unwrap();
+    assert_eq!(result.value.as_deref(), Some("bitcoin:bc1qv2"));
+    assert_eq!(result.record_version, Some(2));
+    assert_eq!(response.to_map()["bitcoin-payment"]["record_version"].as_deref(), Some("2"));
+}
+
+#[test]
+fn a_missing_versioned_record_falls_back_to_the_unversioned_name() {
+    let resolver = Arc::new(
+        MockTxtResolver::new()
+            .with_record("_v2._bitcoin-payment.example.com", &[])
+            .with_record("_bitcoin-payment.example.com", &["bitcoin:bc1qv1"]),
+    );
+
+    let response = lookup(resolver.clone(), "example.com");
+    let result = response.get("bitcoin-payment").unwrap();
+    assert_eq!(result.value.as_deref(), Some("bitcoin:bc1qv1"));
+    assert_eq!(result.record_version, None);
+    assert_eq!(result.attempts, Some(2));
+    assert_eq!(resolver.calls(), 2);
+    assert!(!response.to_map()["bitcoin-payment"].contains_key("record_version"));
+}
+
+#[test]
+fn transient_errors_do_not_fall_back() {
+    // The versioned name is not registered, so the mock fails it the way an
+    // unreachable resolver would.
+    let resolver = Arc::new(MockTxtResolver::new().with_record("_bitcoin-payment.example.com", &["bitcoin:bc1qv1"]));
+
+    let response = lookup(resolver.clone(), "example.com");
+    let result = response.get("bitcoin-payment").unwrap();
+    assert!(matches!(result.error, Some(SelfieError::Resolver(_))), "{:?}", result.error);
+    assert_eq!(result.value, None);
+    assert_eq!(result.record_version, Some(2));
+    assert_eq!(resolver.calls(), 1);
+}
+
+#[test]
+fn nothing_at_either_name_is_no_records() {
+    let resolver = MockTxtResolver::new()
+        .with_record("_v2._bitcoin-payment.example.com", &[])
+        .with_record("_bitcoin-payment.example.com", &[]);
+
+    let response = lookup(Arc::new(resolver), "example.com");
+    assert_eq!(response.get("bitcoin-payment").unwrap().error, Some(SelfieError::NoRecords));
+}
+
+#[test]
+fn email_names_carry_the_version_before_the_key() {
+    let resolver = Arc::new(
+        MockTxtResolver::new()
+            .with_record("alice.user._v2._bitcoin-payment.example.com", &[])
+            .with_record("alice.user._bitcoin-payment.example.com", &["bitcoin:bc1qalice"])
+            .with_record("bob.user._v2._bitcoin-payment.example.com", &["bitcoin:bc1qbob"]),
+    );
+
+    let alice = lookup(resolver.clone(), "alice@example.com");
+    assert_eq!(alice.get("bitcoin-payment").unwrap().value.as_deref(), Some("bitcoin:bc1qalice"));
+    let bob = lookup(resolver, "bob@example.com");
+    assert_eq!(bob.get("bitcoin-payment").unwrap().value.as_deref(), Some("bitcoin:bc1qbob"));
+    assert_eq!(bob.get("bitcoin-payment").unwrap().record_version, Some(2));
+}
+
+#[test]
+fn versioned_names_that_are_too_long_fall_back() {
+    // Exactly 253 characters unversioned, so only the versioned name is invalid.
+    let domain = format!("{}.{}.{}.{}.com", "a".repeat(63), "b".repeat(63), "c".repeat(63), "d".repeat(40));
+    let unversioned = format!("_bitcoin-payment.{}", domain);
+    assert_eq!(unversioned.len(), 253);
+    let resolver = MockTxtResolver::new().with_record(&unversioned, &["bitcoin:bc1qlong"]);
+
+    let response = lookup(Arc::new(resolver), &domain);
+    assert_eq!(response.get("bitcoin-payment").unwrap().value.as_deref(), Some("bitcoin:bc1qlong"));
+}
+
+#[test]
+fn versioned_names_parse_back_to_their_key() {
+    let parsed = parse_txt_record_key("alice.user._v2._bitcoin-payment.example.com.").unwrap();
+    assert_eq!(
+        parsed,
+        (Identifier::Email { local: "alice".to_string(), domain: "example.com".to_string() }, "bitcoin-payment".to_string())
+    );
+    let parsed = parse_txt_record_key("_v10._pgp.example.com").unwrap();
+    assert_eq!(parsed, (Identifier::Domain("example.com".to_string()), "pgp".to_string()));
+    // "_vx" is not a version label, so it is taken as the key.
+    let parsed = parse_txt_record_key("_vx._pgp.example.com").unwrap();
+    assert_eq!(parsed.1, "vx");
+}
+
+#[derive(Default)]
+struct RecordingProvider {
+    names: Mutex<Vec<String>>,
+}
+
+#[async_trait]
+impl DnsProvider for RecordingProvider {
+    async fn upsert_txt(&self, _zone: &str, name: &str, _values: &[String], _ttl: u32) -> Result<(), ProviderError> {
+        self.names.lock().unwrap().push(name.to_string());
+        Ok(())
+    }
+
+    async fn delete_txt(&self, _zone: &str, _name: &str) -> Result<(), ProviderError> {
+        Ok(())
+    }
+
+    async fn list_txt(&self, _zone: &str) -> Result<Vec<TxtRecordSet>, ProviderError> {
+        Ok(Vec::new())
+    }
+}
+
+#[test]
+fn versioned_records_are_published_under_the_versioned_name() {
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new()));
+    let provider = RecordingProvider::default();
+
+    sdk.publish_via(&provider, &SelfieRecord::new("example.com", "bitcoin-payment", "bitcoin:bc1q").version(2)).unwrap();
+    sdk.publish_via(&provider, &SelfieRecord::new("Alice@Example.com", "nostr", "npub1").version(3)).unwrap();
+    sdk.publish_via(&provider, &SelfieRecord::new("example.com", "pgp", "key").ttl(60)).unwrap();
+
+    assert_eq!(
+        *provider.names.lock().unwrap(),
+        ["_v2._bitcoin-payment.example.com", "alice.user._v3._nostr.example.com", "_pgp.example.com"]
+    );
+}
@@ -0,0 +1,92 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{
+    BuildError, CacheConfig, KeyResult, LookupOptions, ResponseLimits, Sanitization, SdkBuilder, SelfieError, SelfieRecordsSDK,
+};
+
+fn lookup(sdk: &SelfieRecordsSDK, key: &str) -> KeyResult {
+    let options = LookupOptions::new().attempts(1);
+    let response = sdk.get_records_response("example.com", Some(vec![key]), None, &options);
+    response.get(key).unwrap().clone()
+}
+
+fn too_large(name: &str, reason: &str) -> Option<SelfieError> {
+    Some(SelfieError::ResponseTooLarge { name: name.to_string(), reason: reason.to_string() })
+}
+
+#[test]
+fn answers_with_too_many_records_fail_and_are_not_cached() {
+    let values: Vec<String> = (0..9).map(|i| format!("npub1record{}", i)).collect();
+    let values: Vec<&str> = values.iter().map(String::as_str).collect();
+    let mock = Arc::new(MockTxtResolver::new().with_record("_nostr.example.com", &values).with_record("_did.example.com", &values[..8]));
+    let config = CacheConfig { min_ttl: Duration::from_secs(30), ..CacheConfig::default() };
+    let sdk = SelfieRecordsSDK::builder().resolver(mock.clone()).cache(config).build().unwrap();
+
+    let result = lookup(&sdk, "nostr");
+    assert_eq!(result.error, too_large("_nostr.example.com", "9 records, at most 8"));
+    assert_eq!(result.error.as_ref().map(SelfieError::code), Some("E_RESPONSE_TOO_LARGE"));
+    assert_eq!(result.value, None);
+    lookup(&sdk, "nostr");
+    assert_eq!(mock.calls(), 2);
+
+    assert_eq!(lookup(&sdk, "did").error, None);
+}
+
+#[test]
+fn record_and_answer_sizes_are_limited_as_configured() {
+    let mock = MockTxtResolver::new()
+        .with_record("_pgp.example.com", &[&"a".repeat(101)])
+        .with_record("_nostr.example.com", &[&"b".repeat(100), &"c".repeat(100), &"d".repeat(51)])
+        .with_record("_did.example.com", &[&"e".repeat(100), &"f".repeat(100)]);
+    let limits = ResponseLimits { max_value_bytes: 100, max_total_bytes: 250, ..ResponseLimits::default() };
+    let sdk = SelfieRecordsSDK::builder().resolver(Arc::new(mock)).response_limits(limits).build().unwrap();
+
+    assert_eq!(lookup(&sdk, "pgp").error, too_large("_pgp.example.com", "a record of 101 bytes, at most 100"));
+    assert_eq!(lookup(&sdk, "nostr").error, too_large("_nostr.example.com", "251 bytes, at most 250"));
+    assert_eq!(lookup(&sdk, "did").value.map(|value| value.len()), Some(201));
+}
+
+#[test]
+fn zero_limits_are_rejected() {
+    let limits = ResponseLimits { max_records_per_key: 0, ..ResponseLimits::default() };
+    let result = SdkBuilder::new().resolver(Arc::new(MockTxtResolver::new())).response_limits(limits).build();
+    assert!(matches!(result, Err(BuildError::ZeroResponseLimit)));
+}
+
+fn sanitizing(sanitization: Sanitization) -> SelfieRecordsSDK {
+    let mock = MockTxtResolver::new()
+        .with_record("_nostr.example.com", &["  npub1alice\r\n"])
+        .with_record("_bitcoin-payment.example.com", &["bitcoin:bc1q\u{1b}]0;pwned\u{7}\u{1b}[2J "])
+        .with_raw_record("_pgp.example.com", &[b"https://example.com/\xff.asc"]);
+    SelfieRecordsSDK::builder().resolver(Arc::new(mock)).sanitize_values(sanitization).build().unwrap()
+}
+
+#[test]
+fn escaping_trims_values_and_escapes_control_characters() {
+    let sdk = sanitizing(Sanitization::Escape);
+
+    assert_eq!(lookup(&sdk, "nostr").value.as_deref(), Some("npub1alice"));
+    let payment = lookup(&sdk, "bitcoin-payment");
+    assert_eq!(payment.value.as_deref(), Some("bitcoin:bc1q\\u{1b}]0;pwned\\u{7}\\u{1b}[2J"));
+    assert_eq!(payment.raw_bytes(), Some("bitcoin:bc1q\u{1b}]0;pwned\u{7}\u{1b}[2J ".as_bytes()));
+    assert_eq!(lookup(&sdk, "pgp").value.as_deref(), Some("https://example.com/\u{fffd}.asc"));
+}
+
+#[test]
+fn rejecting_fails_values_that_are_not_clean_once_trimmed() {
+    let sdk = sanitizing(Sanitization::Reject);
+
+    assert_eq!(lookup(&sdk, "nostr").value.as_deref(), Some("npub1alice"));
+    let payment = lookup(&sdk, "bitcoin-payment");
+    assert_eq!(
+        payment.error,
+        Some(SelfieError::InvalidRecord { key: "bitcoin-payment".to_string(), reason: "value is control_characters".to_string() })
+    );
+    assert_eq!(payment.value, None);
+    assert_eq!(
+        lookup(&sdk, "pgp").error,
+        Some(SelfieError::InvalidRecord { key: "pgp".to_string(), reason: "value is not_utf8".to_string() })
+    );
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::Arc;
+
+use common::server::records_server;
+use common::txt_record;
+use selfie_records_sdk::{BuildError, DirectResolver, KeyResult, LookupOptions, NameServerGroup, Route, SelfieError, SelfieRecordsSDK, SdkBuilder};
+
+/// The public view: everything except the internal records.
+fn public_server() -> SocketAddr {
+    records_server(vec![
+        txt_record("_bitcoin-payment.example.com.", "bitcoin:bc1qpublic"),
+        txt_record("_nostr.example.com.", "npub1public"),
+    ])
+}
+
+/// The corporate resolver: internal records only.
+fn corp_server() -> SocketAddr {
+    records_server(vec![
+        txt_record("_pgp.example.com.", "https://corp.example.com/key.asc"),
+        txt_record("_bitcoin-payment.corp.example.com.", "bitcoin:bc1qcorp"),
+        txt_record("_pgp.corp.example.com.", "https://corp.example.com/corp.asc"),
+        txt_record("_bitcoin-payment.eu.corp.example.com.", "bitcoin:bc1qeu"),
+    ])
+}
+
+fn builder(default: SocketAddr) -> SdkBuilder {
+    SelfieRecordsSDK::builder().resolver(Arc::new(DirectResolver::new(default)))
+}
+
+fn lookup(sdk: &SelfieRecordsSDK, name: &str, key: &str) -> KeyResult {
+    let options = LookupOptions::new().attempts(1);
+    sdk.get_records_response(name, Some(vec![key]), None, &options).get(key).unwrap().clone()
+}
+
+#[test]
+fn keys_with_a_rule_go_to_their_group() {
+    let (public, corp) = (public_server(), corp_server());
+    let sdk = builder(public).route_key("pgp", NameServerGroup::from(corp)).build().unwrap();
+
+    let pgp = lookup(&sdk, "example.com", "pgp");
+    assert_eq!(pgp.value.as_deref(), Some("https://corp.example.com/key.asc"));
+    assert_eq!(pgp.route, Some(Route::Key));
+    assert_eq!(pgp.resolver, Some(corp.to_string()));
+
+    let payment = lookup(&sdk, "example.com", "bitcoin-payment");
+    assert_eq!(payment.value.as_deref(), Some("bitcoin:bc1qpublic"));
+    assert_eq!(payment.route, Some(Route::Default));
+    assert_eq!(payment.resolver, Some(public.to_string()));
+}
+
+#[test]
+fn without_rules_everything_goes_to_the_default() {
+    let sdk = builder(public_server()).build().unwrap();
+
+    let pgp = lookup(&sdk, "example.com", "pgp");
+    assert_eq!(pgp.error, Some(SelfieError::NoRecords));
+    assert_eq!(pgp.route, Some(Route::Default));
+}
+
+#[test]
+fn suffix_rules_match_whole_labels() {
+    let (public, corp) = (public_server(), corp_server());
+    let sdk = builder(public).route_suffix("Corp.Example.com.", corp).build().unwrap();
+
+    let payment = lookup(&sdk, "corp.example.com", "bitcoin-payment");
+    assert_eq!(payment.value.as_deref(), Some("bitcoin:bc1qcorp"));
+    assert_eq!(payment.route, Some(Route::Suffix("corp.example.com".to_string())));
+    let nested = lookup(&sdk, "eu.corp.example.com", "bitcoin-payment");
+    assert_eq!(nested.value.as_deref(), Some("bitcoin:bc1qeu"));
+
+    // Only the domain of an address is routed on.
+    let sdk = builder(public).route_suffix("example.com", corp).build().unwrap();
+    assert_eq!(lookup(&sdk, "alice@example.com", "nostr").route, Some(Route::Suffix("example.com".to_string())));
+    let sdk = builder(public).route_suffix("ample.com", corp).build().unwrap();
+    let outside = lookup(&sdk, "example.com", "bitcoin-payment");
+    assert_eq!(outside.route, Some(Route::Default));
+    assert_eq!(outside.value.as_deref(), Some("bitcoin:bc1qpublic"));
+}
+
+#[test]
+fn the_longest_suffix_wins() {
+    let (public, corp) = (public_server(), corp_server());
+    let sdk = builder(public).route_suffix("corp.example.com", corp).route_suffix("example.com", public).build().unwrap();
+
+    assert_eq!(lookup(&sdk, "corp.example.com", "bitcoin-payment").value.as_deref(), Some("bitcoin:bc1qcorp"));
+    let public_result = lookup(&sdk, "example.com", "bitcoin-payment");
+    assert_eq!(public_result.route, Some(Route::Suffix("example.com".to_string())));
+    assert_eq!(public_result.value.as_deref(), Some("bitcoin:bc1qpublic"));
+}
+
+#[test]
+fn key_rules_beat_suffix_rules() {
+    let (public, corp) = (public_server(), corp_server());
+    let sdk = builder(public).route_suffix("corp.example.com", public).route_key("pgp", corp).build().unwrap();
+
+    let pgp = lookup(&sdk, "corp.example.com", "pgp");
+    assert_eq!(pgp.route, Some(Route::Key));
+    assert_eq!(pgp.value.as_deref(), Some("https://corp.example.com/corp.asc"));
+    let payment = lookup(&sdk, "corp.example.com", "bitcoin-payment");
+    assert_eq!(payment.route, Some(Route::Suffix("corp.example.com".to_string())));
+    assert_eq!(payment.error, Some(SelfieError::NoRecords));
+}
+
+#[test]
+fn the_route_is_in_the_string_map() {
+    let (public, corp) = (public_server(), corp_server());
+    let sdk = builder(public).route_key("pgp", corp).build().unwrap();
+
+    let map = sdk.get_records_with("example.com", Some(vec!["pgp"]), None, &LookupOptions::new().attempts(1)).to_map();
+    assert_eq!(map["pgp"]["route"].as_deref(), Some("key"));
+    assert_eq!(map["pgp"]["resolver"].as_deref(), Some(&*corp.to_string()));
+}
+
+#[test]
+fn groups_fall_over_to_their_next_server() {
+    // Nothing listens on this port any more, so queries to it are refused.
+    let dead = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let corp = corp_server();
+    let group = format!("{}, {}", dead, corp);
+    let sdk = builder(public_server()).route_key("pgp", group.as_str()).build().unwrap();
+
+    let pgp = lookup(&sdk, "example.com", "pgp");
+    assert_eq!(pgp.value.as_deref(), Some("https://corp.example.com/key.asc"));
+    assert_eq!(pgp.resolver, Some(format!("{},{}", dead, corp)));
+}
+
+#[test]
+fn invalid_addresses_are_rejected_on_build() {
+    let error = builder(public_server()).route_key("pgp", "10.0.0.53, not-an-address").build().unwrap_err();
+    assert_eq!(error, BuildError::InvalidRoute("invalid nameserver address \"not-an-address\"".to_string()));
+
+    assert!(SelfieRecordsSDK::builder().route_suffix("corp.example.com", "10.0.0.53").build().is_ok());
+    assert!(SelfieRecordsSDK::builder().route_key("pgp", "10.0.0.53:5353").build().is_ok());
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use std::net::UdpSocket;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use common::server::{dns_server, records_server};
+use common::txt_record;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{BuildError, NameserverSource, SdkBuilder, SelfieRecordsSDK, Transport, TransportPreference};
+
+#[test]
+fn nonsense_settings_are_rejected_on_build() {
+    let error = |builder: SdkBuilder| builder.build().unwrap_err();
+
+    assert_eq!(error(SelfieRecordsSDK::builder().timeout(Duration::ZERO)), BuildError::ZeroTimeout);
+    assert_eq!(error(SelfieRecordsSDK::builder().nameservers(&[])), BuildError::NoNameservers);
+    assert_eq!(error(SelfieRecordsSDK::builder().default_keys(&[])), BuildError::NoDefaultKeys);
+}
+
+#[test]
+fn queries_go_to_the_nameservers() {
+    let server = records_server(vec![txt_record("_nostr.example.com.", "npub1configured")]);
+    let sdk = SelfieRecordsSDK::builder().nameservers(&[server]).build().unwrap();
+
+    let records = sdk.get_records("example.com", Some(vec!["nostr"]), None).to_map();
+
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("npub1configured"));
+}
+
+#[test]
+fn the_system_configuration_is_the_default() {
+    let builder = SelfieRecordsSDK::builder();
+    assert_eq!(builder.get_nameserver_source(), &NameserverSource::System);
+    // Builds whether or not this host's configuration can be read.
+    builder.build().unwrap();
+
+    let server = records_server(vec![txt_record("_nostr.example.com.", "npub1static")]);
+    let builder = SelfieRecordsSDK::builder().nameserver_source(NameserverSource::Static(vec![server]));
+    assert_eq!(builder.get_nameserver_source(), &NameserverSource::Static(vec![server]));
+    let records = builder.build().unwrap().get_records("example.com", Some(vec!["nostr"]), None).to_map();
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("npub1static"));
+
+    let error = SelfieRecordsSDK::builder().nameserver_source(NameserverSource::Static(Vec::new())).build().unwrap_err();
+    assert_eq!(error, BuildError::NoNameservers);
+}
+
+#[test]
+fn tcp_only_skips_udp() {
+    let server = dns_server("npub1tcp", true);
+    let lookup = |tcp_only| {
+        let sdk = SelfieRecordsSDK::builder().nameservers(&[server]).tcp_only(tcp_only).build().unwrap();
+        let response = sdk.get_records_response("example.com", Some(vec!["nostr"]), None, &Default::default());
+        response.get("nostr").unwrap().clone()
+    };
+
+    let result = lookup(true);
+    assert_eq!(result.value.as_deref(), Some("npub1tcp"));
+    let wire = result.wire.unwrap();
+    assert_eq!((wire.transport_used, wire.truncated), (Transport::Tcp, false));
+    // Over UDP the truncated answer is repeated over TCP.
+    assert!(lookup(false).wire.unwrap().truncated);
+}
+
+#[test]
+fn the_transport_preference_applies_to_servers_named_per_call() {
+    let value = "lno1".repeat(200);
+    let server = dns_server(&value, true);
+    let lookup = |transport| {
+        let sdk = SelfieRecordsSDK::builder().transport_preference(transport).build().unwrap();
+        let response = sdk.get_records_response("example.com", Some(vec!["bitcoin-payment"]), Some(&server.to_string()), &Default::default());
+        response.get("bitcoin-payment").unwrap().clone()
+    };
+
+    assert_eq!(SelfieRecordsSDK::builder().get_transport_preference(), TransportPreference::UdpThenTcp);
+    // The full record comes over TCP either way, after a truncated UDP answer by default.
+    for (transport, truncated) in [(TransportPreference::UdpThenTcp, true), (TransportPreference::TcpOnly, false)] {
+        let result = lookup(transport);
+        assert_eq!(result.value.as_deref(), Some(value.as_str()));
+        let wire = result.wire.unwrap();
+        assert_eq!((wire.transport_used, wire.truncated), (Transport::Tcp, truncated), "{:?}", transport);
+    }
+}
+
+#[test]
+fn the_timeout_applies_to_calls_without_options() {
+    // A server that never answers.
+    let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let sdk = SelfieRecordsSDK::builder()
+        .nameservers(&[silent.local_addr().unwrap()])
+        .timeout(Duration::from_millis(100))
+        .attempts(1)
+        .build()
+        .unwrap();
+
+    let started = Instant::now();
+    let records = sdk.get_records("example.com", Some(vec!["nostr"]), None).to_map();
+
+    assert_eq!(records["nostr"]["error_code"].as_deref(), Some("E_TIMEOUT"));
+    // The default would wait five seconds.
+    assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+}
+
+#[test]
+fn the_attempts_apply_to_calls_without_options() {
+    // `_nostr.example.com` is not registered, so every attempt fails.
+    let mock = Arc::new(MockTxtResolver::new());
+    let sdk = SelfieRecordsSDK::builder().resolver(mock.clone()).attempts(3).build().unwrap();
+
+    let records = sdk.get_records("example.com", Some(vec!["nostr"]), None).to_map();
+
+    assert_eq!(records["nostr"]["error_code"].as_deref(), Some("E_RESOLVER"));
+    assert_eq!(mock.calls(), 3);
+}
+
+#[test]
+fn default_keys_replace_the_well_known_ones() {
+    let mock = MockTxtResolver::new().with_record("_nostr.example.com", &["npub1default"]).with_record("_did.example.com", &[]);
+    let sdk = SelfieRecordsSDK::builder().resolver(Arc::new(mock)).default_keys(&["nostr", "did"]).build().unwrap();
+
+    let records = sdk.get_records("example.com", None, None).to_map();
+
+    let mut keys: Vec<&str> = records.keys().map(String::as_str).collect();
+    keys.sort();
+    assert_eq!(keys, ["did", "nostr"]);
+    assert_eq!(records["nostr"]["value"].as_deref(), Some("npub1default"));
+}
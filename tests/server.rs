@@ -0,0 +1,215 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use common::server::dns_server;
+use selfie_records_sdk::server::Server;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{LookupOptions, SelfieRecordsSDK};
+use serde_json::Value;
+
+fn mock() -> MockTxtResolver {
+    MockTxtResolver::new()
+        .with_record("alice.user._bitcoin-payment.example.com", &["bitcoin:bc1qserved"])
+        .with_ttl("alice.user._bitcoin-payment.example.com", 300)
+        .with_record("alice.user._nostr.example.com", &["npub1served"])
+        .with_record("_bitcoin-payment.empty.example", &[])
+        .with_record("_bitcoin-payment.slow.example", &["bitcoin:bc1qslow"])
+        .with_delay("_bitcoin-payment.slow.example", Duration::from_millis(500))
+}
+
+/// Serves `sdk` on an ephemeral port for the rest of the test run.
+fn serve_sdk(sdk: SelfieRecordsSDK, configure: impl FnOnce(Server) -> Server) -> SocketAddr {
+    let server = configure(Server::new(Arc::new(sdk)).lookup_options(LookupOptions::new().attempts(1)));
+    let listening = server.listen("127.0.0.1:0").unwrap();
+    let addr = listening.local_addr().unwrap();
+    thread::spawn(move || listening.run());
+    addr
+}
+
+fn serve(mock: Arc<MockTxtResolver>, configure: impl FnOnce(Server) -> Server) -> SocketAddr {
+    serve_sdk(SelfieRecordsSDK::with_resolver(mock), configure)
+}
+
+/// Sends `method` for `target` and returns the status, headers and JSON body.
+fn request(addr: SocketAddr, method: &str, target: &str) -> (u16, String, Value) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "{} {} HTTP/1.1\r\nHost: {}\r\n\r\n", method, target, addr).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head[9..12].parse().unwrap();
+    (status, head.to_string(), serde_json::from_str(body).unwrap())
+}
+
+fn get(addr: SocketAddr, target: &str) -> (u16, Value) {
+    let (status, _, body) = request(addr, "GET", target);
+    (status, body)
+}
+
+#[test]
+fn records_are_served_as_json() {
+    let addr = serve(Arc::new(mock()), |server| server);
+
+    let (status, body) = get(addr, "/v1/records/alice%40example.com?keys=bitcoin-payment,nostr");
+
+    assert_eq!(status, 200);
+    assert_eq!(body["bitcoin-payment"]["value"], "bitcoin:bc1qserved");
+    assert_eq!(body["nostr"]["value"], "npub1served");
+    assert_eq!(body.as_object().unwrap().len(), 2);
+    let (status, body) = get(addr, "/v1/records/alice@example.com?keys=nostr");
+    assert_eq!((status, &body["nostr"]["value"]), (200, &Value::from("npub1served")));
+}
+
+#[test]
+fn requests_share_the_sdk_and_its_cache() {
+    let mock = Arc::new(mock());
+    let sdk = SelfieRecordsSDK::builder().resolver(mock.clone()).cache_ttl(Duration::from_secs(60)).build().unwrap();
+    let addr = serve_sdk(sdk, |server| server);
+
+    for _ in 0..3 {
+        let (status, body) = get(addr, "/v1/records/alice@example.com?keys=bitcoin-payment");
+        assert_eq!((status, &body["bitcoin-payment"]["value"]), (200, &Value::from("bitcoin:bc1qserved")));
+    }
+    assert_eq!(mock.calls(), 1);
+}
+
+#[test]
+fn missing_records_are_not_errors_of_the_service() {
+    let addr = serve(Arc::new(mock()), |server| server);
+
+    let (status, body) = get(addr, "/v1/records/empty.example?keys=bitcoin-payment");
+
+    assert_eq!(status, 200);
+    assert_eq!(body["bitcoin-payment"]["value"], Value::Null);
+    assert_eq!(body["bitcoin-payment"]["error"]["code"], "E_NO_RECORDS");
+}
+
+#[test]
+fn invalid_names_are_bad_requests() {
+    let addr = serve(Arc::new(mock()), |server| server);
+
+    let (status, body) = get(addr, "/v1/records/not%20a%20name");
+
+    assert_eq!(status, 400);
+    assert_eq!(body["error"]["code"], "E_INVALID_NAME");
+    assert_eq!(body["error"]["numeric_code"], 1);
+}
+
+#[test]
+fn resolver_failures_are_bad_gateways() {
+    let addr = serve(Arc::new(mock()), |server| server);
+
+    let (status, body) = get(addr, "/v1/records/unknown.example?keys=bitcoin-payment");
+    assert_eq!(status, 502);
+    assert_eq!(body["error"]["code"], "E_RESOLVER");
+    assert_eq!(body["records"]["bitcoin-payment"]["error"]["code"], "E_RESOLVER");
+
+    let (status, body) = get(addr, "/v1/records/slow.example?keys=bitcoin-payment&timeout=0.1");
+    assert_eq!(status, 502);
+    assert_eq!(body["error"]["code"], "E_TIMEOUT");
+    let (status, body) = get(addr, "/v1/records/slow.example?keys=bitcoin-payment&timeout=5");
+    assert_eq!((status, &body["bitcoin-payment"]["value"]), (200, &Value::from("bitcoin:bc1qslow")));
+}
+
+#[test]
+fn invalid_parameters_are_bad_requests() {
+    let addr = serve(Arc::new(mock()), |server| server);
+
+    for (query, message) in [
+        ("timeout=soon", "timeout must be a number of seconds from 0 to 60"),
+        ("timeout=0", "timeout must be a number of seconds from 0 to 60"),
+        ("dns=bad%20server", "Invalid nameserver \"bad server\""),
+        ("dns=resolver.example:dns", "Invalid nameserver \"resolver.example:dns\""),
+        ("limit=1", "unknown parameter \"limit\""),
+        ("keys=%zz", "keys is not valid percent-encoded UTF-8"),
+    ] {
+        let (status, body) = get(addr, &format!("/v1/records/example.com?{}", query));
+        assert_eq!((status, &body["error"]["message"]), (400, &Value::from(message)), "{}", query);
+    }
+}
+
+#[test]
+fn dns_takes_only_addresses() {
+    let addr = serve(Arc::new(mock()), |server| server);
+    let port = dns_server("npub1direct", false).port();
+
+    let (status, body) = get(addr, &format!("/v1/records/alice@example.com?keys=nostr&dns=127.0.0.1:{}", port));
+    assert_eq!((status, &body["nostr"]["value"]), (200, &Value::from("npub1direct")));
+    for dns in [format!("localhost:{}", port), "https://127.0.0.1/dns-query".to_string(), "tls://127.0.0.1@dns.example".to_string()] {
+        let (status, body) = get(addr, &format!("/v1/records/alice@example.com?keys=nostr&dns={}", dns));
+        assert_eq!((status, &body["error"]["message"]), (400, &Value::from("dns must be an IP address, with an optional port")), "{}", dns);
+    }
+}
+
+#[test]
+fn plus_is_a_space_only_in_the_query() {
+    let mock = Arc::new(mock().with_record("alice+pay.user._nostr.example.com", &["npub1plus"]));
+    let addr = serve(mock, |server| server);
+
+    let (status, body) = get(addr, "/v1/records/alice+pay@example.com?keys=nostr");
+    assert_eq!((status, &body["nostr"]["value"]), (200, &Value::from("npub1plus")));
+    let (status, body) = get(addr, "/v1/records/example.com?keys=nostr+pgp");
+    assert_eq!((status, &body["nostr pgp"]["error"]["code"]), (200, &Value::from("E_INVALID_KEY")));
+}
+
+#[test]
+fn health_unknown_paths_and_methods_are_answered() {
+    let addr = serve(Arc::new(mock()), |server| server);
+
+    assert_eq!(get(addr, "/healthz"), (200, serde_json::json!({"status": "ok"})));
+    assert_eq!(get(addr, "/v2/records/example.com").0, 404);
+    assert_eq!(get(addr, "/v1/records/").0, 404);
+    assert_eq!(request(addr, "POST", "/v1/records/example.com").0, 405);
+    let (_, head, _) = request(addr, "GET", "/healthz");
+    assert!(head.contains("\r\nContent-Type: application/json\r\n"), "{}", head);
+}
+
+#[test]
+fn clients_over_the_rate_limit_are_turned_away() {
+    let addr = serve(Arc::new(mock()), |server| server.rate_limit(2, Duration::from_secs(60)));
+
+    assert_eq!(get(addr, "/healthz").0, 200);
+    assert_eq!(get(addr, "/healthz").0, 200);
+    let (status, head, body) = request(addr, "GET", "/healthz");
+
+    assert_eq!(status, 429);
+    assert_eq!(body["error"]["message"], "rate limit exceeded");
+    let retry_after: u64 = head.split("\r\nRetry-After: ").nth(1).unwrap().parse().unwrap();
+    assert!((1..=60).contains(&retry_after), "{}", retry_after);
+}
+
+#[test]
+fn lookups_beyond_the_limit_are_turned_away() {
+    let addr = serve(Arc::new(mock()), |server| server.max_lookups(1));
+
+    let slow = thread::spawn(move || get(addr, "/v1/records/slow.example?keys=bitcoin-payment"));
+    thread::sleep(Duration::from_millis(200));
+    let (status, body) = get(addr, "/v1/records/alice@example.com?keys=nostr");
+    assert_eq!(status, 503);
+    assert_eq!(body["error"]["message"], "too many lookups running");
+    // Health checks do not wait for lookups.
+    assert_eq!(get(addr, "/healthz").0, 200);
+
+    assert_eq!(slow.join().unwrap().0, 200);
+    assert_eq!(get(addr, "/v1/records/alice@example.com?keys=nostr").0, 200);
+}
+
+#[test]
+fn connections_beyond_the_limit_are_turned_away() {
+    let addr = serve(Arc::new(mock()), |server| server.max_connections(1));
+
+    let idle = TcpStream::connect(addr).unwrap();
+    thread::sleep(Duration::from_millis(200));
+    let (status, body) = get(addr, "/healthz");
+    assert_eq!(status, 503);
+    assert_eq!(body["error"]["message"], "too many connections");
+
+    drop(idle);
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(get(addr, "/healthz").0, 200);
+}
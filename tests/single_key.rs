@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{NameError, RecordEntry, SelfieError, SelfieRecordsSDK};
+
+fn sdk() -> SelfieRecordsSDK {
+    SelfieRecordsSDK::with_resolver(Arc::new(
+        MockTxtResolver::new()
+            .with_record("alice.user._bitcoin-payment.example.com", &["bitcoin:bc1qalice"])
+            .with_record("_nostr.example.com", &["npub1example"])
+            .with_record("_pgp.example.com", &[]),
+    ))
+}
+
+#[test]
+fn found_records_come_back_directly() {
+    let sdk = sdk();
+
+    let payment = sdk.get_bitcoin_payment("alice@example.com");
+    assert_eq!(payment.entry(), RecordEntry::Found { value: "bitcoin:bc1qalice" });
+    assert_eq!(sdk.get_nostr("example.com").entry(), RecordEntry::Found { value: "npub1example" });
+    assert_eq!(sdk.get_record("example.com", "NOSTR").value.as_deref(), Some("npub1example"));
+}
+
+#[test]
+fn missing_records_are_not_errors() {
+    let sdk = sdk();
+
+    assert_eq!(sdk.get_record("example.com", "pgp").entry(), RecordEntry::NotFound);
+    // The mock fails names it does not know, like an unreachable server.
+    let failed = sdk.get_record("example.com", "did");
+    assert!(matches!(failed.entry(), RecordEntry::Error(SelfieError::Resolver(_))), "{:?}", failed);
+}
+
+#[test]
+fn invalid_names_and_keys_fail() {
+    let sdk = sdk();
+
+    let result = sdk.get_record("-x.com", "nostr");
+    assert!(matches!(result.entry(), RecordEntry::Error(SelfieError::InvalidName { reason: NameError::InvalidLabel { .. }, .. })), "{:?}", result);
+    let result = sdk.get_record("example.com", "no_such key");
+    assert!(matches!(result.entry(), RecordEntry::Error(SelfieError::InvalidKey { .. })), "{:?}", result);
+}
+
+#[test]
+fn get_record_agrees_with_get_records() {
+    let sdk = sdk();
+
+    for (name, key) in [("alice@example.com", "bitcoin-payment"), ("example.com", "nostr"), ("example.com", "pgp"), ("example.com", "did")] {
+        let records = sdk.get_records(name, Some(vec![key]), None).to_map();
+        let result = sdk.get_record(name, key);
+        assert_eq!(result.value, records[key]["value"], "{} {}", name, key);
+        assert_eq!(result.error.map(|e| e.to_string()), records[key]["error"], "{} {}", name, key);
+    }
+}
+
+#[tokio::test]
+async fn the_async_twin_agrees() {
+    let sdk = sdk();
+
+    assert_eq!(sdk.get_record_async("example.com", "nostr").await.entry(), RecordEntry::Found { value: "npub1example" });
+    assert_eq!(sdk.get_record_async("example.com", "pgp").await.entry(), RecordEntry::NotFound);
+}
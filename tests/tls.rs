@@ -0,0 +1,189 @@
+#![cfg(feature = "tls")]
+
+mod common;
+
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use common::server::DOH_VALUE;
+use common::tls::{doh_http2_server, doh_tls_server, dot_pipelining_server, dot_server, tls_config};
+use selfie_records_sdk::doh::{DohResolver, PoolStats};
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{DirectResolver, KeyResult, LookupOptions, SelfieError, SelfieRecordsSDK, TlsTransport, TxtResolver};
+
+fn dot_transport(port: u16, tls_name: &str) -> Arc<TlsTransport> {
+    let server = format!("tls://127.0.0.1:{}@{}", port, tls_name).parse().unwrap();
+    Arc::new(TlsTransport::new(server).tls_config(tls_config()))
+}
+
+fn lookup(sdk: &SelfieRecordsSDK, dns_server: Option<&str>) -> KeyResult {
+    let response = sdk.get_records_response("example.com", Some(vec!["bitcoin-payment"]), dns_server, &LookupOptions::new().attempts(1));
+    response.get("bitcoin-payment").unwrap().clone()
+}
+
+#[test]
+fn https_endpoints_are_queried_over_one_tls_connection() {
+    let (url, handshakes) = doh_tls_server();
+    let resolver = Arc::new(DohResolver::new(&url).unwrap().tls_config(tls_config()));
+    let sdk = SelfieRecordsSDK::with_resolver(resolver.clone());
+
+    for _ in 0..3 {
+        sdk.clear_cache();
+        assert_eq!(lookup(&sdk, None).value.as_deref(), Some(DOH_VALUE));
+    }
+
+    assert_eq!(handshakes.load(Ordering::SeqCst), 1);
+    assert_eq!(resolver.pool_stats(), PoolStats { opened: 1, reused: 2, retried: 0, idle: 1 });
+}
+
+#[tokio::test]
+async fn http2_endpoints_carry_the_queries_at_once_on_one_connection() {
+    // Holds the responses until all four queries are in, which one
+    // HTTP/1.1 connection could never have.
+    let (url, handshakes) = doh_http2_server(4);
+    let resolver = Arc::new(DohResolver::new(&url).unwrap().tls_config(tls_config()).max_connections(1));
+    let sdk = SelfieRecordsSDK::with_resolver(resolver.clone());
+
+    let keys = vec!["bitcoin-payment", "nostr", "pgp", "lightning"];
+    let response = sdk.get_records_response_async("example.com", Some(keys.clone()), None, &LookupOptions::new().attempts(1)).await;
+    for key in keys {
+        assert_eq!(response.get(key).unwrap().value.as_deref(), Some(DOH_VALUE), "{}: {:?}", key, response.get(key));
+    }
+    sdk.clear_cache();
+    let response = sdk.get_records_response_async("example.com", Some(vec!["nostr"]), None, &LookupOptions::new().attempts(1)).await;
+    assert_eq!(response.get("nostr").unwrap().value.as_deref(), Some(DOH_VALUE));
+
+    assert_eq!(handshakes.load(Ordering::SeqCst), 1);
+    assert_eq!(resolver.pool_stats(), PoolStats { opened: 1, reused: 4, retried: 0, idle: 1 });
+}
+
+#[test]
+fn warming_up_does_the_handshake_before_the_first_lookup() {
+    let (url, handshakes) = doh_tls_server();
+    let resolver = Arc::new(DohResolver::new(&url).unwrap().tls_config(tls_config()));
+    let sdk = SelfieRecordsSDK::with_resolver(resolver.clone());
+
+    let ready = sdk.warm_up();
+    assert!(ready[0].connected && ready[0].is_ready(), "{:?}", ready[0]);
+    assert_eq!(resolver.pool_stats().opened, 1);
+
+    assert_eq!(lookup(&sdk, None).value.as_deref(), Some(DOH_VALUE));
+    assert_eq!(handshakes.load(Ordering::SeqCst), 1);
+    assert_eq!(resolver.pool_stats().reused, 1);
+}
+
+#[test]
+fn untrusted_certificates_are_rejected() {
+    let (url, _) = doh_tls_server();
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DohResolver::new(&url).unwrap()));
+
+    let error = lookup(&sdk, None).error.unwrap();
+
+    assert_eq!(error.code(), "E_TLS_CERTIFICATE");
+    assert!(matches!(&error, SelfieError::TlsCertificate { server, reason } if *server == url && reason.contains("UnknownIssuer")), "{:?}", error);
+    let ready = sdk.warm_up();
+    assert!(matches!(ready[0].error, Some(SelfieError::TlsCertificate { .. })), "{:?}", ready[0]);
+}
+
+#[test]
+fn a_call_can_name_an_https_endpoint() {
+    let (url, handshakes) = doh_tls_server();
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new()));
+
+    // The call's endpoint is checked against the public roots, which did
+    // not issue the test certificate.
+    let error = lookup(&sdk, Some(&url)).error.unwrap();
+    assert!(matches!(error, SelfieError::TlsCertificate { .. }), "{:?}", error);
+    assert_eq!(handshakes.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn presets_name_the_public_endpoints() {
+    assert_eq!(DohResolver::cloudflare().describe(), "https://cloudflare-dns.com/dns-query");
+    assert_eq!(DohResolver::google().describe(), "https://dns.google/dns-query");
+    assert_eq!(DohResolver::quad9().describe(), "https://dns.quad9.net/dns-query");
+}
+
+#[test]
+fn dot_servers_are_queried_over_one_tls_connection() {
+    let (addr, handshakes) = dot_server("bitcoin:bc1qdot");
+    let transport = dot_transport(addr.port(), "localhost");
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DirectResolver::with_transport(transport.clone())));
+
+    for _ in 0..3 {
+        sdk.clear_cache();
+        let result = lookup(&sdk, None);
+        assert_eq!(result.value.as_deref(), Some("bitcoin:bc1qdot"));
+        assert_eq!(result.wire.unwrap().transport_used.to_string(), "tls");
+    }
+
+    assert_eq!(handshakes.load(Ordering::SeqCst), 1);
+    assert_eq!(transport.pool_stats(), PoolStats { opened: 1, reused: 2, retried: 0, idle: 1 });
+}
+
+#[tokio::test]
+async fn dot_exchanges_are_pipelined_on_one_connection() {
+    // Answers the first four queries only once all are in, last first.
+    let (addr, handshakes) = dot_pipelining_server(4, "bitcoin:bc1qdot");
+    let transport = Arc::new(TlsTransport::new(format!("tls://127.0.0.1:{}@localhost", addr.port()).parse().unwrap()).tls_config(tls_config()).max_connections(1));
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DirectResolver::with_transport(transport.clone())));
+
+    let keys = vec!["bitcoin-payment", "nostr", "pgp", "lightning"];
+    let response = sdk.get_records_response_async("example.com", Some(keys.clone()), None, &LookupOptions::new().attempts(1)).await;
+    for key in keys {
+        assert_eq!(response.get(key).unwrap().value.as_deref(), Some("bitcoin:bc1qdot"), "{}: {:?}", key, response.get(key));
+    }
+
+    assert_eq!(handshakes.load(Ordering::SeqCst), 1);
+    assert_eq!(transport.pool_stats(), PoolStats { opened: 1, reused: 3, retried: 0, idle: 1 });
+}
+
+#[test]
+fn warming_up_a_dot_server_does_the_handshake_before_the_first_lookup() {
+    let (addr, handshakes) = dot_server("bitcoin:bc1qdot");
+    let transport = dot_transport(addr.port(), "localhost");
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DirectResolver::with_transport(transport.clone())));
+
+    let ready = sdk.warm_up();
+    assert!(ready[0].connected && ready[0].is_ready(), "{:?}", ready[0]);
+    assert_eq!(transport.pool_stats().opened, 1);
+
+    assert_eq!(lookup(&sdk, None).value.as_deref(), Some("bitcoin:bc1qdot"));
+    assert_eq!(handshakes.load(Ordering::SeqCst), 1);
+    assert_eq!(transport.pool_stats().reused, 1);
+}
+
+#[test]
+fn dot_certificates_must_match_the_tls_dns_name() {
+    let (addr, _) = dot_server("bitcoin:bc1qdot");
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DirectResolver::with_transport(dot_transport(addr.port(), "wrong.example"))));
+
+    let error = lookup(&sdk, None).error.unwrap();
+
+    assert_eq!(error.code(), "E_TLS_CERTIFICATE");
+    let expected = format!("tls://127.0.0.1:{}@wrong.example", addr.port());
+    assert!(matches!(&error, SelfieError::TlsCertificate { server, reason } if *server == expected && reason.contains("NotValidForName")), "{:?}", error);
+    let ready = sdk.warm_up();
+    assert!(matches!(ready[0].error, Some(SelfieError::TlsCertificate { .. })), "{:?}", ready[0]);
+}
+
+#[test]
+fn a_call_can_name_a_dot_server() {
+    let (addr, handshakes) = dot_server("bitcoin:bc1qdot");
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new()));
+
+    // Checked against the public roots, like a call's DoH endpoint.
+    let error = lookup(&sdk, Some(&format!("tls://127.0.0.1:{}@localhost", addr.port()))).error.unwrap();
+    assert!(matches!(error, SelfieError::TlsCertificate { .. }), "{:?}", error);
+    assert_eq!(handshakes.load(Ordering::SeqCst), 0);
+}
+
+/// Run with `cargo test --features tls -- --ignored`.
+#[test]
+#[ignore = "queries cloudflare-dns.com"]
+fn public_endpoints_answer_over_https() {
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DohResolver::cloudflare()));
+    let response = sdk.get_records_response("example.com", Some(vec!["bitcoin-payment"]), None, &LookupOptions::new());
+    let result = response.get("bitcoin-payment").unwrap();
+    assert!(matches!(result.error, None | Some(SelfieError::NoRecords | SelfieError::NxDomain)), "{:?}", result);
+}
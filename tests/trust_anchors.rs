@@ -0,0 +1,314 @@
+#![cfg(feature = "dnssec")]
+
+mod common;
+
+use std::sync::Arc;
+
+use common::{cname_record, ns_record, signed_delegation, signed_keys, txt_record, ZoneKey};
+use data_encoding::{BASE64, HEXUPPER};
+use selfie_records_sdk::dnssec::{load_trust_anchors, parse_trust_anchors, BogusReason, TrustAnchor, Verdict};
+use selfie_records_sdk::testing::{MockRecordSource, MockTxtResolver};
+use selfie_records_sdk::{BuildError, LookupOptions, SelfieError, SelfieRecordsSDK};
+use trust_dns_proto::rr::dnssec::rdata::{DNSSECRData, DS};
+use trust_dns_proto::rr::dnssec::{Algorithm, DigestType};
+use trust_dns_proto::rr::RData;
+
+const QNAME: &str = "_bitcoin-payment.example.com.";
+const VALUE: &str = "bitcoin:bc1qexample";
+
+/// A private root delegating to `com.` and `example.com.`, with one signed
+/// and one unsigned TXT record.
+fn private_chain(root: &ZoneKey) -> MockRecordSource {
+    let com = ZoneKey::new("com.", 2);
+    let example = ZoneKey::new("example.com.", 3);
+    let txt = vec![txt_record(QNAME, VALUE)];
+    let txt_sig = example.sign(&txt);
+    MockRecordSource::new().with_records(
+        [
+            signed_keys(root),
+            signed_delegation(root, &com),
+            signed_keys(&com),
+            signed_delegation(&com, &example),
+            signed_keys(&example),
+            txt,
+            vec![txt_sig],
+            vec![txt_record("_nostr.example.com.", "npub1unsigned")],
+        ]
+        .concat(),
+    )
+}
+
+fn root_ds(root: &ZoneKey) -> DS {
+    match root.ds_record().rdata() {
+        RData::DNSSEC(DNSSECRData::DS(ds)) => ds.clone(),
+        _ => unreachable!(),
+    }
+}
+
+fn sdk_for(root: &ZoneKey, anchors: Vec<TrustAnchor>) -> SelfieRecordsSDK {
+    SelfieRecordsSDK::builder()
+        .resolver(Arc::new(MockTxtResolver::new()))
+        .dnssec_source(Arc::new(private_chain(root)))
+        .trust_anchors(anchors)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn test_parse_zone_file_anchors() {
+    let root = ZoneKey::new(".", 1);
+    let key = BASE64.encode(root.dnskey.public_key());
+    let ds = root_ds(&root);
+    let text = format!(
+        "; private root keys\n$TTL 3600\n. 172800 IN DNSKEY 257 3 15 {}\n. IN DS {} 15 2 (\n    {} )\n",
+        key,
+        root.key_tag(),
+        HEXUPPER.encode(ds.digest())
+    );
+
+    let anchors = parse_trust_anchors(&text).unwrap();
+    assert_eq!(anchors, vec![TrustAnchor::from_dnskey(root.dnskey.clone()), TrustAnchor::from_ds(ds)]);
+}
+
+#[test]
+fn test_parse_named_conf_anchors() {
+    let root = ZoneKey::new(".", 1);
+    let key = BASE64.encode(root.dnskey.public_key());
+    let (first, second) = key.split_at(20);
+    let ds = root_ds(&root);
+    let text = format!(
+        "/* private root */\ntrust-anchors {{\n  // current key\n  \".\" initial-key 257 3 15 \"{} {}\";\n  \".\" static-ds {} 15 2 \"{}\";\n}};\n",
+        first,
+        second,
+        root.key_tag(),
+        HEXUPPER.encode(ds.digest())
+    );
+    let anchors = parse_trust_anchors(&text).unwrap();
+    assert_eq!(anchors, vec![TrustAnchor::from_dnskey(root.dnskey.clone()), TrustAnchor::from_ds(ds)]);
+
+    let text = format!("trusted-keys {{ \".\" 257 3 15 \"{}\"; }};", key);
+    assert_eq!(parse_trust_anchors(&text).unwrap(), vec![TrustAnchor::from_dnskey(root.dnskey.clone())]);
+}
+
+#[test]
+fn test_iana_root_matches_published_ds() {
+    let published = ". IN DS 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D\n\
+                     . IN DS 38696 8 2 683D2D0ACB8C9B712A1948B27F741219298D0A450D612C483AF444A4C0FB2B16";
+    assert_eq!(parse_trust_anchors(published).unwrap(), TrustAnchor::iana_root());
+    assert!(matches!(&TrustAnchor::iana_root()[0], TrustAnchor::Ds(ds)
+        if ds.algorithm() == Algorithm::RSASHA256 && ds.digest_type() == DigestType::SHA256));
+}
+
+#[test]
+fn test_invalid_anchor_files() {
+    for text in [
+        "",
+        "; only comments",
+        "example.com. IN DNSKEY 257 3 15 AAAA",
+        ". IN DNSKEY 257 2 15 AAAA",
+        ". IN DNSKEY 257 3 15 !!!",
+        ". IN DS 20326 8 2 XYZ",
+        ". IN A 192.0.2.1",
+        "trust-anchors { \".\" initial-key 257 3; };",
+        "trust-anchors { \".\" initial-key 257 3 15 \"AAAA\";",
+    ] {
+        assert!(parse_trust_anchors(text).is_err(), "{:?}", text);
+    }
+    assert!(load_trust_anchors("/nonexistent/root.key").is_err());
+}
+
+#[test]
+fn test_private_root_validates_with_custom_anchor() {
+    let root = ZoneKey::new(".", 1);
+
+    let trace = sdk_for(&root, vec![TrustAnchor::from_dnskey(root.dnskey.clone())]).dnssec_trace(QNAME).unwrap();
+    assert_eq!(trace.verdict(), &Verdict::Secure);
+
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(Arc::new(MockTxtResolver::new()))
+        .dnssec_source(Arc::new(private_chain(&root)))
+        .build()
+        .unwrap();
+    let trace = sdk.dnssec_trace(QNAME).unwrap();
+    assert_eq!(trace.verdict(), &Verdict::Bogus(BogusReason::NoTrustedKey));
+}
+
+#[test]
+fn test_anchor_file_during_rollover() {
+    let old_root = ZoneKey::new(".", 9);
+    let new_root = ZoneKey::new(".", 1);
+    let path = std::env::temp_dir().join(format!("selfie-anchors-{}.key", std::process::id()));
+    let text: String = [&old_root, &new_root]
+        .iter()
+        .map(|key| format!(". IN DS {} 15 2 {}\n", key.key_tag(), HEXUPPER.encode(root_ds(key).digest())))
+        .collect();
+    std::fs::write(&path, text).unwrap();
+    let anchors = load_trust_anchors(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(anchors.len(), 2);
+
+    for root in [&old_root, &new_root] {
+        let trace = sdk_for(root, anchors.clone()).dnssec_trace(QNAME).unwrap();
+        assert_eq!(trace.verdict(), &Verdict::Secure);
+    }
+}
+
+#[test]
+fn test_require_dnssec_needs_anchors() {
+    let result = SelfieRecordsSDK::builder().require_dnssec(true).trust_anchors(Vec::new()).build();
+    assert_eq!(result.unwrap_err(), BuildError::NoTrustAnchors);
+
+    assert!(SelfieRecordsSDK::builder().trust_anchors(Vec::new()).build().is_ok());
+    assert!(SelfieRecordsSDK::builder().require_dnssec(true).build().is_ok());
+}
+
+#[test]
+fn test_require_dnssec_uses_custom_anchors_for_lookups() {
+    let root = ZoneKey::new(".", 1);
+    let mock = Arc::new(
+        MockTxtResolver::new()
+            .with_record("_bitcoin-payment.example.com", &[VALUE])
+            .with_record("_nostr.example.com", &["npub1unsigned"]),
+    );
+    let build = |anchor: &ZoneKey| {
+        SelfieRecordsSDK::builder()
+            .resolver(mock.clone())
+            .dnssec_source(Arc::new(private_chain(&root)))
+            .trust_anchor(TrustAnchor::from_dnskey(anchor.dnskey.clone()))
+            .require_dnssec(true)
+            .build()
+            .unwrap()
+    };
+
+    let records = build(&root).get_records("example.com", Some(vec!["bitcoin-payment", "nostr"]), None).to_map();
+    assert_eq!(records["bitcoin-payment"]["value"].as_deref(), Some(VALUE));
+    assert_eq!(records["bitcoin-payment"]["authenticated"].as_deref(), Some("true"));
+    assert_eq!(records["nostr"]["value"], None);
+    assert!(records["nostr"]["error"].as_deref().unwrap().starts_with("DNSSEC validation failed"));
+
+    let records = build(&ZoneKey::new(".", 7)).get_records("example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+    assert_eq!(
+        records["bitcoin-payment"]["error"].as_deref(),
+        Some("DNSSEC validation failed: bogus (no DNSKEY matches a trust anchor)")
+    );
+}
+
+
+#[test]
+fn test_require_dnssec_tells_unsigned_zones_from_forged_records() {
+    let root = ZoneKey::new(".", 1);
+    let com = ZoneKey::new("com.", 2);
+    let example = ZoneKey::new("example.com.", 3);
+    let cname = vec![cname_record(QNAME, "pay.example.com.")];
+    let cname_sig = example.sign(&cname);
+    let source = MockRecordSource::new().with_records(
+        [
+            signed_keys(&root),
+            signed_delegation(&root, &com),
+            signed_keys(&com),
+            signed_delegation(&com, &example),
+            signed_keys(&example),
+            // A signed alias to a record nothing signed.
+            cname,
+            vec![cname_sig],
+            vec![txt_record("pay.example.com.", "bitcoin:bc1qattacker")],
+            // A zone delegated without a DS record.
+            vec![ns_record("unsigned.com.", "ns1.example.net.")],
+            vec![txt_record("_bitcoin-payment.unsigned.com.", VALUE)],
+        ]
+        .concat(),
+    );
+    let mock = Arc::new(
+        MockTxtResolver::new()
+            .with_record("_bitcoin-payment.example.com", &["bitcoin:bc1qattacker"])
+            .with_record("_bitcoin-payment.unsigned.com", &[VALUE]),
+    );
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(mock)
+        .dnssec_source(Arc::new(source))
+        .trust_anchor(TrustAnchor::from_dnskey(root.dnskey.clone()))
+        .require_dnssec(true)
+        .build()
+        .unwrap();
+    let lookup = |name: &str| {
+        let response = sdk.get_records_response(name, Some(vec!["bitcoin-payment"]), None, &LookupOptions::new());
+        response.get("bitcoin-payment").unwrap().clone()
+    };
+
+    let forged = lookup("example.com");
+    assert_eq!((forged.value, forged.authenticated), (None, false));
+    assert_eq!(forged.error, Some(SelfieError::Dnssec("bogus (TXT is not signed)".to_string())));
+    assert_eq!(forged.error.unwrap().code(), "E_DNSSEC_BOGUS");
+
+    let unsigned = lookup("unsigned.com");
+    assert_eq!((unsigned.value, unsigned.authenticated), (None, false));
+    assert_eq!(unsigned.error, Some(SelfieError::DnssecInsecure("insecure (no DS for unsigned.com. at its parent)".to_string())));
+    assert_eq!(unsigned.error.unwrap().code(), "E_DNSSEC_INSECURE");
+}
+
+#[test]
+fn test_report_dnssec_flags_values_instead_of_failing_them() {
+    let root = ZoneKey::new(".", 1);
+    let mock = Arc::new(
+        MockTxtResolver::new()
+            .with_record("_bitcoin-payment.example.com", &[VALUE])
+            .with_record("_nostr.example.com", &["npub1unsigned"]),
+    );
+    let build = |anchor: &ZoneKey| {
+        SelfieRecordsSDK::builder()
+            .resolver(mock.clone())
+            .dnssec_source(Arc::new(private_chain(&root)))
+            .trust_anchor(TrustAnchor::from_dnskey(anchor.dnskey.clone()))
+            .report_dnssec(true)
+            .build()
+            .unwrap()
+    };
+    let lookup = |sdk: &SelfieRecordsSDK, key: &str| {
+        let response = sdk.get_records_response("example.com", Some(vec![key]), None, &LookupOptions::new());
+        let result = response.get(key).unwrap().clone();
+        (result.value, result.error, result.authenticated)
+    };
+
+    let sdk = build(&root);
+    assert_eq!(lookup(&sdk, "bitcoin-payment"), (Some(VALUE.to_string()), None, true));
+    // The signature over `_nostr` is missing, so it is not authenticated.
+    assert_eq!(lookup(&sdk, "nostr"), (Some("npub1unsigned".to_string()), None, false));
+    // Nor is anything under a root that is not trusted.
+    assert_eq!(lookup(&build(&ZoneKey::new(".", 7)), "bitcoin-payment"), (Some(VALUE.to_string()), None, false));
+    // Without validation nothing is.
+    let unvalidated = SelfieRecordsSDK::with_resolver(mock.clone());
+    assert_eq!(lookup(&unvalidated, "bitcoin-payment"), (Some(VALUE.to_string()), None, false));
+}
+
+#[test]
+fn test_dnssec_checks_the_records_resolved_against_the_signed_ones() {
+    let root = ZoneKey::new(".", 1);
+    // A resolver answering with something other than what the zone signed.
+    let mock = Arc::new(MockTxtResolver::new().with_record("_bitcoin-payment.example.com", &["bitcoin:bc1qattacker"]));
+    let build = |required: bool| {
+        SelfieRecordsSDK::builder()
+            .resolver(mock.clone())
+            .dnssec_source(Arc::new(private_chain(&root)))
+            .trust_anchor(TrustAnchor::from_dnskey(root.dnskey.clone()))
+            .require_dnssec(required)
+            .report_dnssec(!required)
+            .build()
+            .unwrap()
+    };
+    let lookup = |sdk: &SelfieRecordsSDK| {
+        let response = sdk.get_records_response("example.com", Some(vec!["bitcoin-payment"]), None, &LookupOptions::new());
+        let result = response.get("bitcoin-payment").unwrap().clone();
+        (result.value, result.error, result.authenticated)
+    };
+
+    let (value, error, authenticated) = lookup(&build(true));
+    assert_eq!((value, authenticated), (None, false));
+    assert_eq!(error.unwrap().code(), "E_DNSSEC_BOGUS");
+    assert_eq!(lookup(&build(false)), (Some("bitcoin:bc1qattacker".to_string()), None, false));
+}
+
+#[test]
+fn test_report_dnssec_needs_anchors() {
+    let result = SelfieRecordsSDK::builder().report_dnssec(true).trust_anchors(Vec::new()).build();
+    assert_eq!(result.unwrap_err(), BuildError::NoTrustAnchors);
+}
@@ -0,0 +1,167 @@
+use std::sync::Arc;
+
+use icu_properties::props::ExtendedPictographic;
+use icu_properties::CodePointSetData;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{
+    get_txt_record_key, validate_name, validate_name_with, LocalPartPolicy, LookupOptions, NameError, NameKind, SelfieError, SelfieRecordsSDK,
+};
+
+const CORPUS: &str = include_str!("fixtures/names/corpus.txt");
+
+enum Expected<'a> {
+    /// The name as it is queried, `local@domain` for addresses.
+    Valid(&'a str),
+    Invalid(&'a str),
+}
+
+fn corpus() -> Vec<(&'static str, Expected<'static>)> {
+    let cases = CORPUS.lines().filter(|line| !line.starts_with('#'));
+    cases
+        .map(|line| {
+            let (input, expected) = line.split_once("=> ").unwrap_or_else(|| panic!("bad corpus line {:?}", line));
+            let expected = match expected.split_once(' ').unwrap() {
+                ("domain" | "email", name) => Expected::Valid(name),
+                ("error", reason) => Expected::Invalid(reason),
+                (kind, _) => panic!("unknown kind {:?}", kind),
+            };
+            (input.trim_end(), expected)
+        })
+        .collect()
+}
+
+#[test]
+fn validate_name_follows_the_corpus() {
+    for (input, expected) in corpus() {
+        let found = validate_name(input).map(|kind| match kind {
+            NameKind::Domain(domain) => domain.to_string(),
+            NameKind::Email { local, domain } => format!("{}@{}", local, domain),
+        });
+        match expected {
+            Expected::Valid(name) => assert_eq!(found.as_deref(), Ok(name), "{}", input),
+            Expected::Invalid(reason) => assert_eq!(found.map_err(|e| e.to_string()), Err(reason.to_string()), "{}", input),
+        }
+    }
+}
+
+#[test]
+fn lookups_follow_the_corpus() {
+    let cases = corpus();
+    let mut mock = MockTxtResolver::new();
+    for (_, expected) in &cases {
+        if let Expected::Valid(name) = expected {
+            mock = mock.with_record(&get_txt_record_key(name, "pgp").unwrap(), &[name]);
+        }
+    }
+    let mock = Arc::new(mock);
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+
+    for (input, expected) in cases {
+        let response = sdk.get_records_response(input, Some(vec!["pgp"]), None, &LookupOptions::new().attempts(1));
+        let result = response.get("pgp").unwrap();
+        match expected {
+            Expected::Valid(name) => assert_eq!(result.value.as_deref(), Some(name), "{}: {:?}", input, result.error),
+            Expected::Invalid(reason) => match &result.error {
+                Some(SelfieError::InvalidName { reason: found, .. }) => assert_eq!(found.to_string(), reason, "{}", input),
+                other => panic!("{}: {:?}", input, other),
+            },
+        }
+    }
+}
+
+#[test]
+fn unicode_domains_are_queried_in_their_ascii_form() {
+    let domain = match validate_name("anna@Bücher.example").unwrap() {
+        NameKind::Email { domain, .. } => domain,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!((domain.as_str(), domain.to_unicode().as_str()), ("xn--bcher-kva.example", "bücher.example"));
+    let domain = match validate_name("例え.jp").unwrap() {
+        NameKind::Domain(domain) => domain,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!((domain.as_str(), domain.to_unicode().as_str()), ("xn--r8jz45g.jp", "例え.jp"));
+
+    assert_eq!(get_txt_record_key("anna@bücher.example", "nostr").unwrap(), "anna.user._nostr.xn--bcher-kva.example");
+    assert_eq!(get_txt_record_key("例え.jp", "nostr").unwrap(), "_nostr.xn--r8jz45g.jp");
+    // Deviation characters are kept as IDNA2008 has them, not mapped to
+    // what transitional processing makes of them.
+    assert_eq!(get_txt_record_key("straße.de", "nostr").unwrap(), "_nostr.xn--strae-oqa.de");
+    assert_eq!(get_txt_record_key("ς.gr", "nostr").unwrap(), "_nostr.xn--3xa.gr");
+}
+
+#[test]
+fn rejected_names_are_reported_as_typed() {
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new()));
+
+    let response = sdk.get_records_response("💩.la", Some(vec!["nostr"]), None, &LookupOptions::new());
+
+    let error = response.get("nostr").unwrap().error.clone().unwrap();
+    assert!(matches!(&error, SelfieError::InvalidName { name, .. } if name == "💩.la"), "{:?}", error);
+    assert!(error.to_string().contains("💩"), "{}", error);
+}
+
+#[test]
+fn local_parts_are_normalized_by_the_policy() {
+    let keep_case = LocalPartPolicy::new().lowercase(false);
+    let strip = LocalPartPolicy::new().strip_plus_suffix(true);
+    let both = LocalPartPolicy::new().lowercase(false).strip_plus_suffix(true);
+    for (input, policy, queried) in [
+        ("Alice@Example.COM", LocalPartPolicy::default(), "alice.user._nostr.example.com"),
+        ("Alice+Tag@example.com", LocalPartPolicy::default(), "alice+tag.user._nostr.example.com"),
+        ("Alice@Example.COM", keep_case, "Alice.user._nostr.example.com"),
+        ("Alice+Tag@example.com", strip, "alice.user._nostr.example.com"),
+        ("Alice+Tag@example.com", both, "Alice.user._nostr.example.com"),
+        ("alice+a+b@example.com", strip, "alice.user._nostr.example.com"),
+        ("+tag@example.com", strip, "+tag.user._nostr.example.com"),
+        ("ÉLODIE@example.com", LocalPartPolicy::default(), "élodie.user._nostr.example.com"),
+    ] {
+        let mock = MockTxtResolver::new().with_record(queried, &["npub1"]);
+        let sdk = SelfieRecordsSDK::builder().resolver(Arc::new(mock)).local_part_policy(policy).build().unwrap();
+        let response = sdk.get_records_response(input, Some(vec!["nostr"]), None, &LookupOptions::new().attempts(1));
+        let result = response.get("nostr").unwrap();
+        assert_eq!(result.value.as_deref(), Some("npub1"), "{} with {:?}: {:?}", input, policy, result.error);
+        assert!(matches!(validate_name_with(input, &policy), Ok(NameKind::Email { .. })), "{}", input);
+    }
+
+    assert_eq!(get_txt_record_key("Alice+Tag@Example.COM", "nostr").unwrap(), "alice+tag.user._nostr.example.com");
+    assert_eq!(get_txt_record_key("Example.COM", "nostr").unwrap(), "_nostr.example.com");
+}
+
+#[test]
+fn local_parts_that_cannot_be_queried_are_rejected() {
+    for (input, position) in [("al ice@example.com", 2), ("\"al\\ice\"@example.com", 0), ("ali\u{7}ce@example.com", 3)] {
+        assert_eq!(validate_name(input), Err(NameError::InvalidLocalPart { position }), "{:?}", input);
+    }
+    assert_eq!(validate_name("alice@bob@example.com"), Err(NameError::MultipleAt));
+    assert!(matches!(get_txt_record_key("a@b@c.d", "nostr"), Err(SelfieError::InvalidName { reason: NameError::MultipleAt, .. })));
+    assert!(matches!(get_txt_record_key("al ice@example.com", "nostr"), Err(SelfieError::InvalidName { .. })));
+    assert_eq!(get_txt_record_key("example.com", "my key"), Err(SelfieError::InvalidKey { key: "my key".to_string() }));
+}
+
+#[test]
+fn pictographic_labels_end_where_extended_pictographic_does() {
+    // Each range's first and last character, and its neighbours outside.
+    let rejected = ['\u{a9}', '\u{2194}', '\u{2199}', '\u{2b55}', '\u{3030}', '\u{1f004}', '\u{1f0ff}', '\u{1f64f}', '\u{1f947}', '\u{1faff}'];
+    let accepted = ['\u{2193}', '\u{219a}', '\u{2b56}', '\u{3031}', '\u{1f650}', '\u{1f946}', '\u{1fb00}', '\u{2bff}'];
+    for c in rejected.into_iter().chain(['\u{fe0f}', '\u{e0020}', '\u{e007f}']) {
+        let label = format!("a{}", c);
+        assert_eq!(validate_name(&format!("{}.example", label)), Err(NameError::InvalidLabel { label }), "U+{:04X}", c as u32);
+    }
+    for c in accepted {
+        assert!(matches!(validate_name(&format!("a{}.example", c)), Ok(NameKind::Domain(_))), "U+{:04X}", c as u32);
+    }
+}
+
+/// src/name/pictographic.rs's table, generated from the Unicode data of
+/// icu_properties; a failure prints the table to paste in.
+#[test]
+fn the_pictographic_table_is_the_unicode_data() {
+    let ranges: Vec<String> = CodePointSetData::new::<ExtendedPictographic>()
+        .iter_ranges()
+        .map(|range| format!("(0x{:04X}, 0x{:04X}),", range.start(), range.end()))
+        .collect();
+    let lines: Vec<String> = ranges.chunks(4).map(|line| format!("    {}", line.join(" "))).collect();
+    let table = format!("const EXTENDED_PICTOGRAPHIC: &[(u32, u32)] = &[\n{}\n];\n", lines.join("\n"));
+    assert!(include_str!("../src/name/pictographic.rs").contains(&table), "src/name/pictographic.rs is out of date:\n{}", table);
+}
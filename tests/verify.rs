@@ -0,0 +1,291 @@
+use std::sync::Arc;
+
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::verify::{Comparison, Manifest, Matcher, Outcome, Pattern, VerificationResult};
+use selfie_records_sdk::{LookupOptions, SelfieError, SelfieRecordsSDK};
+
+const MANIFEST: &str = r#"{
+    "alice@example.com": {
+        "bitcoin-payment": "bitcoin:bc1qalice",
+        "nostr": "prefix:npub1",
+        "pgp": "regex:^https://example\\.com/[a-z]+\\.asc$"
+    },
+    "example.org": {
+        "bitcoin-payment": "bitcoin:bc1qorg",
+        "nostr": "npub1org"
+    }
+}"#;
+
+fn mock() -> MockTxtResolver {
+    MockTxtResolver::new()
+        .with_record("alice.user._bitcoin-payment.example.com", &["bitcoin:bc1qalice"])
+        .with_record("alice.user._nostr.example.com", &["npub1alice"])
+        .with_record("alice.user._pgp.example.com", &["https://example.com/alice.asc"])
+        .with_record("_bitcoin-payment.example.org", &["bitcoin:bc1qdrifted"])
+        .with_record("_nostr.example.org", &[])
+}
+
+fn verify(manifest: &Manifest, mock: MockTxtResolver) -> Vec<(String, String, Outcome)> {
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+    let verification = manifest.verify(&sdk, &LookupOptions::new().attempts(1));
+    verification.entries.into_iter().map(|entry| (entry.name, entry.key, entry.outcome)).collect()
+}
+
+fn entry(name: &str, key: &str, outcome: Outcome) -> (String, String, Outcome) {
+    (name.to_string(), key.to_string(), outcome)
+}
+
+#[test]
+fn manifests_are_objects_of_names_keys_and_values() {
+    let manifest = Manifest::parse(MANIFEST).unwrap();
+
+    let alice = &manifest.names["alice@example.com"];
+    assert_eq!(alice["bitcoin-payment"], Matcher::Exact("bitcoin:bc1qalice".to_string()));
+    assert_eq!(alice["nostr"], Matcher::Prefix("npub1".to_string()));
+    assert!(matches!(&alice["pgp"], Matcher::Regex(_)));
+    assert_eq!(manifest.names["example.org"].len(), 2);
+    assert_eq!(Manifest::parse(&manifest.to_json().to_string()).unwrap(), manifest);
+}
+
+#[test]
+fn malformed_manifests_are_rejected() {
+    for (json, error) in [
+        ("[]", "Invalid manifest: expected an object of names"),
+        (r#"{"example.com": "bitcoin:bc1q"}"#, "Invalid manifest: example.com: expected an object of keys"),
+        (r#"{"example.com": {"nostr": 1}}"#, "Invalid manifest: example.com nostr: expected a string"),
+        (r#"{"example.com": {"nostr": "regex:(npub|nsec)"}}"#, "Invalid manifest: example.com nostr: ( is not supported"),
+    ] {
+        assert_eq!(Manifest::parse(json).unwrap_err().to_string(), error, "{}", json);
+    }
+    assert!(Manifest::parse("{").unwrap_err().to_string().starts_with("Invalid manifest: "));
+}
+
+#[test]
+fn matchers_compare_exactly_by_prefix_or_by_pattern() {
+    let matcher = |expected: &str| Matcher::parse(expected).unwrap();
+
+    assert!(matcher("npub1abc").matches("npub1abc"));
+    assert!(!matcher("npub1abc").matches("npub1abcd"));
+    assert!(matcher("prefix:npub1").matches("npub1abc"));
+    assert!(!matcher("prefix:npub1").matches("nsec1abc"));
+    assert!(matcher("regex:bc1q").matches("bitcoin:bc1qabc"));
+    assert!(!matcher("regex:^bc1q").matches("bitcoin:bc1qabc"));
+    assert_eq!(matcher("exact:prefix:npub1"), Matcher::Exact("prefix:npub1".to_string()));
+    assert!(!matcher("exact:prefix:npub1").matches("prefix:npub1abc"));
+    for expected in ["npub1abc", "prefix:npub1", "regex:^a+$", "exact:regex:a"] {
+        assert_eq!(matcher(expected).to_string(), expected);
+    }
+}
+
+#[test]
+fn patterns_support_a_subset_of_regular_expressions() {
+    let pattern = |source: &str| Pattern::new(source).unwrap();
+
+    assert!(pattern("^bitcoin:bc1q[02-9ac-hj-np-z]+$").is_match("bitcoin:bc1q0ac"));
+    assert!(!pattern("^bitcoin:bc1q[02-9ac-hj-np-z]+$").is_match("bitcoin:bc1qb"));
+    assert!(pattern(r"amount=\d+\.\d*").is_match("bitcoin:bc1q?amount=1.5"));
+    assert!(pattern(r"^\w+@\w+\.com$").is_match("alice@example.com"));
+    assert!(pattern("^colou?r$").is_match("color") && pattern("^colou?r$").is_match("colour"));
+    assert!(pattern("^a.*z$").is_match("a to z"));
+    assert!(pattern("^[^;]*$").is_match("no separators") && !pattern("^[^;]*$").is_match("a;b"));
+    assert!(pattern("").is_match("anything"));
+    assert!(pattern("^$").is_match("") && !pattern("^$").is_match("x"));
+    // Matching stays linear where backtracking would not.
+    assert!(!pattern(&format!("^{}{}$", "a?".repeat(30), "a".repeat(30))).is_match(&"a".repeat(29)));
+}
+
+#[test]
+fn unsupported_pattern_syntax_is_rejected() {
+    for (source, error) in [
+        ("(npub)", "( is not supported"),
+        ("npub|nsec", "| is not supported"),
+        ("a{3}", "{ is not supported"),
+        (r"\bword", "\\b is not supported"),
+        ("*a", "* has nothing to repeat or anchor"),
+        ("a**", "quantifiers cannot be repeated"),
+        ("a$b", "$ must end the pattern"),
+        ("[a-", "[ is not closed"),
+        ("[z-a]", "range z-a is reversed"),
+    ] {
+        assert_eq!(Pattern::new(source).unwrap_err(), error, "{}", source);
+    }
+}
+
+#[test]
+fn every_record_is_reported_as_a_match_mismatch_or_missing() {
+    let manifest = Manifest::parse(MANIFEST).unwrap();
+
+    let entries = verify(&manifest, mock());
+
+    assert_eq!(
+        entries,
+        [
+            entry("alice@example.com", "bitcoin-payment", Outcome::Match),
+            entry("alice@example.com", "nostr", Outcome::Match),
+            entry("alice@example.com", "pgp", Outcome::Match),
+            entry("example.org", "bitcoin-payment", Outcome::Mismatch { actual: "bitcoin:bc1qdrifted".to_string() }),
+            entry("example.org", "nostr", Outcome::Missing),
+        ]
+    );
+}
+
+#[test]
+fn failed_lookups_are_neither_matches_nor_drift() {
+    let manifest = Manifest::parse(r#"{"example.net": {"nostr": "npub1net"}}"#).unwrap();
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new()));
+
+    let verification = manifest.verify(&sdk, &LookupOptions::new().attempts(1));
+
+    assert!(matches!(verification.entries[0].outcome, Outcome::Failed(SelfieError::Resolver(_))));
+    assert!(!verification.passed());
+    assert!(verification.failed());
+}
+
+#[test]
+fn names_are_only_queried_for_the_keys_they_declare() {
+    let manifest = Manifest::parse(MANIFEST).unwrap();
+    let mock = Arc::new(mock());
+    let sdk = SelfieRecordsSDK::with_resolver(mock.clone());
+
+    let verification = manifest.verify(&sdk, &LookupOptions::new().attempts(1));
+
+    assert_eq!(verification.entries.len(), 5);
+    assert_eq!(mock.calls(), 5);
+}
+
+#[test]
+fn updates_write_the_observed_values_back() {
+    let mut manifest = Manifest::parse(MANIFEST).unwrap();
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock()));
+    let verification = manifest.verify(&sdk, &LookupOptions::new().attempts(1));
+    assert!(!verification.passed());
+
+    assert_eq!(manifest.update(&verification), 2);
+
+    let org = &manifest.names["example.org"];
+    assert_eq!(org.len(), 1);
+    assert_eq!(org["bitcoin-payment"], Matcher::Exact("bitcoin:bc1qdrifted".to_string()));
+    // Matchers that still match are kept as written.
+    assert_eq!(manifest.names["alice@example.com"]["nostr"], Matcher::Prefix("npub1".to_string()));
+    assert!(manifest.verify(&sdk, &LookupOptions::new().attempts(1)).passed());
+}
+
+#[test]
+fn names_left_without_records_are_dropped_on_update() {
+    let mut manifest = Manifest::parse(r#"{"example.org": {"nostr": "npub1org"}}"#).unwrap();
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock()));
+    let verification = manifest.verify(&sdk, &LookupOptions::new().attempts(1));
+
+    assert_eq!(manifest.update(&verification), 1);
+    assert!(manifest.names.is_empty());
+    assert_eq!(manifest.to_json(), serde_json::json!({}));
+}
+
+#[test]
+fn verify_record_reports_each_outcome() {
+    let mock = MockTxtResolver::new()
+        .with_record("alice.user._nostr.example.com", &["npub1alice"])
+        .with_record("_nostr.example.org", &[]);
+    let sdk = SelfieRecordsSDK::builder().resolver(Arc::new(mock)).attempts(1).build().unwrap();
+
+    assert_eq!(sdk.verify_record("alice@example.com", "nostr", "npub1alice"), VerificationResult::Match);
+    assert_eq!(
+        sdk.verify_record("alice@example.com", "nostr", "npub1bob"),
+        VerificationResult::MismatchFound { actual: vec!["npub1alice".to_string()] }
+    );
+    assert_eq!(sdk.verify_record("example.org", "nostr", "npub1org"), VerificationResult::NotFound);
+    // `_nostr.example.net` is not registered, so the lookup fails.
+    assert!(matches!(sdk.verify_record("example.net", "nostr", "npub1net"), VerificationResult::Error(SelfieError::Resolver(_))));
+}
+
+#[test]
+fn one_matching_record_among_several_is_a_match() {
+    let mock = MockTxtResolver::new().with_record("_bitcoin-payment.example.com", &["v=spf1 -all", "bitcoin:bc1qnew", "bitcoin:bc1qold"]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    assert_eq!(sdk.verify_record("example.com", "bitcoin-payment", "bitcoin:bc1qold"), VerificationResult::Match);
+    let actual = ["v=spf1 -all", "bitcoin:bc1qnew", "bitcoin:bc1qold"].map(String::from).to_vec();
+    assert_eq!(sdk.verify_record("example.com", "bitcoin-payment", "bitcoin:bc1q"), VerificationResult::MismatchFound { actual });
+}
+
+#[test]
+fn whitespace_only_counts_for_exact_comparisons() {
+    let mock = MockTxtResolver::new().with_record("alice.user._pgp.example.com", &["59E1 187D 54EA 5CF2 FCD2  919B 9B34 E613 6E5F 37D6 "]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+    let verify = |expected: &str, comparison| sdk.verify_record_with("alice@example.com", "pgp", expected, comparison);
+
+    let spaced = "59E1 187D 54EA 5CF2 FCD2  919B 9B34 E613 6E5F 37D6";
+    assert!(matches!(verify(spaced, Comparison::Exact), VerificationResult::MismatchFound { .. }));
+    assert_eq!(verify(spaced, Comparison::Trimmed), VerificationResult::Match);
+    assert!(matches!(verify("59E1187D54EA5CF2FCD2919B9B34E6136E5F37D6", Comparison::Trimmed), VerificationResult::MismatchFound { .. }));
+    assert_eq!(verify("59E1187D54EA5CF2FCD2919B9B34E6136E5F37D6", Comparison::IgnoreWhitespace), VerificationResult::Match);
+}
+
+const TOML_MANIFEST: &str = include_str!("fixtures/verify/manifest.toml");
+
+#[test]
+fn manifests_can_list_records_in_toml() {
+    let manifest = Manifest::parse_toml(TOML_MANIFEST).unwrap();
+
+    assert_eq!(manifest.names["alice@example.com"]["nostr"], Matcher::Prefix("npub1".to_string()));
+    assert_eq!(manifest.names["example.net"]["nostr"], Matcher::Exact("npub1net".to_string()));
+    assert_eq!(manifest.names.values().map(|keys| keys.len()).sum::<usize>(), 5);
+    assert_eq!(Manifest::parse_toml(&manifest.to_toml()).unwrap(), manifest);
+    let json = r#"{"records": [{"name": "example.net", "key": "nostr", "value": "npub1net"}]}"#;
+    assert_eq!(Manifest::parse(json).unwrap().names["example.net"], manifest.names["example.net"]);
+    // Values that need escaping survive the round trip.
+    let escaped = Manifest::parse(r#"{"example.com": {"nostr": "a \"quoted\"\\value\n"}}"#).unwrap();
+    assert_eq!(Manifest::parse_toml(&escaped.to_toml()).unwrap(), escaped);
+}
+
+#[test]
+fn duplicates_and_unknown_keys_are_rejected_up_front() {
+    let record = |key: &str| format!("[[records]]\nname = \"example.com\"\nkey = \"{}\"\nvalue = \"npub1\"\n", key);
+    for (toml, error) in [
+        (record("nostr") + &record("Nostr"), "Invalid manifest: example.com Nostr: listed twice"),
+        (record("no_str"), "Invalid manifest: example.com no_str: not a record key"),
+        (record("nostr") + "kye = \"pgp\"\n", "Invalid manifest: line 5: unknown field kye"),
+        (record("nostr") + "name = \"example.org\"\n", "Invalid manifest: line 5: name is set twice"),
+        ("[[records]]\nname = \"example.com\"\nkey = \"nostr\"\n".to_string(), "Invalid manifest: line 1: record without value"),
+        ("name = \"example.com\"\n".to_string(), "Invalid manifest: line 1: field outside of a [[records]] table"),
+        ("[records]\n".to_string(), "Invalid manifest: line 1: expected [[records]]"),
+        ("[[names]]\n".to_string(), "Invalid manifest: line 1: unknown table names, expected [[records]]"),
+    ] {
+        assert_eq!(Manifest::parse_toml(&toml).unwrap_err().to_string(), error, "{}", toml);
+    }
+    for (json, error) in [
+        (
+            r#"{"records": [{"name": "a.com", "key": "nostr", "value": "x"}, {"name": "a.com", "key": "nostr", "value": "y"}]}"#,
+            "Invalid manifest: a.com nostr: listed twice",
+        ),
+        (r#"{"records": [{"name": "a.com", "key": "nostr", "valeu": "x"}]}"#, "Invalid manifest: record 1: unknown field valeu"),
+        (r#"{"records": [{"name": "a.com", "key": "nostr"}]}"#, "Invalid manifest: record 1: missing value"),
+        (r#"{"a.com": {"no str": "x"}}"#, "Invalid manifest: a.com no str: not a record key"),
+    ] {
+        assert_eq!(Manifest::parse(json).unwrap_err().to_string(), error, "{}", json);
+    }
+}
+
+#[test]
+fn verify_manifest_reports_every_outcome() {
+    let manifest = Manifest::parse_toml(TOML_MANIFEST).unwrap();
+    let sdk = SelfieRecordsSDK::builder().resolver(Arc::new(mock())).attempts(1).build().unwrap();
+
+    let report = sdk.verify_manifest(&manifest);
+
+    let outcomes: Vec<_> = report.entries.iter().map(|entry| (entry.name.as_str(), entry.key.as_str(), &entry.outcome)).collect();
+    assert!(matches!(outcomes[2], ("example.net", "nostr", Outcome::Failed(SelfieError::Resolver(_)))), "{:?}", outcomes);
+    assert_eq!(outcomes[0], ("alice@example.com", "bitcoin-payment", &Outcome::Match));
+    assert_eq!(outcomes[3], ("example.org", "bitcoin-payment", &Outcome::Mismatch { actual: "bitcoin:bc1qdrifted".to_string() }));
+    assert_eq!(outcomes[4], ("example.org", "nostr", &Outcome::Missing));
+
+    let json = report.to_json();
+    assert_eq!(json["passed"], false);
+    let fields = |index: usize, field: &str| json["entries"][index][field].as_str().map(str::to_string);
+    assert_eq!(fields(0, "outcome").as_deref(), Some("match"));
+    assert_eq!(fields(2, "outcome").as_deref(), Some("error"));
+    assert_eq!(fields(2, "error_code").as_deref(), Some("E_RESOLVER"));
+    assert_eq!(fields(3, "actual").as_deref(), Some("bitcoin:bc1qdrifted"));
+    assert_eq!(fields(4, "outcome").as_deref(), Some("missing"));
+    assert_eq!(fields(4, "expected").as_deref(), Some("npub1org"));
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use common::server::{doh_keepalive_server, DOH_VALUE};
+use selfie_records_sdk::doh::{DohResolver, PoolStats};
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{LookupOptions, SelfieRecordsSDK, TxtResolver};
+
+fn lookup(sdk: &SelfieRecordsSDK, name: &str) {
+    let records = sdk.get_records_with(name, Some(vec!["bitcoin-payment"]), None, &LookupOptions::new().attempts(1)).to_map();
+    assert_eq!(records["bitcoin-payment"]["value"].as_deref(), Some(DOH_VALUE), "{:?}", records);
+}
+
+#[test]
+fn the_first_lookup_uses_the_warmed_up_connection() {
+    let (url, connections) = doh_keepalive_server(None);
+    let resolver = Arc::new(DohResolver::new(&url).unwrap());
+    let sdk = SelfieRecordsSDK::with_resolver(resolver.clone());
+
+    let ready = sdk.warm_up();
+
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].endpoint, url);
+    assert!(ready[0].connected && ready[0].is_ready(), "{:?}", ready[0]);
+    assert_eq!(resolver.pool_stats(), PoolStats { opened: 1, reused: 0, retried: 0, idle: 1 });
+
+    lookup(&sdk, "warm.example.com");
+    assert_eq!(resolver.pool_stats(), PoolStats { opened: 1, reused: 1, retried: 0, idle: 1 });
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn warming_up_again_keeps_the_open_connection() {
+    let (url, connections) = doh_keepalive_server(None);
+    let resolver = Arc::new(DohResolver::new(&url).unwrap());
+    let sdk = SelfieRecordsSDK::with_resolver(resolver.clone());
+
+    sdk.warm_up();
+    let ready = sdk.warm_up();
+
+    assert!(!ready[0].connected && ready[0].is_ready(), "{:?}", ready[0]);
+    assert_eq!(resolver.pool_stats().opened, 1);
+    lookup(&sdk, "again.example.com");
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn probes_are_sent_over_the_warmed_up_connection() {
+    let (url, connections) = doh_keepalive_server(None);
+    let resolver = Arc::new(DohResolver::new(&url).unwrap());
+    let sdk = SelfieRecordsSDK::with_resolver(resolver.clone());
+
+    let ready = sdk.warm_up_probing(Some("_bitcoin-payment.example.com"));
+
+    assert!(ready[0].is_ready(), "{:?}", ready[0]);
+    assert_eq!(resolver.pool_stats(), PoolStats { opened: 1, reused: 1, retried: 0, idle: 1 });
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn unreachable_endpoints_are_reported() {
+    let resolver = Arc::new(DohResolver::new("http://127.0.0.1:9/dns-query").unwrap());
+    let unpooled = Arc::new(DohResolver::new("http://127.0.0.1:9/unpooled").unwrap().max_connections(0));
+    let sdk = SelfieRecordsSDK::builder()
+        .resolver(resolver)
+        .route_key("nostr", unpooled as Arc<dyn TxtResolver>)
+        .build()
+        .unwrap();
+
+    let ready = sdk.warm_up();
+
+    let endpoints: Vec<&str> = ready.iter().map(|ready| ready.endpoint.as_str()).collect();
+    assert_eq!(endpoints, ["http://127.0.0.1:9/dns-query", "http://127.0.0.1:9/unpooled"]);
+    assert!(ready.iter().all(|ready| !ready.is_ready() && !ready.connected), "{:?}", ready);
+}
+
+#[test]
+fn backends_without_connections_have_nothing_to_warm_up() {
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(MockTxtResolver::new()));
+
+    assert_eq!(sdk.warm_up(), []);
+}
+
+#[test]
+fn warm_up_can_run_in_the_background() {
+    let (url, _) = doh_keepalive_server(None);
+    let resolver = Arc::new(DohResolver::new(&url).unwrap());
+    let sdk = Arc::new(SelfieRecordsSDK::with_resolver(resolver.clone()));
+
+    let ready = sdk.warm_up_in_background().join().unwrap();
+
+    assert!(ready[0].connected, "{:?}", ready[0]);
+    lookup(&sdk, "background.example.com");
+    assert_eq!(resolver.pool_stats().reused, 1);
+}
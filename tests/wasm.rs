@@ -0,0 +1,82 @@
+//! The SDK built for wasm32 with the `wasm` feature, run by
+//! `wasm-bindgen-test-runner` under Node.js with `fetch` replaced by a mock:
+//!
+//! `cargo test --target wasm32-unknown-unknown --no-default-features --features wasm --test wasm`
+#![cfg(target_arch = "wasm32")]
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use js_sys::{Array, Function, Reflect, Uint8Array};
+use selfie_records_sdk::{Bip21Uri, LookupOptions, RecordEntry, SelfieError, SelfieRecordsSDK, Source, Transport};
+use trust_dns_proto::op::{Message, MessageType};
+use trust_dns_proto::rr::rdata::TXT;
+use trust_dns_proto::rr::{RData, Record};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const PAYMENT: &str = "bitcoin:bc1qwasm?amount=0.5";
+
+/// The answer to `query`: a TXT record for the bitcoin-payment name, no
+/// records for any other.
+fn answer(query: &[u8]) -> Vec<u8> {
+    let mut response = Message::from_vec(query).unwrap();
+    response.set_message_type(MessageType::Response);
+    let name = response.queries()[0].name().clone();
+    if name.to_ascii() == "_bitcoin-payment.example.com." {
+        response.add_answer(Record::from_rdata(name, 300, RData::TXT(TXT::new(vec![PAYMENT.to_string()]))));
+    }
+    response.to_vec().unwrap()
+}
+
+/// Replaces the global `fetch` with `body`, the body of an async function of
+/// `request` that may call `answer` and push to `requests`, returning the
+/// array the requests are pushed to.
+fn mock_fetch(body: &str) -> Array {
+    let requests = Array::new();
+    let answer = Closure::<dyn Fn(Uint8Array) -> Uint8Array>::new(|query: Uint8Array| Uint8Array::from(answer(&query.to_vec()).as_slice()));
+    let mock = Function::new_with_args("answer, requests", &format!("return async (request) => {{ {} }};", body));
+    let fetch = mock.call2(&JsValue::NULL, answer.as_ref(), &requests).unwrap();
+    answer.forget();
+    Reflect::set(&js_sys::global(), &"fetch".into(), &fetch).unwrap();
+    requests
+}
+
+#[wasm_bindgen_test]
+async fn lookups_are_posted_to_cloudflare_through_fetch() {
+    let requests = mock_fetch(
+        "requests.push(`${request.method} ${request.url} ${request.headers.get('content-type')}`);
+         const query = new Uint8Array(await request.arrayBuffer());
+         return new Response(answer(query), { status: 200, headers: { 'Content-Type': 'application/dns-message' } });",
+    );
+    let sdk = SelfieRecordsSDK::new();
+
+    let records = sdk.get_records_response_async("example.com", Some(vec!["bitcoin-payment", "nostr"]), None, &LookupOptions::new()).await;
+
+    let payment = records.get("bitcoin-payment").unwrap();
+    assert_eq!(payment.entry(), RecordEntry::Found { value: PAYMENT }, "{:?}", payment);
+    let uri: Bip21Uri = payment.value.as_deref().unwrap().parse().unwrap();
+    assert_eq!(uri.address, "bc1qwasm");
+    assert_eq!(uri.param("amount"), Some("0.5"));
+    assert_eq!(payment.source, Some(Source::Dns));
+    assert_eq!(payment.wire.as_ref().map(|wire| wire.transport_used), Some(Transport::Doh));
+    assert_eq!(payment.ttl, Some(Duration::from_secs(300)));
+    // From the JavaScript clock: after 2023.
+    assert!(payment.resolved_at.unwrap() > UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    assert_eq!(records.get("nostr").unwrap().entry(), RecordEntry::NotFound);
+
+    let requests: Vec<String> = requests.iter().map(|request| request.as_string().unwrap()).collect();
+    assert_eq!(requests, vec!["POST https://cloudflare-dns.com/dns-query application/dns-message"; 2]);
+}
+
+#[wasm_bindgen_test]
+async fn unanswered_fetches_time_out_on_the_javascript_timer() {
+    mock_fetch("return new Promise(() => {});");
+    let sdk = SelfieRecordsSDK::new();
+
+    let options = LookupOptions::new().timeout(Duration::from_millis(50)).attempts(1);
+    let records = sdk.get_records_response_async("example.com", Some(vec!["bitcoin-payment"]), None, &options).await;
+
+    let payment = records.get("bitcoin-payment").unwrap();
+    assert!(matches!(payment.error, Some(SelfieError::Timeout(_))), "{:?}", payment);
+}
@@ -0,0 +1,117 @@
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use async_trait::async_trait;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::watch::{ChangeDetector, ChangeEvent, WatchOptions, MIN_INTERVAL};
+use selfie_records_sdk::{LookupOptions, SelfieError, SelfieRecordsSDK, TxtResolver};
+
+/// Answers each query with the next of its answers, repeating the last.
+struct Scripted {
+    answers: Vec<Result<Vec<String>, SelfieError>>,
+    calls: Mutex<usize>,
+}
+
+impl Scripted {
+    fn new(answers: &[Option<&str>]) -> Self {
+        let answers = answers
+            .iter()
+            .map(|answer| match answer {
+                Some("error") => Err(SelfieError::Resolver("connection refused".to_string())),
+                Some(value) => Ok(vec![value.to_string()]),
+                None => Ok(Vec::new()),
+            })
+            .collect();
+        Scripted { answers, calls: Mutex::new(0) }
+    }
+
+    fn calls(&self) -> usize {
+        *self.calls.lock().unwrap()
+    }
+}
+
+#[async_trait]
+impl TxtResolver for Scripted {
+    async fn txt_lookup(&self, _name: &str) -> Result<Vec<String>, SelfieError> {
+        let mut calls = self.calls.lock().unwrap();
+        *calls += 1;
+        self.answers[(*calls - 1).min(self.answers.len() - 1)].clone()
+    }
+}
+
+fn values(events: &[ChangeEvent]) -> Vec<(Option<&str>, Option<&str>)> {
+    events.iter().map(|event| (event.old_value.as_deref(), event.new_value.as_deref())).collect()
+}
+
+#[test]
+fn appearing_changing_and_removed_records_are_reported() {
+    let sdk = Arc::new(SelfieRecordsSDK::with_resolver(Arc::new(Scripted::new(&[None, Some("npub1a"), Some("npub1b"), None]))));
+    let (sender, events) = mpsc::channel();
+
+    let watcher = sdk.watch("example.com", Some(vec!["nostr"]), MIN_INTERVAL, move |event| sender.send(event).unwrap());
+    let events: Vec<ChangeEvent> = events.iter().take(3).collect();
+    watcher.stop();
+
+    assert_eq!(values(&events), [(None, Some("npub1a")), (Some("npub1a"), Some("npub1b")), (Some("npub1b"), None)]);
+    assert!(events.iter().all(|event| event.key == "nostr" && event.name == "example.com"));
+    assert!(events[0].previous_observed_at < events[0].observed_at);
+    assert!(events.windows(2).all(|pair| pair[0].observed_at < pair[1].observed_at));
+}
+
+#[test]
+fn stopping_ends_the_polls_at_once() {
+    let resolver = Arc::new(Scripted::new(&[Some("npub1a")]));
+    let sdk = Arc::new(SelfieRecordsSDK::with_resolver(resolver.clone()));
+
+    let watcher = sdk.watch("example.com", Some(vec!["nostr"]), Duration::from_secs(60), |event| panic!("{:?}", event));
+    while resolver.calls() == 0 {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let started = Instant::now();
+    watcher.stop();
+
+    assert!(started.elapsed() < Duration::from_secs(1), "{:?}", started.elapsed());
+    assert_eq!(resolver.calls(), 1);
+}
+
+#[test]
+fn intervals_are_at_least_the_minimum() {
+    assert_eq!(WatchOptions::new(Duration::ZERO).get_interval(), MIN_INTERVAL);
+    assert_eq!(WatchOptions::new(Duration::from_secs(30)).get_interval(), Duration::from_secs(30));
+}
+
+#[test]
+fn failed_lookups_are_not_removals_unless_asked() {
+    let response = |records: &[&str]| {
+        let mock = MockTxtResolver::new();
+        let mock = if records.is_empty() { mock } else { mock.with_record("_nostr.example.com", records) };
+        SelfieRecordsSDK::with_resolver(Arc::new(mock)).get_records_response("example.com", Some(vec!["nostr"]), None, &LookupOptions::new().attempts(1))
+    };
+    let (found, failed) = (response(&["npub1a"]), response(&[]));
+    assert!(matches!(failed.get("nostr").unwrap().error, Some(SelfieError::Resolver(_))));
+    let now = SystemTime::now();
+
+    let mut detector = ChangeDetector::new("example.com", "custom");
+    assert!(detector.observe(&found, now).is_empty());
+    assert!(detector.observe(&failed, now).is_empty());
+    assert!(detector.observe(&found, now).is_empty());
+
+    let mut detector = ChangeDetector::new("example.com", "custom").errors_as_removed(true);
+    detector.observe(&found, now);
+    assert_eq!(values(&detector.observe(&failed, now)), [(Some("npub1a"), None)]);
+}
+
+#[tokio::test]
+async fn the_async_watcher_yields_changes() {
+    let resolver = Arc::new(Scripted::new(&[Some("npub1a"), Some("error")]));
+    let sdk = Arc::new(SelfieRecordsSDK::with_resolver(resolver.clone()));
+    let options = WatchOptions::new(MIN_INTERVAL).errors_as_removed(true).lookup_options(LookupOptions::new().attempts(1));
+
+    let mut changes = sdk.watch_async_with("example.com", Some(vec!["nostr"]), &options);
+    let event = changes.next().await.unwrap();
+
+    assert_eq!(values(&[event]), [(Some("npub1a"), None)]);
+    changes.stop();
+    assert_eq!(changes.next().await, None);
+}
@@ -0,0 +1,150 @@
+#![cfg(feature = "webhook")]
+
+mod common;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use common::server::http_server;
+use data_encoding::HEXLOWER;
+use ring::hmac;
+use selfie_records_sdk::watch::{ChangeDetector, ChangeEvent, ChangeSink, WebhookSink, SIGNATURE_HEADER};
+use selfie_records_sdk::{KeyResult, RecordsResponse, SelfieError};
+
+const SECRET: &[u8] = b"shared webhook secret";
+
+fn at(seconds: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+fn response(entries: &[(&str, KeyResult)]) -> RecordsResponse {
+    let mut response = RecordsResponse::default();
+    for (key, result) in entries {
+        response.insert(key, result.clone());
+    }
+    response
+}
+
+fn found(value: &str) -> KeyResult {
+    KeyResult { value: Some(value.to_string()), ..KeyResult::default() }
+}
+
+fn failed(error: SelfieError) -> KeyResult {
+    KeyResult { error: Some(error), ..KeyResult::default() }
+}
+
+fn event() -> ChangeEvent {
+    ChangeEvent {
+        name: "alice@example.com".to_string(),
+        key: "bitcoin-payment".to_string(),
+        old_value: Some("bitcoin:bc1qold".to_string()),
+        new_value: Some("bitcoin:bc1qnew".to_string()),
+        previous_observed_at: at(1_700_000_000),
+        observed_at: at(1_700_000_300),
+        resolver: "8.8.8.8".to_string(),
+    }
+}
+
+fn sink(url: &str) -> WebhookSink {
+    WebhookSink::new(url).unwrap().secret(SECRET).backoff(Duration::from_millis(1))
+}
+
+#[test]
+fn test_detector_reports_changes() {
+    let mut detector = ChangeDetector::new("alice@example.com", "system");
+    let baseline = response(&[("bitcoin-payment", found("bitcoin:bc1qold")), ("nostr", failed(SelfieError::NoRecords))]);
+    assert!(detector.observe(&baseline, at(100)).is_empty());
+    assert!(detector.observe(&baseline, at(200)).is_empty());
+
+    let changed = response(&[("bitcoin-payment", found("bitcoin:bc1qnew")), ("nostr", found("npub1alice"))]);
+    let events = detector.observe(&changed, at(300));
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].key, "bitcoin-payment");
+    assert_eq!(events[0].old_value.as_deref(), Some("bitcoin:bc1qold"));
+    assert_eq!(events[0].new_value.as_deref(), Some("bitcoin:bc1qnew"));
+    assert_eq!(events[0].previous_observed_at, at(200));
+    assert_eq!(events[0].observed_at, at(300));
+    assert_eq!(events[1].key, "nostr");
+    assert_eq!(events[1].old_value, None);
+
+    // A timeout says nothing about the record; a missing record removes it.
+    let timeout = SelfieError::Resolver("Error querying 8.8.8.8: request timed out".to_string());
+    let failed = response(&[("bitcoin-payment", failed(timeout)), ("nostr", failed(SelfieError::NoRecords))]);
+    let events = detector.observe(&failed, at(400));
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].key, "nostr");
+    assert_eq!(events[0].new_value, None);
+    assert!(detector.observe(&changed, at(500)).iter().all(|event| event.key == "nostr"));
+}
+
+#[test]
+fn test_event_payload() {
+    let payload = event().to_json();
+    assert_eq!(
+        payload,
+        serde_json::json!({
+            "name": "alice@example.com",
+            "key": "bitcoin-payment",
+            "old_value": "bitcoin:bc1qold",
+            "new_value": "bitcoin:bc1qnew",
+            "previous_observed_at": 1_700_000_000u64,
+            "observed_at": 1_700_000_300u64,
+            "resolver": "8.8.8.8",
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_webhook_is_signed() {
+    let (url, requests) = http_server(vec![200]);
+    sink(&url).deliver(&event()).await.unwrap();
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    assert!(request.head.starts_with("POST /hooks/selfie HTTP/1.1"));
+    assert_eq!(request.header("content-type"), Some("application/json"));
+
+    let key = hmac::Key::new(hmac::HMAC_SHA256, SECRET);
+    let expected = format!("sha256={}", HEXLOWER.encode(hmac::sign(&key, &request.body).as_ref()));
+    assert_eq!(request.header(&SIGNATURE_HEADER.to_ascii_lowercase()), Some(expected.as_str()));
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body, event().to_json());
+}
+
+#[tokio::test]
+async fn test_webhook_retries_server_errors() {
+    let (url, requests) = http_server(vec![503, 500, 204]);
+    sink(&url).retries(2).deliver(&event()).await.unwrap();
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 3);
+    assert!(requests.iter().all(|request| request.body == requests[0].body));
+}
+
+#[tokio::test]
+async fn test_webhook_dead_letter() {
+    let path = std::env::temp_dir().join(format!("selfie-dead-letter-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let (url, requests) = http_server(vec![502, 502]);
+    let e = sink(&url).retries(1).dead_letter(&path).deliver(&event()).await.unwrap_err();
+    assert!(e.to_string().contains("HTTP status 502 after 2 attempt(s)"), "{}", e);
+    assert_eq!(requests.lock().unwrap().len(), 2);
+
+    // Client errors are not retried.
+    let (url, requests) = http_server(vec![400]);
+    sink(&url).dead_letter(&path).deliver(&event()).await.unwrap_err();
+    assert_eq!(requests.lock().unwrap().len(), 1);
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let lines: Vec<serde_json::Value> = log.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["event"], event().to_json());
+    assert_eq!(lines[1]["error"], "HTTP status 400 after 1 attempt(s)");
+}
+
+#[test]
+fn test_webhook_url_must_be_an_http_url() {
+    assert_eq!(WebhookSink::new("https://hooks.example.com/selfie").is_ok(), cfg!(feature = "tls"));
+    assert!(WebhookSink::new("hooks.example.com").is_err());
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::server::dns_server;
+use selfie_records_sdk::testing::MockTxtResolver;
+use selfie_records_sdk::{DirectResolver, SelfieRecordsSDK};
+
+fn large_pgp_key() -> String {
+    "mDMEas7+mhYJKwYBBAHaRw8BAQdAfhyleP6CuVNnVdgd29xQfXxv0FT6ogkjLuav".repeat(20)
+}
+
+fn wire_fields(wire: &str) -> Vec<(&str, &str)> {
+    wire.split(' ').map(|field| field.split_once('=').unwrap()).collect()
+}
+
+#[test]
+fn test_truncated_udp_answer_falls_back_to_tcp() {
+    let value = large_pgp_key();
+    let server = dns_server(&value, true);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DirectResolver::new(server)));
+
+    let records = sdk.get_records("example.com", Some(vec!["pgp"]), None).to_map();
+
+    let pgp = &records["pgp"];
+    assert_eq!(pgp["value"].as_deref(), Some(value.as_str()));
+    let wire = pgp["wire"].as_deref().unwrap();
+    let fields = wire_fields(wire);
+    assert_eq!(fields[..2], [("transport", "tcp"), ("truncated", "true")], "{}", wire);
+    assert!(fields[2].1.parse::<usize>().unwrap() > value.len());
+    assert_eq!(fields[3], ("edns_udp_size", "4096"));
+}
+
+#[test]
+fn test_udp_answer_that_fits() {
+    let server = dns_server("bitcoin:bc1qexample", false);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(DirectResolver::new(server)));
+
+    let records = sdk.get_records("example.com", Some(vec!["bitcoin-payment"]), None).to_map();
+
+    let wire = records["bitcoin-payment"]["wire"].as_deref().unwrap();
+    let fields = wire_fields(wire);
+    assert_eq!(fields[..2], [("transport", "udp"), ("truncated", "false")], "{}", wire);
+    assert!(fields[2].1.parse::<usize>().unwrap() < 512);
+}
+
+#[test]
+fn test_resolver_path_reports_transport_only() {
+    let mock = MockTxtResolver::new().with_record("_nostr.example.com", &["npub1example"]);
+    let sdk = SelfieRecordsSDK::with_resolver(Arc::new(mock));
+
+    let records = sdk.get_records("example.com", Some(vec!["nostr", "pgp"]), None).to_map();
+
+    assert_eq!(records["nostr"]["wire"].as_deref(), Some("transport=resolver truncated=false"));
+    assert!(!records["pgp"].contains_key("wire"));
+}